@@ -4,7 +4,7 @@ use ff_standard_lib::messages::data_server_messaging::DataServerResponse;
 use crate::server_features::server_side_brokerage::BrokerApiResponse;
 use ff_standard_lib::standardized_types::accounts::{Account, AccountId};
 use ff_standard_lib::standardized_types::enums::StrategyMode;
-use ff_standard_lib::standardized_types::orders::{Order, OrderId, OrderUpdateEvent, OrderUpdateType};
+use ff_standard_lib::standardized_types::orders::{Order, OrderGroupId, OrderGroupType, OrderId, OrderUpdateEvent, OrderUpdateType};
 use ff_standard_lib::standardized_types::subscriptions::SymbolName;
 use ff_standard_lib::StreamName;
 use crate::bitget_api::api_client::BitgetClient;
@@ -77,6 +77,11 @@ impl BrokerApiResponse for BitgetClient {
         todo!()
     }
 
+    #[allow(unused)]
+    async fn live_order_group(&self, stream_name: StreamName, mode: StrategyMode, group_id: OrderGroupId, group_type: OrderGroupType, orders: Vec<Order>) -> Result<(), Vec<OrderUpdateEvent>> {
+        todo!()
+    }
+
     #[allow(unused)]
     async fn update_order(&self, account: Account, order_id: OrderId, update: OrderUpdateType) -> Result<(), OrderUpdateEvent> {
         todo!()
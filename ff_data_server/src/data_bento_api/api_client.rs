@@ -3,9 +3,11 @@ use ff_standard_lib::messages::data_server_messaging::FundForgeError;
 use crate::ServerLaunchOptions;
 use std::fs;
 use dashmap::DashMap;
-use databento::{HistoricalClient, LiveClient};
-use databento::dbn::{SType, Schema};
-use tokio::sync::{Mutex, OnceCell};
+use databento::HistoricalClient;
+use ff_standard_lib::standardized_types::base_data::base_data_enum::BaseDataEnum;
+use ff_standard_lib::standardized_types::subscriptions::DataSubscription;
+use tokio::sync::{broadcast, Mutex, OnceCell};
+use tokio::task::JoinHandle;
 use toml::Value;
 
 
@@ -27,8 +29,11 @@ pub fn get_data_bento_client() -> Result<Arc<DataBentoClient>, FundForgeError> {
 }
 
 pub struct DataBentoClient {
+    pub(crate) api_key: String,
     pub historical_client: Arc<Mutex<HistoricalClient>>,
-    pub live_clients: DashMap<(String, Schema, SType), LiveClient>,
+    /// Each live subscription has its own live session, shared by all the streams subscribed to it.
+    pub live_feed_broadcasters: DashMap<DataSubscription, broadcast::Sender<BaseDataEnum>>,
+    pub live_feed_tasks: DashMap<DataSubscription, JoinHandle<()>>,
 }
 
 impl DataBentoClient {
@@ -48,13 +53,20 @@ impl DataBentoClient {
         };
 
         Ok(Self {
+            api_key: key,
             historical_client: Arc::new(Mutex::new(historical_client)),
-            live_clients: DashMap::new()
+            live_feed_broadcasters: DashMap::new(),
+            live_feed_tasks: DashMap::new(),
         })
     }
 
+    /// Closes all live sessions, dropping a live client closes its connection.
     pub fn shutdown(&self) {
-
+        for task in self.live_feed_tasks.iter() {
+            task.value().abort();
+        }
+        self.live_feed_tasks.clear();
+        self.live_feed_broadcasters.clear();
     }
 
     pub fn get_api_key(options: &ServerLaunchOptions) -> Result<String, FundForgeError> {
@@ -0,0 +1,249 @@
+use std::collections::HashMap;
+use chrono::{DateTime, TimeZone, Utc};
+use databento::dbn::decode::AsyncDbnDecoder;
+use databento::dbn::{Mbp1Msg, OhlcvMsg, RType, Record, RecordRef, Schema, Side, SymbolIndex, TradeMsg, UNDEF_PRICE};
+use rust_decimal::Decimal;
+use time::OffsetDateTime;
+use tokio::io::AsyncReadExt;
+use ff_standard_lib::messages::data_server_messaging::FundForgeError;
+use ff_standard_lib::standardized_types::base_data::base_data_enum::BaseDataEnum;
+use ff_standard_lib::standardized_types::base_data::base_data_type::BaseDataType;
+use ff_standard_lib::standardized_types::base_data::candle::Candle;
+use ff_standard_lib::standardized_types::base_data::quote::Quote;
+use ff_standard_lib::standardized_types::base_data::tick::{Aggressor, Tick};
+use ff_standard_lib::standardized_types::datavendor_enum::DataVendor;
+use ff_standard_lib::standardized_types::new_types::{Price, Volume};
+use ff_standard_lib::standardized_types::resolution::Resolution;
+use ff_standard_lib::standardized_types::subscriptions::{CandleType, Symbol, SymbolName};
+use crate::data_bento_api::symbology::fund_forge_symbol;
+
+/// The DataBento schema which serves a fund forge data type and resolution.
+/// Ticks are the trades schema, quotes are top of book (MBP-1) and candles are the OHLCV schemas.
+pub fn schema_for(base_data_type: BaseDataType, resolution: Resolution) -> Result<Schema, FundForgeError> {
+    match (base_data_type, resolution) {
+        (BaseDataType::Ticks, Resolution::Ticks(1)) => Ok(Schema::Trades),
+        (BaseDataType::Quotes, Resolution::Instant) => Ok(Schema::Mbp1),
+        (BaseDataType::Candles, Resolution::Seconds(1)) => Ok(Schema::Ohlcv1S),
+        (BaseDataType::Candles, Resolution::Minutes(1)) => Ok(Schema::Ohlcv1M),
+        (BaseDataType::Candles, Resolution::Hours(1)) => Ok(Schema::Ohlcv1H),
+        (BaseDataType::Candles, Resolution::Day) => Ok(Schema::Ohlcv1D),
+        _ => Err(FundForgeError::ClientSideErrorDebug(format!("{} {} is not available from {}, use a consolidator", resolution, base_data_type, DataVendor::DataBento))),
+    }
+}
+
+/// DataBento prices are fixed precision integers where 1 unit is 1e-9, `None` if the price is undefined (an empty side of the book).
+fn price(px: i64) -> Option<Price> {
+    match px == UNDEF_PRICE {
+        true => None,
+        false => Some(Decimal::new(px, 9).normalize()),
+    }
+}
+
+fn event_time(ts: u64) -> DateTime<Utc> {
+    Utc.timestamp_nanos(ts as i64)
+}
+
+/// The DataBento api uses `time` date times.
+pub fn to_offset_date_time(time: DateTime<Utc>) -> OffsetDateTime {
+    OffsetDateTime::from_unix_timestamp_nanos(time.timestamp_nanos_opt().unwrap_or_default() as i128).unwrap_or(OffsetDateTime::UNIX_EPOCH)
+}
+
+pub fn from_offset_date_time(time: OffsetDateTime) -> DateTime<Utc> {
+    Utc.timestamp_nanos(time.unix_timestamp_nanos() as i64)
+}
+
+/// Ticks and quotes are saved by time, events at or before the last event are moved 1 nanosecond after it so none are overwritten.
+fn unique_time(time: DateTime<Utc>, last_time: &mut DateTime<Utc>) -> DateTime<Utc> {
+    let time = match time <= *last_time {
+        true => *last_time + chrono::Duration::nanoseconds(1),
+        false => time,
+    };
+    *last_time = time;
+    time
+}
+
+/// Converts a DataBento trades, MBP-1 or OHLCV record to base data for `symbol`, other record types return `None`.
+/// `last_time` is the time of the last tick or quote for the symbol.
+pub fn record_to_base_data(record: &RecordRef, symbol: &Symbol, last_time: &mut DateTime<Utc>) -> Option<BaseDataEnum> {
+    let rtype = record.rtype().ok()?;
+    match rtype {
+        RType::Mbp0 => {
+            let trade = record.get::<TradeMsg>()?;
+            let price = price(trade.price)?;
+            // the side of a trade is the side of the aggressor
+            let aggressor = match trade.side() {
+                Ok(Side::Bid) => Aggressor::Buy,
+                Ok(Side::Ask) => Aggressor::Sell,
+                _ => Aggressor::None,
+            };
+            let time = unique_time(event_time(trade.hd.ts_event), last_time);
+            Some(BaseDataEnum::Tick(Tick::new(symbol.clone(), price, time.to_string(), Volume::from(trade.size), aggressor)))
+        }
+        RType::Mbp1 => {
+            let quote = record.get::<Mbp1Msg>()?;
+            let level = &quote.levels[0];
+            let ask = price(level.ask_px)?;
+            let bid = price(level.bid_px)?;
+            let time = unique_time(event_time(quote.hd.ts_event), last_time);
+            Some(BaseDataEnum::Quote(Quote::new(symbol.clone(), ask, bid, Volume::from(level.ask_sz), Volume::from(level.bid_sz), time.to_string())))
+        }
+        RType::Ohlcv1S | RType::Ohlcv1M | RType::Ohlcv1H | RType::Ohlcv1D => {
+            let resolution = match rtype {
+                RType::Ohlcv1S => Resolution::Seconds(1),
+                RType::Ohlcv1M => Resolution::Minutes(1),
+                RType::Ohlcv1H => Resolution::Hours(1),
+                _ => Resolution::Day,
+            };
+            let bar = record.get::<OhlcvMsg>()?;
+            let high = price(bar.high)?;
+            let low = price(bar.low)?;
+            // the ohlcv event time is the open time of the bar, DataBento only publishes closed bars
+            Some(BaseDataEnum::Candle(Candle {
+                symbol: symbol.clone(),
+                high,
+                low,
+                open: price(bar.open)?,
+                close: price(bar.close)?,
+                volume: Volume::from(bar.volume),
+                ask_volume: Volume::ZERO,
+                bid_volume: Volume::ZERO,
+                range: high - low,
+                time: event_time(bar.hd.ts_event).to_string(),
+                is_closed: true,
+                resolution,
+                candle_type: CandleType::CandleStick,
+            }))
+        }
+        _ => None,
+    }
+}
+
+/// Decodes a DBN stream to base data, the instrument ids of the records are mapped to fund forge symbols with the symbology in the stream metadata.
+/// Records for instruments which are not fund forge products are skipped.
+pub async fn decode_dbn<R: AsyncReadExt + Unpin>(decoder: &mut AsyncDbnDecoder<R>) -> Result<Vec<BaseDataEnum>, FundForgeError> {
+    let symbol_map = decoder.metadata().symbol_map().map_err(|e| {
+        FundForgeError::ServerErrorDebug(format!("Failed to read {} symbology: {}", DataVendor::DataBento, e))
+    })?;
+    let mut symbols: HashMap<String, Option<Symbol>> = HashMap::new();
+    let mut last_times: HashMap<SymbolName, DateTime<Utc>> = HashMap::new();
+    let mut data = vec![];
+    loop {
+        let record = match decoder.decode_record_ref().await {
+            Ok(Some(record)) => record,
+            Ok(None) => break,
+            Err(e) => return Err(FundForgeError::ServerErrorDebug(format!("Failed to decode {} record: {}", DataVendor::DataBento, e)))
+        };
+        let data_bento_symbol = match symbol_map.get_for_rec(&record) {
+            Some(data_bento_symbol) => data_bento_symbol,
+            None => continue,
+        };
+        let symbol = match symbols.entry(data_bento_symbol.clone()).or_insert_with(|| fund_forge_symbol(data_bento_symbol)) {
+            Some(symbol) => symbol,
+            None => continue,
+        };
+        let last_time = last_times.entry(symbol.name.clone()).or_insert(DateTime::<Utc>::MIN_UTC);
+        if let Some(base_data) = record_to_base_data(&record, symbol, last_time) {
+            data.push(base_data);
+        }
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::str::FromStr;
+    use rust_decimal_macros::dec;
+    use ff_standard_lib::standardized_types::base_data::traits::BaseData;
+
+    async fn load_fixture(file_name: &str) -> Vec<BaseDataEnum> {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("resources").join("test_data").join("databento").join(file_name);
+        let mut decoder = AsyncDbnDecoder::from_file(path).await.unwrap();
+        decode_dbn(&mut decoder).await.unwrap()
+    }
+
+    fn t0() -> DateTime<Utc> {
+        DateTime::from_str("2024-06-03T13:30:00Z").unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_decode_trades() {
+        let data = load_fixture("glbx-mdp3-trades.dbn").await;
+        // the trade for an instrument without a symbol mapping is skipped
+        assert_eq!(data.len(), 4);
+        let ticks: Vec<Tick> = data.into_iter().map(|d| match d {
+            BaseDataEnum::Tick(tick) => tick,
+            _ => panic!("expected a tick"),
+        }).collect();
+
+        assert_eq!(ticks[0].symbol.name, "ES");
+        assert_eq!(ticks[0].symbol.data_vendor, DataVendor::DataBento);
+        assert_eq!(ticks[0].price, dec!(5300.25));
+        assert_eq!(ticks[0].volume, dec!(2));
+        assert!(ticks[0].aggressor == Aggressor::Sell);
+        assert_eq!(ticks[0].time_utc(), t0());
+
+        // the second trade at the same time is moved 1 nanosecond later
+        assert!(ticks[1].aggressor == Aggressor::Buy);
+        assert_eq!(ticks[1].time_utc(), t0() + chrono::Duration::nanoseconds(1));
+
+        assert_eq!(ticks[2].symbol.name, "NQ");
+        assert_eq!(ticks[2].price, dec!(18700.5));
+        assert!(ticks[2].aggressor == Aggressor::None);
+
+        assert_eq!(ticks[3].symbol.name, "ES");
+        assert_eq!(ticks[3].price, dec!(5300.5));
+        assert_eq!(ticks[3].volume, dec!(5));
+        assert_eq!(ticks[3].time_utc(), t0() + chrono::Duration::seconds(1));
+    }
+
+    #[tokio::test]
+    async fn test_decode_mbp_1() {
+        let data = load_fixture("glbx-mdp3-mbp-1.dbn").await;
+        // the book with an empty ask side is skipped
+        assert_eq!(data.len(), 2);
+        match &data[0] {
+            BaseDataEnum::Quote(quote) => {
+                assert_eq!(quote.bid, dec!(5300));
+                assert_eq!(quote.ask, dec!(5300.25));
+                assert_eq!(quote.bid_volume, dec!(10));
+                assert_eq!(quote.ask_volume, dec!(12));
+                assert_eq!(quote.time_utc(), t0());
+            }
+            _ => panic!("expected a quote"),
+        }
+        assert_eq!(data[1].time_utc(), t0() + chrono::Duration::seconds(2));
+    }
+
+    #[tokio::test]
+    async fn test_decode_ohlcv_1m() {
+        let data = load_fixture("glbx-mdp3-ohlcv-1m.dbn").await;
+        assert_eq!(data.len(), 3);
+        match &data[0] {
+            BaseDataEnum::Candle(candle) => {
+                assert_eq!(candle.resolution, Resolution::Minutes(1));
+                assert_eq!(candle.open, dec!(5300));
+                assert_eq!(candle.high, dec!(5301));
+                assert_eq!(candle.low, dec!(5299.5));
+                assert_eq!(candle.close, dec!(5300.75));
+                assert_eq!(candle.range, dec!(1.5));
+                assert_eq!(candle.volume, dec!(1520));
+                assert!(candle.is_closed);
+                assert_eq!(candle.time_utc(), t0());
+                assert_eq!(candle.time_closed_utc(), t0() + chrono::Duration::minutes(1));
+            }
+            _ => panic!("expected a candle"),
+        }
+        assert_eq!(data[2].time_utc(), t0() + chrono::Duration::minutes(2));
+    }
+
+    #[test]
+    fn test_schema_for() {
+        assert_eq!(schema_for(BaseDataType::Ticks, Resolution::Ticks(1)).unwrap(), Schema::Trades);
+        assert_eq!(schema_for(BaseDataType::Quotes, Resolution::Instant).unwrap(), Schema::Mbp1);
+        assert_eq!(schema_for(BaseDataType::Candles, Resolution::Minutes(1)).unwrap(), Schema::Ohlcv1M);
+        assert!(schema_for(BaseDataType::Candles, Resolution::Minutes(5)).is_err());
+        assert!(schema_for(BaseDataType::OrderBooks, Resolution::Instant).is_err());
+    }
+}
@@ -1,4 +1,4 @@
 pub mod api_client;
 pub mod vendor_response;
-
-
+pub mod symbology;
+pub mod conversions;
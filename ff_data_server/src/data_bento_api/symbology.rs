@@ -0,0 +1,79 @@
+use ff_standard_lib::product_maps::rithmic::maps::get_exchange_by_symbol_name;
+use ff_standard_lib::standardized_types::datavendor_enum::DataVendor;
+use ff_standard_lib::standardized_types::enums::{FuturesExchange, MarketType};
+use ff_standard_lib::standardized_types::subscriptions::{Symbol, SymbolName};
+
+/// The futures month codes, used to strip the contract month from raw DataBento symbols.
+const MONTH_CODES: [char; 12] = ['F', 'G', 'H', 'J', 'K', 'M', 'N', 'Q', 'U', 'V', 'X', 'Z'];
+
+/// The DataBento dataset which carries the products of a futures exchange.
+/// CME, CBOT, NYMEX and COMEX are all on the CME Globex MDP 3.0 feed, NYBOT products are on ICE Futures US.
+pub fn dataset_for_exchange(exchange: &FuturesExchange) -> Option<&'static str> {
+    match exchange {
+        FuturesExchange::CME | FuturesExchange::CBOT | FuturesExchange::NYMEX | FuturesExchange::COMEX => Some("GLBX.MDP3"),
+        FuturesExchange::NYBOT => Some("IFUS.IMPACT"),
+        FuturesExchange::MGEX => None,
+    }
+}
+
+/// We request the front month by calendar roll with the DataBento continuous symbology, so "ES" is requested as "ES.c.0", `.v.` would rank the contracts by volume.
+pub fn continuous_symbol(symbol_name: &SymbolName) -> String {
+    format!("{}.c.0", symbol_name)
+}
+
+/// Maps a DataBento symbol back to the fund forge symbol name.
+///
+/// Continuous ("ES.c.0", "ES.v.0"), parent ("ES.FUT") and raw contract ("ESZ4", "ESZ24") symbols all map to "ES".
+/// Spreads ("ESZ4-ESH5") and options have no fund forge symbol and return `None`.
+pub fn fund_forge_symbol_name(data_bento_symbol: &str) -> Option<SymbolName> {
+    if data_bento_symbol.contains('-') || data_bento_symbol.contains(' ') {
+        return None;
+    }
+    let name = match data_bento_symbol.split_once('.') {
+        Some((name, _)) => name,
+        None => {
+            // raw contract symbols are the product code followed by the month code and the year digits
+            let product = data_bento_symbol.trim_end_matches(|c: char| c.is_ascii_digit());
+            if product.len() == data_bento_symbol.len() {
+                data_bento_symbol
+            } else {
+                product.strip_suffix(|c: char| MONTH_CODES.contains(&c))?
+            }
+        }
+    };
+    match name.is_empty() {
+        true => None,
+        false => Some(name.to_string()),
+    }
+}
+
+/// Maps a DataBento symbol to a fund forge `Symbol`, `None` if the product is not one of our futures products.
+pub fn fund_forge_symbol(data_bento_symbol: &str) -> Option<Symbol> {
+    let name = fund_forge_symbol_name(data_bento_symbol)?;
+    let exchange = get_exchange_by_symbol_name(&name)?;
+    Some(Symbol::new(name, DataVendor::DataBento, MarketType::Futures(exchange)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fund_forge_symbol_name() {
+        assert_eq!(fund_forge_symbol_name("ES.c.0"), Some("ES".to_string()));
+        assert_eq!(fund_forge_symbol_name("MNQ.v.1"), Some("MNQ".to_string()));
+        assert_eq!(fund_forge_symbol_name("ZN.FUT"), Some("ZN".to_string()));
+        assert_eq!(fund_forge_symbol_name("ESZ4"), Some("ES".to_string()));
+        assert_eq!(fund_forge_symbol_name("ZNZ24"), Some("ZN".to_string()));
+        assert_eq!(fund_forge_symbol_name("6EH5"), Some("6E".to_string()));
+        assert_eq!(fund_forge_symbol_name("ES"), Some("ES".to_string()));
+        assert_eq!(fund_forge_symbol_name("ESZ4-ESH5"), None);
+        assert_eq!(fund_forge_symbol_name("ES4"), None);
+
+        let symbol = fund_forge_symbol(&continuous_symbol(&"NQ".to_string())).unwrap();
+        assert_eq!(symbol.name, "NQ");
+        assert_eq!(symbol.data_vendor, DataVendor::DataBento);
+        assert_eq!(symbol.market_type, MarketType::Futures(FuturesExchange::CME));
+        assert_eq!(dataset_for_exchange(&FuturesExchange::COMEX), Some("GLBX.MDP3"));
+    }
+}
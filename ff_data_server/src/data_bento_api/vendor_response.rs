@@ -1,4 +1,4 @@
-use std::num::NonZeroU64;
+use std::cmp::min;
 use async_trait::async_trait;
 use ff_standard_lib::messages::data_server_messaging::{DataServerResponse, FundForgeError};
 use crate::server_features::server_side_datavendor::VendorApiResponse;
@@ -7,23 +7,28 @@ use ff_standard_lib::standardized_types::enums::{FuturesExchange, MarketType, Pr
 use ff_standard_lib::standardized_types::resolution::Resolution;
 use ff_standard_lib::standardized_types::subscriptions::{DataSubscription, Symbol, SymbolName};
 use ff_standard_lib::StreamName;
-use chrono::{DateTime, Utc};
-use databento::dbn::{Schema, TradeMsg};
-use databento::historical::symbology::{SymbologyClient};
+use chrono::{DateTime, Duration, Utc};
+use databento::dbn::{SType, Schema};
 use databento::historical::timeseries::GetRangeParams;
-use indicatif::{ProgressBar};
-use time::macros::{datetime};
+use databento::live::Subscription;
+use databento::LiveClient;
+use indicatif::{ProgressBar, ProgressStyle};
+use tokio::sync::broadcast;
 use ff_standard_lib::product_maps::rithmic::maps::{get_futures_exchange, get_futures_symbol_info};
+use ff_standard_lib::standardized_types::base_data::base_data_enum::BaseDataEnum;
 use ff_standard_lib::standardized_types::datavendor_enum::DataVendor;
 use crate::data_bento_api::api_client::DataBentoClient;
+use crate::data_bento_api::conversions::{decode_dbn, from_offset_date_time, record_to_base_data, schema_for, to_offset_date_time};
+use crate::data_bento_api::symbology::{continuous_symbol, dataset_for_exchange};
 use crate::rithmic_api::products::get_futures_symbols;
+use crate::server_features::database::hybrid_storage::DATA_STORAGE;
+use crate::stream_tasks::{subscribe_stream, unsubscribe_stream};
 
 #[async_trait]
 impl VendorApiResponse for DataBentoClient {
-    #[allow(unused)]
-    async fn symbols_response(&self,  _mode: StrategyMode, _stream_name: StreamName, market_type: MarketType, time: Option<DateTime<Utc>>, callback_id: u64) -> DataServerResponse{
+    async fn symbols_response(&self,  _mode: StrategyMode, _stream_name: StreamName, market_type: MarketType, _time: Option<DateTime<Utc>>, callback_id: u64) -> DataServerResponse{
         match market_type {
-            MarketType::Futures(exchange) => {
+            MarketType::Futures(_) => {
                 let symbols = get_futures_symbols();
                 let mut symbols_objects = vec![];
                 for symbol_name in symbols {
@@ -31,65 +36,23 @@ impl VendorApiResponse for DataBentoClient {
                         Ok(exchange) => exchange,
                         Err(_) => continue
                     };
+                    if dataset_for_exchange(&exchange).is_none() {
+                        continue;
+                    }
                     let s = Symbol::new(symbol_name.clone(), DataVendor::DataBento, MarketType::Futures(exchange.clone()));
                     symbols_objects.push(s);
                 }
                 DataServerResponse::Symbols{callback_id, symbols: symbols_objects, market_type}
             },
-            _ => return DataServerResponse::Error{callback_id, error: FundForgeError::ServerErrorDebug("Unsupported market type".to_string())}
-        };
-
-        let mut client = self.historical_client.lock().await;
-        let mut symbology_client: SymbologyClient = client.symbology();
-
-        /*if let Some(time) = time {
-            let year = time.year();
-            let month = time.month();
-            let day = time.day();
-            let month: time::Month = match month {
-                1 => time::Month::January,
-                2 => time::Month::February,
-                3 => time::Month::March,
-                4 => time::Month::April,
-                5 => time::Month::May,
-                6 => time::Month::June,
-                7 => time::Month::July,
-                8 => time::Month::August,
-                9 => time::Month::September,
-                10 => time::Month::October,
-                11 => time::Month::November,
-                12 => time::Month::December,
-                _ => return DataServerResponse::Error{callback_id, error: FundForgeError::ServerErrorDebug("Invalid month".to_string())}
-            };
-            let time = match time::Date::from_calendar_date(year, month, day as u8) {
-                Ok(date) => date,
-                Err(e) => return DataServerResponse::Error{callback_id, error: FundForgeError::ServerErrorDebug(format!("Failed to create date: {}", e))}
-            };
-
-            let date_range = DateRange::from((time.clone(), time));
-
-
-
-            let params: ResolveParams = ResolveParams::builder()
-                .dataset(dataset)
-                .symbols(Symbols::All)
-                .stype_in(SType::RawSymbol)
-                .stype_out(SType::RawSymbol).date_range(date_range).build();
-
-            let symbols = symbology_client.resolve(
-                &params
-            ).await.map_err(|e| FundForgeError::ServerErrorDebug(format!("Failed to resolve symbols: {}", e)));
-
-
-            println!("Symbols: {:?}", symbols);
-        }*/
-        DataServerResponse::Error{callback_id, error: FundForgeError::ServerErrorDebug("No time provided".to_string())}
+            _ => DataServerResponse::Error{callback_id, error: FundForgeError::ServerErrorDebug("Unsupported market type".to_string())}
+        }
     }
+
     #[allow(unused)]
     async fn resolutions_response(&self, _mode: StrategyMode, _stream_name: StreamName, market_type: MarketType, callback_id: u64) -> DataServerResponse {
         DataServerResponse::Resolutions {
             callback_id,
-            subscription_resolutions_types: vec![PrimarySubscription::new(Resolution::Ticks(1), BaseDataType::Ticks), PrimarySubscription::new(Resolution::Instant, BaseDataType::Quotes), PrimarySubscription::new(Resolution::Seconds(1), BaseDataType::Candles), PrimarySubscription::new(Resolution::Minutes(1), BaseDataType::Candles)],
+            subscription_resolutions_types: vec![PrimarySubscription::new(Resolution::Ticks(1), BaseDataType::Ticks), PrimarySubscription::new(Resolution::Instant, BaseDataType::Quotes), PrimarySubscription::new(Resolution::Seconds(1), BaseDataType::Candles), PrimarySubscription::new(Resolution::Minutes(1), BaseDataType::Candles), PrimarySubscription::new(Resolution::Hours(1), BaseDataType::Candles), PrimarySubscription::new(Resolution::Day, BaseDataType::Candles)],
             market_type
         }
     }
@@ -102,7 +65,6 @@ impl VendorApiResponse for DataBentoClient {
                 MarketType::Futures(FuturesExchange::COMEX),
                 MarketType::Futures(FuturesExchange::NYBOT),
                 MarketType::Futures(FuturesExchange::NYMEX),
-                MarketType::Futures(FuturesExchange::MGEX)
             ],
         }
     }
@@ -137,14 +99,85 @@ impl VendorApiResponse for DataBentoClient {
         }
     }
 
-    #[allow(unused)]
     async fn data_feed_subscribe(&self, stream_name: StreamName, subscription: DataSubscription) -> DataServerResponse {
-       todo!()
+        let dataset = match subscription.market_type {
+            MarketType::Futures(exchange) => dataset_for_exchange(&exchange),
+            _ => None,
+        };
+        let dataset = match dataset {
+            Some(dataset) => dataset,
+            None => return DataServerResponse::SubscribeResponse {
+                success: false,
+                reason: Some(format!("Unsupported market type: {:?}", subscription.market_type)),
+                subscription,
+            },
+        };
+        let schema = match schema_for(subscription.base_data_type, subscription.resolution) {
+            Ok(schema) => schema,
+            Err(e) => return DataServerResponse::SubscribeResponse {
+                success: false,
+                reason: Some(e.to_string()),
+                subscription,
+            },
+        };
+
+        if let Some(broadcaster) = self.live_feed_broadcasters.get(&subscription) {
+            let receiver = broadcaster.value().subscribe();
+            subscribe_stream(&stream_name, subscription.clone(), receiver).await;
+            return DataServerResponse::SubscribeResponse {
+                success: true,
+                subscription,
+                reason: None,
+            }
+        }
+
+        let client = match self.start_live_session(dataset, schema, &subscription.symbol.name).await {
+            Ok(client) => client,
+            Err(e) => return DataServerResponse::SubscribeResponse {
+                success: false,
+                reason: Some(format!("Failed to start {} live session: {}", DataVendor::DataBento, e)),
+                subscription,
+            },
+        };
+
+        let (sender, receiver) = broadcast::channel(500);
+        self.live_feed_broadcasters.insert(subscription.clone(), sender.clone());
+        subscribe_stream(&stream_name, subscription.clone(), receiver).await;
+        let task = tokio::task::spawn(live_feed(client, subscription.symbol.clone(), sender));
+        self.live_feed_tasks.insert(subscription.clone(), task);
+        println!("Subscribed: {} {} {}", subscription.symbol.name, subscription.resolution, subscription.base_data_type);
+
+        DataServerResponse::SubscribeResponse {
+            success: true,
+            subscription,
+            reason: None,
+        }
     }
-    #[allow(unused)]
+
     async fn data_feed_unsubscribe(&self, stream_name: StreamName, subscription: DataSubscription) -> DataServerResponse {
-        todo!()
+        unsubscribe_stream(&stream_name, &subscription).await;
+
+        let mut should_disconnect = false;
+        if let Some(broadcaster) = self.live_feed_broadcasters.get(&subscription) {
+            should_disconnect = broadcaster.receiver_count() == 0;
+        }
+
+        if should_disconnect {
+            self.live_feed_broadcasters.remove(&subscription);
+            // dropping the live client closes the session
+            if let Some((_, task)) = self.live_feed_tasks.remove(&subscription) {
+                task.abort();
+            }
+            println!("Unsubscribed: {} {} {}", subscription.symbol.name, subscription.resolution, subscription.base_data_type);
+        }
+
+        DataServerResponse::UnSubscribeResponse {
+            success: true,
+            subscription,
+            reason: None,
+        }
     }
+
     #[allow(unused)]
     async fn base_data_types_response(&self,  _mode: StrategyMode, _stream_name: StreamName, callback_id: u64) -> DataServerResponse {
         DataServerResponse::BaseDataTypes {
@@ -153,9 +186,8 @@ impl VendorApiResponse for DataBentoClient {
         }
     }
 
-    #[allow(unused)]
     async fn logout_command_vendors(&self, _stream_name: StreamName) {
-        todo!()
+        // live sessions are shared by streams, they are closed by data_feed_unsubscribe when the last stream unsubscribes
     }
 
     #[allow(unused)]
@@ -172,36 +204,132 @@ impl VendorApiResponse for DataBentoClient {
     Symbology provides methods that help find and resolve symbols across different symbology systems.
     Batch provides a means of submitting and querying for details of batch download requests.
     */
-    #[allow(unused)]
-    async fn update_historical_data(&self, symbol: Symbol, base_data_type: BaseDataType, resolution: Resolution, from: DateTime<Utc>, to: DateTime<Utc>, from_back: bool ,progress_bar: ProgressBar) -> Result<(), FundForgeError> {
-
-        let schema = match base_data_type {
-            BaseDataType::Quotes => {}
-            _ => return Err(FundForgeError::ClientSideErrorDebug(format!("{} Invalid Data Type for: {}", base_data_type, DataVendor::DataBento)))
+    async fn update_historical_data(&self, symbol: Symbol, base_data_type: BaseDataType, resolution: Resolution, from: DateTime<Utc>, to: DateTime<Utc>, _from_back: bool, progress_bar: ProgressBar) -> Result<(), FundForgeError> {
+        let schema = match schema_for(base_data_type, resolution) {
+            Ok(schema) => schema,
+            Err(e) => {
+                progress_bar.finish_and_clear();
+                return Err(e)
+            }
+        };
+        let dataset = match symbol.market_type {
+            MarketType::Futures(exchange) => dataset_for_exchange(&exchange),
+            _ => None,
+        };
+        let dataset = match dataset {
+            Some(dataset) => dataset,
+            None => {
+                progress_bar.finish_and_clear();
+                return Err(FundForgeError::ClientSideErrorDebug(format!("{} has no {} dataset for: {:?}", symbol.name, DataVendor::DataBento, symbol.market_type)))
+            }
         };
 
-        let ranges = GetRangeParams::builder()
-            .dataset("GLBX.MDP3")
-            .date_time_range((
-                datetime!(2023-06-06 00:00 UTC),
-                datetime!(2023-06-10 12:10 UTC),
-            ))
-            .symbols(symbol.name)
-            .schema(Schema::Trades)
-            .limit(NonZeroU64::new(1))
-            .build();
-
+        let data_storage = DATA_STORAGE.get().unwrap();
         let mut client = self.historical_client.lock().await;
 
-        let mut decoder = client
-            .timeseries()
-            .get_range(&ranges).await.unwrap();
+        // requests ending after the available historical data are rejected, the most recent data is only available live
+        let to = match client.metadata().get_dataset_range(dataset).await {
+            Ok(range) => min(to, from_offset_date_time(range.end)),
+            Err(e) => {
+                progress_bar.finish_and_clear();
+                return Err(FundForgeError::ServerErrorDebug(format!("Failed to get {} range for {}: {}", DataVendor::DataBento, dataset, e)))
+            }
+        };
+        if from >= to {
+            progress_bar.finish_and_clear();
+            return Ok(())
+        }
 
-        match decoder.decode_record::<TradeMsg>().await {
-            Ok(trade) => println!("{trade:#?}"),
-            Err(e) => eprintln!("Failed to decode: {}", e)
+        // the download window is sized to keep each response to roughly a day of ticks
+        let window = match schema {
+            Schema::Mbp1 => Duration::hours(4),
+            Schema::Trades | Schema::Ohlcv1S => Duration::days(1),
+            Schema::Ohlcv1M => Duration::days(30),
+            _ => Duration::days(365),
         };
 
+        let bar_len = ((to - from).num_seconds() as f64 / window.num_seconds() as f64).ceil() as u64;
+        progress_bar.set_length(bar_len);
+        progress_bar.set_style(
+            ProgressStyle::default_bar()
+                .template("{prefix:.green} [{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} {msg} ({eta})")
+                .unwrap()
+                .progress_chars("=>-")
+        );
+        progress_bar.set_message(format!("Starting Download for ({}: {}) from: {}, to {}: Message will not change if no data found", resolution, base_data_type, from, to));
+
+        let mut window_start = from;
+        while window_start < to {
+            let window_end = min(window_start + window, to);
+            let params = GetRangeParams::builder()
+                .dataset(dataset)
+                .symbols(continuous_symbol(&symbol.name))
+                .schema(schema)
+                .stype_in(SType::Continuous)
+                .date_time_range((to_offset_date_time(window_start), to_offset_date_time(window_end)))
+                .build();
+
+            let data = match client.timeseries().get_range(&params).await {
+                Ok(mut decoder) => decode_dbn(&mut decoder).await,
+                Err(e) => Err(FundForgeError::ServerErrorDebug(e.to_string())),
+            };
+            match data {
+                Ok(data) => {
+                    if !data.is_empty() {
+                        progress_bar.set_message(format!("Downloaded: {} Data Points for ({}: {}) from: {}, to {}", data.len(), resolution, base_data_type, window_start, window_end));
+                        if let Err(e) = data_storage.save_data_bulk(data).await {
+                            progress_bar.set_message(format!("Failed to save data for: {} - {}, {}", window_start, window_end, e));
+                            break;
+                        }
+                    }
+                }
+                Err(e) => {
+                    progress_bar.set_message(format!("Failed to get_requests data for: {} - {}, {}", window_start, window_end, e));
+                    break;
+                }
+            }
+            window_start = window_end;
+            progress_bar.inc(1);
+        }
+        progress_bar.finish_and_clear();
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+impl DataBentoClient {
+    /// Each live subscription is a session for one symbol and schema, the front month is streamed with the continuous symbology.
+    async fn start_live_session(&self, dataset: &str, schema: Schema, symbol_name: &SymbolName) -> databento::Result<LiveClient> {
+        let mut client = LiveClient::builder()
+            .key(self.api_key.clone())?
+            .dataset(dataset)
+            .build()
+            .await?;
+        let subscription = Subscription::builder()
+            .symbols(continuous_symbol(symbol_name))
+            .schema(schema)
+            .stype_in(SType::Continuous)
+            .build();
+        client.subscribe(&subscription).await?;
+        client.start().await?;
+        Ok(client)
+    }
+}
+
+/// Broadcasts the records of a live session until the session ends, or the task is aborted when the last stream unsubscribes.
+async fn live_feed(mut client: LiveClient, symbol: Symbol, sender: broadcast::Sender<BaseDataEnum>) {
+    let mut last_time = DateTime::<Utc>::MIN_UTC;
+    loop {
+        match client.next_record().await {
+            Ok(Some(record)) => {
+                if let Some(base_data) = record_to_base_data(&record, &symbol, &mut last_time) {
+                    let _ = sender.send(base_data);
+                }
+            }
+            Ok(None) => break,
+            Err(e) => {
+                eprintln!("{} live session for {} ended: {}", DataVendor::DataBento, symbol.name, e);
+                break;
+            }
+        }
+    }
+}
@@ -15,7 +15,7 @@ use tokio::{signal, task};
 use tokio::sync::{broadcast, OnceCell};
 use tokio_rustls::server::TlsStream;
 use server_features::database::hybrid_storage::{HybridStorage, DATA_STORAGE};
-use crate::data_bento_api::api_client::{data_bento_init};
+use crate::data_bento_api::api_client::{data_bento_init, get_data_bento_client};
 use crate::oanda_api::api_client::{oanda_init};
 use crate::rithmic_api::api_client::{RithmicBrokerageClient, RITHMIC_CLIENTS};
 
@@ -143,6 +143,9 @@ async fn logout_apis() {
             api_client.shutdown().await;
         }
     }
+    if let Ok(client) = get_data_bento_client() {
+        client.shutdown();
+    }
     println!("Logging Out Apis Function Ended");
 }
 
@@ -9,14 +9,15 @@ use dashmap::DashMap;
 use lazy_static::lazy_static;
 use tokio::sync::mpsc::Sender;
 use ff_standard_lib::messages::data_server_messaging::FundForgeError;
-use ff_standard_lib::standardized_types::accounts::{Account, AccountId, AccountInfo};
+use ff_standard_lib::standardized_types::accounts::{Account, AccountId, AccountInfo, AccountSnapshot, BrokerPosition};
 use ff_standard_lib::standardized_types::base_data::base_data_enum::BaseDataEnum;
 use ff_standard_lib::standardized_types::broker_enum::Brokerage;
 use ff_standard_lib::standardized_types::datavendor_enum::DataVendor;
-use ff_standard_lib::standardized_types::orders::OrderId;
+use ff_standard_lib::standardized_types::orders::{Order, OrderId};
 use ff_standard_lib::standardized_types::position::Position;
 use ff_standard_lib::standardized_types::subscriptions::{DataSubscription, Symbol, SymbolName};
-use crate::oanda_api::get::accounts::account_details::get_oanda_account_details;
+use ff_standard_lib::strategies::ledgers::financing::SymbolFinancingRate;
+use crate::oanda_api::get::accounts::account_details::{get_oanda_account_details, parse_oanda_pending_order};
 use crate::oanda_api::get::accounts::account_list::get_oanda_accounts_list;
 use crate::oanda_api::get::accounts::account_summary::get_oanda_account_summary;
 use crate::oanda_api::get::instruments::get_oanda_instruments;
@@ -26,6 +27,8 @@ use crate::oanda_api::get::positions::parse_oanda_position;
 use crate::oanda_api::handlers::quotebar_streams::handle_quotebar_subscribers;
 use crate::oanda_api::models::order::placement::OandaOrderUpdate;
 use crate::ServerLaunchOptions;
+use ff_standard_lib::StreamName;
+use rust_decimal_macros::dec;
 
 lazy_static! {
     pub static ref OANDA_IS_CONNECTED: AtomicBool = AtomicBool::new(false);
@@ -259,6 +262,52 @@ pub(crate) async fn oanda_init(options: ServerLaunchOptions) {
 }
 
 impl OandaClient {
+    /// The open positions and pending orders of the account, read from the account details.
+    /// The orders are registered with this stream so a strategy can cancel or manage orders left working by a previous session.
+    pub async fn account_snapshot(&self, stream_name: StreamName, account_id: AccountId) -> Result<AccountSnapshot, FundForgeError> {
+        let account = match self.accounts.iter().find(|account| account.account_id == account_id) {
+            Some(account) => account.clone(),
+            None => return Err(FundForgeError::ServerErrorDebug(format!("Oanda account not found: {}", account_id)))
+        };
+        let details = get_oanda_account_details(self, &account_id).await?;
+
+        let mut positions = vec![];
+        for position in details.positions {
+            let position = match parse_oanda_position(position, account.clone()) {
+                Some(position) if position.quantity_open != dec!(0) => position,
+                _ => continue
+            };
+            positions.push(BrokerPosition {
+                symbol_name: position.symbol_name.clone(),
+                symbol_code: position.symbol_code.clone(),
+                side: position.side,
+                // short units are negative
+                quantity: position.quantity_open.abs(),
+                average_price: position.average_price,
+            });
+            self.positions.entry(account_id.clone()).or_default().insert(position.symbol_name.clone(), position);
+        }
+
+        let orders: Vec<Order> = details.orders.iter()
+            .filter_map(|order| parse_oanda_pending_order(order, &account))
+            .collect();
+        for order in &orders {
+            self.open_orders.insert(order.id.clone(), order.clone());
+            self.id_stream_name_map.insert(order.id.clone(), stream_name);
+        }
+        Ok(AccountSnapshot { account, positions, orders })
+    }
+
+    /// The financing rates of the instruments available to the account.
+    pub fn financing_rates(&self) -> Vec<SymbolFinancingRate> {
+        self.instruments_map.iter()
+            .map(|instrument| SymbolFinancingRate {
+                symbol_name: instrument.key().clone(),
+                rate: instrument.value().financing.to_financing_rate(),
+            })
+            .collect()
+    }
+
     pub async fn get_order_by_client_id (
         &self,
         account_id: &str,
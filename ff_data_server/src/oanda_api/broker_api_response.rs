@@ -10,7 +10,7 @@ use ff_standard_lib::product_maps::oanda::maps::{OANDA_SYMBOL_INFO};
 use crate::server_features::server_side_brokerage::BrokerApiResponse;
 use ff_standard_lib::standardized_types::accounts::{Account, AccountId};
 use ff_standard_lib::standardized_types::enums::{OrderSide, PositionSide, StrategyMode};
-use ff_standard_lib::standardized_types::orders::{Order, OrderId, OrderState, OrderType, OrderUpdateEvent, OrderUpdateType, TimeInForce};
+use ff_standard_lib::standardized_types::orders::{Order, OrderGroupId, OrderGroupType, OrderId, OrderState, OrderType, OrderUpdateEvent, OrderUpdateType, ProtectiveOrder, TimeInForce};
 use ff_standard_lib::standardized_types::subscriptions::{SymbolName};
 use ff_standard_lib::StreamName;
 use crate::oanda_api::api_client::OandaClient;
@@ -110,6 +110,7 @@ impl BrokerApiResponse for OandaClient {
                         tag: "Exit Short, Before Enter Long".to_string(),
                         symbol_code: order.symbol_code.clone(),
                         exchange: order.exchange.clone(),
+                        brackets: None,
                     };
                     match self.other_orders(stream_name.clone(), mode, exit_long_order).await {
                         Ok(_) => {}
@@ -148,6 +149,7 @@ impl BrokerApiResponse for OandaClient {
                         tag: "Exit Long, Before Enter Short".to_string(),
                         symbol_code: order.symbol_code.clone(),
                         exchange: order.exchange.clone(),
+                        brackets: None,
                     };
                     match self.other_orders(stream_name.clone(), mode, exit_long_order).await {
                         Ok(_) => {}
@@ -196,10 +198,24 @@ impl BrokerApiResponse for OandaClient {
             OrderSide::Sell => -order.quantity_open,
         };
 
+        let is_market_order = matches!(order.order_type, OrderType::Market | OrderType::EnterLong | OrderType::EnterShort | OrderType::ExitLong | OrderType::ExitShort);
+        // Oanda market orders only accept FOK or IOC, and market if touched orders do not accept either.
+        if order.order_type == OrderType::MarketIfTouched && (order.time_in_force == TimeInForce::IOC || order.time_in_force == TimeInForce::FOK) {
+            return Err(OrderUpdateEvent::OrderRejected {
+                account: order.account,
+                symbol_name: order.symbol_name.to_string(),
+                symbol_code: order.symbol_name,
+                order_id: order.id,
+                reason: format!("Time in force not supported by Oanda for market if touched orders: {:?}", order.time_in_force),
+                tag: order.tag,
+                time: Utc::now().to_string(),
+            });
+        }
         let (time_in_force, gtd_time) = match order.time_in_force {
-            TimeInForce::GTC => ("GTC".to_string(), None),
             TimeInForce::IOC => ("IOC".to_string(), None),
             TimeInForce::FOK => ("FOK".to_string(), None),
+            _ if is_market_order => ("FOK".to_string(), None),
+            TimeInForce::GTC => ("GTC".to_string(), None),
             TimeInForce::Day => ("GFD".to_string(), None),
             TimeInForce::Time(time_stamp) => {
                 let time = match DateTime::<Utc>::from_timestamp(time_stamp, 0) {
@@ -395,6 +411,16 @@ impl BrokerApiResponse for OandaClient {
                 })
            }
        };
+        // Oanda attaches protective orders to the trade created by the fill, using the take profit, stop loss and trailing stop loss details.
+        let mut json_order = json_order;
+        if let (OrderType::EnterLong | OrderType::EnterShort, Some(brackets)) = (&order.order_type, &order.brackets) {
+            if let Some(order_json) = json_order["order"].as_object_mut() {
+                for (key, details) in protective_orders_on_fill(brackets) {
+                    order_json.insert(key, details);
+                }
+            }
+        }
+
         match self.client
             .post(&url)
             .header("Content-Type", "application/json")
@@ -610,12 +636,27 @@ impl BrokerApiResponse for OandaClient {
                     tag,
                     symbol_code: position.symbol_name.clone(),
                     exchange: None,
+                    brackets: None,
                 };
                 let _ = self.other_orders(0, StrategyMode::Live, exit_order).await;
             }
         }
     }
 
+    #[allow(unused)]
+    async fn live_order_group(&self, stream_name: StreamName, mode: StrategyMode, group_id: OrderGroupId, group_type: OrderGroupType, orders: Vec<Order>) -> Result<(), Vec<OrderUpdateEvent>> {
+        // Oanda has no native order linking, the strategy emulates order groups by placing and cancelling the orders itself.
+        Err(orders.into_iter().map(|order| OrderUpdateEvent::OrderRejected {
+            account: order.account,
+            symbol_name: order.symbol_name,
+            symbol_code: order.symbol_code,
+            order_id: order.id,
+            reason: format!("Order groups not supported natively with Oanda: {}", group_type),
+            tag: order.tag,
+            time: Utc::now().to_string(),
+        }).collect())
+    }
+
     #[allow(unused)]
     async fn update_order(&self, account: Account, order_id: OrderId, update: OrderUpdateType) -> Result<(), OrderUpdateEvent> {
         Err(OrderUpdateEvent::OrderUpdateRejected {
@@ -626,3 +667,64 @@ impl BrokerApiResponse for OandaClient {
         })
     }
 }
+
+/// Converts our protective orders into the Oanda on fill details for a market order.
+fn protective_orders_on_fill(brackets: &Vec<ProtectiveOrder>) -> Vec<(String, serde_json::Value)> {
+    let mut details = Vec::new();
+    for protective_order in brackets {
+        match protective_order {
+            ProtectiveOrder::TakeProfit { price } => {
+                details.push(("takeProfitOnFill".to_string(), serde_json::json!({
+                    "price": price.to_string(),
+                    "timeInForce": "GTC",
+                })));
+            }
+            ProtectiveOrder::StopLoss { price } => {
+                details.push(("stopLossOnFill".to_string(), serde_json::json!({
+                    "price": price.to_string(),
+                    "timeInForce": "GTC",
+                })));
+            }
+            ProtectiveOrder::TrailingStopLoss { price, trail_value } => {
+                // Oanda trails from the fill price, the requested price is held by a stop loss, so the trade exits at whichever stop the market reaches first,
+                // the same as a trailing stop starting at the requested price. A separate stop loss takes the place of the requested price.
+                let has_stop_loss = brackets.iter().any(|order| matches!(order, ProtectiveOrder::StopLoss { .. }));
+                if !has_stop_loss {
+                    details.push(("stopLossOnFill".to_string(), serde_json::json!({
+                        "price": price.to_string(),
+                        "timeInForce": "GTC",
+                    })));
+                }
+                details.push(("trailingStopLossOnFill".to_string(), serde_json::json!({
+                    "distance": trail_value.to_string(),
+                    "timeInForce": "GTC",
+                })));
+            }
+        }
+    }
+    details
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_trailing_stop_on_fill_starts_at_the_requested_price() {
+        let details = protective_orders_on_fill(&vec![ProtectiveOrder::TrailingStopLoss { price: dec!(1.0950), trail_value: dec!(0.0050) }]);
+        assert_eq!(details.len(), 2);
+        assert_eq!(details[0].0, "stopLossOnFill");
+        assert_eq!(details[0].1["price"], "1.0950");
+        assert_eq!(details[1].0, "trailingStopLossOnFill");
+        assert_eq!(details[1].1["distance"], "0.0050");
+
+        let details = protective_orders_on_fill(&vec![
+            ProtectiveOrder::StopLoss { price: dec!(1.0900) },
+            ProtectiveOrder::TrailingStopLoss { price: dec!(1.0950), trail_value: dec!(0.0050) },
+        ]);
+        assert_eq!(details.len(), 2);
+        assert_eq!(details[0].1["price"], "1.0900");
+        assert_eq!(details[1].0, "trailingStopLossOnFill");
+    }
+}
@@ -1,8 +1,14 @@
+use std::str::FromStr;
 use ff_standard_lib::messages::data_server_messaging::FundForgeError;
 use serde_derive::Deserialize;
 use chrono::{DateTime, Utc};
 use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
 use serde_json::Value;
+use ff_standard_lib::helpers::converters::fund_forge_formatted_symbol_name;
+use ff_standard_lib::standardized_types::accounts::Account;
+use ff_standard_lib::standardized_types::enums::OrderSide;
+use ff_standard_lib::standardized_types::orders::{Order, OrderState, OrderType, TimeInForce};
 use crate::oanda_api::api_client::OandaClient;
 use crate::oanda_api::get::positions::OandaPosition;
 use crate::oanda_api::models::trade::TradeSummary;
@@ -117,4 +123,68 @@ pub(crate) struct OandaAccount {
     pub positions: Vec<OandaPosition>,
     #[serde(default)]
     pub orders: Vec<Value>,
-}
\ No newline at end of file
+}
+
+/// Parses a pending order of `OandaAccount.orders`, orders placed by fund forge keep the client order id and other orders use the oanda order id.
+/// Take profit, stop loss and trailing stop orders belong to a trade and close with it, they are not returned.
+pub(crate) fn parse_oanda_pending_order(order: &Value, account: &Account) -> Option<Order> {
+    let decimal = |field: &str| order[field].as_str().and_then(|value| Decimal::from_str(value).ok());
+    let order_type = match order["type"].as_str()? {
+        "LIMIT" => OrderType::Limit,
+        "STOP" => OrderType::StopMarket,
+        "MARKET_IF_TOUCHED" => OrderType::MarketIfTouched,
+        _ => return None
+    };
+    let symbol_name = fund_forge_formatted_symbol_name(order["instrument"].as_str()?);
+    let units = decimal("units")?;
+    let side = match units > dec!(0) {
+        true => OrderSide::Buy,
+        false => OrderSide::Sell
+    };
+    let price = decimal("price");
+    let (limit_price, trigger_price) = match order_type {
+        OrderType::Limit => (price, None),
+        _ => (decimal("priceBound"), price)
+    };
+    let time_in_force = match order["timeInForce"].as_str() {
+        Some("GTD") => match order["gtdTime"].as_str().and_then(|time| DateTime::<Utc>::from_str(time).ok()) {
+            Some(time) => TimeInForce::Time(time.timestamp()),
+            None => TimeInForce::GTC
+        },
+        Some("GFD") => TimeInForce::Day,
+        Some("IOC") => TimeInForce::IOC,
+        Some("FOK") => TimeInForce::FOK,
+        _ => TimeInForce::GTC
+    };
+    let id = match order["clientExtensions"]["id"].as_str() {
+        Some(id) => id.to_string(),
+        None => order["id"].as_str()?.to_string()
+    };
+    let tag = order["clientExtensions"]["tag"].as_str().unwrap_or("External Order").to_string();
+    let time_created_utc = order["createTime"].as_str()
+        .and_then(|time| DateTime::<Utc>::from_str(time).ok())
+        .unwrap_or_else(Utc::now)
+        .to_string();
+    Some(Order {
+        symbol_name: symbol_name.clone(),
+        symbol_code: symbol_name,
+        account: account.clone(),
+        quantity_open: units.abs(),
+        quantity_filled: dec!(0),
+        average_fill_price: None,
+        limit_price,
+        trigger_price,
+        side,
+        order_type,
+        time_in_force,
+        tag,
+        id,
+        time_created_utc,
+        time_filled_utc: None,
+        state: OrderState::Accepted,
+        fees: dec!(0),
+        value: dec!(0),
+        exchange: None,
+        brackets: None,
+    })
+}
@@ -2,6 +2,7 @@ use rust_decimal::Decimal;
 use chrono::{NaiveDateTime, DateTime as ChronoDateTime, Utc};
 use serde::{self, Deserialize, Deserializer, Serializer, Serialize, de};
 use crate::oanda_api::models::pricing_common::PriceBucket;
+use ff_standard_lib::strategies::ledgers::financing::FinancingRate;
 
 /// A tag associated with an entity.
 #[derive(Serialize, Deserialize, Debug)]
@@ -65,6 +66,30 @@ pub struct InstrumentFinancing {
     pub financing_days_of_week: Vec<FinancingDayOfWeek>,
 }
 
+impl InstrumentFinancing {
+    /// Converts the Oanda financing to the rate used by the backtest ledgers, days which are not listed are not charged.
+    pub fn to_financing_rate(&self) -> FinancingRate {
+        let mut days_charged = [0; 7];
+        for day in &self.financing_days_of_week {
+            let index = match day.day_of_week {
+                DayOfWeek::Monday => 0,
+                DayOfWeek::Tuesday => 1,
+                DayOfWeek::Wednesday => 2,
+                DayOfWeek::Thursday => 3,
+                DayOfWeek::Friday => 4,
+                DayOfWeek::Saturday => 5,
+                DayOfWeek::Sunday => 6,
+            };
+            days_charged[index] = day.days_charged.max(0) as u32;
+        }
+        FinancingRate {
+            long_rate: self.long_rate,
+            short_rate: self.short_rate,
+            days_charged,
+        }
+    }
+}
+
 /// Full specification of an Instrument.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Instrument {
@@ -1,6 +1,7 @@
 use std::future::Future;
 use ff_standard_lib::messages::data_server_messaging::{DataServerRequest, DataServerResponse, FundForgeError};
 use ff_standard_lib::standardized_types::subscriptions::DataSubscription;
+use ff_standard_lib::standardized_types::continuous_contracts::RollRule;
 use ff_standard_lib::standardized_types::bytes_trait::Bytes;
 use chrono::{DateTime, Utc};
 use std::str::FromStr;
@@ -17,7 +18,7 @@ use tokio::sync::mpsc::{Receiver, Sender};
 use tokio::time::timeout;
 use tokio_rustls::server::TlsStream;
 use crate::server_features::database::hybrid_storage::{DATA_STORAGE, MULTIBAR};
-use crate::server_side_brokerage::{account_info_response, accounts_response, commission_info_response, live_market_order, symbol_info_response, symbol_names_response, live_enter_long, live_exit_long, live_exit_short, live_enter_short, other_orders, cancel_order, flatten_all_for, update_order, cancel_orders_on_account, exchange_rate_response, front_month_info_response};
+use crate::server_side_brokerage::{account_info_response, accounts_response, commission_info_response, live_market_order, symbol_info_response, symbol_names_response, live_enter_long, live_exit_long, live_exit_short, live_enter_short, other_orders, cancel_order, flatten_all_for, update_order, cancel_orders_on_account, exchange_rate_response, front_month_info_response, live_order_group, financing_rates_response, account_snapshot_response};
 use crate::server_side_datavendor::{base_data_types_response, decimal_accuracy_response, markets_response, resolutions_response, symbols_response, tick_size_response};
 use ff_standard_lib::standardized_types::enums::StrategyMode;
 use ff_standard_lib::standardized_types::orders::{Order, OrderRequest, OrderType, OrderUpdateEvent};
@@ -100,6 +101,48 @@ pub async fn compressed_file_response(
     }
 }
 
+pub async fn roll_schedule_response(
+    subscription: DataSubscription,
+    roll_rule: RollRule,
+    from_time: String,
+    to_time: String,
+    callback_id: u64,
+) -> DataServerResponse {
+    let from_time = match from_time.parse::<DateTime<Utc>>() {
+        Ok(t) => t,
+        Err(e) => return DataServerResponse::Error {
+            callback_id,
+            error: FundForgeError::ServerErrorDebug(format!("Invalid from_time: {}", e))
+        }
+    };
+    let to_time = match to_time.parse::<DateTime<Utc>>() {
+        Ok(t) => t,
+        Err(e) => return DataServerResponse::Error {
+            callback_id,
+            error: FundForgeError::ServerErrorDebug(format!("Invalid to_time: {}", e))
+        }
+    };
+
+    let data_storage = match DATA_STORAGE.get() {
+        Some(storage) => storage,
+        None => return DataServerResponse::Error {
+            callback_id,
+            error: FundForgeError::ServerErrorDebug("Data storage not initialized".to_string())
+        }
+    };
+
+    match data_storage.get_roll_schedule(&subscription, roll_rule, from_time, to_time).await {
+        Ok(schedule) => DataServerResponse::RollSchedule {
+            callback_id,
+            schedule
+        },
+        Err(e) => DataServerResponse::Error {
+            callback_id,
+            error: e
+        }
+    }
+}
+
 pub async fn manage_async_requests(
     strategy_mode: StrategyMode,
     stream: TlsStream<TcpStream>,
@@ -200,6 +243,12 @@ pub async fn manage_async_requests(
                             sender.clone()).await
                     }
 
+                    DataServerRequest::RollSchedule { callback_id, subscription, roll_rule, from_time, to_time } => {
+                        handle_callback_no_timeouts (
+                            || roll_schedule_response(subscription, roll_rule, from_time, to_time, callback_id),
+                            sender.clone()).await
+                    }
+
                     DataServerRequest::SymbolsVendor {
                         data_vendor,
                         market_type,
@@ -301,6 +350,18 @@ pub async fn manage_async_requests(
                             sender.clone(),callback_id).await
                     }
 
+                    DataServerRequest::FinancingRates { callback_id, brokerage } => {
+                        handle_callback(
+                            || financing_rates_response(brokerage, callback_id),
+                            sender.clone(),callback_id).await
+                    }
+
+                    DataServerRequest::AccountSnapshot { callback_id, brokerage, account_id } => {
+                        handle_callback(
+                            || account_snapshot_response(brokerage, account_id, stream_name, callback_id),
+                            sender.clone(),callback_id).await
+                    }
+
                     DataServerRequest::FrontMonthInfo { callback_id, symbol_name, exchange, brokerage } => {
                         handle_callback(
                             || front_month_info_response(brokerage, symbol_name, exchange, stream_name, callback_id),
@@ -561,5 +622,22 @@ async fn order_response(stream_name: StreamName, mode: StrategyMode, request: Or
         OrderRequest::FlattenAllFor { account } => {
             flatten_all_for(account).await;
         }
+        OrderRequest::CreateGroup { account, group_id, group_type, orders } => {
+            let send_order_result = timeout(TIMEOUT_DURATION, live_order_group(stream_name.clone(), mode, account, group_id, group_type, orders.clone())).await;
+            match send_order_result {
+                Ok(Ok(_)) => {} // Orders placed successfully
+                Ok(Err(events)) => {
+                    for e in events {
+                        send_error_response(&sender, e, &stream_name).await;
+                    }
+                }
+                Err(_) => {
+                    for order in &orders {
+                        let timeout_error = create_order_rejected(order, "Order placement timed out".to_string());
+                        send_error_response(&sender, timeout_error, &stream_name).await;
+                    }
+                }
+            }
+        }
     }
 }
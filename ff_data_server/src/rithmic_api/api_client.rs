@@ -19,11 +19,11 @@ use ff_standard_lib::standardized_types::base_data::base_data_enum::BaseDataEnum
 use ff_standard_lib::standardized_types::broker_enum::Brokerage;
 use ff_standard_lib::standardized_types::datavendor_enum::DataVendor;
 use ff_standard_lib::standardized_types::enums::{FuturesExchange, MarketType, OrderSide, PositionSide, StrategyMode};
-use ff_standard_lib::standardized_types::orders::{Order, OrderId, OrderType, OrderUpdateEvent, OrderUpdateType, TimeInForce};
-use ff_standard_lib::standardized_types::subscriptions::{Symbol, SymbolName};
+use ff_standard_lib::standardized_types::orders::{Order, OrderId, OrderType, OrderUpdateEvent, OrderUpdateType, ProtectiveOrder, TimeInForce};
+use ff_standard_lib::standardized_types::subscriptions::{Symbol, SymbolCode, SymbolName};
 use ff_standard_lib::standardized_types::symbol_info::{FrontMonthInfo};
 use ff_standard_lib::standardized_types::books::BookLevel;
-use ff_standard_lib::standardized_types::accounts::AccountId;
+use ff_standard_lib::standardized_types::accounts::{Account, AccountId, AccountSnapshot, BrokerPosition};
 use ff_standard_lib::StreamName;
 use prost::Message as ProstMessage;
 use rust_decimal::Decimal;
@@ -38,7 +38,7 @@ use tungstenite::{Error, Message};
 use ff_standard_lib::apis::rithmic::rithmic_systems::RithmicSystem;
 use crate::server_features::server_side_datavendor::VendorApiResponse;
 use ff_standard_lib::standardized_types::accounts::AccountInfo;
-use ff_standard_lib::standardized_types::new_types::Volume;
+use ff_standard_lib::standardized_types::new_types::{Price, Volume};
 use ff_standard_lib::standardized_types::position::PositionId;
 use uuid::Uuid;
 use ff_standard_lib::standardized_types::base_data::base_data_type::BaseDataType;
@@ -47,10 +47,12 @@ use crate::rithmic_api::client_base::api_base::{RithmicApiClient, TEMPLATE_VERSI
 use crate::rithmic_api::client_base::credentials::RithmicCredentials;
 use crate::rithmic_api::client_base::rithmic_proto_objects::rti::request_login::SysInfraType;
 #[allow(unused_imports)]
-use crate::rithmic_api::client_base::rithmic_proto_objects::rti::{request_tick_bar_replay, RequestAccountList, RequestAccountRmsInfo, RequestFrontMonthContract, RequestHeartbeat, RequestNewOrder, RequestPnLPositionUpdates, RequestReferenceData, RequestShowOrders, RequestSubscribeForOrderUpdates, RequestTickBarReplay, RequestTimeBarReplay, RequestTradeRoutes};
+use crate::rithmic_api::client_base::rithmic_proto_objects::rti::{request_tick_bar_replay, RequestAccountList, RequestAccountRmsInfo, RequestFrontMonthContract, RequestHeartbeat, RequestNewOrder, RequestPnLPositionSnapshot, RequestPnLPositionUpdates, RequestReferenceData, RequestShowOrders, RequestSubscribeForOrderUpdates, RequestTickBarReplay, RequestTimeBarReplay, RequestTradeRoutes, RequestBracketOrder, request_bracket_order, RequestOcoOrder, request_oco_order, RequestLinkOrders, RequestModifyOrder, RequestCancelOrder, RequestUpdateStopBracketLevel, RequestUpdateTargetBracketLevel, ResponseDepthByOrderSnapshot};
+use crate::rithmic_api::client_base::rithmic_proto_objects::rti::request_bracket_order::BracketType;
 use crate::rithmic_api::client_base::rithmic_proto_objects::rti::request_new_order::{OrderPlacement, PriceType, TransactionType};
 use crate::rithmic_api::plant_handlers::handler_loop::handle_rithmic_responses;
-use ff_standard_lib::product_maps::rithmic::maps::{get_exchange_by_symbol_name};
+use crate::rithmic_api::plant_handlers::handle_order_plant::{ACCOUNT_SNAPSHOT, BASKET_ID_TO_ID_MAP, BASKET_TO_STREAM_NAME_MAP, ID_TO_STREAM_NAME_MAP, ID_TO_TAG};
+use ff_standard_lib::product_maps::rithmic::maps::{get_exchange_by_symbol_name, get_futures_symbol_info};
 use once_cell::sync::OnceCell;
 use ff_standard_lib::standardized_types::resolution::Resolution;
 use crate::rithmic_api::client_base::rithmic_proto_objects::rti::request_time_bar_replay::{Direction, TimeOrder};
@@ -77,6 +79,27 @@ pub fn get_rithmic_client(rithmic_system: &RithmicSystem) -> Option<Arc<RithmicB
     None
 }
 
+/// A Rithmic bracket order protecting an open position.
+/// When the position is scaled the bracket is moved and resized to the whole position, instead of a second bracket being placed.
+#[derive(Clone, Debug)]
+pub struct LiveBracket {
+    pub order_id: OrderId,
+    /// The side of the entry order.
+    pub side: OrderSide,
+    /// The basket id of the entry order, set by the bracket order response.
+    pub basket_id: Option<String>,
+    /// The basket ids of the stop and target orders, set by their order notifications.
+    pub stop_basket_id: Option<String>,
+    pub target_basket_id: Option<String>,
+    pub exchange: String,
+    /// The price the bracket levels are measured from in ticks.
+    pub reference_price: Price,
+    pub quantity: i32,
+    pub stop_ticks: Option<i32>,
+    pub target_ticks: Option<i32>,
+    pub trailing_ticks: Option<i32>,
+}
+
 //todo make a seperate client for data, so we arent initializing pointless maps, this will also make it much more maintainable
 pub struct RithmicBrokerageClient {
     pub brokerage: Brokerage,
@@ -113,8 +136,15 @@ pub struct RithmicBrokerageClient {
 
     pub open_orders: DashMap<AccountId, DashMap<OrderId, Order>>,
     pub id_to_basket_id_map: DashMap<AccountId, DashMap<OrderId, String>>,
+    pub live_brackets: DashMap<AccountId, DashMap<SymbolCode, LiveBracket>>,
     pub pending_order_updates: DashMap<Brokerage, DashMap<OrderId , OrderUpdateType>>,
 
+    /// The working orders by basket id and the open positions collected for `account_snapshot()`, from the snapshot notifications sent before the
+    /// `ResponseShowOrders` and `ResponsePnLPositionSnapshot`. The senders are completed by those responses.
+    pub snapshot_orders: DashMap<AccountId, DashMap<String, Order>>,
+    pub snapshot_positions: DashMap<AccountId, DashMap<SymbolCode, BrokerPosition>>,
+    pub snapshot_senders: DashMap<(AccountId, SysInfraType), oneshot::Sender<()>>,
+
     pub orders_open: DashMap<OrderId, Order>,
 
     //products
@@ -126,6 +156,8 @@ pub struct RithmicBrokerageClient {
     pub tick_feed_broadcasters: Arc<DashMap<SymbolName, broadcast::Sender<BaseDataEnum>>>,
     pub quote_feed_broadcasters: Arc<DashMap<SymbolName, broadcast::Sender<BaseDataEnum>>>,
     pub candle_feed_broadcasters: Arc<DashMap<SymbolName, broadcast::Sender<BaseDataEnum>>>,
+    pub order_book_feed_broadcasters: Arc<DashMap<SymbolName, broadcast::Sender<BaseDataEnum>>>,
+    pub market_by_order_feed_broadcasters: Arc<DashMap<SymbolName, broadcast::Sender<BaseDataEnum>>>,
 
     // first string is fcm id second is trade route
     pub default_trade_route: DashMap<RithmicSystem, AHashMap<(String, FuturesExchange), String>>,
@@ -133,6 +165,17 @@ pub struct RithmicBrokerageClient {
     pub bid_book: DashMap<SymbolName, BTreeMap<u16, BookLevel>>,
     pub ask_book: DashMap<SymbolName, BTreeMap<u16, BookLevel>>,
 
+    // the full market depth as price -> volume, maintained from the order book updates
+    pub depth_bids: DashMap<SymbolName, BTreeMap<Price, Volume>>,
+    pub depth_asks: DashMap<SymbolName, BTreeMap<Price, Volume>>,
+    // streamed order books waiting to be saved to the data storage
+    pub order_book_buffer: DashMap<SymbolName, Vec<BaseDataEnum>>,
+    // streamed market by order events waiting to be saved to the data storage, and the time of the last event so each event has a unique time
+    pub market_by_order_buffer: DashMap<SymbolName, Vec<BaseDataEnum>>,
+    pub market_by_order_last_time: DashMap<SymbolName, DateTime<Utc>>,
+    // the depth by order snapshot has no exchange time, it waits for the first update and is stamped just before it
+    pub market_by_order_snapshots: DashMap<SymbolName, Vec<ResponseDepthByOrderSnapshot>>,
+
     pub order_broadcaster: broadcast::Sender<DataServerResponse>,
 }
 
@@ -187,7 +230,15 @@ impl RithmicBrokerageClient {
             orders_open: Default::default(),
             products: Default::default(),
             candle_feed_broadcasters: Arc::new(Default::default()),
+            order_book_feed_broadcasters: Arc::new(Default::default()),
+            market_by_order_feed_broadcasters: Arc::new(Default::default()),
             ask_book: Default::default(),
+            depth_bids: Default::default(),
+            depth_asks: Default::default(),
+            order_book_buffer: Default::default(),
+            market_by_order_buffer: Default::default(),
+            market_by_order_last_time: Default::default(),
+            market_by_order_snapshots: Default::default(),
             order_broadcaster: sender,
             long_quantity: Default::default(),
             short_quantity: Default::default(),
@@ -195,7 +246,11 @@ impl RithmicBrokerageClient {
             last_tag: Default::default(),
             open_orders: Default::default(),
             id_to_basket_id_map: Default::default(),
+            live_brackets: Default::default(),
             pending_order_updates: Default::default(),
+            snapshot_orders: Default::default(),
+            snapshot_positions: Default::default(),
+            snapshot_senders: Default::default(),
             historical_callbacks: Default::default(),
         };
 
@@ -371,6 +426,8 @@ impl RithmicBrokerageClient {
         let quote_broadcasters = self.quote_feed_broadcasters.clone();
         let tick_feed_broadcasters = self.tick_feed_broadcasters.clone();
         let candle_broadcasters = self.candle_feed_broadcasters.clone();
+        let order_book_broadcasters = self.order_book_feed_broadcasters.clone();
+        let market_by_order_broadcasters = self.market_by_order_feed_broadcasters.clone();
         let task = task::spawn(async move {
             let mut shutdown_receiver = subscribe_server_shutdown();
             'heartbeat_loop: loop {
@@ -379,7 +436,7 @@ impl RithmicBrokerageClient {
                         let now = Utc::now();
                         let skip_heartbeat = match plant {
                             SysInfraType::TickerPlant => {
-                                !quote_broadcasters.is_empty() || !tick_feed_broadcasters.is_empty()
+                                !quote_broadcasters.is_empty() || !tick_feed_broadcasters.is_empty() || !order_book_broadcasters.is_empty() || !market_by_order_broadcasters.is_empty()
                             }
                             SysInfraType::HistoryPlant => {
                                 !candle_broadcasters.is_empty()
@@ -430,6 +487,73 @@ impl RithmicBrokerageClient {
         self.heartbeat_tasks.insert(plant, task);
     }
 
+    /// Requests the working orders and open positions of the account, the snapshot notifications are collected until both responses arrive.
+    /// The orders are registered with this stream so a strategy can cancel or manage orders left working by a previous session,
+    /// orders not placed by fund forge use their basket id as the order id.
+    pub async fn account_snapshot(&self, stream_name: StreamName, account_id: AccountId) -> Result<AccountSnapshot, FundForgeError> {
+        if !self.account_info.contains_key(&account_id) {
+            return Err(FundForgeError::ServerErrorDebug(format!("Rithmic account not found: {}", account_id)));
+        }
+        self.snapshot_orders.insert(account_id.clone(), DashMap::new());
+        self.snapshot_positions.insert(account_id.clone(), DashMap::new());
+        let (orders_sender, orders_receiver) = oneshot::channel();
+        let (positions_sender, positions_receiver) = oneshot::channel();
+        self.snapshot_senders.insert((account_id.clone(), SysInfraType::OrderPlant), orders_sender);
+        self.snapshot_senders.insert((account_id.clone(), SysInfraType::PnlPlant), positions_sender);
+
+        let req = RequestShowOrders {
+            template_id: 320,
+            user_msg: vec![ACCOUNT_SNAPSHOT.to_string(), account_id.clone()],
+            fcm_id: self.fcm_id.clone(),
+            ib_id: self.ib_id.clone(),
+            account_id: Some(account_id.clone()),
+        };
+        self.send_message(&SysInfraType::OrderPlant, req).await;
+        let req = RequestPnLPositionSnapshot {
+            template_id: 402,
+            user_msg: vec![ACCOUNT_SNAPSHOT.to_string(), account_id.clone()],
+            fcm_id: self.fcm_id.clone(),
+            ib_id: self.ib_id.clone(),
+            account_id: Some(account_id.clone()),
+        };
+        self.send_message(&SysInfraType::PnlPlant, req).await;
+
+        if orders_receiver.await.is_err() || positions_receiver.await.is_err() {
+            return Err(FundForgeError::ServerErrorDebug(format!("Rithmic account snapshot was not completed for: {}", account_id)));
+        }
+
+        let orders: Vec<(String, Order)> = self.snapshot_orders.remove(&account_id)
+            .map(|(_, orders)| orders.into_iter().collect())
+            .unwrap_or_default();
+        for (basket_id, order) in &orders {
+            BASKET_ID_TO_ID_MAP.entry(self.brokerage).or_default().insert(basket_id.clone(), order.id.clone());
+            BASKET_TO_STREAM_NAME_MAP.entry(self.brokerage).or_default().insert(basket_id.clone(), stream_name);
+            ID_TO_STREAM_NAME_MAP.entry(self.brokerage).or_default().insert(order.id.clone(), stream_name);
+            ID_TO_TAG.entry(self.brokerage).or_default().insert(order.id.clone(), order.tag.clone());
+            self.id_to_basket_id_map.entry(account_id.clone()).or_default().insert(order.id.clone(), basket_id.clone());
+            self.open_orders.entry(account_id.clone()).or_default().insert(order.id.clone(), order.clone());
+        }
+
+        let positions: Vec<BrokerPosition> = self.snapshot_positions.remove(&account_id)
+            .map(|(_, positions)| positions.into_iter().map(|(_, position)| position).collect())
+            .unwrap_or_default();
+        // positions opened before the data server started are not in the quantity maps, exits and flatten_all_for() read them from here
+        for position in &positions {
+            let (quantities, opposite) = match position.side {
+                PositionSide::Long => (&self.long_quantity, &self.short_quantity),
+                _ => (&self.short_quantity, &self.long_quantity),
+            };
+            quantities.entry(account_id.clone()).or_insert_with(DashMap::new).insert(position.symbol_code.clone(), position.quantity);
+            opposite.entry(account_id.clone()).or_insert_with(DashMap::new).remove(&position.symbol_code);
+        }
+
+        Ok(AccountSnapshot {
+            account: Account::new(self.brokerage, account_id),
+            positions,
+            orders: orders.into_iter().map(|(_, order)| order).collect(),
+        })
+    }
+
     pub async fn request_updates(&self, account_id: AccountId) {
         if let Some(id_account_info_kvp) = self.account_info.get(&account_id) {
             self.last_tag.insert(account_id.clone(), DashMap::new());
@@ -852,6 +976,429 @@ impl RithmicBrokerageClient {
         self.send_message(&SysInfraType::OrderPlant, req).await;
     }
 
+    /// Submits an entry order with its protective orders attached using a Rithmic bracket order.
+    /// Rithmic brackets are specified in ticks from the fill price, so the protective order prices are converted using the current best bid or offer.
+    pub async fn submit_bracket_order(&self, stream_name: StreamName, mut order: Order, details: CommonRithmicOrderDetails, brackets: Vec<ProtectiveOrder>) -> Result<(), OrderUpdateEvent> {
+        let tick_size = match get_futures_symbol_info(&order.symbol_name) {
+            Ok(info) => info.tick_size,
+            Err(e) => return Err(Self::reject_order(&order, format!("Unable to convert brackets to ticks: {}", e)))
+        };
+
+        let reference_price = match order.side {
+            OrderSide::Buy => self.ask_book.get(&details.symbol_code).and_then(|book| book.get(&0).map(|level| level.price)),
+            OrderSide::Sell => self.bid_book.get(&details.symbol_code).and_then(|book| book.get(&0).map(|level| level.price)),
+        };
+        let reference_price = match reference_price {
+            Some(price) => price,
+            None => return Err(Self::reject_order(&order, format!("Unable to convert brackets to ticks, no market price for: {}", details.symbol_code)))
+        };
+
+        // scaling into a position keeps the working bracket, its levels are measured from the price of the first entry
+        let working_bracket = self.working_bracket(&order.account.account_id, &details.symbol_code);
+        let reference_price = match &working_bracket {
+            Some(bracket) if bracket.side == order.side => bracket.reference_price,
+            _ => reference_price
+        };
+        let to_ticks = |price: Decimal| -> Option<i32> {
+            ((price - reference_price).abs() / tick_size).round().to_i32()
+        };
+
+        let (mut target_ticks, mut stop_ticks, mut trailing_ticks) = (None, None, None);
+        for protective_order in &brackets {
+            match protective_order {
+                ProtectiveOrder::TakeProfit { price } => target_ticks = to_ticks(*price),
+                ProtectiveOrder::StopLoss { price } => stop_ticks = to_ticks(*price),
+                ProtectiveOrder::TrailingStopLoss { price, trail_value } => {
+                    stop_ticks = to_ticks(*price);
+                    trailing_ticks = (trail_value / tick_size).round().to_i32();
+                }
+            }
+        }
+
+        let bracket_type = match (target_ticks.is_some(), stop_ticks.is_some()) {
+            (true, true) => BracketType::TargetAndStop,
+            (true, false) => BracketType::TargetOnly,
+            (false, true) => BracketType::StopOnly,
+            (false, false) => return Err(Self::reject_order(&order, "Invalid brackets, no target or stop price".to_string()))
+        };
+
+        // when reversing a position the entry quantity includes the exit, the brackets only protect the new position
+        let bracket_quantity = order.quantity_open.to_i32().unwrap_or(details.quantity);
+
+        let account_id = order.account.account_id.clone();
+        let symbol_code = details.symbol_code.clone();
+        if let Some(bracket) = working_bracket {
+            let position_quantity = self.position_quantity(&account_id, &symbol_code, bracket.side);
+            let same_levels = bracket.stop_ticks.is_some() == stop_ticks.is_some() && bracket.target_ticks.is_some() == target_ticks.is_some();
+            if bracket.side == order.side && position_quantity > 0 && bracket.basket_id.is_some() && same_levels {
+                let updated = LiveBracket {
+                    quantity: position_quantity + bracket_quantity,
+                    stop_ticks,
+                    target_ticks,
+                    trailing_ticks,
+                    ..bracket.clone()
+                };
+                self.submit_market_order(stream_name, order, details).await;
+                self.update_live_bracket(stream_name, &account_id, &symbol_code, &bracket, updated).await;
+                return Ok(());
+            }
+            // the new bracket replaces the working bracket
+            self.cancel_live_bracket(&account_id, &symbol_code).await;
+        }
+
+        if order.exchange.is_none() {
+            order.exchange = Some(details.exchange.to_string());
+        }
+
+        // the entry is a market order, so only immediate time in force is passed through, the brackets work for the rest of the day
+        let duration = match order.time_in_force {
+            TimeInForce::IOC => request_bracket_order::Duration::Ioc,
+            TimeInForce::FOK => request_bracket_order::Duration::Fok,
+            _ => request_bracket_order::Duration::Day
+        };
+
+        let req = RequestBracketOrder {
+            template_id: 330,
+            user_msg: vec![stream_name.to_string(), order.account.account_id.clone(), order.tag.clone(), order.symbol_name.clone(), details.symbol_code.clone()],
+            user_tag: Some(order.id.clone()),
+            window_name: Some(stream_name.to_string()),
+            fcm_id: self.fcm_id.clone(),
+            ib_id: self.ib_id.clone(),
+            account_id: Some(order.account.account_id.clone()),
+            symbol: Some(details.symbol_code.clone()),
+            exchange: Some(details.exchange.to_string()),
+            quantity: Some(details.quantity),
+            price: None,
+            trigger_price: None,
+            transaction_type: Some(details.transaction_type.into()),
+            duration: Some(duration.into()),
+            price_type: Some(request_bracket_order::PriceType::Market.into()),
+            trade_route: Some(details.route),
+            manual_or_auto: Some(request_bracket_order::OrderPlacement::Auto.into()),
+            user_type: self.credentials.user_type,
+            bracket_type: Some(bracket_type.into()),
+            break_even_ticks: None,
+            break_even_trigger_ticks: None,
+            target_quantity: target_ticks.map(|_| bracket_quantity),
+            target_ticks,
+            stop_quantity: stop_ticks.map(|_| bracket_quantity),
+            stop_ticks,
+            trailing_stop_trigger_ticks: trailing_ticks,
+            trailing_stop_by_last_trade_price: trailing_ticks.map(|_| true),
+            target_market_order_if_touched: None,
+            stop_market_on_reject: Some(true),
+            target_market_at_ssboe: None,
+            target_market_at_usecs: None,
+            stop_market_at_ssboe: None,
+            stop_market_at_usecs: None,
+            target_market_order_after_secs: None,
+            release_at_ssboe: None,
+            release_at_usecs: None,
+            cancel_at_ssboe: None,
+            cancel_at_usecs: None,
+            cancel_after_secs: None,
+            if_touched_symbol: None,
+            if_touched_exchange: None,
+            if_touched_condition: None,
+            if_touched_price_field: None,
+            if_touched_price: None,
+        };
+
+        self.live_brackets.entry(account_id).or_default().insert(symbol_code, LiveBracket {
+            order_id: order.id.clone(),
+            side: order.side,
+            basket_id: None,
+            stop_basket_id: None,
+            target_basket_id: None,
+            exchange: details.exchange.to_string(),
+            reference_price,
+            quantity: bracket_quantity,
+            stop_ticks,
+            target_ticks,
+            trailing_ticks,
+        });
+        if let Some(account_map) = self.last_tag.get(&order.account.account_id) {
+            account_map.insert(details.symbol_code, order.tag.clone());
+        }
+        self.send_message(&SysInfraType::OrderPlant, req).await;
+        Ok(())
+    }
+
+    /// The bracket protecting the position, if the position is still open.
+    fn working_bracket(&self, account_id: &AccountId, symbol_code: &SymbolCode) -> Option<LiveBracket> {
+        let bracket = self.live_brackets.get(account_id)?.get(symbol_code)?.value().clone();
+        // brackets placed in the last moments have no position yet, once they have been accepted they only protect an open position
+        match bracket.basket_id.is_none() || self.position_quantity(account_id, symbol_code, bracket.side) > 0 {
+            true => Some(bracket),
+            false => {
+                if let Some(account_map) = self.live_brackets.get(account_id) {
+                    account_map.remove(symbol_code);
+                }
+                None
+            }
+        }
+    }
+
+    fn position_quantity(&self, account_id: &AccountId, symbol_code: &SymbolCode, side: OrderSide) -> i32 {
+        let quantities = match side {
+            OrderSide::Buy => &self.long_quantity,
+            OrderSide::Sell => &self.short_quantity,
+        };
+        quantities.get(account_id)
+            .and_then(|account_map| account_map.get(symbol_code).and_then(|quantity| quantity.value().to_i32()))
+            .unwrap_or_default()
+    }
+
+    /// Moves the working bracket to the new levels and resizes the stop and target orders to the new position size.
+    /// The levels are moved with the bracket level requests, the quantity and trail of the stop and target orders are changed with modify order requests.
+    pub async fn update_live_bracket(&self, stream_name: StreamName, account_id: &AccountId, symbol_code: &SymbolCode, bracket: &LiveBracket, updated: LiveBracket) {
+        let basket_id = match &bracket.basket_id {
+            Some(basket_id) => basket_id.clone(),
+            None => return
+        };
+        let user_msg = vec![stream_name.to_string(), account_id.clone(), bracket.order_id.clone()];
+        if updated.stop_ticks != bracket.stop_ticks {
+            let req = RequestUpdateStopBracketLevel {
+                template_id: 334,
+                user_msg: user_msg.clone(),
+                fcm_id: self.fcm_id.clone(),
+                ib_id: self.ib_id.clone(),
+                account_id: Some(account_id.clone()),
+                basket_id: Some(basket_id.clone()),
+                level: bracket.stop_ticks,
+                stop_ticks: updated.stop_ticks,
+            };
+            self.send_message(&SysInfraType::OrderPlant, req).await;
+        }
+        if updated.target_ticks != bracket.target_ticks {
+            let req = RequestUpdateTargetBracketLevel {
+                template_id: 332,
+                user_msg: user_msg.clone(),
+                fcm_id: self.fcm_id.clone(),
+                ib_id: self.ib_id.clone(),
+                account_id: Some(account_id.clone()),
+                basket_id: Some(basket_id.clone()),
+                level: bracket.target_ticks,
+                target_ticks: updated.target_ticks,
+            };
+            self.send_message(&SysInfraType::OrderPlant, req).await;
+        }
+
+        let quantity_changed = updated.quantity != bracket.quantity;
+        if let Some(stop_basket_id) = &bracket.stop_basket_id {
+            if quantity_changed || updated.trailing_ticks != bracket.trailing_ticks {
+                self.modify_bracket_order(account_id, symbol_code, &bracket.exchange, stop_basket_id, updated.quantity, updated.trailing_ticks).await;
+            }
+        }
+        if let (Some(target_basket_id), true) = (&bracket.target_basket_id, quantity_changed) {
+            self.modify_bracket_order(account_id, symbol_code, &bracket.exchange, target_basket_id, updated.quantity, None).await;
+        }
+
+        if let Some(account_map) = self.live_brackets.get(account_id) {
+            account_map.insert(symbol_code.clone(), updated);
+        }
+    }
+
+    /// Resizes the working bracket after part of the position was exited, the bracket is cancelled if the whole position was exited.
+    pub async fn resize_live_bracket(&self, stream_name: StreamName, account_id: &AccountId, symbol_code: &SymbolCode, exit_side: OrderSide, remaining_quantity: i32) {
+        let bracket = match self.working_bracket(account_id, symbol_code) {
+            Some(bracket) if bracket.side != exit_side => bracket,
+            _ => return
+        };
+        match remaining_quantity > 0 {
+            true => {
+                let updated = LiveBracket { quantity: remaining_quantity, ..bracket.clone() };
+                self.update_live_bracket(stream_name, account_id, symbol_code, &bracket, updated).await;
+            }
+            false => self.cancel_live_bracket(account_id, symbol_code).await
+        }
+    }
+
+    /// Cancels the stop and target orders of the working bracket and stops tracking it.
+    pub async fn cancel_live_bracket(&self, account_id: &AccountId, symbol_code: &SymbolCode) {
+        let bracket = match self.live_brackets.get(account_id).and_then(|account_map| account_map.remove(symbol_code)) {
+            Some((_, bracket)) => bracket,
+            None => return
+        };
+        for basket_id in [bracket.stop_basket_id, bracket.target_basket_id].into_iter().flatten() {
+            let req = RequestCancelOrder {
+                template_id: 316,
+                user_msg: vec!["Cancel Bracket Order".to_string()],
+                window_name: None,
+                fcm_id: self.fcm_id.clone(),
+                ib_id: self.ib_id.clone(),
+                account_id: Some(account_id.clone()),
+                basket_id: Some(basket_id),
+                manual_or_auto: Some(2),
+            };
+            self.send_message(&SysInfraType::OrderPlant, req).await;
+        }
+    }
+
+    async fn modify_bracket_order(&self, account_id: &AccountId, symbol_code: &SymbolCode, exchange: &str, basket_id: &str, quantity: i32, trailing_ticks: Option<i32>) {
+        let req = RequestModifyOrder {
+            template_id: 314,
+            user_msg: vec!["Bracket Order".to_string()],
+            window_name: None,
+            fcm_id: self.fcm_id.clone(),
+            ib_id: self.ib_id.clone(),
+            account_id: Some(account_id.clone()),
+            basket_id: Some(basket_id.to_string()),
+            symbol: Some(symbol_code.clone()),
+            exchange: Some(exchange.to_string()),
+            quantity: Some(quantity),
+            price: None,
+            trigger_price: None,
+            price_type: None,
+            manual_or_auto: Some(2),
+            trailing_stop: trailing_ticks.map(|_| true),
+            trail_by_ticks: trailing_ticks,
+            if_touched_symbol: None,
+            if_touched_exchange: None,
+            if_touched_condition: None,
+            if_touched_price_field: None,
+            if_touched_price: None,
+        };
+        self.send_message(&SysInfraType::OrderPlant, req).await;
+    }
+
+    /// Records the basket id of a stop or target order placed by a bracket, the orders are linked to the entry by its basket id.
+    pub fn record_bracket_order(&self, account_id: &AccountId, symbol_code: &SymbolCode, parent_basket_id: &str, basket_id: &str, is_stop: bool) {
+        if let Some(account_map) = self.live_brackets.get(account_id) {
+            if let Some(mut bracket) = account_map.get_mut(symbol_code) {
+                if bracket.basket_id.as_deref() != Some(parent_basket_id) {
+                    return;
+                }
+                match is_stop {
+                    true => bracket.stop_basket_id = Some(basket_id.to_string()),
+                    false => bracket.target_basket_id = Some(basket_id.to_string()),
+                }
+            }
+        }
+    }
+
+    /// Submits two orders as a Rithmic OCO order, when either order fills the other is cancelled by the exchange.
+    pub async fn submit_oco_order(&self, stream_name: StreamName, legs: Vec<(Order, CommonRithmicOrderDetails)>) -> Result<(), Vec<OrderUpdateEvent>> {
+        let reject_all = |reason: String| -> Vec<OrderUpdateEvent> {
+            legs.iter().map(|(order, _)| Self::reject_order(order, reason.clone())).collect()
+        };
+        let account_id = match legs.first() {
+            Some((order, _)) => order.account.account_id.clone(),
+            None => return Ok(())
+        };
+
+        let mut req = RequestOcoOrder {
+            template_id: 328,
+            user_msg: vec![],
+            user_tag: vec![],
+            window_name: vec![],
+            fcm_id: self.fcm_id.clone(),
+            ib_id: self.ib_id.clone(),
+            account_id: Some(account_id.clone()),
+            symbol: vec![],
+            exchange: vec![],
+            quantity: vec![],
+            price: vec![],
+            trigger_price: vec![],
+            transaction_type: vec![],
+            duration: vec![],
+            price_type: vec![],
+            trade_route: vec![],
+            manual_or_auto: vec![],
+            trailing_stop: vec![],
+            trail_by_ticks: vec![],
+            trail_by_price_id: vec![],
+            cancel_at_ssboe: None,
+            cancel_at_usecs: None,
+            cancel_after_secs: None,
+        };
+
+        for (order, details) in &legs {
+            let duration = match order.time_in_force {
+                TimeInForce::IOC => request_oco_order::Duration::Ioc,
+                TimeInForce::FOK => request_oco_order::Duration::Fok,
+                TimeInForce::Day => request_oco_order::Duration::Day,
+                TimeInForce::GTC | TimeInForce::Time(_) => request_oco_order::Duration::Gtc,
+            };
+            let price_type = match order.order_type {
+                OrderType::Limit => request_oco_order::PriceType::Limit,
+                OrderType::Market => request_oco_order::PriceType::Market,
+                OrderType::StopLimit => request_oco_order::PriceType::StopLimit,
+                OrderType::StopMarket => request_oco_order::PriceType::StopMarket,
+                _ => return Err(reject_all(format!("Order type not supported in Rithmic OCO orders: {:?}", order.order_type)))
+            };
+            // repeated fields are matched by index, so legs without a price still need a value
+            let price = match order.limit_price.map(|price| price.to_f64()) {
+                None => 0.0,
+                Some(Some(price)) => price,
+                Some(None) => return Err(reject_all(format!("Failed to parse limit price: {:?}", order.limit_price)))
+            };
+            let trigger_price = match order.trigger_price.map(|price| price.to_f64()) {
+                None => 0.0,
+                Some(Some(price)) => price,
+                Some(None) => return Err(reject_all(format!("Failed to parse trigger price: {:?}", order.trigger_price)))
+            };
+
+            // user_msg is shared by both legs, the symbol of the first leg is used to map the response
+            if req.user_msg.is_empty() {
+                req.user_msg = vec![stream_name.to_string(), account_id.clone(), order.tag.clone(), order.symbol_name.clone(), details.symbol_code.clone()];
+            }
+            req.user_tag.push(order.id.clone());
+            req.window_name.push(stream_name.to_string());
+            req.symbol.push(details.symbol_code.clone());
+            req.exchange.push(details.exchange.to_string());
+            req.quantity.push(details.quantity);
+            req.price.push(price);
+            req.trigger_price.push(trigger_price);
+            req.transaction_type.push(details.transaction_type.into());
+            req.duration.push(duration.into());
+            req.price_type.push(price_type.into());
+            req.trade_route.push(details.route.clone());
+            req.manual_or_auto.push(request_oco_order::OrderPlacement::Auto.into());
+
+            if let Some(account_map) = self.last_tag.get(&account_id) {
+                account_map.insert(details.symbol_code.clone(), order.tag.clone());
+            }
+        }
+
+        self.send_message(&SysInfraType::OrderPlant, req).await;
+        Ok(())
+    }
+
+    /// Links orders which have already been placed, when any of the orders fills the others are cancelled by the exchange.
+    /// The orders must have been accepted by Rithmic before they can be linked, so this waits for their basket ids.
+    pub async fn link_orders(&self, account_id: AccountId, order_ids: Vec<OrderId>) -> Result<(), FundForgeError> {
+        let wait_for_baskets = async {
+            loop {
+                if let Some(account_map) = self.id_to_basket_id_map.get(&account_id) {
+                    let basket_ids: Vec<String> = order_ids.iter()
+                        .filter_map(|id| account_map.get(id).map(|basket_id| basket_id.value().clone()))
+                        .collect();
+                    if basket_ids.len() == order_ids.len() {
+                        return basket_ids;
+                    }
+                }
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        };
+
+        let basket_ids = match timeout(Duration::from_secs(5), wait_for_baskets).await {
+            Ok(basket_ids) => basket_ids,
+            Err(_) => return Err(FundForgeError::ServerErrorDebug(format!("Timed out waiting for orders to be accepted before linking: {:?}", order_ids)))
+        };
+
+        let req = RequestLinkOrders {
+            template_id: 344,
+            user_msg: vec!["Link Orders".to_string()],
+            fcm_id: basket_ids.iter().filter_map(|_| self.fcm_id.clone()).collect(),
+            ib_id: basket_ids.iter().filter_map(|_| self.ib_id.clone()).collect(),
+            account_id: basket_ids.iter().map(|_| account_id.clone()).collect(),
+            basket_id: basket_ids,
+        };
+        self.send_message(&SysInfraType::OrderPlant, req).await;
+        Ok(())
+    }
+
     pub(crate) async fn init_rithmic_apis(options: ServerLaunchOptions) {
         let options = options;
         if options.disable_rithmic_server != 0 {
@@ -9,8 +9,8 @@ use ff_standard_lib::messages::data_server_messaging::{DataServerResponse, FundF
 use ff_standard_lib::product_maps::rithmic::maps::{find_base_symbol, get_available_rithmic_symbol_names, get_exchange_by_symbol_name, get_futures_commissions_info, get_futures_symbol_info};
 use crate::server_features::server_side_brokerage::BrokerApiResponse;
 use ff_standard_lib::standardized_types::accounts::{Account, AccountId};
-use ff_standard_lib::standardized_types::enums::StrategyMode;
-use ff_standard_lib::standardized_types::orders::{Order, OrderId, OrderUpdateEvent, OrderUpdateType};
+use ff_standard_lib::standardized_types::enums::{OrderSide, StrategyMode};
+use ff_standard_lib::standardized_types::orders::{Order, OrderGroupId, OrderGroupType, OrderId, OrderType, OrderUpdateEvent, OrderUpdateType};
 use ff_standard_lib::standardized_types::subscriptions::SymbolName;
 use ff_standard_lib::StreamName;
 use crate::request_handlers::RESPONSE_SENDERS;
@@ -179,8 +179,13 @@ impl BrokerApiResponse for RithmicBrokerageClient {
                 details.quantity += additional_volume;
             }
         }
-        self.submit_market_order(stream_name, order, details).await;
-        Ok(())
+        match order.brackets.clone() {
+            Some(brackets) if !brackets.is_empty() => self.submit_bracket_order(stream_name, order, details, brackets).await,
+            _ => {
+                self.submit_market_order(stream_name, order, details).await;
+                Ok(())
+            }
+        }
     }
 
     async fn live_enter_short(&self, stream_name: StreamName, mode: StrategyMode, order: Order) -> Result<(), OrderUpdateEvent> {
@@ -210,8 +215,13 @@ impl BrokerApiResponse for RithmicBrokerageClient {
                 details.quantity += additional_volume;
             }
         }
-        self.submit_market_order(stream_name, order, details).await;
-        Ok(())
+        match order.brackets.clone() {
+            Some(brackets) if !brackets.is_empty() => self.submit_bracket_order(stream_name, order, details, brackets).await,
+            _ => {
+                self.submit_market_order(stream_name, order, details).await;
+                Ok(())
+            }
+        }
     }
 
     async fn live_exit_short(&self, stream_name: StreamName, mode: StrategyMode, order: Order) -> Result<(), OrderUpdateEvent> {
@@ -272,6 +282,7 @@ impl BrokerApiResponse for RithmicBrokerageClient {
                 // For short exits, quantity should be positive
                 details.quantity = details.quantity.abs();
 
+                self.resize_live_bracket(stream_name, &order.account.account_id, &details.symbol_code, OrderSide::Buy, volume.abs() - details.quantity).await;
                 self.submit_market_order(stream_name, order, details).await;
                 Ok(())
             } else {
@@ -336,6 +347,7 @@ impl BrokerApiResponse for RithmicBrokerageClient {
                         }
                     }
                 }
+                self.resize_live_bracket(stream_name, &order.account.account_id, &details.symbol_code, OrderSide::Sell, volume.abs() - details.quantity.abs()).await;
                 self.submit_market_order(stream_name, order, details).await;
                 Ok(())
             } else {
@@ -354,6 +366,63 @@ impl BrokerApiResponse for RithmicBrokerageClient {
         self.submit_order(stream_name, order, details).await
     }
 
+    async fn live_order_group(&self, stream_name: StreamName, mode: StrategyMode, group_id: OrderGroupId, group_type: OrderGroupType, orders: Vec<Order>) -> Result<(), Vec<OrderUpdateEvent>> {
+        let reject_all = |orders: &Vec<Order>, reason: String| -> Vec<OrderUpdateEvent> {
+            orders.iter().map(|order| OrderUpdateEvent::OrderRejected {
+                account: order.account.clone(),
+                symbol_name: order.symbol_name.clone(),
+                symbol_code: order.symbol_code.clone(),
+                order_id: order.id.clone(),
+                reason: reason.clone(),
+                tag: order.tag.clone(),
+                time: Utc::now().to_string(),
+            }).collect()
+        };
+
+        if group_type != OrderGroupType::OneCancelsOther {
+            return Err(reject_all(&orders, format!("Order groups not supported natively with Rithmic: {}", group_type)));
+        }
+
+        let mut legs = Vec::with_capacity(orders.len());
+        for order in &orders {
+            match self.rithmic_order_details(mode, stream_name, order).await {
+                Ok(details) => legs.push((order.clone(), details)),
+                Err(e) => {
+                    let reason = match e {
+                        OrderUpdateEvent::OrderRejected { reason, .. } => reason,
+                        _ => "Invalid order".to_string()
+                    };
+                    return Err(reject_all(&orders, format!("Order Group Rejected: {}: {}", group_id, reason)));
+                }
+            }
+        }
+
+        // Rithmic OCO orders have exactly 2 legs, larger groups are placed as individual orders and then linked.
+        let native_oco = legs.len() == 2 && legs.iter().all(|(order, _)| matches!(order.order_type, OrderType::Limit | OrderType::Market | OrderType::StopLimit | OrderType::StopMarket));
+        if native_oco {
+            return self.submit_oco_order(stream_name, legs).await;
+        }
+
+        let account_id = match orders.first() {
+            Some(order) => order.account.account_id.clone(),
+            None => return Ok(())
+        };
+        let order_ids: Vec<OrderId> = orders.iter().map(|order| order.id.clone()).collect();
+        let mut rejected = vec![];
+        for (order, details) in legs {
+            if let Err(e) = self.submit_order(stream_name, order, details).await {
+                rejected.push(e);
+            }
+        }
+        if !rejected.is_empty() {
+            return Err(rejected);
+        }
+        if let Err(e) = self.link_orders(account_id, order_ids).await {
+            eprintln!("Rithmic failed to link order group {}: {}", group_id, e);
+        }
+        Ok(())
+    }
+
     async fn cancel_orders_on_account(&self, account: Account) {
         const PLANT: SysInfraType = SysInfraType::OrderPlant;
         //Cancel All Orders Request 346
@@ -6,6 +6,8 @@ use dashmap::DashMap;
 #[allow(unused_imports)]
 use crate::rithmic_api::client_base::rithmic_proto_objects::rti::{AccountListUpdates, AccountPnLPositionUpdate, AccountRmsUpdates, BestBidOffer, BracketUpdates, DepthByOrder, DepthByOrderEndEvent, EndOfDayPrices, ExchangeOrderNotification, FrontMonthContractUpdate, IndicatorPrices, InstrumentPnLPositionUpdate, LastTrade, MarketMode, OpenInterest, OrderBook, OrderPriceLimits, QuoteStatistics, RequestAccountList, RequestAccountRmsInfo, RequestHeartbeat, RequestLoginInfo, RequestMarketDataUpdate, RequestPnLPositionSnapshot, RequestPnLPositionUpdates, RequestProductCodes, RequestProductRmsInfo, RequestReferenceData, RequestTickBarUpdate, RequestTimeBarUpdate, RequestVolumeProfileMinuteBars, ResponseAcceptAgreement, ResponseAccountList, ResponseAccountRmsInfo, ResponseAccountRmsUpdates, ResponseAuxilliaryReferenceData, ResponseBracketOrder, ResponseCancelAllOrders, ResponseCancelOrder, ResponseDepthByOrderSnapshot, ResponseDepthByOrderUpdates, ResponseEasyToBorrowList, ResponseExitPosition, ResponseFrontMonthContract, ResponseGetInstrumentByUnderlying, ResponseGetInstrumentByUnderlyingKeys, ResponseGetVolumeAtPrice, ResponseGiveTickSizeTypeTable, ResponseHeartbeat, ResponseLinkOrders, ResponseListAcceptedAgreements, ResponseListExchangePermissions, ResponseListUnacceptedAgreements, ResponseLogin, ResponseLoginInfo, ResponseLogout, ResponseMarketDataUpdate, ResponseMarketDataUpdateByUnderlying, ResponseModifyOrder, ResponseModifyOrderReferenceData, ResponseNewOrder, ResponseOcoOrder, ResponseOrderSessionConfig, ResponsePnLPositionSnapshot, ResponsePnLPositionUpdates, ResponseProductCodes, ResponseProductRmsInfo, ResponseReferenceData, ResponseReplayExecutions, ResponseResumeBars, ResponseRithmicSystemInfo, ResponseSearchSymbols, ResponseSetRithmicMrktDataSelfCertStatus, ResponseShowAgreement, ResponseShowBracketStops, ResponseShowBrackets, ResponseShowOrderHistory, ResponseShowOrderHistoryDates, ResponseShowOrderHistoryDetail, ResponseShowOrderHistorySummary, ResponseShowOrders, ResponseSubscribeForOrderUpdates, ResponseSubscribeToBracketUpdates, ResponseTickBarReplay, ResponseTickBarUpdate, ResponseTimeBarReplay, ResponseTimeBarUpdate, ResponseTradeRoutes, ResponseUpdateStopBracketLevel, ResponseUpdateTargetBracketLevel, ResponseVolumeProfileMinuteBars, RithmicOrderNotification, SymbolMarginRate, TickBar, TimeBar, TradeRoute, TradeStatistics, UpdateEasyToBorrowList};
 use crate::rithmic_api::client_base::rithmic_proto_objects::rti::Reject;
+use crate::rithmic_api::client_base::rithmic_proto_objects::rti::rithmic_order_notification;
+use crate::rithmic_api::client_base::rithmic_proto_objects::rti::rithmic_order_notification::NotifyType;
 use lazy_static::lazy_static;
 use prost::Message as ProstMessage;
 use rust_decimal::Decimal;
@@ -17,7 +19,7 @@ use ff_standard_lib::standardized_types::broker_enum::Brokerage;
 use ff_standard_lib::standardized_types::enums::{FuturesExchange, OrderSide};
 use ff_standard_lib::standardized_types::accounts::Currency;
 use ff_standard_lib::standardized_types::new_types::{Price, Volume};
-use ff_standard_lib::standardized_types::orders::{OrderId, OrderState, OrderUpdateEvent, OrderUpdateType};
+use ff_standard_lib::standardized_types::orders::{Order, OrderId, OrderState, OrderType, OrderUpdateEvent, OrderUpdateType, TimeInForce};
 use ff_standard_lib::StreamName;
 use crate::request_handlers::RESPONSE_SENDERS;
 use crate::rithmic_api::api_client::RithmicBrokerageClient;
@@ -27,6 +29,10 @@ use crate::rithmic_api::plant_handlers::handler_loop::send_updates;
 use ff_standard_lib::product_maps::rithmic::maps::find_base_symbol;
 
 type BasketId = String;
+
+/// The first `user_msg` of the show orders and pnl snapshot requests sent by `RithmicBrokerageClient::account_snapshot()`, the second is the account id.
+pub const ACCOUNT_SNAPSHOT: &str = "Account Snapshot";
+
 lazy_static! {
     pub static ref BASKET_ID_TO_ID_MAP: DashMap<Brokerage, DashMap<BasketId , OrderId>> = DashMap::new();
     pub static ref BASKET_TO_STREAM_NAME_MAP: DashMap<Brokerage, DashMap<BasketId , StreamName>> = DashMap::new();
@@ -283,6 +289,15 @@ pub async fn match_order_plant_id(
             if let Ok(msg) = ResponseShowOrders::decode(&message_buf[..]) {
                 // Show Orders Response
                 // From Server
+                // the open orders are sent as snapshot notifications before the response
+                if msg.user_msg.first().map(|user_msg| user_msg.as_str()) == Some(ACCOUNT_SNAPSHOT) {
+                    if let (Some(account_id), false) = (msg.user_msg.get(1), msg.rp_code.is_empty()) {
+                        if let Some((_, sender)) = client.snapshot_senders.remove(&(account_id.clone(), SysInfraType::OrderPlant)) {
+                            let _ = sender.send(());
+                        }
+                    }
+                    return;
+                }
                 println!("Show Orders Response (Template ID: 321) from Server: {:?}", msg);
             }
         },
@@ -311,28 +326,95 @@ pub async fn match_order_plant_id(
             if let Ok(msg) = ResponseOcoOrder::decode(&message_buf[..]) {
                 // OCO Order Response
                 // From Server
-                println!("OCO Order Response (Template ID: 329) from Server: {:?}", msg);
+                //println!("OCO Order Response (Template ID: 329) from Server: {:?}", msg);
+                // Each leg is mapped the same as a new order, the legs are matched to their basket ids by index.
+                let stream_name = match msg.user_msg.get(0) {
+                    None => return,
+                    Some(stream_name) => u16::from_str(&stream_name).unwrap_or_default()
+                };
+                let account_id = match msg.user_msg.get(1) {
+                    None => return,
+                    Some(id) => id
+                };
+                for (order_id, basket_id) in msg.user_tag.iter().zip(msg.basket_id.iter()) {
+                    BASKET_ID_TO_ID_MAP.entry(client.brokerage.clone()).or_insert(DashMap::new()).insert(basket_id.clone(), order_id.clone());
+                    BASKET_TO_STREAM_NAME_MAP.entry(client.brokerage.clone()).or_insert(DashMap::new()).insert(basket_id.clone(), stream_name);
+                    ID_TO_STREAM_NAME_MAP.entry(client.brokerage.clone()).or_insert(DashMap::new()).insert(order_id.clone(), stream_name);
+
+                    if let Some(tag) = msg.user_msg.get(2) {
+                        ID_TO_TAG.entry(client.brokerage.clone()).or_insert(DashMap::new()).insert(order_id.clone(), tag.clone());
+                    }
+
+                    client.id_to_basket_id_map.entry(account_id.clone()).or_insert(DashMap::new()).insert(order_id.clone(), basket_id.clone());
+
+                    if let Some(account_map) = client.open_orders.get_mut(account_id) {
+                        if let Some(mut open_order) = account_map.get_mut(order_id) {
+                            open_order.state = OrderState::Accepted;
+                        }
+                    }
+                }
             }
         },
         331 => {
             if let Ok(msg) = ResponseBracketOrder::decode(&message_buf[..]) {
                 // Bracket Order Response
                 // From Server
-                println!("Bracket Order Response (Template ID: 331) from Server: {:?}", msg);
+                //println!("Bracket Order Response (Template ID: 331) from Server: {:?}", msg);
+                // The entry order is mapped the same as a new order, the target and stop are reported as linked baskets in the exchange order notifications.
+                if let Some(basket_id) = msg.basket_id {
+                    let order_id = match msg.user_tag {
+                        None => return,
+                        Some(order_id) => order_id
+                    };
+
+                    BASKET_ID_TO_ID_MAP.entry(client.brokerage.clone()).or_insert(DashMap::new()).insert(basket_id.clone(), order_id.clone());
+
+                    let stream_name = match msg.user_msg.get(0) {
+                        None => return,
+                        Some(stream_name) => u16::from_str(&stream_name).unwrap_or_default()
+                    };
+                    BASKET_TO_STREAM_NAME_MAP.entry(client.brokerage.clone()).or_insert(DashMap::new()).insert(basket_id.clone(), stream_name);
+                    ID_TO_STREAM_NAME_MAP.entry(client.brokerage.clone()).or_insert(DashMap::new()).insert(order_id.clone(), stream_name);
+
+                    if let Some(tag) = msg.user_msg.get(2) {
+                        ID_TO_TAG.entry(client.brokerage.clone()).or_insert(DashMap::new()).insert(order_id.clone(), tag.clone());
+                    }
+
+                    let account_id = match msg.user_msg.get(1) {
+                        None => return,
+                        Some(id) => id
+                    };
+                    client.id_to_basket_id_map.entry(account_id.clone()).or_insert(DashMap::new()).insert(order_id.clone(), basket_id.clone());
+
+                    if let (Some(account_map), Some(symbol_code)) = (client.open_orders.get_mut(account_id), msg.user_msg.get(4)) {
+                        if let Some(mut open_order) = account_map.get_mut(&order_id) {
+                            open_order.state = OrderState::Accepted;
+                            open_order.symbol_code = symbol_code.clone();
+                        }
+                    }
+
+                    if let (Some(account_map), Some(symbol_code)) = (client.live_brackets.get(account_id), msg.user_msg.get(4)) {
+                        if let Some(mut bracket) = account_map.get_mut(symbol_code) {
+                            if bracket.order_id == order_id {
+                                bracket.basket_id = Some(basket_id.clone());
+                            }
+                        }
+                    }
+                }
             }
         },
         333 => {
             if let Ok(msg) = ResponseUpdateTargetBracketLevel::decode(&message_buf[..]) {
                 // Update Target Bracket Level Response
                 // From Server
-                println!("Update Target Bracket Level Response (Template ID: 333) from Server: {:?}", msg);
+                bracket_level_rejected(&client, &msg.user_msg, &msg.rp_code, "Target").await;
             }
         },
         335 => {
             if let Ok(msg) = ResponseUpdateStopBracketLevel::decode(&message_buf[..]) {
                 // Update Stop Bracket Level Response
                 // From Server
-                println!("Update Stop Bracket Level Response (Template ID: 335) from Server: {:?}", msg);
+                bracket_level_rejected(&client, &msg.user_msg, &msg.rp_code, "Stop").await;
             }
         },
         337 => {
@@ -367,7 +449,12 @@ pub async fn match_order_plant_id(
             if let Ok(msg) = ResponseLinkOrders::decode(&message_buf[..]) {
                 // Link Orders Response
                 // From Server
-                println!("Link Orders Response (Template ID: 345) from Server: {:?}", msg);
+                // the linked orders are already working, we only need to know if linking failed.
+                if let Some(code) = msg.rp_code.get(0) {
+                    if code != "0" {
+                        eprintln!("Rithmic Link Orders Failed (Template ID: 345): {:?}", msg.rp_code);
+                    }
+                }
             }
         },
         347 => {
@@ -430,6 +517,19 @@ pub async fn match_order_plant_id(
             ssboe: Some(1729085413), usecs: Some(477767) }
             */
             if let Ok(msg) = RithmicOrderNotification::decode(&message_buf[..]) {
+                if msg.is_snapshot == Some(true) {
+                    record_snapshot_order(&client, msg);
+                    return;
+                }
+                // the stop and target orders of a bracket are linked to the entry order by its basket id
+                if let (Some(account_id), Some(symbol_code), Some(basket_id), Some(parent_basket_id), Some(_)) =
+                    (&msg.account_id, &msg.symbol, &msg.basket_id, &msg.original_basket_id, msg.bracket_type) {
+                    if basket_id != parent_basket_id {
+                        let is_stop = msg.price_type == Some(rithmic_order_notification::PriceType::StopMarket as i32)
+                            || msg.price_type == Some(rithmic_order_notification::PriceType::StopLimit as i32);
+                        client.record_bracket_order(account_id, symbol_code, parent_basket_id, basket_id, is_stop);
+                    }
+                }
                 //todo I think these are only for rithmic web or r trader orders
                 //println!("Rithmic Order Notification (Template ID: 351) from Server: {:?}", msg);
                /* if let (Some(basket_id), Some(ssboe), Some(usecs), Some(account_id), Some(notify_type), Some(order_id)) =
@@ -840,6 +940,104 @@ pub async fn match_order_plant_id(
     }
 }
 
+/// Records a working order sent in reply to the show orders request of `RithmicBrokerageClient::account_snapshot()`, later notifications for the same basket replace it.
+fn record_snapshot_order(client: &Arc<RithmicBrokerageClient>, msg: RithmicOrderNotification) {
+    let (Some(account_id), Some(basket_id), Some(symbol_code)) = (msg.account_id, msg.basket_id, msg.symbol) else {
+        return
+    };
+    let orders = match client.snapshot_orders.get(&account_id) {
+        Some(orders) => orders,
+        None => return
+    };
+    if msg.notify_type == Some(NotifyType::Complete as i32) {
+        orders.remove(&basket_id);
+        return;
+    }
+    let symbol_name = match find_base_symbol(&symbol_code) {
+        Some(symbol_name) => symbol_name,
+        None => return
+    };
+    let side = match msg.transaction_type {
+        Some(1) => OrderSide::Buy,
+        Some(2) | Some(3) => OrderSide::Sell,
+        _ => return
+    };
+    let order_type = match msg.price_type {
+        Some(1) => OrderType::Limit,
+        Some(2) => OrderType::Market,
+        Some(3) => OrderType::StopLimit,
+        Some(4) => OrderType::StopMarket,
+        _ => return
+    };
+    let time_in_force = match msg.duration {
+        Some(2) => TimeInForce::GTC,
+        Some(3) => TimeInForce::IOC,
+        Some(4) => TimeInForce::FOK,
+        _ => TimeInForce::Day
+    };
+    let quantity_open = match msg.total_unfilled_size.or(msg.quantity).and_then(Volume::from_i32) {
+        Some(quantity) if quantity > Volume::ZERO => quantity,
+        _ => return
+    };
+    // orders placed by fund forge carry the order id in the user tag
+    let order_id = msg.user_tag.unwrap_or_else(|| basket_id.clone());
+    let tag = ID_TO_TAG.get(&client.brokerage)
+        .and_then(|tags| tags.get(&order_id).map(|tag| tag.value().clone()))
+        .unwrap_or_else(|| "External Order".to_string());
+    let time = match (msg.ssboe, msg.usecs) {
+        (Some(ssboe), Some(usecs)) => create_datetime(ssboe as i64, usecs as i64).to_string(),
+        _ => Utc::now().to_string()
+    };
+    let (limit_price, trigger_price) = match order_type {
+        OrderType::Limit => (msg.price.and_then(Price::from_f64), None),
+        OrderType::StopLimit => (msg.price.and_then(Price::from_f64), msg.trigger_price.and_then(Price::from_f64)),
+        OrderType::StopMarket => (None, msg.trigger_price.or(msg.price).and_then(Price::from_f64)),
+        _ => (None, None)
+    };
+    let order = Order {
+        symbol_name,
+        symbol_code,
+        account: Account::new(client.brokerage, account_id),
+        quantity_open,
+        quantity_filled: msg.total_fill_size.and_then(Volume::from_i32).unwrap_or_default(),
+        average_fill_price: msg.avg_fill_price.and_then(Price::from_f64),
+        limit_price,
+        trigger_price,
+        side,
+        order_type,
+        time_in_force,
+        tag,
+        id: order_id,
+        time_created_utc: time,
+        time_filled_utc: None,
+        state: OrderState::Accepted,
+        fees: Price::ZERO,
+        value: Price::ZERO,
+        exchange: msg.exchange,
+        brackets: None,
+    };
+    orders.insert(basket_id, order);
+}
+
+/// Reports a failed bracket level update to the strategy which placed the bracket, the user message holds the stream name, account id and entry order id.
+async fn bracket_level_rejected(client: &Arc<RithmicBrokerageClient>, user_msg: &[String], rp_code: &[String], level: &str) {
+    let code = match rp_code.first() {
+        Some(code) if code != "0" => code,
+        _ => return
+    };
+    let (Some(account_id), Some(order_id)) = (user_msg.get(1), user_msg.get(2)) else {
+        return;
+    };
+    let time = Utc::now().to_string();
+    let event = OrderUpdateEvent::OrderUpdateRejected {
+        account: Account::new(client.brokerage, account_id.clone()),
+        order_id: order_id.clone(),
+        reason: format!("Rithmic Update {} Bracket Level Failed: {}", level, rp_code.get(1).unwrap_or(code)),
+        time: time.clone(),
+    };
+    send_order_update(client.brokerage, order_id, event, time).await;
+}
+
 async fn send_order_update(brokerage: Brokerage, order_id: &OrderId, event: OrderUpdateEvent, time: String) {
     if let Some(broker_map) = ID_TO_STREAM_NAME_MAP.get(&brokerage) {
         if let Some(stream_name) = broker_map.value().get(order_id) {
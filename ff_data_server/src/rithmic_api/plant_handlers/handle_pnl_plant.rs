@@ -13,7 +13,8 @@ use rust_decimal::{Decimal};
 use rust_decimal::prelude::FromPrimitive;
 use rust_decimal_macros::dec;
 use ff_standard_lib::messages::data_server_messaging::DataServerResponse;
-use ff_standard_lib::standardized_types::accounts::Account;
+use ff_standard_lib::standardized_types::accounts::{Account, BrokerPosition};
+use ff_standard_lib::product_maps::rithmic::maps::find_base_symbol;
 #[allow(unused_imports)]
 use ff_standard_lib::standardized_types::broker_enum::Brokerage;
 use ff_standard_lib::standardized_types::enums::PositionSide;
@@ -21,6 +22,7 @@ use ff_standard_lib::standardized_types::new_types::Volume;
 use ff_standard_lib::standardized_types::subscriptions::{SymbolCode};
 use crate::rithmic_api::api_client::RithmicBrokerageClient;
 use crate::rithmic_api::plant_handlers::create_datetime;
+use crate::rithmic_api::plant_handlers::handle_order_plant::ACCOUNT_SNAPSHOT;
 use crate::rithmic_api::plant_handlers::handler_loop::send_updates;
 use crate::server_features::database::hybrid_storage::MULTIBAR;
 
@@ -104,7 +106,14 @@ pub async fn match_pnl_plant_id(
                 // PnL Position Snapshot Response
                 // From Server
                 //println!("PnL Position Snapshot Response (Template ID: 403) from Server: {:?}", msg);
-
+                // the positions are sent as snapshot updates before the response
+                if msg.user_msg.first().map(|user_msg| user_msg.as_str()) == Some(ACCOUNT_SNAPSHOT) {
+                    if let (Some(account_id), false) = (msg.user_msg.get(1), msg.rp_code.is_empty()) {
+                        if let Some((_, sender)) = client.snapshot_senders.remove(&(account_id.clone(), PLANT)) {
+                            let _ = sender.send(());
+                        }
+                    }
+                }
             }
         },
         450 => {
@@ -129,6 +138,7 @@ pub async fn match_pnl_plant_id(
 
                 match msg.is_snapshot {
                     Some(true) => {
+                        record_snapshot_position(&client, &msg);
                         match msg.closed_position_pnl {
                             None => {},
                             Some(closed_position_pnl) => {
@@ -356,6 +366,36 @@ pub async fn match_pnl_plant_id(
     }
 }
 
+/// Records an open position sent in reply to the pnl snapshot request of `RithmicBrokerageClient::account_snapshot()`.
+fn record_snapshot_position(client: &Arc<RithmicBrokerageClient>, msg: &InstrumentPnLPositionUpdate) {
+    let (Some(account_id), Some(symbol_code), Some(net_quantity)) = (&msg.account_id, &msg.symbol, msg.net_quantity) else {
+        return
+    };
+    let positions = match client.snapshot_positions.get(account_id) {
+        Some(positions) => positions,
+        None => return
+    };
+    if net_quantity == 0 {
+        positions.remove(symbol_code);
+        return;
+    }
+    let symbol_name = match msg.product_code.clone().or_else(|| find_base_symbol(symbol_code)) {
+        Some(symbol_name) => symbol_name,
+        None => return
+    };
+    let side = match net_quantity > 0 {
+        true => PositionSide::Long,
+        false => PositionSide::Short
+    };
+    positions.insert(symbol_code.clone(), BrokerPosition {
+        symbol_name,
+        symbol_code: symbol_code.clone(),
+        side,
+        quantity: Volume::from(net_quantity.abs()),
+        average_price: msg.avg_open_fill_price.and_then(Decimal::from_f64).unwrap_or_default(),
+    });
+}
+
 #[allow(dead_code, unused)]
 fn update_position(
     account_id: String,
@@ -16,14 +16,24 @@ use ff_standard_lib::messages::data_server_messaging::DataServerResponse;
 #[allow(unused_imports)]
 use ff_standard_lib::standardized_types::broker_enum::Brokerage;
 use ff_standard_lib::standardized_types::base_data::base_data_enum::BaseDataEnum;
+use ff_standard_lib::standardized_types::base_data::market_by_order::{MarketByOrder, MarketByOrderAction};
+use ff_standard_lib::standardized_types::base_data::order_book::OrderBook as OrderBookData;
 use ff_standard_lib::standardized_types::base_data::quote::Quote;
 use ff_standard_lib::standardized_types::base_data::tick::{Aggressor, Tick};
-use ff_standard_lib::standardized_types::enums::{FuturesExchange, MarketType};
-use ff_standard_lib::standardized_types::subscriptions::{Symbol};
+use ff_standard_lib::standardized_types::enums::{FuturesExchange, MarketType, OrderSide};
+use ff_standard_lib::standardized_types::subscriptions::{Symbol, SymbolName};
 use ff_standard_lib::standardized_types::symbol_info::FrontMonthInfo;
 use ff_standard_lib::standardized_types::books::BookLevel;
+use ff_standard_lib::standardized_types::new_types::{Price, Volume};
+use std::collections::BTreeMap;
+use crate::rithmic_api::client_base::rithmic_proto_objects::rti::order_book::UpdateType;
+use crate::rithmic_api::client_base::rithmic_proto_objects::rti::depth_by_order::{TransactionType as DepthByOrderTransactionType, UpdateType as DepthByOrderUpdateType};
+use crate::rithmic_api::client_base::rithmic_proto_objects::rti::response_depth_by_order_snapshot::TransactionType as SnapshotTransactionType;
+use crate::rithmic_api::client_base::rithmic_proto_objects::rti::RequestDepthByOrderUpdates;
+use dashmap::DashMap;
 use ff_standard_lib::StreamName;
 use crate::rithmic_api::api_client::RithmicBrokerageClient;
+use crate::server_features::database::hybrid_storage::DATA_STORAGE;
 use crate::rithmic_api::client_base::rithmic_proto_objects::rti::request_login::SysInfraType;
 
 
@@ -183,7 +193,8 @@ pub async fn match_ticker_plant_id(
             if let Ok(msg) = ResponseDepthByOrderSnapshot::decode(&message_buf[..]) {
                 // Depth By Order Snapshot Response
                 // From Server
-                println!("Depth By Order Snapshot Response (Template ID: 116) from Server: {:?}", msg);
+                //println!("Depth By Order Snapshot Response (Template ID: 116) from Server: {:?}", msg);
+                handle_depth_by_order_snapshot(client.clone(), msg).await;
             }
         },
         118 => {
@@ -253,7 +264,8 @@ pub async fn match_ticker_plant_id(
             if let Ok(msg) = OrderBook::decode(&message_buf[..]) {
                 // Order Book
                 // From Server
-                println!("Order Book (Template ID: 156) from Server: {:?}", msg);
+                //println!("Order Book (Template ID: 156) from Server: {:?}", msg);
+                handle_order_book(client.clone(), msg).await;
             }
         },
         157 => {
@@ -281,7 +293,8 @@ pub async fn match_ticker_plant_id(
             if let Ok(msg) = DepthByOrder::decode(&message_buf[..]) {
                 // Depth By Order
                 // From Server
-                println!("Depth By Order (Template ID: 160) from Server: {:?}", msg);
+                //println!("Depth By Order (Template ID: 160) from Server: {:?}", msg);
+                handle_depth_by_order(client.clone(), msg).await;
             }
         },
         161 => {
@@ -516,4 +529,416 @@ fn deserialize_quote_time(msg: &BestBidOffer) -> DateTime<Utc> {
             //eprintln!("Warning: Using current time due to invalid timestamp in quote");
             Utc::now()
         })
-}
\ No newline at end of file
+}
+/// The number of price levels on each side of the market depth we stream as an `OrderBook`.
+const ORDER_BOOK_DEPTH: usize = 10;
+/// The number of streamed order books to buffer before saving them to the data storage, so they can be replayed in backtests.
+const ORDER_BOOK_SAVE_BATCH: usize = 1000;
+
+async fn handle_order_book(client: Arc<RithmicBrokerageClient>, msg: OrderBook) {
+    let time = deserialize_order_book_time(&msg);
+
+    let symbol = match msg.symbol {
+        None => return,
+        Some(ref symbol) => symbol.clone()
+    };
+
+    let publish = {
+        let mut bids = client.depth_bids.entry(symbol.clone()).or_default();
+        let mut asks = client.depth_asks.entry(symbol.clone()).or_default();
+        apply_order_book_update(&mut bids, &mut asks, &msg)
+    };
+
+    if !publish {
+        return;
+    }
+
+    let exchange = match msg.exchange.as_deref().and_then(|e| FuturesExchange::from_string(e).ok()) {
+        Some(ex) => ex,
+        None => {
+            eprintln!("Error deserializing Exchange for symbol {}", symbol);
+            return;
+        }
+    };
+
+    let mut remove_broadcaster = false;
+    if let Some(broadcaster) = client.order_book_feed_broadcasters.get(&symbol) {
+        let symbol_obj = Symbol::new(symbol.clone(), client.data_vendor, MarketType::Futures(exchange));
+        let book = match (client.depth_bids.get(&symbol), client.depth_asks.get(&symbol)) {
+            (Some(bids), Some(asks)) => OrderBookData::from_price_levels(symbol_obj, &bids, &asks, ORDER_BOOK_DEPTH, time.to_string()),
+            _ => return,
+        };
+
+        let data = BaseDataEnum::OrderBook(book);
+        let batch_full = {
+            let mut buffer = client.order_book_buffer.entry(symbol.clone()).or_default();
+            buffer.push(data.clone());
+            buffer.len() >= ORDER_BOOK_SAVE_BATCH
+        };
+        if batch_full {
+            save_depth_buffer(&client.order_book_buffer, &symbol);
+        }
+
+        if let Err(_e) = broadcaster.send(data) {
+            if broadcaster.receiver_count() == 0 {
+                remove_broadcaster = true;
+            }
+        }
+    }
+
+    if remove_broadcaster {
+        if let Some((_, broadcaster)) = client.order_book_feed_broadcasters.remove(&symbol) {
+            if broadcaster.receiver_count() == 0 {
+                client.depth_bids.remove(&symbol);
+                client.depth_asks.remove(&symbol);
+                save_depth_buffer(&client.order_book_buffer, &symbol);
+                let req = RequestMarketDataUpdate {
+                    template_id: 100,
+                    user_msg: vec![],
+                    symbol: Some(symbol.clone()),
+                    exchange: Some(exchange.to_string()),
+                    request: Some(2), // 2 for unsubscribe
+                    update_bits: Some(4), //1 ticks, 2 quotes, 4 order book
+                };
+
+                const PLANT: SysInfraType = SysInfraType::TickerPlant;
+                client.send_message(&PLANT, req).await;
+                println!("Unsubscribed: {} Order Books", symbol);
+            }
+        }
+    }
+}
+
+/// Saves the buffered depth data (order books or market by order events) for the symbol to the data storage in the background.
+pub(crate) fn save_depth_buffer(buffer: &DashMap<SymbolName, Vec<BaseDataEnum>>, symbol: &str) {
+    let data = match buffer.remove(symbol) {
+        Some((_, data)) if !data.is_empty() => data,
+        _ => return,
+    };
+    tokio::spawn(async move {
+        if let Some(data_storage) = DATA_STORAGE.get() {
+            if let Err(e) = data_storage.save_data_bulk(data).await {
+                eprintln!("Failed to save depth data: {}", e);
+            }
+        }
+    });
+}
+
+/// Applies an order book message to the price -> volume maps of each side, a level with a size of 0 is removed.
+/// Returns true once the update is complete and the book should be published, multi message updates are only complete on `End`.
+fn apply_order_book_update(bids: &mut BTreeMap<Price, Volume>, asks: &mut BTreeMap<Price, Volume>, msg: &OrderBook) -> bool {
+    let update_type = msg.update_type.and_then(|update_type| UpdateType::try_from(update_type).ok());
+    match update_type {
+        Some(UpdateType::ClearOrderBook) | Some(UpdateType::NoBook) => {
+            bids.clear();
+            asks.clear();
+            return false;
+        }
+        Some(UpdateType::SnapshotImage) => {
+            bids.clear();
+            asks.clear();
+        }
+        _ => {}
+    }
+
+    let apply = |book: &mut BTreeMap<Price, Volume>, prices: &[f64], sizes: &[i32]| {
+        for (price, size) in prices.iter().zip(sizes.iter()) {
+            if let (Some(price), Some(size)) = (Decimal::from_f64(*price), Decimal::from_i32(*size)) {
+                match size > Decimal::ZERO {
+                    true => book.insert(price, size),
+                    false => book.remove(&price),
+                };
+            }
+        }
+    };
+    apply(bids, &msg.bid_price, &msg.bid_size);
+    apply(asks, &msg.ask_price, &msg.ask_size);
+
+    !matches!(update_type, Some(UpdateType::Begin) | Some(UpdateType::Middle))
+}
+
+/// The number of streamed market by order events to buffer before saving them to the data storage.
+const MARKET_BY_ORDER_SAVE_BATCH: usize = 10000;
+
+async fn handle_depth_by_order(client: Arc<RithmicBrokerageClient>, msg: DepthByOrder) {
+    let (symbol, exchange) = match depth_by_order_symbol(&msg.symbol, &msg.exchange) {
+        Some(symbol) => symbol,
+        None => return,
+    };
+    let time = msg.source_ssboe
+        .and_then(|ssboe| msg.source_usecs.map(|usecs| (ssboe, usecs)))
+        .or_else(|| msg.ssboe.and_then(|ssboe| msg.usecs.map(|usecs| (ssboe, usecs))))
+        .and_then(|(ssboe, usecs)| Utc.timestamp_opt(ssboe as i64, usecs as u32 * 1000).single())
+        .unwrap_or_else(Utc::now);
+
+    let symbol_obj = Symbol::new(symbol.clone(), client.data_vendor, MarketType::Futures(exchange));
+    let snapshots = client.market_by_order_snapshots.remove(&symbol).map(|(_, snapshots)| snapshots).unwrap_or_default();
+    let events = {
+        let mut last_time = client.market_by_order_last_time.entry(symbol.clone()).or_insert(DateTime::<Utc>::MIN_UTC);
+        let mut events = depth_by_order_snapshots_events(&symbol_obj, &snapshots, time, &mut last_time);
+        events.extend(depth_by_order_events(&symbol_obj, &msg, time, &mut last_time));
+        events
+    };
+    publish_market_by_order(&client, &symbol, exchange, events).await;
+}
+
+/// The snapshot of the resting orders at one price, sent when we subscribe to market by order depth, published as `Add` events.
+/// The snapshot has no exchange time, so it is held until the first update and published at the exchange time of that update.
+async fn handle_depth_by_order_snapshot(client: Arc<RithmicBrokerageClient>, msg: ResponseDepthByOrderSnapshot) {
+    let (symbol, _) = match depth_by_order_symbol(&msg.symbol, &msg.exchange) {
+        Some(symbol) => symbol,
+        None => return,
+    };
+    if !client.market_by_order_feed_broadcasters.contains_key(&symbol) {
+        return;
+    }
+    client.market_by_order_snapshots.entry(symbol).or_default().push(msg);
+}
+
+fn depth_by_order_symbol(symbol: &Option<String>, exchange: &Option<String>) -> Option<(SymbolName, FuturesExchange)> {
+    let symbol = symbol.clone()?;
+    match exchange.as_deref().and_then(|e| FuturesExchange::from_string(e).ok()) {
+        Some(exchange) => Some((symbol, exchange)),
+        None => {
+            eprintln!("Error deserializing Exchange for symbol {}", symbol);
+            None
+        }
+    }
+}
+
+async fn publish_market_by_order(client: &Arc<RithmicBrokerageClient>, symbol: &SymbolName, exchange: FuturesExchange, events: Vec<MarketByOrder>) {
+    if events.is_empty() {
+        return;
+    }
+    let mut remove_broadcaster = false;
+    if let Some(broadcaster) = client.market_by_order_feed_broadcasters.get(symbol) {
+        let batch_full = {
+            let mut buffer = client.market_by_order_buffer.entry(symbol.clone()).or_default();
+            buffer.extend(events.iter().cloned().map(BaseDataEnum::MarketByOrder));
+            buffer.len() >= MARKET_BY_ORDER_SAVE_BATCH
+        };
+        if batch_full {
+            save_depth_buffer(&client.market_by_order_buffer, symbol);
+        }
+
+        for event in events {
+            if broadcaster.send(BaseDataEnum::MarketByOrder(event)).is_err() && broadcaster.receiver_count() == 0 {
+                remove_broadcaster = true;
+                break;
+            }
+        }
+    }
+
+    if remove_broadcaster {
+        if let Some((_, broadcaster)) = client.market_by_order_feed_broadcasters.remove(symbol) {
+            if broadcaster.receiver_count() == 0 {
+                client.market_by_order_last_time.remove(symbol);
+                client.market_by_order_snapshots.remove(symbol);
+                save_depth_buffer(&client.market_by_order_buffer, symbol);
+                let req = RequestDepthByOrderUpdates {
+                    template_id: 117,
+                    user_msg: vec![],
+                    request: Some(2), // 2 for unsubscribe
+                    symbol: Some(symbol.clone()),
+                    exchange: Some(exchange.to_string()),
+                    depth_price: None,
+                };
+
+                const PLANT: SysInfraType = SysInfraType::TickerPlant;
+                client.send_message(&PLANT, req).await;
+                println!("Unsubscribed: {} Market By Order", symbol);
+            }
+        }
+    }
+}
+
+/// Each market by order event is saved by its time, events at the same time as the last event are moved 1 nanosecond later so none are overwritten.
+fn unique_market_by_order_time(time: DateTime<Utc>, last_time: &mut DateTime<Utc>) -> DateTime<Utc> {
+    let time = match time <= *last_time {
+        true => *last_time + chrono::Duration::nanoseconds(1),
+        false => time,
+    };
+    *last_time = time;
+    time
+}
+
+/// Converts the parallel vectors of a depth by order message into one `MarketByOrder` event per changed order.
+fn depth_by_order_events(symbol: &Symbol, msg: &DepthByOrder, time: DateTime<Utc>, last_time: &mut DateTime<Utc>) -> Vec<MarketByOrder> {
+    let mut events = Vec::with_capacity(msg.exchange_order_id.len());
+    for (index, order_id) in msg.exchange_order_id.iter().enumerate() {
+        let action = match msg.update_type.get(index).and_then(|update_type| DepthByOrderUpdateType::try_from(*update_type).ok()) {
+            Some(DepthByOrderUpdateType::New) => MarketByOrderAction::Add,
+            Some(DepthByOrderUpdateType::Change) => MarketByOrderAction::Modify,
+            Some(DepthByOrderUpdateType::Delete) => MarketByOrderAction::Delete,
+            None => continue,
+        };
+        let side = match msg.transaction_type.get(index).and_then(|side| DepthByOrderTransactionType::try_from(*side).ok()) {
+            Some(DepthByOrderTransactionType::Buy) => OrderSide::Buy,
+            Some(DepthByOrderTransactionType::Sell) => OrderSide::Sell,
+            None => continue,
+        };
+        let price = match msg.depth_price.get(index).and_then(|price| Decimal::from_f64(*price)) {
+            Some(price) => price,
+            None => continue,
+        };
+        let volume = match action {
+            MarketByOrderAction::Delete => Decimal::ZERO,
+            _ => msg.depth_size.get(index).and_then(|size| Decimal::from_i32(*size)).unwrap_or_default(),
+        };
+        let priority = msg.depth_order_priority.get(index).cloned().unwrap_or_default();
+        let time = unique_market_by_order_time(time, last_time);
+        events.push(MarketByOrder::new(symbol.clone(), order_id.clone(), side, action, price, volume, priority, time.to_string()));
+    }
+    events
+}
+
+/// The events of the snapshots held for the first update at `time`, stamped in the nanoseconds before it so the update keeps its exchange time.
+fn depth_by_order_snapshots_events(symbol: &Symbol, snapshots: &[ResponseDepthByOrderSnapshot], time: DateTime<Utc>, last_time: &mut DateTime<Utc>) -> Vec<MarketByOrder> {
+    let orders: usize = snapshots.iter().map(|snapshot| snapshot.exchange_order_id.len()).sum();
+    let snapshot_time = time - chrono::Duration::nanoseconds(orders as i64);
+    snapshots.iter()
+        .flat_map(|snapshot| depth_by_order_snapshot_events(symbol, snapshot, snapshot_time, last_time))
+        .collect()
+}
+
+fn depth_by_order_snapshot_events(symbol: &Symbol, msg: &ResponseDepthByOrderSnapshot, time: DateTime<Utc>, last_time: &mut DateTime<Utc>) -> Vec<MarketByOrder> {
+    let side = match msg.depth_side.and_then(|side| SnapshotTransactionType::try_from(side).ok()) {
+        Some(SnapshotTransactionType::Buy) => OrderSide::Buy,
+        Some(SnapshotTransactionType::Sell) => OrderSide::Sell,
+        None => return vec![],
+    };
+    let price = match msg.depth_price.and_then(Decimal::from_f64) {
+        Some(price) => price,
+        None => return vec![],
+    };
+    msg.exchange_order_id.iter().enumerate()
+        .map(|(index, order_id)| {
+            let volume = msg.depth_size.get(index).and_then(|size| Decimal::from_i32(*size)).unwrap_or_default();
+            let priority = msg.depth_order_priority.get(index).cloned().unwrap_or_default();
+            let time = unique_market_by_order_time(time, last_time);
+            MarketByOrder::new(symbol.clone(), order_id.clone(), side, MarketByOrderAction::Add, price, volume, priority, time.to_string())
+        })
+        .collect()
+}
+
+fn deserialize_order_book_time(msg: &OrderBook) -> DateTime<Utc> {
+    msg.ssboe
+        .and_then(|ssboe| msg.usecs.map(|usecs| (ssboe, usecs)))
+        .and_then(|(ssboe, usecs)| {
+            Utc.timestamp_opt(ssboe as i64, usecs as u32 * 1000).single()
+        })
+        .unwrap_or_else(|| {
+            Utc::now()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+    use ff_standard_lib::standardized_types::base_data::traits::BaseData;
+    use ff_standard_lib::standardized_types::datavendor_enum::DataVendor;
+
+    fn order_book_msg(update_type: UpdateType, bid_price: Vec<f64>, bid_size: Vec<i32>, ask_price: Vec<f64>, ask_size: Vec<i32>) -> OrderBook {
+        OrderBook {
+            template_id: 156,
+            update_type: Some(update_type as i32),
+            bid_price,
+            bid_size,
+            ask_price,
+            ask_size,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_apply_order_book_update() {
+        let mut bids = BTreeMap::new();
+        let mut asks = BTreeMap::new();
+
+        let snapshot = order_book_msg(UpdateType::SnapshotImage, vec![99.75, 99.5], vec![5, 10], vec![100.0, 100.25], vec![2, 3]);
+        assert!(apply_order_book_update(&mut bids, &mut asks, &snapshot));
+        assert_eq!(bids.len(), 2);
+        assert_eq!(asks.get(&dec!(100.0)), Some(&dec!(2)));
+
+        // a multi message update is only published at the end
+        let begin = order_book_msg(UpdateType::Begin, vec![99.75], vec![0], vec![], vec![]);
+        assert!(!apply_order_book_update(&mut bids, &mut asks, &begin));
+        assert!(!bids.contains_key(&dec!(99.75)));
+        let end = order_book_msg(UpdateType::End, vec![], vec![], vec![100.0], vec![7]);
+        assert!(apply_order_book_update(&mut bids, &mut asks, &end));
+        assert_eq!(asks.get(&dec!(100.0)), Some(&dec!(7)));
+
+        let clear = order_book_msg(UpdateType::ClearOrderBook, vec![], vec![], vec![], vec![]);
+        assert!(!apply_order_book_update(&mut bids, &mut asks, &clear));
+        assert!(bids.is_empty() && asks.is_empty());
+    }
+
+    #[test]
+    fn test_depth_by_order_events() {
+        let symbol = Symbol::new("ES".to_string(), DataVendor::Rithmic, MarketType::Futures(FuturesExchange::CME));
+        let msg = DepthByOrder {
+            template_id: 160,
+            update_type: vec![DepthByOrderUpdateType::New as i32, DepthByOrderUpdateType::Change as i32, DepthByOrderUpdateType::Delete as i32],
+            transaction_type: vec![DepthByOrderTransactionType::Buy as i32, DepthByOrderTransactionType::Sell as i32, DepthByOrderTransactionType::Buy as i32],
+            depth_price: vec![99.75, 100.25, 99.5],
+            depth_size: vec![5, 3, 2],
+            depth_order_priority: vec![1, 2, 3],
+            exchange_order_id: vec!["a".to_string(), "b".to_string(), "c".to_string()],
+            ..Default::default()
+        };
+        let time = Utc::now();
+        let mut last_time = DateTime::<Utc>::MIN_UTC;
+        let events = depth_by_order_events(&symbol, &msg, time, &mut last_time);
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0].action, MarketByOrderAction::Add);
+        assert_eq!(events[0].side, OrderSide::Buy);
+        assert_eq!(events[0].price, dec!(99.75));
+        assert_eq!(events[1].action, MarketByOrderAction::Modify);
+        assert_eq!(events[1].side, OrderSide::Sell);
+        assert_eq!(events[1].volume, dec!(3));
+        assert_eq!(events[2].action, MarketByOrderAction::Delete);
+        assert_eq!(events[2].volume, dec!(0));
+
+        // events at the same time are moved 1 nanosecond apart so they are not overwritten in storage
+        assert_eq!(events[0].time_utc(), time);
+        assert_eq!(events[2].time_utc(), time + chrono::Duration::nanoseconds(2));
+        assert_eq!(last_time, time + chrono::Duration::nanoseconds(2));
+    }
+
+    #[test]
+    fn test_depth_by_order_snapshot_is_stamped_before_the_first_update() {
+        let symbol = Symbol::new("ES".to_string(), DataVendor::Rithmic, MarketType::Futures(FuturesExchange::CME));
+        let snapshot = |side: SnapshotTransactionType, price: f64, order_ids: Vec<&str>| ResponseDepthByOrderSnapshot {
+            template_id: 116,
+            depth_side: Some(side as i32),
+            depth_price: Some(price),
+            depth_size: order_ids.iter().map(|_| 1).collect(),
+            depth_order_priority: (1..=order_ids.len() as u64).collect(),
+            exchange_order_id: order_ids.iter().map(|id| id.to_string()).collect(),
+            ..Default::default()
+        };
+        let snapshots = vec![snapshot(SnapshotTransactionType::Buy, 99.75, vec!["a", "b"]), snapshot(SnapshotTransactionType::Sell, 100.25, vec!["c"])];
+        let update = DepthByOrder {
+            template_id: 160,
+            update_type: vec![DepthByOrderUpdateType::Delete as i32],
+            transaction_type: vec![DepthByOrderTransactionType::Buy as i32],
+            depth_price: vec![99.75],
+            depth_size: vec![1],
+            depth_order_priority: vec![1],
+            exchange_order_id: vec!["a".to_string()],
+            ..Default::default()
+        };
+        let time = Utc.timestamp_opt(1_720_000_000, 0).unwrap();
+        let mut last_time = DateTime::<Utc>::MIN_UTC;
+        let mut events = depth_by_order_snapshots_events(&symbol, &snapshots, time, &mut last_time);
+        events.extend(depth_by_order_events(&symbol, &update, time, &mut last_time));
+
+        let times: Vec<DateTime<Utc>> = events.iter().map(|event| event.time_utc()).collect();
+        let nanoseconds = chrono::Duration::nanoseconds;
+        assert_eq!(times, vec![time - nanoseconds(3), time - nanoseconds(2), time - nanoseconds(1), time]);
+        assert!(events[..3].iter().all(|event| event.action == MarketByOrderAction::Add));
+        assert_eq!(events[2].side, OrderSide::Sell);
+        assert_eq!(events[3].action, MarketByOrderAction::Delete);
+    }
+}
@@ -4,7 +4,7 @@ use async_trait::async_trait;
 use chrono::{DateTime, Datelike, Duration, TimeDelta, Utc};
 use indicatif::{ProgressBar, ProgressStyle};
 use crate::rithmic_api::client_base::rithmic_proto_objects::rti::request_login::SysInfraType;
-use crate::rithmic_api::client_base::rithmic_proto_objects::rti::{RequestMarketDataUpdate, RequestTimeBarUpdate};
+use crate::rithmic_api::client_base::rithmic_proto_objects::rti::{RequestDepthByOrderSnapshot, RequestDepthByOrderUpdates, RequestMarketDataUpdate, RequestTimeBarUpdate};
 use crate::rithmic_api::client_base::rithmic_proto_objects::rti::request_time_bar_update::BarType;
 use ff_standard_lib::messages::data_server_messaging::{DataServerResponse, FundForgeError};
 use crate::server_features::server_side_datavendor::VendorApiResponse;
@@ -18,6 +18,7 @@ use tokio::time::timeout;
 use ff_standard_lib::product_maps::rithmic::maps::{get_available_rithmic_symbol_names, get_exchange_by_symbol_name, get_futures_symbol_info};
 use ff_standard_lib::standardized_types::base_data::base_data_enum::BaseDataEnum;
 use crate::rithmic_api::api_client::{RithmicBrokerageClient, RITHMIC_DATA_IS_CONNECTED};
+use crate::rithmic_api::plant_handlers::handle_tick_plant::save_depth_buffer;
 use crate::server_features::database::hybrid_storage::DATA_STORAGE;
 use crate::stream_tasks::{subscribe_stream, unsubscribe_stream};
 
@@ -72,6 +73,8 @@ impl VendorApiResponse for RithmicBrokerageClient {
                 resolutions.push(PrimarySubscription::new(Resolution::Ticks(1), BaseDataType::Ticks));
                 resolutions.push(PrimarySubscription::new(Resolution::Instant, BaseDataType::Quotes));
                 resolutions.push(PrimarySubscription::new(Resolution::Seconds(1), BaseDataType::Candles));
+                resolutions.push(PrimarySubscription::new(Resolution::Instant, BaseDataType::OrderBooks));
+                resolutions.push(PrimarySubscription::new(Resolution::Instant, BaseDataType::MarketByOrder));
             }
         }
 
@@ -143,7 +146,7 @@ impl VendorApiResponse for RithmicBrokerageClient {
             return DataServerResponse::SubscribeResponse{ success: false, subscription: subscription.clone(), reason: Some(format!("This subscription is not available with {}: {}", subscription.symbol.data_vendor, subscription))}
         }
 
-        const BASEDATA_TYPES: &[BaseDataType] = &[BaseDataType::Ticks, BaseDataType::Quotes, BaseDataType::Candles];
+        const BASEDATA_TYPES: &[BaseDataType] = &[BaseDataType::Ticks, BaseDataType::Quotes, BaseDataType::Candles, BaseDataType::OrderBooks, BaseDataType::MarketByOrder];
         if !BASEDATA_TYPES.contains(&subscription.base_data_type) {
             return DataServerResponse::SubscribeResponse{ success: false, subscription: subscription.clone(), reason: Some(format!("This subscription is not available with {}: {}", subscription.symbol.data_vendor, subscription))}
         };
@@ -186,14 +189,39 @@ impl VendorApiResponse for RithmicBrokerageClient {
                     is_subscribed = false;
                 }
             }
+            BaseDataType::OrderBooks => {
+                if let Some(broadcaster) = self.order_book_feed_broadcasters.get(&subscription.symbol.name) {
+                    let receiver = broadcaster.value().subscribe();
+                    subscribe_stream(&stream_name, subscription.clone(), receiver).await;
+                } else {
+                    let (sender, receiver) = broadcast::channel(500);
+                    self.order_book_feed_broadcasters.insert(subscription.symbol.name.clone(), sender);
+                    subscribe_stream(&stream_name, subscription.clone(), receiver).await;
+                    self.depth_bids.insert(subscription.symbol.name.clone(), BTreeMap::new());
+                    self.depth_asks.insert(subscription.symbol.name.clone(), BTreeMap::new());
+                    is_subscribed = false;
+                }
+            }
+            BaseDataType::MarketByOrder => {
+                if let Some(broadcaster) = self.market_by_order_feed_broadcasters.get(&subscription.symbol.name) {
+                    let receiver = broadcaster.value().subscribe();
+                    subscribe_stream(&stream_name, subscription.clone(), receiver).await;
+                } else {
+                    let (sender, receiver) = broadcast::channel(5000);
+                    self.market_by_order_feed_broadcasters.insert(subscription.symbol.name.clone(), sender);
+                    subscribe_stream(&stream_name, subscription.clone(), receiver).await;
+                    is_subscribed = false;
+                }
+            }
             _ => todo!("Handle gracefully by returning err")
         }
 
         if !is_subscribed {
-            if subscription.base_data_type == BaseDataType::Quotes || subscription.base_data_type == BaseDataType::Ticks {
+            if subscription.base_data_type == BaseDataType::Quotes || subscription.base_data_type == BaseDataType::Ticks || subscription.base_data_type == BaseDataType::OrderBooks {
                 let bits = match subscription.base_data_type {
                     BaseDataType::Ticks => 1,
                     BaseDataType::Quotes => 2,
+                    BaseDataType::OrderBooks => 4,
                     _ => return DataServerResponse::SubscribeResponse { success: false, subscription: subscription.clone(), reason: Some(format!("This subscription is not available with {}: {}", self.data_vendor, subscription)) }
                     //BaseDataType::Candles => {}
                 };
@@ -203,11 +231,32 @@ impl VendorApiResponse for RithmicBrokerageClient {
                     symbol: Some(subscription.symbol.name.to_string()),
                     exchange: Some(exchange),
                     request: Some(1), //1 subscribe 2 unsubscribe
-                    update_bits: Some(bits), //1 for ticks 2 for quotes
+                    update_bits: Some(bits), //1 for ticks 2 for quotes 4 for order books
                 };
 
                 const PLANT: SysInfraType = SysInfraType::TickerPlant;
                 self.send_message(&PLANT, req).await;
+            } else if subscription.base_data_type == BaseDataType::MarketByOrder {
+                // subscribe to the updates first so no change is missed while the snapshot of the resting orders is sent
+                let req = RequestDepthByOrderUpdates {
+                    template_id: 117,
+                    user_msg: vec![],
+                    request: Some(1), //1 subscribe 2 unsubscribe
+                    symbol: Some(subscription.symbol.name.to_string()),
+                    exchange: Some(exchange.clone()),
+                    depth_price: None,
+                };
+                const PLANT: SysInfraType = SysInfraType::TickerPlant;
+                self.send_message(&PLANT, req).await;
+
+                let req = RequestDepthByOrderSnapshot {
+                    template_id: 115,
+                    user_msg: vec![],
+                    symbol: Some(subscription.symbol.name.to_string()),
+                    exchange: Some(exchange),
+                    depth_price: None,
+                };
+                self.send_message(&PLANT, req).await;
             } else if subscription.base_data_type == BaseDataType::Candles {
                 let (num, res_type) = match subscription.resolution {
                     Resolution::Seconds(num) => (num as i32, BarType::SecondBar),
@@ -254,6 +303,9 @@ impl VendorApiResponse for RithmicBrokerageClient {
             BaseDataType::Ticks => (1, &self.tick_feed_broadcasters),
             BaseDataType::Quotes => (2, &self.quote_feed_broadcasters),
             BaseDataType::Candles => (3, &self.candle_feed_broadcasters),
+            BaseDataType::OrderBooks => (4, &self.order_book_feed_broadcasters),
+            // market by order depth is not part of the market data update bits, it has its own request
+            BaseDataType::MarketByOrder => (0, &self.market_by_order_feed_broadcasters),
             _ => return DataServerResponse::SubscribeResponse {
                 success: false,
                 subscription: subscription.clone(),
@@ -269,7 +321,7 @@ impl VendorApiResponse for RithmicBrokerageClient {
         }
 
         if should_disconnect {
-            if subscription.base_data_type == BaseDataType::Quotes || subscription.base_data_type == BaseDataType::Ticks {
+            if subscription.base_data_type == BaseDataType::Quotes || subscription.base_data_type == BaseDataType::Ticks || subscription.base_data_type == BaseDataType::OrderBooks {
                 broadcaster_map.remove(&symbol);
 
                 let req = RequestMarketDataUpdate {
@@ -289,6 +341,28 @@ impl VendorApiResponse for RithmicBrokerageClient {
                     self.ask_book.remove(&symbol);
                     self.bid_book.remove(&symbol);
                 }
+                if subscription.base_data_type == BaseDataType::OrderBooks {
+                    self.depth_bids.remove(&symbol);
+                    self.depth_asks.remove(&symbol);
+                    save_depth_buffer(&self.order_book_buffer, &symbol);
+                }
+            } else if subscription.base_data_type == BaseDataType::MarketByOrder {
+                broadcaster_map.remove(&symbol);
+
+                let req = RequestDepthByOrderUpdates {
+                    template_id: 117,
+                    user_msg: vec![],
+                    request: Some(2), // 2 for unsubscribe
+                    symbol: Some(symbol.clone()),
+                    exchange: Some(exchange),
+                    depth_price: None,
+                };
+
+                const PLANT: SysInfraType = SysInfraType::TickerPlant;
+                self.send_message(&PLANT, req).await;
+                self.market_by_order_last_time.remove(&symbol);
+                self.market_by_order_snapshots.remove(&symbol);
+                save_depth_buffer(&self.market_by_order_buffer, &symbol);
             } else if subscription.base_data_type == BaseDataType::Candles {
                 let (num, res_type) = match subscription.resolution {
                     Resolution::Seconds(num) => (num as i32, BarType::SecondBar),
@@ -318,7 +392,9 @@ impl VendorApiResponse for RithmicBrokerageClient {
         // Check if we need to switch heartbeat
         if self.tick_feed_broadcasters.is_empty() &&
             self.quote_feed_broadcasters.is_empty() &&
-            self.candle_feed_broadcasters.is_empty()
+            self.candle_feed_broadcasters.is_empty() &&
+            self.order_book_feed_broadcasters.is_empty() &&
+            self.market_by_order_feed_broadcasters.is_empty()
         {
             //todo fix in ff_rithmic api this causes a lock
          /*   if let Err(e) = self.client.switch_heartbeat_required(SysInfraType::TickerPlant, true).await {
@@ -337,7 +413,7 @@ impl VendorApiResponse for RithmicBrokerageClient {
         //todo get_requests dynamically from server using stream name to fwd callback
         DataServerResponse::BaseDataTypes {
             callback_id,
-            base_data_types: vec![BaseDataType::Ticks, BaseDataType::Quotes, BaseDataType::Candles],
+            base_data_types: vec![BaseDataType::Ticks, BaseDataType::Quotes, BaseDataType::Candles, BaseDataType::OrderBooks, BaseDataType::MarketByOrder],
         }
     }
 
@@ -368,6 +444,15 @@ impl VendorApiResponse for RithmicBrokerageClient {
             return Err(FundForgeError::ClientSideErrorDebug(format!("{}, Ticks data can only be requested with 1 tick resolution", symbol_name)))
         }
 
+        if base_data_type == BaseDataType::OrderBooks {
+            progress_bar.finish_and_clear();
+            return Err(FundForgeError::ClientSideErrorDebug(format!("{}, Order book history can not be downloaded, it is recorded while streaming order books", symbol_name)))
+        }
+        if base_data_type == BaseDataType::MarketByOrder {
+            progress_bar.finish_and_clear();
+            return Err(FundForgeError::ClientSideErrorDebug(format!("{}, Market by order history can not be downloaded, it is recorded while streaming market by order depth", symbol_name)))
+        }
+
         let data_storage = DATA_STORAGE.get().unwrap();
 
         let mut window_start = from;
@@ -0,0 +1,212 @@
+use chrono::{DateTime, Duration, Utc};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use ff_standard_lib::messages::data_server_messaging::FundForgeError;
+use ff_standard_lib::product_maps::rithmic::rollover::{get_calendar_rolls, short_symbol_code};
+use ff_standard_lib::standardized_types::base_data::base_data_enum::BaseDataEnum;
+use ff_standard_lib::standardized_types::base_data::base_data_type::BaseDataType;
+use ff_standard_lib::standardized_types::continuous_contracts::{reference_price, RollEvent, RollRule, RollSchedule};
+use ff_standard_lib::standardized_types::new_types::Price;
+use ff_standard_lib::standardized_types::resolution::Resolution;
+use ff_standard_lib::standardized_types::subscriptions::{DataSubscription, Symbol, SymbolCode};
+use crate::server_features::database::hybrid_storage::HybridStorage;
+
+/// How many days either side of the calendar roll the volume and open interest rules look for a crossover.
+const CROSSOVER_DAYS: i64 = 10;
+
+impl HybridStorage {
+    /// The contracts of a continuous futures series between `from` and `to`, the data of each contract is stored with its symbol code as the symbol name.
+    /// The contracts are the front months of `CONTRACT_SPECS`, the `RollRule` decides when the series moves to the next one.
+    pub async fn get_roll_schedule(
+        &self,
+        subscription: &DataSubscription,
+        roll_rule: RollRule,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<RollSchedule, FundForgeError> {
+        let symbol_name = subscription.symbol.name.clone();
+        let calendar_rolls = get_calendar_rolls(&symbol_name, from, to)
+            .map_err(|e| FundForgeError::ServerErrorDebug(format!("Unable to get the roll schedule for {}: {}", symbol_name, e)))?;
+        let contracts: Vec<(DateTime<Utc>, Symbol)> = calendar_rolls.into_iter()
+            .map(|(time, symbol_code)| (time, self.contract_symbol(subscription, &symbol_code)))
+            .collect();
+
+        let mut rolls: Vec<RollEvent> = vec![];
+        for pair in contracts.windows(2) {
+            let (_, from_symbol) = &pair[0];
+            let (calendar_time, to_symbol) = &pair[1];
+            let mut roll_time = match roll_rule {
+                RollRule::Calendar => *calendar_time,
+                RollRule::Volume | RollRule::OpenInterest => self.crossover_time(subscription, roll_rule, from_symbol, to_symbol, *calendar_time).await.unwrap_or(*calendar_time),
+            };
+            if let Some(last_roll) = rolls.last() {
+                if roll_time <= last_roll.time_utc() {
+                    roll_time = *calendar_time;
+                }
+            }
+
+            let from_price = self.reference_price_asof(subscription, from_symbol, roll_time).await;
+            let to_price = self.reference_price_asof(subscription, to_symbol, roll_time).await;
+            let (from_price, to_price) = match (from_price, to_price) {
+                (Some(from_price), Some(to_price)) => (from_price, to_price),
+                _ => {
+                    eprintln!("No price for {} or {} at {}, the roll is not adjusted", from_symbol.name, to_symbol.name, roll_time);
+                    (dec!(0), dec!(0))
+                }
+            };
+            rolls.push(RollEvent {
+                symbol_name: symbol_name.clone(),
+                from_symbol_code: from_symbol.name.clone(),
+                to_symbol_code: to_symbol.name.clone(),
+                time: roll_time.to_string(),
+                from_price,
+                to_price,
+            });
+        }
+
+        Ok(RollSchedule {
+            symbol_name,
+            first_symbol_code: contracts[0].1.name.clone(),
+            rolls,
+        })
+    }
+
+    /// The symbol the contract data is stored under, Rithmic contracts are stored with a single digit year.
+    fn contract_symbol(&self, subscription: &DataSubscription, symbol_code: &SymbolCode) -> Symbol {
+        let symbol = Symbol::new(symbol_code.clone(), subscription.symbol.data_vendor, subscription.symbol.market_type);
+        if self.get_base_path(&symbol, &subscription.resolution, &subscription.base_data_type, false).exists() {
+            return symbol;
+        }
+        let short_symbol = Symbol::new(short_symbol_code(symbol_code), subscription.symbol.data_vendor, subscription.symbol.market_type);
+        match self.get_base_path(&short_symbol, &subscription.resolution, &subscription.base_data_type, false).exists() {
+            true => short_symbol,
+            false => symbol,
+        }
+    }
+
+    async fn reference_price_asof(&self, subscription: &DataSubscription, symbol: &Symbol, time: DateTime<Utc>) -> Option<Price> {
+        match self.get_data_point_asof(symbol, &subscription.resolution, &subscription.base_data_type, time).await {
+            Ok(Some(data)) => reference_price(&data),
+            _ => None,
+        }
+    }
+
+    /// The start of the day after the first day the next contract has more volume or open interest than the current contract.
+    /// Returns `None` if there is no crossover within `CROSSOVER_DAYS` of the calendar roll.
+    async fn crossover_time(
+        &self,
+        subscription: &DataSubscription,
+        roll_rule: RollRule,
+        from_symbol: &Symbol,
+        to_symbol: &Symbol,
+        calendar_time: DateTime<Utc>,
+    ) -> Option<DateTime<Utc>> {
+        let mut day_start = calendar_time - Duration::days(CROSSOVER_DAYS);
+        let last_day = calendar_time + Duration::days(CROSSOVER_DAYS);
+        while day_start < last_day {
+            let day_end = day_start + Duration::days(1);
+            let current = self.day_activity(subscription, roll_rule, from_symbol, day_start, day_end).await;
+            let next = self.day_activity(subscription, roll_rule, to_symbol, day_start, day_end).await;
+            // we only know the day crossed over once it has closed, so the roll is at the start of the next day
+            if next > current && next > dec!(0) {
+                return Some(day_end);
+            }
+            day_start = day_end;
+        }
+        None
+    }
+
+    /// The volume traded in the day, or the last open interest of the day for `RollRule::OpenInterest`.
+    async fn day_activity(&self, subscription: &DataSubscription, roll_rule: RollRule, symbol: &Symbol, day_start: DateTime<Utc>, day_end: DateTime<Utc>) -> Decimal {
+        let last_time = day_end - Duration::nanoseconds(1);
+        match roll_rule {
+            RollRule::OpenInterest => {
+                let data = self.get_data_range(symbol, &Resolution::Instant, &BaseDataType::Fundamentals, day_start, last_time).await.unwrap_or_default();
+                data.iter().rev()
+                    .find_map(|data| match data {
+                        BaseDataEnum::Fundamental(fundamental) if fundamental.name == "open_interest" => fundamental.values.get("open_interest").cloned(),
+                        _ => None,
+                    })
+                    .unwrap_or_default()
+            }
+            RollRule::Volume | RollRule::Calendar => {
+                let data = self.get_data_range(symbol, &subscription.resolution, &subscription.base_data_type, day_start, last_time).await.unwrap_or_default();
+                data.iter()
+                    .map(|data| match data {
+                        BaseDataEnum::Candle(candle) => candle.volume,
+                        BaseDataEnum::QuoteBar(bar) => bar.volume,
+                        BaseDataEnum::Tick(tick) => tick.volume,
+                        BaseDataEnum::Quote(_) | BaseDataEnum::OrderBook(_) | BaseDataEnum::MarketByOrder(_) | BaseDataEnum::Fundamental(_) => dec!(0),
+                    })
+                    .sum()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use tempfile::TempDir;
+    use ff_standard_lib::standardized_types::base_data::candle::Candle;
+    use ff_standard_lib::standardized_types::datavendor_enum::DataVendor;
+    use ff_standard_lib::standardized_types::enums::{FuturesExchange, MarketType};
+    use ff_standard_lib::standardized_types::subscriptions::CandleType;
+    use crate::ServerLaunchOptions;
+    use super::*;
+
+    fn time(time: &str) -> DateTime<Utc> {
+        DateTime::from_str(time).unwrap()
+    }
+
+    fn candle(symbol_code: &str, time: DateTime<Utc>, close: Price, volume: Decimal) -> BaseDataEnum {
+        let symbol = Symbol::new(symbol_code.to_string(), DataVendor::Rithmic, MarketType::Futures(FuturesExchange::CME));
+        let mut candle = Candle::new(symbol, close, volume, dec!(0), dec!(0), time.to_string(), Resolution::Hours(1), CandleType::CandleStick);
+        candle.is_closed = true;
+        BaseDataEnum::Candle(candle)
+    }
+
+    #[tokio::test]
+    async fn test_roll_schedule() {
+        let temp_dir = TempDir::new().unwrap();
+        let options = ServerLaunchOptions {
+            data_folder: temp_dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let storage = HybridStorage::new(std::time::Duration::from_secs(3600), options, 5, 300);
+
+        // the june contract trades more than the march contract from 2024-03-07 06:00, two days before the calendar roll
+        let start = time("2024-03-05T00:00:00Z");
+        let mut data = vec![];
+        for hour in 0..24 * 7 {
+            let bar_time = start + Duration::hours(hour);
+            let june_is_active = bar_time >= time("2024-03-07T06:00:00Z");
+            data.push(candle("ESH24", bar_time, dec!(5000), if june_is_active { dec!(50) } else { dec!(100) }));
+            data.push(candle("ESM24", bar_time, dec!(5050), if june_is_active { dec!(200) } else { dec!(10) }));
+        }
+        storage.save_data_bulk(data).await.unwrap();
+
+        let subscription = DataSubscription::new("ES".to_string(), DataVendor::Rithmic, Resolution::Hours(1), BaseDataType::Candles, MarketType::Futures(FuturesExchange::CME));
+        let to = time("2024-03-12T00:00:00Z");
+
+        let calendar = storage.get_roll_schedule(&subscription, RollRule::Calendar, start, to).await.unwrap();
+        assert_eq!(calendar.first_symbol_code, "ESH24");
+        assert_eq!(calendar.rolls.len(), 1);
+        let roll = &calendar.rolls[0];
+        assert_eq!((roll.from_symbol_code.as_str(), roll.to_symbol_code.as_str()), ("ESH24", "ESM24"));
+        // midnight in Chicago on the rollover day
+        assert_eq!(roll.time_utc(), time("2024-03-09T06:00:00Z"));
+        assert_eq!(roll.gap(), dec!(50));
+
+        // the series rolls at the start of the day after the crossover
+        let volume = storage.get_roll_schedule(&subscription, RollRule::Volume, start, to).await.unwrap();
+        assert_eq!(volume.rolls[0].time_utc(), time("2024-03-08T06:00:00Z"));
+
+        // without open interest data the series rolls on the calendar
+        let open_interest = storage.get_roll_schedule(&subscription, RollRule::OpenInterest, start, to).await.unwrap();
+        assert_eq!(open_interest.rolls[0].time_utc(), time("2024-03-09T06:00:00Z"));
+
+        let unknown = DataSubscription::new("UNKNOWN".to_string(), DataVendor::Rithmic, Resolution::Hours(1), BaseDataType::Candles, MarketType::Futures(FuturesExchange::CME));
+        assert!(storage.get_roll_schedule(&unknown, RollRule::Calendar, start, to).await.is_err());
+    }
+}
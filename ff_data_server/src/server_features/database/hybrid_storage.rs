@@ -639,7 +639,7 @@ impl HybridStorage {
 
         //println!("Returning {} files", files_data.len());
         if files_data.is_empty() {
-            return Err(FundForgeError::ServerErrorDebug("No files found in range".to_string()));
+            return Err(FundForgeError::NoDataInRange("No files found in range".to_string()));
         }
 
         Ok(files_data)
@@ -700,6 +700,32 @@ mod test {
         assert_eq!(latest, expected_latest);
     }
 
+    #[tokio::test]
+    async fn test_order_book_storage() {
+        use ff_standard_lib::standardized_types::base_data::order_book::OrderBook;
+        use ff_standard_lib::standardized_types::books::BookLevel;
+        use ff_standard_lib::standardized_types::enums::{FuturesExchange, MarketType};
+        use ff_standard_lib::standardized_types::datavendor_enum::DataVendor;
+        use rust_decimal::Decimal;
+        use rust_decimal_macros::dec;
+        use std::str::FromStr;
+
+        let (storage, _temp) = setup_test_storage();
+        let symbol = Symbol::new("MES".to_string(), DataVendor::Rithmic, MarketType::Futures(FuturesExchange::CME));
+        let start = DateTime::<Utc>::from_str("2024-06-03T14:00:00Z").unwrap();
+        let test_data: Vec<BaseDataEnum> = (0..10).map(|i| {
+            let price = dec!(5000) + Decimal::from(i);
+            let bids = vec![BookLevel::new(0, price - dec!(0.25), dec!(10)), BookLevel::new(1, price - dec!(0.5), dec!(20))];
+            let asks = vec![BookLevel::new(0, price, dec!(5)), BookLevel::new(1, price + dec!(0.25), dec!(15))];
+            BaseDataEnum::OrderBook(OrderBook::new(symbol.clone(), bids, asks, (start + chrono::Duration::seconds(i)).to_string()))
+        }).collect();
+
+        storage.save_data_bulk(test_data.clone()).await.unwrap();
+
+        let data = storage.get_data_range(&symbol, &Resolution::Instant, &BaseDataType::OrderBooks, start, start + chrono::Duration::minutes(1)).await.unwrap();
+        assert_eq!(data, test_data);
+    }
+
     #[tokio::test]
     async fn test_get_ranges_across_days() {
         let (storage, _temp) = setup_test_storage();
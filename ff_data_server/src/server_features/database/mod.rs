@@ -1,4 +1,5 @@
 pub mod hybrid_storage;
 pub mod decompressed_functions;
 pub mod update_functions;
-pub mod exchange_rate;
\ No newline at end of file
+pub mod exchange_rate;
+pub mod continuous_contracts;
\ No newline at end of file
@@ -19,6 +19,7 @@ use ff_standard_lib::standardized_types::datavendor_enum::DataVendor;
 use ff_standard_lib::standardized_types::enums::MarketType;
 use ff_standard_lib::standardized_types::resolution::Resolution;
 use ff_standard_lib::standardized_types::subscriptions::{Symbol, SymbolName};
+use crate::data_bento_api::api_client::get_data_bento_client;
 use crate::oanda_api::api_client::{OANDA_CLIENT, OANDA_IS_CONNECTED};
 use crate::rithmic_api::api_client::{get_rithmic_market_data_system, RITHMIC_CLIENTS, RITHMIC_DATA_IS_CONNECTED};
 use crate::server_features::database::hybrid_storage::{HybridStorage, MULTIBAR};
@@ -131,6 +132,12 @@ impl HybridStorage {
                     None => return,
                 }
             }
+            DataVendor::DataBento => {
+                match get_data_bento_client() {
+                    Ok(client) => client,
+                    Err(_) => return,
+                }
+            }
             _ => return,
         };
 
@@ -217,7 +224,10 @@ impl HybridStorage {
                 DataVendor::Oanda if !OANDA_IS_CONNECTED.load(Ordering::SeqCst) => {
                     continue
                 },
-                DataVendor::DataBento | DataVendor::Bitget => {
+                DataVendor::DataBento if get_data_bento_client().is_err() => {
+                    continue
+                },
+                DataVendor::Bitget => {
                     continue
                 },
                 _ => (),
@@ -259,7 +269,7 @@ impl HybridStorage {
                                     continue;
                                 }
                             },
-                            DataVendor::Rithmic => {
+                            DataVendor::Rithmic | DataVendor::DataBento => {
                                 match get_exchange_by_symbol_name(&symbol_config.symbol_name) {
                                     Some(exchange) => MarketType::Futures(exchange),
                                     None => {
@@ -390,6 +400,12 @@ impl HybridStorage {
                     None => return,
                 }
             }
+            DataVendor::DataBento => {
+                match get_data_bento_client() {
+                    Ok(client) => client,
+                    Err(_) => return,
+                }
+            }
             _ => return,
         };
 
@@ -2,7 +2,7 @@ use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use ff_standard_lib::messages::data_server_messaging::DataServerResponse;
 use ff_standard_lib::standardized_types::enums::StrategyMode;
-use ff_standard_lib::standardized_types::orders::{Order, OrderId, OrderUpdateEvent, OrderUpdateType};
+use ff_standard_lib::standardized_types::orders::{Order, OrderGroupId, OrderGroupType, OrderId, OrderUpdateEvent, OrderUpdateType};
 use ff_standard_lib::standardized_types::subscriptions::SymbolName;
 use ff_standard_lib::standardized_types::accounts::{Account, AccountId};
 use ff_standard_lib::StreamName;
@@ -197,6 +197,18 @@ pub trait BrokerApiResponse: Sync + Send {
         order: Order,
     ) -> Result<(), OrderUpdateEvent>;
 
+    /// Place a group of linked orders using the brokers native order linking, see `OrderGroupType`.
+    /// The strategy only sends `OrderRequest::CreateGroup` to brokers where `Brokerage::supports_order_group()` is true, for other brokers the group is emulated by the strategy.
+    /// The Err contains a rejection event for every order in the group, since the group is placed or rejected as a whole.
+    async fn live_order_group(
+        &self,
+        stream_name: StreamName,
+        mode: StrategyMode,
+        group_id: OrderGroupId,
+        group_type: OrderGroupType,
+        orders: Vec<Order>,
+    ) -> Result<(), Vec<OrderUpdateEvent>>;
+
     /// cancel all pending orders on an account
     async fn cancel_orders_on_account(
         &self,
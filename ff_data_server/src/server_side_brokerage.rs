@@ -5,7 +5,7 @@ use crate::server_features::server_side_brokerage::BrokerApiResponse;
 use ff_standard_lib::standardized_types::broker_enum::Brokerage;
 use ff_standard_lib::standardized_types::enums::{FuturesExchange, OrderSide, StrategyMode};
 use ff_standard_lib::standardized_types::new_types::{TimeString};
-use ff_standard_lib::standardized_types::orders::{Order, OrderId, OrderUpdateEvent, OrderUpdateType};
+use ff_standard_lib::standardized_types::orders::{Order, OrderGroupId, OrderGroupType, OrderId, OrderUpdateEvent, OrderUpdateType};
 use ff_standard_lib::standardized_types::subscriptions::SymbolName;
 use ff_standard_lib::standardized_types::accounts::{Account, AccountId, Currency};
 use ff_standard_lib::StreamName;
@@ -16,6 +16,8 @@ use ff_standard_lib::standardized_types::datavendor_enum::DataVendor;
 use ff_standard_lib::standardized_types::orders::OrderUpdateEvent::OrderUpdateRejected;
 use crate::oanda_api::api_client::{get_oanda_client, OANDA_CLIENT};
 use crate::server_features::database::hybrid_storage::DATA_STORAGE;
+use crate::get_data_folder;
+use ff_standard_lib::strategies::ledgers::financing::{BrokerageFinancing, SymbolFinancingRate};
 
 pub const TIMEOUT_DURATION: Duration = Duration::from_secs(10);
 
@@ -68,6 +70,59 @@ pub async fn commission_info_response(mode: StrategyMode, brokerage: Brokerage,
     timeout(TIMEOUT_DURATION, operation).await.unwrap_or_else(|_| DataServerResponse::Error { callback_id, error: FundForgeError::ServerErrorDebug("Operation timed out".to_string()) })
 }
 
+/// return `DataServerResponse::FinancingRates` or `DataServerResponse::Error(FundForgeError)`.
+/// Rate tables stored in `data/financing/{brokerage}.toml` are used first, otherwise Oanda serves the financing rates of its instruments.
+pub async fn financing_rates_response(brokerage: Brokerage, callback_id: u64) -> DataServerResponse {
+    let path = get_data_folder().join("financing").join(format!("{}.toml", brokerage));
+    if path.exists() {
+        let table = match std::fs::read_to_string(&path) {
+            Ok(table) => table,
+            Err(e) => return DataServerResponse::Error { callback_id, error: FundForgeError::ServerErrorDebug(format!("Failed to read {}: {}", path.display(), e)) }
+        };
+        return match toml::from_str::<BrokerageFinancing>(&table) {
+            Ok(financing) => DataServerResponse::FinancingRates {
+                callback_id,
+                rates: financing.symbols.into_iter().map(|(symbol_name, rate)| SymbolFinancingRate { symbol_name, rate }).collect()
+            },
+            Err(e) => DataServerResponse::Error { callback_id, error: FundForgeError::ServerErrorDebug(format!("Invalid financing rates {}: {}", path.display(), e)) }
+        }
+    }
+    match brokerage {
+        Brokerage::Oanda => if let Some(client) = get_oanda_client() {
+            return DataServerResponse::FinancingRates {
+                callback_id,
+                rates: client.financing_rates()
+            }
+        },
+        _ => {}
+    }
+    DataServerResponse::Error{ callback_id, error: FundForgeError::ServerErrorDebug(format!("No financing rates stored for: {}", brokerage))}
+}
+
+/// return `DataServerResponse::AccountSnapshot` or `DataServerResponse::Error(FundForgeError)`.
+/// The open positions and working orders at the brokerage, used to reconcile live strategies on startup.
+pub async fn account_snapshot_response(brokerage: Brokerage, account_id: AccountId, stream_name: StreamName, callback_id: u64) -> DataServerResponse {
+    let operation = async {
+        let snapshot = match brokerage {
+            Brokerage::Rithmic(system) => match RITHMIC_CLIENTS.get(&system) {
+                Some(client) => client.value().account_snapshot(stream_name, account_id).await,
+                None => Err(FundForgeError::ServerErrorDebug(format!("Unable to find api client instance for: {}", brokerage)))
+            },
+            Brokerage::Oanda => match get_oanda_client() {
+                Some(client) => client.account_snapshot(stream_name, account_id).await,
+                None => Err(FundForgeError::ServerErrorDebug(format!("Unable to find api client instance for: {}", brokerage)))
+            },
+            Brokerage::Bitget | Brokerage::Test => Err(FundForgeError::ServerErrorDebug(format!("Account snapshots are not supported for: {}", brokerage)))
+        };
+        match snapshot {
+            Ok(snapshot) => DataServerResponse::AccountSnapshot { callback_id, snapshot },
+            Err(error) => DataServerResponse::Error { callback_id, error }
+        }
+    };
+
+    timeout(TIMEOUT_DURATION, operation).await.unwrap_or_else(|_| DataServerResponse::Error { callback_id, error: FundForgeError::ServerErrorDebug("Operation timed out".to_string()) })
+}
+
 pub async fn front_month_info_response(brokerage: Brokerage, symbol_name: SymbolName, exchange: FuturesExchange, stream_name: StreamName, callback_id: u64) -> DataServerResponse {
     let operation = async {
         match brokerage {
@@ -444,6 +499,40 @@ pub async fn other_orders(stream_name: StreamName, mode: StrategyMode, order: Or
     }
 }
 
+pub async fn live_order_group(stream_name: StreamName, mode: StrategyMode, account: Account, group_id: OrderGroupId, group_type: OrderGroupType, orders: Vec<Order>) -> Result<(), Vec<OrderUpdateEvent>> {
+    let reject_all = |reason: String| -> Vec<OrderUpdateEvent> {
+        orders.iter().map(|order| create_order_rejected(order, reason.clone())).collect()
+    };
+    let operation = async {
+        match account.brokerage {
+            Brokerage::Test => {
+                Err(reject_all("Test Brokerage Can Not Place Live Orders".to_string()))
+            }
+            Brokerage::Rithmic(system) => {
+                RITHMIC_CLIENTS.get(&system)
+                    .ok_or_else(|| reject_all(format!("Client Not found for Rithmic system: {}", system)))?
+                    .live_order_group(stream_name, mode, group_id.clone(), group_type, orders.clone())
+                    .await
+            }
+            Brokerage::Bitget => {
+                BITGET_CLIENT.get()
+                    .ok_or_else(|| reject_all("Bitget client not found".to_string()))?
+                    .live_order_group(stream_name, mode, group_id.clone(), group_type, orders.clone())
+                    .await
+            }
+            Brokerage::Oanda => OANDA_CLIENT.get()
+                .ok_or_else(|| reject_all("Oanda client not found".to_string()))?
+                .live_order_group(stream_name, mode, group_id.clone(), group_type, orders.clone())
+                .await
+        }
+    };
+
+    match timeout(TIMEOUT_DURATION, operation).await {
+        Ok(result) => result,
+        Err(_) => Err(reject_all("Operation timed out".to_string()))
+    }
+}
+
 pub async fn cancel_order(account: Account, order_id: OrderId) {
     match account.brokerage {
         Brokerage::Test => {}
@@ -0,0 +1,83 @@
+/// The SplitMix64 increment.
+const GAMMA: u64 = 0x9E37_79B9_7F4A_7C15;
+
+/// Scrambles a SplitMix64 state into the next output.
+pub(crate) fn split_mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Returns the next SplitMix64 state after `state`.
+pub(crate) fn next_state(state: u64) -> u64 {
+    state.wrapping_add(GAMMA)
+}
+
+/// Converts a random u64 to a value in the range 0..1, using the top 53 bits so the fraction is exact as an f64.
+pub(crate) fn to_fraction(value: u64) -> f64 {
+    (value >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// A seeded SplitMix64 generator, the same seed always produces the same sequence.
+/// Used for the random fills, monte carlo simulations and random parameter searches, it is fast and well distributed but not suitable for cryptography.
+pub(crate) struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.state = next_state(self.state);
+        split_mix(self.state)
+    }
+
+    /// Returns a value in the range 0..1.
+    pub(crate) fn next_fraction(&mut self) -> f64 {
+        to_fraction(self.next_u64())
+    }
+
+    /// Returns an index in the range 0..len, `len` must not be 0.
+    pub(crate) fn next_index(&mut self, len: usize) -> usize {
+        (self.next_fraction() * len as f64) as usize
+    }
+
+    /// Returns true with the probability `chance`.
+    pub(crate) fn next_bool(&mut self, chance: f64) -> bool {
+        self.next_fraction() < chance
+    }
+
+    /// Shuffles the values in place with a Fisher-Yates shuffle.
+    pub(crate) fn shuffle<T>(&mut self, values: &mut [T]) {
+        for i in (1..values.len()).rev() {
+            let j = self.next_index(i + 1);
+            values.swap(i, j);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_mix() {
+        let mut rng = SplitMix64::new(1);
+        let values: Vec<u64> = (0..100).map(|_| rng.next_u64()).collect();
+        let mut same_seed = SplitMix64::new(1);
+        assert!(values.iter().all(|value| *value == same_seed.next_u64()));
+
+        let mut indexes = [0usize; 4];
+        for _ in 0..4000 {
+            indexes[rng.next_index(4)] += 1;
+        }
+        assert!(indexes.iter().all(|count| *count > 800 && *count < 1200));
+
+        let mut shuffled: Vec<usize> = (0..10).collect();
+        rng.shuffle(&mut shuffled);
+        shuffled.sort();
+        assert_eq!(shuffled, (0..10).collect::<Vec<usize>>());
+    }
+}
@@ -6,13 +6,15 @@ use serde::{Deserialize as SerdeDeserialize, Serialize as SerdeSerialize};
 use std::fmt::{Debug, Display};
 use rkyv::ser::Serializer;
 use rust_decimal::Decimal;
-use crate::standardized_types::accounts::{Account, AccountId, AccountInfo, Currency};
+use crate::standardized_types::accounts::{Account, AccountId, AccountInfo, AccountSnapshot, Currency};
 use crate::standardized_types::broker_enum::Brokerage;
 use crate::standardized_types::datavendor_enum::DataVendor;
 use crate::standardized_types::base_data::base_data_type::BaseDataType;
 use crate::standardized_types::new_types::{Price};
 use crate::standardized_types::orders::{OrderRequest, OrderUpdateEvent};
 use crate::standardized_types::symbol_info::{CommissionInfo, FrontMonthInfo, SymbolInfo};
+use crate::strategies::ledgers::financing::SymbolFinancingRate;
+use crate::standardized_types::continuous_contracts::{RollRule, RollSchedule};
 
 /// An Api key String
 pub type ApiKey = String;
@@ -131,6 +133,14 @@ pub enum DataServerRequest {
         from_time: String,
         to_time: String
     },
+    /// The contracts of a continuous futures series between the times, `subscription` has the symbol name of the product and the data of each contract is stored with its symbol code as the symbol name.
+    RollSchedule {
+        callback_id: u64,
+        subscription: DataSubscription,
+        roll_rule: RollRule,
+        from_time: String,
+        to_time: String
+    },
     FrontMonthInfo {
         callback_id: u64,
         symbol_name: SymbolName,
@@ -139,6 +149,10 @@ pub enum DataServerRequest {
     },
     Accounts{callback_id: u64, brokerage: Brokerage},
     SymbolNames{callback_id: u64, brokerage: Brokerage, time: Option<String>},
+    /// The financing rates stored by the data server for the brokerage, used to charge overnight financing in backtests.
+    FinancingRates{callback_id: u64, brokerage: Brokerage},
+    /// The open positions and working orders at the brokerage, used to reconcile live strategies on startup.
+    AccountSnapshot{callback_id: u64, brokerage: Brokerage, account_id: AccountId},
     RegisterStreamer{port: u16, secs: u64, subsec: u32},
 }
 
@@ -176,7 +190,10 @@ impl DataServerRequest {
             DataServerRequest::WarmUpResolutions { callback_id, .. } => {*callback_id = id}
             DataServerRequest::ExchangeRate { callback_id, .. } => {*callback_id = id}
             DataServerRequest::GetCompressedHistoricalData { callback_id, .. } => {*callback_id = id}
+            DataServerRequest::RollSchedule { callback_id, .. } => {*callback_id = id}
             DataServerRequest::FrontMonthInfo { callback_id, .. } => {*callback_id = id}
+            DataServerRequest::FinancingRates { callback_id, .. } => {*callback_id = id}
+            DataServerRequest::AccountSnapshot { callback_id, .. } => {*callback_id = id}
         }
     }
 }
@@ -199,6 +216,11 @@ DataServerResponse {
         payload: Vec<Vec<u8>>
     },
 
+    RollSchedule {
+        callback_id: u64,
+        schedule: RollSchedule
+    },
+
     /// Responds with `instruments` as `Vec<InstrumentEnum>` which contains:
     /// *  `Vec<Symbol>` for all symbols available on the server, to fullfill this the vendor will need a fn that converts from its instrument format into a `Symbol` object.
     Symbols {
@@ -306,6 +328,10 @@ DataServerResponse {
 
     CommissionInfo{callback_id: u64, commission_info: CommissionInfo},
 
+    FinancingRates{callback_id: u64, rates: Vec<SymbolFinancingRate>},
+
+    AccountSnapshot{callback_id: u64, snapshot: AccountSnapshot},
+
     LiveAccountUpdates {account: Account, cash_value: Decimal, cash_available: Decimal, cash_used: Decimal},
 
     /// Booked pnl is only sent for closed positions, it is the amount of booked pnl since the last side change from none to long or short
@@ -351,12 +377,15 @@ impl DataServerResponse {
             DataServerResponse::SymbolNames {callback_id, ..} => Some(callback_id.clone()),
             DataServerResponse::RegistrationResponse(_) => None,
             DataServerResponse::CommissionInfo { callback_id,.. } => Some(callback_id.clone()),
+            DataServerResponse::FinancingRates { callback_id,.. } => Some(*callback_id),
+            DataServerResponse::AccountSnapshot { callback_id,.. } => Some(*callback_id),
             DataServerResponse::FrontMonthInfo { callback_id, .. } => Some(callback_id.clone()),
             DataServerResponse::LiveAccountUpdates { .. } => None,
             DataServerResponse::LivePositionUpdates { .. } => None,
             DataServerResponse::AsyncError { .. } => None,
             DataServerResponse::ExchangeRate { callback_id, .. } => Some(callback_id.clone()),
             DataServerResponse::CompressedHistoricalData { callback_id, .. } => Some(callback_id.clone()),
+            DataServerResponse::RollSchedule { callback_id, .. } => Some(*callback_id),
         }
     }
 }
@@ -383,6 +412,8 @@ pub enum FundForgeError {
     UnknownBlameError(String),
     /// An unknown error occurred, the debug message is provided as `String`.
     ConnectionNotFound(String),
+    /// The data server has no data for the requested range, a weekend, holiday or a contract which was not trading.
+    NoDataInRange(String),
 }
 
 impl Debug for FundForgeError {
@@ -398,6 +429,7 @@ impl Debug for FundForgeError {
             }
             FundForgeError::UnknownBlameError(debug) => write!(f, "UnknownBlameError: {}", debug),
             FundForgeError::ConnectionNotFound(debug) => write!(f, "ConnectionNotFound {}:", debug),
+            FundForgeError::NoDataInRange(debug) => write!(f, "NoDataInRange: {}", debug),
         }
     }
 }
@@ -417,6 +449,7 @@ impl Display for FundForgeError {
             FundForgeError::ConnectionNotFound(debug) => {
                 write!(f, "ConnectionNotFound: {}:", debug)
             }
+            FundForgeError::NoDataInRange(debug) => write!(f, "NoDataInRange: {}", debug),
         }
     }
 }
@@ -1,4 +1,4 @@
-use chrono::{DateTime, Datelike, Utc};
+use chrono::{DateTime, Datelike, TimeZone, Utc};
 use std::collections::HashMap;
 use thiserror::Error;
 use crate::product_maps::rithmic::maps::get_futures_trading_hours;
@@ -183,6 +183,67 @@ pub fn get_front_month(symbol: &str, utc_time: DateTime<Utc>) -> Result<SymbolCo
     Ok(format!("{}{}{:02}", symbol, month_code, year_code))
 }
 
+/// The contracts `get_front_month()` returns between `from` and `to`, each with the time it becomes the front month.
+/// The first contract is the front month at `from`, later contracts take over at the start of the rollover day in the exchange time zone.
+pub fn get_calendar_rolls(symbol: &str, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Vec<(DateTime<Utc>, SymbolCode)>, RolloverError> {
+    let time_zone = match get_futures_trading_hours(symbol) {
+        Some(hours) => hours.timezone,
+        None => return Err(RolloverError::UnknownSymbol(symbol.to_string()))
+    };
+
+    let mut contracts = vec![(from, get_front_month(symbol, from)?)];
+    let mut date = from.with_timezone(&time_zone).date_naive();
+    let last_date = to.with_timezone(&time_zone).date_naive();
+    while let Some(next_date) = date.succ_opt() {
+        if next_date > last_date {
+            break;
+        }
+        date = next_date;
+        let start_of_day = match time_zone.from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap()).earliest() {
+            Some(time) => time.to_utc(),
+            None => continue,
+        };
+        let symbol_code = get_front_month(symbol, start_of_day)?;
+        if contracts.last().map(|(_, last_code)| last_code != &symbol_code).unwrap_or(true) {
+            contracts.push((start_of_day, symbol_code));
+        }
+    }
+    Ok(contracts)
+}
+
+/// The time `symbol_code` stops being the front month and the contract which replaces it, searching a year either side of `time`.
+/// Accepts codes with a 2 or single digit year, the next contract is returned in the same format.
+/// Returns `None` if `symbol_code` is not a front month of the symbol within the search.
+pub fn get_contract_rollover(symbol: &str, symbol_code: &str, time: DateTime<Utc>) -> Result<Option<(DateTime<Utc>, SymbolCode)>, RolloverError> {
+    let search = chrono::Duration::days(400);
+    let contracts = get_calendar_rolls(symbol, time - search, time + search)?;
+    let is_short = short_symbol_code(symbol_code) == symbol_code;
+    for pair in contracts.windows(2) {
+        let (_, current_code) = &pair[0];
+        if current_code != symbol_code && short_symbol_code(current_code) != symbol_code {
+            continue;
+        }
+        let (rollover_time, next_code) = &pair[1];
+        let next_code = match is_short {
+            true => short_symbol_code(next_code),
+            false => next_code.clone(),
+        };
+        return Ok(Some((*rollover_time, next_code)));
+    }
+    Ok(None)
+}
+
+/// Converts a symbol code with a 2 digit year like "ESH24", as returned by `get_front_month()`, to the single digit year used by Rithmic, "ESH4".
+pub fn short_symbol_code(symbol_code: &str) -> SymbolCode {
+    let chars: Vec<char> = symbol_code.chars().collect();
+    if chars.len() < 4 || !chars[chars.len() - 2].is_ascii_digit() || !chars[chars.len() - 1].is_ascii_digit() {
+        return symbol_code.to_string();
+    }
+    let mut short: String = chars[..chars.len() - 2].iter().collect();
+    short.push(chars[chars.len() - 1]);
+    short
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -216,6 +277,41 @@ mod tests {
         test_case("ES", "2024-03-09 14:30:00", "ESM24"); // June contract after rollover
     }
 
+    #[test]
+    fn test_calendar_rolls() {
+        let from = NaiveDateTime::parse_from_str("2024-01-01 00:00:00", "%Y-%m-%d %H:%M:%S").unwrap().and_utc();
+        let to = NaiveDateTime::parse_from_str("2024-07-01 00:00:00", "%Y-%m-%d %H:%M:%S").unwrap().and_utc();
+        let rolls = get_calendar_rolls("ES", from, to).unwrap();
+        let expected = vec![
+            (from, "ESH24".to_string()),
+            // midnight in Chicago, before and after daylight saving starts
+            (NaiveDateTime::parse_from_str("2024-03-09 06:00:00", "%Y-%m-%d %H:%M:%S").unwrap().and_utc(), "ESM24".to_string()),
+            (NaiveDateTime::parse_from_str("2024-06-09 05:00:00", "%Y-%m-%d %H:%M:%S").unwrap().and_utc(), "ESU24".to_string()),
+        ];
+        assert_eq!(rolls, expected);
+        assert!(get_calendar_rolls("UNKNOWN", from, to).is_err());
+    }
+
+    #[test]
+    fn test_short_symbol_code() {
+        assert_eq!(short_symbol_code("ESH24"), "ESH4");
+        assert_eq!(short_symbol_code("M6AZ24"), "M6AZ4");
+        assert_eq!(short_symbol_code("ESH4"), "ESH4");
+    }
+
+    #[test]
+    fn test_contract_rollover() {
+        let time = NaiveDateTime::parse_from_str("2024-02-20 15:00:00", "%Y-%m-%d %H:%M:%S").unwrap().and_utc();
+        let rollover = NaiveDateTime::parse_from_str("2024-03-09 06:00:00", "%Y-%m-%d %H:%M:%S").unwrap().and_utc();
+        assert_eq!(get_contract_rollover("ES", "ESH24", time).unwrap(), Some((rollover, "ESM24".to_string())));
+        assert_eq!(get_contract_rollover("ES", "ESH4", time).unwrap(), Some((rollover, "ESM4".to_string())));
+        // a contract which already rolled is still found
+        let later = NaiveDateTime::parse_from_str("2024-03-20 15:00:00", "%Y-%m-%d %H:%M:%S").unwrap().and_utc();
+        assert_eq!(get_contract_rollover("ES", "ESH24", later).unwrap(), Some((rollover, "ESM24".to_string())));
+        assert_eq!(get_contract_rollover("ES", "ESH30", time).unwrap(), None);
+        assert!(get_contract_rollover("UNKNOWN", "UNKNOWNH24", time).is_err());
+    }
+
     #[test]
     fn test_year_boundary() {
         test_case("CL", "2024-12-18 14:30:00", "CLF25"); // January contract after December rollover
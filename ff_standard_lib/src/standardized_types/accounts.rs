@@ -4,8 +4,11 @@ use rkyv::{Archive, Deserialize as Deserialize_rkyv, Serialize as Serialize_rkyv
 use rust_decimal::Decimal;
 use serde_derive::{Deserialize, Serialize};
 use crate::standardized_types::broker_enum::Brokerage;
+use crate::standardized_types::enums::PositionSide;
 use crate::standardized_types::new_types::{Price, Volume};
+use crate::standardized_types::orders::Order;
 use crate::standardized_types::position::Position;
+use crate::standardized_types::subscriptions::{SymbolCode, SymbolName};
 
 #[derive(Clone, Serialize_rkyv, Deserialize_rkyv, Archive, Debug)]
 #[archive(compare(PartialEq), check_bytes)]
@@ -31,6 +34,29 @@ pub struct AccountInfo {
     pub leverage: u32
 }
 
+/// An open position as the brokerage reports it, used to reconcile the strategy with the account on live startup.
+#[derive(Clone, Serialize_rkyv, Deserialize_rkyv, Archive, PartialEq, Debug)]
+#[archive(compare(PartialEq), check_bytes)]
+#[archive_attr(derive(Debug))]
+pub struct BrokerPosition {
+    pub symbol_name: SymbolName,
+    pub symbol_code: SymbolCode,
+    pub side: PositionSide,
+    pub quantity: Volume,
+    pub average_price: Price,
+}
+
+/// The open positions and working orders of an account at the brokerage.
+/// Orders which were not placed by fund forge have the brokerage order id as their `id` and an empty `tag`.
+#[derive(Clone, Serialize_rkyv, Deserialize_rkyv, Archive, PartialEq, Debug)]
+#[archive(compare(PartialEq), check_bytes)]
+#[archive_attr(derive(Debug))]
+pub struct AccountSnapshot {
+    pub account: Account,
+    pub positions: Vec<BrokerPosition>,
+    pub orders: Vec<Order>,
+}
+
 pub type AccountId = String;
 pub type AccountName = String;
 
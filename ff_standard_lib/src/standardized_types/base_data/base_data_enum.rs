@@ -1,13 +1,15 @@
 use crate::standardized_types::base_data::base_data_type::BaseDataType;
 use crate::standardized_types::base_data::candle::Candle;
 use crate::standardized_types::base_data::fundamental::Fundamental;
+use crate::standardized_types::base_data::market_by_order::MarketByOrder;
+use crate::standardized_types::base_data::order_book::OrderBook;
 use crate::standardized_types::base_data::quote::Quote;
 use crate::standardized_types::base_data::quotebar::QuoteBar;
 use crate::standardized_types::base_data::tick::Tick;
 use crate::standardized_types::base_data::traits::BaseData;
 use crate::messages::data_server_messaging::FundForgeError;
 use crate::standardized_types::enums::MarketType;
-use crate::standardized_types::subscriptions::{DataSubscription, Symbol};
+use crate::standardized_types::subscriptions::{DataSubscription, Symbol, SymbolName};
 use crate::standardized_types::bytes_trait::Bytes;
 use chrono::{DateTime, TimeZone, Utc};
 use chrono_tz::Tz;
@@ -30,6 +32,8 @@ use crate::standardized_types::resolution::Resolution;
 /// * `Tick`        see [`BaseDataEnum::Tick`](ff_data_vendors::base_data_types::base_data_enum::BaseDataEnum::Tick)
 /// * `Quote`       see [`BaseDataEnum::Quote`](ff_data_vendors::base_data_types::base_data_enum::BaseDataEnum::Quote)
 /// * `Fundamental` see [`BaseDataEnum::Fundamental`](ff_data_vendors::base_data_types::base_data_enum::BaseDataEnum::Fundamental)
+/// * `OrderBook`   see [`BaseDataEnum::OrderBook`](ff_data_vendors::base_data_types::base_data_enum::BaseDataEnum::OrderBook)
+/// * `MarketByOrder` see [`BaseDataEnum::MarketByOrder`](ff_data_vendors::base_data_types::base_data_enum::BaseDataEnum::MarketByOrder)
 #[derive(Clone, Serialize_rkyv, Deserialize_rkyv, Archive, PartialEq, Debug)]
 #[archive(compare(PartialEq), check_bytes)]
 #[archive_attr(derive(Debug))]
@@ -110,6 +114,28 @@ pub enum BaseDataEnum {
     /// * `bias` - `Bias` enum The bias of the fundamental data `Bias` enum variant.
     /// * `data_vendor` - `DataVendor` enum The data vendor of the fundamental data `DataVendor` enum variant.
     Fundamental(Fundamental),
+
+    /// The `OrderBook` struct is used to represent the market depth of an asset at a given time, N price levels on each side of the book. see [`OrderBook`](ff_data_vendors::base_data_types::order_book::OrderBook)
+    ///
+    /// # Properties
+    /// * `symbol` - The symbol of the asset.
+    /// * `bids` - The bid `BookLevel`s, level 0 is the best bid.
+    /// * `asks` - The ask `BookLevel`s, level 0 is the best ask.
+    /// * `time` - The time of the snapshot.
+    OrderBook(OrderBook),
+
+    /// The `MarketByOrder` struct is used to represent a change to a single resting order in the exchange order book. see [`MarketByOrder`](ff_data_vendors::base_data_types::market_by_order::MarketByOrder)
+    ///
+    /// # Properties
+    /// * `symbol` - The symbol of the asset.
+    /// * `order_id` - The exchange order id of the resting order.
+    /// * `side` - `Buy` for a resting bid, `Sell` for a resting ask.
+    /// * `action` - `MarketByOrderAction` The change made to the order.
+    /// * `price` - The price of the order after the change.
+    /// * `volume` - The remaining volume of the order after the change.
+    /// * `priority` - The exchange queue priority of the order.
+    /// * `time` - The time of the event.
+    MarketByOrder(MarketByOrder),
 }
 
 impl Display for BaseDataEnum {
@@ -164,6 +190,28 @@ impl Display for BaseDataEnum {
                 fundamental.time,
                 fundamental.name
             ),
+            BaseDataEnum::OrderBook(book) => write!(
+                f,
+                "{}: {}, {}: {}, {}, {}",
+                book.symbol.name,
+                book.symbol.data_vendor,
+                book.best_bid().map(|level| level.price.to_string()).unwrap_or_default(),
+                book.best_ask().map(|level| level.price.to_string()).unwrap_or_default(),
+                book.depth(),
+                book.time
+            ),
+            BaseDataEnum::MarketByOrder(mbo) => write!(
+                f,
+                "{}: {}, {:?} {} {}: {} @ {}, {}",
+                mbo.symbol.name,
+                mbo.symbol.data_vendor,
+                mbo.action,
+                mbo.side,
+                mbo.order_id,
+                mbo.volume,
+                mbo.price,
+                mbo.time
+            ),
         }
     }
 }
@@ -188,6 +236,8 @@ impl BaseDataEnum {
             BaseDataEnum::Tick(_) => BaseDataType::Ticks,
             BaseDataEnum::Quote(_) => BaseDataType::Quotes,
             BaseDataEnum::Fundamental(_) => BaseDataType::Fundamentals,
+            BaseDataEnum::OrderBook(_) => BaseDataType::OrderBooks,
+            BaseDataEnum::MarketByOrder(_) => BaseDataType::MarketByOrder,
         }
     }
 
@@ -199,6 +249,19 @@ impl BaseDataEnum {
         }
     }
 
+    /// Renames the symbol of the data, used to give the data of each futures contract the symbol name of a continuous series.
+    pub fn set_symbol_name(&mut self, symbol_name: SymbolName) {
+        match self {
+            BaseDataEnum::Candle(candle) => candle.symbol.name = symbol_name,
+            BaseDataEnum::QuoteBar(bar) => bar.symbol.name = symbol_name,
+            BaseDataEnum::Tick(tick) => tick.symbol.name = symbol_name,
+            BaseDataEnum::Quote(quote) => quote.symbol.name = symbol_name,
+            BaseDataEnum::Fundamental(fundamental) => fundamental.symbol.name = symbol_name,
+            BaseDataEnum::OrderBook(book) => book.symbol.name = symbol_name,
+            BaseDataEnum::MarketByOrder(mbo) => mbo.symbol.name = symbol_name,
+        }
+    }
+
     /// Deserializes from `Vec<u8>` to `Vec<BaseDataEnum>`
     pub fn from_array_bytes(data: &Vec<u8>) -> Result<Vec<BaseDataEnum>, Error> {
         let archived_quotebars = match rkyv::check_archived_root::<Vec<BaseDataEnum>>(&data[..]) {
@@ -262,6 +325,8 @@ impl BaseData for BaseDataEnum {
             BaseDataEnum::Tick(tick) => tick.symbol.clone(),
             BaseDataEnum::Quote(quote) => quote.symbol.clone(),
             BaseDataEnum::Fundamental(fundamental) => fundamental.symbol.clone(),
+            BaseDataEnum::OrderBook(book) => book.symbol.clone(),
+            BaseDataEnum::MarketByOrder(mbo) => mbo.symbol.clone(),
         }
     }
 
@@ -280,6 +345,8 @@ impl BaseData for BaseDataEnum {
             BaseDataEnum::Fundamental(fundamental) => {
                 DateTime::from_str(&fundamental.time).unwrap()
             }
+            BaseDataEnum::OrderBook(book) => DateTime::from_str(&book.time).unwrap(),
+            BaseDataEnum::MarketByOrder(mbo) => DateTime::from_str(&mbo.time).unwrap(),
         }
     }
 
@@ -292,6 +359,8 @@ impl BaseData for BaseDataEnum {
             BaseDataEnum::Tick(tick) => tick.time_utc(),
             BaseDataEnum::Quote(quote) => quote.time_utc(),
             BaseDataEnum::Fundamental(fundamental) => fundamental.time_utc(),
+            BaseDataEnum::OrderBook(book) => book.time_utc(),
+            BaseDataEnum::MarketByOrder(mbo) => mbo.time_utc(),
         }
     }
 
@@ -307,6 +376,8 @@ impl BaseData for BaseDataEnum {
             BaseDataEnum::Tick(tick) => tick.symbol.data_vendor.clone(),
             BaseDataEnum::Quote(quote) => quote.symbol.data_vendor.clone(),
             BaseDataEnum::Fundamental(fundamental) => fundamental.symbol.data_vendor.clone(),
+            BaseDataEnum::OrderBook(book) => book.symbol.data_vendor,
+            BaseDataEnum::MarketByOrder(mbo) => mbo.symbol.data_vendor,
         }
     }
 
@@ -317,6 +388,8 @@ impl BaseData for BaseDataEnum {
             BaseDataEnum::Tick(tick) => tick.symbol.market_type.clone(),
             BaseDataEnum::Quote(quote) => quote.symbol.market_type.clone(),
             BaseDataEnum::Fundamental(fundamental) => fundamental.symbol.market_type.clone(),
+            BaseDataEnum::OrderBook(book) => book.symbol.market_type,
+            BaseDataEnum::MarketByOrder(mbo) => mbo.symbol.market_type,
         }
     }
 
@@ -338,6 +411,8 @@ impl BaseData for BaseDataEnum {
             BaseDataEnum::Tick(tick) => &tick.symbol,
             BaseDataEnum::Quote(quote) => &quote.symbol,
             BaseDataEnum::Fundamental(fundamental) => &fundamental.symbol,
+            BaseDataEnum::OrderBook(book) => &book.symbol,
+            BaseDataEnum::MarketByOrder(mbo) => &mbo.symbol,
         }
     }
 
@@ -1,4 +1,6 @@
 use crate::standardized_types::base_data::candle::Candle;
+use crate::standardized_types::base_data::market_by_order::MarketByOrder;
+use crate::standardized_types::base_data::order_book::OrderBook;
 use crate::standardized_types::base_data::quote::Quote;
 use crate::standardized_types::base_data::quotebar::QuoteBar;
 use crate::standardized_types::base_data::tick::Tick;
@@ -34,6 +36,8 @@ pub enum BaseDataType {
     QuoteBars = 2,
     Candles = 3,
     Fundamentals = 4,
+    OrderBooks = 5,
+    MarketByOrder = 6,
 }
 impl BaseDataType {
     // Function to get_requests the TypeId of the associated data type
@@ -44,7 +48,8 @@ impl BaseDataType {
             BaseDataType::QuoteBars => TypeId::of::<QuoteBar>(),
             BaseDataType::Candles => TypeId::of::<Candle>(),
             BaseDataType::Fundamentals => TypeId::of::<String>(),
-            //BaseDataType::OrderBooks => TypeId::of::<OrderBook>(),
+            BaseDataType::OrderBooks => TypeId::of::<OrderBook>(),
+            BaseDataType::MarketByOrder => TypeId::of::<MarketByOrder>(),
         }
     }
 
@@ -56,7 +61,8 @@ impl BaseDataType {
             "Quotebars" => Ok(BaseDataType::QuoteBars),
             "Candles" => Ok(BaseDataType::Candles),
             "Fundamentals" => Ok(BaseDataType::Fundamentals),
-            // "order books" => Ok(BaseDataType::OrderBooks),
+            "Orderbooks" => Ok(BaseDataType::OrderBooks),
+            "Marketbyorder" => Ok(BaseDataType::MarketByOrder),
             _ => Err(format!("Unknown BaseDataType: {}", string_ref)),
         }
     }
@@ -69,7 +75,8 @@ impl BaseDataType {
             BaseDataType::QuoteBars => "Quotebars".to_string(),
             BaseDataType::Candles => "Candles".to_string(),
             BaseDataType::Fundamentals => "Fundamentals".to_string(),
-            //BaseDataType::OrderBooks => "order books".to_string(),
+            BaseDataType::OrderBooks => "Orderbooks".to_string(),
+            BaseDataType::MarketByOrder => "Marketbyorder".to_string(),
         }
     }
 }
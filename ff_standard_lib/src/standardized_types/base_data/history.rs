@@ -17,9 +17,10 @@ use tokio::sync::oneshot;
 use crate::strategies::client_features::connection_types::ConnectionType;
 use crate::standardized_types::enums::{StrategyMode, PrimarySubscription};
 use crate::standardized_types::market_hours::TradingHours;
-use crate::strategies::client_features::request_handler::{send_request, StrategyRequest};
+use crate::strategies::client_features::request_handler::{DataServerSender, StrategyRequest};
 use crate::strategies::client_features::server_connections::SETTINGS_MAP;
 use crate::strategies::consolidators::consolidator_enum::ConsolidatorEnum;
+use crate::standardized_types::continuous_contracts::{adjust_base_data, BackAdjustment, RollRule, RollSchedule};
 
 
 // Helper function to process a single compressed payload
@@ -92,6 +93,7 @@ pub async fn get_compressed_historical_data(
     subscriptions: Vec<DataSubscription>,
     from_time: DateTime<Utc>,
     to_time: DateTime<Utc>,
+    data_server_sender: &DataServerSender,
 ) -> Result<BTreeMap<i64, TimeSlice>, FundForgeError> {
     let connections = SETTINGS_MAP.clone();
     if connections.len() <= 2 {
@@ -108,7 +110,7 @@ pub async fn get_compressed_historical_data(
             tx
         );
         //println!("{:?}", request);
-        send_request(request).await;
+        data_server_sender.send(request).await;
         let response = rx.await.expect(&"Failed to receive callback data");
         //println!("Received data response for {} to {}", from_time, to_time);
         match response {
@@ -148,7 +150,7 @@ pub async fn get_compressed_historical_data(
                 );
 
                 async move {
-                    send_request(request).await;
+                    data_server_sender.send(request).await;
                     let response = rx.await.expect(&"Failed to receive callback data");
 
                     match response {
@@ -182,6 +184,82 @@ pub async fn get_compressed_historical_data(
     }
 }
 
+/// Requests the contracts of a continuous futures series from the data server, `subscription` has the symbol name of the product, for example "ES".
+pub async fn get_roll_schedule(
+    subscription: DataSubscription,
+    roll_rule: RollRule,
+    from_time: DateTime<Utc>,
+    to_time: DateTime<Utc>,
+    data_server_sender: &DataServerSender,
+) -> Result<RollSchedule, FundForgeError> {
+    let vendor_connection = ConnectionType::Vendor(subscription.symbol.data_vendor);
+    let connection_type = match SETTINGS_MAP.contains_key(&vendor_connection) {
+        true => vendor_connection,
+        false => ConnectionType::Default,
+    };
+    let (tx, rx) = oneshot::channel();
+    let request = StrategyRequest::CallBack(
+        connection_type,
+        DataServerRequest::RollSchedule {
+            callback_id: 0,
+            subscription,
+            roll_rule,
+            from_time: from_time.to_string(),
+            to_time: to_time.to_string(),
+        },
+        tx
+    );
+    data_server_sender.send(request).await;
+    match rx.await {
+        Ok(DataServerResponse::RollSchedule { schedule, .. }) => Ok(schedule),
+        Ok(DataServerResponse::Error { error, .. }) => Err(error),
+        Ok(_) => Err(FundForgeError::UnknownBlameError("Incorrect response received at callback".to_string())),
+        Err(e) => Err(FundForgeError::ClientSideErrorDebug(format!("Failed to receive roll schedule: {}", e))),
+    }
+}
+
+/// A range the data server has no data for is empty, the same as a weekend or holiday, any other error is returned.
+pub(crate) fn no_data_as_empty(result: Result<BTreeMap<i64, TimeSlice>, FundForgeError>) -> Result<BTreeMap<i64, TimeSlice>, FundForgeError> {
+    match result {
+        Err(FundForgeError::NoDataInRange(_)) => Ok(BTreeMap::new()),
+        result => result,
+    }
+}
+
+/// Stitches the data of each contract in the schedule into one series for the continuous `subscription`.
+/// The data is renamed to the symbol name of the subscription and the prices are adjusted for every roll in the schedule after the data.
+pub async fn get_continuous_historical_data(
+    subscription: &DataSubscription,
+    schedule: &RollSchedule,
+    adjustment: BackAdjustment,
+    from_time: DateTime<Utc>,
+    to_time: DateTime<Utc>,
+    data_server_sender: &DataServerSender,
+) -> Result<BTreeMap<i64, TimeSlice>, FundForgeError> {
+    let mut combined_data: BTreeMap<i64, TimeSlice> = BTreeMap::new();
+    for (symbol_code, from, to) in schedule.periods(from_time, to_time) {
+        let mut contract_subscription = subscription.clone();
+        contract_subscription.symbol.name = symbol_code;
+        let value = schedule.adjustment_at(to, adjustment);
+        let time_slices = no_data_as_empty(get_compressed_historical_data(vec![contract_subscription], from, to, data_server_sender).await)?;
+        for (timestamp, time_slice) in time_slices {
+            let adjusted: TimeSlice = time_slice.iter()
+                .cloned()
+                .map(|mut data| {
+                    data.set_symbol_name(subscription.symbol.name.clone());
+                    adjust_base_data(&mut data, adjustment, value);
+                    data
+                })
+                .collect();
+            combined_data
+                .entry(timestamp)
+                .and_modify(|existing: &mut TimeSlice| existing.merge(adjusted.clone()))
+                .or_insert(adjusted);
+        }
+    }
+    Ok(combined_data)
+}
+
 pub fn get_lowest_resolution(
     all_symbol_subscriptions: &HashMap<Symbol, Vec<DataSubscription>>,
     symbol: &Symbol,
@@ -203,14 +281,15 @@ pub async fn range_history_data(
     subscription: DataSubscription,
     mode: StrategyMode,
     market_hours: Option<TradingHours>,
+    data_server_sender: &DataServerSender,
 ) -> BTreeMap<DateTime<Utc>, BaseDataEnum> {
     if from_time > to_time {
         panic!("From time cannot be greater than to time");
     }
     let sub_res_type = PrimarySubscription::new(subscription.resolution, subscription.base_data_type);
-    let resolutions = subscription.symbol.data_vendor.warm_up_resolutions(subscription.symbol.market_type).await.unwrap();
+    let resolutions = subscription.symbol.data_vendor.warm_up_resolutions(subscription.symbol.market_type, data_server_sender).await.unwrap();
     if resolutions.contains(&sub_res_type) {
-        let data = match get_compressed_historical_data(vec![subscription.clone()], from_time, to_time).await {
+        let data = match get_compressed_historical_data(vec![subscription.clone()], from_time, to_time, data_server_sender).await {
             Ok(data) => {
                 data
             }
@@ -239,7 +318,7 @@ pub async fn range_history_data(
 
         let history_to_retain = duration_ns / resolution_ns;
         let consolidator = ConsolidatorEnum::create_consolidator(subscription, false, market_hours).await;
-        let (_, window) = ConsolidatorEnum::warmup(consolidator, to_time, history_to_retain as i32, mode).await;
+        let (_, window) = ConsolidatorEnum::warmup(consolidator, to_time, history_to_retain as i32, mode, data_server_sender).await;
         let mut map:BTreeMap<DateTime<Utc>, BaseDataEnum> = BTreeMap::new();
         for base_data in window.history() {
             let data_time = base_data.time_closed_utc();
@@ -265,3 +344,22 @@ pub fn generate_file_dates(
     }
     month_years
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_data_as_empty() {
+        let no_data = no_data_as_empty(Err(FundForgeError::NoDataInRange("No files found in range".to_string())));
+        assert!(no_data.unwrap().is_empty());
+
+        // connection and server errors are not a gap in the data
+        let server_error = no_data_as_empty(Err(FundForgeError::ServerErrorDebug("Timeout reading file".to_string())));
+        assert!(matches!(server_error, Err(FundForgeError::ServerErrorDebug(_))));
+
+        let mut time_slices = BTreeMap::new();
+        time_slices.insert(1, TimeSlice::new());
+        assert_eq!(no_data_as_empty(Ok(time_slices)).unwrap().len(), 1);
+    }
+}
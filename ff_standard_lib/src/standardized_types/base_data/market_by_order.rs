@@ -0,0 +1,166 @@
+use crate::standardized_types::base_data::base_data_type::BaseDataType;
+use crate::standardized_types::enums::{MarketType, OrderSide};
+use crate::standardized_types::subscriptions::{DataSubscription, Symbol};
+use chrono::{DateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use rkyv::{Archive, Deserialize as Deserialize_rkyv, Serialize as Serialize_rkyv};
+use std::fmt;
+use std::fmt::{Debug, Display};
+use std::str::FromStr;
+use crate::standardized_types::datavendor_enum::DataVendor;
+use crate::standardized_types::base_data::traits::BaseData;
+use crate::standardized_types::new_types::{Price, TimeString, Volume};
+use crate::standardized_types::resolution::Resolution;
+
+#[derive(Clone, Serialize_rkyv, Deserialize_rkyv, Archive, PartialEq, Eq, Copy, Debug)]
+#[archive(compare(PartialEq), check_bytes)]
+#[archive_attr(derive(Debug))]
+/// The change a `MarketByOrder` event makes to a resting order.
+pub enum MarketByOrderAction {
+    /// A new order joined the back of the queue at its price.
+    Add,
+    /// The price or volume of a resting order changed.
+    Modify,
+    /// The order left the book, it was filled or cancelled.
+    Delete,
+}
+
+#[derive(Clone, Serialize_rkyv, Deserialize_rkyv, Archive, PartialEq)]
+#[archive(compare(PartialEq), check_bytes)]
+#[archive_attr(derive(Debug))]
+/// A `MarketByOrder` event is a change to a single resting order in the exchange order book (market by order depth).
+///
+/// # Parameters
+/// * `symbol: Symbol` - The symbol of the asset.
+/// * `order_id: String` - The exchange order id of the resting order.
+/// * `side: OrderSide` - `Buy` for a resting bid, `Sell` for a resting ask.
+/// * `action: MarketByOrderAction` - The change made to the order.
+/// * `price: Price` - The price of the order after the change.
+/// * `volume: Volume` - The remaining volume of the order after the change, 0 for a delete.
+/// * `priority: u64` - The exchange queue priority of the order, lower is closer to the front.
+/// * `time: String` - The time of the event.
+pub struct MarketByOrder {
+    pub symbol: Symbol,
+    pub order_id: String,
+    pub side: OrderSide,
+    pub action: MarketByOrderAction,
+    pub price: Price,
+    pub volume: Volume,
+    pub priority: u64,
+    pub time: TimeString,
+}
+
+impl BaseData for MarketByOrder {
+    fn symbol_name(&self) -> Symbol {
+        self.symbol.clone()
+    }
+
+    fn time_local(&self, time_zone: &Tz) -> DateTime<Tz> {
+        let utc_time: DateTime<Utc> = DateTime::from_str(&self.time).unwrap();
+        time_zone.from_utc_datetime(&utc_time.naive_utc())
+    }
+
+    fn time_utc(&self) -> DateTime<Utc> {
+        DateTime::from_str(&self.time).unwrap()
+    }
+
+    fn time_closed_utc(&self) -> DateTime<Utc> {
+        self.time_utc()
+    }
+
+    fn time_closed_local(&self, time_zone: &Tz) -> DateTime<Tz> {
+        time_zone.from_utc_datetime(&self.time_utc().naive_utc())
+    }
+
+    fn data_vendor(&self) -> DataVendor {
+        self.symbol.data_vendor
+    }
+
+    fn market_type(&self) -> MarketType {
+        self.symbol.market_type
+    }
+
+    fn resolution(&self) -> Resolution {
+        Resolution::Instant
+    }
+
+    fn symbol(&self) -> &Symbol {
+        &self.symbol
+    }
+
+    fn subscription(&self) -> DataSubscription {
+        let symbol = self.symbol.clone();
+        let resolution = self.resolution();
+        let candle_type = None;
+        DataSubscription::from_base_data(
+            symbol.name.clone(),
+            symbol.data_vendor,
+            resolution,
+            BaseDataType::MarketByOrder,
+            symbol.market_type,
+            candle_type,
+        )
+    }
+}
+
+impl MarketByOrder {
+    /// Create a new `MarketByOrder` event with the given parameters.
+    ///
+    /// # Parameters
+    /// 1. `symbol` - The symbol of the asset.
+    /// 2. `order_id` - The exchange order id of the resting order.
+    /// 3. `side` - `Buy` for a resting bid, `Sell` for a resting ask.
+    /// 4. `action` - The change made to the order.
+    /// 5. `price` - The price of the order after the change.
+    /// 6. `volume` - The remaining volume of the order after the change.
+    /// 7. `priority` - The exchange queue priority of the order.
+    /// 8. `time` - The time of the event.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        symbol: Symbol,
+        order_id: String,
+        side: OrderSide,
+        action: MarketByOrderAction,
+        price: Price,
+        volume: Volume,
+        priority: u64,
+        time: TimeString,
+    ) -> Self {
+        MarketByOrder {
+            symbol,
+            order_id,
+            side,
+            action,
+            price,
+            volume,
+            priority,
+            time,
+        }
+    }
+}
+
+impl Display for MarketByOrder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:?},{},{},{:?},{},{},{}",
+            self.symbol,
+            self.order_id,
+            self.side,
+            self.action,
+            self.price,
+            self.volume,
+            self.time,
+        )
+    }
+}
+
+impl Debug for MarketByOrder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "MarketByOrder {{ symbol: {:?}, order_id: {}, side: {}, action: {:?}, price: {}, volume: {}, priority: {}, time: {} }}",
+            self.symbol, self.order_id, self.side, self.action, self.price, self.volume, self.priority, self.time
+        )
+    }
+}
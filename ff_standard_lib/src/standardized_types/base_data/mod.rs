@@ -3,6 +3,8 @@ pub mod base_data_enum;
 pub mod candle;
 pub mod fundamental;
 pub mod history;
+pub mod market_by_order;
+pub mod order_book;
 pub mod quotebar;
 pub mod quote;
 pub mod tick;
@@ -0,0 +1,242 @@
+use crate::standardized_types::base_data::base_data_type::BaseDataType;
+use crate::standardized_types::enums::MarketType;
+use crate::standardized_types::subscriptions::{DataSubscription, Symbol};
+use chrono::{DateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use rkyv::{Archive, Deserialize as Deserialize_rkyv, Serialize as Serialize_rkyv};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fmt::{Debug, Display};
+use std::str::FromStr;
+use rust_decimal::Decimal;
+use crate::standardized_types::books::BookLevel;
+use crate::standardized_types::datavendor_enum::DataVendor;
+use crate::standardized_types::base_data::traits::BaseData;
+use crate::standardized_types::new_types::{Price, TimeString, Volume};
+use crate::standardized_types::resolution::Resolution;
+
+#[derive(Clone, Serialize_rkyv, Deserialize_rkyv, Archive, PartialEq)]
+#[archive(compare(PartialEq), check_bytes)]
+#[archive_attr(derive(Debug))]
+/// An `OrderBook` is a snapshot of the market depth for a given symbol, N price levels on each side of the book.
+///
+/// # Parameters
+/// * `symbol: Symbol` - The symbol of the asset.
+/// * `bids: Vec<BookLevel>` - The bid levels, level 0 is the best (highest) bid.
+/// * `asks: Vec<BookLevel>` - The ask levels, level 0 is the best (lowest) ask.
+/// * `time: String` - The time of the snapshot.
+pub struct OrderBook {
+    pub symbol: Symbol,
+    pub bids: Vec<BookLevel>,
+    pub asks: Vec<BookLevel>,
+    pub time: TimeString,
+}
+
+impl BaseData for OrderBook {
+    fn symbol_name(&self) -> Symbol {
+        self.symbol.clone()
+    }
+
+    fn time_local(&self, time_zone: &Tz) -> DateTime<Tz> {
+        let utc_time: DateTime<Utc> = DateTime::from_str(&self.time).unwrap();
+        time_zone.from_utc_datetime(&utc_time.naive_utc())
+    }
+
+    fn time_utc(&self) -> DateTime<Utc> {
+        DateTime::from_str(&self.time).unwrap()
+    }
+
+    fn time_closed_utc(&self) -> DateTime<Utc> {
+        self.time_utc()
+    }
+
+    fn time_closed_local(&self, time_zone: &Tz) -> DateTime<Tz> {
+        time_zone.from_utc_datetime(&self.time_utc().naive_utc())
+    }
+
+    fn data_vendor(&self) -> DataVendor {
+        self.symbol.data_vendor
+    }
+
+    fn market_type(&self) -> MarketType {
+        self.symbol.market_type
+    }
+
+    fn resolution(&self) -> Resolution {
+        Resolution::Instant
+    }
+
+    fn symbol(&self) -> &Symbol {
+        &self.symbol
+    }
+
+    fn subscription(&self) -> DataSubscription {
+        let symbol = self.symbol.clone();
+        let resolution = self.resolution();
+        let candle_type = None;
+        DataSubscription::from_base_data(
+            symbol.name.clone(),
+            symbol.data_vendor,
+            resolution,
+            BaseDataType::OrderBooks,
+            symbol.market_type,
+            candle_type,
+        )
+    }
+}
+
+impl OrderBook {
+    /// Create a new `OrderBook` with the given parameters.
+    ///
+    /// # Parameters
+    /// 1. `symbol` - The symbol of the asset.
+    /// 2. `bids` - The bid levels, best bid first.
+    /// 3. `asks` - The ask levels, best ask first.
+    /// 4. `time` - The time of the snapshot.
+    pub fn new(
+        symbol: Symbol,
+        bids: Vec<BookLevel>,
+        asks: Vec<BookLevel>,
+        time: TimeString,
+    ) -> Self {
+        OrderBook {
+            symbol,
+            bids,
+            asks,
+            time,
+        }
+    }
+
+    /// Create an `OrderBook` from price -> volume maps of each side, keeping the best `depth` levels.
+    /// Prices with zero volume are skipped.
+    pub fn from_price_levels(
+        symbol: Symbol,
+        bids: &BTreeMap<Price, Volume>,
+        asks: &BTreeMap<Price, Volume>,
+        depth: usize,
+        time: TimeString,
+    ) -> Self {
+        let bids = bids.iter()
+            .rev()
+            .filter(|(_, volume)| **volume > Decimal::ZERO)
+            .take(depth)
+            .enumerate()
+            .map(|(level, (price, volume))| BookLevel::new(level as u16, *price, *volume))
+            .collect();
+        let asks = asks.iter()
+            .filter(|(_, volume)| **volume > Decimal::ZERO)
+            .take(depth)
+            .enumerate()
+            .map(|(level, (price, volume))| BookLevel::new(level as u16, *price, *volume))
+            .collect();
+        OrderBook::new(symbol, bids, asks, time)
+    }
+
+    pub fn best_bid(&self) -> Option<&BookLevel> {
+        self.bids.first()
+    }
+
+    pub fn best_ask(&self) -> Option<&BookLevel> {
+        self.asks.first()
+    }
+
+    /// The number of levels on the deeper side of the book.
+    pub fn depth(&self) -> usize {
+        self.bids.len().max(self.asks.len())
+    }
+
+    /// The volume weighted average price to fill `volume` against the book, buys walk the asks and sells walk the bids.
+    /// If the book is too thin the remaining volume is filled at the last visible level, returns `None` for an empty side.
+    pub fn fill_price(&self, is_buy: bool, volume: Volume) -> Option<Price> {
+        let levels = match is_buy {
+            true => &self.asks,
+            false => &self.bids,
+        };
+        let last = levels.last()?;
+        let mut remaining = volume;
+        let mut total_price_volume = Decimal::ZERO;
+        for level in levels {
+            if remaining <= Decimal::ZERO {
+                break;
+            }
+            let fill_volume = remaining.min(level.volume);
+            total_price_volume += level.price * fill_volume;
+            remaining -= fill_volume;
+        }
+        if remaining > Decimal::ZERO {
+            total_price_volume += last.price * remaining;
+        }
+        match volume > Decimal::ZERO {
+            true => Some(total_price_volume / volume),
+            false => Some(levels[0].price),
+        }
+    }
+}
+
+impl Display for OrderBook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let best_bid = self.best_bid().map(|level| level.price.to_string()).unwrap_or_default();
+        let best_ask = self.best_ask().map(|level| level.price.to_string()).unwrap_or_default();
+        write!(
+            f,
+            "{:?},{},{},{},{}",
+            self.symbol,
+            best_bid,
+            best_ask,
+            self.depth(),
+            self.time,
+        )
+    }
+}
+
+impl Debug for OrderBook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "OrderBook {{ symbol: {:?}, bids: {:?}, asks: {:?}, time: {} }}",
+            self.symbol, self.bids, self.asks, self.time
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+    use crate::standardized_types::enums::FuturesExchange;
+
+    fn test_book() -> OrderBook {
+        let symbol = Symbol::new("ES".to_string(), DataVendor::Rithmic, MarketType::Futures(FuturesExchange::CME));
+        let mut bids = BTreeMap::new();
+        bids.insert(dec!(99.75), dec!(5));
+        bids.insert(dec!(99.50), dec!(10));
+        bids.insert(dec!(99.25), dec!(0));
+        let mut asks = BTreeMap::new();
+        asks.insert(dec!(100.00), dec!(2));
+        asks.insert(dec!(100.25), dec!(3));
+        asks.insert(dec!(100.50), dec!(20));
+        OrderBook::from_price_levels(symbol, &bids, &asks, 2, Utc::now().to_string())
+    }
+
+    #[test]
+    fn test_from_price_levels() {
+        let book = test_book();
+        assert_eq!(book.bids.len(), 2);
+        assert_eq!(book.asks.len(), 2);
+        assert_eq!(book.best_bid().unwrap().price, dec!(99.75));
+        assert_eq!(book.best_ask().unwrap().price, dec!(100.00));
+        assert_eq!(book.bids[1].level(), 1);
+        assert_eq!(book.bids[1].price, dec!(99.50));
+        assert_eq!(book.asks[1].price, dec!(100.25));
+    }
+
+    #[test]
+    fn test_fill_price_walks_depth() {
+        let book = test_book();
+        assert_eq!(book.fill_price(true, dec!(1)), Some(dec!(100.00)));
+        // 2 @ 100.00 + 3 @ 100.25 = 500.75 / 5
+        assert_eq!(book.fill_price(true, dec!(5)), Some(dec!(100.15)));
+        // 5 @ 99.75 + 10 @ 99.50, the last 5 at the deepest visible level 99.50 = 1991.25 / 20
+        assert_eq!(book.fill_price(false, dec!(20)), Some(dec!(99.5625)));
+    }
+}
@@ -18,4 +18,9 @@ impl BookLevel {
             volume
         }
     }
+
+    /// The level in the order book where 0 is the best bid or best ask.
+    pub fn level(&self) -> u16 {
+        self.level
+    }
 }
\ No newline at end of file
@@ -6,6 +6,7 @@ use chrono_tz::{America, Tz};
 use chrono_tz::Tz::UTC;
 use crate::apis::rithmic::rithmic_systems::RithmicSystem;
 use crate::messages::data_server_messaging::FundForgeError;
+use crate::standardized_types::orders::OrderGroupType;
 
 #[derive(Serialize, Deserialize, Clone, Eq, Serialize_rkyv, Deserialize_rkyv,
     Archive, PartialEq, Debug, Hash, PartialOrd, Ord, Copy)]
@@ -27,6 +28,12 @@ impl Brokerage {
             Brokerage::Oanda => UTC,
         }
     }
+
+    /// Returns true if the brokerage links the orders in an order group natively,
+    /// otherwise the strategy emulates the group by placing and cancelling the orders itself.
+    pub fn supports_order_group(&self, group_type: &OrderGroupType) -> bool {
+        matches!((self, group_type), (Brokerage::Rithmic(_), OrderGroupType::OneCancelsOther))
+    }
 }
 
 impl fmt::Display for Brokerage {
@@ -0,0 +1,317 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use chrono::{DateTime, Duration, Utc};
+use rkyv::{Archive, Deserialize as Deserialize_rkyv, Serialize as Serialize_rkyv};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use strum_macros::Display;
+use crate::standardized_types::base_data::base_data_enum::BaseDataEnum;
+use crate::standardized_types::new_types::Price;
+use crate::standardized_types::subscriptions::{SymbolCode, SymbolName};
+
+/// How a continuous futures series decides when to roll from one contract to the next.
+#[derive(Clone, Serialize_rkyv, Deserialize_rkyv, Archive, PartialEq, Eq, Copy, Debug, Display, Hash)]
+#[archive(compare(PartialEq), check_bytes)]
+#[archive_attr(derive(Debug))]
+pub enum RollRule {
+    /// Rolls at the start of the rollover day of the symbol in `CONTRACT_SPECS`, to the contract returned by `get_front_month()`.
+    Calendar,
+    /// Rolls at the start of the day after the next contract traded more volume than the current contract.
+    Volume,
+    /// Rolls at the start of the day after the next contract had more open interest than the current contract.
+    /// Open interest is read from `Fundamental` data named "open_interest" stored for each contract, with the value in `values["open_interest"]`.
+    OpenInterest,
+}
+
+/// How the prices of earlier contracts are adjusted so the continuous series does not jump at each roll.
+#[derive(Clone, PartialEq, Eq, Copy, Debug, Display, Hash)]
+pub enum BackAdjustment {
+    /// Adds the price gap between the contracts at each roll to all earlier prices, point moves are unchanged. Earlier prices can become negative.
+    Panama,
+    /// Multiplies all earlier prices by the ratio of the contract prices at each roll, percentage moves are unchanged.
+    Ratio,
+    /// Stitches the contracts without adjusting prices, the series jumps at each roll.
+    None,
+}
+
+/// A continuous series for a futures product, subscriptions to `symbol_name` in backtests receive the stitched data of each contract.
+/// The data keeps the symbol name of the product, the strategy receives a `StrategyEvent::RollEvent` when the series moves to the next `SymbolCode`.
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub struct ContinuousContract {
+    pub symbol_name: SymbolName,
+    pub roll_rule: RollRule,
+    pub adjustment: BackAdjustment,
+}
+
+impl ContinuousContract {
+    pub fn new(symbol_name: SymbolName, roll_rule: RollRule, adjustment: BackAdjustment) -> Self {
+        Self {
+            symbol_name,
+            roll_rule,
+            adjustment,
+        }
+    }
+}
+
+/// A continuous series moving from one contract to the next.
+/// Data closing at or before `time` is from the old contract, data closing after `time` is from the new contract.
+#[derive(Clone, Serialize_rkyv, Deserialize_rkyv, Archive, PartialEq, Debug)]
+#[archive(compare(PartialEq), check_bytes)]
+#[archive_attr(derive(Debug))]
+pub struct RollEvent {
+    pub symbol_name: SymbolName,
+    pub from_symbol_code: SymbolCode,
+    pub to_symbol_code: SymbolCode,
+    pub time: String,
+    /// The last price of the old contract at the roll, before adjustment.
+    pub from_price: Price,
+    /// The last price of the new contract at the roll, before adjustment.
+    pub to_price: Price,
+}
+
+impl RollEvent {
+    pub fn time_utc(&self) -> DateTime<Utc> {
+        DateTime::from_str(&self.time).unwrap()
+    }
+
+    /// The price difference between the new and old contract, added to earlier prices by `BackAdjustment::Panama`.
+    pub fn gap(&self) -> Price {
+        self.to_price - self.from_price
+    }
+
+    /// The price ratio of the new and old contract, earlier prices are multiplied by it with `BackAdjustment::Ratio`.
+    pub fn ratio(&self) -> Decimal {
+        match self.from_price.is_zero() {
+            true => dec!(1),
+            false => self.to_price / self.from_price,
+        }
+    }
+}
+
+impl Display for RollEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} Roll: {} @ {} -> {} @ {}, {}", self.symbol_name, self.from_symbol_code, self.from_price, self.to_symbol_code, self.to_price, self.time)
+    }
+}
+
+/// The contracts of a continuous series between two times, calculated by the data server from the `RollRule`.
+#[derive(Clone, Serialize_rkyv, Deserialize_rkyv, Archive, PartialEq, Debug)]
+#[archive(compare(PartialEq), check_bytes)]
+#[archive_attr(derive(Debug))]
+pub struct RollSchedule {
+    pub symbol_name: SymbolName,
+    /// The contract at the start of the schedule.
+    pub first_symbol_code: SymbolCode,
+    /// The rolls in time order.
+    pub rolls: Vec<RollEvent>,
+}
+
+impl RollSchedule {
+    /// The contract of data closing at `time`.
+    pub fn symbol_code_at(&self, time: DateTime<Utc>) -> &SymbolCode {
+        self.rolls.iter()
+            .rfind(|roll| roll.time_utc() < time)
+            .map(|roll| &roll.to_symbol_code)
+            .unwrap_or(&self.first_symbol_code)
+    }
+
+    /// The contract of each part of the range, with the first and last time of the part, the parts do not overlap.
+    pub fn periods(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<(SymbolCode, DateTime<Utc>, DateTime<Utc>)> {
+        let mut periods = vec![];
+        let mut symbol_code = self.symbol_code_at(from).clone();
+        let mut start = from;
+        for roll in &self.rolls {
+            let roll_time = roll.time_utc();
+            if roll_time < from || roll_time >= to {
+                continue;
+            }
+            periods.push((symbol_code, start, roll_time));
+            symbol_code = roll.to_symbol_code.clone();
+            start = roll_time + Duration::nanoseconds(1);
+        }
+        periods.push((symbol_code, start, to));
+        periods
+    }
+
+    /// The rolls after `from` up to and including `to`.
+    pub fn rolls_between(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<&RollEvent> {
+        self.rolls.iter()
+            .filter(|roll| {
+                let roll_time = roll.time_utc();
+                roll_time > from && roll_time <= to
+            })
+            .collect()
+    }
+
+    /// The adjustment of data closing at `time` for all the later rolls in the schedule.
+    /// The amount added to prices with `BackAdjustment::Panama`, the multiplier of prices with `BackAdjustment::Ratio`, and 0 with `BackAdjustment::None`.
+    pub fn adjustment_at(&self, time: DateTime<Utc>, adjustment: BackAdjustment) -> Decimal {
+        let later_rolls = self.rolls.iter().filter(|roll| roll.time_utc() >= time);
+        match adjustment {
+            BackAdjustment::Panama => later_rolls.map(|roll| roll.gap()).sum(),
+            BackAdjustment::Ratio => later_rolls.fold(dec!(1), |factor, roll| factor * roll.ratio()),
+            BackAdjustment::None => dec!(0),
+        }
+    }
+}
+
+/// Adjusts the prices of the data by the value from `RollSchedule::adjustment_at()`, spreads and ranges are only changed by `BackAdjustment::Ratio`.
+pub fn adjust_base_data(data: &mut BaseDataEnum, adjustment: BackAdjustment, value: Decimal) {
+    let adjust = |price: &mut Price| match adjustment {
+        BackAdjustment::Panama => *price += value,
+        BackAdjustment::Ratio => *price *= value,
+        BackAdjustment::None => {}
+    };
+    let scale = |amount: &mut Price| {
+        if adjustment == BackAdjustment::Ratio {
+            *amount *= value;
+        }
+    };
+    match data {
+        BaseDataEnum::Candle(candle) => {
+            for price in [&mut candle.open, &mut candle.high, &mut candle.low, &mut candle.close] {
+                adjust(price);
+            }
+            scale(&mut candle.range);
+        }
+        BaseDataEnum::QuoteBar(bar) => {
+            for price in [&mut bar.bid_open, &mut bar.bid_high, &mut bar.bid_low, &mut bar.bid_close, &mut bar.ask_open, &mut bar.ask_high, &mut bar.ask_low, &mut bar.ask_close] {
+                adjust(price);
+            }
+            scale(&mut bar.range);
+            scale(&mut bar.spread);
+        }
+        BaseDataEnum::Tick(tick) => adjust(&mut tick.price),
+        BaseDataEnum::Quote(quote) => {
+            adjust(&mut quote.bid);
+            adjust(&mut quote.ask);
+        }
+        BaseDataEnum::OrderBook(book) => {
+            for level in book.bids.iter_mut().chain(book.asks.iter_mut()) {
+                adjust(&mut level.price);
+            }
+        }
+        BaseDataEnum::MarketByOrder(mbo) => adjust(&mut mbo.price),
+        BaseDataEnum::Fundamental(_) => {}
+    }
+}
+
+/// The price used to measure the gap between contracts at a roll, the close of bars, the mid price of quotes and books and the price of ticks.
+pub fn reference_price(data: &BaseDataEnum) -> Option<Price> {
+    match data {
+        BaseDataEnum::Candle(candle) => Some(candle.close),
+        BaseDataEnum::QuoteBar(bar) => Some((bar.bid_close + bar.ask_close) / dec!(2)),
+        BaseDataEnum::Tick(tick) => Some(tick.price),
+        BaseDataEnum::Quote(quote) => Some((quote.bid + quote.ask) / dec!(2)),
+        BaseDataEnum::OrderBook(book) => match (book.best_bid(), book.best_ask()) {
+            (Some(bid), Some(ask)) => Some((bid.price + ask.price) / dec!(2)),
+            _ => None,
+        },
+        BaseDataEnum::MarketByOrder(_) | BaseDataEnum::Fundamental(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::standardized_types::base_data::candle::Candle;
+    use crate::standardized_types::datavendor_enum::DataVendor;
+    use crate::standardized_types::enums::{FuturesExchange, MarketType};
+    use crate::standardized_types::resolution::Resolution;
+    use crate::standardized_types::subscriptions::{CandleType, Symbol};
+
+    fn time(time: &str) -> DateTime<Utc> {
+        DateTime::from_str(time).unwrap()
+    }
+
+    fn roll(from_symbol_code: &str, to_symbol_code: &str, roll_time: &str, from_price: Price, to_price: Price) -> RollEvent {
+        RollEvent {
+            symbol_name: "ES".to_string(),
+            from_symbol_code: from_symbol_code.to_string(),
+            to_symbol_code: to_symbol_code.to_string(),
+            time: time(roll_time).to_string(),
+            from_price,
+            to_price,
+        }
+    }
+
+    fn schedule() -> RollSchedule {
+        RollSchedule {
+            symbol_name: "ES".to_string(),
+            first_symbol_code: "ESH24".to_string(),
+            rolls: vec![
+                roll("ESH24", "ESM24", "2024-03-09T06:00:00Z", dec!(5000), dec!(5050)),
+                roll("ESM24", "ESU24", "2024-06-09T05:00:00Z", dec!(5400), dec!(5460)),
+            ],
+        }
+    }
+
+    fn candle(close: Price) -> BaseDataEnum {
+        let symbol = Symbol::new("ESH24".to_string(), DataVendor::Rithmic, MarketType::Futures(FuturesExchange::CME));
+        let mut candle = Candle::new(symbol, close - dec!(2), dec!(10), dec!(5), dec!(5), "2024-03-01 14:00:00 UTC".to_string(), Resolution::Minutes(1), CandleType::CandleStick);
+        candle.high = close + dec!(1);
+        candle.low = close - dec!(3);
+        candle.close = close;
+        candle.range = dec!(4);
+        BaseDataEnum::Candle(candle)
+    }
+
+    #[test]
+    fn test_schedule_periods() {
+        let schedule = schedule();
+        assert_eq!(schedule.symbol_code_at(time("2024-01-10T00:00:00Z")), "ESH24");
+        // data closing at the roll is the last data of the old contract
+        assert_eq!(schedule.symbol_code_at(time("2024-03-09T06:00:00Z")), "ESH24");
+        assert_eq!(schedule.symbol_code_at(time("2024-03-09T06:01:00Z")), "ESM24");
+        assert_eq!(schedule.symbol_code_at(time("2024-07-01T00:00:00Z")), "ESU24");
+
+        let periods = schedule.periods(time("2024-03-01T00:00:00Z"), time("2024-04-01T00:00:00Z"));
+        assert_eq!(periods, vec![
+            ("ESH24".to_string(), time("2024-03-01T00:00:00Z"), time("2024-03-09T06:00:00Z")),
+            ("ESM24".to_string(), time("2024-03-09T06:00:00Z") + Duration::nanoseconds(1), time("2024-04-01T00:00:00Z")),
+        ]);
+        let periods = schedule.periods(time("2024-04-01T00:00:00Z"), time("2024-04-02T00:00:00Z"));
+        assert_eq!(periods, vec![("ESM24".to_string(), time("2024-04-01T00:00:00Z"), time("2024-04-02T00:00:00Z"))]);
+
+        assert_eq!(schedule.rolls_between(time("2024-03-09T00:00:00Z"), time("2024-03-10T00:00:00Z")).len(), 1);
+        assert!(schedule.rolls_between(time("2024-03-09T06:00:00Z"), time("2024-03-10T00:00:00Z")).is_empty());
+    }
+
+    #[test]
+    fn test_back_adjustment() {
+        let schedule = schedule();
+        let before_first_roll = time("2024-03-01T00:00:00Z");
+        let between_rolls = time("2024-04-01T00:00:00Z");
+        let after_last_roll = time("2024-07-01T00:00:00Z");
+
+        // panama adds the gaps of every later roll, 50 + 60
+        assert_eq!(schedule.adjustment_at(before_first_roll, BackAdjustment::Panama), dec!(110));
+        assert_eq!(schedule.adjustment_at(between_rolls, BackAdjustment::Panama), dec!(60));
+        assert_eq!(schedule.adjustment_at(after_last_roll, BackAdjustment::Panama), dec!(0));
+        assert_eq!(schedule.adjustment_at(before_first_roll, BackAdjustment::None), dec!(0));
+
+        let mut data = candle(dec!(4990));
+        adjust_base_data(&mut data, BackAdjustment::Panama, dec!(110));
+        match &data {
+            BaseDataEnum::Candle(candle) => {
+                assert_eq!((candle.open, candle.high, candle.low, candle.close, candle.range), (dec!(5098), dec!(5101), dec!(5097), dec!(5100), dec!(4)));
+            }
+            _ => unreachable!(),
+        }
+
+        // ratio multiplies by the new / old price of every later roll, 1 once all rolls have passed
+        let ratio = schedule.adjustment_at(time("2024-06-09T05:00:00Z") + Duration::nanoseconds(1), BackAdjustment::Ratio);
+        assert_eq!(ratio, dec!(1));
+        let ratio = schedule.adjustment_at(between_rolls, BackAdjustment::Ratio);
+        assert_eq!(ratio, dec!(5460) / dec!(5400));
+        let mut data = candle(dec!(5000));
+        adjust_base_data(&mut data, BackAdjustment::Ratio, dec!(1.01));
+        match &data {
+            BaseDataEnum::Candle(candle) => {
+                assert_eq!((candle.close, candle.range), (dec!(5050), dec!(4.04)));
+            }
+            _ => unreachable!(),
+        }
+        assert_eq!(reference_price(&data), Some(dec!(5050)));
+    }
+}
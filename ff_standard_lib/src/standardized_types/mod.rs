@@ -14,3 +14,4 @@ pub mod position;
 pub mod books;
 pub mod accounts;
 pub mod market_hours;
+pub mod continuous_contracts;
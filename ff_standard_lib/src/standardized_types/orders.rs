@@ -23,6 +23,8 @@ pub enum OrderRequest {
     Update{account: Account, order_id: OrderId, update: OrderUpdateType },
     CancelAll{account: Account},
     FlattenAllFor{account: Account},
+    /// Places a group of linked orders, see `OrderGroupType` for how the orders are linked.
+    CreateGroup{account: Account, group_id: OrderGroupId, group_type: OrderGroupType, orders: Vec<Order>},
 }
 
 impl OrderRequest {
@@ -33,6 +35,7 @@ impl OrderRequest {
             OrderRequest::Update { account,.. } => account.brokerage.clone(),
             OrderRequest::CancelAll { account,.. } => account.brokerage.clone(),
             OrderRequest::FlattenAllFor { account,.. } => account.brokerage.clone(),
+            OrderRequest::CreateGroup { account,.. } => account.brokerage,
         }
     }
 
@@ -43,6 +46,7 @@ impl OrderRequest {
             OrderRequest::Update { account,.. } => &account.account_id,
             OrderRequest::CancelAll { account,.. } => &account.account_id,
             OrderRequest::FlattenAllFor { account,.. } => &account.account_id,
+            OrderRequest::CreateGroup { account,.. } => &account.account_id,
         }
     }
 
@@ -53,6 +57,7 @@ impl OrderRequest {
             OrderRequest::Update { account,.. } =>  &account.account_id,
             OrderRequest::CancelAll { account,.. } =>  &account.account_id,
             OrderRequest::FlattenAllFor { account,.. } =>  &account.account_id,
+            OrderRequest::CreateGroup { account,.. } =>  &account.account_id,
         }
     }
 }
@@ -161,7 +166,9 @@ pub struct Order {
     pub state: OrderState,
     pub fees: Price,
     pub value: Price,
-    pub exchange: Option<String>
+    pub exchange: Option<String>,
+    /// The protective orders to attach to the position once this order fills, only used by `OrderType::EnterLong` and `OrderType::EnterShort`.
+    pub brackets: Option<Vec<ProtectiveOrder>>
 }
 
 impl Order {
@@ -205,7 +212,8 @@ impl Order {
             state: OrderState::Created,
             fees: dec!(0.0),
             value: dec!(0.0),
-            exchange
+            exchange,
+            brackets: None
         }
     }
 
@@ -245,7 +253,8 @@ impl Order {
             fees: dec!(0.0),
             value: dec!(0.0),
             account: account.clone(),
-            exchange
+            exchange,
+            brackets: None
         }
     }
 
@@ -285,7 +294,8 @@ impl Order {
             fees: dec!(0.0),
             value: dec!(0.0),
             account: account.clone(),
-            exchange
+            exchange,
+            brackets: None
         }
     }
 
@@ -326,7 +336,8 @@ impl Order {
             fees: dec!(0.0),
             value: dec!(0.0),
             account: account.clone(),
-            exchange
+            exchange,
+            brackets: None
         }
     }
 
@@ -364,7 +375,8 @@ impl Order {
             fees: dec!(0.0),
             value: dec!(0.0),
             account: account.clone(),
-            exchange
+            exchange,
+            brackets: None
         }
     }
 
@@ -401,7 +413,8 @@ impl Order {
             fees: dec!(0.0),
             value: dec!(0.0),
             account: account.clone(),
-            exchange
+            exchange,
+            brackets: None
         }
     }
 
@@ -438,7 +451,8 @@ impl Order {
             fees: dec!(0.0),
             value: dec!(0.0),
             account: account.clone(),
-            exchange
+            exchange,
+            brackets: None
         }
     }
 
@@ -450,7 +464,8 @@ impl Order {
         tag: String,
         order_id: OrderId,
         time: DateTime<Utc>,
-        exchange: Option<String>
+        exchange: Option<String>,
+        brackets: Option<Vec<ProtectiveOrder>>
     ) -> Self {
         let symbol_code = match symbol_code {
             Some(code) => code,
@@ -475,7 +490,8 @@ impl Order {
             fees: dec!(0.0),
             value: dec!(0.0),
             account: account.clone(),
-            exchange
+            exchange,
+            brackets
         }
     }
 
@@ -487,7 +503,8 @@ impl Order {
         tag: String,
         order_id: OrderId,
         time: DateTime<Utc>,
-        exchange: Option<String>
+        exchange: Option<String>,
+        brackets: Option<Vec<ProtectiveOrder>>
     ) -> Self {
         let symbol_code = match symbol_code {
             Some(code) => code,
@@ -512,7 +529,8 @@ impl Order {
             state: OrderState::Created,
             fees: dec!(0.0),
             value: dec!(0.0),
-            exchange
+            exchange,
+            brackets
         }
     }
 
@@ -560,6 +578,22 @@ impl Order {
 
 pub type OrderId = String;
 
+pub type OrderGroupId = String;
+
+#[derive(
+    Clone, Copy, Serialize_rkyv, Deserialize_rkyv, Archive, PartialEq, Eq, Debug, Serialize, Deserialize, Display
+)]
+#[archive(compare(PartialEq), check_bytes)]
+#[archive_attr(derive(Debug))]
+/// How the orders in an `OrderRequest::CreateGroup` are linked.
+pub enum OrderGroupType {
+    /// All orders are placed, when any order in the group is filled or partially filled the remaining orders are cancelled.
+    OneCancelsOther,
+    /// Only the first order is placed, the remaining orders are placed once the first order is completely filled.
+    /// If the first order is cancelled or rejected the remaining orders are cancelled without being placed.
+    OneTriggersOther,
+}
+
 #[derive(
     Clone, Serialize_rkyv, Deserialize_rkyv, Archive, PartialEq, Debug, Serialize, Deserialize, Display
 )]
@@ -13,12 +13,15 @@ use crate::helpers::converters::format_duration;
 use crate::helpers::decimal_calculators::calculate_theoretical_pnl;
 use crate::standardized_types::accounts::{Account, AccountId, Currency};
 use crate::standardized_types::base_data::base_data_enum::BaseDataEnum;
+use crate::standardized_types::base_data::traits::BaseData;
 use crate::standardized_types::broker_enum::Brokerage;
 use crate::standardized_types::enums::{PositionSide, StrategyMode};
 use crate::standardized_types::subscriptions::{SymbolCode, SymbolName};
 use crate::standardized_types::new_types::{Price, Volume};
 use crate::standardized_types::orders::OrderId;
 use crate::standardized_types::symbol_info::SymbolInfo;
+use crate::strategies::ledgers::commissions::CommissionBreakdown;
+use crate::strategies::roll_manager::is_roll_tag;
 
 pub type PositionId = String;
 #[derive(Serialize)]
@@ -30,9 +33,18 @@ pub(crate) struct PositionExport {
     average_entry_price: Price,
     average_exit_price: Price,
     booked_pnl: Price,
+    financing: Price,
     open_pnl: Price,
     highest_recoded_price: Price,
     lowest_recoded_price: Price,
+    mae_price: Price,
+    mae_ticks: Decimal,
+    mae_value: Decimal,
+    mae_time: String,
+    mfe_price: Price,
+    mfe_ticks: Decimal,
+    mfe_value: Decimal,
+    mfe_time: String,
     entry_time: String,
     exit_time: String,
     hold_duration: String,
@@ -226,18 +238,75 @@ pub enum PositionCalculationMode {
     LIFO,
 }
 
+/// The maximum adverse excursion (MAE) and maximum favourable excursion (MFE) of a position or trade, measured from its entry price while it was open.
+/// For bar data the time is the close of the bar which reached the high or low.
+#[derive(Clone, Serialize_rkyv, Deserialize_rkyv, Archive, Debug, PartialEq, Serialize, Deserialize, PartialOrd, Default)]
+#[archive(compare(PartialEq), check_bytes)]
+#[archive_attr(derive(Debug))]
+pub struct Excursion {
+    /// The largest move against the entry price.
+    pub mae_price: Price,
+    pub mae_ticks: Decimal,
+    /// The open loss at the MAE in the account currency, as a positive value.
+    pub mae_value: Decimal,
+    pub mae_time: Option<String>,
+    /// The largest move in favour of the entry price.
+    pub mfe_price: Price,
+    pub mfe_ticks: Decimal,
+    /// The open profit at the MFE in the account currency.
+    pub mfe_value: Decimal,
+    pub mfe_time: Option<String>,
+}
+
+impl Excursion {
+    /// Records the high and low of new data for an entry at `entry_price`, the values in the account currency are calculated by `Position::excursion_value`.
+    /// The ticks are left at zero for symbols without a tick size.
+    pub(crate) fn update(&mut self, side: PositionSide, entry_price: Price, high: Price, low: Price, tick_size: Price, time: DateTime<Utc>) {
+        let (adverse, favourable) = match side {
+            PositionSide::Long => (entry_price - low, high - entry_price),
+            PositionSide::Short => (high - entry_price, entry_price - low),
+            PositionSide::Flat => return,
+        };
+        if adverse > self.mae_price {
+            self.mae_price = adverse;
+            self.mae_ticks = ticks(adverse, tick_size);
+            self.mae_time = Some(time.to_string());
+        }
+        if favourable > self.mfe_price {
+            self.mfe_price = favourable;
+            self.mfe_ticks = ticks(favourable, tick_size);
+            self.mfe_time = Some(time.to_string());
+        }
+    }
+
+}
+
+fn ticks(distance: Price, tick_size: Price) -> Decimal {
+    if tick_size > dec!(0.0) {
+        distance / tick_size
+    } else {
+        dec!(0.0)
+    }
+}
+
 #[derive(Clone, Serialize_rkyv, Deserialize_rkyv, Archive, Debug, PartialEq, Serialize, Deserialize, PartialOrd,)]
 #[archive(compare(PartialEq), check_bytes)]
 #[archive_attr(derive(Debug))]
 pub struct EntryPrice {
     pub volume: Volume,
     pub price: Price,
-    pub order_id: OrderId
+    pub order_id: OrderId,
+    /// The entry fees for the remaining volume, moved to each `Trade` as the volume is exited.
+    #[serde(default)]
+    pub commission: CommissionBreakdown,
+    /// The excursion from this entry price, copied to each `Trade` as the volume is exited.
+    #[serde(default)]
+    pub excursion: Excursion,
 }
 
 impl EntryPrice {
     pub fn new(volume: Volume, price: Price, order_id: OrderId) -> Self {
-        Self { volume, price, order_id }
+        Self { volume, price, order_id, commission: CommissionBreakdown::default(), excursion: Excursion::default() }
     }
 }
 
@@ -274,6 +343,12 @@ pub struct Trade {
     pub exit_time: String,
     pub profit: Price,
     pub result: TradeResult,
+    /// The entry and exit fees for the quantity of this trade in the account currency, `profit` does not include them.
+    #[serde(default)]
+    pub commission: CommissionBreakdown,
+    /// The MAE and MFE from the entry price while the trade was open, the values are for the quantity of this trade.
+    #[serde(default)]
+    pub excursion: Excursion,
 }
 
 #[derive(Debug)]
@@ -287,6 +362,11 @@ pub struct PositionStatistics {
     pub worst_exit_price: Decimal,
     pub total_quantity_traded: Decimal,
     pub weighted_avg_hold_time: Duration,
+    /// The MAE and MFE of the position from its average entry price.
+    pub excursion: Excursion,
+    /// The largest MAE and MFE of the trades in the account currency.
+    pub worst_trade_mae: Decimal,
+    pub best_trade_mfe: Decimal,
 }
 
 
@@ -306,6 +386,9 @@ pub struct Position {
     pub average_price: Price,
     pub open_pnl: Price,
     pub booked_pnl: Price,
+    /// The overnight financing credited to the position, negative if it was charged, included in `booked_pnl`.
+    #[serde(default)]
+    pub financing: Price,
     pub highest_recoded_price: Price,
     pub lowest_recoded_price: Price,
     pub exchange_rate_multiplier: Decimal,
@@ -317,6 +400,12 @@ pub struct Position {
     pub position_calculation_mode: PositionCalculationMode,
     pub open_entry_prices: VecDeque<EntryPrice>,
     pub completed_trades: Vec<Trade>,
+    /// The MAE and MFE of the position from its average entry price, the values are for the open quantity at the time.
+    #[serde(default)]
+    pub excursion: Excursion,
+    /// True if the position was closed by a roll to the next contract, see `RollSettings`.
+    #[serde(default)]
+    pub rolled: bool,
 }
 
 impl Position {
@@ -347,6 +436,7 @@ impl Position {
             average_price,
             open_pnl: dec!(0.0),
             booked_pnl: dec!(0.0),
+            financing: dec!(0.0),
             highest_recoded_price: average_price,
             lowest_recoded_price: average_price,
             average_exit_price: None,
@@ -357,7 +447,9 @@ impl Position {
             tag,
             position_calculation_mode,
             open_entry_prices: VecDeque::from(vec![EntryPrice::new(quantity, average_price, entry_order_id)]),
-            completed_trades: vec![]
+            completed_trades: vec![],
+            excursion: Excursion::default(),
+            rolled: false,
         }
     }
 
@@ -396,9 +488,18 @@ impl Position {
             average_entry_price: final_entry_price,
             average_exit_price: final_exit_price,
             booked_pnl: self.booked_pnl.round_dp(2),
+            financing: self.financing.round_dp(2),
             open_pnl: self.open_pnl.round_dp(2),
             highest_recoded_price: self.highest_recoded_price,
             lowest_recoded_price: self.lowest_recoded_price,
+            mae_price: self.excursion.mae_price,
+            mae_ticks: self.excursion.mae_ticks.round_dp(2),
+            mae_value: self.excursion.mae_value.round_dp(2),
+            mae_time: self.excursion.mae_time.clone().unwrap_or_else(|| "None".to_string()),
+            mfe_price: self.excursion.mfe_price,
+            mfe_ticks: self.excursion.mfe_ticks.round_dp(2),
+            mfe_value: self.excursion.mfe_value.round_dp(2),
+            mfe_time: self.excursion.mfe_time.clone().unwrap_or_else(|| "None".to_string()),
             exit_time,
             entry_time: self.open_time.to_string(),
             hold_duration,
@@ -426,12 +527,26 @@ impl Position {
                 PositionSide::Short => (quote.bid, quote.bid, quote.bid),
                 _ => unreachable!("This shouldn't be possible"),
             },
+            BaseDataEnum::OrderBook(book) => {
+                let level = match self.side {
+                    PositionSide::Long => book.best_ask(),
+                    PositionSide::Short => book.best_bid(),
+                    _ => unreachable!("This shouldn't be possible"),
+                };
+                match level {
+                    Some(level) => (level.price, level.price, level.price),
+                    None => return self.open_pnl,
+                }
+            }
+            // a single resting order is not a market price
+            BaseDataEnum::MarketByOrder(_) => return self.open_pnl,
             BaseDataEnum::Fundamental(_) => panic!("Fundamentals should not be here"),
         };
 
         // Update highest and lowest recorded prices
         self.highest_recoded_price = self.highest_recoded_price.max(highest_price);
         self.lowest_recoded_price = self.lowest_recoded_price.min(lowest_price);
+        self.record_excursion(highest_price, lowest_price, base_data.time_closed_utc(), account_currency);
 
         // Calculate the open PnL
         self.open_pnl = calculate_theoretical_pnl(
@@ -448,6 +563,39 @@ impl Position {
         self.open_pnl.clone()
     }
 
+    /// Updates the MAE and MFE of the position and each open entry with the high and low of new data.
+    pub(crate) fn record_excursion(&mut self, high: Price, low: Price, time: DateTime<Utc>, account_currency: Currency) {
+        let tick_size = self.symbol_info.tick_size;
+        for entry in self.open_entry_prices.iter_mut() {
+            entry.excursion.update(self.side, entry.price, high, low, tick_size, time);
+        }
+        let mut excursion = self.excursion.clone();
+        excursion.update(self.side, self.average_price, high, low, tick_size, time);
+        let valued = self.excursion_value(&excursion, self.average_price, self.quantity_open, account_currency);
+        // the values are only replaced when the position reaches a new excursion, so they are not reduced when the position is scaled out
+        if excursion.mae_time != self.excursion.mae_time {
+            excursion.mae_value = valued.mae_value.max(self.excursion.mae_value);
+        }
+        if excursion.mfe_time != self.excursion.mfe_time {
+            excursion.mfe_value = valued.mfe_value.max(self.excursion.mfe_value);
+        }
+        self.excursion = excursion;
+    }
+
+    /// Returns the excursion with the values in the account currency for `quantity` entered at `entry_price`.
+    fn excursion_value(&self, excursion: &Excursion, entry_price: Price, quantity: Volume, account_currency: Currency) -> Excursion {
+        let (mae_exit, mfe_exit) = match self.side {
+            PositionSide::Long => (entry_price - excursion.mae_price, entry_price + excursion.mfe_price),
+            _ => (entry_price + excursion.mae_price, entry_price - excursion.mfe_price),
+        };
+        let value = |exit_price: Price| calculate_theoretical_pnl(self.account.brokerage, self.side, entry_price, exit_price, quantity, &self.symbol_info, self.exchange_rate_multiplier, account_currency).abs();
+        Excursion {
+            mae_value: value(mae_exit),
+            mfe_value: value(mfe_exit),
+            ..excursion.clone()
+        }
+    }
+
     /// Reduces position size a position event, this event will include a booked_pnl property
     pub(crate) async fn reduce_position_size(&mut self, market_price: Price, quantity: Volume, order_id: OrderId, account_currency: Currency, exchange_rate: Decimal, time: DateTime<Utc>, tag: String) -> PositionUpdateEvent {
         if quantity > self.quantity_open {
@@ -470,6 +618,7 @@ impl Position {
 
             // Calculate how much we can exit from this entry price level
             let exit_quantity = remaining_exit_quantity.min(entry.volume);
+            let entry_commission = entry.commission.clone() * (exit_quantity / entry.volume);
 
             // Calculate PnL for this portion
             let portion_booked_pnl = calculate_theoretical_pnl(
@@ -488,6 +637,7 @@ impl Position {
                 pnl if pnl < dec!(0.0) => TradeResult::Loss,
                 _ => TradeResult::BreakEven,
             };
+            let excursion = self.excursion_value(&entry.excursion, entry.price, exit_quantity, account_currency);
             // Record the trade
             self.completed_trades.push(Trade {
                 entry_price: entry.price,
@@ -499,13 +649,17 @@ impl Position {
                 exit_time: time.to_string(),
                 profit: portion_booked_pnl,
                 exit_order_id: order_id.clone(),
-                result
+                result,
+                commission: entry_commission.clone(),
+                excursion,
             });
 
             // If we didn't use all of this entry, we need to put back the remainder
             let remaining_entry_volume = entry.volume - exit_quantity;
             if remaining_entry_volume > dec!(0.0) {
-                let remaining_entry = EntryPrice::new(remaining_entry_volume, entry.price, entry.order_id.clone());
+                let mut remaining_entry = EntryPrice::new(remaining_entry_volume, entry.price, entry.order_id.clone());
+                remaining_entry.commission = entry.commission - entry_commission;
+                remaining_entry.excursion = entry.excursion.clone();
                 match self.position_calculation_mode {
                     PositionCalculationMode::FIFO => temp_entries.push_back(remaining_entry),
                     PositionCalculationMode::LIFO => temp_entries.push_front(remaining_entry),
@@ -567,6 +721,7 @@ impl Position {
         if self.is_closed {
             self.open_pnl = dec!(0);
             self.close_time = Some(time.to_string());
+            self.rolled = is_roll_tag(&tag);
             PositionUpdateEvent::PositionClosed {
                 position_id: self.position_id.clone(),
                 side: self.side.clone(),
@@ -653,6 +808,26 @@ impl Position {
     }
 
 
+    /// Records the fees for the most recent entry, they are moved to the trades which exit it.
+    pub(crate) fn record_entry_commission(&mut self, commission: CommissionBreakdown) {
+        if let Some(entry) = self.open_entry_prices.back_mut() {
+            entry.commission = entry.commission.clone() + commission;
+        }
+    }
+
+    /// Splits the fees for an exit between the trades it completed, in proportion to their quantity.
+    pub(crate) fn record_exit_commission(&mut self, trades_completed: usize, commission: CommissionBreakdown) {
+        let start = self.completed_trades.len().saturating_sub(trades_completed);
+        let trades = &mut self.completed_trades[start..];
+        let exit_quantity: Volume = trades.iter().map(|trade| trade.exit_quantity).sum();
+        if exit_quantity <= dec!(0) {
+            return;
+        }
+        for trade in trades {
+            trade.commission = trade.commission.clone() + commission.clone() * (trade.exit_quantity / exit_quantity);
+        }
+    }
+
     pub fn get_statistics(&self) -> PositionStatistics {
         let mut stats = PositionStatistics {
             total_trades: self.completed_trades.len(),
@@ -664,6 +839,9 @@ impl Position {
             worst_exit_price: dec!(0.0),
             total_quantity_traded: dec!(0.0),
             weighted_avg_hold_time: Duration::zero(),
+            excursion: self.excursion.clone(),
+            worst_trade_mae: dec!(0.0),
+            best_trade_mfe: dec!(0.0),
         };
 
         if self.completed_trades.is_empty() {
@@ -685,6 +863,8 @@ impl Position {
             // Update best/worst trades
             stats.best_trade_pnl = stats.best_trade_pnl.max(trade_pnl);
             stats.worst_trade_pnl = stats.worst_trade_pnl.min(trade_pnl);
+            stats.worst_trade_mae = stats.worst_trade_mae.max(trade.excursion.mae_value);
+            stats.best_trade_mfe = stats.best_trade_mfe.max(trade.excursion.mfe_value);
 
             // Track weighted prices
             total_weighted_entry += trade.entry_price * trade.entry_quantity;
@@ -1167,4 +1347,34 @@ mod test {
         assert!(export.average_exit_price > export.average_entry_price); // Should be profitable
         assert_eq!(export.quantity, dec!(3.0));
     }
+
+    #[tokio::test]
+    async fn test_excursion_recorded_for_trades() {
+        let mut position = setup_basic_position();
+        let time = Utc::now();
+        position.record_excursion(dec!(17510.0), dec!(17480.0), time, Currency::USD);
+        position.record_excursion(dec!(17505.0), dec!(17490.0), time + Duration::minutes(1), Currency::USD);
+
+        let excursion = position.excursion.clone();
+        assert_eq!(excursion.mae_price, dec!(20.0));
+        assert_eq!(excursion.mae_ticks, dec!(20.0) / position.symbol_info.tick_size);
+        assert_eq!(excursion.mfe_price, dec!(10.0));
+        assert_eq!(excursion.mae_time, Some(time.to_string()));
+        assert!(excursion.mae_value > excursion.mfe_value);
+
+        position.reduce_position_size(dec!(17500.0), dec!(1.0), "Test".to_string(), Currency::USD, dec!(1.0), time + Duration::minutes(2), "exit".to_string()).await;
+        let trade = position.completed_trades.last().unwrap();
+        assert_eq!(trade.excursion, excursion);
+        assert_eq!(position.get_statistics().worst_trade_mae, excursion.mae_value);
+    }
+
+    #[test]
+    fn test_excursion_ticks_without_tick_size() {
+        let mut excursion = Excursion::default();
+        excursion.update(PositionSide::Long, dec!(17500.0), dec!(17510.0), dec!(17480.0), dec!(0.0), Utc::now());
+
+        assert_eq!(excursion.mae_price, dec!(20.0));
+        assert_eq!(excursion.mae_ticks, dec!(0.0));
+        assert_eq!(excursion.mfe_ticks, dec!(0.0));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,484 @@
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use chrono::{DateTime, Duration as ChronoDuration, Utc, Weekday};
+use dashmap::DashMap;
+use tokio::sync::mpsc::Sender;
+use rkyv::{AlignedVec, Archive, Deserialize as Deserialize_rkyv, Serialize as Serialize_rkyv};
+use rkyv::de::deserializers::SharedDeserializeMap;
+use rkyv::ser::serializers::AllocSerializer;
+use rkyv::validation::validators::DefaultValidator;
+use rkyv::CheckBytes;
+use rust_decimal::Decimal;
+use crate::gui_types::drawing_objects::drawing_tool_enum::DrawingTool;
+use crate::helpers::get_resources;
+use crate::messages::data_server_messaging::FundForgeError;
+use crate::standardized_types::accounts::Account;
+use crate::standardized_types::orders::{Order, OrderId};
+use crate::standardized_types::position::Position;
+use crate::standardized_types::rolling_window::RollingWindow;
+use crate::strategies::handlers::drawing_object_handler::DrawingObjectHandler;
+use crate::strategies::handlers::indicator_handler::IndicatorHandler;
+use crate::strategies::handlers::timed_events_handler::{EventTimeEnum, TimedEvent, TimedEventHandler};
+use crate::strategies::historical_time::StrategyClock;
+use crate::strategies::indicators::indicator_values::IndicatorValues;
+use crate::strategies::indicators::indicators_trait::IndicatorName;
+use crate::strategies::ledgers::ledger_service::LedgerService;
+use crate::strategies::strategy_events::StrategyEvent;
+
+const CHECKPOINT_FILE: &str = "checkpoint.rkyv";
+
+const WEEKDAYS: [Weekday; 7] = [Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri, Weekday::Sat, Weekday::Sun];
+
+/// Where and how often a live strategy saves a `StrategyCheckpoint`, passed to `FundForgeStrategy::initialize()`.
+/// Checkpoints are only used in `Live` and `LivePaperTrading` modes, backtests always start from the start date.
+#[derive(Clone, Debug, Default)]
+pub struct CheckpointSettings {
+    folder: Option<PathBuf>,
+    interval: Option<Duration>,
+    max_age: Option<ChronoDuration>,
+}
+
+impl CheckpointSettings {
+    /// No checkpoints are saved or restored.
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// Saves a checkpoint every `interval` to `resources/checkpoints/{name}`, the name should be unique to the strategy.
+    pub fn new(name: &str, interval: Duration) -> Self {
+        Self {
+            folder: Some(get_resources().join("checkpoints").join(name)),
+            interval: Some(interval),
+            max_age: None,
+        }
+    }
+
+    pub fn with_folder(mut self, folder: impl Into<PathBuf>) -> Self {
+        self.folder = Some(folder.into());
+        self
+    }
+
+    /// Checkpoints older than `max_age` are ignored on start up, the strategy starts from scratch.
+    pub fn with_max_age(mut self, max_age: ChronoDuration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.folder.is_some()
+    }
+
+    pub fn file_path(&self) -> Option<PathBuf> {
+        self.folder.as_ref().map(|folder| folder.join(CHECKPOINT_FILE))
+    }
+}
+
+/// The state of one ledger when the checkpoint was saved.
+#[derive(Clone, Serialize_rkyv, Deserialize_rkyv, Archive, PartialEq, Debug)]
+#[archive(check_bytes)]
+#[archive_attr(derive(Debug))]
+pub struct LedgerCheckpoint {
+    pub account: Account,
+    pub cash_value: Decimal,
+    pub cash_available: Decimal,
+    pub cash_used: Decimal,
+    pub total_booked_pnl: Decimal,
+    pub commissions_paid: Decimal,
+    pub total_financing: Decimal,
+    pub positions: Vec<Position>,
+    pub positions_closed: Vec<Position>,
+}
+
+/// The history of one indicator when the checkpoint was saved, the latest value first like `RollingWindow`.
+#[derive(Clone, Serialize_rkyv, Deserialize_rkyv, Archive, PartialEq, Debug)]
+#[archive(check_bytes)]
+#[archive_attr(derive(Debug))]
+pub struct IndicatorHistory {
+    pub name: IndicatorName,
+    pub number: u64,
+    pub values: Vec<IndicatorValues>,
+}
+
+/// The time of a `TimedEvent` when the checkpoint was saved, weekdays are numbered from monday and intervals are in milliseconds.
+#[derive(Clone, Serialize_rkyv, Deserialize_rkyv, Archive, PartialEq, Debug)]
+#[archive(check_bytes)]
+#[archive_attr(derive(Debug))]
+pub enum EventTimeCheckpoint {
+    Weekday { day: u32, fire_in_warmup: bool },
+    HourOfDay { hour: u32, fire_in_warmup: bool },
+    TimeOnWeekDay { day: u32, hour: u32, minute: u32, second: u32, fire_in_warmup: bool },
+    DateTime { date_time: String, fire_in_warmup: bool },
+    TimeOfDay { hour: u32, minute: u32, second: u32, fire_in_warmup: bool },
+    Every { milliseconds: i64, next_time: String, fire_in_warmup: bool },
+}
+
+impl From<&EventTimeEnum> for EventTimeCheckpoint {
+    fn from(time: &EventTimeEnum) -> Self {
+        match *time {
+            EventTimeEnum::Weekday { day, fire_in_warmup } => EventTimeCheckpoint::Weekday { day: day.num_days_from_monday(), fire_in_warmup },
+            EventTimeEnum::HourOfDay { hour, fire_in_warmup } => EventTimeCheckpoint::HourOfDay { hour, fire_in_warmup },
+            EventTimeEnum::TimeOnWeekDay { day, hour, minute, second, fire_in_warmup } => EventTimeCheckpoint::TimeOnWeekDay { day: day.num_days_from_monday(), hour, minute, second, fire_in_warmup },
+            EventTimeEnum::DateTime { date_time, fire_in_warmup } => EventTimeCheckpoint::DateTime { date_time: date_time.to_string(), fire_in_warmup },
+            EventTimeEnum::TimeOfDay { hour, minute, second, fire_in_warmup } => EventTimeCheckpoint::TimeOfDay { hour, minute, second, fire_in_warmup },
+            EventTimeEnum::Every { duration, next_time, fire_in_warmup } => EventTimeCheckpoint::Every { milliseconds: duration.num_milliseconds(), next_time: next_time.to_string(), fire_in_warmup },
+        }
+    }
+}
+
+impl EventTimeCheckpoint {
+    /// Returns `None` if a weekday or time can not be read.
+    pub fn event_time(&self) -> Option<EventTimeEnum> {
+        let time = match self {
+            EventTimeCheckpoint::Weekday { day, fire_in_warmup } => EventTimeEnum::Weekday { day: *WEEKDAYS.get(*day as usize)?, fire_in_warmup: *fire_in_warmup },
+            EventTimeCheckpoint::HourOfDay { hour, fire_in_warmup } => EventTimeEnum::HourOfDay { hour: *hour, fire_in_warmup: *fire_in_warmup },
+            EventTimeCheckpoint::TimeOnWeekDay { day, hour, minute, second, fire_in_warmup } => EventTimeEnum::TimeOnWeekDay { day: *WEEKDAYS.get(*day as usize)?, hour: *hour, minute: *minute, second: *second, fire_in_warmup: *fire_in_warmup },
+            EventTimeCheckpoint::DateTime { date_time, fire_in_warmup } => EventTimeEnum::DateTime { date_time: date_time.parse().ok()?, fire_in_warmup: *fire_in_warmup },
+            EventTimeCheckpoint::TimeOfDay { hour, minute, second, fire_in_warmup } => EventTimeEnum::TimeOfDay { hour: *hour, minute: *minute, second: *second, fire_in_warmup: *fire_in_warmup },
+            EventTimeCheckpoint::Every { milliseconds, next_time, fire_in_warmup } => EventTimeEnum::Every { duration: ChronoDuration::milliseconds(*milliseconds), next_time: next_time.parse().ok()?, fire_in_warmup: *fire_in_warmup },
+        };
+        Some(time)
+    }
+}
+
+/// A timed event when the checkpoint was saved.
+#[derive(Clone, Serialize_rkyv, Deserialize_rkyv, Archive, PartialEq, Debug)]
+#[archive(check_bytes)]
+#[archive_attr(derive(Debug))]
+pub struct TimedEventCheckpoint {
+    pub name: String,
+    pub time: EventTimeCheckpoint,
+    /// The last time the event fired, so an event which already fired today does not fire again after the restart.
+    pub last_fired: Option<String>,
+}
+
+impl TimedEventCheckpoint {
+    pub(crate) fn new(event: &TimedEvent, last_fired: Option<DateTime<Utc>>) -> Self {
+        Self {
+            name: event.name.clone(),
+            time: EventTimeCheckpoint::from(&event.time),
+            last_fired: last_fired.map(|time| time.to_string()),
+        }
+    }
+}
+
+/// Sent to live strategies when they resume from a checkpoint, or when a checkpoint can not be saved.
+#[derive(Clone, Serialize_rkyv, Deserialize_rkyv, Archive, PartialEq, Debug)]
+#[archive(compare(PartialEq), check_bytes)]
+#[archive_attr(derive(Debug))]
+pub enum CheckpointEvent {
+    /// The strategy resumed from the checkpoint saved at `time` instead of warming up.
+    Restored { time: String },
+    /// The checkpoint on the interval was not saved, the last checkpoint is kept.
+    SaveFailed { reason: String },
+}
+
+impl Display for CheckpointEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CheckpointEvent::Restored { time } => write!(f, "Checkpoint: Restored strategy state from {}", time),
+            CheckpointEvent::SaveFailed { reason } => write!(f, "Checkpoint: Failed to save: {}", reason),
+        }
+    }
+}
+
+/// The state of a strategy which can not be rebuilt from historical data, saved with rkyv so a live strategy can resume after a restart.
+///
+/// A strategy which resumes from a checkpoint does not warm up, the indicator `history()` windows and the timed events are restored into their handlers.
+/// Indicators added again by the strategy are rebuilt from the data server, until then their restored history is returned.
+#[derive(Clone, Serialize_rkyv, Deserialize_rkyv, Archive, PartialEq, Debug)]
+#[archive(check_bytes)]
+#[archive_attr(derive(Debug))]
+pub struct StrategyCheckpoint {
+    pub time: String,
+    pub ledgers: Vec<LedgerCheckpoint>,
+    pub open_orders: Vec<Order>,
+    pub indicator_history: Vec<IndicatorHistory>,
+    pub timed_events: Vec<TimedEventCheckpoint>,
+    pub drawing_tools: Vec<DrawingTool>,
+    /// The custom state set with `FundForgeStrategy::set_checkpoint_state()`, rkyv bytes.
+    pub user_state: Vec<u8>,
+}
+
+impl StrategyCheckpoint {
+    pub fn time_utc(&self) -> DateTime<Utc> {
+        self.time.parse().unwrap_or_default()
+    }
+
+    pub fn indicator_history(&self, name: &IndicatorName) -> Option<RollingWindow<IndicatorValues>> {
+        self.indicator_history.iter()
+            .find(|history| &history.name == name)
+            .map(|history| RollingWindow {
+                history: history.values.clone(),
+                number: history.number,
+            })
+    }
+
+    pub fn to_bytes(&self) -> Result<AlignedVec, FundForgeError> {
+        rkyv::to_bytes::<_, 1024>(self)
+            .map_err(|e| FundForgeError::ClientSideErrorDebug(format!("Failed to serialize checkpoint: {}", e)))
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, FundForgeError> {
+        from_rkyv_bytes(bytes)
+    }
+
+    /// Writes the checkpoint to a temporary file and renames it, so a crash while saving never leaves a partial checkpoint.
+    pub fn save(&self, file_path: &Path) -> Result<(), FundForgeError> {
+        if let Some(folder) = file_path.parent() {
+            fs::create_dir_all(folder)
+                .map_err(|e| FundForgeError::ClientSideErrorDebug(format!("Failed to create directory {}: {}", folder.display(), e)))?;
+        }
+        let temp_path = file_path.with_extension("tmp");
+        fs::write(&temp_path, self.to_bytes()?)
+            .map_err(|e| FundForgeError::ClientSideErrorDebug(format!("Failed to write {}: {}", temp_path.display(), e)))?;
+        fs::rename(&temp_path, file_path)
+            .map_err(|e| FundForgeError::ClientSideErrorDebug(format!("Failed to write {}: {}", file_path.display(), e)))
+    }
+
+    /// Returns `None` if no checkpoint has been saved.
+    pub fn load(file_path: &Path) -> Result<Option<Self>, FundForgeError> {
+        if !file_path.exists() {
+            return Ok(None);
+        }
+        let bytes = fs::read(file_path)
+            .map_err(|e| FundForgeError::ClientSideErrorDebug(format!("Failed to read {}: {}", file_path.display(), e)))?;
+        Self::from_bytes(&bytes).map(Some)
+    }
+}
+
+pub(crate) fn to_rkyv_bytes<T: rkyv::Serialize<AllocSerializer<256>>>(value: &T) -> Result<Vec<u8>, FundForgeError> {
+    rkyv::to_bytes::<_, 256>(value)
+        .map(|bytes| bytes.to_vec())
+        .map_err(|e| FundForgeError::ClientSideErrorDebug(format!("Failed to serialize checkpoint state: {}", e)))
+}
+
+/// Copies the bytes into an aligned buffer before validating them, bytes read from a file have no alignment guarantee.
+pub(crate) fn from_rkyv_bytes<T>(bytes: &[u8]) -> Result<T, FundForgeError>
+where
+    T: Archive,
+    T::Archived: for<'a> CheckBytes<DefaultValidator<'a>> + rkyv::Deserialize<T, SharedDeserializeMap>,
+{
+    let mut aligned = AlignedVec::with_capacity(bytes.len());
+    aligned.extend_from_slice(bytes);
+    rkyv::from_bytes::<T>(&aligned)
+        .map_err(|e| FundForgeError::ClientSideErrorDebug(format!("Failed to deserialize checkpoint: {}", e)))
+}
+
+/// Collects the state of the strategy handlers into a `StrategyCheckpoint` and saves it on an interval once the warm up is complete.
+pub(crate) struct Checkpointer {
+    settings: CheckpointSettings,
+    ledger_service: Arc<LedgerService>,
+    open_order_cache: Arc<DashMap<OrderId, Order>>,
+    indicator_handler: Arc<IndicatorHandler>,
+    timed_event_handler: Arc<TimedEventHandler>,
+    drawing_objects_handler: Arc<DrawingObjectHandler>,
+    user_state: RwLock<Vec<u8>>,
+    clock: StrategyClock,
+    strategy_event_sender: Sender<StrategyEvent>,
+}
+
+impl Checkpointer {
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        settings: CheckpointSettings,
+        ledger_service: Arc<LedgerService>,
+        open_order_cache: Arc<DashMap<OrderId, Order>>,
+        indicator_handler: Arc<IndicatorHandler>,
+        timed_event_handler: Arc<TimedEventHandler>,
+        drawing_objects_handler: Arc<DrawingObjectHandler>,
+        clock: StrategyClock,
+        strategy_event_sender: Sender<StrategyEvent>,
+    ) -> Self {
+        Self {
+            settings,
+            ledger_service,
+            open_order_cache,
+            indicator_handler,
+            timed_event_handler,
+            drawing_objects_handler,
+            user_state: RwLock::new(vec![]),
+            clock,
+            strategy_event_sender,
+        }
+    }
+
+    /// Loads the checkpoint if one exists and is not older than the max age, a checkpoint which can not be read is an error so it is not silently replaced.
+    pub(crate) fn load(&self) -> Result<Option<StrategyCheckpoint>, FundForgeError> {
+        let file_path = match self.settings.file_path() {
+            Some(file_path) => file_path,
+            None => return Ok(None),
+        };
+        let checkpoint = match StrategyCheckpoint::load(&file_path)? {
+            Some(checkpoint) => checkpoint,
+            None => return Ok(None),
+        };
+        if let Some(max_age) = self.settings.max_age {
+            if Utc::now() - checkpoint.time_utc() > max_age {
+                return Ok(None);
+            }
+        }
+        Ok(Some(checkpoint))
+    }
+
+    /// Restores the ledgers, open orders, indicator history, timed events, drawing tools and custom state of the checkpoint.
+    pub(crate) async fn restore(&self, checkpoint: &StrategyCheckpoint) {
+        for ledger in &checkpoint.ledgers {
+            self.ledger_service.restore_checkpoint(ledger.clone()).await;
+        }
+        for order in &checkpoint.open_orders {
+            self.open_order_cache.insert(order.id.clone(), order.clone());
+        }
+        for history in &checkpoint.indicator_history {
+            self.indicator_handler.restore_history(history.name.clone(), RollingWindow { history: history.values.clone(), number: history.number });
+        }
+        self.timed_event_handler.restore_checkpoint(&checkpoint.timed_events).await;
+        for drawing_tool in &checkpoint.drawing_tools {
+            self.drawing_objects_handler.drawing_tool_add(drawing_tool.clone()).await;
+        }
+        *self.user_state.write().unwrap() = checkpoint.user_state.clone();
+        self.send_event(CheckpointEvent::Restored { time: checkpoint.time.clone() }).await;
+    }
+
+    async fn send_event(&self, event: CheckpointEvent) {
+        let _ = self.strategy_event_sender.send(StrategyEvent::CheckpointEvent(event)).await;
+    }
+
+    pub(crate) fn set_user_state(&self, state: Vec<u8>) {
+        *self.user_state.write().unwrap() = state;
+    }
+
+    pub(crate) fn user_state(&self) -> Vec<u8> {
+        self.user_state.read().unwrap().clone()
+    }
+
+    pub(crate) async fn snapshot(&self) -> StrategyCheckpoint {
+        let drawing_tools = self.drawing_objects_handler.drawing_tools().await
+            .values()
+            .flatten()
+            .cloned()
+            .collect();
+        let indicator_history = self.indicator_handler.histories()
+            .into_iter()
+            .map(|(name, window)| IndicatorHistory { name, number: window.number, values: window.history })
+            .collect();
+        StrategyCheckpoint {
+            time: Utc::now().to_string(),
+            ledgers: self.ledger_service.checkpoint(),
+            open_orders: self.open_order_cache.iter().map(|order| order.value().clone()).collect(),
+            indicator_history,
+            timed_events: self.timed_event_handler.checkpoint().await,
+            drawing_tools,
+            user_state: self.user_state(),
+        }
+    }
+
+    pub(crate) async fn save(&self) -> Result<PathBuf, FundForgeError> {
+        let file_path = self.settings.file_path()
+            .ok_or_else(|| FundForgeError::ClientSideErrorDebug("Checkpoints are not enabled, see CheckpointSettings".to_string()))?;
+        self.snapshot().await.save(&file_path)?;
+        Ok(file_path)
+    }
+
+    /// Saves a checkpoint every interval, checkpoints are not saved during the warm up so a restart during warm up keeps the last good checkpoint.
+    /// A checkpoint which can not be saved is sent to the strategy as `CheckpointEvent::SaveFailed`.
+    pub(crate) fn run(checkpointer: Arc<Self>) {
+        let interval = match checkpointer.settings.interval {
+            Some(interval) if checkpointer.settings.is_enabled() => interval,
+            _ => return,
+        };
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                if !checkpointer.clock.is_warmup_complete() {
+                    continue;
+                }
+                if let Err(e) = checkpointer.save().await {
+                    checkpointer.send_event(CheckpointEvent::SaveFailed { reason: e.to_string() }).await;
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+    use crate::standardized_types::broker_enum::Brokerage;
+
+    #[derive(Archive, Serialize_rkyv, Deserialize_rkyv, Debug, PartialEq)]
+    #[archive(check_bytes)]
+    struct UserState {
+        trades_today: u32,
+        last_signal: String,
+    }
+
+    #[test]
+    fn test_checkpoint_round_trip() {
+        let account = Account::new(Brokerage::Test, "Test_Account_1".to_string());
+        let user_state = UserState { trades_today: 3, last_signal: "long".to_string() };
+        let checkpoint = StrategyCheckpoint {
+            time: Utc::now().to_string(),
+            ledgers: vec![LedgerCheckpoint {
+                account,
+                cash_value: dec!(100250.5),
+                cash_available: dec!(90000),
+                cash_used: dec!(10250.5),
+                total_booked_pnl: dec!(250.5),
+                commissions_paid: dec!(4.2),
+                total_financing: dec!(-1.1),
+                positions: vec![],
+                positions_closed: vec![],
+            }],
+            open_orders: vec![],
+            indicator_history: vec![IndicatorHistory { name: "atr".to_string(), number: 5, values: vec![] }],
+            timed_events: vec![],
+            drawing_tools: vec![],
+            user_state: to_rkyv_bytes(&user_state).unwrap(),
+        };
+
+        let folder = std::env::temp_dir().join(format!("ff_checkpoint_test_{}", std::process::id()));
+        let file_path = folder.join(CHECKPOINT_FILE);
+        assert_eq!(StrategyCheckpoint::load(&file_path).unwrap(), None);
+        checkpoint.save(&file_path).unwrap();
+        let loaded = StrategyCheckpoint::load(&file_path).unwrap().unwrap();
+        fs::remove_dir_all(&folder).unwrap();
+
+        assert_eq!(loaded, checkpoint);
+        assert_eq!(loaded.indicator_history(&"atr".to_string()).unwrap().number, 5);
+        assert!(loaded.indicator_history(&"sma".to_string()).is_none());
+        assert_eq!(from_rkyv_bytes::<UserState>(&loaded.user_state).unwrap(), user_state);
+        assert!(StrategyCheckpoint::from_bytes(&loaded.user_state[1..]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_timed_events_checkpoint() {
+        let (sender, _receiver) = tokio::sync::mpsc::channel(10);
+        let handler = TimedEventHandler::new(sender.clone());
+        let next_time: DateTime<Utc> = "2024-06-03T14:00:00Z".parse().unwrap();
+        handler.add_event(TimedEvent::new("every".to_string(), EventTimeEnum::Every { duration: ChronoDuration::minutes(15), next_time, fire_in_warmup: false })).await;
+        handler.add_event(TimedEvent::new("friday".to_string(), EventTimeEnum::TimeOnWeekDay { day: Weekday::Fri, hour: 20, minute: 0, second: 0, fire_in_warmup: true })).await;
+        handler.update_time(next_time).await;
+
+        let events = handler.checkpoint().await;
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].last_fired, Some(next_time.to_string()));
+        assert_eq!(events[1].last_fired, None);
+        assert_eq!(events[1].time.event_time(), Some(EventTimeEnum::TimeOnWeekDay { day: Weekday::Fri, hour: 20, minute: 0, second: 0, fire_in_warmup: true }));
+
+        let restored = TimedEventHandler::new(sender);
+        restored.restore_checkpoint(&events).await;
+        // the strategy adds its events again when it starts, they replace the restored events and keep the last fired time
+        restored.add_event(TimedEvent::new("friday".to_string(), EventTimeEnum::TimeOnWeekDay { day: Weekday::Fri, hour: 20, minute: 0, second: 0, fire_in_warmup: true })).await;
+        let mut restored_events = restored.checkpoint().await;
+        restored_events.sort_by(|a, b| a.name.cmp(&b.name));
+        assert_eq!(restored_events.len(), 2);
+        assert_eq!(restored_events[0], events[0]);
+        assert_eq!(restored_events[1], events[1]);
+    }
+}
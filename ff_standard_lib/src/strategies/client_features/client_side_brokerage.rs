@@ -5,13 +5,14 @@ use tokio::sync::oneshot;
 use crate::messages::data_server_messaging::{DataServerRequest, DataServerResponse, FundForgeError};
 use crate::product_maps::oanda::maps::{get_oanda_symbol_names, OANDA_SYMBOL_INFO, SYMBOL_DIVISORS};
 use crate::product_maps::rithmic::maps::{find_base_symbol, get_available_rithmic_symbol_names, get_rithmic_intraday_margin_in_usd, get_futures_symbol_info};
-use crate::standardized_types::accounts::{AccountId, AccountInfo, Currency};
+use crate::standardized_types::accounts::{AccountId, AccountInfo, AccountSnapshot, Currency};
 use crate::standardized_types::broker_enum::Brokerage;
 use crate::standardized_types::new_types::{Price, Volume};
 use crate::standardized_types::subscriptions::SymbolName;
 use crate::standardized_types::symbol_info::{CommissionInfo, SymbolInfo};
 use crate::strategies::client_features::connection_types::ConnectionType;
-use crate::strategies::client_features::request_handler::{send_request, StrategyRequest};
+use crate::strategies::client_features::request_handler::{DataServerSender, StrategyRequest};
+use crate::strategies::ledgers::financing::SymbolFinancingRate;
 
 impl Brokerage {
     pub async fn intraday_margin_required(&self, symbol_name: &SymbolName, quantity: Volume, price: Price, account_currency: Currency, base_currency: Option<Currency>, position_currency: Currency, conversion_rate: Decimal) -> Result<Option<Decimal>, FundForgeError> {
@@ -63,7 +64,7 @@ impl Brokerage {
         }
     }
 
-    pub async fn symbol_info(&self, symbol_name: SymbolName) -> Result<SymbolInfo, FundForgeError> {
+    pub async fn symbol_info(&self, symbol_name: SymbolName, data_server_sender: &DataServerSender) -> Result<SymbolInfo, FundForgeError> {
         match self {
             Brokerage::Rithmic(_) => {
                 match get_futures_symbol_info(&symbol_name) {
@@ -95,7 +96,7 @@ impl Brokerage {
                 };
                 let (sender, receiver) = oneshot::channel();
                 let msg = StrategyRequest::CallBack(ConnectionType::Broker(self.clone()), request, sender);
-                send_request(msg).await;
+                data_server_sender.send(msg).await;
                 match receiver.await {
                     Ok(response) => match response {
                         DataServerResponse::SymbolInfo { symbol_info, .. } => Ok(symbol_info),
@@ -108,14 +109,27 @@ impl Brokerage {
         }
     }
 
-    pub async fn accounts(&self) -> Result<Vec<AccountId>, FundForgeError> {
+    /// The smallest quantity increment the brokerage accepts for the symbol, position sizes are rounded down to a multiple of it.
+    /// Oanda trades fx in whole units and cfds in tenths of a unit, Bitget trades crypto in fractions of a coin.
+    pub fn lot_step(&self, symbol_info: &SymbolInfo) -> Volume {
+        match self {
+            Brokerage::Test | Brokerage::Rithmic(_) => dec!(1),
+            Brokerage::Oanda => match symbol_info.base_currency {
+                Some(_) => dec!(1),
+                None => dec!(0.1),
+            },
+            Brokerage::Bitget => dec!(0.0001),
+        }
+    }
+
+    pub async fn accounts(&self, data_server_sender: &DataServerSender) -> Result<Vec<AccountId>, FundForgeError> {
         let request = DataServerRequest::Accounts {
             callback_id: 0,
             brokerage: self.clone(),
         };
         let (sender, receiver) = oneshot::channel();
         let msg = StrategyRequest::CallBack(ConnectionType::Broker(self.clone()), request, sender);
-        send_request(msg).await;
+        data_server_sender.send(msg).await;
         match receiver.await {
             Ok(response) => match response {
                 DataServerResponse::Accounts { accounts, .. } => Ok(accounts),
@@ -126,7 +140,7 @@ impl Brokerage {
         }
     }
 
-    pub async fn symbol_names(&self, callback_id: u64, time: Option<DateTime<Utc>>) -> Result<Vec<SymbolName>, FundForgeError> {
+    pub async fn symbol_names(&self, callback_id: u64, time: Option<DateTime<Utc>>, data_server_sender: &DataServerSender) -> Result<Vec<SymbolName>, FundForgeError> {
         match self {
             Brokerage::Rithmic(_) => Ok(get_available_rithmic_symbol_names().clone()),
             Brokerage::Oanda => Ok(get_oanda_symbol_names().clone()),
@@ -142,7 +156,7 @@ impl Brokerage {
                 };
                 let (sender, receiver) = oneshot::channel();
                 let msg = StrategyRequest::CallBack(ConnectionType::Broker(self.clone()), request, sender);
-                send_request(msg).await;
+                data_server_sender.send(msg).await;
                 match receiver.await {
                     Ok(response) => match response {
                         DataServerResponse::SymbolNames { symbol_names, .. } => Ok(symbol_names),
@@ -155,7 +169,7 @@ impl Brokerage {
         }
     }
 
-    pub async fn commission_info(&self, symbol_name: SymbolName) -> Result<CommissionInfo, FundForgeError> {
+    pub async fn commission_info(&self, symbol_name: SymbolName, data_server_sender: &DataServerSender) -> Result<CommissionInfo, FundForgeError> {
         let request = DataServerRequest::CommissionInfo {
             callback_id: 0,
             brokerage: self.clone(),
@@ -163,7 +177,7 @@ impl Brokerage {
         };
         let (sender, receiver) = oneshot::channel();
         let msg = StrategyRequest::CallBack(ConnectionType::Broker(self.clone()), request, sender);
-        send_request(msg).await;
+        data_server_sender.send(msg).await;
         match receiver.await {
             Ok(response) => match response {
                 DataServerResponse::CommissionInfo { commission_info, .. } => Ok(commission_info),
@@ -174,7 +188,26 @@ impl Brokerage {
         }
     }
 
-    pub async fn account_info(&self, account_id: AccountId) -> Result<AccountInfo, FundForgeError> {
+    /// The financing rates stored by the data server, brokerages without stored rates return an error.
+    pub async fn financing_rates(&self, data_server_sender: &DataServerSender) -> Result<Vec<SymbolFinancingRate>, FundForgeError> {
+        let request = DataServerRequest::FinancingRates {
+            callback_id: 0,
+            brokerage: *self,
+        };
+        let (sender, receiver) = oneshot::channel();
+        let msg = StrategyRequest::CallBack(ConnectionType::Broker(*self), request, sender);
+        data_server_sender.send(msg).await;
+        match receiver.await {
+            Ok(response) => match response {
+                DataServerResponse::FinancingRates { rates, .. } => Ok(rates),
+                DataServerResponse::Error { error, .. } => Err(error),
+                _ => Err(FundForgeError::ClientSideErrorDebug("Incorrect response received at callback".to_string()))
+            },
+            Err(e) => Err(FundForgeError::ClientSideErrorDebug(format!("Receiver error at callback recv: {}", e)))
+        }
+    }
+
+    pub async fn account_info(&self, account_id: AccountId, data_server_sender: &DataServerSender) -> Result<AccountInfo, FundForgeError> {
         let request = DataServerRequest::AccountInfo {
             callback_id: 0,
             brokerage: self.clone(),
@@ -182,7 +215,7 @@ impl Brokerage {
         };
         let (sender, receiver) = oneshot::channel();
         let msg = StrategyRequest::CallBack(ConnectionType::Broker(self.clone()), request, sender);
-        send_request(msg).await;
+        data_server_sender.send(msg).await;
         match receiver.await {
             Ok(response) => match response {
                 DataServerResponse::AccountInfo { account_info, .. } => Ok(account_info),
@@ -192,4 +225,24 @@ impl Brokerage {
             Err(e) => Err(FundForgeError::ClientSideErrorDebug(format!("Receiver error at callback recv: {}", e)))
         }
     }
-}
\ No newline at end of file
+
+    /// The open positions and working orders of the account at the brokerage, used by `reconciliation` on live startup.
+    pub async fn account_snapshot(&self, account_id: AccountId, data_server_sender: &DataServerSender) -> Result<AccountSnapshot, FundForgeError> {
+        let request = DataServerRequest::AccountSnapshot {
+            callback_id: 0,
+            brokerage: *self,
+            account_id
+        };
+        let (sender, receiver) = oneshot::channel();
+        let msg = StrategyRequest::CallBack(ConnectionType::Broker(*self), request, sender);
+        data_server_sender.send(msg).await;
+        match receiver.await {
+            Ok(response) => match response {
+                DataServerResponse::AccountSnapshot { snapshot, .. } => Ok(snapshot),
+                DataServerResponse::Error { error, .. } => Err(error),
+                _ => Err(FundForgeError::ClientSideErrorDebug("Incorrect response received at callback".to_string()))
+            },
+            Err(e) => Err(FundForgeError::ClientSideErrorDebug(format!("Receiver error at callback recv: {}", e)))
+        }
+    }
+}
@@ -4,7 +4,7 @@ use crate::messages::data_server_messaging::{DataServerRequest, DataServerRespon
 use crate::standardized_types::new_types::Price;
 use crate::standardized_types::subscriptions::{Symbol, SymbolName};
 use crate::strategies::client_features::connection_types::ConnectionType;
-use crate::strategies::client_features::request_handler::{send_request, StrategyRequest};
+use crate::strategies::client_features::request_handler::{send_shared_request, StrategyRequest};
 impl Symbol {
     pub async fn tick_size(&self) -> Result<Price, FundForgeError> {
         let request = DataServerRequest::TickSize {
@@ -14,7 +14,7 @@ impl Symbol {
         };
         let (sender, receiver) = oneshot::channel();
         let msg = StrategyRequest::CallBack(ConnectionType::Vendor(self.data_vendor.clone()), request, sender);
-        send_request(msg).await;
+        send_shared_request(msg).await;
 
         match receiver.await {
             Ok(response) => match response {
@@ -39,7 +39,7 @@ impl Symbol {
         };
         let (sender, receiver) = oneshot::channel();
         let msg = StrategyRequest::CallBack(ConnectionType::Vendor(self.data_vendor.clone()), request, sender);
-        send_request(msg).await;
+        send_shared_request(msg).await;
 
         match receiver.await {
             Ok(response) => match response {
@@ -10,10 +10,10 @@ use crate::standardized_types::enums::{MarketType, PrimarySubscription};
 use crate::standardized_types::new_types::Price;
 use crate::standardized_types::subscriptions::{Symbol, SymbolName};
 use crate::strategies::client_features::connection_types::ConnectionType;
-use crate::strategies::client_features::request_handler::{send_request, StrategyRequest};
+use crate::strategies::client_features::request_handler::{send_shared_request, DataServerSender, StrategyRequest};
 
 impl DataVendor {
-    pub async fn symbols(&self, market_type: MarketType, time: Option<DateTime<Utc>>) -> Result<Vec<Symbol>, FundForgeError> {
+    pub async fn symbols(&self, market_type: MarketType, time: Option<DateTime<Utc>>, data_server_sender: &DataServerSender) -> Result<Vec<Symbol>, FundForgeError> {
         let time = match time {
             None => None,
             Some(t) => Some(t.to_string())
@@ -26,7 +26,7 @@ impl DataVendor {
         };
         let (sender, receiver) = oneshot::channel();
         let msg = StrategyRequest::CallBack(ConnectionType::Vendor(self.clone()), request,sender);
-        send_request(msg).await;
+        data_server_sender.send(msg).await;
         match receiver.await {
             Ok(response) => {
                 match response {
@@ -39,14 +39,14 @@ impl DataVendor {
         }
     }
 
-    pub async fn base_data_types(&self) -> Result<Vec<BaseDataType>, FundForgeError> {
+    pub async fn base_data_types(&self, data_server_sender: &DataServerSender) -> Result<Vec<BaseDataType>, FundForgeError> {
         let request = DataServerRequest::BaseDataTypes {
             callback_id: 0,
             data_vendor: self.clone(),
         };
         let (sender, receiver) = oneshot::channel();
         let msg = StrategyRequest::CallBack(ConnectionType::Vendor(self.clone()), request,sender);
-        send_request(msg).await;
+        data_server_sender.send(msg).await;
         match receiver.await {
             Ok(response) => {
                 match response {
@@ -59,7 +59,7 @@ impl DataVendor {
         }
     }
 
-    pub async fn resolutions(&self, market_type: MarketType) -> Result<Vec<PrimarySubscription>, FundForgeError> {
+    pub async fn resolutions(&self, market_type: MarketType, data_server_sender: &DataServerSender) -> Result<Vec<PrimarySubscription>, FundForgeError> {
         let request = DataServerRequest::Resolutions {
             callback_id: 0,
             data_vendor: self.clone(),
@@ -67,7 +67,7 @@ impl DataVendor {
         };
         let (sender, receiver) = oneshot::channel();
         let msg = StrategyRequest::CallBack(ConnectionType::Vendor(self.clone()), request,sender);
-        send_request(msg).await;
+        data_server_sender.send(msg).await;
         match receiver.await {
             Ok(response) => {
                 match response {
@@ -80,7 +80,7 @@ impl DataVendor {
         }
     }
 
-    pub async fn warm_up_resolutions(&self, market_type: MarketType) -> Result<Vec<PrimarySubscription>, FundForgeError> {
+    pub async fn warm_up_resolutions(&self, market_type: MarketType, data_server_sender: &DataServerSender) -> Result<Vec<PrimarySubscription>, FundForgeError> {
         let request = DataServerRequest::WarmUpResolutions {
             callback_id: 0,
             data_vendor: self.clone(),
@@ -88,7 +88,7 @@ impl DataVendor {
         };
         let (sender, receiver) = oneshot::channel();
         let msg = StrategyRequest::CallBack(ConnectionType::Vendor(self.clone()), request,sender);
-        send_request(msg).await;
+        data_server_sender.send(msg).await;
         match receiver.await {
             Ok(response) => {
                 match response {
@@ -101,14 +101,14 @@ impl DataVendor {
         }
     }
 
-    pub async fn markets(&self) -> Result<Vec<MarketType>, FundForgeError> {
+    pub async fn markets(&self, data_server_sender: &DataServerSender) -> Result<Vec<MarketType>, FundForgeError> {
         let request = DataServerRequest::Markets {
             callback_id: 0,
             data_vendor: self.clone(),
         };
         let (sender, receiver) = oneshot::channel();
         let msg = StrategyRequest::CallBack(ConnectionType::Vendor(self.clone()), request,sender);
-        send_request(msg).await;
+        data_server_sender.send(msg).await;
         match receiver.await {
             Ok(response) => {
                 match response {
@@ -121,6 +121,7 @@ impl DataVendor {
         }
     }
 
+    /// Requested through the shared sender, indicators and consolidators look it up when they are created, before they belong to a strategy.
     pub async fn decimal_accuracy(&self, symbol_name: SymbolName) -> Result<u32, FundForgeError> {
         match self {
             DataVendor::Rithmic => {
@@ -149,7 +150,7 @@ impl DataVendor {
         };
         let (sender, receiver) = oneshot::channel();
         let msg = StrategyRequest::CallBack(ConnectionType::Vendor(self.clone()), request,sender);
-        send_request(msg).await;
+        send_shared_request(msg).await;
         match receiver.await {
             Ok(response) => {
                 match response {
@@ -162,6 +163,7 @@ impl DataVendor {
         }
     }
 
+    /// Requested through the shared sender, indicators and consolidators look it up when they are created, before they belong to a strategy.
     pub async fn tick_size(&self, symbol_name: SymbolName) -> Result<Price, FundForgeError> {
         match self {
             DataVendor::DataBento => {}
@@ -192,7 +194,7 @@ impl DataVendor {
         };
         let (sender, receiver) = oneshot::channel();
         let msg = StrategyRequest::CallBack(ConnectionType::Vendor(self.clone()), request,sender);
-        send_request(msg).await;
+        send_shared_request(msg).await;
         match receiver.await {
             Ok(response) => {
                 match response {
@@ -1,8 +1,9 @@
 use std::collections::BTreeMap;
 use std::time::Duration;
+use tokio::sync::watch;
 use tokio::sync::mpsc::{Sender};
 use std::sync::Arc;
-use chrono::{Utc};
+use chrono::{DateTime, Utc};
 use tokio::runtime::Runtime;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
@@ -13,12 +14,10 @@ use crate::standardized_types::bytes_trait::Bytes;
 use crate::standardized_types::time_slices::TimeSlice;
 use crate::strategies::client_features::connection_settings::client_settings::ConnectionSettings;
 use crate::strategies::client_features::init_clients::create_async_api_client;
-use crate::strategies::client_features::server_connections::set_warmup_complete;
 use crate::strategies::handlers::indicator_handler::IndicatorHandler;
-use crate::strategies::handlers::live_warmup::WARMUP_COMPLETE_BROADCASTER;
 use crate::strategies::handlers::market_handler::price_service::MarketPriceService;
 use crate::strategies::handlers::subscription_handler::SubscriptionHandler;
-use crate::strategies::historical_time::{get_backtest_time, update_backtest_time};
+use crate::strategies::historical_time::StrategyClock;
 use crate::strategies::ledgers::ledger_service::LedgerService;
 use crate::strategies::strategy_events::StrategyEvent;
 
@@ -31,6 +30,8 @@ pub async fn handle_live_data(
     indicator_handler: Arc<IndicatorHandler>,
     subscription_handler: Arc<SubscriptionHandler>,
     market_price_service: Arc<MarketPriceService>,
+    clock: StrategyClock,
+    warmup_complete_sender: watch::Sender<Option<DateTime<Utc>>>,
 ) {
 
     let mut stream_client = match create_async_api_client(&connection_settings, true).await {
@@ -57,6 +58,7 @@ pub async fn handle_live_data(
         return;
     }
 
+    let warmup_completion_receiver = warmup_complete_sender.subscribe();
     let _ = tokio::task::spawn_blocking(move || {
         let rt = Runtime::new().unwrap();
         rt.block_on(async {
@@ -67,6 +69,8 @@ pub async fn handle_live_data(
                 indicator_handler,
                 subscription_handler,
                 market_price_service,
+                clock,
+                warmup_completion_receiver,
             ).await;
         });
     });
@@ -79,11 +83,12 @@ async fn receive_and_process(
     indicator_handler: Arc<IndicatorHandler>,
     subscription_handler: Arc<SubscriptionHandler>,
     price_service: Arc<MarketPriceService>,
+    clock: StrategyClock,
+    mut warmup_completion_receiver: watch::Receiver<Option<DateTime<Utc>>>,
 ) {
     const LENGTH: usize = 4;
     let mut length_bytes = [0u8; LENGTH];
     let mut  buffered_data: BTreeMap<i64, TimeSlice> =BTreeMap::new();
-    let mut warmup_completion_receiver = WARMUP_COMPLETE_BROADCASTER.subscribe();
     #[allow(unused_assignments)]
     let mut warm_up_end = Utc::now();
     // First phase: Buffer data during warmup
@@ -119,8 +124,8 @@ async fn receive_and_process(
                     }
                 }
             }
-            Ok(time) = warmup_completion_receiver.recv() => {
-                warm_up_end = time;
+            Ok(time) = warmup_completion_receiver.wait_for(|time| time.is_some()) => {
+                warm_up_end = time.unwrap_or_else(Utc::now);
                 break;
             }
         }
@@ -134,7 +139,7 @@ async fn receive_and_process(
             .range(..=Utc::now().timestamp())
             .filter(|(_, slice)| !slice.is_empty())
         {
-            if *time <= get_backtest_time().timestamp() {
+            if *time <= clock.time().timestamp() {
                 continue;
             }
             let mut strategy_time_slice = TimeSlice::new();
@@ -155,7 +160,7 @@ async fn receive_and_process(
         }
     }
     drop(buffered_data);
-    set_warmup_complete();
+    clock.set_warmup_complete();
 
 
     let now = tokio::time::Instant::now();
@@ -179,7 +184,7 @@ async fn receive_and_process(
                     };
                     let _ = strategy_event_sender.send(StrategyEvent::TimeSlice(consolidated_data)).await;
                 }
-                update_backtest_time(now);
+                clock.update(now);
             }
             result = stream_client.read_exact(&mut length_bytes) => {
                 match result {
@@ -4,13 +4,13 @@ use crate::messages::data_server_messaging::{DataServerRequest, StreamRequest};
 use crate::standardized_types::enums::StrategyMode;
 use crate::strategies::client_features::connection_settings::client_settings::initialise_settings;
 use crate::strategies::client_features::connection_types::ConnectionType;
-use crate::strategies::client_features::request_handler::StrategyRequest;
-use crate::strategies::client_features::request_handler;
+use crate::strategies::client_features::request_handler::{DataServerSender, StrategyRequest};
 use crate::strategies::handlers::subscription_handler::SubscriptionHandler;
 
 pub(crate) async fn live_subscription_handler(
     mode: StrategyMode,
-    subscription_handler: Arc<SubscriptionHandler>
+    subscription_handler: Arc<SubscriptionHandler>,
+    data_server_sender: DataServerSender
 ) {
     if mode == StrategyMode::Backtest {
         return;
@@ -37,11 +37,11 @@ pub(crate) async fn live_subscription_handler(
                 };
                 if !subscribed.contains(&connection_type) {
                     let register = StrategyRequest::OneWay(connection_type.clone(), DataServerRequest::Register(mode.clone()));
-                    request_handler::send_request(register).await;
+                    data_server_sender.send(register).await;
                     subscribed.push(connection_type.clone());
                 }
                 let request = StrategyRequest::OneWay(connection_type, request);
-                request_handler::send_request(request).await;
+                data_server_sender.send(request).await;
             }
         }
         while let Ok(updated_subscriptions) = subscription_update_channel.recv().await {
@@ -81,7 +81,7 @@ pub(crate) async fn live_subscription_handler(
                 for (connection, requests) in requests_map {
                     for request in requests {
                         let request = StrategyRequest::OneWay(connection.clone(), request);
-                        request_handler::send_request(request).await;
+                        data_server_sender.send(request).await;
                     }
                 }
                 current_subscriptions = updated_subscriptions.clone();
@@ -7,9 +7,9 @@ use crate::standardized_types::accounts::Currency;
 use crate::standardized_types::datavendor_enum::DataVendor;
 use crate::standardized_types::enums::OrderSide;
 use crate::strategies::client_features::connection_types::ConnectionType;
-use crate::strategies::client_features::request_handler::{send_request, StrategyRequest};
+use crate::strategies::client_features::request_handler::{DataServerSender, StrategyRequest};
 
-pub async fn get_exchange_rate(from_currency: Currency, to_currency: Currency, date_time: DateTime<Utc>, side: OrderSide) -> Result<Decimal, FundForgeError> {
+pub async fn get_exchange_rate(from_currency: Currency, to_currency: Currency, date_time: DateTime<Utc>, side: OrderSide, data_server_sender: &DataServerSender) -> Result<Decimal, FundForgeError> {
     let currency_pair_string = format!("{}-{}", from_currency.to_string(), to_currency.to_string());
     let data_vendor = match OANDA_FX_SYMBOLS.contains(&currency_pair_string) {
         true => DataVendor::Oanda,
@@ -32,7 +32,7 @@ pub async fn get_exchange_rate(from_currency: Currency, to_currency: Currency, d
     //eprintln!("Getting exchange rate for {}-{} at {}", from_currency.to_string(), to_currency.to_string(), date_time);
     let (sender, receiver) = oneshot::channel();
     let msg = StrategyRequest::CallBack(ConnectionType::Vendor(data_vendor), request, sender);
-    send_request(msg).await;
+    data_server_sender.send(msg).await;
     match receiver.await {
         Ok(response) => match response {
             DataServerResponse::ExchangeRate { rate, .. } => Ok(rate),
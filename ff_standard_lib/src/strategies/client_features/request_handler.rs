@@ -16,11 +16,35 @@ pub(crate) enum StrategyRequest {
     OneWay(ConnectionType, DataServerRequest),
 }
 
-pub(crate) static DATA_SERVER_SENDER: OnceCell<Sender<StrategyRequest>> = OnceCell::new();
+/// The requests of one strategy to its own data server connections, cloned into each handler that sends requests for it so several strategies can run in one process.
+/// Each strategy registers its connections with its own mode, so its streams, orders and lookups are answered for that mode and routed back to that strategy.
+#[derive(Clone, Debug)]
+pub struct DataServerSender {
+    sender: Sender<StrategyRequest>,
+}
+
+/// The sender of the first strategy initialized in the process, used for the symbol precision lookups made when an indicator or consolidator is created, before it belongs to a strategy.
+static SHARED_SENDER: OnceCell<DataServerSender> = OnceCell::new();
+
+impl DataServerSender {
+    /// Returns the sender and the receiver which `init_connections` forwards to the data server connections.
+    pub(crate) fn new() -> (Self, mpsc::Receiver<StrategyRequest>) {
+        let (sender, receiver) = mpsc::channel(1000);
+        let data_server_sender = Self { sender };
+        let _ = SHARED_SENDER.set(data_server_sender.clone());
+        (data_server_sender, receiver)
+    }
+
+    #[inline(always)]
+    pub(crate) async fn send(&self, req: StrategyRequest) {
+        self.sender.send(req).await.unwrap();
+    }
+}
 
+/// Sends a lookup made outside of a strategy, the response is matched to the request by its callback id.
 #[inline(always)]
-pub(crate) async fn send_request(req: StrategyRequest) {
-    DATA_SERVER_SENDER.get().unwrap().send(req).await.unwrap();
+pub(crate) async fn send_shared_request(req: StrategyRequest) {
+    SHARED_SENDER.get().unwrap().send(req).await;
 }
 
 /// This response handler is also acting as a live engine.
@@ -92,4 +116,23 @@ pub(crate) async fn request_handler(
             }
         }
     });
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::standardized_types::enums::StrategyMode;
+
+    #[tokio::test]
+    async fn test_strategies_have_their_own_connections() {
+        let (backtest_sender, mut backtest_receiver) = DataServerSender::new();
+        let (live_sender, mut live_receiver) = DataServerSender::new();
+
+        backtest_sender.send(StrategyRequest::OneWay(ConnectionType::Default, DataServerRequest::Register(StrategyMode::Backtest))).await;
+        live_sender.send(StrategyRequest::OneWay(ConnectionType::Default, DataServerRequest::Register(StrategyMode::Live))).await;
+
+        assert!(matches!(backtest_receiver.try_recv(), Ok(StrategyRequest::OneWay(_, DataServerRequest::Register(StrategyMode::Backtest)))));
+        assert!(matches!(live_receiver.try_recv(), Ok(StrategyRequest::OneWay(_, DataServerRequest::Register(StrategyMode::Live)))));
+        assert!(backtest_receiver.try_recv().is_err());
+        assert!(live_receiver.try_recv().is_err());
+    }
+}
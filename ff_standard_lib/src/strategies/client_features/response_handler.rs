@@ -4,7 +4,7 @@ use tokio::io::{AsyncReadExt, ReadHalf};
 use tokio_rustls::TlsStream;
 use tokio::net::TcpStream;
 use std::sync::Arc;
-use tokio::sync::oneshot;
+use tokio::sync::{oneshot, watch};
 use tokio::sync::mpsc::Sender;
 use chrono::{DateTime, Utc};
 use std::str::FromStr;
@@ -14,12 +14,13 @@ use crate::standardized_types::enums::StrategyMode;
 use crate::standardized_types::orders::OrderUpdateEvent;
 use crate::standardized_types::subscriptions::DataSubscriptionEvent;
 use crate::strategies::client_features::connection_types::ConnectionType;
-use crate::strategies::client_features::{live_data_receiver, request_handler};
-use crate::strategies::client_features::request_handler::StrategyRequest;
+use crate::strategies::client_features::live_data_receiver;
+use crate::strategies::client_features::request_handler::{DataServerSender, StrategyRequest};
 use crate::strategies::client_features::server_connections::SETTINGS_MAP;
 use crate::strategies::handlers::indicator_handler::IndicatorHandler;
 use crate::strategies::handlers::market_handler::price_service::MarketPriceService;
 use crate::strategies::handlers::subscription_handler::SubscriptionHandler;
+use crate::strategies::historical_time::StrategyClock;
 use crate::strategies::ledgers::ledger_service::LedgerService;
 use crate::strategies::strategy_events::StrategyEvent;
 
@@ -34,14 +35,17 @@ pub async fn response_handler(
     ledger_service: Arc<LedgerService>, //it is better to do this than use messaging, because using a direct fn call we can concurrently update individual ledgers and have a que per ledger. sending a msg here would cause a bottleneck with more ledgers.
     indicator_handler: Arc<IndicatorHandler>,
     subscription_handler: Arc<SubscriptionHandler>,
-    market_price_service: Arc<MarketPriceService>
+    market_price_service: Arc<MarketPriceService>,
+    clock: StrategyClock,
+    data_server_sender: DataServerSender,
+    warmup_complete_sender: watch::Sender<Option<DateTime<Utc>>>
 ) {
     let settings_map = SETTINGS_MAP.clone();
     for (connection, settings) in settings_map.iter() {
         let order_updates_sender = order_updates_sender.clone();
         if let Some((connection, stream)) = server_receivers.remove(connection) {
             let register_message = StrategyRequest::OneWay(connection.clone(), DataServerRequest::Register(mode.clone()));
-            request_handler::send_request(register_message).await;
+            data_server_sender.send(register_message).await;
 
             let mut receiver = stream;
             let callbacks = callbacks.clone();
@@ -51,6 +55,8 @@ pub async fn response_handler(
             let subscription_handler = subscription_handler.clone();
             let indicator_handler = indicator_handler.clone();
             let market_price_service = market_price_service.clone();
+            let clock = clock.clone();
+            let warmup_complete_sender = warmup_complete_sender.clone();
             tokio::task::spawn(async move {
                 const LENGTH: usize = 8;
                 let mut length_bytes = [0u8; LENGTH];
@@ -119,7 +125,7 @@ pub async fn response_handler(
                                 DataServerResponse::RegistrationResponse(port) => {
                                     //println!("Connected to server port: {}", port);
                                     if mode != StrategyMode::Backtest {
-                                        live_data_receiver::handle_live_data(settings.clone(), port, buffer_duration, strategy_event_sender.clone(), ledger_service.clone(), indicator_handler.clone(), subscription_handler.clone(), market_price_service.clone()).await;
+                                        live_data_receiver::handle_live_data(settings.clone(), port, buffer_duration, strategy_event_sender.clone(), ledger_service.clone(), indicator_handler.clone(), subscription_handler.clone(), market_price_service.clone(), clock.clone(), warmup_complete_sender.clone()).await;
                                     }
                                 }
                                 _ => unreachable!("Incorrect response here: {:?}", response)
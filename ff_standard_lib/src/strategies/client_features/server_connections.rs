@@ -3,7 +3,6 @@ use crate::strategies::client_features::init_clients::create_async_api_client;
 use crate::strategies::client_features::connection_settings::client_settings::{initialise_settings, ConnectionSettings};
 use crate::messages::data_server_messaging::DataServerResponse;
 use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
 use chrono::{DateTime, Utc};
 use dashmap::DashMap;
@@ -11,7 +10,7 @@ use lazy_static::lazy_static;
 use tokio::io;
 use tokio::io::{ReadHalf, WriteHalf};
 use tokio::net::TcpStream;
-use tokio::sync::{mpsc, oneshot};
+use tokio::sync::{mpsc, oneshot, watch};
 use tokio::sync::mpsc::Sender;
 use tokio_rustls::TlsStream;
 use crate::strategies::client_features::connection_types::ConnectionType;
@@ -21,24 +20,15 @@ use crate::strategies::strategy_events::StrategyEvent;
 use crate::strategies::handlers::subscription_handler::SubscriptionHandler;
 use crate::standardized_types::orders::OrderUpdateEvent;
 use crate::strategies::client_features::{request_handler, response_handler};
-use crate::strategies::client_features::request_handler::DATA_SERVER_SENDER;
+use crate::strategies::client_features::request_handler::{DataServerSender, StrategyRequest};
 use crate::strategies::handlers::market_handler::price_service::MarketPriceService;
+use crate::strategies::historical_time::StrategyClock;
 use crate::strategies::ledgers::ledger_service::LedgerService;
 
 lazy_static! {
-    static ref WARM_UP_COMPLETE: AtomicBool = AtomicBool::new(false);
     pub(crate) static ref SETTINGS_MAP: Arc<HashMap<ConnectionType, ConnectionSettings>> = Arc::new(initialise_settings().unwrap());
 }
 
-#[inline(always)]
-pub(crate) fn set_warmup_complete() {
-    WARM_UP_COMPLETE.store(true, Ordering::SeqCst);
-}
-#[inline(always)]
-pub(crate) fn is_warmup_complete() -> bool {
-    WARM_UP_COMPLETE.load(Ordering::SeqCst)
-}
-
 pub(crate) async fn init_connections(
     gui_enabled: bool,
     buffer_duration: Duration,
@@ -49,7 +39,11 @@ pub(crate) async fn init_connections(
     ledger_service: Arc<LedgerService>,
     indicator_handler: Arc<IndicatorHandler>,
     subscription_handler: Arc<SubscriptionHandler>,
-    market_price_service: Arc<MarketPriceService>
+    market_price_service: Arc<MarketPriceService>,
+    clock: StrategyClock,
+    data_server_sender: DataServerSender,
+    request_receiver: mpsc::Receiver<StrategyRequest>,
+    warmup_complete_sender: watch::Sender<Option<DateTime<Utc>>>
 ) {
     let server_receivers: DashMap<ConnectionType, ReadHalf<TlsStream<TcpStream>>> = DashMap::with_capacity(SETTINGS_MAP.len());
     let server_senders: DashMap<ConnectionType, WriteHalf<TlsStream<TcpStream>>> = DashMap::with_capacity(SETTINGS_MAP.len());
@@ -70,12 +64,7 @@ pub(crate) async fn init_connections(
         server_senders.insert(connection_type.clone(), write_half);
         server_receivers.insert(connection_type.clone(), read_half);
     }
-    let (tx, rx) = mpsc::channel(1000);
-    let _ = DATA_SERVER_SENDER.get_or_init(|| {
-        tx
-    }).clone();
-
     let callbacks: Arc<DashMap<u64, oneshot::Sender<DataServerResponse>>> = Default::default();
-    request_handler::request_handler(rx, server_senders, callbacks.clone()).await;
-    response_handler::response_handler(mode, buffer_duration, server_receivers, callbacks, order_updates_sender, synchronise_accounts, strategy_event_sender, ledger_service, indicator_handler, subscription_handler, market_price_service).await;
+    request_handler::request_handler(request_receiver, server_senders, callbacks.clone()).await;
+    response_handler::response_handler(mode, buffer_duration, server_receivers, callbacks, order_updates_sender, synchronise_accounts, strategy_event_sender, ledger_service, indicator_handler, subscription_handler, market_price_service, clock, data_server_sender, warmup_complete_sender).await;
 }
@@ -377,6 +377,8 @@ impl CandleStickConsolidator {
                 self.update_candles(base_data)
             }
             BaseDataType::Fundamentals => panic!("Fundamentals are not supported"),
+            BaseDataType::OrderBooks => panic!("Order books are not supported"),
+            BaseDataType::MarketByOrder => panic!("Market by order is not supported"),
         }
     }
 }
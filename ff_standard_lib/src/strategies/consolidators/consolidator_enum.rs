@@ -11,6 +11,7 @@ use crate::standardized_types::base_data::base_data_type::BaseDataType;
 use crate::standardized_types::base_data::history::{get_compressed_historical_data};
 use crate::standardized_types::market_hours::TradingHours;
 use crate::standardized_types::resolution::Resolution;
+use crate::strategies::client_features::request_handler::DataServerSender;
 use crate::strategies::consolidators::daily_candles::DailyConsolidator;
 use crate::strategies::consolidators::daily_quotebars::DailyQuoteConsolidator;
 use crate::strategies::consolidators::weekly::WeeklyCandleConsolidator;
@@ -205,13 +206,14 @@ impl ConsolidatorEnum {
         to_time: DateTime<Utc>,
         history_to_retain: i32,
         _strategy_mode: StrategyMode,
+        data_server_sender: &DataServerSender,
     ) -> (ConsolidatorEnum, RollingWindow<BaseDataEnum>) {
         let subscription = consolidator.subscription();
         let mut vendor_resolutions = filter_resolutions(
             subscription
                 .symbol
                 .data_vendor
-                .warm_up_resolutions(subscription.market_type.clone())
+                .warm_up_resolutions(subscription.market_type, data_server_sender)
                 .await
                 .unwrap(),
             consolidator.subscription().resolution,
@@ -254,7 +256,7 @@ impl ConsolidatorEnum {
 
         let mut history = RollingWindow::new(history_to_retain as usize);
         //eprintln!("Warmup from: {} to: {}", from_time, to_time);
-        let data = match get_compressed_historical_data(vec![base_subscription.clone()], from_time, to_time).await {
+        let data = match get_compressed_historical_data(vec![base_subscription.clone()], from_time, to_time, data_server_sender).await {
             Ok(data) => data,
             Err(_) => {
                 //eprintln!("No data available or error: {}", e);
@@ -15,18 +15,25 @@ use crate::standardized_types::subscriptions::{DataSubscription, SymbolCode, Sym
 use crate::strategies::handlers::timed_events_handler::{TimedEvent, TimedEventHandler};
 use std::collections::BTreeMap;
 use std::fs;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
 use dashmap::DashMap;
 use rust_decimal::Decimal;
-use tokio::sync::{mpsc, oneshot, Notify};
+use rkyv::Archive;
+use rkyv::de::deserializers::SharedDeserializeMap;
+use rkyv::ser::serializers::AllocSerializer;
+use rkyv::validation::validators::DefaultValidator;
+use rkyv::CheckBytes;
+use tokio::sync::{mpsc, oneshot, watch, Notify};
 use tokio::sync::mpsc::Sender;
 use uuid::Uuid;
 use crate::helpers::converters::{naive_date_time_to_tz, naive_date_time_to_utc, resolve_market_datetime_in_timezone};
 use crate::helpers::decimal_calculators::round_to_tick_size;
-use crate::strategies::client_features::server_connections::{init_connections, is_warmup_complete};
+use crate::strategies::client_features::server_connections::init_connections;
 use crate::standardized_types::base_data::candle::Candle;
 use crate::standardized_types::base_data::quote::Quote;
+use crate::standardized_types::base_data::order_book::OrderBook;
 use crate::standardized_types::base_data::quotebar::QuoteBar;
 use crate::standardized_types::base_data::tick::Tick;
 use crate::messages::data_server_messaging::{DataServerRequest, DataServerResponse};
@@ -36,20 +43,41 @@ use crate::standardized_types::base_data::base_data_enum::BaseDataEnum;
 use crate::standardized_types::broker_enum::Brokerage;
 use crate::standardized_types::market_hours::TradingHours;
 use crate::standardized_types::new_types::{Price, Volume};
-use crate::standardized_types::orders::{Order, OrderId, OrderRequest, OrderType, OrderUpdateType, TimeInForce};
+use crate::standardized_types::orders::{Order, OrderGroupId, OrderGroupType, OrderId, OrderRequest, OrderType, OrderUpdateEvent, OrderUpdateType, ProtectiveOrder, TimeInForce};
 use crate::standardized_types::position::Position;
 use crate::strategies::client_features::connection_types::ConnectionType;
 use crate::strategies::client_features::live_subscriptions::live_subscription_handler;
-use crate::strategies::client_features::request_handler::{send_request, StrategyRequest};
-use crate::strategies::handlers::live_warmup::live_warm_up;
+use crate::strategies::client_features::request_handler::{DataServerSender, StrategyRequest};
+use crate::strategies::handlers::live_warmup::{live_warm_up, skip_warm_up};
 use crate::strategies::handlers::market_handler::backtest_matching_engine;
 use crate::strategies::handlers::market_handler::backtest_matching_engine::BackTestEngineMessage;
+use crate::strategies::handlers::market_handler::fill_models::{FillModel, TopOfBook};
+use crate::strategies::handlers::market_handler::intrabar::IntrabarPath;
+use crate::strategies::handlers::market_handler::latency::LatencyModel;
 use crate::strategies::handlers::market_handler::live_order_matching::live_order_handler;
+use crate::strategies::handlers::market_handler::order_groups::OrderGroupManager;
 use crate::strategies::handlers::market_handler::price_service::MarketPriceService;
+use crate::strategies::handlers::market_handler::risk_manager::{RiskContext, RiskManager, RiskViolation};
 use crate::strategies::historical_engine::HistoricalEngine;
-use crate::strategies::historical_time::{get_backtest_time, update_backtest_time};
+use crate::strategies::historical_time::StrategyClock;
 use crate::strategies::indicators::indicator_events::IndicatorEvents;
+use crate::strategies::ledgers::commissions::{CommissionModel, CommissionSchedules};
+use crate::strategies::ledgers::financing::FinancingSchedules;
+use crate::strategies::ledgers::margin::MarginSchedules;
+use crate::strategies::optimisation::export_optimisation_report;
+use crate::strategies::statistics::PerformanceReport;
 use crate::strategies::ledgers::ledger_service::LedgerService;
+use crate::strategies::position_sizing::PositionSizer;
+use crate::standardized_types::continuous_contracts::ContinuousContract;
+use crate::strategies::roll_manager::{RollManager, RollSettings};
+use crate::strategies::reconciliation::{reconcile_account, ReconciliationPolicy, ReconciliationReport};
+use crate::strategies::checkpoint::{from_rkyv_bytes, to_rkyv_bytes, CheckpointSettings, Checkpointer, StrategyCheckpoint};
+use crate::strategies::client_features::other_requests::get_exchange_rate;
+use crate::messages::data_server_messaging::FundForgeError;
+use rust_decimal_macros::dec;
+
+/// The directory the html reports are written to by `FundForgeStrategy::write_reports()`.
+pub const HTML_REPORT_DIRECTORY: &str = "./reports";
 
 /// The `FundForgeStrategy` struct is the main_window struct for the FundForge strategy. It contains the state of the strategy and the callback function for data updates.
 
@@ -86,12 +114,32 @@ pub struct FundForgeStrategy {
 
     ledger_service: Arc<LedgerService>,
 
-    market_price_service: Arc<MarketPriceService>
+    market_price_service: Arc<MarketPriceService>,
 
+    order_groups: Arc<OrderGroupManager>,
+
+    /// The strategy time, shared with the handlers and engines of this strategy only.
+    clock: StrategyClock,
+
+    /// The requests to the data server connections of this strategy only.
+    data_server_sender: DataServerSender,
+
+    risk_manager: RiskManager,
+
+    strategy_event_sender: mpsc::Sender<StrategyEvent>,
+
+    checkpointer: Arc<Checkpointer>,
+
+    /// The checkpoint the strategy resumed from in live modes.
+    restored_checkpoint: Option<StrategyCheckpoint>,
+
+    /// The reports of the startup reconciliation with the brokerage, one per account.
+    reconciliation_reports: Vec<ReconciliationReport>
 }
 
 impl FundForgeStrategy {
     /// Initializes a new `FundForgeStrategy` instance with the provided parameters.
+    /// Returns an error if the commission, margin or financing schedules in the resources folder can not be loaded.
     ///
     /// # Arguments
     /// `strategy_mode: StrategyMode`: The mode of the strategy (Backtest, Live, LivePaperTrading).
@@ -129,6 +177,24 @@ impl FundForgeStrategy {
     /// `tick_over_no_data: bool`: If true the Backtest engine will tick at buffer resolution speed over weekends or other no data periods.
     ///
     /// `synchronize_accounts: bool` If true strategy positions will update in sync with the brokerage, if false the engine will simulate positions using the same logic as backtesting. //todo[ReadMe], explain in more detail
+    ///
+    /// `fill_model: Option<Box<dyn FillModel>>`: The model used to fill orders in backtests and live paper trading, see the built-in models in `fill_models`. If None, orders fill at the top of the book with `TopOfBook`.
+    ///
+    /// `commission_model: Option<Box<dyn CommissionModel>>`: The model used to charge fees on fills in backtests and live paper trading. If None, the schedules in `resources/commissions.toml` are used with `CommissionSchedules`.
+    ///
+    /// `intrabar_path: IntrabarPath`: The path price is assumed to take inside bars when backtesting on `Candle` or `QuoteBar` data, used to trigger stop and limit orders crossed by the high or low. `IntrabarPath::Close` only uses the close.
+    ///
+    /// `latency_model: LatencyModel`: The simulated time for order requests to reach each brokerage in backtests, cancels which arrive after an order fills are rejected as they would be live. Use `LatencyModel::none()` to process requests immediately, this is ignored in live paper trading.
+    ///
+    /// `risk_manager: RiskManager`: The limits every order is checked against before it is sent, orders which break a limit are returned as `OrderUpdateEvent::OrderRejected`. Use `RiskManager::none()` to send every order.
+    ///
+    /// `checkpoint_settings: CheckpointSettings`: In live modes the strategy saves a `StrategyCheckpoint` on an interval and resumes its positions, open orders, indicator history, timed events, drawing tools and custom state from the last checkpoint when it starts, without warming up. A checkpoint which can not be read is returned as an error. Use `CheckpointSettings::none()` to always start from scratch, this is ignored in backtests.
+    ///
+    /// `reconciliation_policy: Option<ReconciliationPolicy>`: In live mode the open orders and positions at the brokerage are compared with the restored checkpoint before the strategy starts, the policy decides whether the differences are adopted, cancelled and flattened, or the strategy refuses to start. Use `None` to skip the reconciliation, this is ignored in backtests and live paper trading.
+    ///
+    /// `continuous_contracts: Vec<ContinuousContract>`: In backtests, subscriptions to the symbol name of a `ContinuousContract` receive one series stitched from the data of each futures contract, rolled with the `RollRule` and back adjusted with the `BackAdjustment`. A `StrategyEvent::RollEvent` is sent at each roll. Use `vec![]` for none, this is ignored in live modes.
+    ///
+    /// `roll_settings: RollSettings`: Open futures positions are closed and the same size is opened in the next front month a number of days before the contract rolls over, both orders are tagged with `roll_tag()` and the trade statistics count a rolled position and the position it rolled into as one trade. Use `RollSettings::none()` to leave expiring positions to the strategy.
     pub async fn initialize(
         strategy_mode: StrategyMode,
         backtest_accounts_starting_cash: Decimal,
@@ -145,11 +211,28 @@ impl FundForgeStrategy {
         gui_enabled: bool,
         tick_over_no_data: bool,
         synchronize_accounts: bool,
-        accounts: Vec<Account>
-    ) -> FundForgeStrategy {
-
+        accounts: Vec<Account>,
+        fill_model: Option<Box<dyn FillModel>>,
+        commission_model: Option<Box<dyn CommissionModel>>,
+        intrabar_path: IntrabarPath,
+        latency_model: LatencyModel,
+        risk_manager: RiskManager,
+        checkpoint_settings: CheckpointSettings,
+        reconciliation_policy: Option<ReconciliationPolicy>,
+        continuous_contracts: Vec<ContinuousContract>,
+        roll_settings: RollSettings
+    ) -> Result<FundForgeStrategy, FundForgeError> {
+
+        let (data_server_sender, request_receiver) = DataServerSender::new();
+        let (warmup_complete_sender, _) = watch::channel(None);
         let price_service = Arc::new(MarketPriceService::new());
-        let ledger_service = Arc::new(LedgerService::new(strategy_event_sender.clone(), price_service.clone()));
+        let commission_model: Arc<dyn CommissionModel> = match commission_model {
+            Some(commission_model) => Arc::from(commission_model),
+            None => Arc::new(CommissionSchedules::from_resources()?)
+        };
+        let margin_schedules = MarginSchedules::from_resources()?;
+        let financing_schedules = FinancingSchedules::from_resources()?;
+        let ledger_service = Arc::new(LedgerService::new(strategy_event_sender.clone(), price_service.clone(), commission_model, Arc::new(margin_schedules), Arc::new(financing_schedules), data_server_sender.clone()));
 
         let timed_event_handler = Arc::new(TimedEventHandler::new(strategy_event_sender.clone()));
         let drawing_objects_handler = Arc::new(DrawingObjectHandler::new(AHashMap::new()));
@@ -159,21 +242,22 @@ impl FundForgeStrategy {
         let end_time = resolve_market_datetime_in_timezone(time_zone, end_date).to_utc();
 
         let warm_up_start_time = start_time - warmup_duration;
-        update_backtest_time(warm_up_start_time);
+        let clock = StrategyClock::new(warm_up_start_time);
 
         let open_order_cache: Arc<DashMap<OrderId, Order>> = Arc::new(DashMap::new());
         let closed_order_cache: Arc<DashMap<OrderId, Order>> = Arc::new(DashMap::new());
 
         let notify = Arc::new(Notify::new());
-        let subscription_handler = Arc::new(SubscriptionHandler::new(strategy_mode, strategy_event_sender.clone()).await);
-        let indicator_handler = Arc::new(IndicatorHandler::new(strategy_mode.clone(), subscription_handler.clone()).await);
+        let subscription_handler = Arc::new(SubscriptionHandler::new(strategy_mode, strategy_event_sender.clone(), clock.clone(), data_server_sender.clone()).await);
+        let indicator_handler = Arc::new(IndicatorHandler::new(strategy_mode, subscription_handler.clone(), clock.clone(), data_server_sender.clone()).await);
 
+        let order_groups = Arc::new(OrderGroupManager::new());
         let (live_order_updates_sender, live_order_updates_receiver) = tokio::sync::mpsc::channel(100);
         if strategy_mode == StrategyMode::Live {
-            live_order_handler(open_order_cache.clone(), closed_order_cache.clone(), live_order_updates_receiver, strategy_event_sender.clone(), ledger_service.clone(), synchronize_accounts);
+            live_order_handler(open_order_cache.clone(), closed_order_cache.clone(), live_order_updates_receiver, strategy_event_sender.clone(), ledger_service.clone(), order_groups.clone(), synchronize_accounts, data_server_sender.clone());
         }
 
-        init_connections(gui_enabled, buffering_duration.clone(), strategy_mode.clone(), live_order_updates_sender, synchronize_accounts, strategy_event_sender.clone(), ledger_service.clone(), indicator_handler.clone(), subscription_handler.clone(), price_service.clone()).await;
+        init_connections(gui_enabled, buffering_duration, strategy_mode, live_order_updates_sender, synchronize_accounts, strategy_event_sender.clone(), ledger_service.clone(), indicator_handler.clone(), subscription_handler.clone(), price_service.clone(), clock.clone(), data_server_sender.clone(), request_receiver, warmup_complete_sender.clone()).await;
 
         for (primary, sub, trading_hours) in intraday_subscriptions {
             subscription_handler.subscribe(primary, sub, warm_up_start_time, fill_forward, retain_history, false, trading_hours).await;
@@ -182,12 +266,29 @@ impl FundForgeStrategy {
         let paper_order_sender = match strategy_mode {
             StrategyMode::Live => None,
             StrategyMode::LivePaperTrading | StrategyMode::Backtest => {
-                let sender = backtest_matching_engine::backtest_matching_engine(open_order_cache.clone(), closed_order_cache.clone(), strategy_event_sender.clone(), ledger_service.clone(), notify.clone(), price_service.clone()).await;
+                let fill_model: Arc<dyn FillModel> = match fill_model {
+                    Some(fill_model) => Arc::from(fill_model),
+                    None => Arc::new(TopOfBook)
+                };
+                // live paper trading has real latency and the engine is not driven by backtest time
+                let latency_model = match strategy_mode {
+                    StrategyMode::Backtest => latency_model,
+                    _ => LatencyModel::none()
+                };
+                let sender = backtest_matching_engine::backtest_matching_engine(open_order_cache.clone(), closed_order_cache.clone(), strategy_event_sender.clone(), ledger_service.clone(), notify.clone(), price_service.clone(), fill_model, intrabar_path, latency_model, clock.clone()).await;
                 Some(sender) //todo, live paper wont update orders unless we update time in the backtest engine.
             }
         };
 
-        let strategy = FundForgeStrategy {
+        let checkpointer = Arc::new(Checkpointer::new(checkpoint_settings, ledger_service.clone(), open_order_cache.clone(), indicator_handler.clone(), timed_event_handler.clone(), drawing_objects_handler.clone(), clock.clone(), strategy_event_sender.clone()));
+        let roll_manager = Arc::new(RollManager::new(roll_settings, strategy_mode, ledger_service.clone(), open_order_cache.clone(), paper_order_sender.clone(), clock.clone(), data_server_sender.clone()));
+
+        let restored_checkpoint = match strategy_mode {
+            StrategyMode::Backtest => None,
+            StrategyMode::Live | StrategyMode::LivePaperTrading => checkpointer.load()?,
+        };
+
+        let mut strategy = FundForgeStrategy {
             historical_message_sender: paper_order_sender.clone(),
             backtest_accounts_starting_cash,
             backtest_account_currency,
@@ -199,11 +300,19 @@ impl FundForgeStrategy {
             subscription_handler: subscription_handler.clone(),
             indicator_handler: indicator_handler.clone(),
             timed_event_handler: timed_event_handler.clone(),
-            drawing_objects_handler,
+            drawing_objects_handler: drawing_objects_handler.clone(),
             synchronize_accounts,
             accounts: accounts.clone(),
             ledger_service: ledger_service.clone(),
-            market_price_service: price_service.clone()
+            market_price_service: price_service.clone(),
+            order_groups,
+            clock: clock.clone(),
+            data_server_sender: data_server_sender.clone(),
+            risk_manager,
+            strategy_event_sender: strategy_event_sender.clone(),
+            checkpointer: checkpointer.clone(),
+            restored_checkpoint,
+            reconciliation_reports: vec![]
         };
 
 
@@ -224,25 +333,56 @@ impl FundForgeStrategy {
                     timed_event_handler.clone(),
                     indicator_handler.clone(),
                     subscription_handler.clone(),
-                    price_service.clone()
+                    price_service.clone(),
+                    clock.clone(),
+                    continuous_contracts,
+                    roll_manager.clone(),
+                    data_server_sender.clone()
                 ).await;
 
                 HistoricalEngine::launch(engine).await;
             }
             StrategyMode::LivePaperTrading | StrategyMode::Live  => {
                 TimedEventHandler::run_time_updates(timed_event_handler.clone()).await;
-                live_subscription_handler(strategy_mode.clone(), subscription_handler.clone()).await;
+                live_subscription_handler(strategy_mode, subscription_handler.clone(), data_server_sender.clone()).await;
             },
         }
 
-        for account in accounts {
-            ledger_service.init_ledger(&account,strategy_mode, synchronize_accounts, backtest_accounts_starting_cash, backtest_account_currency).await;
+        for account in &accounts {
+            ledger_service.init_ledger(account,strategy_mode, synchronize_accounts, backtest_accounts_starting_cash, backtest_account_currency).await;
+        }
+
+        if let (StrategyMode::Live, Some(policy)) = (strategy_mode, reconciliation_policy) {
+            for account in &accounts {
+                match reconcile_account(account, policy, strategy.restored_checkpoint.as_mut(), &ledger_service, &strategy.open_order_cache, &data_server_sender).await {
+                    Ok(report) => {
+                        if policy == ReconciliationPolicy::Refuse && !report.is_reconciled() {
+                            return Err(FundForgeError::ClientSideErrorDebug(format!("Refusing to start, {}", report)));
+                        }
+                        strategy.reconciliation_reports.push(report);
+                    }
+                    Err(e) => {
+                        if policy == ReconciliationPolicy::Refuse {
+                            return Err(FundForgeError::ClientSideErrorDebug(format!("Refusing to start, unable to reconcile {}: {}", account, e)));
+                        }
+                        eprintln!("Unable to reconcile {}: {}", account, e);
+                    }
+                }
+            }
         }
 
         if strategy_mode != StrategyMode::Backtest {
-            live_warm_up(Utc::now() - warmup_duration, buffering_duration, subscription_handler, strategy_event_sender, timed_event_handler, ledger_service, indicator_handler, price_service.clone()).await;
+            if let Some(checkpoint) = &strategy.restored_checkpoint {
+                checkpointer.restore(checkpoint).await;
+            }
+            Checkpointer::run(checkpointer);
+            RollManager::run(roll_manager);
+            match strategy.restored_checkpoint.is_some() {
+                true => skip_warm_up(strategy_event_sender, clock, warmup_complete_sender).await,
+                false => live_warm_up(Utc::now() - warmup_duration, buffering_duration, subscription_handler, strategy_event_sender, timed_event_handler, ledger_service, indicator_handler, price_service.clone(), clock, warmup_complete_sender, data_server_sender).await,
+            }
         }
-        strategy
+        Ok(strategy)
     }
 
     pub fn calculate_stop_price(&self, entry_price: Decimal, position_side: PositionSide, max_loss: Decimal, value_per_tick: Decimal, tick_size: Decimal, quantity: Decimal) -> Decimal {
@@ -292,7 +432,7 @@ impl FundForgeStrategy {
                 };
                 let (sender, receiver) = oneshot::channel();
                 let msg = StrategyRequest::CallBack(ConnectionType::Broker(brokerage), request, sender);
-                send_request(msg).await;
+                self.data_server_sender.send(msg).await;
                 match receiver.await {
                     Ok(response) => {
                         match response {
@@ -350,7 +490,8 @@ impl FundForgeStrategy {
         self.ledger_service.is_short(account, name)
     }
 
-    async fn order_id(
+    /// Returns a new unique order id, use this when creating orders manually for `order_group()`.
+    pub async fn order_id(
         &self,
     ) -> OrderId {
         let guid = Uuid::new_v4();
@@ -360,6 +501,69 @@ impl FundForgeStrategy {
         )
     }
 
+    /// Sends the order to the broker, or to the backtest engine in backtests and live paper trading, if it passes the `RiskManager`.
+    async fn send_order(&self, order: Order) -> OrderId {
+        let order_id = order.id.clone();
+        if let Err(violation) = self.risk_check(&order, true) {
+            let account = order.account.clone();
+            self.reject_orders(&account, vec![order], violation).await;
+            return order_id;
+        }
+        let order_request = OrderRequest::Create { account: order.account.clone(), order_type: order.order_type.clone(), order: order.clone() };
+        if self.mode == StrategyMode::Live {
+            self.open_order_cache.insert(order_id.clone(), order);
+            let connection_type = ConnectionType::Broker(order_request.brokerage());
+            let request = StrategyRequest::OneWay(connection_type, DataServerRequest::OrderRequest { request: order_request });
+            self.data_server_sender.send(request).await;
+        } else if let Some(historical_message_sender) = &self.historical_message_sender {
+            historical_message_sender.send(BackTestEngineMessage::OrderRequest(order_request)).await.unwrap();
+        }
+        order_id
+    }
+
+    fn risk_check(&self, order: &Order, new_order: bool) -> Result<(), RiskViolation> {
+        let time = self.time_utc();
+        let size = self.ledger_service.position_size(&order.account, &order.symbol_code);
+        let position = match self.ledger_service.is_short(&order.account, &order.symbol_code) {
+            true => -size,
+            false => size
+        };
+        let context = RiskContext {
+            time,
+            position,
+            open_orders: self.open_order_cache.iter().filter(|open_order| open_order.account == order.account).count(),
+            open_quantity: self.open_order_cache.iter()
+                .filter(|open_order| open_order.id != order.id && open_order.account == order.account && open_order.symbol_name == order.symbol_name && open_order.side == order.side)
+                .filter(|open_order| !matches!(open_order.order_type, OrderType::ExitLong | OrderType::ExitShort))
+                .map(|open_order| open_order.quantity_open)
+                .sum(),
+            equity: self.ledger_service.account_equity(&order.account, time),
+        };
+        self.risk_manager.check(order, &context, new_order)
+    }
+
+    /// Returns the orders to the strategy as `OrderRejected` events, and flattens the account if the `RiskManager` flattens on a violation.
+    async fn reject_orders(&self, account: &Account, orders: Vec<Order>, violation: RiskViolation) {
+        let time = self.time_utc().to_string();
+        for order in orders {
+            let event = StrategyEvent::OrderEvents(OrderUpdateEvent::OrderRejected {
+                account: order.account,
+                symbol_name: order.symbol_name,
+                symbol_code: order.symbol_code,
+                order_id: order.id,
+                reason: violation.to_string(),
+                tag: order.tag,
+                time: time.clone()
+            });
+            if let Err(e) = self.strategy_event_sender.send(event).await {
+                eprintln!("Risk Manager: Failed to send event: {}", e);
+            }
+        }
+        if self.risk_manager.flatten_on_violation() {
+            self.flatten_all_for(account.clone()).await;
+        }
+    }
+
     //todo[Strategy]
     pub async fn custom_order(&self, _order: Order, _order_type: OrderType) -> OrderId {
         todo!("Make a fn that takes an order and figures out what to do with it")
@@ -367,6 +571,7 @@ impl FundForgeStrategy {
 
 
     /// Enters a long position and closes any short positions open for the account and symbol
+    /// If `brackets` is `Some`, the protective orders will be attached to the position when the order fills, replacing any existing protective orders for the position.
     pub async fn enter_long(
         &self,
         symbol_name: &SymbolName,
@@ -375,6 +580,7 @@ impl FundForgeStrategy {
         exchange: Option<String>,
         quantity: Volume,
         tag: String,
+        brackets: Option<Vec<ProtectiveOrder>>,
     ) -> OrderId {
         let order_id = self.order_id().await;
         let order = Order::enter_long(
@@ -385,23 +591,14 @@ impl FundForgeStrategy {
             tag,
             order_id.clone(),
             self.time_utc(),
-            exchange
+            exchange,
+            brackets
         );
-        let order_request = OrderRequest::Create{ account: account.clone(), order: order.clone(), order_type: OrderType::EnterLong };
-        if self.mode == StrategyMode::Live {
-            self.open_order_cache.insert(order_id.clone(), order.clone());
-            let connection_type = ConnectionType::Broker(order_request.brokerage());
-            let request = StrategyRequest::OneWay(connection_type, DataServerRequest::OrderRequest { request: order_request });
-            send_request(request).await;
-        } else {
-            if let Some(historical_message_sender) = &self.historical_message_sender {
-                historical_message_sender.send(BackTestEngineMessage::OrderRequest(order_request)).await.unwrap();
-            }
-        }
-        order_id
+        self.send_order(order).await
     }
 
     /// Enters a short position and closes any long positions open for the account and symbol
+    /// If `brackets` is `Some`, the protective orders will be attached to the position when the order fills, replacing any existing protective orders for the position.
     pub async fn enter_short(
         &self,
         symbol_name: &SymbolName,
@@ -410,6 +607,7 @@ impl FundForgeStrategy {
         exchange: Option<String>,
         quantity: Volume,
         tag: String,
+        brackets: Option<Vec<ProtectiveOrder>>,
     ) -> OrderId {
         let order_id = self.order_id().await;
         let order = Order::enter_short(
@@ -420,20 +618,10 @@ impl FundForgeStrategy {
             tag,
             order_id.clone(),
             self.time_utc(),
-            exchange
+            exchange,
+            brackets
         );
-        let order_request = OrderRequest::Create{ account: account.clone(), order: order.clone(), order_type: OrderType::EnterShort};
-        if self.mode == StrategyMode::Live {
-            self.open_order_cache.insert(order_id.clone(), order.clone());
-            let connection_type = ConnectionType::Broker(order_request.brokerage());
-            let request = StrategyRequest::OneWay(connection_type, DataServerRequest::OrderRequest { request: order_request });
-            send_request(request).await;
-        } else {
-            if let Some(historical_message_sender) = &self.historical_message_sender {
-                historical_message_sender.send(BackTestEngineMessage::OrderRequest(order_request)).await.unwrap();
-            }
-        }
-        order_id
+        self.send_order(order).await
     }
 
     /// Exits a long position or does nothing if no long position
@@ -457,18 +645,7 @@ impl FundForgeStrategy {
             self.time_utc(),
             exchange
         );
-        let order_request = OrderRequest::Create{ account: account.clone(), order: order.clone(), order_type: OrderType::ExitLong};
-        if self.mode == StrategyMode::Live {
-            self.open_order_cache.insert(order_id.clone(), order.clone());
-            let connection_type = ConnectionType::Broker(order_request.brokerage());
-            let request = StrategyRequest::OneWay(connection_type, DataServerRequest::OrderRequest { request: order_request });
-            send_request(request).await;
-        } else {
-            if let Some(historical_message_sender) = &self.historical_message_sender {
-                historical_message_sender.send(BackTestEngineMessage::OrderRequest(order_request)).await.unwrap();
-            }
-        }
-        order_id
+        self.send_order(order).await
     }
 
     /// Exits a short position or does nothing if no short position
@@ -492,18 +669,7 @@ impl FundForgeStrategy {
             self.time_utc(),
             exchange
         );
-        let order_request = OrderRequest::Create{ account: account.clone(), order: order.clone(), order_type: OrderType::ExitShort};
-        if self.mode == StrategyMode::Live {
-            self.open_order_cache.insert(order_id.clone(), order.clone());
-            let connection_type = ConnectionType::Broker(order_request.brokerage());
-            let request = StrategyRequest::OneWay(connection_type, DataServerRequest::OrderRequest { request: order_request });
-            send_request(request).await;
-        } else {
-            if let Some(historical_message_sender) = &self.historical_message_sender {
-                historical_message_sender.send(BackTestEngineMessage::OrderRequest(order_request)).await.unwrap();
-            }
-        }
-        order_id
+        self.send_order(order).await
     }
 
     /// Buys the market and effects any open positions, or creates a new one
@@ -528,19 +694,7 @@ impl FundForgeStrategy {
             self.time_utc(),
             exchange
         );
-        let order_request = OrderRequest::Create{ account: account.clone(), order: order.clone(), order_type: OrderType::Market};
-
-        if self.mode == StrategyMode::Live {
-            self.open_order_cache.insert(order_id.clone(), order.clone());
-            let connection_type = ConnectionType::Broker(order_request.brokerage());
-            let request = StrategyRequest::OneWay(connection_type, DataServerRequest::OrderRequest { request: order_request });
-            send_request(request).await;
-        } else {
-            if let Some(historical_message_sender) = &self.historical_message_sender {
-                historical_message_sender.send(BackTestEngineMessage::OrderRequest(order_request)).await.unwrap();
-            }
-        }
-        order_id
+        self.send_order(order).await
     }
 
     /// Sells the market and effects any open positions, or creates a new one
@@ -565,18 +719,7 @@ impl FundForgeStrategy {
             self.time_utc(),
             exchange
         );
-        let order_request = OrderRequest::Create{ account: account.clone(), order: order.clone(), order_type: OrderType::Market};
-        if self.mode == StrategyMode::Live {
-            self.open_order_cache.insert(order_id.clone(), order.clone());
-            let connection_type = ConnectionType::Broker(order_request.brokerage());
-            let request = StrategyRequest::OneWay(connection_type, DataServerRequest::OrderRequest { request: order_request });
-            send_request(request).await;
-        } else {
-            if let Some(historical_message_sender) = &self.historical_message_sender {
-                historical_message_sender.send(BackTestEngineMessage::OrderRequest(order_request)).await.unwrap();
-            }
-        }
-        order_id
+        self.send_order(order).await
     }
 
     /// Will wait for limit price to be hit to fill, if TIF == TimeInForce::Day, it will be cancelled in backtests when the day is over.
@@ -594,18 +737,7 @@ impl FundForgeStrategy {
     ) -> OrderId {
         let order_id = self.order_id().await;
         let order = Order::limit_order(symbol_name.clone(), symbol_code, account, quantity, side, tag, order_id.clone(), self.time_utc(), limit_price, tif, exchange);
-        let order_request = OrderRequest::Create{ account: account.clone(), order: order.clone(), order_type: OrderType::Limit};
-        if self.mode == StrategyMode::Live {
-            self.open_order_cache.insert(order_id.clone(), order.clone());
-            let connection_type = ConnectionType::Broker(order_request.brokerage());
-            let request = StrategyRequest::OneWay(connection_type, DataServerRequest::OrderRequest { request: order_request });
-            send_request(request).await;
-        } else {
-            if let Some(historical_message_sender) = &self.historical_message_sender {
-                historical_message_sender.send(BackTestEngineMessage::OrderRequest(order_request)).await.unwrap();
-            }
-        }
-        order_id
+        self.send_order(order).await
     }
 
     /// Will trigger if trigger price is hit and buy or sell at market price.
@@ -623,18 +755,7 @@ impl FundForgeStrategy {
     ) -> OrderId {
         let order_id = self.order_id().await;
         let order = Order::market_if_touched(symbol_name.clone(), symbol_code, account, quantity, side, tag, order_id.clone(), self.time_utc(),trigger_price, tif, exchange);
-        let order_request = OrderRequest::Create{ account: account.clone(), order: order.clone(), order_type: OrderType::MarketIfTouched};
-        if self.mode == StrategyMode::Live {
-            self.open_order_cache.insert(order_id.clone(), order.clone());
-            let connection_type = ConnectionType::Broker(order_request.brokerage());
-            let request = StrategyRequest::OneWay(connection_type, DataServerRequest::OrderRequest { request: order_request });
-            send_request(request).await;
-        } else {
-            if let Some(historical_message_sender) = &self.historical_message_sender {
-                historical_message_sender.send(BackTestEngineMessage::OrderRequest(order_request)).await.unwrap();
-            }
-        }
-        order_id
+        self.send_order(order).await
     }
 
     /// Will buy or sell market price if trigger is hit
@@ -652,18 +773,7 @@ impl FundForgeStrategy {
     ) -> OrderId {
         let order_id = self.order_id().await;
         let order = Order::stop(symbol_name.clone(), symbol_code, account, quantity, side, tag, order_id.clone(), self.time_utc(),trigger_price, tif, exchange);
-        let order_request = OrderRequest::Create{ account: account.clone(), order: order.clone(), order_type: OrderType::StopMarket};
-        if self.mode == StrategyMode::Live {
-            self.open_order_cache.insert(order_id.clone(), order.clone());
-            let connection_type = ConnectionType::Broker(order_request.brokerage());
-            let request = StrategyRequest::OneWay(connection_type, DataServerRequest::OrderRequest { request: order_request });
-            send_request(request).await;
-        } else {
-            if let Some(historical_message_sender) = &self.historical_message_sender {
-                historical_message_sender.send(BackTestEngineMessage::OrderRequest(order_request)).await.unwrap();
-            }
-        }
-        order_id
+        self.send_order(order).await
     }
 
     /// Will trigger on trigger price but fill only when price is on the correct side of limit price, will partially fill in backtest if we have order book data present.
@@ -682,18 +792,51 @@ impl FundForgeStrategy {
     ) -> OrderId {
         let order_id = self.order_id().await;
         let order = Order::stop_limit(symbol_name.clone(), symbol_code, account, quantity, side, tag, order_id.clone(), self.time_utc(),limit_price, trigger_price, tif, exchange);
-        let order_request = OrderRequest::Create{ account: account.clone(), order: order.clone(), order_type: OrderType::StopLimit};
+        self.send_order(order).await
+    }
+
+    /// Places a group of linked orders, the orders should be created with the `Order` constructors using ids from `order_id()`.
+    ///
+    /// `OrderGroupType::OneCancelsOther`: All orders are placed, when any order is filled the remaining orders are cancelled. eg: a breakout buy stop above and sell stop below the market.
+    ///
+    /// `OrderGroupType::OneTriggersOther`: Only the first order is placed, the remaining orders are placed once it is filled.
+    ///
+    /// Brokers with native support link the orders on the broker side, for other brokers the strategy emulates the group by placing and cancelling the orders itself.
+    pub async fn order_group(&self, account: &Account, group_type: OrderGroupType, orders: Vec<Order>) -> OrderGroupId {
+        let group_id = Uuid::new_v4().to_string();
+        // the group is placed atomically, if any order breaks a risk limit none of the orders are placed.
+        if let Some(violation) = orders.iter().find_map(|order| self.risk_check(order, true).err()) {
+            self.reject_orders(account, orders, violation).await;
+            return group_id;
+        }
         if self.mode == StrategyMode::Live {
-            self.open_order_cache.insert(order_id.clone(), order.clone());
-            let connection_type = ConnectionType::Broker(order_request.brokerage());
-            let request = StrategyRequest::OneWay(connection_type, DataServerRequest::OrderRequest { request: order_request });
-            send_request(request).await;
+            let orders = match account.brokerage.supports_order_group(&group_type) {
+                true => {
+                    for order in &orders {
+                        self.open_order_cache.insert(order.id.clone(), order.clone());
+                    }
+                    let order_request = OrderRequest::CreateGroup { account: account.clone(), group_id: group_id.clone(), group_type, orders };
+                    let connection_type = ConnectionType::Broker(order_request.brokerage());
+                    let request = StrategyRequest::OneWay(connection_type, DataServerRequest::OrderRequest { request: order_request });
+                    self.data_server_sender.send(request).await;
+                    return group_id;
+                }
+                false => self.order_groups.add_group(group_id.clone(), group_type, account.clone(), orders)
+            };
+            for order in orders {
+                self.open_order_cache.insert(order.id.clone(), order.clone());
+                let order_request = OrderRequest::Create { account: account.clone(), order_type: order.order_type.clone(), order };
+                let connection_type = ConnectionType::Broker(order_request.brokerage());
+                let request = StrategyRequest::OneWay(connection_type, DataServerRequest::OrderRequest { request: order_request });
+                self.data_server_sender.send(request).await;
+            }
         } else {
+            let order_request = OrderRequest::CreateGroup { account: account.clone(), group_id: group_id.clone(), group_type, orders };
             if let Some(historical_message_sender) = &self.historical_message_sender {
                 historical_message_sender.send(BackTestEngineMessage::OrderRequest(order_request)).await.unwrap();
             }
         }
-        order_id
+        group_id
     }
 
     /// Cancels the order if it is not filled, cancelled or rejected.
@@ -714,7 +857,7 @@ impl FundForgeStrategy {
         if self.mode == StrategyMode::Live {
             let connection_type = ConnectionType::Broker(order_request.brokerage());
             let request = StrategyRequest::OneWay(connection_type, DataServerRequest::OrderRequest { request: order_request });
-            send_request(request).await;
+            self.data_server_sender.send(request).await;
         } else {
             if let Some(historical_message_sender) = &self.historical_message_sender {
                 historical_message_sender.send(BackTestEngineMessage::OrderRequest(order_request)).await.unwrap();
@@ -732,6 +875,22 @@ impl FundForgeStrategy {
             return; // Order not found, exit the function
         };
 
+        // increasing the quantity is checked by the risk manager as if the order was placed again
+        if let OrderUpdateType::Quantity(quantity) = &order_update_type {
+            let updated_order = self.open_order_cache.get(&order_id).map(|order| {
+                let mut order = order.value().clone();
+                order.quantity_open = *quantity;
+                order
+            });
+            if let Some(Err(violation)) = updated_order.map(|order| self.risk_check(&order, false)) {
+                let event = StrategyEvent::OrderEvents(OrderUpdateEvent::OrderUpdateRejected { account, order_id, reason: violation.to_string(), time: self.time_utc().to_string() });
+                if let Err(e) = self.strategy_event_sender.send(event).await {
+                    eprintln!("Risk Manager: Failed to send event: {}", e);
+                }
+                return;
+            }
+        }
+
         let order_request = OrderRequest::Update {
             order_id,
             account,
@@ -741,7 +900,7 @@ impl FundForgeStrategy {
         if self.mode == StrategyMode::Live {
             let connection_type = ConnectionType::Broker(order_request.brokerage());
             let request = StrategyRequest::OneWay(connection_type, DataServerRequest::OrderRequest { request: order_request });
-            send_request(request).await;
+            self.data_server_sender.send(request).await;
         } else {
             if let Some(historical_message_sender) = &self.historical_message_sender {
                 historical_message_sender.send(BackTestEngineMessage::OrderRequest(order_request)).await.unwrap();
@@ -772,7 +931,7 @@ impl FundForgeStrategy {
             if self.mode == StrategyMode::Live {
                 let connection_type = ConnectionType::Broker(order_request.brokerage());
                 let request = StrategyRequest::OneWay(connection_type, DataServerRequest::OrderRequest { request: order_request });
-                send_request(request).await;
+                self.data_server_sender.send(request).await;
             }
         }
     }
@@ -783,7 +942,7 @@ impl FundForgeStrategy {
             let order_request = OrderRequest::FlattenAllFor {account};
             let connection_type = ConnectionType::Broker(order_request.brokerage());
             let request = StrategyRequest::OneWay(connection_type, DataServerRequest::OrderRequest { request: order_request });
-            send_request(request).await;
+            self.data_server_sender.send(request).await;
         } else {
             let order_request = OrderRequest::FlattenAllFor {account};
             if let Some(historical_message_sender) = &self.historical_message_sender {
@@ -891,6 +1050,26 @@ impl FundForgeStrategy {
         self.ledger_service.balance(account)
     }
 
+    /// Returns a `PositionSizer` for the symbol using the current balance of the account and the exchange rate from the account currency to the pnl currency of the symbol.
+    /// The exchange rate is requested from the data server when the currencies differ, so don't call this on every tick.
+    /// ```ignore
+    /// let sizer = strategy.position_sizer(&account, &symbol_name).await?;
+    /// // risk 1% of the balance with the stop 2 atr from the entry
+    /// if let Some(atr) = strategy.indicator_current(&atr_name).as_ref().and_then(atr_value) {
+    ///     let quantity = sizer.volatility_target(dec!(1), candle.close, atr, dec!(2));
+    /// }
+    /// ```
+    pub async fn position_sizer(&self, account: &Account, symbol_name: &SymbolName) -> Result<PositionSizer, FundForgeError> {
+        let symbol_info = account.brokerage.symbol_info(symbol_name.clone(), &self.data_server_sender).await?;
+        let account_currency = self.ledger_service.account_currency(account)
+            .ok_or_else(|| FundForgeError::ClientSideErrorDebug(format!("No ledger found for account: {}", account)))?;
+        let exchange_rate = match account_currency == symbol_info.pnl_currency {
+            true => dec!(1),
+            false => get_exchange_rate(account_currency, symbol_info.pnl_currency, self.time_utc(), OrderSide::Buy, &self.data_server_sender).await?,
+        };
+        Ok(PositionSizer::new(account.brokerage, symbol_info, account_currency, self.balance(account), exchange_rate))
+    }
+
     /// see the indicator_enum.rs for more details
     pub async fn indicator_history(
         &self,
@@ -1015,6 +1194,11 @@ impl FundForgeStrategy {
         self.subscription_handler.quote_index(subscription, index)
     }
 
+    /// Returns `OrderBook` at the specified index, where 0 is current `OrderBook` and 1 is 2nd last `OrderBook` and 10 is 10 `OrderBook`s ago (11th).
+    pub fn order_book_index(&self, subscription: &DataSubscription, index: usize) -> Option<OrderBook> {
+        self.subscription_handler.order_book_index(subscription, index)
+    }
+
     /// Current Tz time, depends on the `StrategyMode`. \
     /// Backtest will return the last data point time, live will return the current time.
     pub fn time_local(&self) -> DateTime<Tz> {
@@ -1029,12 +1213,12 @@ impl FundForgeStrategy {
     /// Current Utc time, depends on the `StrategyMode`. \
     /// Backtest will return the last data point time, live will return the current time.
     pub fn time_utc(&self) -> DateTime<Utc> {
-        match is_warmup_complete() {
+        match self.clock.is_warmup_complete() {
             true => match self.mode {
-                StrategyMode::Backtest => get_backtest_time(),
+                StrategyMode::Backtest => self.clock.time(),
                 _ => Utc::now(),
             },
-            false => get_backtest_time(),
+            false => self.clock.time(),
         }
     }
 
@@ -1048,7 +1232,7 @@ impl FundForgeStrategy {
         trading_hours: Option<TradingHours>,
     ) -> BTreeMap<DateTime<Utc>, BaseDataEnum> {
         let start_date = naive_date_time_to_tz(from_time, time_zone);
-        range_history_data(start_date.to_utc(), self.time_utc(), subscription.clone(), self.mode, trading_hours).await
+        range_history_data(start_date.to_utc(), self.time_utc(), subscription.clone(), self.mode, trading_hours, &self.data_server_sender).await
     }
 
     /// Returns a BTreeMap of BaseDataEnum where data.time_closed_utc() is key and data is value.
@@ -1060,7 +1244,7 @@ impl FundForgeStrategy {
         trading_hours: Option<TradingHours>,
     ) -> BTreeMap<DateTime<Utc>, BaseDataEnum> {
         let start_date = naive_date_time_to_utc(from_time);
-        range_history_data(start_date.to_utc(), self.time_utc(), subscription.clone(), self.mode, trading_hours).await
+        range_history_data(start_date.to_utc(), self.time_utc(), subscription.clone(), self.mode, trading_hours, &self.data_server_sender).await
     }
 
     /// Returns a BTreeMap of BaseDataEnum where data.time_closed_utc() is key and data is value.
@@ -1081,7 +1265,7 @@ impl FundForgeStrategy {
             false => end_date.to_utc(),
         };
 
-        range_history_data(start_date.to_utc(), end_date, subscription.clone(), self.mode, trading_hours).await
+        range_history_data(start_date.to_utc(), end_date, subscription.clone(), self.mode, trading_hours, &self.data_server_sender).await
     }
 
     /// Currently returns only primary data that is available, needs to be updated to be able to return all subscriptions via consolidated data
@@ -1100,7 +1284,7 @@ impl FundForgeStrategy {
             false => end_date,
         };
 
-        range_history_data(start_date.to_utc(), end_date, subscription.clone(), self.mode, trading_hours).await
+        range_history_data(start_date.to_utc(), end_date, subscription.clone(), self.mode, trading_hours, &self.data_server_sender).await
     }
 
     /// Prints a ledgers statistics
@@ -1125,6 +1309,18 @@ impl FundForgeStrategy {
         self.ledger_service.export_trades_to_csv(account, directory);
     }
 
+    /// Exports the performance report for the account to a self-contained html file in the directory and returns the path of the file.
+    /// The report includes the equity and drawdown curves, monthly returns, the pnl distribution, a per symbol breakdown and the trade list.
+    pub fn export_html_report(&self, account: &Account, directory: &str) -> Result<PathBuf, FundForgeError> {
+        self.ledger_service.export_html_report(account, directory)
+    }
+
+    /// Writes the html report of each account with closed positions to `HTML_REPORT_DIRECTORY` and returns the paths of the files.
+    /// Call this when handling the `ShutdownEvent`, after flattening, so the report includes the positions closed at shutdown.
+    pub fn write_reports(&self) -> Result<Vec<PathBuf>, FundForgeError> {
+        self.ledger_service.export_html_reports(HTML_REPORT_DIRECTORY)
+    }
+
     /// Save positions to a json file in the directory
     /// Useful for machine learning etc.
     pub fn save_positions_to_file(&self, account: &Account, file_path: &str) {
@@ -1153,6 +1349,58 @@ impl FundForgeStrategy {
         self.ledger_service.print_trade_statistics(account);
     }
 
+    /// Returns the performance report for the account, the report can be requested at any time during or after a run and serialized with `report.to_json()`.
+    /// Returns `None` if there is no ledger for the account.
+    pub fn performance_report(&self, account: &Account) -> Option<PerformanceReport> {
+        self.ledger_service.performance_report(account)
+    }
+
+    /// Writes the performance report for the account to the file requested by the `Optimiser`, call it when the strategy receives the `ShutdownEvent`.
+    /// Does nothing if the strategy was not launched by an optimiser, see `ParameterSet::from_env()`.
+    pub fn export_optimisation_report(&self, account: &Account) {
+        if let Some(report) = self.performance_report(account) {
+            export_optimisation_report(&report);
+        }
+    }
+
+    /// Saves a `StrategyCheckpoint` now, for example before a planned shutdown, and returns the file path.
+    /// Returns an error if checkpoints are not enabled in the `CheckpointSettings`.
+    pub async fn save_checkpoint(&self) -> Result<PathBuf, FundForgeError> {
+        self.checkpointer.save().await
+    }
+
+    /// The checkpoint the strategy resumed from, `None` if it started from scratch.
+    /// Use `checkpoint.indicator_history(&name)` to compare the indicator history before the restart with the indicator rebuilt from the data server.
+    pub fn restored_checkpoint(&self) -> Option<&StrategyCheckpoint> {
+        self.restored_checkpoint.as_ref()
+    }
+
+    /// The reports of the startup reconciliation with the brokerage, empty if no `ReconciliationPolicy` was used.
+    pub fn reconciliation_reports(&self) -> &[ReconciliationReport] {
+        &self.reconciliation_reports
+    }
+
+    /// Sets the custom state saved with each checkpoint, the state is any type deriving the rkyv `Archive`, `Serialize` and `Deserialize` traits with `#[archive(check_bytes)]`.
+    /// Set the state whenever it changes, it is copied into the next checkpoint.
+    pub fn set_checkpoint_state<T: rkyv::Serialize<AllocSerializer<256>>>(&self, state: &T) -> Result<(), FundForgeError> {
+        self.checkpointer.set_user_state(to_rkyv_bytes(state)?);
+        Ok(())
+    }
+
+    /// Returns the custom state restored from the checkpoint, or the last state set with `set_checkpoint_state()`.
+    /// Returns `None` if no state has been set or the saved state is not a `T`.
+    pub fn checkpoint_state<T>(&self) -> Option<T>
+    where
+        T: Archive,
+        T::Archived: for<'a> CheckBytes<DefaultValidator<'a>> + rkyv::Deserialize<T, SharedDeserializeMap>,
+    {
+        let bytes = self.checkpointer.user_state();
+        if bytes.is_empty() {
+            return None;
+        }
+        from_rkyv_bytes(&bytes).ok()
+    }
+
     // Updated position query functions
     pub fn in_profit(&self, account: &Account, symbol_name: &SymbolName) -> bool {
         self.ledger_service.in_profit(account, symbol_name)
@@ -1187,3 +1435,4 @@ impl FundForgeStrategy {
         self.ledger_service.booked_pnl_account(account)
     }
 }
+
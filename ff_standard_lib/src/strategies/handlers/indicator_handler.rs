@@ -11,27 +11,35 @@ use crate::strategies::consolidators::consolidator_enum::ConsolidatorEnum;
 use crate::strategies::indicators::indicator_events::IndicatorEvents;
 use crate::strategies::indicators::indicators_trait::{IndicatorName, Indicators};
 use crate::strategies::indicators::indicator_values::IndicatorValues;
-use crate::strategies::client_features::server_connections::is_warmup_complete;
 use crate::standardized_types::base_data::base_data_enum::BaseDataEnum;
 use crate::standardized_types::base_data::base_data_type::BaseDataType;
 use crate::standardized_types::base_data::traits::BaseData;
 use crate::standardized_types::market_hours::TradingHours;
 use crate::strategies::handlers::subscription_handler::SubscriptionHandler;
+use crate::strategies::historical_time::StrategyClock;
+use crate::strategies::client_features::request_handler::DataServerSender;
 
 pub struct IndicatorHandler {
     indicators: Arc<DashMap<DataSubscription, DashMap<IndicatorName, Box<dyn Indicators>>>>,
     strategy_mode: StrategyMode,
     subscription_map: DashMap<IndicatorName, DataSubscription>, //used to quickly find the subscription of an indicator by name.
     subscription_handler: Arc<SubscriptionHandler>,
+    clock: StrategyClock,
+    data_server_sender: DataServerSender,
+    /// The history restored from a `StrategyCheckpoint` for indicators the strategy has not added again yet.
+    restored_history: DashMap<IndicatorName, RollingWindow<IndicatorValues>>,
 }
 
 impl IndicatorHandler {
-    pub async fn new(strategy_mode: StrategyMode, subscription_handler: Arc<SubscriptionHandler>) -> Self {
+    pub async fn new(strategy_mode: StrategyMode, subscription_handler: Arc<SubscriptionHandler>, clock: StrategyClock, data_server_sender: DataServerSender) -> Self {
         let handler =Self {
             indicators: Default::default(),
             strategy_mode,
             subscription_map: Default::default(),
             subscription_handler,
+            clock,
+            data_server_sender,
+            restored_history: Default::default(),
         };
         handler
     }
@@ -44,9 +52,10 @@ impl IndicatorHandler {
         }
 
         let name = indicator.name().clone();
+        self.restored_history.remove(&name);
 
-        let indicator = match is_warmup_complete() {
-            true => warmup(time, self.strategy_mode.clone(), indicator, self.subscription_handler.clone(), market_hours).await,
+        let indicator = match self.clock.is_warmup_complete() {
+            true => warmup(time, self.strategy_mode, indicator, self.subscription_handler.clone(), market_hours, &self.data_server_sender).await,
             false => indicator,
         };
 
@@ -110,6 +119,9 @@ impl IndicatorHandler {
     }
 
     pub async fn history(&self, name: IndicatorName) -> Option<RollingWindow<IndicatorValues>> {
+        if let Some(history) = self.restored_history.get(&name) {
+            return Some(history.value().clone());
+        }
         let subscription = match self.subscription_map.get(&name) {
             Some(sub) => sub.clone(),
             None => return None,
@@ -126,7 +138,25 @@ impl IndicatorHandler {
         None
     }
 
+    /// The history of every indicator, used to save a `StrategyCheckpoint`.
+    pub(crate) fn histories(&self) -> Vec<(IndicatorName, RollingWindow<IndicatorValues>)> {
+        self.indicators.iter()
+            .flat_map(|map| map.value().iter().map(|indicator| (indicator.key().clone(), indicator.value().history())).collect::<Vec<_>>())
+            .chain(self.restored_history.iter().map(|history| (history.key().clone(), history.value().clone())))
+            .collect()
+    }
+
+    /// Keeps the history of an indicator from a `StrategyCheckpoint`, it is returned for the name until the strategy adds the indicator again.
+    pub(crate) fn restore_history(&self, name: IndicatorName, history: RollingWindow<IndicatorValues>) {
+        if !self.subscription_map.contains_key(&name) {
+            self.restored_history.insert(name, history);
+        }
+    }
+
     pub fn current(&self, name: &IndicatorName) -> Option<IndicatorValues> {
+        if let Some(history) = self.restored_history.get(name) {
+            return history.last().cloned();
+        }
         let subscription = match self.subscription_map.get(name) {
             Some(sub) => sub.clone(),
             None => return None,
@@ -142,6 +172,9 @@ impl IndicatorHandler {
     }
 
     pub fn index(&self, name: &IndicatorName, index: usize) -> Option<IndicatorValues> {
+        if let Some(history) = self.restored_history.get(name) {
+            return history.get(index).cloned();
+        }
         let subscription = match self.subscription_map.get(name) {
             Some(sub) => sub.clone(),
             None => return None,
@@ -165,6 +198,7 @@ async fn warmup( //todo make async task version for live mode
     mut indicator: Box<dyn Indicators>,
      subscription_handler: Arc<SubscriptionHandler>,
      market_hours: Option<TradingHours>,
+     data_server_sender: &DataServerSender,
 ) -> Box<dyn Indicators> {
    //1. Check if we have history for the indicator.subscription
     let subscription =  indicator.subscription();
@@ -218,7 +252,7 @@ async fn warmup( //todo make async task version for live mode
     }
     let _ = subscription_handler.deref();
     let consolidator = ConsolidatorEnum::create_consolidator(subscription.clone(), false, market_hours).await;
-    let (_, window) = ConsolidatorEnum::warmup(consolidator, to_time, (indicator.data_required_warmup() + 1) as i32, strategy_mode).await;
+    let (_, window) = ConsolidatorEnum::warmup(consolidator, to_time, (indicator.data_required_warmup() + 1) as i32, strategy_mode, data_server_sender).await;
     for data in window.history {
         let _ = indicator.update_base_data(&data);
     }
@@ -1,24 +1,33 @@
 use std::sync::Arc;
 use std::time::Duration;
 use chrono::{DateTime, NaiveTime, TimeZone, Utc};
-use lazy_static::lazy_static;
-use tokio::sync::broadcast;
+use tokio::sync::watch;
 use tokio::sync::mpsc::Sender;
 use crate::standardized_types::base_data::history::{get_compressed_historical_data};
 use crate::standardized_types::time_slices::TimeSlice;
+use crate::strategies::client_features::request_handler::DataServerSender;
 use crate::strategies::handlers::indicator_handler::IndicatorHandler;
 use crate::strategies::handlers::market_handler::price_service::MarketPriceService;
 use crate::strategies::handlers::subscription_handler::SubscriptionHandler;
 use crate::strategies::handlers::timed_events_handler::TimedEventHandler;
-use crate::strategies::historical_time::update_backtest_time;
+use crate::strategies::historical_time::StrategyClock;
 use crate::strategies::ledgers::ledger_service::LedgerService;
 use crate::strategies::strategy_events::StrategyEvent;
 
-lazy_static! {
-    pub(crate) static ref WARMUP_COMPLETE_BROADCASTER: broadcast::Sender<DateTime<Utc>> = {
-        let (tx, _) = broadcast::channel(1);
-        tx
-    };
+/// Completes the warm up at the current time without replaying historical data, used when the strategy resumes from a `StrategyCheckpoint`.
+pub(crate) async fn skip_warm_up(
+    strategy_event_sender: Sender<StrategyEvent>,
+    clock: StrategyClock,
+    warmup_complete_sender: watch::Sender<Option<DateTime<Utc>>>,
+) {
+    let now = Utc::now();
+    clock.update(now);
+    warmup_complete_sender.send_replace(Some(now));
+    tokio::task::spawn(async move {
+        if let Err(e) = strategy_event_sender.send(StrategyEvent::WarmUpComplete).await {
+            eprintln!("Live Warmup: Failed to send event: {}", e);
+        }
+    });
 }
 
 pub(crate) async fn live_warm_up(
@@ -29,7 +38,10 @@ pub(crate) async fn live_warm_up(
     timed_event_handler: Arc<TimedEventHandler>,
     ledger_service: Arc<LedgerService>,
     indicator_handler: Arc<IndicatorHandler>,
-    market_price_service: Arc<MarketPriceService>
+    market_price_service: Arc<MarketPriceService>,
+    clock: StrategyClock,
+    warmup_complete_sender: watch::Sender<Option<DateTime<Utc>>>,
+    data_server_sender: DataServerSender
 ) {
     tokio::task::spawn(async move {
         println!("Live Warmup: Warming up the strategy...");
@@ -72,14 +84,14 @@ pub(crate) async fn live_warm_up(
             first_iteration = false;
 
             if last_time >= Utc::now() {
-                WARMUP_COMPLETE_BROADCASTER.send(last_time).unwrap();
+                warmup_complete_sender.send_replace(Some(last_time));
                 if let Err(e) = strategy_event_sender.send(StrategyEvent::WarmUpComplete).await {
                     eprintln!("Live Warmup: Failed to send event: {}", e);
                 }
                 break 'main_loop;
             }
 
-            let mut time_slices = match get_compressed_historical_data(primary_subscriptions.clone(), last_time, to_time).await {
+            let mut time_slices = match get_compressed_historical_data(primary_subscriptions.clone(), last_time, to_time, &data_server_sender).await {
                 Ok(time_slices) => {
                     if time_slices.is_empty() {
                         println!("Live Warmup: No data period, weekend or holiday: skipping to next day");
@@ -100,7 +112,7 @@ pub(crate) async fn live_warm_up(
 
                 // Early exit check
                 if time >= Utc::now() {
-                    WARMUP_COMPLETE_BROADCASTER.send(time).unwrap();
+                    warmup_complete_sender.send_replace(Some(time));
                     if let Err(e) = strategy_event_sender.send(StrategyEvent::WarmUpComplete).await {
                         eprintln!("Live Warmup: Failed to send event: {}", e);
                     }
@@ -117,7 +129,7 @@ pub(crate) async fn live_warm_up(
 
                 // Update time handlers
                 timed_event_handler.update_time(time).await;
-                update_backtest_time(time);
+                clock.update(time);
 
                 // Extract data for current time window
                 let time_range = last_time.timestamp_nanos_opt().unwrap()..=time.timestamp_nanos_opt().unwrap();
@@ -10,9 +10,14 @@ use crate::standardized_types::broker_enum::Brokerage;
 use crate::standardized_types::enums::{OrderSide};
 use crate::product_maps::rithmic::maps::get_futures_trading_hours;
 use crate::standardized_types::new_types::{Price, Volume};
-use crate::standardized_types::orders::{Order, OrderId, OrderRequest, OrderState, OrderType, OrderUpdateEvent, OrderUpdateType, TimeInForce};
+use crate::standardized_types::orders::{Order, OrderGroupType, OrderId, OrderRequest, OrderState, OrderType, OrderUpdateEvent, OrderUpdateType, TimeInForce};
+use crate::strategies::handlers::market_handler::bracket_orders::BracketOrderManager;
+use crate::strategies::handlers::market_handler::fill_models::FillModel;
+use crate::strategies::handlers::market_handler::intrabar::{IntrabarPath, IntrabarTouch};
+use crate::strategies::handlers::market_handler::latency::{InFlightEvents, InFlightRequests, LatencyModel};
+use crate::strategies::handlers::market_handler::order_groups::OrderGroupManager;
 use crate::strategies::handlers::market_handler::price_service::MarketPriceService;
-use crate::strategies::historical_time::get_backtest_time;
+use crate::strategies::historical_time::StrategyClock;
 use crate::strategies::ledgers::ledger_service::{LedgerService};
 use crate::strategies::strategy_events::StrategyEvent;
 
@@ -21,288 +26,321 @@ pub enum BackTestEngineMessage {
     OrderRequest(OrderRequest)
 }
 
+/// The events of a single request or buffer are queued before the response latency is applied, so this only has to hold the events of one pass.
+const ENGINE_EVENT_BUFFER: usize = 10_000;
+
+/// The order caches and services the backtest matching engine processes order requests with.
+struct EngineContext {
+    open_order_cache: Arc<DashMap<OrderId, Order>>,
+    closed_order_cache: Arc<DashMap<OrderId, Order>>,
+    /// Events are sent to the engine first, the engine forwards them to the strategy once the response latency has passed.
+    strategy_event_sender: Sender<StrategyEvent>,
+    ledger_service: Arc<LedgerService>,
+    market_price_service: Arc<MarketPriceService>,
+    order_groups: OrderGroupManager,
+    fill_model: Arc<dyn FillModel>,
+    intrabar_path: IntrabarPath,
+}
+
 pub(crate) async fn backtest_matching_engine(
     open_order_cache: Arc<DashMap<OrderId, Order>>, //todo, make these static or lifetimes if possible.. might not be optimal though, look it up!
     closed_order_cache: Arc<DashMap<OrderId, Order>>,
     strategy_event_sender: Sender<StrategyEvent>,
     ledger_service: Arc<LedgerService>,
     notify: Arc<tokio::sync::Notify>,
-    market_price_service: Arc<MarketPriceService>
+    market_price_service: Arc<MarketPriceService>,
+    fill_model: Arc<dyn FillModel>,
+    intrabar_path: IntrabarPath,
+    latency_model: LatencyModel,
+    clock: StrategyClock
 ) -> Sender<BackTestEngineMessage> {
     let (sender, mut receiver) = tokio::sync::mpsc::channel(100);
     tokio::task::spawn(async move {
+        let (engine_event_sender, mut engine_event_receiver) = tokio::sync::mpsc::channel(ENGINE_EVENT_BUFFER);
+        let context = EngineContext {
+            open_order_cache,
+            closed_order_cache,
+            strategy_event_sender: engine_event_sender,
+            ledger_service,
+            market_price_service,
+            order_groups: OrderGroupManager::new(),
+            fill_model,
+            intrabar_path,
+        };
+        let mut bracket_orders = BracketOrderManager::new();
+        let mut in_flight_requests = InFlightRequests::default();
+        let mut in_flight_events = InFlightEvents::default();
        notify.notify_one();
         while let Some(backtest_message) = receiver.recv().await {
+            let time = clock.time();
+            let is_buffer_time = matches!(backtest_message, BackTestEngineMessage::TickBufferTime);
             match backtest_message {
                 BackTestEngineMessage::OrderRequest(order_request) => {
                     //println!("{:?}", order_request);
-                    let time = get_backtest_time();
-                    match order_request {
-                        OrderRequest::Create { account, mut order, .. } => {
-                            let market_price = match market_price_service.get_market_price(order.side, &order.symbol_name, &order.symbol_code) {
-                                Some(price) => price,
-                                None => panic!("No market price found")
-                            };
-                            //eprintln!("Market Price: {}", market_price);
-                            if order.quantity_open <= dec!(0) {
-                                open_order_cache.remove(&order.id);
-                                let fail_event = StrategyEvent::OrderEvents(OrderUpdateEvent::OrderRejected {
-                                    account,
-                                    symbol_name: order.symbol_name,
-                                    symbol_code: order.symbol_code,
-                                    order_id: order.id.clone(), reason: String::from("Order Quantity Must Be Greater Than Zero"),
-                                    tag: order.tag,
-                                    time: time.to_string()
-                                });
-                                match strategy_event_sender.send(fail_event).await {
-                                    Ok(_) => {}
-                                    Err(e) => eprintln!("Timed Event Handler: Failed to send event: {}", e)
-                                }
-                                continue
-                            }
-                            // check limit price
-                            if (order.order_type == OrderType::StopLimit || order.order_type == OrderType::Limit) &&
-                                ((order.side == OrderSide::Buy && order.limit_price.unwrap() > market_price)
-                                || (order.side == OrderSide::Sell && order.limit_price.unwrap() < market_price))
-                            {
-                                let side_string = match order.side {
-                                    OrderSide::Buy => "Below",
-                                    OrderSide::Sell => "Above"
-                                };
-                                let fail_event = StrategyEvent::OrderEvents(OrderUpdateEvent::OrderRejected {
-                                    account,
-                                    symbol_name: order.symbol_name,
-                                    symbol_code: order.symbol_code,
-                                    order_id: order.id.clone(), reason: String::from(format!("{} Limit Price Must Be {} or Equal to Market Price", order.side, side_string)),
-                                    tag: order.tag,
-                                    time: time.to_string()
-                                });
-                                match strategy_event_sender.send(fail_event).await {
-                                    Ok(_) => {}
-                                    Err(e) => eprintln!("Timed Event Handler: Failed to send event: {}", e)
-                                }
-                                continue
-                            }
-                            //check trigger price
-                            if (order.order_type == OrderType::StopMarket || order.order_type == OrderType::StopLimit || order.order_type == OrderType::MarketIfTouched) &&
-                                ((order.side == OrderSide::Sell && order.trigger_price.unwrap() >= market_price) ||
-                                    (order.side == OrderSide::Buy && order.trigger_price.unwrap() <= market_price))
-                            {
-                                let side_string = match order.side {
-                                    OrderSide::Buy => "Above",  // Changed from "Below"
-                                    OrderSide::Sell => "Below"  // Changed from "Above"
-                                };
-                                let fail_event = StrategyEvent::OrderEvents(OrderUpdateEvent::OrderRejected {
-                                    account,
-                                    symbol_name: order.symbol_name,
-                                    symbol_code: order.symbol_code,
-                                    order_id: order.id.clone(), reason: String::from(format!("{} Trigger Price Must Be {} Market Price", order.side, side_string)),
-                                    tag: order.tag,
-                                    time: time.to_string()
-                                });
-                                match strategy_event_sender.send(fail_event).await {
-                                    Ok(_) => {}
-                                    Err(e) => eprintln!("Timed Event Handler: Failed to send event: {}", e)
-                                }
-                                continue
-                            }
+                    // the request reaches the broker after the simulated latency, until then orders keep working and can still fill.
+                    let latency = latency_model.latency(&order_request);
+                    if let Some(order_request) = in_flight_requests.send(order_request, time, latency) {
+                        handle_order_request(order_request, time, &context, &mut bracket_orders).await;
+                    }
+                }
+                BackTestEngineMessage::TickBufferTime => {
+                    while let Some(order_request) = in_flight_requests.next_arrived(time) {
+                        handle_order_request(order_request, time, &context, &mut bracket_orders).await;
+                    }
+                    if !context.open_order_cache.is_empty() {
+                        simulated_order_matching(time, &context.open_order_cache, &context.closed_order_cache, context.strategy_event_sender.clone(), &context.ledger_service, &context.market_price_service, &mut bracket_orders, &context.order_groups, &context.fill_model, context.intrabar_path).await;
+                    }
+                }
+            }
+            // the strategy only sees the order events once they have arrived back from the broker
+            while let Ok(event) = engine_event_receiver.try_recv() {
+                let latency = latency_model.response_latency(&event);
+                if let Some(event) = in_flight_events.send(event, time, latency) {
+                    forward_event(event, &strategy_event_sender).await;
+                }
+            }
+            while let Some(event) = in_flight_events.next_arrived(time) {
+                forward_event(event, &strategy_event_sender).await;
+            }
+            if is_buffer_time {
+                notify.notify_one();
+            }
+        }
+    });
+    sender
+}
 
-                            if order.order_type == OrderType::ExitLong && !ledger_service.is_long(&account, &order.symbol_code) {
-                                let fail_event = StrategyEvent::OrderEvents(OrderUpdateEvent::OrderRejected {
-                                    account,
-                                    symbol_name: order.symbol_name,
-                                    symbol_code: order.symbol_code,
-                                    order_id: order.id.clone(), reason: String::from("No Long Position To Exit"),
-                                    tag: order.tag,
-                                    time: time.to_string()
-                                });
-                                match strategy_event_sender.send(fail_event).await {
-                                    Ok(_) => {}
-                                    Err(e) => eprintln!("Timed Event Handler: Failed to send event: {}", e)
-                                }
-                                continue
-                            }
+async fn forward_event(event: StrategyEvent, strategy_event_sender: &Sender<StrategyEvent>) {
+    match strategy_event_sender.send(event).await {
+        Ok(_) => {}
+        Err(e) => eprintln!("Backtest Matching Engine: Failed to send event: {}", e)
+    }
+}
 
-                            if order.order_type == OrderType::ExitShort && !ledger_service.is_short(&account, &order.symbol_code) {
-                                let fail_event = StrategyEvent::OrderEvents(OrderUpdateEvent::OrderRejected {
-                                    account,
-                                    symbol_name: order.symbol_name,
-                                    symbol_code: order.symbol_code,
-                                    order_id: order.id.clone(), reason: String::from("No Short Position To Exit"),
-                                    tag: order.tag,
-                                    time: time.to_string()
-                                });
-                                match strategy_event_sender.send(fail_event).await {
-                                    Ok(_) => {}
-                                    Err(e) => eprintln!("Timed Event Handler: Failed to send event: {}", e)
-                                }
-                                continue
-                            }
+/// Processes an order request once it has reached the simulated broker.
+async fn handle_order_request(
+    order_request: OrderRequest,
+    time: DateTime<Utc>,
+    context: &EngineContext,
+    bracket_orders: &mut BracketOrderManager
+) {
+    let EngineContext { open_order_cache, closed_order_cache, strategy_event_sender, ledger_service, market_price_service, order_groups, fill_model, intrabar_path } = context;
+    let intrabar_path = *intrabar_path;
+    match order_request {
+        OrderRequest::Create { account, order, .. } => {
+            let reason = match market_price_service.get_market_price(order.side, &order.symbol_name, &order.symbol_code) {
+                Some(market_price) => order_rejection_reason(&order, market_price, time, ledger_service),
+                None => Some("No Market Price Found".to_string())
+            };
+            if let Some(reason) = reason {
+                open_order_cache.remove(&order.id);
+                let fail_event = StrategyEvent::OrderEvents(OrderUpdateEvent::OrderRejected {
+                    account,
+                    symbol_name: order.symbol_name,
+                    symbol_code: order.symbol_code,
+                    order_id: order.id.clone(), reason,
+                    tag: order.tag,
+                    time: time.to_string()
+                });
+                match strategy_event_sender.send(fail_event).await {
+                    Ok(_) => {}
+                    Err(e) => eprintln!("Timed Event Handler: Failed to send event: {}", e)
+                }
+                return
+            }
 
-                            order.state = OrderState::Accepted;
-                            {
-                                open_order_cache.insert(order.id.clone(), order.clone());
-                            }
-                            let accept_event = StrategyEvent::OrderEvents(OrderUpdateEvent::OrderAccepted {
-                                account: account.clone(),
-                                symbol_name: order.symbol_name.clone(),
-                                symbol_code: order.symbol_code.clone(),
-                                order_id: order.id.clone(),
-                                tag: order.tag.clone(),
-                                time: time.to_string()
-                            });
-                            match strategy_event_sender.send(accept_event).await {
-                                Ok(_) => {}
-                                Err(e) => eprintln!("Timed Event Handler: Failed to send event: {}", e)
-                            }
-                            simulated_order_matching(&open_order_cache, &closed_order_cache, strategy_event_sender.clone(), &ledger_service, &market_price_service).await;
-                        }
-                        OrderRequest::Cancel { account,order_id } => {
-                            if let Some((existing_order_id, order)) = open_order_cache.remove(&order_id) {
-                                let cancel_event = StrategyEvent::OrderEvents(OrderUpdateEvent::OrderCancelled {
-                                    account,
-                                    symbol_name: order.symbol_name.clone(),
-                                    symbol_code: order.symbol_code.clone(),
-                                    order_id: existing_order_id,
-                                    tag: order.tag.clone(), 
-                                    time: time.to_string(),
-                                    reason: "User Request".to_string(),
-                                });
-                                match strategy_event_sender.send(cancel_event).await {
-                                    Ok(_) => {}
-                                    Err(e) => eprintln!("Timed Event Handler: Failed to send event: {}", e)
-                                }
-                                //eprintln!("Order Cancelled: {:?}", order_id);
-                                closed_order_cache.insert(order_id, order);
-                            } else {
-                                let fail_event = StrategyEvent::OrderEvents(OrderUpdateEvent::OrderUpdateRejected {
-                                    account, order_id, reason: String::from("No pending order found"), time: time.to_string()
-                                });
-                                match strategy_event_sender.send(fail_event).await {
-                                    Ok(_) => {}
-                                    Err(e) => eprintln!("Timed Event Handler: Failed to send event: {}", e)
-                                }
-                            }
-                            simulated_order_matching(&open_order_cache, &closed_order_cache, strategy_event_sender.clone(), &ledger_service, &market_price_service).await;
-                        }
-                        OrderRequest::Update { account, order_id, update } => {
-                            if let Some((order_id, mut order)) = open_order_cache.remove(&order_id) {
-                                match &update {
-                                    OrderUpdateType::LimitPrice(price) => {
-                                        if let Some(ref mut limit_price) = order.limit_price {
-                                            *limit_price = price.clone();
-                                        }
-                                    }
-                                    OrderUpdateType::TriggerPrice(price) => {
-                                        if let Some(ref mut trigger_price) = order.trigger_price {
-                                            *trigger_price = price.clone();
-                                        }
-                                    }
-                                    OrderUpdateType::Quantity(quantity) => {
-                                        order.quantity_open = quantity.clone();
-                                    }
-                                }
-                                let update_event = StrategyEvent::OrderEvents(OrderUpdateEvent::OrderUpdated {
-                                    account, symbol_name: order.symbol_name.clone(), symbol_code: order.symbol_name.clone(),
-                                    order_id: order.id.clone(), update_type: update, text: "User Request".to_string(), tag: order.tag.clone(), time: time.to_string()
-                                });
-                                open_order_cache.insert(order_id, order);
-                                match strategy_event_sender.send(update_event).await {
-                                    Ok(_) => {}
-                                    Err(e) => eprintln!("Timed Event Handler: Failed to send event: {}", e)
-                                }
-                            } else {
-                                let fail_event = StrategyEvent::OrderEvents(OrderUpdateEvent::OrderUpdateRejected {
-                                    account, order_id, reason: String::from("No pending order found"), time: time.to_string()
-                                });
-                                match strategy_event_sender.send(fail_event).await {
-                                    Ok(_) => {}
-                                    Err(e) => eprintln!("Timed Event Handler: Failed to send event: {}", e)
-                                }
-                            }
-                            simulated_order_matching(&open_order_cache, &closed_order_cache, strategy_event_sender.clone(), &ledger_service, &market_price_service).await;
-                        }
-                        OrderRequest::CancelAll { account } => {
-                            let mut remove = vec![];
-                            for order in open_order_cache.iter() {
-                                if order.account == account {
-                                    remove.push(order.id.clone());
-                                }
-                            }
-                            for order_id in remove {
-                                if let Some((order_id, mut order)) = open_order_cache.remove(&order_id) {
-                                    order.state = OrderState::Cancelled;
-                                    let cancel_event = StrategyEvent::OrderEvents(
-                                        OrderUpdateEvent::OrderCancelled {
-                                            account: account.clone(),
-                                            symbol_name: order.symbol_name.clone(),
-                                            symbol_code: order.symbol_code.clone(),
-                                            order_id: order.id.clone(),
-                                            reason: "OrderRequest::CancelAll".to_string(),
-                                            tag: order.tag.clone(),
-                                            time: time.to_string(),
-                                        });
-                                    match strategy_event_sender.send(cancel_event).await {
-                                        Ok(_) => {}
-                                        Err(e) => eprintln!("Timed Event Handler: Failed to send event: {}", e)
-                                    }
-                                    closed_order_cache.insert(order_id, order);
-                                }
-                            }
-                            simulated_order_matching(&open_order_cache, &closed_order_cache, strategy_event_sender.clone(), &ledger_service, &market_price_service).await;
+            accept_order(order, time, open_order_cache, strategy_event_sender).await;
+            simulated_order_matching(time, open_order_cache, closed_order_cache, strategy_event_sender.clone(), ledger_service, market_price_service, bracket_orders, order_groups, fill_model, intrabar_path).await;
+        }
+        OrderRequest::Cancel { account,order_id } => {
+            if let Some((existing_order_id, order)) = open_order_cache.remove(&order_id) {
+                let cancel_event = StrategyEvent::OrderEvents(OrderUpdateEvent::OrderCancelled {
+                    account,
+                    symbol_name: order.symbol_name.clone(),
+                    symbol_code: order.symbol_code.clone(),
+                    order_id: existing_order_id,
+                    tag: order.tag.clone(), 
+                    time: time.to_string(),
+                    reason: "User Request".to_string(),
+                });
+                match strategy_event_sender.send(cancel_event).await {
+                    Ok(_) => {}
+                    Err(e) => eprintln!("Timed Event Handler: Failed to send event: {}", e)
+                }
+                //eprintln!("Order Cancelled: {:?}", order_id);
+                closed_order_cache.insert(order_id.clone(), order);
+                close_order_group(&order_id, time, closed_order_cache, strategy_event_sender, order_groups).await;
+            } else {
+                let fail_event = StrategyEvent::OrderEvents(OrderUpdateEvent::OrderUpdateRejected {
+                    account, order_id, reason: String::from("No pending order found"), time: time.to_string()
+                });
+                match strategy_event_sender.send(fail_event).await {
+                    Ok(_) => {}
+                    Err(e) => eprintln!("Timed Event Handler: Failed to send event: {}", e)
+                }
+            }
+            simulated_order_matching(time, open_order_cache, closed_order_cache, strategy_event_sender.clone(), ledger_service, market_price_service, bracket_orders, order_groups, fill_model, intrabar_path).await;
+        }
+        OrderRequest::Update { account, order_id, update } => {
+            if let Some((order_id, mut order)) = open_order_cache.remove(&order_id) {
+                match &update {
+                    OrderUpdateType::LimitPrice(price) => {
+                        if let Some(ref mut limit_price) = order.limit_price {
+                            *limit_price = *price;
                         }
-                        OrderRequest::FlattenAllFor { account} => {
-                            let orders_to_remove: Vec<_> = open_order_cache.iter()
-                                .filter(|order| order.account == account)
-                                .map(|order| order.id.clone())
-                                .collect();
-
-                            for order_id in orders_to_remove {
-                                let (_, mut order) = open_order_cache.remove(&order_id).unwrap(); // Changed here
-                                order.state = OrderState::Cancelled;
-                                let event = StrategyEvent::OrderEvents(OrderUpdateEvent::OrderCancelled {
-                                    account: account.clone(),
-                                    symbol_name: order.symbol_name.clone(),
-                                    symbol_code: order.symbol_code.clone(),
-                                    order_id,  // Using the order_id from the for loop
-                                    reason: "Flatten All".to_string(),
-                                    tag: order.tag.clone(),
-                                    time: time.to_string(),
-                                });
-                                match strategy_event_sender.send(event).await {
-                                    Ok(_) => {}
-                                    Err(e) => eprintln!("Timed Event Handler: Failed to send event: {}", e)
-                                }
-                                closed_order_cache.insert(order.id.clone(), order);
-                            }
-                            ledger_service.flatten_all_for_paper_account(account, time).await;
+                    }
+                    OrderUpdateType::TriggerPrice(price) => {
+                        if let Some(ref mut trigger_price) = order.trigger_price {
+                            *trigger_price = *price;
                         }
                     }
+                    OrderUpdateType::Quantity(quantity) => {
+                        order.quantity_open = *quantity;
+                    }
                 }
-                BackTestEngineMessage::TickBufferTime => {
-                    if !open_order_cache.is_empty() {
-                        simulated_order_matching(&open_order_cache, &closed_order_cache, strategy_event_sender.clone(), &ledger_service, &market_price_service).await;
+                let update_event = StrategyEvent::OrderEvents(OrderUpdateEvent::OrderUpdated {
+                    account, symbol_name: order.symbol_name.clone(), symbol_code: order.symbol_name.clone(),
+                    order_id: order.id.clone(), update_type: update, text: "User Request".to_string(), tag: order.tag.clone(), time: time.to_string()
+                });
+                open_order_cache.insert(order_id, order);
+                match strategy_event_sender.send(update_event).await {
+                    Ok(_) => {}
+                    Err(e) => eprintln!("Timed Event Handler: Failed to send event: {}", e)
+                }
+            } else {
+                let fail_event = StrategyEvent::OrderEvents(OrderUpdateEvent::OrderUpdateRejected {
+                    account, order_id, reason: String::from("No pending order found"), time: time.to_string()
+                });
+                match strategy_event_sender.send(fail_event).await {
+                    Ok(_) => {}
+                    Err(e) => eprintln!("Timed Event Handler: Failed to send event: {}", e)
+                }
+            }
+            simulated_order_matching(time, open_order_cache, closed_order_cache, strategy_event_sender.clone(), ledger_service, market_price_service, bracket_orders, order_groups, fill_model, intrabar_path).await;
+        }
+        OrderRequest::CancelAll { account } => {
+            let mut remove = vec![];
+            for order in open_order_cache.iter() {
+                if order.account == account {
+                    remove.push(order.id.clone());
+                }
+            }
+            for order_id in remove {
+                if let Some((order_id, mut order)) = open_order_cache.remove(&order_id) {
+                    order.state = OrderState::Cancelled;
+                    let cancel_event = StrategyEvent::OrderEvents(
+                        OrderUpdateEvent::OrderCancelled {
+                            account: account.clone(),
+                            symbol_name: order.symbol_name.clone(),
+                            symbol_code: order.symbol_code.clone(),
+                            order_id: order.id.clone(),
+                            reason: "OrderRequest::CancelAll".to_string(),
+                            tag: order.tag.clone(),
+                            time: time.to_string(),
+                        });
+                    match strategy_event_sender.send(cancel_event).await {
+                        Ok(_) => {}
+                        Err(e) => eprintln!("Timed Event Handler: Failed to send event: {}", e)
                     }
-                    notify.notify_one();
+                    closed_order_cache.insert(order_id, order);
                 }
             }
+            bracket_orders.remove_account(&account);
+            let pending = order_groups.remove_account(&account);
+            cancel_pending_orders(pending, "OrderRequest::CancelAll".to_string(), time, closed_order_cache, strategy_event_sender).await;
+            simulated_order_matching(time, open_order_cache, closed_order_cache, strategy_event_sender.clone(), ledger_service, market_price_service, bracket_orders, order_groups, fill_model, intrabar_path).await;
         }
-    });
-    sender
+        OrderRequest::FlattenAllFor { account} => {
+            let orders_to_remove: Vec<_> = open_order_cache.iter()
+                .filter(|order| order.account == account)
+                .map(|order| order.id.clone())
+                .collect();
+
+            for order_id in orders_to_remove {
+                let (_, mut order) = open_order_cache.remove(&order_id).unwrap(); // Changed here
+                order.state = OrderState::Cancelled;
+                let event = StrategyEvent::OrderEvents(OrderUpdateEvent::OrderCancelled {
+                    account: account.clone(),
+                    symbol_name: order.symbol_name.clone(),
+                    symbol_code: order.symbol_code.clone(),
+                    order_id,  // Using the order_id from the for loop
+                    reason: "Flatten All".to_string(),
+                    tag: order.tag.clone(),
+                    time: time.to_string(),
+                });
+                match strategy_event_sender.send(event).await {
+                    Ok(_) => {}
+                    Err(e) => eprintln!("Timed Event Handler: Failed to send event: {}", e)
+                }
+                closed_order_cache.insert(order.id.clone(), order);
+            }
+            bracket_orders.remove_account(&account);
+            let pending = order_groups.remove_account(&account);
+            cancel_pending_orders(pending, "Flatten All".to_string(), time, closed_order_cache, strategy_event_sender).await;
+            ledger_service.flatten_all_for_paper_account(account, time).await;
+        }
+        OrderRequest::CreateGroup { account, group_id, group_type, orders } => {
+            // one triggers other orders are only validated once they are triggered, since they usually depend on the first order filling.
+            let validate = match group_type {
+                OrderGroupType::OneCancelsOther => orders.len(),
+                OrderGroupType::OneTriggersOther => 1
+            };
+            let mut rejection = None;
+            for order in orders.iter().take(validate) {
+                let reason = match market_price_service.get_market_price(order.side, &order.symbol_name, &order.symbol_code) {
+                    Some(market_price) => order_rejection_reason(order, market_price, time, ledger_service),
+                    None => Some("No Market Price Found".to_string())
+                };
+                if let Some(reason) = reason {
+                    rejection = Some(format!("Order Group Rejected: {}: {}", order.id, reason));
+                    break;
+                }
+            }
+            // the group is placed atomically, if any order is invalid none of the orders are placed.
+            if let Some(reason) = rejection {
+                for order in orders {
+                    let fail_event = StrategyEvent::OrderEvents(OrderUpdateEvent::OrderRejected {
+                        account: account.clone(),
+                        symbol_name: order.symbol_name,
+                        symbol_code: order.symbol_code,
+                        order_id: order.id,
+                        reason: reason.clone(),
+                        tag: order.tag,
+                        time: time.to_string()
+                    });
+                    match strategy_event_sender.send(fail_event).await {
+                        Ok(_) => {}
+                        Err(e) => eprintln!("Backtest Matching Engine: Failed to send event: {}", e)
+                    }
+                }
+                return
+            }
+            for order in order_groups.add_group(group_id, group_type, account, orders) {
+                accept_order(order, time, open_order_cache, strategy_event_sender).await;
+            }
+            simulated_order_matching(time, open_order_cache, closed_order_cache, strategy_event_sender.clone(), ledger_service, market_price_service, bracket_orders, order_groups, fill_model, intrabar_path).await;
+        }
+    }
 }
 
 pub(crate) async fn simulated_order_matching (
+    time: DateTime<Utc>,
     open_order_cache: &Arc<DashMap<OrderId, Order>>,
     closed_order_cache: &Arc<DashMap<OrderId, Order>>,
     strategy_event_sender: Sender<StrategyEvent>,
     ledger_service: &Arc<LedgerService>,
-    market_price_service: &Arc<MarketPriceService>
+    market_price_service: &Arc<MarketPriceService>,
+    bracket_orders: &mut BracketOrderManager,
+    order_groups: &OrderGroupManager,
+    fill_model: &Arc<dyn FillModel>,
+    intrabar_path: IntrabarPath
 ) {
-    let time = get_backtest_time();
     let mut rejected = Vec::new();
     let mut cancelled = Vec::new();
     let mut filled = Vec::new();
     let mut partially_filled = Vec::new();
+    let mut intrabar_filled = Vec::new();
+    let mut immediate = Vec::new();
     for order in open_order_cache.iter() {
         //println!("Order matching: {:?}", order.value());
         match &order.time_in_force {
@@ -370,18 +408,8 @@ pub(crate) async fn simulated_order_matching (
                     continue
                 }
             }
-            TimeInForce::IOC=> {
-               /* if time > order.time_created_utc() + Duration::seconds(1) {
-                    let reason = "Time In Force Expired: TimeInForce::IOC".to_string();
-                    cancelled.push((order.id.clone(), reason));
-                }*/
-            }
-            TimeInForce::FOK => {
-                /*if time > order.time_created_utc() + buffer_resolution  {
-                   let reason = "Time In Force Expired: TimeInForce::FOK".to_string();
-                    cancelled.push((order.id.clone(), reason));
-                }*/
-            }
+            // immediate orders are matched once against the current market, anything left unfilled is cancelled after matching
+            TimeInForce::IOC | TimeInForce::FOK => immediate.push((order.id.clone(), order.time_in_force.clone())),
             TimeInForce::Time(cancel_time) => {
                 let cancel_time = match DateTime::<Utc>::from_timestamp(*cancel_time, 0) {
                     Some(time) => time,
@@ -399,6 +427,17 @@ pub(crate) async fn simulated_order_matching (
                 }
             }
         }
+        // orders which were working when the last bar opened are triggered using the bar path
+        if let Some(touch) = intrabar_touch(&order, intrabar_path, market_price_service) {
+            let context = market_price_service.fill_context(&order.account.brokerage, order.side, &order.symbol_name, &order.symbol_code, order.quantity_open, touch.price);
+            let (price, volume) = fill_model.fill(&context);
+            let price = match order.limit_price {
+                Some(limit) => clamp_to_limit(order.side, price, limit),
+                None => price
+            };
+            intrabar_filled.push((touch.position, order.id.clone(), price, volume));
+            continue;
+        }
         //3. respond with an order event
         match &order.order_type {
             OrderType::Limit => {
@@ -407,29 +446,43 @@ pub(crate) async fn simulated_order_matching (
                     None => panic!("No market price found")
                 };
 
+                let limit = order.limit_price.unwrap();
                 let is_fill_triggered = match order.side {
-                    OrderSide::Buy => market_price <= order.limit_price.unwrap(),
-                    OrderSide::Sell => market_price >= order.limit_price.unwrap()
+                    OrderSide::Buy => market_price <= limit,
+                    OrderSide::Sell => market_price >= limit
                 };
+                // with market by order depth an order resting at its limit keeps its place in the queue, it only fills at the limit once the volume queued ahead of it has traded or been cancelled.
+                // the market trading through the limit, or the other side resting at the limit with nothing ahead of us, fills it against the book as usual.
+                if let Some(queue) = market_price_service.queue_positions().position(&order, time) {
+                    let traded_through = is_fill_triggered && market_price != limit;
+                    let touched_at_front = is_fill_triggered && queue.volume_ahead <= dec!(0);
+                    if !traded_through && !touched_at_front {
+                        let volume_filled = queue.traded_volume.min(order.quantity_open);
+                        // a fill or kill order which can not fill completely is cancelled without filling, so it keeps the queue untouched
+                        let is_killed = order.time_in_force == TimeInForce::FOK && volume_filled < order.quantity_open;
+                        if volume_filled > dec!(0) && !is_killed {
+                            market_price_service.queue_positions().consume(&order.id, volume_filled);
+                            push_fill(&order, limit, volume_filled, &mut filled, &mut partially_filled);
+                        }
+                        continue;
+                    }
+                }
                 if is_fill_triggered {
-                    let (market_fill_price, volume_filled) = match market_price_service.estimate_limit_fill(order.side, &order.symbol_name, &order.symbol_code, order.quantity_open, order.limit_price.unwrap()) {
+                    let (market_fill_price, volume_filled) = match model_limit_fill(&order, order.limit_price.unwrap(), market_price_service, fill_model) {
                         Some((price, volume)) => {
                             (price, volume)
                         },
                         None => continue
                     };
-                    match volume_filled == order.quantity_open {
-                        true => filled.push((order.id.clone(),  market_fill_price)),
-                        false => partially_filled.push((order.id.clone(),  market_fill_price, volume_filled))
-                    }
+                    push_fill(&order, market_fill_price, volume_filled, &mut filled, &mut partially_filled);
                 }
             }
             OrderType::Market => {
-                let market_price = match market_price_service.estimate_fill_price(order.side, &order.symbol_name, &order.symbol_code, order.quantity_open) {
-                    Some(price) => price,
+                let (market_price, volume_filled) = match model_market_fill(&order, order.quantity_open, market_price_service, fill_model) {
+                    Some(fill) => fill,
                     None => continue
                 };
-                filled.push((order.id.clone(), market_price));
+                push_fill(&order, market_price, volume_filled, &mut filled, &mut partially_filled);
             },
             // Handle OrderType::StopMarket separately
             OrderType::StopMarket => {
@@ -445,11 +498,11 @@ pub(crate) async fn simulated_order_matching (
                 };
 
                 if is_fill_triggered {
-                    let market_fill_price = match market_price_service.estimate_fill_price(order.side, &order.symbol_name, &order.symbol_code, order.quantity_open) {
-                        Some(price) => price,
+                    let (market_fill_price, volume_filled) = match model_market_fill(&order, order.quantity_open, market_price_service, fill_model) {
+                        Some(fill) => fill,
                         None => continue
                     };
-                    filled.push((order.id.clone(), market_fill_price));
+                    push_fill(&order, market_fill_price, volume_filled, &mut filled, &mut partially_filled);
                 }
             }
 
@@ -490,11 +543,11 @@ pub(crate) async fn simulated_order_matching (
                 };
 
                 if is_fill_triggered {
-                    let market_fill_price = match market_price_service.estimate_fill_price(order.side, &order.symbol_name, &order.symbol_code, order.quantity_open) {
-                        Some(price) => price,
+                    let (market_fill_price, volume_filled) = match model_market_fill(&order, order.quantity_open, market_price_service, fill_model) {
+                        Some(fill) => fill,
                         None => continue
                     };
-                    filled.push((order.id.clone(), market_fill_price));
+                    push_fill(&order, market_fill_price, volume_filled, &mut filled, &mut partially_filled);
                 }
             }
             OrderType::StopLimit => {
@@ -507,48 +560,35 @@ pub(crate) async fn simulated_order_matching (
                     OrderSide::Buy => market_price <= order.trigger_price.unwrap() && market_price > order.limit_price.unwrap(),
                     OrderSide::Sell => market_price >= order.trigger_price.unwrap() && market_price < order.limit_price.unwrap()
                 };
-                let (market_price, volume_filled) = match market_price_service.estimate_limit_fill(order.side, &order.symbol_name, &order.symbol_code, order.quantity_open, order.limit_price.unwrap()) {
-                    Some((price, volume)) => {
-                        (price, volume)
-                    },
-                    None => continue
-                };
                 if is_fill_triggered {
-                    match volume_filled == order.quantity_open {
-                        true => filled.push((order.id.clone(),  market_price)),
-                        false => partially_filled.push((order.id.clone(),  market_price, volume_filled))
-                    }
+                    let (market_price, volume_filled) = match model_limit_fill(&order, order.limit_price.unwrap(), market_price_service, fill_model) {
+                        Some((price, volume)) => {
+                            (price, volume)
+                        },
+                        None => continue
+                    };
+                    push_fill(&order, market_price, volume_filled, &mut filled, &mut partially_filled);
                 }
             },
             OrderType::EnterLong => {
-                if ledger_service.is_short(&order.account, &order.symbol_code) {
-                    let quantity = ledger_service.position_size(&order.account, &order.symbol_code);
-                    let market_fill_price = match market_price_service.estimate_fill_price(order.side, &order.symbol_name, &order.symbol_code, quantity) {
-                        Some(price) => price,
-                        None => continue
-                    };
-                    ledger_service.paper_exit_position(&order.account,  order.symbol_code.clone(),  order.id.clone(), time, market_fill_price, String::from("Force Exit By Enter Long")).await;
+                if ledger_service.is_short(&order.account, &order.symbol_code) && !force_exit(&order, time, String::from("Force Exit By Enter Long"), ledger_service, market_price_service, fill_model).await {
+                    continue;
                 }
-                let market_fill_price = match market_price_service.estimate_fill_price(order.side, &order.symbol_name, &order.symbol_code, order.quantity_open) {
-                    Some(price) => price,
+                let (market_fill_price, volume_filled) = match model_market_fill(&order, order.quantity_open, market_price_service, fill_model) {
+                    Some(fill) => fill,
                     None => continue
                 };
-                filled.push((order.id.clone(), market_fill_price));
+                push_fill(&order, market_fill_price, volume_filled, &mut filled, &mut partially_filled);
             }
             OrderType::EnterShort => {
-                if ledger_service.is_long(&order.account, &order.symbol_code) {
-                    let quantity = ledger_service.position_size(&order.account, &order.symbol_code);
-                    let market_fill_price = match market_price_service.estimate_fill_price(order.side, &order.symbol_name, &order.symbol_code, quantity) {
-                        Some(price) => price,
-                        None => continue
-                    };
-                    ledger_service.paper_exit_position(&order.account,  order.symbol_code.clone(), order.id.clone(), time, market_fill_price, String::from("Force Exit By Enter Short")).await;
+                if ledger_service.is_long(&order.account, &order.symbol_code) && !force_exit(&order, time, String::from("Force Exit By Enter Short"), ledger_service, market_price_service, fill_model).await {
+                    continue;
                 }
-                let market_fill_price = match market_price_service.estimate_fill_price(order.side, &order.symbol_name, &order.symbol_code, order.quantity_open) {
-                    Some(price) => price,
+                let (market_fill_price, volume_filled) = match model_market_fill(&order, order.quantity_open, market_price_service, fill_model) {
+                    Some(fill) => fill,
                     None => continue
                 };
-                filled.push((order.id.clone(), market_fill_price));
+                push_fill(&order, market_fill_price, volume_filled, &mut filled, &mut partially_filled);
             }
             OrderType::ExitLong => {
                 let long_quantity = ledger_service.position_size(&order.account, &order.symbol_code);
@@ -562,12 +602,11 @@ pub(crate) async fn simulated_order_matching (
                     true => long_quantity,
                     false => order.quantity_open
                 };
-                let market_fill_price = match market_price_service.estimate_fill_price(order.side, &order.symbol_name, &order.symbol_code, adjusted_size) {
-                    Some(price) => price,
+                let (market_fill_price, volume_filled) = match model_market_fill(&order, adjusted_size, market_price_service, fill_model) {
+                    Some(fill) => fill,
                     None => continue
                 };
-
-                filled.push((order.id.clone(), market_fill_price));
+                push_exit_fill(&order, market_fill_price, volume_filled, adjusted_size, &mut filled, &mut partially_filled);
             }
             OrderType::ExitShort => {
                 let short_quantity = ledger_service.position_size(&order.account, &order.symbol_code);
@@ -581,27 +620,439 @@ pub(crate) async fn simulated_order_matching (
                     true => short_quantity,
                     false => order.quantity_open
                 };
-                let market_fill_price = match market_price_service.estimate_fill_price(order.side, &order.symbol_name, &order.symbol_code, adjusted_size) {
-                    Some(price) => price,
+                let (market_fill_price, volume_filled) = match model_market_fill(&order, adjusted_size, market_price_service, fill_model) {
+                    Some(fill) => fill,
                     None => continue
                 };
-                filled.push((order.id.clone(), market_fill_price));
+                push_exit_fill(&order, market_fill_price, volume_filled, adjusted_size, &mut filled, &mut partially_filled);
+            }
+        }
+    }
+
+    // orders triggered inside the bar are filled in the order the bar path reached them, before orders filled at the close
+    if !intrabar_filled.is_empty() {
+        intrabar_filled.sort_by_key(|fill| fill.0);
+        let mut bar_filled = Vec::new();
+        for (_, order_id, price, volume) in intrabar_filled {
+            if let Some(order) = open_order_cache.get(&order_id) {
+                push_fill(order.value(), price, volume, &mut bar_filled, &mut partially_filled);
+            }
+        }
+        bar_filled.extend(filled);
+        filled = bar_filled;
+    }
+
+    // fill or kill orders which can only partially fill are cancelled without filling, immediate or cancel orders keep the partial fill and cancel the remainder
+    let mut immediate_remainders = Vec::new();
+    for (order_id, time_in_force) in immediate {
+        if filled.iter().any(|(id, _)| *id == order_id) || rejected.iter().any(|(id, _)| *id == order_id) {
+            continue;
+        }
+        let is_partial = partially_filled.iter().any(|(id, _, _)| *id == order_id);
+        match time_in_force {
+            TimeInForce::FOK => {
+                partially_filled.retain(|(id, _, _)| *id != order_id);
+                cancelled.push((order_id, "Time In Force Expired: TimeInForce::FOK".to_string()));
+            }
+            _ => match is_partial {
+                true => immediate_remainders.push(order_id),
+                false => cancelled.push((order_id, "Time In Force Expired: TimeInForce::IOC".to_string())),
             }
         }
     }
 
     for (order_id, reason) in rejected {
         reject_order(reason, &order_id, time, &open_order_cache, closed_order_cache, &strategy_event_sender).await;
+        close_order_group(&order_id, time, closed_order_cache, &strategy_event_sender, order_groups).await;
     }
     for (order_id, price) in filled {
-        fill_order(&order_id, time, price, &open_order_cache, &closed_order_cache, &strategy_event_sender, &ledger_service).await;
+        // protective orders for a position which was already closed this pass are cancelled below instead of filled
+        if is_stale(&order_id, open_order_cache, ledger_service, bracket_orders) {
+            continue;
+        }
+        let bracketed_entry = unattached_brackets(&order_id, open_order_cache, bracket_orders);
+        // one cancels other orders which were cancelled by an earlier fill in this pass are no longer in the open order cache and will not be filled.
+        let is_filled = fill_order(&order_id, time, price, open_order_cache, closed_order_cache, &strategy_event_sender, ledger_service).await;
+        if let (true, Some(entry_order)) = (is_filled, bracketed_entry) {
+            attach_brackets(&entry_order, time, open_order_cache, closed_order_cache, &strategy_event_sender, ledger_service, bracket_orders).await;
+        }
+        match is_filled {
+            true => {
+                let triggered = update_order_group(&order_id, time, open_order_cache, closed_order_cache, &strategy_event_sender, order_groups).await;
+                place_triggered_orders(triggered, time, open_order_cache, &strategy_event_sender, ledger_service, market_price_service).await;
+            }
+            false => close_order_group(&order_id, time, closed_order_cache, &strategy_event_sender, order_groups).await
+        }
     }
     for (order_id, price, volume) in partially_filled {
-        partially_fill_order(&order_id, time, price, volume, &open_order_cache, &closed_order_cache, &strategy_event_sender, &ledger_service).await;
+        if is_stale(&order_id, open_order_cache, ledger_service, bracket_orders) {
+            continue;
+        }
+        let bracketed_entry = unattached_brackets(&order_id, open_order_cache, bracket_orders);
+        let is_filled = partially_fill_order(&order_id, time, price, volume, open_order_cache, closed_order_cache, &strategy_event_sender, ledger_service).await;
+        // the brackets protect the filled part of the entry, they are resized by `update_brackets()` as the rest fills
+        if let (true, Some(entry_order)) = (is_filled, bracketed_entry) {
+            attach_brackets(&entry_order, time, open_order_cache, closed_order_cache, &strategy_event_sender, ledger_service, bracket_orders).await;
+        }
+        match is_filled {
+            true => {
+                let triggered = update_order_group(&order_id, time, open_order_cache, closed_order_cache, &strategy_event_sender, order_groups).await;
+                place_triggered_orders(triggered, time, open_order_cache, &strategy_event_sender, ledger_service, market_price_service).await;
+            }
+            false => close_order_group(&order_id, time, closed_order_cache, &strategy_event_sender, order_groups).await
+        }
+    }
+    for order_id in immediate_remainders {
+        cancel_order("Time In Force Expired: TimeInForce::IOC".to_string(), &order_id, time, open_order_cache, closed_order_cache, &strategy_event_sender).await;
+        close_order_group(&order_id, time, closed_order_cache, &strategy_event_sender, order_groups).await;
     }
 
     for (order_id, reason) in cancelled {
         cancel_order(reason, &order_id, time, &open_order_cache, closed_order_cache, &strategy_event_sender).await;
+        close_order_group(&order_id, time, closed_order_cache, &strategy_event_sender, order_groups).await;
+    }
+
+    update_brackets(time, open_order_cache, closed_order_cache, &strategy_event_sender, ledger_service, market_price_service, bracket_orders).await;
+    market_price_service.queue_positions().retain(|order_id| open_order_cache.contains_key(order_id));
+}
+
+/// Estimates the fill for a marketable order using the fill model.
+fn model_market_fill(order: &Order, quantity: Volume, market_price_service: &Arc<MarketPriceService>, fill_model: &Arc<dyn FillModel>) -> Option<(Price, Volume)> {
+    let book_price = market_price_service.estimate_fill_price(order.side, &order.symbol_name, &order.symbol_code, quantity)?;
+    let context = market_price_service.fill_context(&order.account.brokerage, order.side, &order.symbol_name, &order.symbol_code, quantity, book_price);
+    Some(fill_model.fill(&context))
+}
+
+/// Estimates the fill for a triggered limit order using the fill model, the fill price is never worse than the limit.
+fn model_limit_fill(order: &Order, limit: Price, market_price_service: &Arc<MarketPriceService>, fill_model: &Arc<dyn FillModel>) -> Option<(Price, Volume)> {
+    let (book_price, volume) = market_price_service.estimate_limit_fill(order.side, &order.symbol_name, &order.symbol_code, order.quantity_open, limit)?;
+    let context = market_price_service.fill_context(&order.account.brokerage, order.side, &order.symbol_name, &order.symbol_code, volume, book_price);
+    let (price, volume) = fill_model.fill(&context);
+    Some((clamp_to_limit(order.side, price, limit), volume))
+}
+
+fn clamp_to_limit(side: OrderSide, price: Price, limit: Price) -> Price {
+    match side {
+        OrderSide::Buy => price.min(limit),
+        OrderSide::Sell => price.max(limit),
+    }
+}
+
+/// Returns where the order was triggered inside the last bar for its symbol.
+/// Orders created after the bar opened were not working for the whole bar, so they are only triggered by the close.
+fn intrabar_touch(order: &Order, intrabar_path: IntrabarPath, market_price_service: &Arc<MarketPriceService>) -> Option<IntrabarTouch> {
+    if intrabar_path == IntrabarPath::Close {
+        return None;
+    }
+    let level = match order.order_type {
+        // limit orders with a queue position fill from the market by order depth instead of the bar path
+        OrderType::Limit if market_price_service.queue_positions().has_book(&order.symbol_code) || market_price_service.queue_positions().has_book(&order.symbol_name) => return None,
+        OrderType::Limit => order.limit_price?,
+        OrderType::StopMarket | OrderType::MarketIfTouched => order.trigger_price?,
+        _ => return None
+    };
+    let bar = market_price_service.last_bar(order.side, &order.symbol_name)?;
+    if order.time_created_utc() > bar.open_time {
+        return None;
+    }
+    intrabar_path.touch(&order.order_type, order.side, level, &bar)
+}
+
+fn push_fill(order: &Order, price: Price, volume: Volume, filled: &mut Vec<(OrderId, Price)>, partially_filled: &mut Vec<(OrderId, Price, Volume)>) {
+    if volume <= dec!(0) {
+        return;
+    }
+    match volume >= order.quantity_open {
+        true => filled.push((order.id.clone(), price)),
+        false => partially_filled.push((order.id.clone(), price, volume))
+    }
+}
+
+/// Exits the opposite position before an entry order reverses it, returns true once the position is closed.
+/// When the fill model only fills part of the position, the position is reduced by the filled volume and the entry waits for the next pass.
+async fn force_exit(
+    order: &Order,
+    time: DateTime<Utc>,
+    tag: String,
+    ledger_service: &Arc<LedgerService>,
+    market_price_service: &Arc<MarketPriceService>,
+    fill_model: &Arc<dyn FillModel>
+) -> bool {
+    let quantity = ledger_service.position_size(&order.account, &order.symbol_code);
+    let (market_fill_price, volume_filled) = match model_market_fill(order, quantity, market_price_service, fill_model) {
+        Some(fill) => fill,
+        None => return false
+    };
+    if volume_filled >= quantity {
+        ledger_service.paper_exit_position(&order.account, order.symbol_code.clone(), order.id.clone(), time, market_fill_price, tag).await;
+        return true;
+    }
+    if volume_filled > dec!(0) {
+        let (sender, receiver) = oneshot::channel();
+        ledger_service.update_or_create_position(&order.account, order.symbol_name.clone(), order.symbol_code.clone(), volume_filled, order.side, time, market_fill_price, tag, Some(sender), order.id.clone()).await;
+        let _ = receiver.await;
+    }
+    false
+}
+
+/// Exit orders are sized down to the open position, so they are filled once the fill model fills the whole position.
+fn push_exit_fill(order: &Order, price: Price, volume: Volume, adjusted_size: Volume, filled: &mut Vec<(OrderId, Price)>, partially_filled: &mut Vec<(OrderId, Price, Volume)>) {
+    if volume <= dec!(0) {
+        return;
+    }
+    match volume >= adjusted_size {
+        true => filled.push((order.id.clone(), price)),
+        false => partially_filled.push((order.id.clone(), price, volume))
+    }
+}
+
+/// Protective orders for a position which is no longer open must not be filled, they are cancelled by `update_brackets()`.
+fn is_stale(order_id: &OrderId, open_order_cache: &Arc<DashMap<OrderId, Order>>, ledger_service: &Arc<LedgerService>, bracket_orders: &BracketOrderManager) -> bool {
+    open_order_cache.get(order_id).is_some_and(|order| bracket_orders.is_stale(order.value(), ledger_service))
+}
+
+/// The entry order if it has brackets which are not working yet, they are attached once the entry is filled or partially filled.
+fn unattached_brackets(order_id: &OrderId, open_order_cache: &Arc<DashMap<OrderId, Order>>, bracket_orders: &BracketOrderManager) -> Option<Order> {
+    let order = open_order_cache.get(order_id)?;
+    match order.brackets.is_some() && !bracket_orders.is_attached(order.value()) {
+        true => Some(order.value().clone()),
+        false => None
+    }
+}
+
+/// Places the protective orders for a filled entry order, any existing protective orders for the position are cancelled and replaced.
+async fn attach_brackets(
+    entry_order: &Order,
+    time: DateTime<Utc>,
+    open_order_cache: &Arc<DashMap<OrderId, Order>>,
+    closed_order_cache: &Arc<DashMap<OrderId, Order>>,
+    strategy_event_sender: &Sender<StrategyEvent>,
+    ledger_service: &Arc<LedgerService>,
+    bracket_orders: &mut BracketOrderManager
+) {
+    let brackets = match &entry_order.brackets {
+        Some(brackets) => brackets,
+        None => return
+    };
+    let position_size = ledger_service.position_size(&entry_order.account, &entry_order.symbol_code);
+    if position_size <= dec!(0) {
+        return;
+    }
+    let (orders, replaced) = bracket_orders.attach(entry_order, brackets, position_size, time);
+    for order_id in replaced {
+        cancel_order("Protective Order Replaced".to_string(), &order_id, time, open_order_cache, closed_order_cache, strategy_event_sender).await;
+    }
+    for order in orders {
+        accept_order(order, time, open_order_cache, strategy_event_sender).await;
+    }
+}
+
+/// Updates the group of an order after it was filled, cancelling the other orders as required by the group type.
+/// Returns the one triggers other orders which should now be placed, a partially filled order only triggers them once it is completely filled.
+async fn update_order_group(
+    order_id: &OrderId,
+    time: DateTime<Utc>,
+    open_order_cache: &Arc<DashMap<OrderId, Order>>,
+    closed_order_cache: &Arc<DashMap<OrderId, Order>>,
+    strategy_event_sender: &Sender<StrategyEvent>,
+    order_groups: &OrderGroupManager
+) -> Vec<Order> {
+    let fully_filled = !open_order_cache.contains_key(order_id);
+    let (cancel, trigger) = order_groups.on_fill(order_id, fully_filled);
+    for id in cancel {
+        cancel_order(format!("One Cancels Other: {} Filled", order_id), &id, time, open_order_cache, closed_order_cache, strategy_event_sender).await;
+    }
+    trigger
+}
+
+/// Places the orders triggered by a one triggers other group, orders which are no longer valid are rejected.
+async fn place_triggered_orders(
+    orders: Vec<Order>,
+    time: DateTime<Utc>,
+    open_order_cache: &Arc<DashMap<OrderId, Order>>,
+    strategy_event_sender: &Sender<StrategyEvent>,
+    ledger_service: &Arc<LedgerService>,
+    market_price_service: &Arc<MarketPriceService>
+) {
+    for order in orders {
+        let reason = match market_price_service.get_market_price(order.side, &order.symbol_name, &order.symbol_code) {
+            Some(market_price) => order_rejection_reason(&order, market_price, time, ledger_service),
+            None => Some("No Market Price Found".to_string())
+        };
+        match reason {
+            None => accept_order(order, time, open_order_cache, strategy_event_sender).await,
+            Some(reason) => {
+                let fail_event = StrategyEvent::OrderEvents(OrderUpdateEvent::OrderRejected {
+                    account: order.account,
+                    symbol_name: order.symbol_name,
+                    symbol_code: order.symbol_code,
+                    order_id: order.id,
+                    reason,
+                    tag: order.tag,
+                    time: time.to_string()
+                });
+                match strategy_event_sender.send(fail_event).await {
+                    Ok(_) => {}
+                    Err(e) => eprintln!("Backtest Matching Engine: Failed to send event: {}", e)
+                }
+            }
+        }
+    }
+}
+
+/// Removes a cancelled or rejected order from its group, cancelling any one triggers other orders which will now never be placed.
+async fn close_order_group(
+    order_id: &OrderId,
+    time: DateTime<Utc>,
+    closed_order_cache: &Arc<DashMap<OrderId, Order>>,
+    strategy_event_sender: &Sender<StrategyEvent>,
+    order_groups: &OrderGroupManager
+) {
+    let pending = order_groups.on_closed(order_id);
+    cancel_pending_orders(pending, format!("One Triggers Other: {} Not Filled", order_id), time, closed_order_cache, strategy_event_sender).await;
+}
+
+/// Cancels orders which were never placed.
+async fn cancel_pending_orders(
+    pending: Vec<Order>,
+    reason: String,
+    time: DateTime<Utc>,
+    closed_order_cache: &Arc<DashMap<OrderId, Order>>,
+    strategy_event_sender: &Sender<StrategyEvent>
+) {
+    for mut order in pending {
+        order.state = OrderState::Cancelled;
+        let event = StrategyEvent::OrderEvents(OrderUpdateEvent::OrderCancelled {
+            account: order.account.clone(),
+            symbol_name: order.symbol_name.clone(),
+            symbol_code: order.symbol_code.clone(),
+            order_id: order.id.clone(),
+            reason: reason.clone(),
+            tag: order.tag.clone(),
+            time: time.to_string(),
+        });
+        closed_order_cache.insert(order.id.clone(), order);
+        match strategy_event_sender.send(event).await {
+            Ok(_) => {}
+            Err(e) => eprintln!("Backtest Matching Engine: Failed to send event: {}", e)
+        }
+    }
+}
+
+/// Returns the reason an order is rejected when it is placed, or `None` if the order is valid at the current market price.
+fn order_rejection_reason(order: &Order, market_price: Price, time: DateTime<Utc>, ledger_service: &Arc<LedgerService>) -> Option<String> {
+    if order.quantity_open <= dec!(0) {
+        return Some(String::from("Order Quantity Must Be Greater Than Zero"));
+    }
+    // check limit price, immediate orders are expected to cross the market and fill straight away
+    let is_immediate = matches!(order.time_in_force, TimeInForce::IOC | TimeInForce::FOK);
+    if !is_immediate && (order.order_type == OrderType::StopLimit || order.order_type == OrderType::Limit) &&
+        ((order.side == OrderSide::Buy && order.limit_price.unwrap() > market_price)
+        || (order.side == OrderSide::Sell && order.limit_price.unwrap() < market_price))
+    {
+        let side_string = match order.side {
+            OrderSide::Buy => "Below",
+            OrderSide::Sell => "Above"
+        };
+        return Some(format!("{} Limit Price Must Be {} or Equal to Market Price", order.side, side_string));
+    }
+    //check trigger price
+    if (order.order_type == OrderType::StopMarket || order.order_type == OrderType::StopLimit || order.order_type == OrderType::MarketIfTouched) &&
+        ((order.side == OrderSide::Sell && order.trigger_price.unwrap() >= market_price) ||
+            (order.side == OrderSide::Buy && order.trigger_price.unwrap() <= market_price))
+    {
+        let side_string = match order.side {
+            OrderSide::Buy => "Above",  // Changed from "Below"
+            OrderSide::Sell => "Below"  // Changed from "Above"
+        };
+        return Some(format!("{} Trigger Price Must Be {} Market Price", order.side, side_string));
+    }
+    if order.order_type == OrderType::ExitLong && !ledger_service.is_long(&order.account, &order.symbol_code) {
+        return Some(String::from("No Long Position To Exit"));
+    }
+    if order.order_type == OrderType::ExitShort && !ledger_service.is_short(&order.account, &order.symbol_code) {
+        return Some(String::from("No Short Position To Exit"));
+    }
+    ledger_service.margin_rejection_reason(order, market_price, time)
+}
+
+/// Adds the order to the open order cache and sends the accepted event.
+async fn accept_order(
+    mut order: Order,
+    time: DateTime<Utc>,
+    open_order_cache: &Arc<DashMap<OrderId, Order>>,
+    strategy_event_sender: &Sender<StrategyEvent>
+) {
+    order.state = OrderState::Accepted;
+    let accept_event = StrategyEvent::OrderEvents(OrderUpdateEvent::OrderAccepted {
+        account: order.account.clone(),
+        symbol_name: order.symbol_name.clone(),
+        symbol_code: order.symbol_code.clone(),
+        order_id: order.id.clone(),
+        tag: order.tag.clone(),
+        time: time.to_string()
+    });
+    open_order_cache.insert(order.id.clone(), order);
+    match strategy_event_sender.send(accept_event).await {
+        Ok(_) => {}
+        Err(e) => eprintln!("Backtest Matching Engine: Failed to send event: {}", e)
+    }
+}
+
+/// Cancels protective orders once their position is closed, resizes them when the position is scaled and moves any trailing stops.
+async fn update_brackets(
+    time: DateTime<Utc>,
+    open_order_cache: &Arc<DashMap<OrderId, Order>>,
+    closed_order_cache: &Arc<DashMap<OrderId, Order>>,
+    strategy_event_sender: &Sender<StrategyEvent>,
+    ledger_service: &Arc<LedgerService>,
+    market_price_service: &Arc<MarketPriceService>,
+    bracket_orders: &mut BracketOrderManager
+) {
+    let (cancel, resize) = bracket_orders.synchronize(open_order_cache, ledger_service);
+    for (order_id, reason) in cancel {
+        cancel_order(reason, &order_id, time, open_order_cache, closed_order_cache, strategy_event_sender).await;
+    }
+
+    let mut updates: Vec<(OrderId, OrderUpdateType)> = resize.into_iter()
+        .map(|(order_id, quantity)| (order_id, OrderUpdateType::Quantity(quantity)))
+        .collect();
+    for (order_id, trigger_price) in bracket_orders.trail(open_order_cache, market_price_service) {
+        updates.push((order_id, OrderUpdateType::TriggerPrice(trigger_price)));
+    }
+
+    for (order_id, update) in updates {
+        if let Some(mut order) = open_order_cache.get_mut(&order_id) {
+            let text = match &update {
+                OrderUpdateType::Quantity(quantity) => {
+                    order.quantity_open = *quantity;
+                    "Protective Order Resized To Position".to_string()
+                }
+                OrderUpdateType::TriggerPrice(price) => {
+                    order.trigger_price = Some(*price);
+                    "Trailing Stop Moved".to_string()
+                }
+                OrderUpdateType::LimitPrice(price) => {
+                    order.limit_price = Some(*price);
+                    "Protective Order Updated".to_string()
+                }
+            };
+            let update_event = StrategyEvent::OrderEvents(OrderUpdateEvent::OrderUpdated {
+                account: order.account.clone(),
+                symbol_name: order.symbol_name.clone(),
+                symbol_code: order.symbol_code.clone(),
+                order_id: order.id.clone(),
+                update_type: update,
+                text,
+                tag: order.tag.clone(),
+                time: time.to_string()
+            });
+            drop(order);
+            match strategy_event_sender.send(update_event).await {
+                Ok(_) => {}
+                Err(e) => eprintln!("Backtest Matching Engine: Failed to send event: {}", e)
+            }
+        }
     }
 }
 
@@ -613,7 +1064,7 @@ async fn fill_order(
     closed_order_cache: &Arc<DashMap<OrderId, Order>>,
     strategy_event_sender: &Sender<StrategyEvent>,
     ledger_service: &Arc<LedgerService>
-) {
+) -> bool {
     if let Some((_, mut order)) = open_order_cache.remove(order_id) {  // Remove the order here
        let (sender, receiver) = oneshot::channel();
         ledger_service.update_or_create_position(&order.account, order.symbol_name.clone(), order.symbol_code.clone(), order.quantity_open.clone(), order.side.clone(), time.clone(), market_price, order.tag.clone(), Some(sender), order_id.clone()).await;
@@ -627,6 +1078,7 @@ async fn fill_order(
                             Err(e) => eprintln!("Backtest Matching Engine: Failed to send event: {}", e)
                         }
                         closed_order_cache.insert(order.id.clone(), order);
+                        false
                     }
                     None => {
 
@@ -644,16 +1096,23 @@ async fn fill_order(
                         });
                         order.quantity_filled += order.quantity_open.clone();
                         order.quantity_open = dec!(0.0);
+                        order.state = OrderState::Filled;
                         match strategy_event_sender.send(order_event).await {
                             Ok(_) => {}
                             Err(e) => eprintln!("Backtest Matching Engine: Failed to send event: {}", e)
                         }
                         closed_order_cache.insert(order.id.clone(), order);
+                        true
                     }
                 }
             }
-            Err(e) => eprintln!("Backtest Matching Engine: Failed to receive event: {}", e)
+            Err(e) => {
+                eprintln!("Backtest Matching Engine: Failed to receive event: {}", e);
+                false
+            }
         }
+    } else {
+        false
     }
 }
 
@@ -666,7 +1125,7 @@ async fn partially_fill_order(
     closed_order_cache: &Arc<DashMap<OrderId, Order>>,
     strategy_event_sender: &Sender<StrategyEvent>,
     ledger_service: &Arc<LedgerService>
-) {
+) -> bool {
     if let Some((_, mut order)) = open_order_cache.remove(order_id) {
         let (sender, receiver) = oneshot::channel();
         ledger_service.update_or_create_position(&order.account, order.symbol_name.clone(),  order.symbol_code.clone(), fill_volume, order.side.clone(), time, fill_price, order.tag.clone(), Some(sender), order_id.clone()).await;
@@ -681,6 +1140,7 @@ async fn partially_fill_order(
                             Err(e) => eprintln!("Backtest Matching Engine: Failed to send event: {}", e)
                         }
                         closed_order_cache.insert(order.id.clone(), order);
+                        false
                     }
                     None => {
                         order.quantity_open -= fill_volume;
@@ -717,15 +1177,23 @@ async fn partially_fill_order(
                             Err(e) => eprintln!("Backtest Matching Engine: Failed to send event: {}", e)
                         }
                         if is_fully_filled {
+                            order.state = OrderState::Filled;
                             closed_order_cache.insert(order.id.clone(), order);
                         } else {
+                            order.state = OrderState::PartiallyFilled;
                             open_order_cache.insert(order_id.clone(), order);
                         }
+                        true
                     }
                 }
             }
-            Err(e) => eprintln!("Backtest Matching Engine: Failed to receive event: {}", e)
+            Err(e) => {
+                eprintln!("Backtest Matching Engine: Failed to receive event: {}", e);
+                false
+            }
         }
+    } else {
+        false
     }
 }
 
@@ -786,3 +1254,90 @@ async fn cancel_order(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apis::rithmic::rithmic_systems::RithmicSystem;
+    use crate::standardized_types::accounts::Account;
+    use std::str::FromStr;
+    use crate::standardized_types::accounts::Currency;
+    use crate::standardized_types::base_data::base_data_enum::BaseDataEnum;
+    use crate::standardized_types::base_data::quote::Quote;
+    use crate::standardized_types::datavendor_enum::DataVendor;
+    use crate::standardized_types::enums::{FuturesExchange, MarketType, StrategyMode};
+    use crate::standardized_types::orders::ProtectiveOrder;
+    use crate::standardized_types::subscriptions::Symbol;
+    use crate::standardized_types::time_slices::TimeSlice;
+    use crate::strategies::client_features::request_handler::DataServerSender;
+    use crate::strategies::handlers::market_handler::fill_models::VolumeParticipation;
+    use crate::strategies::ledgers::commissions::CommissionSchedules;
+    use crate::strategies::ledgers::financing::FinancingSchedules;
+    use crate::strategies::ledgers::margin::MarginSchedules;
+
+    #[tokio::test]
+    async fn test_immediate_limit_orders_can_cross_the_market() {
+        let (sender, _receiver) = tokio::sync::mpsc::channel(100);
+        let ledger_service = Arc::new(LedgerService::new(
+            sender,
+            Arc::new(MarketPriceService::new()),
+            Arc::new(CommissionSchedules::default()),
+            Arc::new(MarginSchedules::default()),
+            Arc::new(FinancingSchedules::default()),
+            DataServerSender::new().0,
+        ));
+        let account = Account::new(Brokerage::Rithmic(RithmicSystem::RithmicPaperTrading), "Test".to_string());
+        let time = Utc::now();
+        let limit = |order_id: &str, time_in_force: TimeInForce| {
+            Order::limit_order("ES".to_string(), None, &account, dec!(1), OrderSide::Buy, "Entry".to_string(), order_id.to_string(), time, dec!(100.25), time_in_force, None)
+        };
+
+        assert!(order_rejection_reason(&limit("1", TimeInForce::GTC), dec!(100), time, &ledger_service).is_some());
+        assert!(order_rejection_reason(&limit("2", TimeInForce::IOC), dec!(100), time, &ledger_service).is_none());
+        assert!(order_rejection_reason(&limit("3", TimeInForce::FOK), dec!(100), time, &ledger_service).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_brackets_are_attached_to_partially_filled_entries() {
+        let (sender, _receiver) = tokio::sync::mpsc::channel(100);
+        let market_price_service = Arc::new(MarketPriceService::new());
+        let ledger_service = Arc::new(LedgerService::new(
+            sender,
+            market_price_service.clone(),
+            Arc::new(CommissionSchedules::default()),
+            Arc::new(MarginSchedules::default()),
+            Arc::new(FinancingSchedules::default()),
+            DataServerSender::new().0,
+        ));
+        let account = Account::new(Brokerage::Rithmic(RithmicSystem::RithmicPaperTrading), "Test".to_string());
+        ledger_service.init_ledger(&account, StrategyMode::Backtest, false, dec!(100000), Currency::USD).await;
+        let time = DateTime::<Utc>::from_str("2024-06-03T14:00:00Z").unwrap();
+
+        // 4 contracts on each side of the book and 25% participation fill one contract per update
+        let symbol = Symbol::new("ES".to_string(), DataVendor::Rithmic, MarketType::Futures(FuturesExchange::CME));
+        let mut time_slice = TimeSlice::new();
+        time_slice.add(BaseDataEnum::Quote(Quote::new(symbol, dec!(5000), dec!(4999.75), dec!(4), dec!(4), time.to_string())));
+        market_price_service.update_market_data(Arc::new(time_slice));
+        let fill_model: Arc<dyn FillModel> = Arc::new(VolumeParticipation::new(dec!(25)));
+
+        // entries are fill or kill by default, a good till cancelled entry keeps working until the rest is filled
+        let mut entry = Order::enter_long("ES".to_string(), Some("ESM4".to_string()), &account, dec!(3), "Entry".to_string(), "1".to_string(), time, None, Some(vec![ProtectiveOrder::TakeProfit { price: dec!(5010) }, ProtectiveOrder::StopLoss { price: dec!(4990) }]));
+        entry.time_in_force = TimeInForce::GTC;
+        let open_order_cache = Arc::new(DashMap::new());
+        open_order_cache.insert(entry.id.clone(), entry);
+        let closed_order_cache = Arc::new(DashMap::new());
+        let (strategy_event_sender, _strategy_event_receiver) = tokio::sync::mpsc::channel(100);
+        let mut bracket_orders = BracketOrderManager::new();
+        let order_groups = OrderGroupManager::new();
+
+        for filled in [dec!(1), dec!(2), dec!(3)] {
+            simulated_order_matching(time, &open_order_cache, &closed_order_cache, strategy_event_sender.clone(), &ledger_service, &market_price_service, &mut bracket_orders, &order_groups, &fill_model, IntrabarPath::Close).await;
+            assert_eq!(ledger_service.position_size(&account, &"ESM4".to_string()), filled);
+            // the protective orders exist from the first partial fill and always cover the whole position
+            for order_id in ["1-TP", "1-SL"] {
+                assert_eq!(open_order_cache.get(order_id).map(|order| order.quantity_open), Some(filled), "{} after {} filled", order_id, filled);
+            }
+        }
+        assert_eq!(closed_order_cache.get("1").map(|order| order.state.clone()), Some(OrderState::Filled));
+    }
+}
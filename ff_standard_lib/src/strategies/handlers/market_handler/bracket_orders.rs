@@ -0,0 +1,389 @@
+use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use std::sync::Arc;
+use crate::standardized_types::accounts::Account;
+use crate::standardized_types::enums::{OrderSide, PositionSide};
+use crate::standardized_types::new_types::{Price, Volume};
+use crate::standardized_types::orders::{Order, OrderId, OrderType, ProtectiveOrder, TimeInForce};
+use crate::standardized_types::subscriptions::SymbolCode;
+use crate::strategies::handlers::market_handler::price_service::MarketPriceService;
+use crate::strategies::ledgers::ledger_service::LedgerService;
+
+/// The protective orders attached to a single position.
+/// The orders are linked, when the position is closed by any of them, the remaining orders are cancelled.
+struct PositionBrackets {
+    parent_order_id: OrderId,
+    position_side: PositionSide,
+    order_ids: Vec<OrderId>,
+    /// Trailing stop order ids and the distance the trigger price trails the market by.
+    trailing_stops: HashMap<OrderId, Price>,
+}
+
+/// The protective orders to cancel with the reason, and the protective orders to resize with their new quantity.
+type BracketChanges = (Vec<(OrderId, String)>, Vec<(OrderId, Volume)>);
+
+/// Manages the protective orders created by `OrderType::EnterLong` and `OrderType::EnterShort` orders in the backtest matching engine.
+/// The child orders are regular stop and limit orders living in the open order cache, this struct only tracks which position they protect.
+pub(crate) struct BracketOrderManager {
+    brackets: HashMap<(Account, SymbolCode), PositionBrackets>,
+}
+
+impl BracketOrderManager {
+    pub(crate) fn new() -> Self {
+        Self {
+            brackets: HashMap::new(),
+        }
+    }
+
+    /// Creates the child orders for a filled entry order, the caller is responsible for adding them to the open order cache.
+    /// Returns the ids of any existing protective orders for the position which should now be cancelled, since new brackets replace the old ones.
+    pub(crate) fn attach(
+        &mut self,
+        entry_order: &Order,
+        brackets: &Vec<ProtectiveOrder>,
+        position_size: Volume,
+        time: DateTime<Utc>
+    ) -> (Vec<Order>, Vec<OrderId>) {
+        let (position_side, exit_side) = match entry_order.order_type {
+            OrderType::EnterLong => (PositionSide::Long, OrderSide::Sell),
+            OrderType::EnterShort => (PositionSide::Short, OrderSide::Buy),
+            _ => return (vec![], vec![])
+        };
+
+        let key = (entry_order.account.clone(), entry_order.symbol_code.clone());
+        let replaced = match self.brackets.remove(&key) {
+            Some(existing) => existing.order_ids,
+            None => vec![]
+        };
+
+        let mut orders = Vec::new();
+        let mut trailing_stops = HashMap::new();
+        for protective_order in brackets {
+            let order = match protective_order {
+                ProtectiveOrder::TakeProfit { price } => {
+                    Order::limit_order(
+                        entry_order.symbol_name.clone(),
+                        Some(entry_order.symbol_code.clone()),
+                        &entry_order.account,
+                        position_size,
+                        exit_side,
+                        format!("{}: Take Profit", entry_order.tag),
+                        format!("{}-TP", entry_order.id),
+                        time,
+                        *price,
+                        TimeInForce::GTC,
+                        entry_order.exchange.clone()
+                    )
+                }
+                ProtectiveOrder::StopLoss { price } => {
+                    Order::stop(
+                        entry_order.symbol_name.clone(),
+                        Some(entry_order.symbol_code.clone()),
+                        &entry_order.account,
+                        position_size,
+                        exit_side,
+                        format!("{}: Stop Loss", entry_order.tag),
+                        format!("{}-SL", entry_order.id),
+                        time,
+                        *price,
+                        TimeInForce::GTC,
+                        entry_order.exchange.clone()
+                    )
+                }
+                ProtectiveOrder::TrailingStopLoss { price, trail_value } => {
+                    let order = Order::stop(
+                        entry_order.symbol_name.clone(),
+                        Some(entry_order.symbol_code.clone()),
+                        &entry_order.account,
+                        position_size,
+                        exit_side,
+                        format!("{}: Trailing Stop Loss", entry_order.tag),
+                        format!("{}-TSL", entry_order.id),
+                        time,
+                        *price,
+                        TimeInForce::GTC,
+                        entry_order.exchange.clone()
+                    );
+                    trailing_stops.insert(order.id.clone(), *trail_value);
+                    order
+                }
+            };
+            orders.push(order);
+        }
+
+        if !orders.is_empty() {
+            self.brackets.insert(key, PositionBrackets {
+                parent_order_id: entry_order.id.clone(),
+                position_side,
+                order_ids: orders.iter().map(|order| order.id.clone()).collect(),
+                trailing_stops,
+            });
+        }
+        (orders, replaced)
+    }
+
+    /// Returns true if the protective orders of the entry order are already working, the entry was partially filled earlier.
+    /// `synchronize()` keeps their quantity in line with the position as the rest of the entry fills.
+    pub(crate) fn is_attached(&self, entry_order: &Order) -> bool {
+        let key = (entry_order.account.clone(), entry_order.symbol_code.clone());
+        self.brackets.get(&key).is_some_and(|brackets| brackets.parent_order_id == entry_order.id)
+    }
+
+    /// Returns true if the order is a protective order for a position which is no longer open.
+    /// These orders must not be filled, they will be cancelled by the next call to `synchronize()`.
+    pub(crate) fn is_stale(&self, order: &Order, ledger_service: &Arc<LedgerService>) -> bool {
+        let key = (order.account.clone(), order.symbol_code.clone());
+        match self.brackets.get(&key) {
+            None => false,
+            Some(brackets) => {
+                if !brackets.order_ids.contains(&order.id) {
+                    return false;
+                }
+                !Self::position_is_open(brackets, &order.account, &order.symbol_code, ledger_service)
+            }
+        }
+    }
+
+    /// Keeps the protective orders in line with the positions they protect.
+    /// Returns the orders to cancel because their position was closed or reversed, and the orders which need their quantity resized to the current position size.
+    pub(crate) fn synchronize(
+        &mut self,
+        open_order_cache: &Arc<DashMap<OrderId, Order>>,
+        ledger_service: &Arc<LedgerService>
+    ) -> BracketChanges {
+        let mut cancel = Vec::new();
+        let mut resize = Vec::new();
+        let mut closed = Vec::new();
+        for ((account, symbol_code), brackets) in self.brackets.iter_mut() {
+            brackets.order_ids.retain(|id| open_order_cache.contains_key(id));
+            brackets.trailing_stops.retain(|id, _| open_order_cache.contains_key(id));
+
+            if !Self::position_is_open(brackets, account, symbol_code, ledger_service) {
+                for id in &brackets.order_ids {
+                    cancel.push((id.clone(), format!("Position Closed: Protective Order For {}", brackets.parent_order_id)));
+                }
+                closed.push((account.clone(), symbol_code.clone()));
+                continue;
+            }
+            if brackets.order_ids.is_empty() {
+                closed.push((account.clone(), symbol_code.clone()));
+                continue;
+            }
+
+            let position_size = ledger_service.position_size(account, symbol_code);
+            for id in &brackets.order_ids {
+                if let Some(order) = open_order_cache.get(id) {
+                    if order.quantity_open != position_size {
+                        resize.push((id.clone(), position_size));
+                    }
+                }
+            }
+        }
+        for key in closed {
+            self.brackets.remove(&key);
+        }
+        (cancel, resize)
+    }
+
+    /// Returns the new trigger prices for any trailing stops where the market has moved in favour of the position.
+    pub(crate) fn trail(
+        &self,
+        open_order_cache: &Arc<DashMap<OrderId, Order>>,
+        market_price_service: &Arc<MarketPriceService>
+    ) -> Vec<(OrderId, Price)> {
+        let mut updates = Vec::new();
+        for brackets in self.brackets.values() {
+            for (order_id, trail_value) in &brackets.trailing_stops {
+                let order = match open_order_cache.get(order_id) {
+                    Some(order) => order,
+                    None => continue
+                };
+                let trigger_price = match order.trigger_price {
+                    Some(price) => price,
+                    None => continue
+                };
+                // we trail the price we would exit at, so the opposite side of the book to the stop order.
+                let market_price = match market_price_service.get_market_price(order.side, &order.symbol_name, &order.symbol_code) {
+                    Some(price) => price,
+                    None => continue
+                };
+                match order.side {
+                    OrderSide::Sell => {
+                        let new_trigger = market_price - trail_value;
+                        if new_trigger > trigger_price {
+                            updates.push((order_id.clone(), new_trigger));
+                        }
+                    }
+                    OrderSide::Buy => {
+                        let new_trigger = market_price + trail_value;
+                        if new_trigger < trigger_price {
+                            updates.push((order_id.clone(), new_trigger));
+                        }
+                    }
+                }
+            }
+        }
+        updates
+    }
+
+    /// Removes all protective orders tracked for the account, used when the account is flattened or all orders are cancelled.
+    pub(crate) fn remove_account(&mut self, account: &Account) {
+        self.brackets.retain(|(bracket_account, _), _| bracket_account != account);
+    }
+
+    fn position_is_open(brackets: &PositionBrackets, account: &Account, symbol_code: &SymbolCode, ledger_service: &Arc<LedgerService>) -> bool {
+        match brackets.position_side {
+            PositionSide::Long => ledger_service.is_long(account, symbol_code),
+            PositionSide::Short => ledger_service.is_short(account, symbol_code),
+            PositionSide::Flat => false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use rust_decimal_macros::dec;
+    use crate::apis::rithmic::rithmic_systems::RithmicSystem;
+    use crate::standardized_types::accounts::Currency;
+    use crate::standardized_types::base_data::base_data_enum::BaseDataEnum;
+    use crate::standardized_types::base_data::tick::{Aggressor, Tick};
+    use crate::standardized_types::broker_enum::Brokerage;
+    use crate::standardized_types::datavendor_enum::DataVendor;
+    use crate::standardized_types::enums::{FuturesExchange, MarketType, StrategyMode};
+    use crate::standardized_types::subscriptions::Symbol;
+    use crate::standardized_types::time_slices::TimeSlice;
+    use crate::strategies::client_features::request_handler::DataServerSender;
+    use crate::strategies::ledgers::commissions::CommissionSchedules;
+    use crate::strategies::ledgers::financing::FinancingSchedules;
+    use crate::strategies::ledgers::margin::MarginSchedules;
+
+    fn time() -> DateTime<Utc> {
+        DateTime::from_str("2024-06-03T14:00:00Z").unwrap()
+    }
+
+    fn account() -> Account {
+        Account::new(Brokerage::Rithmic(RithmicSystem::RithmicPaperTrading), "Test".to_string())
+    }
+
+    async fn ledger_service(market_price_service: &Arc<MarketPriceService>) -> Arc<LedgerService> {
+        let (sender, _receiver) = tokio::sync::mpsc::channel(100);
+        let ledger_service = Arc::new(LedgerService::new(
+            sender,
+            market_price_service.clone(),
+            Arc::new(CommissionSchedules::default()),
+            Arc::new(MarginSchedules::default()),
+            Arc::new(FinancingSchedules::default()),
+            DataServerSender::new().0,
+        ));
+        ledger_service.init_ledger(&account(), StrategyMode::Backtest, false, dec!(100000), Currency::USD).await;
+        ledger_service
+    }
+
+    async fn fill(ledger_service: &Arc<LedgerService>, side: OrderSide, quantity: Volume, price: Price, order_id: &str) {
+        let (sender, receiver) = tokio::sync::oneshot::channel();
+        ledger_service.update_or_create_position(&account(), "ES".to_string(), "ESM4".to_string(), quantity, side, time(), price, "Test".to_string(), Some(sender), order_id.to_string()).await;
+        let _ = receiver.await;
+    }
+
+    fn market_price(market_price_service: &Arc<MarketPriceService>, price: Price, aggressor: Aggressor) {
+        let symbol = Symbol::new("ES".to_string(), DataVendor::Rithmic, MarketType::Futures(FuturesExchange::CME));
+        let mut time_slice = TimeSlice::new();
+        time_slice.add(BaseDataEnum::Tick(Tick::new(symbol, price, time().to_string(), dec!(1), aggressor)));
+        market_price_service.update_market_data(Arc::new(time_slice));
+    }
+
+    fn enter_long(order_id: &str, brackets: Vec<ProtectiveOrder>) -> Order {
+        Order::enter_long("ES".to_string(), Some("ESM4".to_string()), &account(), dec!(1), "Entry".to_string(), order_id.to_string(), time(), None, Some(brackets))
+    }
+
+    fn open_orders(orders: Vec<Order>) -> Arc<DashMap<OrderId, Order>> {
+        let open_order_cache = Arc::new(DashMap::new());
+        for order in orders {
+            open_order_cache.insert(order.id.clone(), order);
+        }
+        open_order_cache
+    }
+
+    #[tokio::test]
+    async fn test_brackets_resize_when_the_position_is_scaled() {
+        let market_price_service = Arc::new(MarketPriceService::new());
+        let ledger_service = ledger_service(&market_price_service).await;
+        let mut manager = BracketOrderManager::new();
+
+        fill(&ledger_service, OrderSide::Buy, dec!(1), dec!(5000), "1").await;
+        let entry = enter_long("1", vec![ProtectiveOrder::TakeProfit { price: dec!(5010) }, ProtectiveOrder::StopLoss { price: dec!(4990) }]);
+        let (orders, replaced) = manager.attach(&entry, entry.brackets.as_ref().unwrap(), dec!(1), time());
+        assert!(replaced.is_empty());
+        assert_eq!(orders.len(), 2);
+        assert!(orders.iter().all(|order| order.side == OrderSide::Sell && order.quantity_open == dec!(1)));
+        let open_order_cache = open_orders(orders);
+
+        // scaling in resizes the brackets to the new position size
+        fill(&ledger_service, OrderSide::Buy, dec!(2), dec!(5002), "2").await;
+        let (cancel, mut resize) = manager.synchronize(&open_order_cache, &ledger_service);
+        resize.sort();
+        assert!(cancel.is_empty());
+        assert_eq!(resize, vec![("1-SL".to_string(), dec!(3)), ("1-TP".to_string(), dec!(3))]);
+        for (order_id, quantity) in resize {
+            open_order_cache.get_mut(&order_id).unwrap().quantity_open = quantity;
+        }
+
+        // scaling out resizes them back down
+        fill(&ledger_service, OrderSide::Sell, dec!(2), dec!(5004), "3").await;
+        let (cancel, resize) = manager.synchronize(&open_order_cache, &ledger_service);
+        assert!(cancel.is_empty());
+        assert_eq!(resize.len(), 2);
+        assert!(resize.iter().all(|(_, quantity)| *quantity == dec!(1)));
+
+        // closing the position cancels them
+        fill(&ledger_service, OrderSide::Sell, dec!(1), dec!(5004), "4").await;
+        let (cancel, resize) = manager.synchronize(&open_order_cache, &ledger_service);
+        assert!(resize.is_empty());
+        assert_eq!(cancel.len(), 2);
+        assert!(manager.brackets.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_new_brackets_replace_existing_brackets() {
+        let market_price_service = Arc::new(MarketPriceService::new());
+        let mut manager = BracketOrderManager::new();
+
+        let first = enter_long("1", vec![ProtectiveOrder::StopLoss { price: dec!(4990) }]);
+        manager.attach(&first, first.brackets.as_ref().unwrap(), dec!(1), time());
+        let second = enter_long("2", vec![ProtectiveOrder::StopLoss { price: dec!(4995) }]);
+        let (orders, replaced) = manager.attach(&second, second.brackets.as_ref().unwrap(), dec!(2), time());
+        assert_eq!(replaced, vec!["1-SL".to_string()]);
+        assert_eq!((orders[0].quantity_open, orders[0].trigger_price), (dec!(2), Some(dec!(4995))));
+
+        let ledger_service = ledger_service(&market_price_service).await;
+        assert!(manager.is_stale(&orders[0], &ledger_service));
+    }
+
+    #[tokio::test]
+    async fn test_trailing_stop_follows_the_market() {
+        let market_price_service = Arc::new(MarketPriceService::new());
+        let ledger_service = ledger_service(&market_price_service).await;
+        let mut manager = BracketOrderManager::new();
+
+        fill(&ledger_service, OrderSide::Buy, dec!(1), dec!(5000), "1").await;
+        let entry = enter_long("1", vec![ProtectiveOrder::TrailingStopLoss { price: dec!(4990), trail_value: dec!(10) }]);
+        let (orders, _) = manager.attach(&entry, entry.brackets.as_ref().unwrap(), dec!(1), time());
+        let open_order_cache = open_orders(orders);
+
+        // the stop starts at the requested price and only moves once the market is more than the trail value above it
+        market_price(&market_price_service, dec!(4999), Aggressor::Sell);
+        assert!(manager.trail(&open_order_cache, &market_price_service).is_empty());
+
+        market_price(&market_price_service, dec!(5005), Aggressor::Sell);
+        let updates = manager.trail(&open_order_cache, &market_price_service);
+        assert_eq!(updates, vec![("1-TSL".to_string(), dec!(4995))]);
+        open_order_cache.get_mut("1-TSL").unwrap().trigger_price = Some(dec!(4995));
+
+        // the stop never moves against the position
+        market_price(&market_price_service, dec!(5001), Aggressor::Sell);
+        assert!(manager.trail(&open_order_cache, &market_price_service).is_empty());
+    }
+}
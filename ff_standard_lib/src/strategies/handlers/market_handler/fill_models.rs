@@ -0,0 +1,293 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use ahash::AHashMap;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use rust_decimal::prelude::FromPrimitive;
+use rust_decimal_macros::dec;
+use crate::helpers::decimal_calculators::round_to_tick_size;
+use crate::helpers::random::{next_state, split_mix, to_fraction};
+use crate::standardized_types::enums::OrderSide;
+use crate::standardized_types::new_types::{Price, Volume};
+use crate::standardized_types::subscriptions::SymbolName;
+
+/// The open, high, low, close and volume of the last bar for one side of the market.
+/// For quote bars the buy side uses the ask prices and the sell side uses the bid prices, for candles both sides use the trade prices.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BarRange {
+    pub open: Price,
+    pub high: Price,
+    pub low: Price,
+    pub close: Price,
+    pub volume: Volume,
+    pub open_time: DateTime<Utc>,
+}
+
+/// The market state passed to a `FillModel` when an order is executable in the backtest matching engine.
+#[derive(Clone, Debug)]
+pub struct FillContext {
+    pub symbol_name: SymbolName,
+    pub side: OrderSide,
+    /// The quantity the order is trying to fill.
+    pub quantity: Volume,
+    /// The fill price estimated by walking the order book, or the last price when there is no book.
+    pub book_price: Price,
+    pub best_bid: Option<Price>,
+    pub best_ask: Option<Price>,
+    /// The volume at the top of the book for the side the order fills against, `None` if the data has no book volume.
+    pub book_volume: Option<Volume>,
+    /// The last bar for the side the order fills against, `None` if the strategy is not subscribed to bars.
+    pub bar: Option<BarRange>,
+    /// The tick size of a futures symbol or the pip size of a forex or CFD symbol, other symbols use the smallest increment of the quoted prices.
+    pub tick_size: Option<Price>,
+    /// The smallest quantity the brokerage trades the symbol in, whole contracts for futures, see `Brokerage::lot_step()`. `None` if the symbol is unknown.
+    pub lot_step: Option<Volume>,
+}
+
+/// Decides the price and quantity of simulated fills in backtests and live paper trading.
+/// The model is selected with `FundForgeStrategy::initialize`, when no model is selected `TopOfBook` is used.
+/// Limit orders are never filled at a worse price than the limit, the matching engine clamps the price returned by the model.
+pub trait FillModel: Send + Sync {
+    /// Returns the fill price and the quantity filled for an order which is executable at the current market.
+    /// Returning a quantity less than `context.quantity` partially fills the order, the remainder can fill on later updates.
+    fn fill(&self, context: &FillContext) -> (Price, Volume);
+}
+
+/// Fills at the top of the book, or at the last price when there is no book.
+/// This is the default model and the least conservative.
+pub struct TopOfBook;
+
+impl FillModel for TopOfBook {
+    fn fill(&self, context: &FillContext) -> (Price, Volume) {
+        (context.book_price, context.quantity)
+    }
+}
+
+/// Moves every fill a fixed number of ticks against the order.
+pub struct FixedTicksSlippage {
+    pub ticks: Decimal,
+    /// Tick sizes which override the tick size from the fill context, symbols without a tick size are not slipped.
+    pub tick_sizes: AHashMap<SymbolName, Price>,
+}
+
+impl FixedTicksSlippage {
+    pub fn new(ticks: Decimal) -> Self {
+        Self {
+            ticks,
+            tick_sizes: AHashMap::new(),
+        }
+    }
+
+    /// Sets the tick size used for a symbol, overriding the product maps and the quote precision.
+    pub fn with_tick_size(mut self, symbol_name: SymbolName, tick_size: Price) -> Self {
+        self.tick_sizes.insert(symbol_name, tick_size);
+        self
+    }
+}
+
+impl FillModel for FixedTicksSlippage {
+    fn fill(&self, context: &FillContext) -> (Price, Volume) {
+        let tick_size = match self.tick_sizes.get(&context.symbol_name).cloned().or(context.tick_size) {
+            Some(tick_size) => tick_size,
+            None => return (context.book_price, context.quantity)
+        };
+        (adverse_price(context.side, context.book_price, self.ticks * tick_size), context.quantity)
+    }
+}
+
+/// Moves every fill against the order by a percentage of the current bid ask spread, `dec!(50)` fills half way through the spread.
+/// Fills at the book price when there is no spread, for example with candle data only.
+pub struct PercentOfSpreadSlippage {
+    pub percent: Decimal,
+}
+
+impl PercentOfSpreadSlippage {
+    pub fn new(percent: Decimal) -> Self {
+        Self {
+            percent,
+        }
+    }
+}
+
+impl FillModel for PercentOfSpreadSlippage {
+    fn fill(&self, context: &FillContext) -> (Price, Volume) {
+        let spread = match (context.best_bid, context.best_ask) {
+            (Some(bid), Some(ask)) if ask > bid => ask - bid,
+            _ => return (context.book_price, context.quantity)
+        };
+        let slippage = spread * self.percent / dec!(100);
+        let price = adverse_price(context.side, context.book_price, slippage);
+        match context.tick_size {
+            Some(tick_size) => (round_to_tick_size(price, tick_size), context.quantity),
+            None => (price, context.quantity)
+        }
+    }
+}
+
+/// Caps each fill to a percentage of the available volume, `Quote.ask_volume` or `Quote.bid_volume` at the top of the book, otherwise the last `Candle.volume`.
+/// Orders larger than the cap are partially filled with the cap rounded down to the lot step, and the remainder fills on later updates.
+/// A cap smaller than one lot step fills nothing on that update. Orders are filled in full when the data has no volume.
+pub struct VolumeParticipation {
+    pub max_participation_percent: Decimal,
+}
+
+impl VolumeParticipation {
+    pub fn new(max_participation_percent: Decimal) -> Self {
+        Self {
+            max_participation_percent,
+        }
+    }
+}
+
+impl FillModel for VolumeParticipation {
+    fn fill(&self, context: &FillContext) -> (Price, Volume) {
+        let available = match context.book_volume {
+            Some(volume) if volume > dec!(0) => volume,
+            _ => match &context.bar {
+                Some(bar) if bar.volume > dec!(0) => bar.volume,
+                _ => return (context.book_price, context.quantity)
+            }
+        };
+        let cap = available * self.max_participation_percent / dec!(100);
+        let cap = match context.lot_step {
+            Some(lot_step) if lot_step > dec!(0) => ((cap / lot_step).floor() * lot_step).normalize(),
+            _ => cap
+        };
+        (context.book_price, context.quantity.min(cap))
+    }
+}
+
+/// Fills at a random price between the book price and the worst price of the last bar, the high for buys and the low for sells.
+/// The random sequence is seeded, so a backtest using the same seed and data produces the same fills.
+pub struct RandomWithinBar {
+    state: AtomicU64,
+}
+
+impl RandomWithinBar {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            state: AtomicU64::new(seed),
+        }
+    }
+
+    /// SplitMix64, returns a value in the range 0..1.
+    fn next_fraction(&self) -> Decimal {
+        let state = self.state.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |state| Some(next_state(state))).unwrap_or_default();
+        Decimal::from_f64(to_fraction(split_mix(next_state(state)))).unwrap_or_default()
+    }
+}
+
+impl FillModel for RandomWithinBar {
+    fn fill(&self, context: &FillContext) -> (Price, Volume) {
+        let worst = match (&context.bar, context.side) {
+            (Some(bar), OrderSide::Buy) if bar.high > context.book_price => bar.high,
+            (Some(bar), OrderSide::Sell) if bar.low < context.book_price => bar.low,
+            _ => return (context.book_price, context.quantity)
+        };
+        let price = context.book_price + (worst - context.book_price) * self.next_fraction();
+        match context.tick_size {
+            Some(tick_size) => (round_to_tick_size(price, tick_size), context.quantity),
+            None => (price, context.quantity)
+        }
+    }
+}
+
+fn adverse_price(side: OrderSide, price: Price, slippage: Price) -> Price {
+    match side {
+        OrderSide::Buy => price + slippage,
+        OrderSide::Sell => price - slippage,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(side: OrderSide) -> FillContext {
+        FillContext {
+            symbol_name: "NQ".to_string(),
+            side,
+            quantity: dec!(10),
+            book_price: dec!(100),
+            best_bid: Some(dec!(99.5)),
+            best_ask: Some(dec!(100)),
+            book_volume: None,
+            bar: Some(BarRange {
+                open: dec!(99),
+                high: dec!(102),
+                low: dec!(98),
+                close: dec!(100),
+                volume: dec!(20),
+                open_time: Utc::now(),
+            }),
+            tick_size: Some(dec!(0.25)),
+            lot_step: Some(dec!(1)),
+        }
+    }
+
+    #[test]
+    fn test_fixed_ticks_slippage() {
+        let model = FixedTicksSlippage::new(dec!(2));
+        assert_eq!(model.fill(&context(OrderSide::Buy)), (dec!(100.5), dec!(10)));
+        assert_eq!(model.fill(&context(OrderSide::Sell)), (dec!(99.5), dec!(10)));
+
+        let model = FixedTicksSlippage::new(dec!(2)).with_tick_size("NQ".to_string(), dec!(1));
+        assert_eq!(model.fill(&context(OrderSide::Buy)).0, dec!(102));
+
+        // forex symbols slip by the pip size
+        let mut forex = context(OrderSide::Sell);
+        forex.symbol_name = "EUR-USD".to_string();
+        forex.book_price = dec!(1.08500);
+        forex.tick_size = Some(dec!(0.00001));
+        assert_eq!(FixedTicksSlippage::new(dec!(3)).fill(&forex).0, dec!(1.08497));
+    }
+
+    #[test]
+    fn test_percent_of_spread_slippage() {
+        let model = PercentOfSpreadSlippage::new(dec!(100));
+        assert_eq!(model.fill(&context(OrderSide::Buy)).0, dec!(100.5));
+
+        let mut no_quotes = context(OrderSide::Buy);
+        no_quotes.best_bid = None;
+        assert_eq!(model.fill(&no_quotes).0, dec!(100));
+    }
+
+    #[test]
+    fn test_volume_participation() {
+        let model = VolumeParticipation::new(dec!(25));
+        // capped by the bar volume
+        assert_eq!(model.fill(&context(OrderSide::Buy)).1, dec!(5));
+
+        // the book volume is preferred over the bar volume
+        let mut with_book = context(OrderSide::Buy);
+        with_book.book_volume = Some(dec!(100));
+        assert_eq!(model.fill(&with_book).1, dec!(10));
+
+        // futures fill in whole contracts, a cap under one contract fills nothing until the book has more volume
+        let mut thin_book = context(OrderSide::Buy);
+        thin_book.book_volume = Some(dec!(3));
+        assert_eq!(model.fill(&thin_book).1, dec!(0));
+        thin_book.book_volume = Some(dec!(10));
+        assert_eq!(model.fill(&thin_book).1, dec!(2));
+
+        // fractional lot steps keep the fractional part of the cap
+        thin_book.lot_step = Some(dec!(0.1));
+        assert_eq!(model.fill(&thin_book).1, dec!(2.5));
+        thin_book.book_volume = Some(dec!(3));
+        assert_eq!(model.fill(&thin_book).1, dec!(0.7));
+    }
+
+    #[test]
+    fn test_random_within_bar_is_seeded() {
+        let first = RandomWithinBar::new(42);
+        let second = RandomWithinBar::new(42);
+        for _ in 0..100 {
+            let (buy_price, _) = first.fill(&context(OrderSide::Buy));
+            assert_eq!(buy_price, second.fill(&context(OrderSide::Buy)).0);
+            assert!(buy_price >= dec!(100) && buy_price <= dec!(102));
+
+            let (sell_price, _) = first.fill(&context(OrderSide::Sell));
+            assert_eq!(sell_price, second.fill(&context(OrderSide::Sell)).0);
+            assert!(sell_price >= dec!(98) && sell_price <= dec!(100));
+        }
+    }
+}
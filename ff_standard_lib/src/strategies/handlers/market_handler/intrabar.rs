@@ -0,0 +1,127 @@
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use crate::standardized_types::enums::OrderSide;
+use crate::standardized_types::new_types::Price;
+use crate::standardized_types::orders::OrderType;
+use crate::strategies::handlers::market_handler::fill_models::BarRange;
+
+/// The path the backtest matching engine assumes price took inside a bar when the primary data is `Candle` or `QuoteBar`.
+/// Bar data only tells us the open, high, low and close, so a stop or limit order which was crossed by the high or low of a bar is triggered using the assumed path.
+/// If the bar opens through the trigger price the order is filled at the open.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IntrabarPath {
+    /// Only the close of each bar is used, orders which were crossed by the high or low but not the close are not triggered.
+    Close,
+    /// Open, high, low, close.
+    Ohlc,
+    /// Open, low, high, close.
+    Olhc,
+    /// Assumes the worst case for each order, stops are triggered as early as possible and limits as late as possible.
+    /// When a stop loss and take profit are both inside the bar, the stop loss fills first.
+    Pessimistic,
+}
+
+/// Where an order was triggered inside a bar.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct IntrabarTouch {
+    /// The distance along the bar path, 0 is the open and 3 is the close, used to fill orders in the order price reached them.
+    pub(crate) position: Decimal,
+    /// The price the order is triggered at, the open for gaps, otherwise the trigger or limit price.
+    pub(crate) price: Price,
+}
+
+impl IntrabarPath {
+    /// Returns where the bar first reached the trigger price of the order, `None` if the order type is not triggered by a price or the bar never reached it.
+    pub(crate) fn touch(&self, order_type: &OrderType, side: OrderSide, level: Price, bar: &BarRange) -> Option<IntrabarTouch> {
+        // stops are triggered by price moving against the order, limits and market if touched orders by price moving in favour of the order
+        let (rising, is_stop) = match (order_type, side) {
+            (OrderType::StopMarket, OrderSide::Buy) => (true, true),
+            (OrderType::StopMarket, OrderSide::Sell) => (false, true),
+            (OrderType::Limit | OrderType::MarketIfTouched, OrderSide::Buy) => (false, false),
+            (OrderType::Limit | OrderType::MarketIfTouched, OrderSide::Sell) => (true, false),
+            _ => return None
+        };
+        let ohlc = [bar.open, bar.high, bar.low, bar.close];
+        let olhc = [bar.open, bar.low, bar.high, bar.close];
+        match self {
+            IntrabarPath::Close => None,
+            IntrabarPath::Ohlc => touch_along(&ohlc, rising, level),
+            IntrabarPath::Olhc => touch_along(&olhc, rising, level),
+            IntrabarPath::Pessimistic => {
+                let touches = [touch_along(&ohlc, rising, level), touch_along(&olhc, rising, level)];
+                let touches = touches.into_iter().flatten();
+                match is_stop {
+                    true => touches.min_by(|a, b| a.position.cmp(&b.position)),
+                    false => touches.max_by(|a, b| a.position.cmp(&b.position)),
+                }
+            }
+        }
+    }
+}
+
+fn touch_along(path: &[Price; 4], rising: bool, level: Price) -> Option<IntrabarTouch> {
+    let is_through = |price: Price| match rising {
+        true => price >= level,
+        false => price <= level,
+    };
+    if is_through(path[0]) {
+        return Some(IntrabarTouch { position: dec!(0), price: path[0] });
+    }
+    for (index, window) in path.windows(2).enumerate() {
+        let (start, end) = (window[0], window[1]);
+        if is_through(end) {
+            let fraction = (level - start) / (end - start);
+            return Some(IntrabarTouch { position: Decimal::from(index) + fraction, price: level });
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn bar() -> BarRange {
+        BarRange {
+            open: dec!(100),
+            high: dec!(110),
+            low: dec!(90),
+            close: dec!(105),
+            volume: dec!(0),
+            open_time: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_stop_triggered_by_high() {
+        // the close never reaches the stop, the high does
+        let touch = IntrabarPath::Ohlc.touch(&OrderType::StopMarket, OrderSide::Buy, dec!(108), &bar()).unwrap();
+        assert_eq!(touch.price, dec!(108));
+        assert_eq!(touch.position, dec!(0.8));
+
+        assert_eq!(IntrabarPath::Close.touch(&OrderType::StopMarket, OrderSide::Buy, dec!(108), &bar()), None);
+        assert_eq!(IntrabarPath::Ohlc.touch(&OrderType::StopMarket, OrderSide::Buy, dec!(111), &bar()), None);
+    }
+
+    #[test]
+    fn test_gap_fills_at_open() {
+        let touch = IntrabarPath::Olhc.touch(&OrderType::StopMarket, OrderSide::Sell, dec!(102), &bar()).unwrap();
+        assert_eq!(touch, IntrabarTouch { position: dec!(0), price: dec!(100) });
+
+        let touch = IntrabarPath::Ohlc.touch(&OrderType::Limit, OrderSide::Buy, dec!(101), &bar()).unwrap();
+        assert_eq!(touch.price, dec!(100));
+    }
+
+    #[test]
+    fn test_path_order() {
+        let stop = |path: IntrabarPath| path.touch(&OrderType::StopMarket, OrderSide::Sell, dec!(95), &bar()).unwrap().position;
+        let target = |path: IntrabarPath| path.touch(&OrderType::Limit, OrderSide::Sell, dec!(105), &bar()).unwrap().position;
+
+        // the high is reached first, so the take profit fills before the stop loss
+        assert!(target(IntrabarPath::Ohlc) < stop(IntrabarPath::Ohlc));
+        assert!(stop(IntrabarPath::Olhc) < target(IntrabarPath::Olhc));
+        // pessimistic always fills the stop loss first
+        assert!(stop(IntrabarPath::Pessimistic) < target(IntrabarPath::Pessimistic));
+    }
+}
@@ -0,0 +1,284 @@
+use std::collections::BTreeMap;
+use ahash::AHashMap;
+use chrono::{DateTime, Duration, Utc};
+use crate::standardized_types::accounts::Account;
+use crate::standardized_types::broker_enum::Brokerage;
+use crate::standardized_types::orders::{OrderId, OrderRequest};
+use crate::strategies::strategy_events::StrategyEvent;
+
+/// The simulated time it takes a request to reach a broker.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BrokerLatency {
+    /// `OrderRequest::Create`, `OrderRequest::CreateGroup` and `OrderRequest::FlattenAllFor`, the order can not fill until it has reached the broker.
+    pub submit: Duration,
+    /// `OrderRequest::Cancel`, `OrderRequest::CancelAll` and `OrderRequest::Update`, the order can still fill until the request has reached the broker.
+    pub cancel: Duration,
+    /// Order events, accepts, fills, cancels and rejections, the strategy only sees the event once it has arrived back from the broker.
+    pub response: Duration,
+}
+
+impl BrokerLatency {
+    pub fn new(submit: Duration, cancel: Duration, response: Duration) -> Self {
+        Self {
+            submit,
+            cancel,
+            response,
+        }
+    }
+
+    pub fn zero() -> Self {
+        Self::new(Duration::zero(), Duration::zero(), Duration::zero())
+    }
+}
+
+/// Delays order requests and order events in backtests so strategies can not react faster than a live broker.
+/// Requests are held by the backtest matching engine until the backtest time has passed the latency, so the delay is rounded up to the strategy buffer duration.
+/// Brokerages without a latency are processed immediately.
+#[derive(Clone, Debug, Default)]
+pub struct LatencyModel {
+    brokerages: AHashMap<Brokerage, BrokerLatency>,
+}
+
+impl LatencyModel {
+    /// No latency for any brokerage, requests are processed as soon as they arrive.
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// Sets the latency for a brokerage.
+    pub fn with_brokerage(mut self, brokerage: Brokerage, latency: BrokerLatency) -> Self {
+        self.brokerages.insert(brokerage, latency);
+        self
+    }
+
+    /// The delay before the request reaches the broker.
+    pub fn latency(&self, request: &OrderRequest) -> Duration {
+        let latency = match self.brokerages.get(&request.brokerage()) {
+            Some(latency) => latency,
+            None => return Duration::zero()
+        };
+        match request {
+            OrderRequest::Create { .. } | OrderRequest::CreateGroup { .. } | OrderRequest::FlattenAllFor { .. } => latency.submit,
+            OrderRequest::Cancel { .. } | OrderRequest::CancelAll { .. } | OrderRequest::Update { .. } => latency.cancel,
+        }
+    }
+
+    /// The delay before an event sent by the broker reaches the strategy.
+    pub fn response_latency(&self, event: &StrategyEvent) -> Duration {
+        match event {
+            StrategyEvent::OrderEvents(event) => match self.brokerages.get(event.brokerage()) {
+                Some(latency) => latency.response,
+                None => Duration::zero()
+            },
+            _ => Duration::zero()
+        }
+    }
+}
+
+/// Order requests which have not reached the simulated broker yet, in the order they arrive.
+/// A request is never processed before an earlier request for the same order, so a cancel or update can not overtake the create it refers to.
+#[derive(Default)]
+pub(crate) struct InFlightRequests {
+    requests: BTreeMap<(DateTime<Utc>, u64), OrderRequest>,
+    sequence: u64,
+}
+
+impl InFlightRequests {
+    /// Returns the request if it reaches the broker immediately, otherwise it is held until the latency has passed and the earlier requests for the same orders have arrived.
+    pub(crate) fn send(&mut self, request: OrderRequest, time: DateTime<Utc>, latency: Duration) -> Option<OrderRequest> {
+        let earlier = self.requests.iter()
+            .filter(|(_, pending)| is_same_orders(pending, &request))
+            .map(|((arrival, _), _)| *arrival)
+            .max();
+        if latency <= Duration::zero() && earlier.is_none() {
+            return Some(request);
+        }
+        let arrival = match earlier {
+            Some(earlier) => earlier.max(time + latency),
+            None => time + latency
+        };
+        self.requests.insert((arrival, self.sequence), request);
+        self.sequence += 1;
+        None
+    }
+
+    /// Removes the next request which has reached the broker by `time`.
+    pub(crate) fn next_arrived(&mut self, time: DateTime<Utc>) -> Option<OrderRequest> {
+        let entry = self.requests.first_entry()?;
+        match entry.key().0 <= time {
+            true => Some(entry.remove()),
+            false => None
+        }
+    }
+}
+
+/// Order events which have not reached the strategy yet, in the order they arrive.
+#[derive(Default)]
+pub(crate) struct InFlightEvents {
+    events: BTreeMap<(DateTime<Utc>, u64), StrategyEvent>,
+    sequence: u64,
+}
+
+impl InFlightEvents {
+    /// Returns the event if it reaches the strategy immediately, otherwise it is held until the latency has passed.
+    /// The latency is the same for every event from a brokerage, so events for an order arrive in the order they were sent.
+    pub(crate) fn send(&mut self, event: StrategyEvent, time: DateTime<Utc>, latency: Duration) -> Option<StrategyEvent> {
+        if latency <= Duration::zero() {
+            return Some(event);
+        }
+        self.events.insert((time + latency, self.sequence), event);
+        self.sequence += 1;
+        None
+    }
+
+    /// Removes the next event which has reached the strategy by `time`.
+    pub(crate) fn next_arrived(&mut self, time: DateTime<Utc>) -> Option<StrategyEvent> {
+        let entry = self.events.first_entry()?;
+        match entry.key().0 <= time {
+            true => Some(entry.remove()),
+            false => None
+        }
+    }
+}
+
+fn request_account(request: &OrderRequest) -> &Account {
+    match request {
+        OrderRequest::Create { account, .. } => account,
+        OrderRequest::Cancel { account, .. } => account,
+        OrderRequest::Update { account, .. } => account,
+        OrderRequest::CancelAll { account } => account,
+        OrderRequest::FlattenAllFor { account } => account,
+        OrderRequest::CreateGroup { account, .. } => account,
+    }
+}
+
+/// The orders a request refers to, `None` for requests which affect every order on the account.
+fn request_order_ids(request: &OrderRequest) -> Option<Vec<&OrderId>> {
+    match request {
+        OrderRequest::Create { order, .. } => Some(vec![&order.id]),
+        OrderRequest::Cancel { order_id, .. } => Some(vec![order_id]),
+        OrderRequest::Update { order_id, .. } => Some(vec![order_id]),
+        OrderRequest::CreateGroup { orders, .. } => Some(orders.iter().map(|order| &order.id).collect()),
+        OrderRequest::CancelAll { .. } | OrderRequest::FlattenAllFor { .. } => None,
+    }
+}
+
+fn is_same_orders(first: &OrderRequest, second: &OrderRequest) -> bool {
+    if request_account(first) != request_account(second) {
+        return false;
+    }
+    match (request_order_ids(first), request_order_ids(second)) {
+        (Some(first), Some(second)) => first.iter().any(|order_id| second.contains(order_id)),
+        _ => true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+    use crate::apis::rithmic::rithmic_systems::RithmicSystem;
+    use crate::standardized_types::enums::OrderSide;
+    use crate::standardized_types::orders::{Order, OrderType, OrderUpdateEvent};
+
+    fn account() -> Account {
+        Account::new(Brokerage::Rithmic(RithmicSystem::Apex), "Test_Account_1".to_string())
+    }
+
+    fn create(order_id: &str, time: DateTime<Utc>) -> OrderRequest {
+        let order = Order::market_order("ES".to_string(), None, &account(), dec!(1), OrderSide::Buy, "Entry".to_string(), order_id.to_string(), time, None);
+        OrderRequest::Create { account: account(), order, order_type: OrderType::Market }
+    }
+
+    fn order_id(request: &OrderRequest) -> &OrderId {
+        match request_order_ids(request) {
+            Some(order_ids) => order_ids[0],
+            None => panic!("request has no order id")
+        }
+    }
+
+    #[test]
+    fn test_latency_per_brokerage() {
+        let rithmic = account();
+        let oanda = Account::new(Brokerage::Oanda, "Test_Account_2".to_string());
+        let model = LatencyModel::none()
+            .with_brokerage(Brokerage::Rithmic(RithmicSystem::Apex), BrokerLatency::new(Duration::milliseconds(20), Duration::milliseconds(50), Duration::milliseconds(10)));
+
+        let cancel = OrderRequest::Cancel { account: rithmic.clone(), order_id: "1".to_string() };
+        assert_eq!(model.latency(&cancel), Duration::milliseconds(50));
+
+        let cancel_all = OrderRequest::CancelAll { account: rithmic };
+        assert_eq!(model.latency(&cancel_all), Duration::milliseconds(50));
+
+        let flatten = OrderRequest::FlattenAllFor { account: oanda };
+        assert_eq!(model.latency(&flatten), Duration::zero());
+    }
+
+    #[test]
+    fn test_requests_arrive_in_time_order() {
+        let time = Utc::now();
+        let mut in_flight = InFlightRequests::default();
+        assert!(in_flight.send(create("1", time), time, Duration::milliseconds(50)).is_none());
+        assert!(in_flight.send(create("2", time), time, Duration::milliseconds(20)).is_none());
+        assert!(in_flight.send(create("3", time), time, Duration::milliseconds(20)).is_none());
+        assert!(in_flight.next_arrived(time + Duration::milliseconds(10)).is_none());
+
+        // requests arriving at the same time keep the order they were sent in
+        let arrived: Vec<OrderId> = std::iter::from_fn(|| in_flight.next_arrived(time + Duration::milliseconds(20))).map(|request| order_id(&request).clone()).collect();
+        assert_eq!(arrived, vec!["2".to_string(), "3".to_string()]);
+        assert_eq!(order_id(&in_flight.next_arrived(time + Duration::milliseconds(50)).unwrap()), "1");
+        assert!(in_flight.next_arrived(time + Duration::seconds(1)).is_none());
+
+        // without latency the request is processed immediately
+        assert!(in_flight.send(create("4", time), time, Duration::zero()).is_some());
+    }
+
+    #[test]
+    fn test_cancel_can_not_overtake_create() {
+        let time = Utc::now();
+        let mut in_flight = InFlightRequests::default();
+        assert!(in_flight.send(create("1", time), time, Duration::milliseconds(50)).is_none());
+
+        // the cancel is faster than the create but has to wait for it
+        let cancel = OrderRequest::Cancel { account: account(), order_id: "1".to_string() };
+        assert!(in_flight.send(cancel, time, Duration::milliseconds(10)).is_none());
+        // a request for an order without pending requests is not held back
+        let other = OrderRequest::Cancel { account: account(), order_id: "2".to_string() };
+        assert!(in_flight.send(other, time, Duration::zero()).is_some());
+
+        assert!(in_flight.next_arrived(time + Duration::milliseconds(10)).is_none());
+        let first = in_flight.next_arrived(time + Duration::milliseconds(50)).unwrap();
+        assert!(matches!(first, OrderRequest::Create { .. }));
+        let second = in_flight.next_arrived(time + Duration::milliseconds(50)).unwrap();
+        assert!(matches!(second, OrderRequest::Cancel { .. }));
+
+        // account wide requests wait for every pending request on the account
+        assert!(in_flight.send(create("3", time), time, Duration::milliseconds(50)).is_none());
+        assert!(in_flight.send(OrderRequest::CancelAll { account: account() }, time, Duration::zero()).is_none());
+        assert!(matches!(in_flight.next_arrived(time + Duration::milliseconds(50)).unwrap(), OrderRequest::Create { .. }));
+        assert!(matches!(in_flight.next_arrived(time + Duration::milliseconds(50)).unwrap(), OrderRequest::CancelAll { .. }));
+    }
+
+    #[test]
+    fn test_events_are_delayed() {
+        let time = Utc::now();
+        let model = LatencyModel::none()
+            .with_brokerage(Brokerage::Rithmic(RithmicSystem::Apex), BrokerLatency::new(Duration::milliseconds(20), Duration::milliseconds(20), Duration::milliseconds(30)));
+        let accepted = StrategyEvent::OrderEvents(OrderUpdateEvent::OrderAccepted {
+            account: account(),
+            symbol_name: "ES".to_string(),
+            symbol_code: "ES".to_string(),
+            order_id: "1".to_string(),
+            tag: "Entry".to_string(),
+            time: time.to_string(),
+        });
+        let latency = model.response_latency(&accepted);
+        assert_eq!(latency, Duration::milliseconds(30));
+
+        let mut in_flight = InFlightEvents::default();
+        assert!(in_flight.send(accepted, time, latency).is_none());
+        assert!(in_flight.next_arrived(time + Duration::milliseconds(20)).is_none());
+        assert!(in_flight.next_arrived(time + Duration::milliseconds(30)).is_some());
+        assert!(in_flight.next_arrived(time + Duration::milliseconds(30)).is_none());
+    }
+}
@@ -4,7 +4,12 @@ use dashmap::DashMap;
 use rust_decimal_macros::dec;
 use tokio::sync::mpsc;
 use tokio::sync::mpsc::{Receiver};
-use crate::standardized_types::orders::{Order, OrderId, OrderState, OrderUpdateEvent, OrderUpdateType};
+use crate::messages::data_server_messaging::DataServerRequest;
+use crate::standardized_types::accounts::Account;
+use crate::standardized_types::orders::{Order, OrderId, OrderRequest, OrderState, OrderUpdateEvent, OrderUpdateType};
+use crate::strategies::client_features::connection_types::ConnectionType;
+use crate::strategies::client_features::request_handler::{DataServerSender, StrategyRequest};
+use crate::strategies::handlers::market_handler::order_groups::OrderGroupManager;
 use crate::strategies::ledgers::ledger_service::{LedgerService};
 use crate::strategies::strategy_events::StrategyEvent;
 
@@ -16,8 +21,10 @@ pub(crate) fn live_order_handler(
     mut order_event_receiver: Receiver<(OrderUpdateEvent, DateTime<Utc>)>,
     strategy_event_sender: mpsc::Sender<StrategyEvent>,
     ledger_service: Arc<LedgerService>, //it is better to do this, because using a direct fn call we can concurrently update individual ledgers and have a que per ledger. sending a msg here would cause a bottleneck with more ledgers.
+    order_groups: Arc<OrderGroupManager>,
     #[allow(unused)]
-    synchronize_positions: bool
+    synchronize_positions: bool,
+    data_server_sender: DataServerSender
 ) {
     //todo, we need a message que for ledger, where orders and positions are update the ledger 1 at a time per symbol_code, this should fix the possible race conditions of positions updates
     tokio::task::spawn(async move {
@@ -52,12 +59,13 @@ pub(crate) fn live_order_handler(
                          order.state = OrderState::Filled;
                          closed_order_cache.insert(order_id.clone(), order.clone());
                          //println!("{}", order_update_event);
-                         ledger_service.update_or_create_position(&account, symbol_name.clone(), symbol_code.clone(), quantity, side.clone(), time_utc, *price, tag.to_string(), None, order_id).await;
+                         ledger_service.update_or_create_position(account, symbol_name.clone(), symbol_code.clone(), quantity, *side, time_utc, *price, tag.to_string(), None, order_id.clone()).await;
 
                          match strategy_event_sender.send(StrategyEvent::OrderEvents(order_update_event.clone())).await {
                              Ok(_) => {}
                              Err(e) => eprintln!("{}", e)
                          }
+                         update_order_group(&order_groups, account, &order_id, true, &open_order_cache, &data_server_sender).await;
                     }
                 }
                 OrderUpdateEvent::OrderPartiallyFilled { account, symbol_name, symbol_code, order_id, price, quantity, tag, time,  side} => {
@@ -72,10 +80,12 @@ pub(crate) fn live_order_handler(
                        order.quantity_open -= quantity;
                        order.time_filled_utc = Some(time.clone());
                        ledger_service.update_or_create_position(&account, symbol_name.clone(), symbol_code.clone(), quantity.clone(), side.clone(), time_utc, *price, tag.to_string(), None, order_id.clone()).await;
+                       drop(order);
                        match strategy_event_sender.send(StrategyEvent::OrderEvents(order_update_event.clone())).await {
                            Ok(_) => {}
                            Err(e) => eprintln!("{}", e)
                        }
+                       update_order_group(&order_groups, account, order_id, false, &open_order_cache, &data_server_sender).await;
                    }
                 }
                 OrderUpdateEvent::OrderCancelled { order_id,symbol_code,.. } => {
@@ -88,6 +98,7 @@ pub(crate) fn live_order_handler(
                             Ok(_) => {}
                             Err(e) => eprintln!("{}", e)
                         }
+                        close_order_group(&order_groups, &order_id, time_utc, &closed_order_cache, &strategy_event_sender).await;
                     }
                 }
                 OrderUpdateEvent::OrderRejected {symbol_code, order_id,reason, .. } => {
@@ -100,6 +111,7 @@ pub(crate) fn live_order_handler(
                             Ok(_) => {}
                             Err(e) => eprintln!("{}", e)
                         }
+                        close_order_group(&order_groups, &order_id, time_utc, &closed_order_cache, &strategy_event_sender).await;
                     }
                 }
                 OrderUpdateEvent::OrderUpdated { order_id, symbol_code, update_type,.. } => {
@@ -129,4 +141,54 @@ pub(crate) fn live_order_handler(
             }
         }
     });
-}
\ No newline at end of file
+}
+
+/// Emulates order groups for brokers without native support, cancelling or placing the linked orders once an order in the group is filled.
+async fn update_order_group(
+    order_groups: &Arc<OrderGroupManager>,
+    account: &Account,
+    order_id: &OrderId,
+    fully_filled: bool,
+    open_order_cache: &Arc<DashMap<OrderId, Order>>,
+    data_server_sender: &DataServerSender
+) {
+    let (cancel, trigger) = order_groups.on_fill(order_id, fully_filled);
+    for order_id in cancel {
+        let order_request = OrderRequest::Cancel { account: account.clone(), order_id };
+        let connection_type = ConnectionType::Broker(order_request.brokerage());
+        data_server_sender.send(StrategyRequest::OneWay(connection_type, DataServerRequest::OrderRequest { request: order_request })).await;
+    }
+    for order in trigger {
+        open_order_cache.insert(order.id.clone(), order.clone());
+        let order_request = OrderRequest::Create { account: order.account.clone(), order_type: order.order_type.clone(), order };
+        let connection_type = ConnectionType::Broker(order_request.brokerage());
+        data_server_sender.send(StrategyRequest::OneWay(connection_type, DataServerRequest::OrderRequest { request: order_request })).await;
+    }
+}
+
+/// Cancels any emulated one triggers other orders which will never be placed because the first order was cancelled or rejected.
+async fn close_order_group(
+    order_groups: &Arc<OrderGroupManager>,
+    order_id: &OrderId,
+    time_utc: DateTime<Utc>,
+    closed_order_cache: &Arc<DashMap<OrderId, Order>>,
+    strategy_event_sender: &mpsc::Sender<StrategyEvent>
+) {
+    for mut order in order_groups.on_closed(order_id) {
+        order.state = OrderState::Cancelled;
+        let event = OrderUpdateEvent::OrderCancelled {
+            account: order.account.clone(),
+            symbol_name: order.symbol_name.clone(),
+            symbol_code: order.symbol_code.clone(),
+            order_id: order.id.clone(),
+            reason: format!("One Triggers Other: {} Not Filled", order_id),
+            tag: order.tag.clone(),
+            time: time_utc.to_string(),
+        };
+        closed_order_cache.insert(order.id.clone(), order);
+        match strategy_event_sender.send(StrategyEvent::OrderEvents(event)).await {
+            Ok(_) => {}
+            Err(e) => eprintln!("{}", e)
+        }
+    }
+}
@@ -1,3 +1,10 @@
 pub mod backtest_matching_engine;
+pub(crate) mod bracket_orders;
+pub mod fill_models;
+pub mod intrabar;
+pub mod latency;
 pub mod live_order_matching;
-pub mod price_service;
\ No newline at end of file
+pub(crate) mod order_groups;
+pub mod price_service;
+pub mod queue_position;
+pub mod risk_manager;
\ No newline at end of file
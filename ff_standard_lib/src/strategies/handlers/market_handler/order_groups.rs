@@ -0,0 +1,194 @@
+use dashmap::DashMap;
+use crate::standardized_types::accounts::Account;
+use crate::standardized_types::orders::{Order, OrderGroupId, OrderGroupType, OrderId};
+
+/// A group of linked orders created by `OrderRequest::CreateGroup`.
+struct OrderGroup {
+    account: Account,
+    group_type: OrderGroupType,
+    /// The orders in the group which have been placed.
+    order_ids: Vec<OrderId>,
+    /// `OrderGroupType::OneTriggersOther` orders waiting for the first order to fill.
+    pending: Vec<Order>,
+}
+
+/// Links the orders in an order group.
+/// Used by the backtest matching engine, and by the live order handler to emulate order groups for brokers without native support.
+/// The manager only decides which orders to place or cancel, the caller is responsible for placing and cancelling them.
+pub(crate) struct OrderGroupManager {
+    groups: DashMap<OrderGroupId, OrderGroup>,
+    order_to_group: DashMap<OrderId, OrderGroupId>,
+}
+
+impl OrderGroupManager {
+    pub(crate) fn new() -> Self {
+        Self {
+            groups: DashMap::new(),
+            order_to_group: DashMap::new(),
+        }
+    }
+
+    /// Adds a new group and returns the orders which should be placed now.
+    pub(crate) fn add_group(&self, group_id: OrderGroupId, group_type: OrderGroupType, account: Account, mut orders: Vec<Order>) -> Vec<Order> {
+        if orders.is_empty() {
+            return vec![];
+        }
+        let (place, pending) = match group_type {
+            OrderGroupType::OneCancelsOther => (orders, vec![]),
+            OrderGroupType::OneTriggersOther => {
+                let pending = orders.split_off(1);
+                (orders, pending)
+            }
+        };
+        for order in &place {
+            self.order_to_group.insert(order.id.clone(), group_id.clone());
+        }
+        self.groups.insert(group_id, OrderGroup {
+            account,
+            group_type,
+            order_ids: place.iter().map(|order| order.id.clone()).collect(),
+            pending,
+        });
+        place
+    }
+
+    /// Updates the group after an order was filled.
+    /// Returns the orders which should now be cancelled and the orders which should now be placed.
+    pub(crate) fn on_fill(&self, order_id: &OrderId, fully_filled: bool) -> (Vec<OrderId>, Vec<Order>) {
+        let group_id = match self.order_to_group.get(order_id) {
+            Some(group_id) => group_id.value().clone(),
+            None => return (vec![], vec![])
+        };
+        let group_type = match self.groups.get(&group_id) {
+            Some(group) => group.group_type,
+            None => return (vec![], vec![])
+        };
+        match group_type {
+            OrderGroupType::OneCancelsOther => {
+                let group = match self.groups.remove(&group_id) {
+                    Some((_, group)) => group,
+                    None => return (vec![], vec![])
+                };
+                let mut cancel = vec![];
+                for id in group.order_ids {
+                    self.order_to_group.remove(&id);
+                    if &id != order_id {
+                        cancel.push(id);
+                    }
+                }
+                (cancel, vec![])
+            }
+            OrderGroupType::OneTriggersOther => {
+                if !fully_filled {
+                    return (vec![], vec![]);
+                }
+                self.order_to_group.remove(order_id);
+                match self.groups.remove(&group_id) {
+                    Some((_, group)) => (vec![], group.pending),
+                    None => (vec![], vec![])
+                }
+            }
+        }
+    }
+
+    /// Updates the group after an order was cancelled or rejected.
+    /// Returns any orders which were never placed and are now cancelled with the order.
+    pub(crate) fn on_closed(&self, order_id: &OrderId) -> Vec<Order> {
+        let group_id = match self.order_to_group.remove(order_id) {
+            Some((_, group_id)) => group_id,
+            None => return vec![]
+        };
+        let is_empty = match self.groups.get_mut(&group_id) {
+            Some(mut group) => {
+                group.order_ids.retain(|id| id != order_id);
+                group.order_ids.is_empty()
+            }
+            None => return vec![]
+        };
+        if !is_empty {
+            return vec![];
+        }
+        match self.groups.remove(&group_id) {
+            Some((_, group)) => group.pending,
+            None => vec![]
+        }
+    }
+
+    /// Removes all groups for the account, used when the account is flattened or all orders are cancelled.
+    /// Returns any orders which were never placed.
+    pub(crate) fn remove_account(&self, account: &Account) -> Vec<Order> {
+        let group_ids: Vec<OrderGroupId> = self.groups.iter()
+            .filter(|group| &group.account == account)
+            .map(|group| group.key().clone())
+            .collect();
+        let mut pending = vec![];
+        for group_id in group_ids {
+            if let Some((_, group)) = self.groups.remove(&group_id) {
+                for id in &group.order_ids {
+                    self.order_to_group.remove(id);
+                }
+                pending.extend(group.pending);
+            }
+        }
+        pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use rust_decimal_macros::dec;
+    use crate::standardized_types::broker_enum::Brokerage;
+    use crate::standardized_types::enums::OrderSide;
+    use crate::standardized_types::orders::TimeInForce;
+
+    fn account() -> Account {
+        Account::new(Brokerage::Test, "test_account".to_string())
+    }
+
+    fn stop(id: &str, side: OrderSide) -> Order {
+        Order::stop("NQ".to_string(), None, &account(), dec!(1), side, "Test".to_string(), id.to_string(), Utc::now(), dec!(100), TimeInForce::GTC, None)
+    }
+
+    #[test]
+    fn test_one_cancels_other() {
+        let manager = OrderGroupManager::new();
+        let place = manager.add_group("group".to_string(), OrderGroupType::OneCancelsOther, account(), vec![stop("buy", OrderSide::Buy), stop("sell", OrderSide::Sell)]);
+        assert_eq!(place.len(), 2);
+
+        let (cancel, trigger) = manager.on_fill(&"buy".to_string(), false);
+        assert_eq!(cancel, vec!["sell".to_string()]);
+        assert!(trigger.is_empty());
+
+        // the group is gone once any order fills
+        let (cancel, _) = manager.on_fill(&"sell".to_string(), true);
+        assert!(cancel.is_empty());
+    }
+
+    #[test]
+    fn test_one_triggers_other() {
+        let manager = OrderGroupManager::new();
+        let place = manager.add_group("group".to_string(), OrderGroupType::OneTriggersOther, account(), vec![stop("entry", OrderSide::Buy), stop("exit", OrderSide::Sell)]);
+        assert_eq!(place.len(), 1);
+        assert_eq!(place[0].id, "entry");
+
+        let (_, trigger) = manager.on_fill(&"entry".to_string(), false);
+        assert!(trigger.is_empty());
+
+        let (cancel, trigger) = manager.on_fill(&"entry".to_string(), true);
+        assert!(cancel.is_empty());
+        assert_eq!(trigger.len(), 1);
+        assert_eq!(trigger[0].id, "exit");
+    }
+
+    #[test]
+    fn test_one_triggers_other_cancelled() {
+        let manager = OrderGroupManager::new();
+        manager.add_group("group".to_string(), OrderGroupType::OneTriggersOther, account(), vec![stop("entry", OrderSide::Buy), stop("exit", OrderSide::Sell)]);
+        let dropped = manager.on_closed(&"entry".to_string());
+        assert_eq!(dropped.len(), 1);
+        assert_eq!(dropped[0].id, "exit");
+        assert!(manager.on_closed(&"entry".to_string()).is_empty());
+    }
+}
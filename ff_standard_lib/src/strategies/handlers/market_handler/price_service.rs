@@ -5,17 +5,33 @@ use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
 use crate::standardized_types::base_data::base_data_enum::BaseDataEnum;
 use crate::standardized_types::books::BookLevel;
+use crate::standardized_types::broker_enum::Brokerage;
 use crate::standardized_types::enums::OrderSide;
 use crate::standardized_types::new_types::Price;
 use crate::standardized_types::subscriptions::{SymbolCode, SymbolName};
 use crate::standardized_types::time_slices::TimeSlice;
 use crate::standardized_types::base_data::tick::Aggressor;
+use crate::standardized_types::base_data::traits::BaseData;
+use crate::standardized_types::new_types::Volume;
+use crate::product_maps::oanda::maps::OANDA_SYMBOL_INFO;
+use crate::product_maps::rithmic::maps::get_futures_symbol_info;
+use crate::strategies::handlers::market_handler::fill_models::{BarRange, FillContext};
+use crate::strategies::handlers::market_handler::queue_position::QueuePositions;
+
+/// The number of price levels kept in the books of symbols priced from market by order depth.
+const MARKET_BY_ORDER_DEPTH: usize = 10;
 
 pub struct MarketPriceService {
     bid_books: DashMap<SymbolName, BTreeMap<u16, BookLevel>>,
     ask_books: DashMap<SymbolName, BTreeMap<u16, BookLevel>>,
     has_quotes: DashMap<SymbolName, bool>,
+    /// Symbols with an order book subscription, their books are only updated from the depth.
+    has_order_books: DashMap<SymbolName, bool>,
     last_price: DashMap<SymbolName, Price>,
+    bid_bars: DashMap<SymbolName, BarRange>,
+    ask_bars: DashMap<SymbolName, BarRange>,
+    /// The market by order books and the queue position of our limit orders, for symbols with market by order depth.
+    queue_positions: QueuePositions,
 }
 
 impl MarketPriceService {
@@ -24,7 +40,11 @@ impl MarketPriceService {
             bid_books: DashMap::new(),
             ask_books: DashMap::new(),
             has_quotes: DashMap::new(),
+            has_order_books: DashMap::new(),
             last_price: DashMap::new(),
+            bid_bars: DashMap::new(),
+            ask_bars: DashMap::new(),
+            queue_positions: QueuePositions::new(),
         }
     }
 
@@ -33,10 +53,15 @@ impl MarketPriceService {
             match base_data {
                 BaseDataEnum::Candle(candle) => {
                     self.last_price.insert(candle.symbol.name.clone(), candle.close);
+                    let bar = BarRange { open: candle.open, high: candle.high, low: candle.low, close: candle.close, volume: candle.volume, open_time: candle.time_utc() };
+                    self.bid_bars.insert(candle.symbol.name.clone(), bar.clone());
+                    self.ask_bars.insert(candle.symbol.name.clone(), bar);
                 }
                 BaseDataEnum::QuoteBar(quotebar) => {
                     let symbol_name = &quotebar.symbol.name;
-                    if self.has_quotes.contains_key(symbol_name) {
+                    self.bid_bars.insert(symbol_name.clone(), BarRange { open: quotebar.bid_open, high: quotebar.bid_high, low: quotebar.bid_low, close: quotebar.bid_close, volume: quotebar.bid_volume, open_time: quotebar.time_utc() });
+                    self.ask_bars.insert(symbol_name.clone(), BarRange { open: quotebar.ask_open, high: quotebar.ask_high, low: quotebar.ask_low, close: quotebar.ask_close, volume: quotebar.ask_volume, open_time: quotebar.time_utc() });
+                    if self.has_quotes.contains_key(symbol_name) || self.has_order_books.contains_key(symbol_name) {
                         continue;
                     }
 
@@ -49,8 +74,9 @@ impl MarketPriceService {
                 BaseDataEnum::Tick(tick) => {
                     let symbol_name = &tick.symbol.name;
                     self.last_price.insert(symbol_name.clone(), tick.price);
+                    self.queue_positions.update_trade(tick);
 
-                    if tick.aggressor != Aggressor::None && !self.has_quotes.contains_key(symbol_name) {
+                    if tick.aggressor != Aggressor::None && !self.has_quotes.contains_key(symbol_name) && !self.has_order_books.contains_key(symbol_name) {
                         let mut bid_book = self.bid_books.entry(symbol_name.clone()).or_insert_with(BTreeMap::new);
                         let mut ask_book = self.ask_books.entry(symbol_name.clone()).or_insert_with(BTreeMap::new);
 
@@ -63,7 +89,7 @@ impl MarketPriceService {
                 }
                 BaseDataEnum::Quote(quote) => {
                     let symbol_name = &quote.symbol.name;
-                    if !self.has_quotes.contains_key(symbol_name) {
+                    if !self.has_quotes.contains_key(symbol_name) && !self.has_order_books.contains_key(symbol_name) {
                         self.has_quotes.insert(symbol_name.clone(), true);
                         let mut bid_book = self.bid_books.entry(symbol_name.clone()).or_insert_with(BTreeMap::new);
                         let mut ask_book = self.ask_books.entry(symbol_name.clone()).or_insert_with(BTreeMap::new);
@@ -72,11 +98,37 @@ impl MarketPriceService {
                         ask_book.insert(0, BookLevel::new(0, quote.ask, quote.ask_volume));
                     }
                 }
+                BaseDataEnum::OrderBook(book) => {
+                    let symbol_name = &book.symbol.name;
+                    self.has_order_books.insert(symbol_name.clone(), true);
+                    let bids = book.bids.iter().map(|level| (level.level(), level.clone())).collect();
+                    let asks = book.asks.iter().map(|level| (level.level(), level.clone())).collect();
+                    self.bid_books.insert(symbol_name.clone(), bids);
+                    self.ask_books.insert(symbol_name.clone(), asks);
+                }
+                BaseDataEnum::MarketByOrder(mbo) => {
+                    let symbol_name = &mbo.symbol.name;
+                    self.queue_positions.update_market_by_order(mbo);
+                    if self.has_quotes.contains_key(symbol_name) || self.has_order_books.contains_key(symbol_name) {
+                        continue;
+                    }
+                    if let Some(bids) = self.queue_positions.levels(symbol_name, OrderSide::Buy, MARKET_BY_ORDER_DEPTH) {
+                        self.bid_books.insert(symbol_name.clone(), bids);
+                    }
+                    if let Some(asks) = self.queue_positions.levels(symbol_name, OrderSide::Sell, MARKET_BY_ORDER_DEPTH) {
+                        self.ask_books.insert(symbol_name.clone(), asks);
+                    }
+                }
                 _ => eprintln!("Market Price Service: Incorrect data type in Market Updates: {}", base_data.base_data_type())
             }
         }
     }
 
+    /// The queue positions of simulated limit orders for symbols with market by order depth.
+    pub fn queue_positions(&self) -> &QueuePositions {
+        &self.queue_positions
+    }
+
     pub fn get_market_price(&self, order_side: OrderSide, symbol_name: &SymbolName, symbol_code: &SymbolCode) -> Option<Decimal> {
         let order_book = match order_side {
             OrderSide::Buy => self.ask_books.get(symbol_code).or_else(|| self.ask_books.get(symbol_name)),
@@ -94,6 +146,41 @@ impl MarketPriceService {
         }
     }
 
+    /// The last bar for the side of the market an order fills against, the ask bar for buys and the bid bar for sells.
+    pub fn last_bar(&self, order_side: OrderSide, symbol_name: &SymbolName) -> Option<BarRange> {
+        match order_side {
+            OrderSide::Buy => self.ask_bars.get(symbol_name).map(|bar| bar.clone()),
+            OrderSide::Sell => self.bid_bars.get(symbol_name).map(|bar| bar.clone()),
+        }
+    }
+
+    /// The market state used by a `FillModel` to fill `quantity` at `book_price`.
+    pub fn fill_context(&self, brokerage: &Brokerage, order_side: OrderSide, symbol_name: &SymbolName, symbol_code: &SymbolCode, quantity: Volume, book_price: Price) -> FillContext {
+        let top_of_book = |books: &DashMap<SymbolName, BTreeMap<u16, BookLevel>>| -> Option<BookLevel> {
+            books.get(symbol_code).or_else(|| books.get(symbol_name)).and_then(|book| book.get(&0).cloned())
+        };
+        let best_bid = top_of_book(&self.bid_books);
+        let best_ask = top_of_book(&self.ask_books);
+        let book_volume = match order_side {
+            OrderSide::Buy => best_ask.as_ref().map(|level| level.volume),
+            OrderSide::Sell => best_bid.as_ref().map(|level| level.volume),
+        };
+        let best_bid = best_bid.map(|level| level.price);
+        let best_ask = best_ask.map(|level| level.price);
+        FillContext {
+            symbol_name: symbol_name.clone(),
+            side: order_side,
+            quantity,
+            book_price,
+            best_bid,
+            best_ask,
+            book_volume,
+            bar: self.last_bar(order_side, symbol_name),
+            tick_size: Some(symbol_tick_size(symbol_name, [Some(book_price), best_bid, best_ask])),
+            lot_step: symbol_lot_step(brokerage, symbol_name),
+        }
+    }
+
     pub fn estimate_fill_price(&self, order_side: OrderSide, symbol_name: &SymbolName, symbol_code: &SymbolCode, volume: Decimal) -> Option<Decimal> {
         let order_book = match order_side {
             OrderSide::Buy => self.ask_books.get(symbol_code).or_else(|| self.ask_books.get(symbol_name)),
@@ -190,3 +277,64 @@ impl MarketPriceService {
     }
 }
 
+/// The tick size of a futures symbol or the pip size of an Oanda symbol.
+/// Symbols which are not in the product maps use the smallest increment of the quoted prices.
+fn symbol_tick_size(symbol_name: &SymbolName, prices: [Option<Price>; 3]) -> Price {
+    if let Ok(info) = get_futures_symbol_info(symbol_name) {
+        return info.tick_size;
+    }
+    if let Some(info) = OANDA_SYMBOL_INFO.get(symbol_name) {
+        return info.tick_size;
+    }
+    let scale = prices.iter().flatten().map(|price| price.normalize().scale()).max().unwrap_or(0);
+    Decimal::new(1, scale)
+}
+
+/// The lot step of the brokerage for the symbol, `None` if the symbol is not in the product maps.
+fn symbol_lot_step(brokerage: &Brokerage, symbol_name: &SymbolName) -> Option<Volume> {
+    let symbol_info = get_futures_symbol_info(symbol_name).ok().or_else(|| OANDA_SYMBOL_INFO.get(symbol_name).cloned())?;
+    Some(brokerage.lot_step(&symbol_info))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use crate::standardized_types::base_data::order_book::OrderBook;
+    use crate::standardized_types::base_data::quote::Quote;
+    use crate::standardized_types::datavendor_enum::DataVendor;
+    use crate::standardized_types::enums::{FuturesExchange, MarketType};
+    use crate::standardized_types::subscriptions::Symbol;
+
+    #[test]
+    fn test_estimate_fill_price_walks_order_book() {
+        let symbol = Symbol::new("ES".to_string(), DataVendor::Rithmic, MarketType::Futures(FuturesExchange::CME));
+        let bids = vec![BookLevel::new(0, dec!(99.75), dec!(5)), BookLevel::new(1, dec!(99.50), dec!(10))];
+        let asks = vec![BookLevel::new(0, dec!(100.00), dec!(2)), BookLevel::new(1, dec!(100.25), dec!(3))];
+        let book = OrderBook::new(symbol.clone(), bids, asks, Utc::now().to_string());
+        let quote = Quote::new(symbol.clone(), dec!(101), dec!(98), dec!(1), dec!(1), Utc::now().to_string());
+
+        let mut time_slice = TimeSlice::new();
+        time_slice.add(BaseDataEnum::OrderBook(book));
+        time_slice.add(BaseDataEnum::Quote(quote));
+        let service = MarketPriceService::new();
+        service.update_market_data(Arc::new(time_slice));
+
+        let name = symbol.name.clone();
+        // the quote does not replace the depth
+        assert_eq!(service.get_market_price(OrderSide::Buy, &name, &name), Some(dec!(100.00)));
+        // 2 @ 100.00 + 2 @ 100.25
+        assert_eq!(service.estimate_fill_price(OrderSide::Buy, &name, &name, dec!(4)), Some(dec!(100.125)));
+        // 5 @ 99.75 + 5 @ 99.50
+        assert_eq!(service.estimate_fill_price(OrderSide::Sell, &name, &name, dec!(10)), Some(dec!(99.625)));
+        assert_eq!(service.estimate_limit_fill(OrderSide::Buy, &name, &name, dec!(4), dec!(100.00)), Some((dec!(100.00), dec!(2))));
+    }
+
+    #[test]
+    fn test_symbol_tick_size() {
+        assert_eq!(symbol_tick_size(&"ES".to_string(), [None, None, None]), dec!(0.25));
+        assert_eq!(symbol_tick_size(&"EUR-USD".to_string(), [None, None, None]), dec!(0.00001));
+        // unknown symbols use the precision of the quotes
+        assert_eq!(symbol_tick_size(&"BTC-USDT".to_string(), [Some(dec!(65000.5)), Some(dec!(64999.25)), None]), dec!(0.01));
+    }
+}
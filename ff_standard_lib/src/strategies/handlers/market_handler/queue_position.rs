@@ -0,0 +1,318 @@
+use std::collections::BTreeMap;
+use ahash::AHashMap;
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use rust_decimal::Decimal;
+use crate::standardized_types::base_data::market_by_order::{MarketByOrder, MarketByOrderAction};
+use crate::standardized_types::base_data::tick::{Aggressor, Tick};
+use crate::standardized_types::base_data::traits::BaseData;
+use crate::standardized_types::books::BookLevel;
+use crate::standardized_types::enums::OrderSide;
+use crate::standardized_types::new_types::{Price, Volume};
+use crate::standardized_types::orders::{Order, OrderId};
+use crate::standardized_types::subscriptions::SymbolName;
+
+/// An exchange order resting in the market by order book.
+#[derive(Clone, Debug)]
+struct RestingOrder {
+    side: OrderSide,
+    price: Price,
+    volume: Volume,
+}
+
+/// The exchange order book of a symbol rebuilt from market by order events.
+#[derive(Default)]
+struct MarketByOrderBook {
+    orders: AHashMap<String, RestingOrder>,
+    bids: BTreeMap<Price, Volume>,
+    asks: BTreeMap<Price, Volume>,
+}
+
+impl MarketByOrderBook {
+    fn apply(&mut self, mbo: &MarketByOrder) {
+        if let Some(previous) = self.orders.remove(&mbo.order_id) {
+            self.change_level(previous.side, previous.price, -previous.volume);
+        }
+        if mbo.action == MarketByOrderAction::Delete || mbo.volume <= Decimal::ZERO {
+            return;
+        }
+        self.change_level(mbo.side, mbo.price, mbo.volume);
+        self.orders.insert(mbo.order_id.clone(), RestingOrder { side: mbo.side, price: mbo.price, volume: mbo.volume });
+    }
+
+    fn change_level(&mut self, side: OrderSide, price: Price, volume: Volume) {
+        let levels = match side {
+            OrderSide::Buy => &mut self.bids,
+            OrderSide::Sell => &mut self.asks,
+        };
+        let level_volume = levels.entry(price).or_insert(Decimal::ZERO);
+        *level_volume += volume;
+        if *level_volume <= Decimal::ZERO {
+            levels.remove(&price);
+        }
+    }
+
+    /// The exchange orders resting at the price on the side, with their remaining volume.
+    fn orders_at(&self, side: OrderSide, price: Price) -> AHashMap<String, Volume> {
+        self.orders.iter()
+            .filter(|(_, order)| order.side == side && order.price == price)
+            .map(|(id, order)| (id.clone(), order.volume))
+            .collect()
+    }
+
+    /// The best `depth` levels of the side, level 0 is the best price.
+    fn levels(&self, side: OrderSide, depth: usize) -> BTreeMap<u16, BookLevel> {
+        let levels: Box<dyn Iterator<Item = (&Price, &Volume)>> = match side {
+            OrderSide::Buy => Box::new(self.bids.iter().rev()),
+            OrderSide::Sell => Box::new(self.asks.iter()),
+        };
+        levels.take(depth)
+            .enumerate()
+            .map(|(level, (price, volume))| (level as u16, BookLevel::new(level as u16, *price, *volume)))
+            .collect()
+    }
+}
+
+/// Where one of our simulated limit orders is in the queue at its price.
+#[derive(Clone, Debug)]
+struct QueuedOrder {
+    symbol_name: SymbolName,
+    side: OrderSide,
+    price: Price,
+    /// The exchange orders which were resting at our price when we joined the queue, and their remaining volume.
+    ahead: AHashMap<String, Volume>,
+    /// When the last order ahead of us left the queue.
+    front_since: Option<DateTime<Utc>>,
+    /// The volume traded at our price since we reached the front of the queue which has not been filled yet.
+    traded_volume: Volume,
+}
+
+impl QueuedOrder {
+    fn update(&mut self, mbo: &MarketByOrder, time: DateTime<Utc>) {
+        if let Some(volume_ahead) = self.ahead.get_mut(&mbo.order_id) {
+            // an order which is moved, increased or re-entered loses its priority and goes behind us
+            let keeps_priority = mbo.action == MarketByOrderAction::Modify
+                && mbo.side == self.side
+                && mbo.price == self.price
+                && mbo.volume > Decimal::ZERO
+                && mbo.volume <= *volume_ahead;
+            match keeps_priority {
+                true => *volume_ahead = mbo.volume,
+                false => {
+                    self.ahead.remove(&mbo.order_id);
+                }
+            }
+        }
+        if self.ahead.is_empty() && self.front_since.is_none() {
+            self.front_since = Some(time);
+        }
+    }
+
+    fn update_trade(&mut self, tick: &Tick) {
+        let front_since = match self.front_since {
+            Some(time) => time,
+            None => return,
+        };
+        // a trade at the same moment we reached the front is the trade which cleared the orders ahead of us
+        if tick.price != self.price || tick.time_utc() <= front_since {
+            return;
+        }
+        let hits_our_side = match tick.aggressor {
+            Aggressor::Buy => self.side == OrderSide::Sell,
+            Aggressor::Sell => self.side == OrderSide::Buy,
+            Aggressor::None => true,
+        };
+        if hits_our_side {
+            self.traded_volume += tick.volume;
+        }
+    }
+}
+
+/// The place of a simulated limit order in the queue at its price.
+#[derive(Clone, Debug, PartialEq)]
+pub struct QueuePosition {
+    /// The volume of the exchange orders still resting ahead of the order.
+    pub volume_ahead: Volume,
+    /// The volume traded at the order price since the order reached the front of the queue, which the order can fill.
+    pub traded_volume: Volume,
+}
+
+/// Rebuilds the exchange order book of each symbol with market by order depth and tracks the queue position of simulated limit orders.
+/// A limit order joins the back of the queue when it is first matched and moves forward as the orders ahead of it trade or are cancelled.
+#[derive(Default)]
+pub struct QueuePositions {
+    books: DashMap<SymbolName, MarketByOrderBook>,
+    queued_orders: DashMap<OrderId, QueuedOrder>,
+}
+
+impl QueuePositions {
+    pub fn new() -> Self {
+        QueuePositions {
+            books: DashMap::new(),
+            queued_orders: DashMap::new(),
+        }
+    }
+
+    /// Applies a market by order event to the book of its symbol and to the queues of our orders.
+    pub fn update_market_by_order(&self, mbo: &MarketByOrder) {
+        let symbol_name = &mbo.symbol.name;
+        self.books.entry(symbol_name.clone()).or_default().apply(mbo);
+        let time = mbo.time_utc();
+        for mut queued_order in self.queued_orders.iter_mut() {
+            if queued_order.symbol_name == *symbol_name {
+                queued_order.update(mbo, time);
+            }
+        }
+    }
+
+    /// Counts trades at the price of our orders once they are at the front of the queue.
+    pub fn update_trade(&self, tick: &Tick) {
+        for mut queued_order in self.queued_orders.iter_mut() {
+            if queued_order.symbol_name == tick.symbol.name {
+                queued_order.update_trade(tick);
+            }
+        }
+    }
+
+    /// Returns true if the symbol has market by order depth.
+    pub fn has_book(&self, symbol_name: &SymbolName) -> bool {
+        self.books.contains_key(symbol_name)
+    }
+
+    /// The best `depth` levels of the side of the symbol book, `None` if the symbol has no market by order depth.
+    pub fn levels(&self, symbol_name: &SymbolName, side: OrderSide, depth: usize) -> Option<BTreeMap<u16, BookLevel>> {
+        self.books.get(symbol_name).map(|book| book.levels(side, depth))
+    }
+
+    /// The queue position of a limit order, the order joins the back of the queue the first time it is checked or when its side or price changed.
+    /// Returns `None` if the order is not a limit order or its symbol has no market by order depth.
+    pub fn position(&self, order: &Order, time: DateTime<Utc>) -> Option<QueuePosition> {
+        let price = order.limit_price?;
+        let symbol_name = match self.books.contains_key(&order.symbol_code) {
+            true => &order.symbol_code,
+            false => &order.symbol_name,
+        };
+        let book = self.books.get(symbol_name)?;
+
+        let joins_queue = match self.queued_orders.get(&order.id) {
+            Some(queued_order) => queued_order.price != price || queued_order.side != order.side,
+            None => true,
+        };
+        if joins_queue {
+            let ahead = book.orders_at(order.side, price);
+            let front_since = match ahead.is_empty() {
+                true => Some(time),
+                false => None,
+            };
+            self.queued_orders.insert(order.id.clone(), QueuedOrder {
+                symbol_name: symbol_name.clone(),
+                side: order.side,
+                price,
+                ahead,
+                front_since,
+                traded_volume: Decimal::ZERO,
+            });
+        }
+
+        let queued_order = self.queued_orders.get(&order.id)?;
+        Some(QueuePosition {
+            volume_ahead: queued_order.ahead.values().sum(),
+            traded_volume: queued_order.traded_volume,
+        })
+    }
+
+    /// Removes `volume` from the traded volume available to fill the order.
+    pub fn consume(&self, order_id: &OrderId, volume: Volume) {
+        if let Some(mut queued_order) = self.queued_orders.get_mut(order_id) {
+            queued_order.traded_volume = (queued_order.traded_volume - volume).max(Decimal::ZERO);
+        }
+    }
+
+    /// Stops tracking the orders which are no longer open.
+    pub fn retain(&self, is_open: impl Fn(&OrderId) -> bool) {
+        self.queued_orders.retain(|order_id, _| is_open(order_id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use rust_decimal_macros::dec;
+    use crate::standardized_types::accounts::Account;
+    use crate::standardized_types::broker_enum::Brokerage;
+    use crate::standardized_types::datavendor_enum::DataVendor;
+    use crate::standardized_types::enums::{FuturesExchange, MarketType};
+    use crate::standardized_types::orders::TimeInForce;
+    use crate::standardized_types::subscriptions::Symbol;
+
+    fn symbol() -> Symbol {
+        Symbol::new("ES".to_string(), DataVendor::Rithmic, MarketType::Futures(FuturesExchange::CME))
+    }
+
+    fn mbo(order_id: &str, action: MarketByOrderAction, price: Price, volume: Volume, time: DateTime<Utc>) -> MarketByOrder {
+        MarketByOrder::new(symbol(), order_id.to_string(), OrderSide::Buy, action, price, volume, 0, time.to_string())
+    }
+
+    fn tick(price: Price, volume: Volume, time: DateTime<Utc>) -> Tick {
+        Tick::new(symbol(), price, time.to_string(), volume, Aggressor::Sell)
+    }
+
+    fn buy_limit(limit: Price, time: DateTime<Utc>) -> Order {
+        let account = Account::new(Brokerage::Test, "test_account".to_string());
+        Order::limit_order("ES".to_string(), None, &account, dec!(2), OrderSide::Buy, "test".to_string(), "1".to_string(), time, limit, TimeInForce::GTC, None)
+    }
+
+    #[test]
+    fn test_limit_order_waits_for_queue_ahead() {
+        let queues = QueuePositions::new();
+        let start = Utc::now();
+        queues.update_market_by_order(&mbo("a", MarketByOrderAction::Add, dec!(100), dec!(3), start));
+        queues.update_market_by_order(&mbo("b", MarketByOrderAction::Add, dec!(100), dec!(2), start + Duration::nanoseconds(1)));
+        queues.update_market_by_order(&mbo("c", MarketByOrderAction::Add, dec!(99.75), dec!(4), start + Duration::nanoseconds(2)));
+
+        let order = buy_limit(dec!(100), start);
+        let join_time = start + Duration::seconds(1);
+        assert_eq!(queues.position(&order, join_time).unwrap().volume_ahead, dec!(5));
+
+        // orders joining after us are behind us and trades while orders are ahead go to them
+        queues.update_market_by_order(&mbo("d", MarketByOrderAction::Add, dec!(100), dec!(10), join_time + Duration::seconds(1)));
+        queues.update_trade(&tick(dec!(100), dec!(1), join_time + Duration::seconds(2)));
+        queues.update_market_by_order(&mbo("a", MarketByOrderAction::Modify, dec!(100), dec!(2), join_time + Duration::seconds(2)));
+        let position = queues.position(&order, join_time + Duration::seconds(2)).unwrap();
+        assert_eq!(position, QueuePosition { volume_ahead: dec!(4), traded_volume: dec!(0) });
+
+        // a cancel and an order moving to another price leave the queue
+        queues.update_market_by_order(&mbo("a", MarketByOrderAction::Delete, dec!(100), dec!(0), join_time + Duration::seconds(3)));
+        queues.update_market_by_order(&mbo("b", MarketByOrderAction::Modify, dec!(99.50), dec!(2), join_time + Duration::seconds(4)));
+        assert_eq!(queues.position(&order, join_time + Duration::seconds(4)).unwrap().volume_ahead, dec!(0));
+
+        // only trades after we reached the front fill us
+        queues.update_trade(&tick(dec!(100), dec!(2), join_time + Duration::seconds(4)));
+        queues.update_trade(&tick(dec!(100), dec!(1), join_time + Duration::seconds(5)));
+        assert_eq!(queues.position(&order, join_time + Duration::seconds(5)).unwrap().traded_volume, dec!(1));
+        queues.consume(&order.id, dec!(1));
+        assert_eq!(queues.position(&order, join_time + Duration::seconds(5)).unwrap().traded_volume, dec!(0));
+
+        queues.retain(|_| false);
+        assert!(queues.queued_orders.is_empty());
+    }
+
+    #[test]
+    fn test_market_by_order_book_levels() {
+        let queues = QueuePositions::new();
+        let start = Utc::now();
+        queues.update_market_by_order(&mbo("a", MarketByOrderAction::Add, dec!(100), dec!(3), start));
+        queues.update_market_by_order(&mbo("b", MarketByOrderAction::Add, dec!(100), dec!(2), start));
+        queues.update_market_by_order(&mbo("c", MarketByOrderAction::Add, dec!(99.75), dec!(4), start));
+        queues.update_market_by_order(&mbo("a", MarketByOrderAction::Delete, dec!(100), dec!(0), start));
+
+        let bids = queues.levels(&"ES".to_string(), OrderSide::Buy, 10).unwrap();
+        assert_eq!(bids.len(), 2);
+        assert_eq!(bids[&0].price, dec!(100));
+        assert_eq!(bids[&0].volume, dec!(2));
+        assert_eq!(bids[&1].price, dec!(99.75));
+        assert!(queues.levels(&"ES".to_string(), OrderSide::Sell, 10).unwrap().is_empty());
+        assert!(queues.levels(&"NQ".to_string(), OrderSide::Buy, 10).is_none());
+    }
+}
@@ -0,0 +1,340 @@
+use std::collections::VecDeque;
+use std::fmt::{Display, Formatter};
+use std::sync::Mutex;
+use ahash::AHashMap;
+use chrono::{DateTime, Duration, Utc};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use crate::standardized_types::accounts::Account;
+use crate::standardized_types::market_hours::TradingHours;
+use crate::standardized_types::enums::OrderSide;
+use crate::standardized_types::orders::{Order, OrderType};
+use crate::standardized_types::subscriptions::SymbolName;
+use crate::standardized_types::new_types::Volume;
+
+/// The account equity the loss limits are measured against, in the account currency.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct AccountEquity {
+    /// Cash plus the open pnl of all positions.
+    pub equity: Decimal,
+    /// The highest equity recorded by the ledger.
+    pub peak: Decimal,
+    /// The equity at the last update of the previous UTC day, or the starting equity on the first day.
+    pub day_start: Decimal,
+}
+
+/// The state of the account when an order is checked.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct RiskContext {
+    pub time: DateTime<Utc>,
+    /// The open position for the order symbol, positive if long and negative if short.
+    pub position: Volume,
+    /// The orders placed by the strategy which are still open on the account.
+    pub open_orders: usize,
+    /// The quantity of the other open orders for the order symbol on the same side as the order, which add to the position if they fill.
+    pub open_quantity: Volume,
+    /// `None` if the account has no ledger, the loss limits are not checked.
+    pub equity: Option<AccountEquity>,
+}
+
+/// The reason an order was rejected by the `RiskManager`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RiskViolation {
+    PositionSize { symbol_name: SymbolName, size: Volume, limit: Volume },
+    OpenOrders { count: usize, limit: usize },
+    DailyLoss { loss: Decimal, limit: Decimal },
+    Drawdown { drawdown: Decimal, limit: Decimal },
+    OrderRate { count: usize, limit: usize },
+    OutsideTradingHours,
+}
+
+impl Display for RiskViolation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RiskViolation::PositionSize { symbol_name, size, limit } => write!(f, "Risk Manager: Position size {} for {} would exceed the limit of {}", size, symbol_name, limit),
+            RiskViolation::OpenOrders { count, limit } => write!(f, "Risk Manager: {} open orders, the limit is {}", count, limit),
+            RiskViolation::DailyLoss { loss, limit } => write!(f, "Risk Manager: Daily loss {} has reached the limit of {}", loss, limit),
+            RiskViolation::Drawdown { drawdown, limit } => write!(f, "Risk Manager: Drawdown {} from peak equity has reached the limit of {}", drawdown, limit),
+            RiskViolation::OrderRate { count, limit } => write!(f, "Risk Manager: {} orders in the last minute, the limit is {}", count, limit),
+            RiskViolation::OutsideTradingHours => write!(f, "Risk Manager: Outside the allowed trading hours"),
+        }
+    }
+}
+
+/// Checks every order the strategy places before it is sent to the broker or the backtest engine, rejected orders are returned to the strategy as `OrderUpdateEvent::OrderRejected`.
+///
+/// Orders which only reduce a position, such as `exit_long()` or a stop loss, are always allowed except by the orders per minute limit, so the strategy can still get flat after a limit is reached.
+/// The limits apply to each account separately, the daily loss and drawdown are measured from the equity recorded by the ledger.
+#[derive(Debug, Default)]
+pub struct RiskManager {
+    max_position_size: Option<Volume>,
+    symbol_max_position_size: AHashMap<SymbolName, Volume>,
+    max_open_orders: Option<usize>,
+    daily_loss_limit: Option<Decimal>,
+    max_drawdown: Option<Decimal>,
+    max_orders_per_minute: Option<usize>,
+    trading_hours: Option<TradingHours>,
+    flatten_on_violation: bool,
+    order_times: Mutex<AHashMap<Account, VecDeque<DateTime<Utc>>>>,
+}
+
+impl RiskManager {
+    /// No limits, every order is sent.
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// The largest position for any symbol, including the quantity of the order and the open orders on the same side.
+    pub fn with_max_position_size(mut self, max_size: Volume) -> Self {
+        self.max_position_size = Some(max_size);
+        self
+    }
+
+    /// The largest position for the symbol, replacing `with_max_position_size()` for this symbol.
+    pub fn with_symbol_max_position_size(mut self, symbol_name: SymbolName, max_size: Volume) -> Self {
+        self.symbol_max_position_size.insert(symbol_name, max_size);
+        self
+    }
+
+    pub fn with_max_open_orders(mut self, max_open_orders: usize) -> Self {
+        self.max_open_orders = Some(max_open_orders);
+        self
+    }
+
+    /// The most the account equity can fall during a UTC day before new positions are rejected, eg `dec!(1000)`.
+    pub fn with_daily_loss_limit(mut self, limit: Decimal) -> Self {
+        self.daily_loss_limit = Some(limit.abs());
+        self
+    }
+
+    /// The most the account equity can fall from its peak before new positions are rejected, like the trailing drawdown of a funded account.
+    pub fn with_max_drawdown(mut self, limit: Decimal) -> Self {
+        self.max_drawdown = Some(limit.abs());
+        self
+    }
+
+    pub fn with_max_orders_per_minute(mut self, max_orders: usize) -> Self {
+        self.max_orders_per_minute = Some(max_orders);
+        self
+    }
+
+    /// Rejects new positions outside the sessions, eg `CME_HOURS`.
+    pub fn with_trading_hours(mut self, trading_hours: TradingHours) -> Self {
+        self.trading_hours = Some(trading_hours);
+        self
+    }
+
+    /// Flattens all positions on the account with `flatten_all_for()` when an order is rejected.
+    pub fn with_flatten_on_violation(mut self) -> Self {
+        self.flatten_on_violation = true;
+        self
+    }
+
+    pub fn flatten_on_violation(&self) -> bool {
+        self.flatten_on_violation
+    }
+
+    /// Returns the first limit the order would break. Passing orders count towards the orders per minute when `new_order` is true, updates to open orders do not.
+    pub(crate) fn check(&self, order: &Order, context: &RiskContext, new_order: bool) -> Result<(), RiskViolation> {
+        let mut order_times = self.order_times.lock().unwrap();
+        let order_times = order_times.entry(order.account.clone()).or_default();
+        if let Some(limit) = self.max_orders_per_minute {
+            while order_times.front().is_some_and(|time| context.time - *time >= Duration::minutes(1)) {
+                order_times.pop_front();
+            }
+            if new_order && order_times.len() >= limit {
+                return Err(RiskViolation::OrderRate { count: order_times.len(), limit });
+            }
+        }
+
+        let projected = projected_position(order, context.position);
+        let reducing = projected.abs() <= context.position.abs() && projected * context.position >= dec!(0);
+        if !reducing {
+            let projected = match order.side {
+                OrderSide::Buy => projected + context.open_quantity,
+                OrderSide::Sell => projected - context.open_quantity,
+            };
+            self.check_new_risk(order, context, projected, new_order)?;
+        }
+
+        if new_order && self.max_orders_per_minute.is_some() {
+            order_times.push_back(context.time);
+        }
+        Ok(())
+    }
+
+    fn check_new_risk(&self, order: &Order, context: &RiskContext, projected: Volume, new_order: bool) -> Result<(), RiskViolation> {
+        if let Some(trading_hours) = &self.trading_hours {
+            if !trading_hours.is_market_open(context.time) {
+                return Err(RiskViolation::OutsideTradingHours);
+            }
+        }
+        let limit = self.symbol_max_position_size.get(&order.symbol_name).or(self.max_position_size.as_ref());
+        if let Some(limit) = limit {
+            if projected.abs() > *limit {
+                return Err(RiskViolation::PositionSize { symbol_name: order.symbol_name.clone(), size: projected.abs(), limit: *limit });
+            }
+        }
+        if let Some(limit) = self.max_open_orders {
+            if new_order && context.open_orders >= limit {
+                return Err(RiskViolation::OpenOrders { count: context.open_orders, limit });
+            }
+        }
+        if let Some(equity) = &context.equity {
+            if let Some(limit) = self.daily_loss_limit {
+                let loss = equity.day_start - equity.equity;
+                if loss >= limit {
+                    return Err(RiskViolation::DailyLoss { loss, limit });
+                }
+            }
+            if let Some(limit) = self.max_drawdown {
+                let drawdown = equity.peak.max(equity.equity) - equity.equity;
+                if drawdown >= limit {
+                    return Err(RiskViolation::Drawdown { drawdown, limit });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The position after the order fills, positive if long and negative if short.
+fn projected_position(order: &Order, position: Volume) -> Volume {
+    let quantity = order.quantity_open;
+    match order.order_type {
+        OrderType::EnterLong => position.max(dec!(0)) + quantity,
+        OrderType::EnterShort => position.min(dec!(0)) - quantity,
+        OrderType::ExitLong => position - quantity.min(position.max(dec!(0))),
+        OrderType::ExitShort => position + quantity.min((-position).max(dec!(0))),
+        _ => match order.side {
+            OrderSide::Buy => position + quantity,
+            OrderSide::Sell => position - quantity,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use crate::product_maps::rithmic::maps::CME_HOURS;
+    use crate::standardized_types::broker_enum::Brokerage;
+
+    fn order(order_type: OrderType, side: OrderSide, quantity: Volume) -> Order {
+        account_order(order_type, side, quantity, "Test_Account_1")
+    }
+
+    fn account_order(order_type: OrderType, side: OrderSide, quantity: Volume, account_id: &str) -> Order {
+        let account = Account::new(Brokerage::Test, account_id.to_string());
+        let time = Utc::now();
+        let mut order = Order::market_order("MNQ".to_string(), None, &account, quantity, side, String::new(), "1".to_string(), time, None);
+        order.order_type = order_type;
+        order
+    }
+
+    fn context(time: DateTime<Utc>, position: Volume, equity: Decimal) -> RiskContext {
+        RiskContext {
+            time,
+            position,
+            open_orders: 0,
+            open_quantity: dec!(0),
+            equity: Some(AccountEquity { equity, peak: dec!(105000), day_start: dec!(101000) }),
+        }
+    }
+
+    #[test]
+    fn test_position_and_loss_limits() {
+        // a monday during the CME session
+        let time = chrono_tz::America::Chicago.with_ymd_and_hms(2024, 1, 8, 9, 0, 0).unwrap().to_utc();
+        let risk = RiskManager::none()
+            .with_max_position_size(dec!(5))
+            .with_symbol_max_position_size("MES".to_string(), dec!(10))
+            .with_daily_loss_limit(dec!(1000))
+            .with_max_drawdown(dec!(5000))
+            .with_trading_hours(CME_HOURS);
+
+        let buy = order(OrderType::Market, OrderSide::Buy, dec!(2));
+        assert!(risk.check(&buy, &context(time, dec!(3), dec!(100500)), true).is_ok());
+        assert_eq!(
+            risk.check(&buy, &context(time, dec!(4), dec!(100500)), true),
+            Err(RiskViolation::PositionSize { symbol_name: "MNQ".to_string(), size: dec!(6), limit: dec!(5) })
+        );
+        // entering long closes the short first
+        let enter_long = order(OrderType::EnterLong, OrderSide::Buy, dec!(5));
+        assert!(risk.check(&enter_long, &context(time, dec!(-4), dec!(100500)), true).is_ok());
+
+        assert_eq!(
+            risk.check(&buy, &context(time, dec!(0), dec!(100000)), true),
+            Err(RiskViolation::DailyLoss { loss: dec!(1000), limit: dec!(1000) })
+        );
+        let mut drawdown = context(time, dec!(0), dec!(99500));
+        drawdown.equity = Some(AccountEquity { equity: dec!(99500), peak: dec!(105000), day_start: dec!(99000) });
+        assert_eq!(risk.check(&buy, &drawdown, true), Err(RiskViolation::Drawdown { drawdown: dec!(5500), limit: dec!(5000) }));
+
+        // exits are allowed after a limit is reached
+        let exit_long = order(OrderType::ExitLong, OrderSide::Sell, dec!(4));
+        assert!(risk.check(&exit_long, &context(time, dec!(4), dec!(90000)), true).is_ok());
+        let sell = order(OrderType::Limit, OrderSide::Sell, dec!(2));
+        assert!(risk.check(&sell, &context(time, dec!(4), dec!(90000)), true).is_ok());
+        assert!(risk.check(&sell, &context(time, dec!(1), dec!(90000)), true).is_err());
+
+        let saturday = chrono_tz::America::Chicago.with_ymd_and_hms(2024, 1, 6, 9, 0, 0).unwrap().to_utc();
+        assert_eq!(risk.check(&buy, &context(saturday, dec!(0), dec!(101000)), true), Err(RiskViolation::OutsideTradingHours));
+    }
+
+    #[test]
+    fn test_order_rate_and_open_orders() {
+        let time = Utc.with_ymd_and_hms(2024, 1, 8, 15, 0, 0).unwrap();
+        let risk = RiskManager::none()
+            .with_max_orders_per_minute(2)
+            .with_max_open_orders(3);
+        let buy = order(OrderType::Market, OrderSide::Buy, dec!(1));
+
+        assert!(risk.check(&buy, &context(time, dec!(0), dec!(100000)), true).is_ok());
+        assert!(risk.check(&buy, &context(time + Duration::seconds(10), dec!(0), dec!(100000)), true).is_ok());
+        // updates to open orders are not counted
+        assert!(risk.check(&buy, &context(time + Duration::seconds(20), dec!(0), dec!(100000)), false).is_ok());
+        assert_eq!(
+            risk.check(&buy, &context(time + Duration::seconds(30), dec!(0), dec!(100000)), true),
+            Err(RiskViolation::OrderRate { count: 2, limit: 2 })
+        );
+        assert!(risk.check(&buy, &context(time + Duration::seconds(60), dec!(0), dec!(100000)), true).is_ok());
+
+        let mut open_orders = context(time + Duration::minutes(5), dec!(0), dec!(100000));
+        open_orders.open_orders = 3;
+        assert_eq!(risk.check(&buy, &open_orders, true), Err(RiskViolation::OpenOrders { count: 3, limit: 3 }));
+
+        // each account has its own rate limit
+        let risk = RiskManager::none().with_max_orders_per_minute(1);
+        let other_account_buy = account_order(OrderType::Market, OrderSide::Buy, dec!(1), "Test_Account_2");
+        assert!(risk.check(&buy, &context(time, dec!(0), dec!(100000)), true).is_ok());
+        assert!(risk.check(&other_account_buy, &context(time, dec!(0), dec!(100000)), true).is_ok());
+        assert_eq!(
+            risk.check(&buy, &context(time + Duration::seconds(10), dec!(0), dec!(100000)), true),
+            Err(RiskViolation::OrderRate { count: 1, limit: 1 })
+        );
+    }
+
+    #[test]
+    fn test_open_orders_count_towards_position_size() {
+        let time = Utc.with_ymd_and_hms(2024, 1, 8, 15, 0, 0).unwrap();
+        let risk = RiskManager::none().with_max_position_size(dec!(5));
+        let buy_limit = order(OrderType::Limit, OrderSide::Buy, dec!(2));
+
+        // 2 long and 2 more resting in buy limits, another 2 would make 6 if they all fill
+        let mut resting = context(time, dec!(2), dec!(100000));
+        resting.open_quantity = dec!(2);
+        assert_eq!(
+            risk.check(&buy_limit, &resting, true),
+            Err(RiskViolation::PositionSize { symbol_name: "MNQ".to_string(), size: dec!(6), limit: dec!(5) })
+        );
+        resting.open_quantity = dec!(1);
+        assert!(risk.check(&buy_limit, &resting, true).is_ok());
+
+        // resting sell orders do not block a sell which reduces the position
+        let sell_limit = order(OrderType::Limit, OrderSide::Sell, dec!(2));
+        let mut long = context(time, dec!(4), dec!(100000));
+        long.open_quantity = dec!(10);
+        assert!(risk.check(&sell_limit, &long, true).is_ok());
+    }
+}
@@ -3,5 +3,5 @@ pub(crate) mod registry_handler;
 pub(crate) mod subscription_handler;
 pub(crate) mod timed_events_handler;
 pub(crate) mod indicator_handler;
-pub(crate) mod market_handler;
+pub mod market_handler;
 pub(crate) mod live_warmup;
\ No newline at end of file
@@ -14,9 +14,9 @@ use dashmap::DashMap;
 use futures::stream::FuturesUnordered;
 use futures::StreamExt;
 use tokio::sync::RwLock;
-use crate::strategies::client_features::server_connections::{is_warmup_complete};
 use crate::standardized_types::base_data::candle::Candle;
 use crate::standardized_types::base_data::fundamental::Fundamental;
+use crate::standardized_types::base_data::order_book::OrderBook;
 use crate::standardized_types::base_data::quote::Quote;
 use crate::standardized_types::base_data::quotebar::QuoteBar;
 use crate::standardized_types::base_data::tick::Tick;
@@ -27,6 +27,8 @@ use tokio::sync::broadcast;
 use tokio::sync::mpsc::Sender;
 use crate::standardized_types::base_data::history::{get_compressed_historical_data};
 use crate::standardized_types::market_hours::TradingHours;
+use crate::strategies::historical_time::StrategyClock;
+use crate::strategies::client_features::request_handler::DataServerSender;
 
 /// Manages all subscriptions for a strategy. each strategy has its own subscription handler.
 pub struct SubscriptionHandler {
@@ -43,13 +45,16 @@ pub struct SubscriptionHandler {
     tick_history: DashMap<DataSubscription, RollingWindow<Tick>>,
     quote_history: DashMap<DataSubscription, RollingWindow<Quote>>,
     fundamental_history: DashMap<DataSubscription, RollingWindow<Fundamental>>,
+    order_book_history: DashMap<DataSubscription, RollingWindow<OrderBook>>,
     open_candles: DashMap<DataSubscription, Candle>,
     open_bars: DashMap<DataSubscription, QuoteBar>,
-    strategy_event_sender: Sender<StrategyEvent>
+    strategy_event_sender: Sender<StrategyEvent>,
+    clock: StrategyClock,
+    data_server_sender: DataServerSender
 }
 
 impl SubscriptionHandler {
-    pub async fn new(strategy_mode: StrategyMode, strategy_event_sender: Sender<StrategyEvent>) -> Self {
+    pub async fn new(strategy_mode: StrategyMode, strategy_event_sender: Sender<StrategyEvent>, clock: StrategyClock, data_server_sender: DataServerSender) -> Self {
         let (tx, _) = broadcast::channel(16);
         SubscriptionHandler {
             strategy_event_sender,
@@ -63,8 +68,11 @@ impl SubscriptionHandler {
             tick_history: Default::default(),
             quote_history: Default::default(),
             fundamental_history: Default::default(),
+            order_book_history: Default::default(),
             open_candles: Default::default(),
             open_bars: Default::default(),
+            clock,
+            data_server_sender,
         }
     }
 
@@ -120,6 +128,7 @@ impl SubscriptionHandler {
         if !self.symbol_subscriptions.contains_key(&new_subscription.symbol) {
             let symbol_handler = SymbolSubscriptionHandler::new(
                 new_subscription.symbol.clone(),
+                self.data_server_sender.clone(),
             ).await;
             self.symbol_subscriptions.insert(new_subscription.symbol.clone(), symbol_handler);
         }
@@ -133,6 +142,7 @@ impl SubscriptionHandler {
                 self.strategy_mode,
                 fill_forward,
                 hours,
+                self.clock.is_warmup_complete(),
             ).await;
 
         match windows {
@@ -195,6 +205,18 @@ impl SubscriptionHandler {
                                 }
                             }
                         }
+                        BaseDataType::OrderBooks => {
+                            self.order_book_history.insert(subscription.clone(), RollingWindow::new(history_to_retain));
+                            if let Some(mut book_window) = self.order_book_history.get_mut(&subscription) {
+                                for data in window.history {
+                                    if let BaseDataEnum::OrderBook(book) = data {
+                                        book_window.value_mut().add(book);
+                                    }
+                                }
+                            }
+                        }
+                        // market by order events are only used to rebuild the exchange order book, no history is retained
+                        BaseDataType::MarketByOrder => {}
                     }
                 }
                 if broadcast {
@@ -261,6 +283,10 @@ impl SubscriptionHandler {
             BaseDataType::Fundamentals => {
                 self.fundamental_history.remove(&subscription);
             }
+            BaseDataType::OrderBooks => {
+                self.order_book_history.remove(&subscription);
+            }
+            BaseDataType::MarketByOrder => {}
         }
         if broadcast {
             let subscriptions = self.primary_subscriptions().await;
@@ -330,7 +356,12 @@ impl SubscriptionHandler {
                         history.add(q.clone());
                     }
                 }
-                BaseDataEnum::Fundamental(_) => {}
+                BaseDataEnum::OrderBook(book) => {
+                    if let Some(mut history) = self.order_book_history.get_mut(&book.subscription()) {
+                        history.add(book.clone());
+                    }
+                }
+                BaseDataEnum::Fundamental(_) | BaseDataEnum::MarketByOrder(_) => {}
             }
 
             update_futures.push(async move {
@@ -381,6 +412,12 @@ impl SubscriptionHandler {
                         rolling_window.add(fund.clone());
                     }
                 }
+                BaseDataEnum::OrderBook(book) => {
+                    if let Some(mut rolling_window) = self.order_book_history.get_mut(&subscription) {
+                        rolling_window.add(book.clone());
+                    }
+                }
+                BaseDataEnum::MarketByOrder(_) => {}
             }
             time_slice_bars.add(data);
         }
@@ -487,6 +524,10 @@ impl SubscriptionHandler {
         None
     }
 
+    pub fn order_book_index(&self, subscription: &DataSubscription, index: usize) -> Option<OrderBook> {
+        self.order_book_history.get(subscription).and_then(|window| window.get(index).cloned())
+    }
+
     //todo need a live version of this, where we record which consolidators had data and which didnt, we update time for thise that didn't
     pub async fn update_consolidators_time(&self, time: DateTime<Utc>) -> Option<TimeSlice> {
         let symbol_subscriptions = self.symbol_subscriptions.clone();
@@ -533,6 +574,12 @@ impl SubscriptionHandler {
                                 rolling_window.add(fund.clone());
                             }
                         }
+                        BaseDataEnum::OrderBook(ref book) => {
+                            if let Some(mut rolling_window) = self.order_book_history.get_mut(&subscription) {
+                                rolling_window.add(book.clone());
+                            }
+                        }
+                        BaseDataEnum::MarketByOrder(_) => {}
                     }
                 }
                 for base_data in data {
@@ -557,17 +604,20 @@ pub struct SymbolSubscriptionHandler {
     /// The secondary subscriptions are consolidators that are used to consolidate data from the primary subscription. the first key is the primary subscription for each consolidator
     secondary_subscriptions: DashMap<PrimarySubscription, AHashMap<DataSubscription, ConsolidatorEnum>>,
     vendor_primary_resolutions: Vec<PrimarySubscription>,
+    data_server_sender: DataServerSender,
 }
 
 impl SymbolSubscriptionHandler {
     pub async fn new(
         symbol: Symbol,
+        data_server_sender: DataServerSender,
     ) -> Self {
-        let vendor_primary_resolutions = symbol.data_vendor.resolutions(symbol.market_type.clone()).await.unwrap();
+        let vendor_primary_resolutions = symbol.data_vendor.resolutions(symbol.market_type, &data_server_sender).await.unwrap();
         let handler = SymbolSubscriptionHandler {
             primary_subscriptions: DashMap::new(),
             secondary_subscriptions: DashMap::new(),
             vendor_primary_resolutions,
+            data_server_sender,
         };
         handler
     }
@@ -624,12 +674,21 @@ impl SymbolSubscriptionHandler {
         history_to_retain: usize,
         strategy_mode: StrategyMode,
         fill_forward: bool,
-        hours: Option<TradingHours>
+        hours: Option<TradingHours>,
+        is_warmed_up: bool
     ) -> Result<AHashMap<DataSubscription, RollingWindow<BaseDataEnum>>, DataSubscriptionEvent> {
         if new_subscription.base_data_type == BaseDataType::Fundamentals {
             return Err(DataSubscriptionEvent::FailedToSubscribe(new_subscription, "Symbol handler does not handle Fundamental subscriptions".to_string()));
         }
 
+        if new_subscription.base_data_type == BaseDataType::OrderBooks && primary_source.is_some() {
+            return Err(DataSubscriptionEvent::FailedToSubscribe(new_subscription, "Order books can not be consolidated, subscribe without a primary source".to_string()));
+        }
+
+        if new_subscription.base_data_type == BaseDataType::MarketByOrder && primary_source.is_some() {
+            return Err(DataSubscriptionEvent::FailedToSubscribe(new_subscription, "Market by order depth can not be consolidated, subscribe without a primary source".to_string()));
+        }
+
         if let Some(subscription) = self.primary_subscriptions.get(&new_subscription.subscription_resolution_type()) {
             if *subscription.value() == new_subscription {
                 return Err(DataSubscriptionEvent::FailedToSubscribe(new_subscription.clone(), format!("{}: Already subscribed: {}", new_subscription.symbol.data_vendor, new_subscription.symbol.name)))
@@ -641,7 +700,6 @@ impl SymbolSubscriptionHandler {
                 return Err(DataSubscriptionEvent::FailedToSubscribe(new_subscription.clone(), format!("{}: Already subscribed: {}", new_subscription.symbol.data_vendor, new_subscription.symbol.name)))
             }
         }
-        let is_warmed_up = is_warmup_complete();
 
         let mut returned_windows = AHashMap::new();
         if let Some(primary) = primary_source {
@@ -664,7 +722,7 @@ impl SymbolSubscriptionHandler {
                             warm_up_to_time - subtract_duration - Duration::days(5)
                         }
                     };
-                    let data = get_compressed_historical_data(vec![new_primary.clone()], from_time, warm_up_to_time).await.unwrap_or_else(|_e| BTreeMap::new());
+                    let data = get_compressed_historical_data(vec![new_primary.clone()], from_time, warm_up_to_time, &self.data_server_sender).await.unwrap_or_else(|_e| BTreeMap::new());
                     let mut history = RollingWindow::new(history_to_retain);
                     for (_, slice) in data {
                         for data in slice.iter() {
@@ -681,7 +739,7 @@ impl SymbolSubscriptionHandler {
             let consolidator = ConsolidatorEnum::create_consolidator(new_subscription.clone(), fill_forward.clone(), hours).await;
             let (final_consolidator, window) = match is_warmed_up {
                 true => {
-                    let (final_consolidator, window) = ConsolidatorEnum::warmup(consolidator, warm_up_to_time, history_to_retain as i32, strategy_mode).await;
+                    let (final_consolidator, window) = ConsolidatorEnum::warmup(consolidator, warm_up_to_time, history_to_retain as i32, strategy_mode, &self.data_server_sender).await;
                     (final_consolidator, window)
                 },
                 false => (consolidator, RollingWindow::new(history_to_retain))
@@ -710,7 +768,7 @@ impl SymbolSubscriptionHandler {
                             warm_up_to_time - subtract_duration - Duration::days(5)
                         }
                     };
-                    let data = get_compressed_historical_data(vec![new_subscription.clone()], from_time, warm_up_to_time).await.unwrap_or_else(|_e| BTreeMap::new());
+                    let data = get_compressed_historical_data(vec![new_subscription.clone()], from_time, warm_up_to_time, &self.data_server_sender).await.unwrap_or_else(|_e| BTreeMap::new());
                     let mut history = RollingWindow::new(history_to_retain);
                     for (_, slice) in data {
                         for data in slice.iter() {
@@ -5,6 +5,7 @@ use tokio::sync::mpsc::Sender;
 use tokio::sync::RwLock;
 use tokio::task;
 use tokio::time::{interval, sleep, Duration as TokioDuration};
+use crate::strategies::checkpoint::TimedEventCheckpoint;
 use crate::strategies::strategy_events::StrategyEvent;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -110,8 +111,8 @@ impl EventTimeEnum {
 
 #[derive(Clone, Debug)]
 pub struct TimedEvent {
-    name: String,
-    time: EventTimeEnum,
+    pub(crate) name: String,
+    pub(crate) time: EventTimeEnum,
 }
 
 impl TimedEvent {
@@ -138,8 +139,11 @@ impl TimedEventHandler {
         }
     }
 
+    /// Replaces the event with the same name, so events added again by a strategy resuming from a checkpoint keep their last fired time.
     pub async fn add_event(&self, scheduled_event: TimedEvent) {
-        self.schedule.write().await.push(scheduled_event);
+        let mut schedule = self.schedule.write().await;
+        schedule.retain(|event| event.name != scheduled_event.name);
+        schedule.push(scheduled_event);
     }
 
     pub async fn remove_event(&self, name: String) {
@@ -150,6 +154,32 @@ impl TimedEventHandler {
         self.last_fired.write().await.remove(&name);
     }
 
+    /// The scheduled events and the time each last fired, used to save a `StrategyCheckpoint`.
+    pub(crate) async fn checkpoint(&self) -> Vec<TimedEventCheckpoint> {
+        let schedule = self.schedule.read().await;
+        let last_fired = self.last_fired.read().await;
+        schedule.iter()
+            .map(|event| TimedEventCheckpoint::new(event, last_fired.get(&event.name).copied()))
+            .collect()
+    }
+
+    /// Schedules the events of a `StrategyCheckpoint`, events with a time which can not be read are skipped.
+    pub(crate) async fn restore_checkpoint(&self, events: &[TimedEventCheckpoint]) {
+        let mut schedule = self.schedule.write().await;
+        let mut last_fired = self.last_fired.write().await;
+        for checkpoint in events {
+            let time = match checkpoint.time.event_time() {
+                Some(time) => time,
+                None => continue,
+            };
+            schedule.retain(|event| event.name != checkpoint.name);
+            schedule.push(TimedEvent::new(checkpoint.name.clone(), time));
+            if let Some(time) = checkpoint.last_fired.as_ref().and_then(|time| time.parse().ok()) {
+                last_fired.insert(checkpoint.name.clone(), time);
+            }
+        }
+    }
+
     pub async fn run_time_updates(self: Arc<Self>) {
         task::spawn(async move {
             // Wait until the next whole second
@@ -1,22 +1,25 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
 use chrono::{DateTime, Duration as ChronoDuration, NaiveTime, TimeZone, Utc};
-use crate::strategies::client_features::server_connections::{set_warmup_complete};
-use crate::standardized_types::base_data::history::{get_compressed_historical_data};
-use crate::standardized_types::enums::StrategyMode;
+use crate::messages::data_server_messaging::FundForgeError;
+use crate::standardized_types::base_data::history::{get_compressed_historical_data, get_continuous_historical_data, get_roll_schedule, no_data_as_empty};
+use crate::standardized_types::continuous_contracts::{ContinuousContract, RollSchedule};
+use crate::standardized_types::enums::{MarketType, StrategyMode};
 use crate::strategies::strategy_events::StrategyEvent;
 use crate::standardized_types::time_slices::TimeSlice;
 use std::time::Duration;
 use tokio::sync::mpsc::Sender;
-use crate::standardized_types::subscriptions::DataSubscription;
+use crate::standardized_types::subscriptions::{DataSubscription, SymbolName};
 use tokio::sync::{broadcast, mpsc, Notify};
+use crate::strategies::client_features::request_handler::DataServerSender;
 use crate::strategies::handlers::indicator_handler::IndicatorHandler;
 use crate::strategies::handlers::market_handler::backtest_matching_engine::BackTestEngineMessage;
 use crate::strategies::handlers::market_handler::price_service::MarketPriceService;
 use crate::strategies::handlers::subscription_handler::SubscriptionHandler;
 use crate::strategies::handlers::timed_events_handler::TimedEventHandler;
-use crate::strategies::historical_time::update_backtest_time;
+use crate::strategies::historical_time::StrategyClock;
 use crate::strategies::ledgers::ledger_service::LedgerService;
+use crate::strategies::roll_manager::RollManager;
 
 #[allow(dead_code)]
 pub(crate) struct HistoricalEngine {
@@ -35,7 +38,11 @@ pub(crate) struct HistoricalEngine {
     timed_event_handler: Arc<TimedEventHandler>,
     indicator_handler: Arc<IndicatorHandler>,
     subscription_handler: Arc<SubscriptionHandler>,
-    market_price_service: Arc<MarketPriceService>
+    market_price_service: Arc<MarketPriceService>,
+    clock: StrategyClock,
+    continuous_contracts: Vec<ContinuousContract>,
+    roll_manager: Arc<RollManager>,
+    data_server_sender: DataServerSender
 }
 
 // The date 2023-08-19 is in ISO week 33 of the year 2023
@@ -55,7 +62,11 @@ impl HistoricalEngine {
         timed_event_handler: Arc<TimedEventHandler>,
         indicator_handler: Arc<IndicatorHandler>,
         subscription_handler: Arc<SubscriptionHandler>,
-        market_price_service: Arc<MarketPriceService>
+        market_price_service: Arc<MarketPriceService>,
+        clock: StrategyClock,
+        continuous_contracts: Vec<ContinuousContract>,
+        roll_manager: Arc<RollManager>,
+        data_server_sender: DataServerSender
     ) -> Self {
         let rx = subscription_handler.subscribe_primary_subscription_updates();
         let engine = HistoricalEngine {
@@ -74,7 +85,11 @@ impl HistoricalEngine {
             timed_event_handler,
             indicator_handler,
             subscription_handler,
-            market_price_service
+            market_price_service,
+            clock,
+            continuous_contracts,
+            roll_manager,
+            data_server_sender
         };
         engine
     }
@@ -137,6 +152,8 @@ impl HistoricalEngine {
             println!("Historical Engine: Strategy Subscription: {}", subscription);
         }
 
+        // the roll schedules of continuous futures, requested the first time the symbol has data to load
+        let mut roll_schedules: HashMap<SymbolName, RollSchedule> = HashMap::new();
         let mut last_time = warm_up_start_time.clone();
         let mut early_return = false;
         let mut last_date = last_time.date_naive();
@@ -157,7 +174,7 @@ impl HistoricalEngine {
             let to_time = Utc.from_utc_datetime(&end_of_day);
             //eprintln!("Historical Engine: Processing data from {} to {}", last_time, to_time);
 
-            let mut time_slices = match get_compressed_historical_data(primary_subscriptions.clone(), last_time.clone(), to_time).await {
+            let mut time_slices = match self.historical_data(&primary_subscriptions, &mut roll_schedules, warm_up_start_time, last_time, to_time).await {
                 Ok(time_slices) => {
                     if time_slices.is_empty() && !self.tick_over_no_data {
                         //eprintln!("Historical Engine: No data period, weekend or holiday: skipping");
@@ -187,7 +204,7 @@ impl HistoricalEngine {
                     if time >= self.start_time {
                         eprintln!("Historical Engine: Warm up complete: {}", time);
                         warm_up_complete = true;
-                        set_warmup_complete();
+                        self.clock.set_warmup_complete();
                         let event = StrategyEvent::WarmUpComplete;
                         match self.strategy_event_sender.send(event).await {
                             Ok(_) => {}
@@ -258,7 +275,10 @@ impl HistoricalEngine {
                     strategy_time_slice.extend(consolidated_data);
                 }
 
-                update_backtest_time(time);
+                self.clock.update(time);
+                if warm_up_complete {
+                    self.roll_manager.update_time(time).await;
+                }
                 if !strategy_time_slice.is_empty() {
                     // Update indicators and get_requests any generated events.
                     if let Some(events) = self.indicator_handler.update_time_slice(&strategy_time_slice).await {
@@ -276,9 +296,70 @@ impl HistoricalEngine {
                         Err(e) => eprintln!("Historical Engine: Failed to send event: {}", e)
                     }
                 }
+
+                for schedule in roll_schedules.values() {
+                    for roll in schedule.rolls_between(last_time, time) {
+                        match self.strategy_event_sender.send(StrategyEvent::RollEvent(roll.clone())).await {
+                            Ok(_) => {}
+                            Err(e) => eprintln!("Historical Engine: Failed to send event: {}", e)
+                        }
+                    }
+                }
                 self.notified.notified().await;
                 last_time = time.clone();
             }
         }
     }
+
+    /// The primary data for the range, subscriptions to a `ContinuousContract` are stitched from the contracts in the roll schedule of the symbol.
+    async fn historical_data(
+        &self,
+        subscriptions: &[DataSubscription],
+        roll_schedules: &mut HashMap<SymbolName, RollSchedule>,
+        warm_up_start_time: DateTime<Utc>,
+        from_time: DateTime<Utc>,
+        to_time: DateTime<Utc>,
+    ) -> Result<BTreeMap<i64, TimeSlice>, FundForgeError> {
+        let mut contract_subscriptions = vec![];
+        let mut continuous_subscriptions = vec![];
+        for subscription in subscriptions {
+            let continuous_contract = match subscription.market_type {
+                MarketType::Futures(_) => self.continuous_contracts.iter().find(|contract| contract.symbol_name == subscription.symbol.name),
+                _ => None,
+            };
+            match continuous_contract {
+                Some(contract) => continuous_subscriptions.push((subscription.clone(), contract)),
+                None => contract_subscriptions.push(subscription.clone()),
+            }
+        }
+        if continuous_subscriptions.is_empty() {
+            return get_compressed_historical_data(contract_subscriptions, from_time, to_time, &self.data_server_sender).await;
+        }
+
+        let mut time_slices = match contract_subscriptions.is_empty() {
+            true => BTreeMap::new(),
+            false => no_data_as_empty(get_compressed_historical_data(contract_subscriptions, from_time, to_time, &self.data_server_sender).await)?,
+        };
+        for (subscription, contract) in continuous_subscriptions {
+            if !roll_schedules.contains_key(&contract.symbol_name) {
+                let schedule = match get_roll_schedule(subscription.clone(), contract.roll_rule, warm_up_start_time, self.end_time, &self.data_server_sender).await {
+                    Ok(schedule) => schedule,
+                    Err(e) => {
+                        eprintln!("Historical Engine: Failed to get roll schedule for {}, using the data stored for the symbol name without rolls: {}", contract.symbol_name, e);
+                        RollSchedule { symbol_name: contract.symbol_name.clone(), first_symbol_code: contract.symbol_name.clone(), rolls: vec![] }
+                    }
+                };
+                roll_schedules.insert(contract.symbol_name.clone(), schedule);
+            }
+            let schedule = &roll_schedules[&contract.symbol_name];
+            let continuous_data = get_continuous_historical_data(&subscription, schedule, contract.adjustment, from_time, to_time, &self.data_server_sender).await?;
+            for (timestamp, time_slice) in continuous_data {
+                time_slices
+                    .entry(timestamp)
+                    .and_modify(|existing: &mut TimeSlice| existing.merge(time_slice.clone()))
+                    .or_insert(time_slice);
+            }
+        }
+        Ok(time_slices)
+    }
 }
@@ -1,25 +1,47 @@
-use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
 use chrono::{DateTime, Utc, TimeZone};
-use lazy_static::lazy_static;
 
-lazy_static! {
-    static ref ATOMIC_TIMESTAMP_NS: AtomicI64 = AtomicI64::new(0);
+/// The time of one strategy, cloned into each handler that reads or advances it so several strategies can run in one process.
+/// In backtests it is the time of the last data point, in live modes it is updated until the warm up is complete and then the strategy uses `Utc::now()`.
+#[derive(Clone, Debug, Default)]
+pub struct StrategyClock {
+    timestamp_ns: Arc<AtomicI64>,
+    warm_up_complete: Arc<AtomicBool>,
 }
 
-#[inline(always)]
-pub fn update_backtest_time(dt: DateTime<Utc>) {
-    let nanos = dt.timestamp_nanos_opt().unwrap();
-    ATOMIC_TIMESTAMP_NS.store(nanos, Ordering::Release);
-}
+impl StrategyClock {
+    pub fn new(time: DateTime<Utc>) -> Self {
+        let clock = Self::default();
+        clock.update(time);
+        clock
+    }
 
-#[inline(always)]
-pub fn get_backtest_time() -> DateTime<Utc> {
-    let nanos = ATOMIC_TIMESTAMP_NS.load(Ordering::Acquire);
-    Utc.timestamp_nanos(nanos)
-}
+    #[inline(always)]
+    pub fn update(&self, dt: DateTime<Utc>) {
+        let nanos = dt.timestamp_nanos_opt().unwrap();
+        self.timestamp_ns.store(nanos, Ordering::Release);
+    }
+
+    #[inline(always)]
+    pub fn time(&self) -> DateTime<Utc> {
+        let nanos = self.timestamp_ns.load(Ordering::Acquire);
+        Utc.timestamp_nanos(nanos)
+    }
+
+    pub fn advance(&self, duration: chrono::Duration) {
+        self.timestamp_ns.fetch_add(duration.num_nanoseconds().unwrap_or(0), Ordering::AcqRel);
+    }
 
-pub fn advance_engine_time(duration: chrono::Duration) {
-    ATOMIC_TIMESTAMP_NS.fetch_add(duration.num_nanoseconds().unwrap_or(0), Ordering::AcqRel);
+    #[inline(always)]
+    pub(crate) fn set_warmup_complete(&self) {
+        self.warm_up_complete.store(true, Ordering::SeqCst);
+    }
+
+    #[inline(always)]
+    pub fn is_warmup_complete(&self) -> bool {
+        self.warm_up_complete.load(Ordering::SeqCst)
+    }
 }
 
 #[cfg(test)]
@@ -30,27 +52,29 @@ mod tests {
     #[test]
     fn test_update_and_read() {
         let test_time = Utc::now();
-        update_backtest_time(test_time);
-        assert_eq!(get_backtest_time(), test_time);
+        let clock = StrategyClock::default();
+        clock.update(test_time);
+        assert_eq!(clock.time(), test_time);
     }
 
     #[test]
     fn test_advance_time() {
         let initial_time = Utc::now();
-        update_backtest_time(initial_time);
-        advance_engine_time(chrono::Duration::seconds(5));
-        assert_eq!(get_backtest_time(), initial_time + chrono::Duration::seconds(5));
+        let clock = StrategyClock::new(initial_time);
+        clock.advance(chrono::Duration::seconds(5));
+        assert_eq!(clock.time(), initial_time + chrono::Duration::seconds(5));
     }
 
     #[test]
     fn test_concurrent_reads() {
         let test_time = Utc::now();
-        update_backtest_time(test_time);
+        let clock = StrategyClock::new(test_time);
 
         let threads: Vec<_> = (0..5).map(|_| {
+            let clock = clock.clone();
             thread::spawn(move || {
                 for _ in 0..100 {
-                    let read_time = get_backtest_time();
+                    let read_time = clock.time();
                     assert!((read_time - test_time).num_nanoseconds().unwrap().abs() < 1_000_000);
                 }
             })
@@ -60,4 +84,19 @@ mod tests {
             t.join().unwrap();
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_clocks_are_independent() {
+        let start = Utc::now();
+        let clock_1 = StrategyClock::new(start);
+        let clock_2 = StrategyClock::new(start);
+        let handle = clock_1.clone();
+
+        handle.advance(chrono::Duration::minutes(1));
+        handle.set_warmup_complete();
+        assert_eq!(clock_1.time(), start + chrono::Duration::minutes(1));
+        assert!(clock_1.is_warmup_complete());
+        assert_eq!(clock_2.time(), start);
+        assert!(!clock_2.is_warmup_complete());
+    }
+}
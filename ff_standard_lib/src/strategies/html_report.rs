@@ -0,0 +1,445 @@
+use std::collections::BTreeMap;
+use chrono::{Datelike, NaiveDate};
+use rust_decimal::Decimal;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal_macros::dec;
+use crate::standardized_types::position::{Position, Trade};
+use crate::standardized_types::subscriptions::SymbolName;
+use crate::strategies::statistics::PerformanceReport;
+
+const CHART_WIDTH: f64 = 1000.0;
+const CHART_HEIGHT: f64 = 240.0;
+/// The margin left of the charts for the axis labels.
+const CHART_LABEL_WIDTH: f64 = 90.0;
+/// Curves with more points are downsampled so long backtests still produce a small report.
+const MAX_CHART_POINTS: usize = 2000;
+const PNL_DISTRIBUTION_BINS: usize = 20;
+const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+const STYLE: &str = "body{font-family:-apple-system,Segoe UI,Helvetica,Arial,sans-serif;margin:24px;background:#fafafa;color:#222}\
+h1{font-size:22px}h2{font-size:18px;margin-top:32px;border-bottom:1px solid #ddd;padding-bottom:4px}\
+table{border-collapse:collapse;font-size:13px;background:#fff}th,td{border:1px solid #e0e0e0;padding:4px 8px;text-align:right}\
+th{background:#f0f0f0}td.text,th.text{text-align:left}.summary td{min-width:110px}\
+.positive{color:#1a7f37}.negative{color:#c62828}svg{background:#fff;border:1px solid #e0e0e0}\
+.scroll{max-height:600px;overflow-y:auto;display:inline-block}";
+
+impl PerformanceReport {
+    /// Returns the report as a single html page with inline styles and svg charts, it can be opened offline without any external assets.
+    /// `positions` are the closed positions of the ledger, used for the trade list, the pnl distribution and the per symbol breakdown.
+    pub fn to_html(&self, positions: &[Position]) -> String {
+        let mut trades: Vec<(&Position, &Trade)> = positions.iter()
+            .flat_map(|position| position.completed_trades.iter().map(move |trade| (position, trade)))
+            .collect();
+        trades.sort_by(|a, b| a.1.exit_time.cmp(&b.1.exit_time));
+
+        let equity: Vec<f64> = self.equity_curve.iter().filter_map(|point| point.equity.to_f64()).collect();
+        let mut html = String::new();
+        html.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+        html.push_str(&format!("<title>{} Performance Report</title>\n<style>{}</style>\n</head>\n<body>\n", escape_html(&self.account.to_string()), STYLE));
+        html.push_str(&format!("<h1>{} Performance Report</h1>\n", escape_html(&self.account.to_string())));
+        html.push_str(&format!("<p>{} to {}, currency {}</p>\n",
+            escape_html(self.start_time.as_deref().unwrap_or("None")),
+            escape_html(self.end_time.as_deref().unwrap_or("None")),
+            self.currency
+        ));
+
+        html.push_str("<h2>Summary</h2>\n");
+        html.push_str(&self.summary_table());
+        html.push_str("<h2>Equity</h2>\n");
+        html.push_str(&line_chart(&equity, "#1565c0"));
+        html.push_str("<h2>Drawdown %</h2>\n");
+        html.push_str(&line_chart(&drawdown_percent(&equity), "#c62828"));
+        html.push_str("<h2>Monthly Returns %</h2>\n");
+        html.push_str(&self.monthly_returns_table());
+        html.push_str("<h2>Trade PnL Distribution</h2>\n");
+        let net_pnl: Vec<f64> = trades.iter().filter_map(|(_, trade)| net_pnl(trade).to_f64()).collect();
+        html.push_str(&histogram(&net_pnl));
+        html.push_str("<h2>Symbols</h2>\n");
+        html.push_str(&symbol_table(positions));
+        html.push_str("<h2>Trades</h2>\n");
+        html.push_str(&trade_table(&trades));
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+
+    fn summary_table(&self) -> String {
+        let rows = [
+            ("Starting Equity", self.starting_equity.to_string(), "Ending Equity", self.ending_equity.to_string()),
+            ("Net Profit", self.net_profit.to_string(), "Total Return %", self.total_return_percent.to_string()),
+            ("CAGR %", self.cagr_percent.to_string(), "Exposure %", self.exposure_percent.to_string()),
+            ("Sharpe Ratio", self.sharpe_ratio.to_string(), "Sortino Ratio", self.sortino_ratio.to_string()),
+            ("Calmar Ratio", self.calmar_ratio.to_string(), "Max Drawdown", self.max_drawdown.to_string()),
+            ("Max Drawdown %", self.max_drawdown_percent.to_string(), "Max Drawdown Duration", format_seconds(self.max_drawdown_duration_seconds)),
+            ("Trades", self.total_trades.to_string(), "Win Rate %", self.win_rate_percent.to_string()),
+            ("Wins", self.wins.to_string(), "Losses", self.losses.to_string()),
+            ("Average Win", self.average_win.to_string(), "Average Loss", self.average_loss.to_string()),
+            ("Profit Factor", self.profit_factor.to_string(), "Expectancy", self.expectancy.to_string()),
+            ("Average MAE", self.average_mae.to_string(), "Average MFE", self.average_mfe.to_string()),
+            ("Longest Win Streak", self.longest_win_streak.to_string(), "Longest Loss Streak", self.longest_loss_streak.to_string()),
+            ("Commissions", self.commissions_paid.to_string(), "Financing", self.financing.to_string()),
+        ];
+        let mut table = String::from("<table class=\"summary\">\n");
+        for (label_1, value_1, label_2, value_2) in rows {
+            table.push_str(&format!("<tr><th class=\"text\">{}</th><td>{}</td><th class=\"text\">{}</th><td>{}</td></tr>\n", label_1, value_1, label_2, value_2));
+        }
+        table.push_str("</table>\n");
+        table
+    }
+
+    /// Returns the return of each month from the daily closing equity, keyed by year and month index.
+    fn monthly_returns(&self) -> BTreeMap<(i32, usize), Decimal> {
+        let mut closes: BTreeMap<(i32, usize), Decimal> = BTreeMap::new();
+        for daily in &self.daily_returns {
+            if let Ok(date) = NaiveDate::parse_from_str(&daily.date, "%Y-%m-%d") {
+                closes.insert((date.year(), date.month0() as usize), daily.equity);
+            }
+        }
+        let mut previous = self.starting_equity;
+        let mut returns = BTreeMap::new();
+        for (month, equity) in closes {
+            let monthly_return = match previous != dec!(0) {
+                true => (equity - previous) / previous * dec!(100),
+                false => dec!(0)
+            };
+            returns.insert(month, monthly_return.round_dp(2));
+            previous = equity;
+        }
+        returns
+    }
+
+    fn monthly_returns_table(&self) -> String {
+        let returns = self.monthly_returns();
+        if returns.is_empty() {
+            return "<p>No data</p>\n".to_string();
+        }
+        let max_abs = returns.values().map(|r| r.abs()).max().unwrap_or_default();
+        let mut years: BTreeMap<i32, [Option<Decimal>; 12]> = BTreeMap::new();
+        for ((year, month), monthly_return) in &returns {
+            years.entry(*year).or_insert([None; 12])[*month] = Some(*monthly_return);
+        }
+
+        let mut table = String::from("<table>\n<tr><th class=\"text\">Year</th>");
+        for month in MONTHS {
+            table.push_str(&format!("<th>{}</th>", month));
+        }
+        table.push_str("<th>Year</th></tr>\n");
+        for (year, months) in years {
+            table.push_str(&format!("<tr><th class=\"text\">{}</th>", year));
+            let mut compounded = dec!(1);
+            for monthly_return in months {
+                match monthly_return {
+                    Some(monthly_return) => {
+                        compounded *= dec!(1) + monthly_return / dec!(100);
+                        table.push_str(&format!("<td style=\"background:{}\">{}</td>", heat_color(monthly_return, max_abs), monthly_return));
+                    }
+                    None => table.push_str("<td></td>"),
+                }
+            }
+            let yearly = ((compounded - dec!(1)) * dec!(100)).round_dp(2);
+            table.push_str(&format!("<td class=\"{}\"><b>{}</b></td></tr>\n", pnl_class(yearly), yearly));
+        }
+        table.push_str("</table>\n");
+        table
+    }
+}
+
+/// The pnl of a trade after the entry and exit fees.
+fn net_pnl(trade: &Trade) -> Decimal {
+    trade.profit - trade.commission.total()
+}
+
+#[derive(Default)]
+struct SymbolSummary {
+    positions: usize,
+    trades: usize,
+    wins: usize,
+    booked_pnl: Decimal,
+    commissions: Decimal,
+    financing: Decimal,
+    largest_win: Decimal,
+    largest_loss: Decimal,
+    total_mae: Decimal,
+    total_mfe: Decimal,
+}
+
+fn symbol_table(positions: &[Position]) -> String {
+    let mut symbols: BTreeMap<SymbolName, SymbolSummary> = BTreeMap::new();
+    for position in positions {
+        let summary = symbols.entry(position.symbol_name.clone()).or_default();
+        summary.positions += 1;
+        summary.trades += position.completed_trades.len();
+        if position.booked_pnl > dec!(0) {
+            summary.wins += 1;
+        }
+        summary.booked_pnl += position.booked_pnl;
+        summary.commissions += position.completed_trades.iter().map(|trade| trade.commission.total()).sum::<Decimal>();
+        summary.financing += position.financing;
+        summary.largest_win = summary.largest_win.max(position.booked_pnl);
+        summary.largest_loss = summary.largest_loss.min(position.booked_pnl);
+        summary.total_mae += position.excursion.mae_value;
+        summary.total_mfe += position.excursion.mfe_value;
+    }
+    if symbols.is_empty() {
+        return "<p>No closed positions</p>\n".to_string();
+    }
+
+    let mut table = String::from("<table>\n<tr><th class=\"text\">Symbol</th><th>Positions</th><th>Trades</th><th>Win Rate %</th><th>Booked PnL</th><th>Average PnL</th>\
+        <th>Largest Win</th><th>Largest Loss</th><th>Commissions</th><th>Financing</th><th>Average MAE</th><th>Average MFE</th></tr>\n");
+    for (symbol_name, summary) in symbols {
+        let count = Decimal::from(summary.positions);
+        table.push_str(&format!(
+            "<tr><td class=\"text\">{}</td><td>{}</td><td>{}</td><td>{}</td><td class=\"{}\">{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            escape_html(&symbol_name),
+            summary.positions,
+            summary.trades,
+            (Decimal::from(summary.wins) / count * dec!(100)).round_dp(2),
+            pnl_class(summary.booked_pnl),
+            summary.booked_pnl.round_dp(2),
+            (summary.booked_pnl / count).round_dp(2),
+            summary.largest_win.round_dp(2),
+            summary.largest_loss.round_dp(2),
+            summary.commissions.round_dp(2),
+            summary.financing.round_dp(2),
+            (summary.total_mae / count).round_dp(2),
+            (summary.total_mfe / count).round_dp(2),
+        ));
+    }
+    table.push_str("</table>\n");
+    table
+}
+
+fn trade_table(trades: &[(&Position, &Trade)]) -> String {
+    if trades.is_empty() {
+        return "<p>No trades</p>\n".to_string();
+    }
+    let mut table = String::from("<div class=\"scroll\"><table>\n<tr><th>#</th><th class=\"text\">Symbol</th><th class=\"text\">Side</th><th class=\"text\">Entry Time</th><th class=\"text\">Exit Time</th>\
+        <th>Entry Price</th><th>Exit Price</th><th>Quantity</th><th>PnL</th><th>Fees</th><th>Net PnL</th><th>MAE</th><th>MFE</th><th class=\"text\">Result</th><th class=\"text\">Tag</th></tr>\n");
+    for (index, (position, trade)) in trades.iter().enumerate() {
+        let net = net_pnl(trade);
+        table.push_str(&format!(
+            "<tr><td>{}</td><td class=\"text\">{}</td><td class=\"text\">{}</td><td class=\"text\">{}</td><td class=\"text\">{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td class=\"{}\">{}</td><td>{}</td><td>{}</td><td class=\"text\">{}</td><td class=\"text\">{}</td></tr>\n",
+            index + 1,
+            escape_html(&position.symbol_code),
+            position.side,
+            escape_html(&trade.entry_time),
+            escape_html(&trade.exit_time),
+            trade.entry_price,
+            trade.exit_price,
+            trade.exit_quantity,
+            trade.profit.round_dp(2),
+            trade.commission.total().round_dp(2),
+            pnl_class(net),
+            net.round_dp(2),
+            trade.excursion.mae_value.round_dp(2),
+            trade.excursion.mfe_value.round_dp(2),
+            trade.result,
+            escape_html(&position.tag),
+        ));
+    }
+    table.push_str("</table></div>\n");
+    table
+}
+
+/// Returns the percentage below the running peak at each point of the equity curve, as a negative value.
+fn drawdown_percent(equity: &[f64]) -> Vec<f64> {
+    let mut peak = f64::MIN;
+    equity.iter().map(|value| {
+        peak = peak.max(*value);
+        match peak > 0.0 {
+            true => (value - peak) / peak * 100.0,
+            false => 0.0
+        }
+    }).collect()
+}
+
+fn downsample(values: &[f64]) -> Vec<f64> {
+    if values.len() <= MAX_CHART_POINTS {
+        return values.to_vec();
+    }
+    let step = values.len().div_ceil(MAX_CHART_POINTS);
+    let mut sampled: Vec<f64> = values.iter().step_by(step).copied().collect();
+    if !(values.len() - 1).is_multiple_of(step) {
+        sampled.push(values[values.len() - 1]);
+    }
+    sampled
+}
+
+/// Returns the y coordinate of `value` on a chart scaled from `min` to `max`.
+fn scale_y(value: f64, min: f64, max: f64) -> f64 {
+    CHART_HEIGHT - 10.0 - (value - min) / (max - min) * (CHART_HEIGHT - 20.0)
+}
+
+fn value_range(values: &[f64]) -> (f64, f64) {
+    let min = values.iter().cloned().fold(f64::MAX, f64::min);
+    let max = values.iter().cloned().fold(f64::MIN, f64::max);
+    match max > min {
+        true => (min, max),
+        false => (min - 1.0, max + 1.0)
+    }
+}
+
+fn line_chart(values: &[f64], color: &str) -> String {
+    if values.is_empty() {
+        return "<p>No data</p>\n".to_string();
+    }
+    let values = downsample(values);
+    let (min, max) = value_range(&values);
+    let plot_width = CHART_WIDTH - CHART_LABEL_WIDTH;
+    let x_step = match values.len() > 1 {
+        true => plot_width / (values.len() - 1) as f64,
+        false => 0.0
+    };
+    let points: Vec<String> = values.iter().enumerate()
+        .map(|(index, value)| format!("{:.1},{:.1}", CHART_LABEL_WIDTH + index as f64 * x_step, scale_y(*value, min, max)))
+        .collect();
+    format!(
+        "<svg width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\" xmlns=\"http://www.w3.org/2000/svg\">\
+        <text x=\"4\" y=\"16\" font-size=\"11\">{max:.2}</text><text x=\"4\" y=\"{bottom}\" font-size=\"11\">{min:.2}</text>\
+        <line x1=\"{lw}\" y1=\"0\" x2=\"{lw}\" y2=\"{h}\" stroke=\"#ccc\"/>\
+        <polyline fill=\"none\" stroke=\"{color}\" stroke-width=\"1.5\" points=\"{points}\"/></svg>\n",
+        w = CHART_WIDTH,
+        h = CHART_HEIGHT,
+        lw = CHART_LABEL_WIDTH,
+        bottom = CHART_HEIGHT - 6.0,
+        points = points.join(" "),
+    )
+}
+
+fn histogram(values: &[f64]) -> String {
+    if values.is_empty() {
+        return "<p>No trades</p>\n".to_string();
+    }
+    let (min, max) = value_range(values);
+    let bin_width = (max - min) / PNL_DISTRIBUTION_BINS as f64;
+    let mut counts = [0usize; PNL_DISTRIBUTION_BINS];
+    for value in values {
+        let bin = (((value - min) / bin_width) as usize).min(PNL_DISTRIBUTION_BINS - 1);
+        counts[bin] += 1;
+    }
+    let max_count = counts.iter().copied().max().unwrap_or(1).max(1) as f64;
+    let plot_width = CHART_WIDTH - CHART_LABEL_WIDTH;
+    let bar_width = plot_width / PNL_DISTRIBUTION_BINS as f64;
+
+    let mut svg = format!("<svg width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\" xmlns=\"http://www.w3.org/2000/svg\">\
+        <text x=\"4\" y=\"16\" font-size=\"11\">{max_count} trades</text>", w = CHART_WIDTH, h = CHART_HEIGHT + 20.0);
+    for (bin, count) in counts.iter().enumerate() {
+        let bin_start = min + bin as f64 * bin_width;
+        let height = *count as f64 / max_count * (CHART_HEIGHT - 20.0);
+        let color = match bin_start + bin_width / 2.0 < 0.0 {
+            true => "#c62828",
+            false => "#1a7f37"
+        };
+        svg.push_str(&format!(
+            "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"{}\"><title>{:.2} to {:.2}: {} trades</title></rect>",
+            CHART_LABEL_WIDTH + bin as f64 * bar_width + 1.0,
+            CHART_HEIGHT - height,
+            bar_width - 2.0,
+            height,
+            color,
+            bin_start,
+            bin_start + bin_width,
+            count
+        ));
+    }
+    svg.push_str(&format!(
+        "<text x=\"{}\" y=\"{}\" font-size=\"11\">{:.2}</text><text x=\"{}\" y=\"{}\" font-size=\"11\" text-anchor=\"end\">{:.2}</text></svg>\n",
+        CHART_LABEL_WIDTH, CHART_HEIGHT + 16.0, min, CHART_WIDTH - 2.0, CHART_HEIGHT + 16.0, max
+    ));
+    svg
+}
+
+/// Returns a green background for positive returns and a red background for negative returns, stronger for larger returns.
+fn heat_color(value: Decimal, max_abs: Decimal) -> String {
+    let intensity = match max_abs > dec!(0) {
+        true => (value.abs() / max_abs).to_f64().unwrap_or(0.0),
+        false => 0.0
+    };
+    let alpha = 0.1 + intensity * 0.7;
+    match value < dec!(0) {
+        true => format!("rgba(198,40,40,{:.2})", alpha),
+        false => format!("rgba(26,127,55,{:.2})", alpha)
+    }
+}
+
+fn pnl_class(value: Decimal) -> &'static str {
+    match value < dec!(0) {
+        true => "negative",
+        false => "positive"
+    }
+}
+
+fn format_seconds(seconds: i64) -> String {
+    format!("{}d {}h {}m", seconds / 86400, seconds % 86400 / 3600, seconds % 3600 / 60)
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use std::sync::Arc;
+    use chrono::{DateTime, Utc};
+    use crate::apis::rithmic::rithmic_systems::RithmicSystem;
+    use crate::standardized_types::accounts::{Account, Currency};
+    use crate::standardized_types::broker_enum::Brokerage;
+    use crate::standardized_types::enums::{OrderSide, StrategyMode};
+    use crate::strategies::handlers::market_handler::price_service::MarketPriceService;
+    use crate::strategies::client_features::request_handler::DataServerSender;
+    use crate::strategies::ledgers::commissions::CommissionSchedules;
+    use crate::strategies::ledgers::financing::FinancingSchedules;
+    use crate::strategies::ledgers::ledger_service::LedgerService;
+    use crate::strategies::ledgers::margin::MarginSchedules;
+
+    #[test]
+    fn test_drawdown_and_downsample() {
+        let drawdown = drawdown_percent(&[100.0, 110.0, 99.0, 121.0]);
+        assert_eq!(drawdown[0], 0.0);
+        assert!((drawdown[2] + 10.0).abs() < 1e-9);
+        assert_eq!(drawdown[3], 0.0);
+
+        let values: Vec<f64> = (0..5001).map(|value| value as f64).collect();
+        let sampled = downsample(&values);
+        assert!(sampled.len() <= MAX_CHART_POINTS + 1);
+        assert_eq!(sampled.first(), Some(&0.0));
+        assert_eq!(sampled.last(), Some(&5000.0));
+    }
+
+    #[tokio::test]
+    async fn test_report_from_ledger_with_trades() {
+        let account = Account::new(Brokerage::Rithmic(RithmicSystem::RithmicPaperTrading), "Test".to_string());
+        let (sender, _receiver) = tokio::sync::mpsc::channel(100);
+        let ledger_service = LedgerService::new(
+            sender,
+            Arc::new(MarketPriceService::new()),
+            Arc::new(CommissionSchedules::default()),
+            Arc::new(MarginSchedules::default()),
+            Arc::new(FinancingSchedules::default()),
+            DataServerSender::new().0,
+        );
+        ledger_service.init_ledger(&account, StrategyMode::Backtest, false, dec!(100000), Currency::USD).await;
+        let time: DateTime<Utc> = DateTime::from_str("2024-06-03T14:00:00Z").unwrap();
+        for (side, price, order_id) in [(OrderSide::Buy, dec!(5000), "1"), (OrderSide::Sell, dec!(5010), "2")] {
+            let (fill_sender, fill_receiver) = tokio::sync::oneshot::channel();
+            ledger_service.update_or_create_position(&account, "ES".to_string(), "ESM4".to_string(), dec!(1), side, time, price, "Test".to_string(), Some(fill_sender), order_id.to_string()).await;
+            let _ = fill_receiver.await;
+        }
+
+        let html = ledger_service.ledgers.get(&account).unwrap().html_report();
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.ends_with("</html>\n"));
+        assert!(html.contains("<svg"));
+        assert!(html.contains("ESM4"));
+        assert!(!html.contains("<script"));
+    }
+
+    #[test]
+    fn test_escape_html() {
+        assert_eq!(escape_html("<b>\"Long\" & Short</b>"), "&lt;b&gt;&quot;Long&quot; &amp; Short&lt;/b&gt;");
+    }
+}
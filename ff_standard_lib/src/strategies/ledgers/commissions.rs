@@ -0,0 +1,382 @@
+use std::collections::HashMap;
+use std::ops::{Add, Mul, Sub};
+use std::path::Path;
+use chrono::{DateTime, Datelike, Utc};
+use rkyv::{Archive, Deserialize as Deserialize_rkyv, Serialize as Serialize_rkyv};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde_derive::{Deserialize, Serialize};
+use crate::helpers::get_resources;
+use crate::messages::data_server_messaging::FundForgeError;
+use crate::product_maps::rithmic::maps::get_futures_commissions_info;
+use crate::standardized_types::broker_enum::Brokerage;
+use crate::standardized_types::new_types::{Price, Volume};
+use crate::standardized_types::subscriptions::SymbolName;
+
+/// The fees charged for a fill, or for both sides of a `Trade`.
+/// Fixed fees are in the pnl currency of the symbol until they are charged by the ledger, which converts them to the account currency.
+#[derive(Clone, Serialize_rkyv, Deserialize_rkyv, Archive, Debug, PartialEq, Serialize, Deserialize, PartialOrd, Default)]
+#[archive(compare(PartialEq), check_bytes)]
+#[archive_attr(derive(Debug))]
+pub struct CommissionBreakdown {
+    /// The brokerage commission.
+    pub commission: Decimal,
+    pub exchange: Decimal,
+    pub clearing: Decimal,
+    /// National Futures Association and other regulatory fees.
+    pub nfa: Decimal,
+    /// The cost of crossing half the spread, this is already included in the fill price so it is not part of `total()`.
+    pub spread: Decimal,
+}
+
+impl CommissionBreakdown {
+    /// The fees deducted from the account.
+    pub fn total(&self) -> Decimal {
+        self.commission + self.exchange + self.clearing + self.nfa
+    }
+}
+
+impl Add for CommissionBreakdown {
+    type Output = CommissionBreakdown;
+
+    fn add(self, other: CommissionBreakdown) -> CommissionBreakdown {
+        CommissionBreakdown {
+            commission: self.commission + other.commission,
+            exchange: self.exchange + other.exchange,
+            clearing: self.clearing + other.clearing,
+            nfa: self.nfa + other.nfa,
+            spread: self.spread + other.spread,
+        }
+    }
+}
+
+impl Sub for CommissionBreakdown {
+    type Output = CommissionBreakdown;
+
+    fn sub(self, other: CommissionBreakdown) -> CommissionBreakdown {
+        CommissionBreakdown {
+            commission: self.commission - other.commission,
+            exchange: self.exchange - other.exchange,
+            clearing: self.clearing - other.clearing,
+            nfa: self.nfa - other.nfa,
+            spread: self.spread - other.spread,
+        }
+    }
+}
+
+impl Mul<Decimal> for CommissionBreakdown {
+    type Output = CommissionBreakdown;
+
+    fn mul(self, multiplier: Decimal) -> CommissionBreakdown {
+        CommissionBreakdown {
+            commission: self.commission * multiplier,
+            exchange: self.exchange * multiplier,
+            clearing: self.clearing * multiplier,
+            nfa: self.nfa * multiplier,
+            spread: self.spread * multiplier,
+        }
+    }
+}
+
+/// The fill passed to a `CommissionModel` by the ledger.
+#[derive(Clone, Debug)]
+pub struct CommissionContext {
+    pub brokerage: Brokerage,
+    pub symbol_name: SymbolName,
+    pub quantity: Volume,
+    pub price: Price,
+    /// The value of a 1.0 move in price for 1 unit, `value_per_tick / tick_size`.
+    pub value_per_unit: Decimal,
+    /// The ask minus the bid when the fill happened, `None` if the data has no quotes.
+    pub spread: Option<Price>,
+    /// The quantity filled by the account this calendar month before this fill, used for tiered pricing.
+    pub monthly_volume: Volume,
+}
+
+impl CommissionContext {
+    /// The value of the fill in the pnl currency of the symbol.
+    pub fn notional(&self) -> Decimal {
+        self.price * self.quantity * self.value_per_unit
+    }
+}
+
+/// Calculates the fees for each fill in backtests and paper trading.
+pub trait CommissionModel: Send + Sync {
+    fn commission(&self, context: &CommissionContext) -> CommissionBreakdown;
+}
+
+/// A per contract rate which applies until the account has traded `up_to` contracts in the calendar month.
+/// A fill which crosses `up_to` is split, the contracts above it are charged at the next tier's rate.
+/// The last tier should have no `up_to`, contracts beyond the last tier are charged at its rate.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CommissionTier {
+    #[serde(default)]
+    pub up_to: Option<Volume>,
+    pub per_side: Decimal,
+}
+
+/// The fee schedule for a symbol or brokerage, parsed from the `type` key of a table in `commissions.toml`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum CommissionSchedule {
+    /// Futures style fees charged per contract per side, the brokerage commission can be tiered by monthly volume.
+    PerContract {
+        #[serde(default)]
+        tiers: Vec<CommissionTier>,
+        #[serde(default)]
+        exchange: Decimal,
+        #[serde(default)]
+        clearing: Decimal,
+        #[serde(default)]
+        nfa: Decimal,
+    },
+    /// Crypto style fees charged as a percentage of the notional value, `percent = 0.1` charges 0.1%.
+    PercentOfNotional {
+        percent: Decimal,
+        #[serde(default)]
+        minimum: Decimal,
+    },
+    /// No commission, the brokerage is paid through the spread, half the spread is recorded for each fill.
+    Spread,
+}
+
+impl CommissionModel for CommissionSchedule {
+    fn commission(&self, context: &CommissionContext) -> CommissionBreakdown {
+        match self {
+            CommissionSchedule::PerContract { tiers, exchange, clearing, nfa } => {
+                CommissionBreakdown {
+                    commission: tiered_commission(tiers, context.monthly_volume, context.quantity),
+                    exchange: exchange * context.quantity,
+                    clearing: clearing * context.quantity,
+                    nfa: nfa * context.quantity,
+                    spread: dec!(0),
+                }
+            }
+            CommissionSchedule::PercentOfNotional { percent, minimum } => CommissionBreakdown {
+                commission: (context.notional().abs() * percent / dec!(100)).max(*minimum),
+                ..Default::default()
+            },
+            CommissionSchedule::Spread => CommissionBreakdown {
+                spread: context.spread.unwrap_or_default() / dec!(2) * context.quantity * context.value_per_unit,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// Charges each contract of a fill at the rate of the tier the monthly volume is in when the contract is filled.
+fn tiered_commission(tiers: &[CommissionTier], monthly_volume: Volume, quantity: Volume) -> Decimal {
+    let end = monthly_volume + quantity;
+    let mut charged_to = monthly_volume;
+    let mut commission = dec!(0);
+    for tier in tiers {
+        let tier_end = match tier.up_to {
+            Some(up_to) => up_to.min(end),
+            None => end
+        };
+        if tier_end > charged_to {
+            commission += tier.per_side * (tier_end - charged_to);
+            charged_to = tier_end;
+        }
+    }
+    if let Some(last) = tiers.last() {
+        if end > charged_to {
+            commission += last.per_side * (end - charged_to);
+        }
+    }
+    commission
+}
+
+/// The schedules for one brokerage in `commissions.toml`, symbol schedules are used before the default.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct BrokerageCommissions {
+    #[serde(default)]
+    pub default: Option<CommissionSchedule>,
+    #[serde(default)]
+    pub symbols: HashMap<SymbolName, CommissionSchedule>,
+}
+
+/// The commission model used by the ledgers, loaded from `resources/commissions.toml`.
+/// Brokerages are keyed by their display name, eg `"Rithmic Apex"`, or `"Rithmic"` for all Rithmic systems.
+/// Symbols without a schedule fall back to the per contract commission in the futures product maps.
+///
+/// ```toml
+/// [brokerages.Rithmic.symbols.MNQ]
+/// type = "PerContract"
+/// tiers = [{ up_to = 1000, per_side = 0.25 }, { per_side = 0.2 }]
+/// exchange = 0.35
+/// clearing = 0.02
+/// nfa = 0.02
+///
+/// [brokerages.Bitget.default]
+/// type = "PercentOfNotional"
+/// percent = 0.06
+///
+/// [brokerages.Oanda.default]
+/// type = "Spread"
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CommissionSchedules {
+    #[serde(default)]
+    pub brokerages: HashMap<String, BrokerageCommissions>,
+}
+
+impl CommissionSchedules {
+    /// Loads `commissions.toml` from the strategy resources folder, if there is no file only the product map commissions are used.
+    pub fn from_resources() -> Result<Self, FundForgeError> {
+        let path = get_resources().join("commissions.toml");
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        Self::from_file(&path)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, FundForgeError> {
+        let toml_content = std::fs::read_to_string(path)
+            .map_err(|e| FundForgeError::ClientSideErrorDebug(format!("Failed to read {}: {}", path.display(), e)))?;
+        Self::from_toml(&toml_content)
+    }
+
+    pub fn from_toml(toml_content: &str) -> Result<Self, FundForgeError> {
+        toml::from_str(toml_content)
+            .map_err(|e| FundForgeError::ClientSideErrorDebug(format!("Invalid commission schedules: {}", e)))
+    }
+
+    /// Returns the schedule for the symbol, the exact brokerage is checked before the brokerage family.
+    pub fn schedule(&self, brokerage: &Brokerage, symbol_name: &SymbolName) -> Option<&CommissionSchedule> {
+        let configured: Vec<&BrokerageCommissions> = schedule_keys(brokerage).iter().filter_map(|key| self.brokerages.get(key)).collect();
+        configured.iter().find_map(|commissions| commissions.symbols.get(symbol_name))
+            .or_else(|| configured.iter().find_map(|commissions| commissions.default.as_ref()))
+    }
+}
+
+/// The keys a brokerage can be configured under in the resources toml files, the exact brokerage first and then the brokerage family.
+pub(crate) fn schedule_keys(brokerage: &Brokerage) -> Vec<String> {
+    let mut keys = vec![brokerage.to_string()];
+    if let Brokerage::Rithmic(_) = brokerage {
+        keys.push("Rithmic".to_string());
+    }
+    keys
+}
+
+impl CommissionModel for CommissionSchedules {
+    fn commission(&self, context: &CommissionContext) -> CommissionBreakdown {
+        if let Some(schedule) = self.schedule(&context.brokerage, &context.symbol_name) {
+            return schedule.commission(context);
+        }
+        match get_futures_commissions_info(&context.symbol_name) {
+            Ok(commission_info) => CommissionBreakdown {
+                commission: commission_info.per_side * context.quantity,
+                ..Default::default()
+            },
+            Err(_) => CommissionBreakdown::default()
+        }
+    }
+}
+
+/// The quantity filled by an account in the current calendar month, used for tiered commissions.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct MonthlyVolume {
+    month: Option<(i32, u32)>,
+    volume: Volume,
+}
+
+impl MonthlyVolume {
+    /// Returns the volume traded this month before the fill and adds the fill.
+    pub(crate) fn record(&mut self, time: DateTime<Utc>, quantity: Volume) -> Volume {
+        let month = Some((time.year(), time.month()));
+        if self.month != month {
+            self.month = month;
+            self.volume = dec!(0);
+        }
+        let before = self.volume;
+        self.volume += quantity;
+        before
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apis::rithmic::rithmic_systems::RithmicSystem;
+
+    const SCHEDULES: &str = r#"
+        [brokerages.Rithmic.symbols.MNQ]
+        type = "PerContract"
+        tiers = [{ up_to = 1000, per_side = 0.25 }, { per_side = 0.2 }]
+        exchange = 0.35
+        clearing = 0.02
+        nfa = 0.02
+
+        [brokerages.Bitget.default]
+        type = "PercentOfNotional"
+        percent = 0.1
+
+        [brokerages.Oanda.default]
+        type = "Spread"
+    "#;
+
+    fn context(brokerage: Brokerage, symbol_name: &str, monthly_volume: Volume) -> CommissionContext {
+        CommissionContext {
+            brokerage,
+            symbol_name: symbol_name.to_string(),
+            quantity: dec!(2),
+            price: dec!(100),
+            value_per_unit: dec!(1),
+            spread: Some(dec!(0.5)),
+            monthly_volume,
+        }
+    }
+
+    #[test]
+    fn test_tiered_per_contract() {
+        let schedules = CommissionSchedules::from_toml(SCHEDULES).unwrap();
+        let brokerage = Brokerage::Rithmic(RithmicSystem::Apex);
+
+        let breakdown = schedules.commission(&context(brokerage, "MNQ", dec!(0)));
+        assert_eq!(breakdown.commission, dec!(0.5));
+        assert_eq!(breakdown.exchange, dec!(0.7));
+        assert_eq!(breakdown.total(), dec!(1.28));
+
+        let breakdown = schedules.commission(&context(brokerage, "MNQ", dec!(1000)));
+        assert_eq!(breakdown.commission, dec!(0.4));
+
+        // the fill crosses the tier boundary, 1 contract at 0.25 and 1 at 0.2
+        let breakdown = schedules.commission(&context(brokerage, "MNQ", dec!(999)));
+        assert_eq!(breakdown.commission, dec!(0.45));
+
+        // contracts beyond the last tier with a limit are charged at its rate
+        let tiers = vec![CommissionTier { up_to: Some(dec!(10)), per_side: dec!(0.5) }, CommissionTier { up_to: Some(dec!(20)), per_side: dec!(0.4) }];
+        assert_eq!(tiered_commission(&tiers, dec!(8), dec!(15)), dec!(1) + dec!(4) + dec!(1.2));
+    }
+
+    #[test]
+    fn test_percent_of_notional_and_spread() {
+        let schedules = CommissionSchedules::from_toml(SCHEDULES).unwrap();
+
+        let breakdown = schedules.commission(&context(Brokerage::Bitget, "BTCUSDT", dec!(0)));
+        assert_eq!(breakdown.total(), dec!(0.2));
+
+        let breakdown = schedules.commission(&context(Brokerage::Oanda, "EUR-USD", dec!(0)));
+        assert_eq!(breakdown.total(), dec!(0));
+        assert_eq!(breakdown.spread, dec!(0.5));
+    }
+
+    #[test]
+    fn test_falls_back_to_product_maps() {
+        let schedules = CommissionSchedules::default();
+        let per_side = get_futures_commissions_info(&"MNQ".to_string()).unwrap().per_side;
+        let breakdown = schedules.commission(&context(Brokerage::Test, "MNQ", dec!(0)));
+        assert_eq!(breakdown.commission, per_side * dec!(2));
+    }
+
+    #[test]
+    fn test_monthly_volume_resets() {
+        let mut volume = MonthlyVolume::default();
+        let january = DateTime::<Utc>::from_timestamp(1704067200, 0).unwrap();
+        assert_eq!(volume.record(january, dec!(5)), dec!(0));
+        assert_eq!(volume.record(january, dec!(5)), dec!(5));
+        let february = DateTime::<Utc>::from_timestamp(1706745600, 0).unwrap();
+        assert_eq!(volume.record(february, dec!(5)), dec!(0));
+    }
+}
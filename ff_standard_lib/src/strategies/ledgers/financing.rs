@@ -0,0 +1,291 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::str::FromStr;
+use ahash::AHashMap;
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use rkyv::{Archive, Deserialize as Deserialize_rkyv, Serialize as Serialize_rkyv};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde_derive::{Deserialize, Serialize};
+use crate::helpers::get_resources;
+use crate::messages::data_server_messaging::FundForgeError;
+use crate::standardized_types::broker_enum::Brokerage;
+use crate::standardized_types::enums::PositionSide;
+use crate::standardized_types::subscriptions::SymbolName;
+use crate::strategies::ledgers::commissions::schedule_keys;
+
+fn default_days_charged() -> [u32; 7] {
+    [1, 1, 3, 1, 1, 0, 0]
+}
+
+fn default_rollover_time() -> String {
+    "17:00".to_string()
+}
+
+fn default_rollover_time_zone() -> String {
+    "America/New_York".to_string()
+}
+
+/// The annual financing rates for holding a position over the broker rollover, as a fraction of the notional value.
+/// Negative rates are charged to the account and positive rates are credited, eg `long_rate = -0.0345` charges long positions 3.45% a year.
+#[derive(Clone, Serialize_rkyv, Deserialize_rkyv, Archive, PartialEq, Debug, Serialize, Deserialize)]
+#[archive(compare(PartialEq), check_bytes)]
+#[archive_attr(derive(Debug))]
+pub struct FinancingRate {
+    pub long_rate: Decimal,
+    pub short_rate: Decimal,
+    /// The days charged for the rollover on each day of the week in the broker time zone, starting Monday.
+    /// The default charges forex weekends on Wednesday, CFDs usually charge them on Friday, `[1, 1, 1, 1, 3, 0, 0]`.
+    #[serde(default = "default_days_charged")]
+    pub days_charged: [u32; 7],
+}
+
+impl FinancingRate {
+    /// Returns the amount credited to the account for holding `notional` over the rollover, negative if the account is charged.
+    pub fn financing(&self, side: PositionSide, notional: Decimal, days_charged: u32) -> Decimal {
+        let rate = match side {
+            PositionSide::Long => self.long_rate,
+            PositionSide::Short => self.short_rate,
+            PositionSide::Flat => return dec!(0),
+        };
+        notional.abs() * rate * Decimal::from(days_charged) / dec!(365)
+    }
+}
+
+/// The financing rate for a symbol, sent by the data server in `DataServerResponse::FinancingRates`.
+#[derive(Clone, Serialize_rkyv, Deserialize_rkyv, Archive, PartialEq, Debug, Serialize, Deserialize)]
+#[archive(compare(PartialEq), check_bytes)]
+#[archive_attr(derive(Debug))]
+pub struct SymbolFinancingRate {
+    pub symbol_name: SymbolName,
+    pub rate: FinancingRate,
+}
+
+/// The rollover and rates for one brokerage in `financing.toml`, symbol rates are used before the rates stored by the data server and the default.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BrokerageFinancing {
+    /// The local time positions are charged each day, `"17:00"`.
+    #[serde(default = "default_rollover_time")]
+    pub rollover_time: String,
+    #[serde(default = "default_rollover_time_zone")]
+    pub time_zone: String,
+    #[serde(default)]
+    pub default: Option<FinancingRate>,
+    #[serde(default)]
+    pub symbols: HashMap<SymbolName, FinancingRate>,
+}
+
+impl Default for BrokerageFinancing {
+    fn default() -> Self {
+        Self {
+            rollover_time: default_rollover_time(),
+            time_zone: default_rollover_time_zone(),
+            default: None,
+            symbols: Default::default(),
+        }
+    }
+}
+
+impl BrokerageFinancing {
+    /// Returns the most recent rollover at or before `time`.
+    pub fn last_rollover(&self, time: DateTime<Utc>) -> Result<DateTime<Utc>, FundForgeError> {
+        let (rollover_time, time_zone) = self.parse()?;
+        let local_date = time.with_timezone(&time_zone).date_naive();
+        let rollover = rollover_on(local_date, rollover_time, &time_zone);
+        match rollover > time {
+            true => Ok(rollover_on(local_date - Duration::days(1), rollover_time, &time_zone)),
+            false => Ok(rollover)
+        }
+    }
+
+    /// Returns the rollovers after `from` up to and including `to`, with the days charged index of the day of the week in the broker time zone.
+    pub fn rollovers_between(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Vec<(DateTime<Utc>, usize)>, FundForgeError> {
+        let (rollover_time, time_zone) = self.parse()?;
+        let mut rollovers = vec![];
+        let mut date = from.with_timezone(&time_zone).date_naive();
+        loop {
+            let rollover = rollover_on(date, rollover_time, &time_zone);
+            if rollover > to {
+                break;
+            }
+            if rollover > from {
+                rollovers.push((rollover, date.weekday().num_days_from_monday() as usize));
+            }
+            date += Duration::days(1);
+        }
+        Ok(rollovers)
+    }
+
+    fn parse(&self) -> Result<(NaiveTime, Tz), FundForgeError> {
+        let rollover_time = NaiveTime::parse_from_str(&self.rollover_time, "%H:%M")
+            .map_err(|e| FundForgeError::ClientSideErrorDebug(format!("Invalid rollover time {}: {}", self.rollover_time, e)))?;
+        let time_zone = Tz::from_str(&self.time_zone)
+            .map_err(|e| FundForgeError::ClientSideErrorDebug(format!("Invalid rollover time zone {}: {}", self.time_zone, e)))?;
+        Ok((rollover_time, time_zone))
+    }
+}
+
+fn rollover_on(date: NaiveDate, rollover_time: NaiveTime, time_zone: &Tz) -> DateTime<Utc> {
+    let local = date.and_time(rollover_time);
+    match time_zone.from_local_datetime(&local).earliest() {
+        Some(time) => time.with_timezone(&Utc),
+        // the rollover is inside a daylight saving gap
+        None => time_zone.from_utc_datetime(&local).with_timezone(&Utc),
+    }
+}
+
+/// The financing rates used by the backtest and paper ledgers, loaded from `resources/financing.toml`.
+/// Brokerages without a table use a 17:00 New York rollover and the rates stored by the data server, Oanda serves the instrument financing rates.
+///
+/// ```toml
+/// [brokerages.Oanda]
+/// rollover_time = "17:00"
+/// time_zone = "America/New_York"
+///
+/// [brokerages.Oanda.symbols.EUR-USD]
+/// long_rate = -0.0412
+/// short_rate = 0.0089
+///
+/// [brokerages.Oanda.symbols.SPX500-USD]
+/// long_rate = -0.0745
+/// short_rate = 0.0145
+/// days_charged = [1, 1, 1, 1, 3, 0, 0]
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct FinancingSchedules {
+    #[serde(default)]
+    pub brokerages: HashMap<String, BrokerageFinancing>,
+}
+
+impl FinancingSchedules {
+    /// Loads `financing.toml` from the strategy resources folder, if there is no file only the rates stored by the data server are used.
+    pub fn from_resources() -> Result<Self, FundForgeError> {
+        let path = get_resources().join("financing.toml");
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        Self::from_file(&path)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, FundForgeError> {
+        let toml_content = std::fs::read_to_string(path)
+            .map_err(|e| FundForgeError::ClientSideErrorDebug(format!("Failed to read {}: {}", path.display(), e)))?;
+        Self::from_toml(&toml_content)
+    }
+
+    /// Parses the schedules, an invalid rollover time or time zone is an error so it is reported before the ledgers start.
+    pub fn from_toml(toml_content: &str) -> Result<Self, FundForgeError> {
+        let schedules: Self = toml::from_str(toml_content)
+            .map_err(|e| FundForgeError::ClientSideErrorDebug(format!("Invalid financing schedules: {}", e)))?;
+        for (brokerage, financing) in &schedules.brokerages {
+            financing.parse()
+                .map_err(|e| FundForgeError::ClientSideErrorDebug(format!("Invalid financing schedules for {}: {}", brokerage, e)))?;
+        }
+        Ok(schedules)
+    }
+
+    /// Returns the table for the brokerage, or the default 17:00 New York rollover without rates.
+    pub fn brokerage(&self, brokerage: &Brokerage) -> BrokerageFinancing {
+        schedule_keys(brokerage).iter()
+            .find_map(|key| self.brokerages.get(key))
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+/// Tracks the rollovers already charged by a ledger and caches the rates stored by the data server.
+#[derive(Debug)]
+pub(crate) struct FinancingLedger {
+    pub(crate) brokerage_financing: BrokerageFinancing,
+    /// `None` until the rates are requested from the data server at the first rollover.
+    pub(crate) server_rates: Option<AHashMap<SymbolName, FinancingRate>>,
+    last_rollover: Option<DateTime<Utc>>,
+}
+
+impl FinancingLedger {
+    pub(crate) fn new(schedules: &FinancingSchedules, brokerage: &Brokerage) -> Self {
+        Self {
+            brokerage_financing: schedules.brokerage(brokerage),
+            server_rates: None,
+            last_rollover: None,
+        }
+    }
+
+    /// Returns the rollovers which have passed since the last update, the first update only records the last rollover.
+    pub(crate) fn update(&mut self, time: DateTime<Utc>) -> Result<Vec<(DateTime<Utc>, usize)>, FundForgeError> {
+        let last_rollover = self.brokerage_financing.last_rollover(time)?;
+        let previous = match self.last_rollover.replace(last_rollover) {
+            Some(previous) => previous,
+            None => return Ok(vec![])
+        };
+        if last_rollover <= previous {
+            self.last_rollover = Some(previous);
+            return Ok(vec![])
+        }
+        self.brokerage_financing.rollovers_between(previous, last_rollover)
+    }
+
+    pub(crate) fn rate(&self, symbol_name: &SymbolName) -> Option<&FinancingRate> {
+        self.brokerage_financing.symbols.get(symbol_name)
+            .or_else(|| self.server_rates.as_ref().and_then(|rates| rates.get(symbol_name)))
+            .or(self.brokerage_financing.default.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rollovers_between() {
+        let financing = BrokerageFinancing::default();
+        // Tuesday 2024-10-01 16:00 New York
+        let from = Utc.with_ymd_and_hms(2024, 10, 1, 20, 0, 0).unwrap();
+        assert_eq!(financing.last_rollover(from).unwrap(), Utc.with_ymd_and_hms(2024, 9, 30, 21, 0, 0).unwrap());
+
+        // Thursday 2024-10-03 18:00 New York
+        let to = Utc.with_ymd_and_hms(2024, 10, 3, 22, 0, 0).unwrap();
+        let rollovers = financing.rollovers_between(financing.last_rollover(from).unwrap(), to).unwrap();
+        assert_eq!(rollovers, vec![
+            (Utc.with_ymd_and_hms(2024, 10, 1, 21, 0, 0).unwrap(), 1),
+            (Utc.with_ymd_and_hms(2024, 10, 2, 21, 0, 0).unwrap(), 2),
+            (Utc.with_ymd_and_hms(2024, 10, 3, 21, 0, 0).unwrap(), 3),
+        ]);
+    }
+
+    #[test]
+    fn test_financing_rates() {
+        let schedules = FinancingSchedules::from_toml(r#"
+            [brokerages.Oanda.symbols.EUR-USD]
+            long_rate = -0.0365
+            short_rate = 0.01
+        "#).unwrap();
+        let mut ledger = FinancingLedger::new(&schedules, &Brokerage::Oanda);
+        let rate = ledger.rate(&"EUR-USD".to_string()).unwrap().clone();
+        assert_eq!(rate.days_charged[2], 3);
+        assert_eq!(rate.financing(PositionSide::Long, dec!(100000), 1), dec!(-10));
+        assert_eq!(rate.financing(PositionSide::Short, dec!(100000), 3), dec!(100000) * dec!(0.03) / dec!(365));
+
+        assert_eq!(ledger.update(Utc.with_ymd_and_hms(2024, 10, 1, 20, 0, 0).unwrap()).unwrap(), vec![]);
+        assert_eq!(ledger.update(Utc.with_ymd_and_hms(2024, 10, 1, 20, 30, 0).unwrap()).unwrap(), vec![]);
+        assert_eq!(ledger.update(Utc.with_ymd_and_hms(2024, 10, 1, 21, 0, 0).unwrap()).unwrap().len(), 1);
+        assert_eq!(ledger.rate(&"GBP-USD".to_string()), None);
+    }
+
+    #[test]
+    fn test_invalid_rollover_is_an_error() {
+        let invalid = FinancingSchedules::from_toml(r#"
+            [brokerages.Oanda]
+            rollover_time = "5pm"
+        "#);
+        assert!(invalid.is_err());
+
+        let mut ledger = FinancingLedger {
+            brokerage_financing: BrokerageFinancing { time_zone: "Not/AZone".to_string(), ..Default::default() },
+            server_rates: None,
+            last_rollover: None,
+        };
+        assert!(ledger.update(Utc.with_ymd_and_hms(2024, 10, 1, 20, 0, 0).unwrap()).is_err());
+    }
+}
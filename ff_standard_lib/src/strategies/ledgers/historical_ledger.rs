@@ -1,27 +1,42 @@
+use std::str::FromStr;
 use crate::strategies::ledgers::ledger::Ledger;
 use chrono::{DateTime, Utc};
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
 use crate::messages::data_server_messaging::FundForgeError;
-use crate::product_maps::rithmic::maps::get_futures_commissions_info;
-use crate::standardized_types::accounts::{Currency};
+use crate::standardized_types::broker_enum::Brokerage;
 use crate::standardized_types::enums::{OrderSide, PositionSide, StrategyMode};
 use crate::standardized_types::new_types::{Price, Volume};
-use crate::standardized_types::orders::{OrderId, OrderUpdateEvent};
+use crate::standardized_types::orders::{Order, OrderError, OrderId, OrderType, OrderUpdateEvent};
 use crate::standardized_types::position::{Position, PositionUpdateEvent};
 use crate::standardized_types::subscriptions::{SymbolCode, SymbolName};
+use crate::standardized_types::symbol_info::SymbolInfo;
 use crate::strategies::client_features::other_requests::get_exchange_rate;
+use crate::strategies::ledgers::commissions::{CommissionBreakdown, CommissionContext};
+use crate::strategies::ledgers::margin::is_overnight;
 use crate::strategies::strategy_events::StrategyEvent;
 
 impl Ledger {
-    pub(crate) async fn charge_commission(&mut self, symbol_name: &SymbolName, contracts: Volume, exchange_rate: Decimal) {
-        //todo, this fn get_futures_commissions_info() will need to be a more dynamic / generic function to cover all brokerages, we also need to use
-        // commission info to get the exchange rate, for example pnl currency will not be exchange rate currency
-        if let Ok(commission_info) = get_futures_commissions_info(&symbol_name) {
-            let commission = contracts * commission_info.per_side * exchange_rate;
-            self.cash_available -= commission;
-            self.commissions_paid += commission;
-        }
+    /// Charges the fees for a fill to the account and returns them in the account currency, so they can be recorded on the position.
+    pub(crate) fn charge_commission(&mut self, symbol_code: &SymbolCode, symbol_info: &SymbolInfo, quantity: Volume, price: Price, time: DateTime<Utc>, exchange_rate: Decimal) -> CommissionBreakdown {
+        let symbol_name = &symbol_info.symbol_name;
+        let spread = match (self.market_price_service.get_market_price(OrderSide::Buy, symbol_name, symbol_code), self.market_price_service.get_market_price(OrderSide::Sell, symbol_name, symbol_code)) {
+            (Some(ask), Some(bid)) if ask > bid => Some(ask - bid),
+            _ => None
+        };
+        let context = CommissionContext {
+            brokerage: self.account.brokerage,
+            symbol_name: symbol_name.clone(),
+            quantity,
+            price,
+            value_per_unit: symbol_info.value_per_tick / symbol_info.tick_size,
+            spread,
+            monthly_volume: self.monthly_volume.record(time, quantity),
+        };
+        let commission = self.commission_model.commission(&context) * exchange_rate;
+        self.cash_available -= commission.total();
+        self.commissions_paid += commission.total();
+        commission
     }
 
     pub(crate) async fn release_margin_used(&mut self, symbol_code: &SymbolCode) {
@@ -34,34 +49,34 @@ impl Ledger {
         }
     }
 
-    pub(crate) async fn commit_margin(&mut self, symbol_name: &SymbolName, symbol_code: &SymbolCode, quantity: Volume, market_price: Price, time: DateTime<Utc>, side: OrderSide, base_currency: Option<Currency>, position_currency: Currency) -> Result<(), FundForgeError> {
-        //eprintln!("commit_margin: {}", symbol_code);
+    /// Returns the initial margin for a position of `quantity` in the account currency.
+    pub(crate) async fn initial_margin(&mut self, symbol_info: &SymbolInfo, quantity: Volume, market_price: Price, time: DateTime<Utc>, side: OrderSide) -> Result<Decimal, FundForgeError> {
+        let symbol_name = &symbol_info.symbol_name;
+        let position_currency = symbol_info.pnl_currency;
         let rate = if position_currency == self.currency {
             dec!(1)
         } else {
             match self.rates.get(&position_currency) {
                 Some(rate) => rate.value().clone(),
                 None => {
-                    let rate = get_exchange_rate(position_currency, self.currency, time, side).await.unwrap_or_else(|_e| dec!(1));
+                    let rate = get_exchange_rate(position_currency, self.currency, time, side, &self.data_server_sender).await.unwrap_or_else(|_e| dec!(1));
                     self.rates.insert(position_currency, rate);
                     rate
                 }
             }
         };
-
-        let margin = self.account.brokerage.intraday_margin_required(symbol_name, quantity, market_price, self.currency, base_currency, position_currency, rate).await?
-            .unwrap_or_else(|| quantity * market_price * rate);
-
-        // Check available cash first
-        if  self.cash_available < margin {
-            return Err(FundForgeError::ClientSideErrorDebug(format!(
-                "Insufficient funds: Required {}, Available {}",
-                margin,
-                self.cash_available
-            )));
+        let notional = quantity * market_price * symbol_info.value_per_tick / symbol_info.tick_size;
+        match self.margin_schedules.schedule(&self.account.brokerage, symbol_name) {
+            Some(schedule) => Ok(schedule.requirement(quantity, notional, rate, is_overnight(symbol_name, time)).initial),
+            // symbols without a schedule are charged the brokerage intraday margin
+            None => {
+                let margin = self.account.brokerage.intraday_margin_required(symbol_name, quantity, market_price, self.currency, symbol_info.base_currency, symbol_info.pnl_currency, rate).await?;
+                Ok(margin.unwrap_or_else(|| quantity * market_price * rate))
+            }
         }
+    }
 
-        // Update margin tracking before updating cash
+    fn reserve_margin(&mut self, symbol_code: &SymbolCode, margin: Decimal) {
         // Add to existing margin instead of replacing
         let total_margin = if let Some(existing_margin) = self.margin_used.get(symbol_code) {
             margin + existing_margin.value()
@@ -71,11 +86,154 @@ impl Ledger {
         self.margin_used.insert(symbol_code.clone(), total_margin);
         self.cash_used += margin;
         self.cash_available -= margin;
-        //println!("Margin Used: {}", margin_used);
+    }
+
+    pub(crate) async fn commit_margin(&mut self, symbol_code: &SymbolCode, symbol_info: &SymbolInfo, quantity: Volume, market_price: Price, time: DateTime<Utc>, side: OrderSide) -> Result<(), FundForgeError> {
+        let margin = self.initial_margin(symbol_info, quantity, market_price, time, side).await?;
+
+        // Check available cash first
+        if  self.cash_available < margin {
+            return Err(FundForgeError::ClientSideErrorDebug(format!(
+                "{:?}: Margin Required {}, Available {}",
+                OrderError::InsufficientFunds,
+                margin.round_dp(2),
+                self.cash_available.round_dp(2)
+            )));
+        }
+        self.reserve_margin(symbol_code, margin);
+        Ok(())
+    }
+
+    /// Returns the reason an order would be rejected for insufficient margin, checked by the backtest matching engine before the order is accepted.
+    /// Only the quantity which increases the exposure of the account is checked, orders reducing or reversing a position are allowed to use the margin they release.
+    pub(crate) fn margin_rejection_reason(&self, order: &Order, market_price: Price, time: DateTime<Utc>) -> Option<String> {
+        let (order_side, position_side) = match order.side {
+            OrderSide::Buy => (OrderSide::Buy, PositionSide::Short),
+            OrderSide::Sell => (OrderSide::Sell, PositionSide::Long),
+        };
+        let mut quantity = order.quantity_open;
+        let mut symbol_info = self.symbol_info.get(&order.symbol_name).map(|info| info.value().clone());
+        if let Some(position) = self.positions.get(&order.symbol_code) {
+            if position.side == position_side {
+                quantity -= position.quantity_open;
+            }
+            symbol_info = Some(position.symbol_info.clone());
+        }
+        if quantity <= dec!(0) {
+            return None;
+        }
+        let schedule = self.margin_schedules.schedule(&self.account.brokerage, &order.symbol_name)?;
+        let price = match order.order_type {
+            OrderType::Limit | OrderType::StopLimit => order.limit_price.unwrap_or(market_price),
+            OrderType::StopMarket | OrderType::MarketIfTouched => order.trigger_price.unwrap_or(market_price),
+            _ => market_price
+        };
+        let (value_per_unit, rate) = match &symbol_info {
+            Some(info) => (info.value_per_tick / info.tick_size, self.get_exchange_multiplier(info.pnl_currency)),
+            None => (dec!(1), dec!(1))
+        };
+        let margin = schedule.requirement(quantity, quantity * price * value_per_unit, rate, is_overnight(&order.symbol_name, time)).initial;
+        match margin > self.cash_available {
+            true => Some(format!("{:?}: {} Margin Required {}, Available {}", OrderError::InsufficientFunds, order_side, margin.round_dp(2), self.cash_available.round_dp(2))),
+            false => None
+        }
+    }
 
+    /// Charges or credits overnight financing to the open positions for each broker rollover since the last time slice.
+    /// The financing is booked to the position and the account, positions opened after the rollover are not charged.
+    pub(crate) async fn charge_financing(&mut self, time: DateTime<Utc>) -> Result<(), FundForgeError> {
+        let rollovers = self.financing.update(time)?;
+        if rollovers.is_empty() || self.positions.is_empty() {
+            return Ok(());
+        }
+        if self.financing.server_rates.is_none() {
+            let rates = match self.account.brokerage {
+                Brokerage::Test => vec![],
+                brokerage => brokerage.financing_rates(&self.data_server_sender).await.unwrap_or_default()
+            };
+            self.financing.server_rates = Some(rates.into_iter().map(|rate| (rate.symbol_name, rate.rate)).collect());
+        }
+        for (rollover, day) in rollovers {
+            for mut position in self.positions.iter_mut() {
+                let rate = match self.financing.rate(&position.symbol_name) {
+                    Some(rate) => rate,
+                    None => continue
+                };
+                let days_charged = rate.days_charged[day];
+                if days_charged == 0 || position.is_closed {
+                    continue;
+                }
+                if let Ok(open_time) = DateTime::<Utc>::from_str(&position.open_time) {
+                    if open_time > rollover {
+                        continue;
+                    }
+                }
+                let exit_side = match position.side {
+                    PositionSide::Long => OrderSide::Sell,
+                    PositionSide::Short => OrderSide::Buy,
+                    PositionSide::Flat => continue
+                };
+                let price = self.market_price_service.get_market_price(exit_side, &position.symbol_name, &position.symbol_code)
+                    .unwrap_or(position.average_price);
+                let notional = position.quantity_open * price * position.symbol_info.value_per_tick / position.symbol_info.tick_size;
+                let exchange_rate = match position.symbol_info.pnl_currency == self.currency {
+                    true => dec!(1),
+                    false => self.get_exchange_multiplier(position.symbol_info.pnl_currency)
+                };
+                let financing = rate.financing(position.side, notional, days_charged) * exchange_rate;
+                position.financing += financing;
+                position.booked_pnl += financing;
+                self.symbol_closed_pnl
+                    .entry(position.symbol_name.clone())
+                    .and_modify(|pnl| *pnl += financing)
+                    .or_insert(financing);
+                self.total_booked_pnl += financing;
+                self.total_financing += financing;
+                self.cash_available += financing;
+            }
+        }
+        self.cash_value = self.cash_used + self.cash_available;
         Ok(())
     }
 
+    /// Flattens the account when the equity falls below the maintenance margin of the open positions, like a broker margin closeout.
+    pub(crate) async fn check_maintenance_margin(&mut self, time: DateTime<Utc>) {
+        if self.positions.is_empty() {
+            return;
+        }
+        let mut maintenance_margin = dec!(0);
+        let mut open_pnl = dec!(0);
+        for position in self.positions.iter() {
+            open_pnl += position.open_pnl;
+            let schedule = match self.margin_schedules.schedule(&self.account.brokerage, &position.symbol_name) {
+                Some(schedule) => schedule,
+                None => continue
+            };
+            let notional = position.quantity_open * position.average_price * position.symbol_info.value_per_tick / position.symbol_info.tick_size;
+            let rate = match position.symbol_info.pnl_currency == self.currency {
+                true => dec!(1),
+                false => self.get_exchange_multiplier(position.symbol_info.pnl_currency)
+            };
+            maintenance_margin += schedule.requirement(position.quantity_open, notional, rate, is_overnight(&position.symbol_name, time)).maintenance;
+        }
+        let equity = self.cash_value + open_pnl;
+        if equity >= maintenance_margin {
+            return;
+        }
+        let event = StrategyEvent::MarginCall {
+            account: self.account.clone(),
+            equity,
+            maintenance_margin,
+            time: time.to_string(),
+        };
+        match self.strategy_sender.send(event).await {
+            Ok(_) => {}
+            Err(e) => eprintln!("Error sending margin call event: {}", e)
+        }
+        const MARGIN_CALL_ID_TAG: &str = "Margin Call";
+        self.flatten_paper_positions(time, MARGIN_CALL_ID_TAG).await;
+    }
+
     pub(crate) async fn paper_exit_position(
         &mut self,
         order_id: OrderId,
@@ -94,7 +252,7 @@ impl Ledger {
                     PositionSide::Short => OrderSide::Sell,
                     _ => unreachable!("This shouldn't happen")
                 };
-                match get_exchange_rate(self.currency, existing_position.symbol_info.pnl_currency, time, side).await {
+                match get_exchange_rate(self.currency, existing_position.symbol_info.pnl_currency, time, side, &self.data_server_sender).await {
                     Ok(rate) => {
                         self.rates.insert(existing_position.symbol_info.pnl_currency, rate);
                         rate
@@ -104,8 +262,10 @@ impl Ledger {
             } else {
                 dec!(1.0)
             };
-            self.charge_commission(&symbol_name, existing_position.quantity_open, exchange_rate).await;
+            let commission = self.charge_commission(symbol_code, &existing_position.symbol_info.clone(), existing_position.quantity_open, market_price, time, exchange_rate);
+            let trades_before = existing_position.completed_trades.len();
             let event = existing_position.reduce_position_size(market_price, existing_position.quantity_open, order_id, self.currency, exchange_rate, time, tag).await;
+            existing_position.record_exit_commission(existing_position.completed_trades.len() - trades_before, commission);
             match &event {
                 PositionUpdateEvent::PositionClosed { booked_pnl, .. } => {
                     // TODO[Strategy]: Add option to mirror account position or use internal position curating.
@@ -161,7 +321,7 @@ impl Ledger {
             if is_reducing {
                 remaining_quantity -= existing_position.quantity_open;
                 let exchange_rate = if self.currency != existing_position.symbol_info.pnl_currency {
-                    match get_exchange_rate(self.currency, existing_position.symbol_info.pnl_currency, time, side).await {
+                    match get_exchange_rate(self.currency, existing_position.symbol_info.pnl_currency, time, side, &self.data_server_sender).await {
                         Ok(rate) => {
                             self.rates.insert(existing_position.symbol_info.pnl_currency, rate);
                             rate
@@ -171,15 +331,22 @@ impl Ledger {
                 } else {
                     dec!(1.0)
                 };
-                self.charge_commission(&symbol_name, existing_position.quantity_open, exchange_rate).await;
-                let event = existing_position.reduce_position_size(market_fill_price, quantity, order_id.clone(), self.currency,exchange_rate, time, tag.clone()).await;
+                let exit_quantity = quantity.min(existing_position.quantity_open);
+                let commission = self.charge_commission(&symbol_code, &existing_position.symbol_info.clone(), exit_quantity, market_fill_price, time, exchange_rate);
+                let trades_before = existing_position.completed_trades.len();
+                let event = existing_position.reduce_position_size(market_fill_price, exit_quantity, order_id.clone(), self.currency,exchange_rate, time, tag.clone()).await;
+                existing_position.record_exit_commission(existing_position.completed_trades.len() - trades_before, commission);
 
                // eprintln!("symbol_code: {}, existing_position: {:?}", symbol_code, existing_position);
 
                 match &event {
                     PositionUpdateEvent::PositionReduced { booked_pnl, .. } => {
                         self.release_margin_used(&symbol_code).await;
-                        self.commit_margin(&symbol_name, &symbol_code, existing_position.quantity_open, existing_position.average_price, time, side, existing_position.symbol_info.base_currency, existing_position.symbol_info.pnl_currency).await.unwrap();
+                        // the remaining position keeps its margin even if it is now more than the cash available
+                        // the margin was committed when the position was opened, so it can only fail if the product maps changed since
+                        if let Ok(margin) = self.initial_margin(&existing_position.symbol_info.clone(), existing_position.quantity_open, existing_position.average_price, time, side).await {
+                            self.reserve_margin(&symbol_code, margin);
+                        }
                         self.positions.insert(symbol_code.clone(), existing_position);
 
                         self.symbol_closed_pnl
@@ -219,7 +386,7 @@ impl Ledger {
 
                 position_events.push(event);
             } else {
-                match self.commit_margin(&symbol_name, &symbol_code, quantity, market_fill_price, time, side, existing_position.symbol_info.base_currency, existing_position.symbol_info.pnl_currency).await {
+                match self.commit_margin(&symbol_code, &existing_position.symbol_info.clone(), quantity, market_fill_price, time, side).await {
                     Ok(_) => {}
                     Err(e) => {
                         //todo this now gets added directly to buffer
@@ -237,6 +404,8 @@ impl Ledger {
                     }
                 }
                 let event = existing_position.add_to_position(self.mode, self.is_simulating_pnl, order_id.clone(), self.currency, market_fill_price, quantity, time, tag.clone()).await;
+                let commission = self.charge_commission(&symbol_code, &existing_position.symbol_info.clone(), quantity, market_fill_price, time, existing_position.exchange_rate_multiplier);
+                existing_position.record_entry_commission(commission);
                 self.positions.insert(symbol_code.clone(), existing_position);
 
                 self.cash_value = self.cash_used + self.cash_available;
@@ -247,7 +416,7 @@ impl Ledger {
         }
         if remaining_quantity > dec!(0.0) {
             let info = self.symbol_info(self.account.brokerage, &symbol_name).await;
-            match self.commit_margin(&symbol_name, &symbol_code, quantity, market_fill_price, time, side, info.base_currency, info.pnl_currency).await {
+            match self.commit_margin(&symbol_code, &info, quantity, market_fill_price, time, side).await {
                 Ok(_) => {}
                 Err(e) => {
                    let event = OrderUpdateEvent::OrderRejected {
@@ -272,7 +441,7 @@ impl Ledger {
 
 
             let exchange_rate = if self.currency != info.pnl_currency {
-                match get_exchange_rate(self.currency, info.pnl_currency, time, side).await {
+                match get_exchange_rate(self.currency, info.pnl_currency, time, side, &self.data_server_sender).await {
                     Ok(rate) => {
                         self.rates.insert(info.pnl_currency, rate);
                         rate
@@ -283,8 +452,7 @@ impl Ledger {
                 dec!(1.0)
             };
             //eprintln!("symbol_code: {}, exchange_rate: {}, {}, {}", symbol_code, exchange_rate, self.currency, info.pnl_currency);
-            //todo, we only need to do this for certain brokerages, I will need a better pattern..
-            self.charge_commission(&symbol_name, remaining_quantity, exchange_rate).await;
+            let commission = self.charge_commission(&symbol_code, &info, remaining_quantity, market_fill_price, time, exchange_rate);
             if symbol_name != symbol_code && !self.symbol_code_map.contains_key(&symbol_name) {
                 self.symbol_code_map.insert(symbol_name.clone(), vec![]);
             };
@@ -298,7 +466,7 @@ impl Ledger {
 
             let id = self.generate_id(position_side);
             // Create a new position
-            let position = Position::new(
+            let mut position = Position::new(
                 symbol_name.clone(),
                 symbol_code.clone(),
                 order_id,
@@ -313,6 +481,7 @@ impl Ledger {
                 time,
                 self.position_calculation_mode.clone()
             );
+            position.record_entry_commission(commission);
 
             // Insert the new position into the positions map
             //eprintln!("Symbol Code {}", symbol_code);
@@ -350,6 +519,11 @@ impl Ledger {
     }
 
     pub async fn flatten_all_for_paper_account(&mut self, time: DateTime<Utc>) {
+        const FLATTEN_ALL_ID_TAG: &str = "Flatten All"; //use this as tag and order_id
+        self.flatten_paper_positions(time, FLATTEN_ALL_ID_TAG).await;
+    }
+
+    async fn flatten_paper_positions(&mut self, time: DateTime<Utc>, id_tag: &str) {
         let positions_to_close: Vec<_> = self.positions.iter()
             .map(|position| {
                 (
@@ -379,8 +553,7 @@ impl Ledger {
                 None => continue
             };
 
-            const FLATTEN_ALL_ID_TAG: &str = "Flatten All"; //use this as tag and order_id
-            self.paper_exit_position(FLATTEN_ALL_ID_TAG.to_string(), &symbol_code, time, market_price, FLATTEN_ALL_ID_TAG.to_string()).await;
+            self.paper_exit_position(id_tag.to_string(), &symbol_code, time, market_price, id_tag.to_string()).await;
         }
     }
 }
\ No newline at end of file
@@ -2,8 +2,9 @@ use dashmap::DashMap;
 use tokio::sync::{oneshot};
 use rust_decimal::Decimal;
 use chrono::{DateTime, Duration, Utc};
+use crate::messages::data_server_messaging::FundForgeError;
 use std::fs::create_dir_all;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use csv::Writer;
 use std::sync::Arc;
@@ -15,7 +16,7 @@ use uuid::Uuid;
 use crate::helpers::converters::format_duration;
 use crate::product_maps::oanda::maps::OANDA_SYMBOL_INFO;
 use crate::product_maps::rithmic::maps::{find_base_symbol, get_futures_symbol_info};
-use crate::standardized_types::accounts::{Account, AccountInfo, Currency};
+use crate::standardized_types::accounts::{Account, AccountInfo, BrokerPosition, Currency};
 use crate::standardized_types::base_data::traits::BaseData;
 use crate::standardized_types::broker_enum::Brokerage;
 use crate::standardized_types::enums::{OrderSide, PositionSide, StrategyMode};
@@ -27,6 +28,13 @@ use crate::standardized_types::symbol_info::SymbolInfo;
 use crate::standardized_types::time_slices::TimeSlice;
 use crate::strategies::client_features::other_requests::get_exchange_rate;
 use crate::strategies::handlers::market_handler::price_service::MarketPriceService;
+use crate::strategies::handlers::market_handler::risk_manager::AccountEquity;
+use crate::strategies::ledgers::commissions::{CommissionModel, MonthlyVolume};
+use crate::strategies::ledgers::financing::{FinancingLedger, FinancingSchedules};
+use crate::strategies::ledgers::margin::MarginSchedules;
+use crate::strategies::checkpoint::LedgerCheckpoint;
+use crate::strategies::client_features::request_handler::DataServerSender;
+use crate::strategies::statistics::{join_rolled_positions, EquityRecorder, PerformanceReport};
 use crate::strategies::strategy_events::StrategyEvent;
 
 /*
@@ -43,6 +51,8 @@ pub enum LedgerMessage {
     LiveAccountUpdate{cash_value: Decimal, cash_available: Decimal, cash_used: Decimal},
     ExitPaperPosition{symbol_code: SymbolCode, order_id: OrderId, time: DateTime<Utc>, market_fill_price: Price, tag: String},
     PaperFlattenAll{time: DateTime<Utc>},
+    RestoreCheckpoint{checkpoint: LedgerCheckpoint, restored_sender: oneshot::Sender<()>},
+    AdoptPosition{position: BrokerPosition, time: DateTime<Utc>, adopted_sender: oneshot::Sender<()>},
 }
 
 /// A ledger specific to the strategy which will ignore positions not related to the strategy but will update its balances relative to the actual account balances for live trading.
@@ -62,12 +72,20 @@ pub struct Ledger {
     pub open_pnl: DashMap<SymbolCode, Price>,
     pub total_booked_pnl: Price,
     pub commissions_paid: Decimal,
+    /// The net overnight financing credited to the account, negative if financing was charged, included in `total_booked_pnl`.
+    pub total_financing: Decimal,
     pub mode: StrategyMode,
     pub is_simulating_pnl: bool,
     pub(crate) strategy_sender: Sender<StrategyEvent>,
     pub rates: Arc<DashMap<Currency, Decimal>>,
     pub position_calculation_mode: PositionCalculationMode,
-    pub market_price_service: Arc<MarketPriceService>
+    pub market_price_service: Arc<MarketPriceService>,
+    pub(crate) commission_model: Arc<dyn CommissionModel>,
+    pub(crate) monthly_volume: MonthlyVolume,
+    pub(crate) margin_schedules: Arc<MarginSchedules>,
+    pub(crate) financing: FinancingLedger,
+    pub(crate) equity_recorder: EquityRecorder,
+    pub(crate) data_server_sender: DataServerSender,
     //todo, add daily max loss, max order size etc to ledger
 }
 
@@ -78,8 +96,11 @@ impl Ledger {
         synchronise_accounts: bool,
         strategy_sender: Sender<StrategyEvent>,
         position_calculation_mode: PositionCalculationMode,
-        market_price_service: Arc<MarketPriceService>
-
+        market_price_service: Arc<MarketPriceService>,
+        commission_model: Arc<dyn CommissionModel>,
+        margin_schedules: Arc<MarginSchedules>,
+        financing_schedules: Arc<FinancingSchedules>,
+        data_server_sender: DataServerSender
     ) -> Self {
         let is_simulating_pnl = match synchronise_accounts {
             true => false,
@@ -100,6 +121,7 @@ impl Ledger {
                 .push(position.value().symbol_code.clone());
         }
 
+        let financing = FinancingLedger::new(&financing_schedules, &account_info.brokerage);
         let ledger = Self {
             account: Account::new(account_info.brokerage, account_info.account_id),
             cash_value: account_info.cash_value,
@@ -116,20 +138,30 @@ impl Ledger {
             open_pnl: DashMap::new(),
             total_booked_pnl: dec!(0),
             commissions_paid: Default::default(),
+            total_financing: Default::default(),
             mode,
             is_simulating_pnl,
             strategy_sender,
             rates: Arc::new(Default::default()),
             position_calculation_mode,
             market_price_service,
+            commission_model,
+            monthly_volume: MonthlyVolume::default(),
+            margin_schedules,
+            financing,
+            equity_recorder: EquityRecorder::default(),
+            data_server_sender,
         };
         ledger
     }
 
-    /// Used to save positions to disk in json format
-    /// Useful for machine learning
+    /// Used to save the closed and open positions to disk in json format
+    /// Useful for machine learning and for the `MonteCarlo` analysis of the completed trades
     pub fn save_positions_to_file(&self, file: &str) {
         let mut positions = vec![];
+        for closed in self.positions_closed.iter() {
+            positions.extend(closed.value().iter().cloned());
+        }
         for position in self.positions.iter() {
             positions.push(position.value().clone());
         }
@@ -137,6 +169,114 @@ impl Ledger {
         std::fs::write(file, positions).unwrap();
     }
 
+    pub(crate) fn checkpoint(&self) -> LedgerCheckpoint {
+        LedgerCheckpoint {
+            account: self.account.clone(),
+            cash_value: self.cash_value,
+            cash_available: self.cash_available,
+            cash_used: self.cash_used,
+            total_booked_pnl: self.total_booked_pnl,
+            commissions_paid: self.commissions_paid,
+            total_financing: self.total_financing,
+            positions: self.positions.iter().map(|position| position.value().clone()).collect(),
+            positions_closed: self.positions_closed.iter().flat_map(|positions| positions.value().clone()).collect(),
+        }
+    }
+
+    /// Restores the closed positions and, for ledgers simulating pnl, the balances and open positions of a checkpoint.
+    /// Ledgers synchronized with the brokerage keep the brokerage balances and positions, only the context of open positions with the same symbol code and side is restored.
+    fn restore_checkpoint(&mut self, checkpoint: LedgerCheckpoint) {
+        for position in checkpoint.positions_closed {
+            *self.symbol_closed_pnl.entry(position.symbol_code.clone()).or_insert(dec!(0)) += position.booked_pnl;
+            self.positions_closed.entry(position.symbol_code.clone()).or_default().push(position);
+        }
+        if self.is_simulating_pnl {
+            self.cash_value = checkpoint.cash_value;
+            self.cash_available = checkpoint.cash_available;
+            self.cash_used = checkpoint.cash_used;
+            self.total_booked_pnl = checkpoint.total_booked_pnl;
+            self.commissions_paid = checkpoint.commissions_paid;
+            self.total_financing = checkpoint.total_financing;
+            for position in checkpoint.positions {
+                let mut codes = self.symbol_code_map.entry(position.symbol_name.clone()).or_default();
+                if !codes.contains(&position.symbol_code) {
+                    codes.push(position.symbol_code.clone());
+                }
+                drop(codes);
+                self.symbol_info.insert(position.symbol_name.clone(), position.symbol_info.clone());
+                self.open_pnl.insert(position.symbol_code.clone(), position.open_pnl);
+                self.positions.insert(position.symbol_code.clone(), position);
+            }
+        } else {
+            for position in checkpoint.positions {
+                if let Some(mut existing) = self.positions.get_mut(&position.symbol_code) {
+                    if existing.side == position.side {
+                        existing.position_id = position.position_id;
+                        existing.open_time = position.open_time;
+                        existing.tag = position.tag;
+                        existing.booked_pnl = position.booked_pnl;
+                        existing.completed_trades = position.completed_trades;
+                        existing.excursion = position.excursion;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Opens a position the brokerage reported on live startup, replacing any position the ledger has for the symbol code.
+    async fn adopt_position(&mut self, position: BrokerPosition, time: DateTime<Utc>) {
+        let info = self.symbol_info(self.account.brokerage, &position.symbol_name).await;
+        if position.symbol_name != position.symbol_code {
+            let mut codes = self.symbol_code_map.entry(position.symbol_name.clone()).or_default();
+            if !codes.contains(&position.symbol_code) {
+                codes.push(position.symbol_code.clone());
+            }
+        }
+        let exchange_rate = if self.currency != info.pnl_currency {
+            let side = match position.side {
+                PositionSide::Short => OrderSide::Sell,
+                _ => OrderSide::Buy,
+            };
+            match get_exchange_rate(self.currency, info.pnl_currency, time, side, &self.data_server_sender).await {
+                Ok(rate) => {
+                    self.rates.insert(info.pnl_currency, rate);
+                    rate
+                },
+                Err(_e) => self.get_exchange_multiplier(info.pnl_currency)
+            }
+        } else {
+            dec!(1.0)
+        };
+        let id = self.generate_id(position.side);
+        let position = Position::new(
+            position.symbol_name,
+            position.symbol_code,
+            "Reconciliation".to_string(),
+            self.account.clone(),
+            position.side,
+            position.quantity,
+            position.average_price,
+            id,
+            info,
+            exchange_rate,
+            "Reconciliation".to_string(),
+            time,
+            self.position_calculation_mode.clone()
+        );
+        self.open_pnl.insert(position.symbol_code.clone(), dec!(0));
+        self.positions.insert(position.symbol_code.clone(), position);
+    }
+
+    /// The equity the `RiskManager` loss limits are measured against.
+    pub(crate) fn account_equity(&self, time: DateTime<Utc>) -> AccountEquity {
+        let equity = self.cash_value + self.get_open_pnl();
+        AccountEquity {
+            equity,
+            peak: self.equity_recorder.peak().unwrap_or(equity),
+            day_start: self.equity_recorder.close_before(time.date_naive()).unwrap_or(equity),
+        }
+    }
+
     pub fn get_exchange_multiplier(&self, to_currency: Currency) -> Decimal {
         if self.currency == to_currency {
             return dec!(1.0);
@@ -213,6 +353,14 @@ impl Ledger {
                     LedgerMessage::PaperFlattenAll { time } => {
                         static_self.flatten_all_for_paper_account(time).await;
                     }
+                    LedgerMessage::RestoreCheckpoint { checkpoint, restored_sender } => {
+                        static_self.restore_checkpoint(checkpoint);
+                        let _ = restored_sender.send(());
+                    }
+                    LedgerMessage::AdoptPosition { position, time, adopted_sender } => {
+                        static_self.adopt_position(position, time).await;
+                        let _ = adopted_sender.send(());
+                    }
                 }
             }
         });
@@ -272,7 +420,7 @@ impl Ledger {
                         };
                         let reduced_size = position.quantity_open - quantity;
                         let exchange_rate = if self.currency != position.symbol_info.pnl_currency {
-                            match get_exchange_rate(self.currency, position.symbol_info.pnl_currency, Utc::now(), order_side).await {
+                            match get_exchange_rate(self.currency, position.symbol_info.pnl_currency, Utc::now(), order_side, &self.data_server_sender).await {
                                 Ok(rate) => {
                                     self.rates.insert(position.symbol_info.pnl_currency, rate);
                                     rate
@@ -319,7 +467,7 @@ impl Ledger {
                         Err(_) => return
                     };
                     let exchange_rate = if self.currency != position.symbol_info.pnl_currency {
-                        match get_exchange_rate(self.currency, position.symbol_info.pnl_currency, Utc::now(), order_side).await {
+                        match get_exchange_rate(self.currency, position.symbol_info.pnl_currency, Utc::now(), order_side, &self.data_server_sender).await {
                             Ok(rate) => {
                                 self.rates.insert(position.symbol_info.pnl_currency, rate);
                                 rate
@@ -473,7 +621,7 @@ impl Ledger {
         }
 
         match self.symbol_info.get(symbol_name) {
-            None => match brokerage.symbol_info(symbol_name.clone()).await {
+            None => match brokerage.symbol_info(symbol_name.clone(), &self.data_server_sender).await {
                 Ok(info) => info,
                 Err(e) => panic!("Ledgers: Error getting symbol info: {}, {}: {}", brokerage, symbol_name, e),
             },
@@ -525,29 +673,34 @@ impl Ledger {
         let mut peak = dec!(0.0);
         let mut running_pnl = dec!(0.0);
 
+        // a position rolled to the next contract and the position it rolled into are one trade
+        let mut positions: Vec<Position> = self.positions_closed.iter()
+            .flat_map(|positions| positions.value().clone())
+            .collect();
+        positions.sort_by(|a, b| a.close_time.cmp(&b.close_time));
+        let positions = join_rolled_positions(positions);
+
         // Track running PNL and maximum drawdown
-        for trades in self.positions_closed.iter() {
-            total_trades += trades.value().len();
-            for position in trades.value() {
-                running_pnl += position.booked_pnl;
-                if running_pnl > peak {
-                    peak = running_pnl;
-                }
+        total_trades += positions.len();
+        for position in &positions {
+            running_pnl += position.booked_pnl;
+            if running_pnl > peak {
+                peak = running_pnl;
+            }
 
-                let drawdown = peak - running_pnl;
-                if drawdown > max_drawdown {
-                    max_drawdown = drawdown;
-                }
+            let drawdown = peak - running_pnl;
+            if drawdown > max_drawdown {
+                max_drawdown = drawdown;
+            }
 
-                if position.booked_pnl > dec!(0.0) {
-                    wins += 1;
-                    win_pnl += position.booked_pnl;
-                } else if position.booked_pnl < dec!(0.0) {
-                    losses += 1;
-                    loss_pnl += position.booked_pnl;
-                }
-                pnl += position.booked_pnl;
+            if position.booked_pnl > dec!(0.0) {
+                wins += 1;
+                win_pnl += position.booked_pnl;
+            } else if position.booked_pnl < dec!(0.0) {
+                losses += 1;
+                loss_pnl += position.booked_pnl;
             }
+            pnl += position.booked_pnl;
         }
 
         // Calculate average win and average loss
@@ -615,7 +768,7 @@ impl Ledger {
          Profit Factor: {}, Quality Ratio: {},  Pain to Gain Ratio: {}, \
          Max Drawdown: {}, Total profit: {}, Total Wins: {}, Total Losses: {}, \
          Break Even: {}, Total Positions: {}, Open Positions: {}, \
-         Cash Used: {}, Cash Available: {}, Commission Paid: {}",
+         Cash Used: {}, Cash Available: {}, Commission Paid: {}, Financing: {}",
             self.account,
             cash_value.round_dp(2),
             self.currency,
@@ -633,7 +786,8 @@ impl Ledger {
             self.positions.len(),
             cash_used.round_dp(2),
             cash_available.round_dp(2),
-            commission_paid
+            commission_paid,
+            self.total_financing.round_dp(2)
         )
     }
 
@@ -682,8 +836,19 @@ impl Ledger {
                 }
             }
         }
+        let time = time_slice.iter().map(|base_data| base_data.time_closed_utc()).max();
         if self.mode != StrategyMode::Live {
             self.cash_value = self.cash_used + self.cash_available;
+            if let Some(time) = time {
+                if let Err(e) = self.charge_financing(time).await {
+                    eprintln!("Failed to charge financing for {}: {}", self.account, e);
+                }
+                self.check_maintenance_margin(time).await;
+            }
+        }
+        if let Some(time) = time {
+            let open_pnl: Decimal = self.positions.iter().map(|position| position.open_pnl).sum();
+            self.equity_recorder.record(time, self.cash_value + open_pnl, !self.positions.is_empty());
         }
     }
 
@@ -715,7 +880,7 @@ impl Ledger {
             if is_reducing {
                 remaining_quantity -= existing_position.quantity_open;
                 let exchange_rate = if self.currency != existing_position.symbol_info.pnl_currency {
-                    match get_exchange_rate(self.currency, existing_position.symbol_info.pnl_currency, time, side).await {
+                    match get_exchange_rate(self.currency, existing_position.symbol_info.pnl_currency, time, side, &self.data_server_sender).await {
                         Ok(rate) => {
                             self.rates.insert(existing_position.symbol_info.pnl_currency, rate);
                             rate
@@ -786,7 +951,7 @@ impl Ledger {
                 }
             }
             let exchange_rate = if self.currency != info.pnl_currency {
-                match get_exchange_rate(self.currency, info.pnl_currency, time, side).await {
+                match get_exchange_rate(self.currency, info.pnl_currency, time, side, &self.data_server_sender).await {
                     Ok(rate) => {
                         self.rates.insert(info.pnl_currency, rate);
                         rate
@@ -880,6 +1045,19 @@ impl Ledger {
                                 entry_time: trade.entry_time.clone(),
                                 exit_time: trade.exit_time.clone(),
                                 pnl: trade.profit,
+                                commission: trade.commission.commission,
+                                exchange_fees: trade.commission.exchange,
+                                clearing_fees: trade.commission.clearing,
+                                nfa_fees: trade.commission.nfa,
+                                spread_cost: trade.commission.spread,
+                                mae_price: trade.excursion.mae_price,
+                                mae_ticks: trade.excursion.mae_ticks.round_dp(2),
+                                mae_value: trade.excursion.mae_value.round_dp(2),
+                                mae_time: trade.excursion.mae_time.clone().unwrap_or_else(|| "None".to_string()),
+                                mfe_price: trade.excursion.mfe_price,
+                                mfe_ticks: trade.excursion.mfe_ticks.round_dp(2),
+                                mfe_value: trade.excursion.mfe_value.round_dp(2),
+                                mfe_time: trade.excursion.mfe_time.clone().unwrap_or_else(|| "None".to_string()),
                                 tag: position.tag.clone(),
                                 result: trade.result.to_string()
                             };
@@ -903,6 +1081,28 @@ impl Ledger {
         }
     }
 
+    /// Writes the performance report to a self-contained html file in the folder and returns the path of the file.
+    pub fn export_html_report(&self, folder: &str) -> Result<PathBuf, FundForgeError> {
+        create_dir_all(folder)
+            .map_err(|e| FundForgeError::ClientSideErrorDebug(format!("Failed to create directory {}: {}", folder, e)))?;
+
+        let date = Utc::now().format("%Y%m%d_%H%M").to_string();
+        let brokerage = self.account.brokerage.to_string();
+        let file_path = PathBuf::from(format!("{}/{:?}_Report_{}_{}_{}.html", folder, self.mode, brokerage, self.account.account_id, date));
+        std::fs::write(&file_path, self.html_report())
+            .map_err(|e| FundForgeError::ClientSideErrorDebug(format!("Failed to write report to {}: {}", file_path.display(), e)))?;
+        Ok(file_path)
+    }
+
+    /// Returns the performance report as a self-contained html page, built from the closed positions in the order they were closed.
+    pub fn html_report(&self) -> String {
+        let mut positions: Vec<Position> = self.positions_closed.iter()
+            .flat_map(|positions| positions.value().clone())
+            .collect();
+        positions.sort_by(|a, b| a.close_time.cmp(&b.close_time));
+        PerformanceReport::new(self).to_html(&positions)
+    }
+
     pub fn trade_statistics_to_string(&self) -> String {
         let mut total_trades: usize = 0;
         let mut wins: usize = 0;
@@ -1011,7 +1211,8 @@ impl Ledger {
         Average Hold Time: {}\n\
         Shortest Hold: {}\n\
         Longest Hold: {}\n\
-        Commission Paid: {}\n",
+        Commission Paid: {}\n\
+        Financing: {}\n",
             total_trades,
             win_rate,
             wins,
@@ -1029,7 +1230,8 @@ impl Ledger {
             format_duration(avg_hold_time),
             format_duration(shortest_hold),
             format_duration(longest_hold),
-            self.commissions_paid.round_dp(2)
+            self.commissions_paid.round_dp(2),
+            self.total_financing.round_dp(2)
         )
     }
 }
@@ -1046,6 +1248,19 @@ struct TradeExport {
     entry_time: String,
     exit_time: String,
     pnl: Decimal,
+    commission: Decimal,
+    exchange_fees: Decimal,
+    clearing_fees: Decimal,
+    nfa_fees: Decimal,
+    spread_cost: Decimal,
+    mae_price: Decimal,
+    mae_ticks: Decimal,
+    mae_value: Decimal,
+    mae_time: String,
+    mfe_price: Decimal,
+    mfe_ticks: Decimal,
+    mfe_value: Decimal,
+    mfe_time: String,
     tag: String,
     result: String,
 }
@@ -1055,6 +1270,7 @@ mod test {
     use super::*;
     use rust_decimal_macros::dec;
     use crate::apis::rithmic::rithmic_systems::RithmicSystem;
+    use crate::strategies::ledgers::commissions::CommissionSchedules;
 
     async fn setup_test_ledger() -> (Ledger, tokio::sync::mpsc::Receiver<StrategyEvent>) {
         let (strategy_sender, strategy_receiver) = tokio::sync::mpsc::channel(100);
@@ -1086,6 +1302,11 @@ mod test {
             false,
             strategy_sender,
             PositionCalculationMode::FIFO,
+            Arc::new(MarketPriceService::new()),
+            Arc::new(CommissionSchedules::default()),
+            Arc::new(MarginSchedules::default()),
+            Arc::new(FinancingSchedules::default()),
+            DataServerSender::new().0,
         );
 
         (ledger, strategy_receiver)
@@ -1259,6 +1480,13 @@ mod test {
                     expected_total_pnl,
                     position.booked_pnl
                 );
+
+                // 6 contracts were filled, each trade carries the fees for its share of the entry and exit
+                let per_side = crate::product_maps::rithmic::maps::get_futures_commissions_info(&symbol_name).unwrap().per_side;
+                assert_eq!(ledger.commissions_paid, per_side * dec!(6));
+                for trade in &position.completed_trades {
+                    assert_eq!(trade.commission.total(), per_side * dec!(2));
+                }
             }
         }
 
@@ -1,3 +1,4 @@
+use std::path::PathBuf;
 use std::sync::Arc;
 use chrono::{DateTime, Utc};
 use crate::standardized_types::enums::{OrderSide, PositionSide, StrategyMode};
@@ -7,29 +8,45 @@ use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
 use tokio::sync::{oneshot};
 use crate::standardized_types::position::{Position, PositionCalculationMode};
-use crate::standardized_types::accounts::{Account, Currency};
+use crate::standardized_types::accounts::{Account, BrokerPosition, Currency};
 use crate::standardized_types::broker_enum::Brokerage;
 use crate::standardized_types::new_types::{Price, Volume};
-use crate::standardized_types::orders::{OrderId, OrderUpdateEvent};
+use crate::standardized_types::orders::{Order, OrderId, OrderUpdateEvent};
 use crate::standardized_types::time_slices::TimeSlice;
 use crate::strategies::handlers::market_handler::price_service::MarketPriceService;
+use crate::strategies::handlers::market_handler::risk_manager::AccountEquity;
+use crate::strategies::ledgers::commissions::{CommissionModel, MonthlyVolume};
+use crate::strategies::checkpoint::LedgerCheckpoint;
+use crate::strategies::client_features::request_handler::DataServerSender;
 use crate::strategies::ledgers::ledger::{Ledger, LedgerMessage};
+use crate::strategies::ledgers::financing::{FinancingLedger, FinancingSchedules};
+use crate::strategies::ledgers::margin::MarginSchedules;
+use crate::strategies::statistics::{EquityRecorder, PerformanceReport};
 use crate::strategies::strategy_events::StrategyEvent;
+use crate::messages::data_server_messaging::FundForgeError;
 
 pub(crate) struct LedgerService {
     pub (crate) ledgers: DashMap<Account, &'static Ledger>,
     ledger_senders: DashMap<Account, tokio::sync::mpsc::Sender<LedgerMessage>>,
     strategy_sender: tokio::sync::mpsc::Sender<StrategyEvent>,
-    market_price_service: Arc<MarketPriceService>
+    market_price_service: Arc<MarketPriceService>,
+    commission_model: Arc<dyn CommissionModel>,
+    margin_schedules: Arc<MarginSchedules>,
+    financing_schedules: Arc<FinancingSchedules>,
+    data_server_sender: DataServerSender
 }
 
 impl LedgerService {
-    pub fn new(strategy_sender: tokio::sync::mpsc::Sender<StrategyEvent>, market_price_service: Arc<MarketPriceService>) -> Self {
+    pub fn new(strategy_sender: tokio::sync::mpsc::Sender<StrategyEvent>, market_price_service: Arc<MarketPriceService>, commission_model: Arc<dyn CommissionModel>, margin_schedules: Arc<MarginSchedules>, financing_schedules: Arc<FinancingSchedules>, data_server_sender: DataServerSender) -> Self {
         LedgerService {
             ledgers: Default::default(),
             ledger_senders: Default::default(),
             strategy_sender,
-            market_price_service
+            market_price_service,
+            commission_model,
+            margin_schedules,
+            financing_schedules,
+            data_server_sender
         }
     }
 
@@ -67,6 +84,11 @@ impl LedgerService {
             .unwrap_or_else(|| dec!(0))
     }
 
+    pub fn account_currency(&self, account: &Account) -> Option<Currency> {
+        self.ledgers.get(account)
+            .map(|ledger| ledger.currency)
+    }
+
     pub(crate) async fn update_or_create_position(
         &self,
         account: &Account,
@@ -133,6 +155,21 @@ impl LedgerService {
         }
     }
 
+    pub fn export_html_report(&self, account: &Account, directory: &str) -> Result<PathBuf, FundForgeError> {
+        match self.ledgers.get(account) {
+            Some(ledger) => ledger.export_html_report(directory),
+            None => Err(FundForgeError::ClientSideErrorDebug(format!("No ledger for account: {}", account))),
+        }
+    }
+
+    /// Exports the html report of each ledger which has closed positions, returns the paths of the files written.
+    pub fn export_html_reports(&self, directory: &str) -> Result<Vec<PathBuf>, FundForgeError> {
+        self.ledgers.iter()
+            .filter(|ledger| !ledger.positions_closed.is_empty())
+            .map(|ledger| ledger.export_html_report(directory))
+            .collect()
+    }
+
     pub fn print_trade_statistics(&self, account: &Account) {
         if let Some(ledger) = self.ledgers.get(account) {
             let msg = ledger.trade_statistics_to_string();
@@ -148,6 +185,11 @@ impl LedgerService {
         }
     }
 
+    pub fn performance_report(&self, account: &Account) -> Option<PerformanceReport> {
+        self.ledgers.get(account)
+            .map(|ledger| PerformanceReport::new(ledger.value()))
+    }
+
     pub fn print_ledger(&self, account: &Account) {
        if let Some(ledger) = self.ledgers.get(account) {
            let string = ledger.value().ledger_statistics_to_string(); //todo need to return the string here
@@ -170,7 +212,7 @@ impl LedgerService {
         if !self.ledgers.contains_key(account) {
             match strategy_mode {
                 StrategyMode::Live => {
-                    let account_info = match account.brokerage.account_info(account.account_id.clone()).await {
+                    let account_info = match account.brokerage.account_info(account.account_id.clone(), &self.data_server_sender).await {
                         Ok(ledger) => ledger,
                         Err(e) => {
                             panic!("LEDGER_SERVICE: Error initializing account: {}", e);
@@ -185,6 +227,10 @@ impl LedgerService {
                         self.strategy_sender.clone(),
                         position_calculation_mode,
                         self.market_price_service.clone(),
+                        self.commission_model.clone(),
+                        self.margin_schedules.clone(),
+                        self.financing_schedules.clone(),
+                        self.data_server_sender.clone(),
                     ));
                     let static_ledger: &'static Ledger = Box::leak(ledger);
 
@@ -217,13 +263,19 @@ impl LedgerService {
                         open_pnl: Default::default(),
                         total_booked_pnl: dec!(0),
                         commissions_paid: Default::default(),
+                        total_financing: Default::default(),
                         mode: strategy_mode.clone(),
                         is_simulating_pnl: true,
                         strategy_sender: self.strategy_sender.clone(),
                         rates: Arc::new(DashMap::new()),
                         position_calculation_mode,
                         market_price_service: self.market_price_service.clone(),
-
+                        commission_model: self.commission_model.clone(),
+                        monthly_volume: MonthlyVolume::default(),
+                        margin_schedules: self.margin_schedules.clone(),
+                        financing: FinancingLedger::new(&self.financing_schedules, &account.brokerage),
+                        equity_recorder: EquityRecorder::default(),
+                        data_server_sender: self.data_server_sender.clone(),
                     });
                     let static_ledger: &'static Ledger = Box::leak(ledger);
 
@@ -242,6 +294,43 @@ impl LedgerService {
             }
         }
     }
+    /// Returns the reason the order would be rejected for insufficient margin, `None` if the account has enough cash available.
+    pub(crate) fn margin_rejection_reason(&self, order: &Order, market_price: Price, time: DateTime<Utc>) -> Option<String> {
+        self.ledgers.get(&order.account)
+            .and_then(|ledger| ledger.margin_rejection_reason(order, market_price, time))
+    }
+
+    pub(crate) fn checkpoint(&self) -> Vec<LedgerCheckpoint> {
+        self.ledgers.iter()
+            .map(|ledger| ledger.value().checkpoint())
+            .collect()
+    }
+
+    /// Restores the ledger state of a checkpoint, returns once the ledger has applied it.
+    pub(crate) async fn restore_checkpoint(&self, checkpoint: LedgerCheckpoint) {
+        if let Some(sender) = self.ledger_senders.get(&checkpoint.account) {
+            let (restored_sender, restored_receiver) = oneshot::channel();
+            let msg = LedgerMessage::RestoreCheckpoint{checkpoint, restored_sender};
+            sender.send(msg).await.unwrap();
+            let _ = restored_receiver.await;
+        }
+    }
+
+    /// Opens a position the brokerage reported on live startup, returns once the ledger has applied it.
+    pub(crate) async fn adopt_position(&self, account: &Account, position: BrokerPosition, time: DateTime<Utc>) {
+        if let Some(sender) = self.ledger_senders.get(account) {
+            let (adopted_sender, adopted_receiver) = oneshot::channel();
+            let msg = LedgerMessage::AdoptPosition{position, time, adopted_sender};
+            sender.send(msg).await.unwrap();
+            let _ = adopted_receiver.await;
+        }
+    }
+
+    pub(crate) fn account_equity(&self, account: &Account, time: DateTime<Utc>) -> Option<AccountEquity> {
+        self.ledgers.get(account)
+            .map(|ledger| ledger.account_equity(time))
+    }
+
     pub fn is_long(&self, account: &Account, symbol_name: &SymbolName) -> bool {
         self.ledgers.get(account)
              .map(|ledger| ledger.is_long(symbol_name))
@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+use std::path::Path;
+use chrono::{DateTime, Duration, Utc};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde_derive::{Deserialize, Serialize};
+use crate::helpers::get_resources;
+use crate::messages::data_server_messaging::FundForgeError;
+use crate::product_maps::oanda::maps::SYMBOL_DIVISORS;
+use crate::product_maps::rithmic::maps::{find_base_symbol, get_futures_trading_hours, get_overnight_margin, get_rithmic_intraday_margin_in_usd};
+use crate::standardized_types::broker_enum::Brokerage;
+use crate::standardized_types::new_types::Volume;
+use crate::standardized_types::subscriptions::SymbolName;
+use crate::strategies::ledgers::commissions::schedule_keys;
+
+/// Futures positions still open this close to the end of the session are charged overnight margin.
+const OVERNIGHT_MARGIN_CUTOFF: Duration = Duration::minutes(15);
+
+/// The margin for a position in the account currency.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MarginRequirement {
+    /// The margin committed when the position is opened or increased.
+    pub initial: Decimal,
+    /// When the account equity falls below the maintenance margin of all open positions the ledger simulates a margin call and flattens the account.
+    pub maintenance: Decimal,
+}
+
+fn default_maintenance_percent() -> Decimal {
+    dec!(50)
+}
+
+/// The margin schedule for a symbol or brokerage, parsed from the `type` key of a table in `margins.toml`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum MarginSchedule {
+    /// Futures margin per contract in the pnl currency of the symbol, the maintenance margin defaults to the initial margin.
+    PerContract {
+        intraday_initial: Decimal,
+        #[serde(default)]
+        intraday_maintenance: Option<Decimal>,
+        overnight_initial: Decimal,
+        #[serde(default)]
+        overnight_maintenance: Option<Decimal>,
+    },
+    /// CFD, forex and crypto margin as a leverage ratio of the notional value, `leverage = 20` requires 5% of the notional value.
+    /// The maintenance margin is a percentage of the initial margin, Oanda closes out positions at 50%.
+    Leverage {
+        leverage: Decimal,
+        #[serde(default = "default_maintenance_percent")]
+        maintenance_percent: Decimal,
+    },
+}
+
+impl MarginSchedule {
+    /// Returns the margin for a position, `notional` and the per contract margins are converted to the account currency with `exchange_rate`.
+    pub fn requirement(&self, quantity: Volume, notional: Decimal, exchange_rate: Decimal, overnight: bool) -> MarginRequirement {
+        match self {
+            MarginSchedule::PerContract { intraday_initial, intraday_maintenance, overnight_initial, overnight_maintenance } => {
+                let (initial, maintenance) = match overnight {
+                    true => (*overnight_initial, overnight_maintenance.unwrap_or(*overnight_initial)),
+                    false => (*intraday_initial, intraday_maintenance.unwrap_or(*intraday_initial)),
+                };
+                MarginRequirement {
+                    initial: initial * quantity * exchange_rate,
+                    maintenance: maintenance * quantity * exchange_rate,
+                }
+            }
+            MarginSchedule::Leverage { leverage, maintenance_percent } => {
+                let initial = notional.abs() * exchange_rate / (*leverage).max(dec!(1));
+                MarginRequirement {
+                    initial,
+                    maintenance: initial * maintenance_percent / dec!(100),
+                }
+            }
+        }
+    }
+}
+
+/// The schedules for one brokerage in `margins.toml`, symbol schedules are used before the default.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct BrokerageMargins {
+    #[serde(default)]
+    pub default: Option<MarginSchedule>,
+    #[serde(default)]
+    pub symbols: HashMap<SymbolName, MarginSchedule>,
+    /// Symbols without a schedule use the Rithmic intraday and overnight margins or the Oanda leverage ratios in the product maps.
+    #[serde(default)]
+    pub product_maps: bool,
+}
+
+/// The margin model used by the backtest and paper ledgers, loaded from `resources/margins.toml`.
+/// Symbols without a schedule are charged the brokerage intraday margin and are not margin called, unless `product_maps = true` is set for the brokerage.
+///
+/// ```toml
+/// [brokerages.Rithmic]
+/// product_maps = true
+///
+/// [brokerages.Rithmic.symbols.MNQ]
+/// type = "PerContract"
+/// intraday_initial = 100
+/// overnight_initial = 2220
+/// overnight_maintenance = 2020
+///
+/// [brokerages.Oanda.symbols.EUR-USD]
+/// type = "Leverage"
+/// leverage = 30
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct MarginSchedules {
+    #[serde(default)]
+    pub brokerages: HashMap<String, BrokerageMargins>,
+}
+
+impl MarginSchedules {
+    /// Loads `margins.toml` from the strategy resources folder, if there is no file no schedules are used.
+    pub fn from_resources() -> Result<Self, FundForgeError> {
+        let path = get_resources().join("margins.toml");
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        Self::from_file(&path)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, FundForgeError> {
+        let toml_content = std::fs::read_to_string(path)
+            .map_err(|e| FundForgeError::ClientSideErrorDebug(format!("Failed to read {}: {}", path.display(), e)))?;
+        Self::from_toml(&toml_content)
+    }
+
+    pub fn from_toml(toml_content: &str) -> Result<Self, FundForgeError> {
+        toml::from_str(toml_content)
+            .map_err(|e| FundForgeError::ClientSideErrorDebug(format!("Invalid margin schedules: {}", e)))
+    }
+
+    /// Returns the schedule for the symbol, `None` if the symbol has no configured schedule and the brokerage does not use the product maps.
+    pub fn schedule(&self, brokerage: &Brokerage, symbol_name: &SymbolName) -> Option<MarginSchedule> {
+        let configured: Vec<&BrokerageMargins> = schedule_keys(brokerage).iter().filter_map(|key| self.brokerages.get(key)).collect();
+        let schedule = configured.iter().find_map(|margins| margins.symbols.get(symbol_name))
+            .or_else(|| configured.iter().find_map(|margins| margins.default.as_ref()));
+        if let Some(schedule) = schedule {
+            return Some(schedule.clone());
+        }
+        if !configured.iter().any(|margins| margins.product_maps) {
+            return None;
+        }
+        match brokerage {
+            Brokerage::Rithmic(_) => {
+                let symbol = find_base_symbol(symbol_name).unwrap_or(symbol_name.clone());
+                let intraday_initial = get_rithmic_intraday_margin_in_usd(&symbol)?;
+                Some(MarginSchedule::PerContract {
+                    intraday_initial,
+                    intraday_maintenance: None,
+                    overnight_initial: get_overnight_margin(&symbol).unwrap_or(intraday_initial),
+                    overnight_maintenance: None,
+                })
+            }
+            Brokerage::Oanda => SYMBOL_DIVISORS.get(symbol_name.as_str()).map(|leverage| MarginSchedule::Leverage {
+                leverage: *leverage,
+                maintenance_percent: default_maintenance_percent(),
+            }),
+            Brokerage::Test | Brokerage::Bitget => None,
+        }
+    }
+}
+
+/// Returns true if a futures position held at `time` is charged overnight margin, when the session is closed or about to close.
+/// Symbols without trading hours are always charged intraday margin.
+pub fn is_overnight(symbol_name: &SymbolName, time: DateTime<Utc>) -> bool {
+    let symbol = find_base_symbol(symbol_name).unwrap_or(symbol_name.clone());
+    match get_futures_trading_hours(&symbol) {
+        None => false,
+        Some(hours) => match hours.seconds_until_close(time) {
+            None => true,
+            Some(seconds) => seconds < OVERNIGHT_MARGIN_CUTOFF.num_seconds()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apis::rithmic::rithmic_systems::RithmicSystem;
+
+    #[test]
+    fn test_per_contract_intraday_and_overnight() {
+        let schedules = MarginSchedules::from_toml(r#"
+            [brokerages.Rithmic.symbols.MNQ]
+            type = "PerContract"
+            intraday_initial = 100
+            overnight_initial = 2000
+            overnight_maintenance = 1800
+        "#).unwrap();
+        let schedule = schedules.schedule(&Brokerage::Rithmic(RithmicSystem::Apex), &"MNQ".to_string()).unwrap();
+
+        let intraday = schedule.requirement(dec!(2), dec!(0), dec!(1), false);
+        assert_eq!(intraday, MarginRequirement { initial: dec!(200), maintenance: dec!(200) });
+
+        let overnight = schedule.requirement(dec!(2), dec!(0), dec!(1), true);
+        assert_eq!(overnight, MarginRequirement { initial: dec!(4000), maintenance: dec!(3600) });
+    }
+
+    #[test]
+    fn test_leverage_from_product_maps() {
+        // without a schedule the ledger uses the brokerage intraday margin
+        assert_eq!(MarginSchedules::default().schedule(&Brokerage::Oanda, &"EUR-USD".to_string()), None);
+        assert_eq!(MarginSchedules::default().schedule(&Brokerage::Test, &"EUR-USD".to_string()), None);
+
+        let schedules = MarginSchedules::from_toml(r#"
+            [brokerages.Oanda]
+            product_maps = true
+        "#).unwrap();
+        let schedule = schedules.schedule(&Brokerage::Oanda, &"EUR-USD".to_string()).unwrap();
+        let leverage = *SYMBOL_DIVISORS.get("EUR-USD").unwrap();
+
+        let requirement = schedule.requirement(dec!(10000), dec!(11000), dec!(1), false);
+        assert_eq!(requirement.initial, dec!(11000) / leverage);
+        assert_eq!(requirement.maintenance, requirement.initial / dec!(2));
+
+        assert_eq!(schedules.schedule(&Brokerage::Oanda, &"NOT-A-SYMBOL".to_string()), None);
+    }
+}
@@ -1,3 +1,6 @@
+pub mod commissions;
+pub mod financing;
 pub mod ledger;
 pub mod ledger_service;
-pub(crate) mod historical_ledger;
\ No newline at end of file
+pub mod margin;
+pub(crate) mod historical_ledger;
@@ -7,4 +7,12 @@ pub mod fund_forge_strategy;
 pub mod ledgers;
 pub mod handlers;
 pub mod statistics;
+pub mod html_report;
+pub mod checkpoint;
+pub mod reconciliation;
+pub mod monte_carlo;
+pub mod position_sizing;
+pub mod roll_manager;
+pub mod optimisation;
+pub mod walk_forward;
 pub mod client_features;
\ No newline at end of file
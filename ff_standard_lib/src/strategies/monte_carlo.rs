@@ -0,0 +1,290 @@
+use std::fs;
+use rust_decimal::Decimal;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal_macros::dec;
+use serde_derive::{Deserialize, Serialize};
+use crate::helpers::random::SplitMix64;
+use crate::messages::data_server_messaging::FundForgeError;
+use crate::standardized_types::position::Position;
+
+/// How each simulated trade sequence is drawn from the original trades.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ResamplingMethod {
+    /// Every trade is used once in a random order. The final equity is the same in every simulation unless trades are skipped, only the path and drawdowns change.
+    Reshuffle,
+    /// The trades are drawn at random with replacement, so a trade can be used several times or not at all.
+    Resample,
+}
+
+/// The results of a simulation at one confidence level, eg at 95% the final equity is at least `final_equity` and the drawdown is at most `max_drawdown` in 95% of simulations.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MonteCarloConfidence {
+    pub confidence_percent: Decimal,
+    pub final_equity: Decimal,
+    pub net_profit: Decimal,
+    pub max_drawdown: Decimal,
+    pub max_drawdown_percent: Decimal,
+}
+
+/// The distribution of the simulated results, money fields are in the currency of the trade pnl.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MonteCarloReport {
+    pub method: ResamplingMethod,
+    pub simulations: usize,
+    pub trades: usize,
+    pub skip_percent: Decimal,
+    pub starting_equity: Decimal,
+    /// The equity at or below which an account is ruined.
+    pub ruin_equity: Decimal,
+    /// The percentage of simulations which fell to the ruin equity at any point.
+    pub risk_of_ruin_percent: Decimal,
+    /// The results of the trades in their original order.
+    pub original_final_equity: Decimal,
+    pub original_max_drawdown: Decimal,
+    pub original_max_drawdown_percent: Decimal,
+    pub mean_final_equity: Decimal,
+    pub confidence_levels: Vec<MonteCarloConfidence>,
+}
+
+impl MonteCarloReport {
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+/// Tests how much of a result depends on the order and selection of the trades, by replaying the completed trades of the positions in random sequences.
+/// Each trade is the pnl of a `Trade` in `Position::completed_trades` less its commissions, in order of exit time.
+///
+/// Works offline on positions saved with `strategy.save_positions_to_file()`, so the same analysis can be applied to backtest and live results.
+pub struct MonteCarlo {
+    trades: Vec<Decimal>,
+    starting_equity: Decimal,
+    method: ResamplingMethod,
+    simulations: usize,
+    skip_percent: Decimal,
+    ruin_equity: Decimal,
+    confidence_levels: Vec<Decimal>,
+    seed: u64,
+}
+
+impl MonteCarlo {
+    /// Runs 1000 reshuffled simulations, the account is ruined if it loses half the starting equity.
+    pub fn new(positions: &[Position], starting_equity: Decimal) -> Self {
+        let mut trades: Vec<(&String, Decimal)> = positions.iter()
+            .flat_map(|position| position.completed_trades.iter())
+            .map(|trade| (&trade.exit_time, trade.profit - trade.commission.total()))
+            .collect();
+        trades.sort_by(|a, b| a.0.cmp(b.0));
+        Self::from_trades(trades.into_iter().map(|(_, pnl)| pnl).collect(), starting_equity)
+    }
+
+    /// Loads the positions saved with `strategy.save_positions_to_file()`.
+    pub fn from_file(file: &str, starting_equity: Decimal) -> Result<Self, FundForgeError> {
+        let json = fs::read_to_string(file)
+            .map_err(|e| FundForgeError::ClientSideErrorDebug(format!("Failed to read {}: {}", file, e)))?;
+        let positions: Vec<Position> = serde_json::from_str(&json)
+            .map_err(|e| FundForgeError::ClientSideErrorDebug(format!("Failed to parse positions {}: {}", file, e)))?;
+        Ok(Self::new(&positions, starting_equity))
+    }
+
+    /// The trade pnls in the order they were taken.
+    pub fn from_trades(trades: Vec<Decimal>, starting_equity: Decimal) -> Self {
+        Self {
+            trades,
+            starting_equity,
+            method: ResamplingMethod::Reshuffle,
+            simulations: 1000,
+            skip_percent: dec!(0),
+            ruin_equity: starting_equity / dec!(2),
+            confidence_levels: vec![dec!(50), dec!(90), dec!(95), dec!(99)],
+            seed: 0,
+        }
+    }
+
+    pub fn with_method(mut self, method: ResamplingMethod) -> Self {
+        self.method = method;
+        self
+    }
+
+    pub fn with_simulations(mut self, simulations: usize) -> Self {
+        self.simulations = simulations.max(1);
+        self
+    }
+
+    /// Skips each trade with this chance, eg `dec!(10)` for 10%, to test the result of missed fills or days the strategy was not run.
+    pub fn with_skip_percent(mut self, skip_percent: Decimal) -> Self {
+        self.skip_percent = skip_percent.max(dec!(0)).min(dec!(100));
+        self
+    }
+
+    /// The equity at or below which the account is ruined, eg the trailing drawdown limit of a funded account.
+    pub fn with_ruin_equity(mut self, ruin_equity: Decimal) -> Self {
+        self.ruin_equity = ruin_equity;
+        self
+    }
+
+    /// The confidence levels in percent, eg `dec!(95)`.
+    pub fn with_confidence_levels(mut self, confidence_levels: Vec<Decimal>) -> Self {
+        self.confidence_levels = confidence_levels;
+        self
+    }
+
+    /// The same seed always returns the same report.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn trades(&self) -> &[Decimal] {
+        &self.trades
+    }
+
+    pub fn run(&self) -> MonteCarloReport {
+        let original = self.simulate(self.trades.iter().copied());
+        let mut rng = SplitMix64::new(self.seed);
+        let skip_chance = (self.skip_percent / dec!(100)).to_f64().unwrap_or(0.0);
+
+        let mut results = Vec::with_capacity(self.simulations);
+        let mut sequence = self.trades.clone();
+        for _ in 0..self.simulations {
+            match self.method {
+                ResamplingMethod::Reshuffle => rng.shuffle(&mut sequence),
+                ResamplingMethod::Resample => {
+                    for trade in sequence.iter_mut() {
+                        *trade = self.trades[rng.next_index(self.trades.len())];
+                    }
+                }
+            }
+            let trades: Vec<Decimal> = sequence.iter()
+                .copied()
+                .filter(|_| skip_chance <= 0.0 || !rng.next_bool(skip_chance))
+                .collect();
+            results.push(self.simulate(trades.into_iter()));
+        }
+
+        let ruined = results.iter().filter(|result| result.ruined).count();
+        let mean_final_equity = match results.is_empty() {
+            true => self.starting_equity,
+            false => results.iter().map(|result| result.final_equity).sum::<Decimal>() / Decimal::from(results.len()),
+        };
+        let mut final_equities: Vec<Decimal> = results.iter().map(|result| result.final_equity).collect();
+        let mut drawdowns: Vec<Decimal> = results.iter().map(|result| result.max_drawdown).collect();
+        let mut drawdown_percents: Vec<Decimal> = results.iter().map(|result| result.max_drawdown_percent).collect();
+        final_equities.sort();
+        drawdowns.sort();
+        drawdown_percents.sort();
+
+        let confidence_levels = self.confidence_levels.iter().map(|confidence| {
+            // the worst outcomes are the low equities and the high drawdowns
+            let final_equity = percentile(&final_equities, dec!(100) - *confidence).unwrap_or(self.starting_equity);
+            MonteCarloConfidence {
+                confidence_percent: *confidence,
+                final_equity: final_equity.round_dp(2),
+                net_profit: (final_equity - self.starting_equity).round_dp(2),
+                max_drawdown: percentile(&drawdowns, *confidence).unwrap_or_default().round_dp(2),
+                max_drawdown_percent: percentile(&drawdown_percents, *confidence).unwrap_or_default().round_dp(2),
+            }
+        }).collect();
+
+        MonteCarloReport {
+            method: self.method,
+            simulations: self.simulations,
+            trades: self.trades.len(),
+            skip_percent: self.skip_percent,
+            starting_equity: self.starting_equity.round_dp(2),
+            ruin_equity: self.ruin_equity.round_dp(2),
+            risk_of_ruin_percent: (Decimal::from(ruined) / Decimal::from(self.simulations) * dec!(100)).round_dp(2),
+            original_final_equity: original.final_equity.round_dp(2),
+            original_max_drawdown: original.max_drawdown.round_dp(2),
+            original_max_drawdown_percent: original.max_drawdown_percent.round_dp(2),
+            mean_final_equity: mean_final_equity.round_dp(2),
+            confidence_levels,
+        }
+    }
+
+    fn simulate(&self, trades: impl Iterator<Item = Decimal>) -> SimulationResult {
+        let mut result = SimulationResult {
+            final_equity: self.starting_equity,
+            max_drawdown: dec!(0),
+            max_drawdown_percent: dec!(0),
+            ruined: self.starting_equity <= self.ruin_equity,
+        };
+        let mut peak = self.starting_equity;
+        for pnl in trades {
+            result.final_equity += pnl;
+            if result.final_equity > peak {
+                peak = result.final_equity;
+                continue;
+            }
+            let drawdown = peak - result.final_equity;
+            result.max_drawdown = result.max_drawdown.max(drawdown);
+            if peak > dec!(0) {
+                result.max_drawdown_percent = result.max_drawdown_percent.max(drawdown / peak * dec!(100));
+            }
+            if result.final_equity <= self.ruin_equity {
+                result.ruined = true;
+            }
+        }
+        result
+    }
+}
+
+struct SimulationResult {
+    final_equity: Decimal,
+    max_drawdown: Decimal,
+    max_drawdown_percent: Decimal,
+    ruined: bool,
+}
+
+/// Returns the nearest rank percentile of the sorted values.
+fn percentile(sorted: &[Decimal], percent: Decimal) -> Option<Decimal> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = (percent.max(dec!(0)).min(dec!(100)) / dec!(100) * Decimal::from(sorted.len() - 1)).round();
+    let index = rank.to_usize().unwrap_or(0);
+    sorted.get(index).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reshuffle_and_resample() {
+        let trades = vec![dec!(500), dec!(-200), dec!(300), dec!(-400), dec!(600), dec!(-100)];
+        let report = MonteCarlo::from_trades(trades.clone(), dec!(10000))
+            .with_simulations(500)
+            .with_seed(3)
+            .run();
+        assert_eq!(report.original_final_equity, dec!(10700));
+        assert_eq!(report.original_max_drawdown, dec!(400));
+        // reshuffling only changes the order, so every simulation ends at the same equity
+        assert!(report.confidence_levels.iter().all(|level| level.final_equity == dec!(10700)));
+        // the worst order loses all 3 losing trades in a row
+        let drawdown_99 = report.confidence_levels.iter().find(|level| level.confidence_percent == dec!(99)).unwrap().max_drawdown;
+        assert!(drawdown_99 >= dec!(400) && drawdown_99 <= dec!(700));
+        assert_eq!(report.risk_of_ruin_percent, dec!(0));
+        assert_eq!(report, MonteCarlo::from_trades(trades.clone(), dec!(10000)).with_simulations(500).with_seed(3).run());
+
+        let resampled = MonteCarlo::from_trades(trades, dec!(1000))
+            .with_method(ResamplingMethod::Resample)
+            .with_skip_percent(dec!(20))
+            .with_simulations(500)
+            .with_ruin_equity(dec!(500))
+            .run();
+        let levels = &resampled.confidence_levels;
+        assert!(levels[0].final_equity >= levels[3].final_equity);
+        assert!(levels[0].max_drawdown <= levels[3].max_drawdown);
+        assert!(resampled.risk_of_ruin_percent > dec!(0) && resampled.risk_of_ruin_percent < dec!(100));
+    }
+
+    #[test]
+    fn test_percentile() {
+        let values: Vec<Decimal> = (1..=11).map(Decimal::from).collect();
+        assert_eq!(percentile(&values, dec!(50)), Some(dec!(6)));
+        assert_eq!(percentile(&values, dec!(90)), Some(dec!(10)));
+        assert_eq!(percentile(&values, dec!(100)), Some(dec!(11)));
+        assert_eq!(percentile(&[], dec!(50)), None);
+    }
+}
@@ -0,0 +1,508 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs::create_dir_all;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use chrono::{NaiveDateTime, Utc};
+use csv::Writer;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde_derive::{Deserialize, Serialize};
+use tokio::process::Command;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use crate::helpers::random::SplitMix64;
+use crate::messages::data_server_messaging::FundForgeError;
+use crate::strategies::statistics::PerformanceReport;
+
+/// The json `ParameterSet` for a run, set by the `Optimiser` when it launches the strategy.
+pub const OPTIMISATION_PARAMETERS_ENV: &str = "FUNDFORGE_OPTIMISATION_PARAMETERS";
+
+/// The file the strategy writes its `PerformanceReport` to with `strategy.export_optimisation_report(&account)`.
+pub const OPTIMISATION_REPORT_ENV: &str = "FUNDFORGE_OPTIMISATION_REPORT";
+
+/// The backtest start and end dates for a run, set by the `Optimiser` when it was given a `BacktestWindow`.
+pub const OPTIMISATION_START_ENV: &str = "FUNDFORGE_OPTIMISATION_START";
+pub const OPTIMISATION_END_ENV: &str = "FUNDFORGE_OPTIMISATION_END";
+
+const WINDOW_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Random searches stop drawing samples after this many duplicates in a row, when the space is almost exhausted.
+const MAX_DUPLICATE_SAMPLES: usize = 1000;
+
+/// The values a parameter can take.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParameterRange {
+    /// From `start` to `end` inclusive in increments of `step`.
+    Range { start: Decimal, end: Decimal, step: Decimal },
+    Values(Vec<Decimal>),
+}
+
+impl ParameterRange {
+    pub fn values(&self) -> Vec<Decimal> {
+        match self {
+            ParameterRange::Range { start, end, step } => {
+                if *step <= dec!(0) {
+                    return vec![*start];
+                }
+                let mut values = vec![];
+                let mut value = *start;
+                while value <= *end {
+                    values.push(value);
+                    value += *step;
+                }
+                values
+            }
+            ParameterRange::Values(values) => values.clone(),
+        }
+    }
+}
+
+/// The parameter values for one backtest, read by the strategy with `ParameterSet::from_env()`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ParameterSet {
+    pub values: BTreeMap<String, Decimal>,
+}
+
+impl ParameterSet {
+    /// Returns the parameters the strategy was launched with by the `Optimiser`, `None` when the strategy was not launched by an optimiser.
+    pub fn from_env() -> Option<Self> {
+        let json = std::env::var(OPTIMISATION_PARAMETERS_ENV).ok()?;
+        match serde_json::from_str(&json) {
+            Ok(parameters) => Some(parameters),
+            Err(e) => {
+                eprintln!("Optimisation: Invalid parameters {}: {}", json, e);
+                None
+            }
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<Decimal> {
+        self.values.get(name).cloned()
+    }
+
+    /// Returns the value of the parameter, or `default` if it is not in the set, so the strategy can still run on its own.
+    pub fn get_or(&self, name: &str, default: Decimal) -> Decimal {
+        self.get(name).unwrap_or(default)
+    }
+}
+
+/// The backtest dates for a run, used by walk forward analysis to run the same strategy over different periods.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BacktestWindow {
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+}
+
+impl BacktestWindow {
+    pub fn new(start: NaiveDateTime, end: NaiveDateTime) -> Self {
+        Self { start, end }
+    }
+
+    /// Returns the dates the strategy was launched with by the `Optimiser`, `None` when the strategy was launched without a window.
+    /// The strategy passes the dates to `FundForgeStrategy::initialize` in place of its own backtest dates.
+    pub fn from_env() -> Option<Self> {
+        let start = std::env::var(OPTIMISATION_START_ENV).ok()?;
+        let end = std::env::var(OPTIMISATION_END_ENV).ok()?;
+        match (NaiveDateTime::parse_from_str(&start, WINDOW_FORMAT), NaiveDateTime::parse_from_str(&end, WINDOW_FORMAT)) {
+            (Ok(start), Ok(end)) => Some(Self { start, end }),
+            _ => {
+                eprintln!("Optimisation: Invalid backtest window {} to {}", start, end);
+                None
+            }
+        }
+    }
+}
+
+/// The parameters to optimise and the values to test for each.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ParameterSpace {
+    parameters: BTreeMap<String, ParameterRange>,
+}
+
+impl ParameterSpace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Tests the parameter from `start` to `end` inclusive in increments of `step`.
+    pub fn with_range(mut self, name: &str, start: Decimal, end: Decimal, step: Decimal) -> Self {
+        self.parameters.insert(name.to_string(), ParameterRange::Range { start, end, step });
+        self
+    }
+
+    /// Tests each of the values for the parameter, use `dec!(0)` and `dec!(1)` for flags.
+    pub fn with_values(mut self, name: &str, values: Vec<Decimal>) -> Self {
+        self.parameters.insert(name.to_string(), ParameterRange::Values(values));
+        self
+    }
+
+    /// The number of combinations in a grid search.
+    pub fn combinations(&self) -> usize {
+        self.parameters.values().map(|range| range.values().len()).product()
+    }
+
+    /// Returns every combination of the parameter values.
+    pub fn grid(&self) -> Vec<ParameterSet> {
+        let mut sets = vec![ParameterSet::default()];
+        for (name, range) in &self.parameters {
+            let values = range.values();
+            sets = sets.into_iter()
+                .flat_map(|set| values.iter().map(move |value| {
+                    let mut set = set.clone();
+                    set.values.insert(name.clone(), *value);
+                    set
+                }))
+                .collect();
+        }
+        sets
+    }
+
+    /// Returns up to `samples` unique combinations drawn at random, the same seed always returns the same combinations.
+    pub fn random(&self, samples: usize, seed: u64) -> Vec<ParameterSet> {
+        let ranges: Vec<(&String, Vec<Decimal>)> = self.parameters.iter().map(|(name, range)| (name, range.values())).collect();
+        if ranges.iter().any(|(_, values)| values.is_empty()) {
+            return vec![];
+        }
+        let samples = samples.min(self.combinations());
+        let mut rng = SplitMix64::new(seed);
+        let mut seen = HashSet::new();
+        let mut sets = Vec::with_capacity(samples);
+        let mut duplicates = 0;
+        while sets.len() < samples && duplicates < MAX_DUPLICATE_SAMPLES {
+            let mut set = ParameterSet::default();
+            for (name, values) in &ranges {
+                set.values.insert((*name).clone(), values[rng.next_index(values.len())]);
+            }
+            match seen.insert(set.clone()) {
+                true => {
+                    duplicates = 0;
+                    sets.push(set);
+                }
+                false => duplicates += 1,
+            }
+        }
+        sets
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SearchMethod {
+    /// Runs every combination in the parameter space.
+    Grid,
+    /// Runs `samples` unique combinations drawn at random with the seed.
+    Random { samples: usize, seed: u64 },
+}
+
+/// The `PerformanceReport` metric used to rank the results.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OptimisationMetric {
+    NetProfit,
+    TotalReturn,
+    ProfitFactor,
+    /// Ranked lowest first.
+    MaxDrawdown,
+    /// Ranked lowest first.
+    MaxDrawdownPercent,
+    SharpeRatio,
+    SortinoRatio,
+    CalmarRatio,
+    WinRate,
+    Expectancy,
+}
+
+impl OptimisationMetric {
+    pub fn value(&self, report: &PerformanceReport) -> Decimal {
+        match self {
+            OptimisationMetric::NetProfit => report.net_profit,
+            OptimisationMetric::TotalReturn => report.total_return_percent,
+            OptimisationMetric::ProfitFactor => report.profit_factor,
+            OptimisationMetric::MaxDrawdown => report.max_drawdown,
+            OptimisationMetric::MaxDrawdownPercent => report.max_drawdown_percent,
+            OptimisationMetric::SharpeRatio => report.sharpe_ratio,
+            OptimisationMetric::SortinoRatio => report.sortino_ratio,
+            OptimisationMetric::CalmarRatio => report.calmar_ratio,
+            OptimisationMetric::WinRate => report.win_rate_percent,
+            OptimisationMetric::Expectancy => report.expectancy,
+        }
+    }
+
+    pub fn higher_is_better(&self) -> bool {
+        !matches!(self, OptimisationMetric::MaxDrawdown | OptimisationMetric::MaxDrawdownPercent)
+    }
+}
+
+/// The outcome of one backtest, `report` is `None` if the backtest failed or did not export a report.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OptimisationResult {
+    pub parameters: ParameterSet,
+    pub report: Option<PerformanceReport>,
+    pub error: Option<String>,
+}
+
+impl OptimisationResult {
+    pub fn metric(&self, metric: OptimisationMetric) -> Option<Decimal> {
+        self.report.as_ref().map(|report| metric.value(report))
+    }
+}
+
+/// Sorts the results best first by the metric, failed backtests are last.
+pub fn rank_results(results: &mut [OptimisationResult], metric: OptimisationMetric) {
+    results.sort_by(|a, b| match (a.metric(metric), b.metric(metric)) {
+        (Some(a), Some(b)) => match metric.higher_is_better() {
+            true => b.cmp(&a),
+            false => a.cmp(&b),
+        },
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => std::cmp::Ordering::Equal,
+    });
+}
+
+/// Runs a backtest for each parameter set and ranks the results by a `PerformanceReport` metric.
+///
+/// Each backtest is a separate process of the strategy `program`, so the backtests are fully isolated from each other and share only the data server.
+/// The parameter set is passed to the process in `FUNDFORGE_OPTIMISATION_PARAMETERS`, the strategy reads it with `ParameterSet::from_env()`
+/// and calls `strategy.export_optimisation_report(&account)` when it receives the `ShutdownEvent`.
+/// The output of each run is written to `run_{index}.log` in the output folder.
+#[derive(Clone, Debug)]
+pub struct Optimiser {
+    program: PathBuf,
+    args: Vec<String>,
+    space: ParameterSpace,
+    search: SearchMethod,
+    metric: OptimisationMetric,
+    max_concurrent: usize,
+    output_folder: PathBuf,
+    window: Option<BacktestWindow>,
+}
+
+impl Optimiser {
+    /// Runs 1 backtest at a time and writes the results to `./optimisation`, use `with_concurrency` to run backtests in parallel.
+    /// `program` is usually `std::env::current_exe()`, so the strategy binary optimises itself when it was not launched with a parameter set.
+    pub fn new(program: PathBuf, space: ParameterSpace, search: SearchMethod, metric: OptimisationMetric) -> Self {
+        Self {
+            program,
+            args: vec![],
+            space,
+            search,
+            metric,
+            max_concurrent: 1,
+            output_folder: PathBuf::from("./optimisation"),
+            window: None,
+        }
+    }
+
+    /// The number of backtests to run at the same time, each one is a separate connection to the data server.
+    pub fn with_concurrency(mut self, max_concurrent: usize) -> Self {
+        self.max_concurrent = max_concurrent.max(1);
+        self
+    }
+
+    pub fn with_output_folder(mut self, folder: &str) -> Self {
+        self.output_folder = PathBuf::from(folder);
+        self
+    }
+
+    /// The command line arguments passed to each run of the program.
+    pub fn with_args(mut self, args: Vec<String>) -> Self {
+        self.args = args;
+        self
+    }
+
+    /// Runs each backtest over the window, the strategy reads the dates with `BacktestWindow::from_env()`.
+    pub fn with_window(mut self, window: BacktestWindow) -> Self {
+        self.window = Some(window);
+        self
+    }
+
+    pub fn metric(&self) -> OptimisationMetric {
+        self.metric
+    }
+
+    pub fn output_folder(&self) -> &Path {
+        &self.output_folder
+    }
+
+    /// Returns the parameter sets which will be tested.
+    pub fn parameter_sets(&self) -> Vec<ParameterSet> {
+        match &self.search {
+            SearchMethod::Grid => self.space.grid(),
+            SearchMethod::Random { samples, seed } => self.space.random(*samples, *seed),
+        }
+    }
+
+    /// Runs the backtests, writes the ranked results table to the output folder and returns the results best first.
+    pub async fn run(&self) -> Result<Vec<OptimisationResult>, FundForgeError> {
+        self.run_parameter_sets(self.parameter_sets()).await
+    }
+
+    /// Runs a backtest for each of the parameter sets, in place of the sets from the parameter space.
+    pub(crate) async fn run_parameter_sets(&self, parameter_sets: Vec<ParameterSet>) -> Result<Vec<OptimisationResult>, FundForgeError> {
+        create_dir_all(&self.output_folder)
+            .map_err(|e| FundForgeError::ClientSideErrorDebug(format!("Failed to create directory {}: {}", self.output_folder.display(), e)))?;
+
+        let total = parameter_sets.len();
+        println!("Optimisation: Running {} backtests, {} at a time", total, self.max_concurrent);
+
+        let semaphore = Arc::new(Semaphore::new(self.max_concurrent));
+        let mut runs = JoinSet::new();
+        for (index, parameters) in parameter_sets.into_iter().enumerate() {
+            let semaphore = semaphore.clone();
+            let program = self.program.clone();
+            let args = self.args.clone();
+            let output_folder = self.output_folder.clone();
+            let window = self.window;
+            runs.spawn(async move {
+                let _permit = semaphore.acquire_owned().await.expect("Optimisation semaphore closed");
+                let result = run_backtest(&program, &args, &output_folder, window, index, parameters).await;
+                (index, result)
+            });
+        }
+
+        let mut results = Vec::with_capacity(total);
+        while let Some(run) = runs.join_next().await {
+            match run {
+                Ok((index, result)) => {
+                    match (&result.error, result.metric(self.metric)) {
+                        (Some(e), _) => eprintln!("Optimisation: Run {} {:?} failed: {}", index, result.parameters.values, e),
+                        (None, Some(value)) => println!("Optimisation: Run {} {:?} {:?}: {}", index, result.parameters.values, self.metric, value),
+                        (None, None) => {}
+                    }
+                    results.push(result);
+                }
+                Err(e) => eprintln!("Optimisation: Run panicked: {}", e),
+            }
+        }
+
+        rank_results(&mut results, self.metric);
+        let date = Utc::now().format("%Y%m%d_%H%M").to_string();
+        let file_path = self.output_folder.join(format!("{:?}_Optimisation_{}.csv", self.metric, date));
+        write_results(&file_path, &results, self.metric)?;
+        println!("Optimisation: Results written to {}", file_path.display());
+        Ok(results)
+    }
+}
+
+async fn run_backtest(program: &Path, args: &[String], output_folder: &Path, window: Option<BacktestWindow>, index: usize, parameters: ParameterSet) -> OptimisationResult {
+    let report_path = output_folder.join(format!("run_{}.json", index));
+    let log_path = output_folder.join(format!("run_{}.log", index));
+    let error = |error: String| OptimisationResult { parameters: parameters.clone(), report: None, error: Some(error) };
+
+    let json = match serde_json::to_string(&parameters) {
+        Ok(json) => json,
+        Err(e) => return error(format!("Failed to serialize parameters: {}", e)),
+    };
+    let log = match std::fs::File::create(&log_path) {
+        Ok(log) => log,
+        Err(e) => return error(format!("Failed to create {}: {}", log_path.display(), e)),
+    };
+    let log_err = match log.try_clone() {
+        Ok(log) => log,
+        Err(e) => return error(format!("Failed to create {}: {}", log_path.display(), e)),
+    };
+    // a report left by a previous optimisation must not be read as the result of this run
+    let _ = std::fs::remove_file(&report_path);
+
+    let mut command = Command::new(program);
+    command.args(args)
+        .env(OPTIMISATION_PARAMETERS_ENV, json)
+        .env(OPTIMISATION_REPORT_ENV, &report_path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::from(log))
+        .stderr(Stdio::from(log_err));
+    if let Some(window) = window {
+        command.env(OPTIMISATION_START_ENV, window.start.format(WINDOW_FORMAT).to_string())
+            .env(OPTIMISATION_END_ENV, window.end.format(WINDOW_FORMAT).to_string());
+    }
+    let status = command.status().await;
+    match status {
+        Ok(status) if !status.success() => return error(format!("Exited with {}, see {}", status, log_path.display())),
+        Err(e) => return error(format!("Failed to start {}: {}", program.display(), e)),
+        Ok(_) => {}
+    }
+
+    let report = match std::fs::read_to_string(&report_path) {
+        Ok(report) => report,
+        Err(e) => return error(format!("No report at {}, the strategy must call export_optimisation_report: {}", report_path.display(), e)),
+    };
+    match serde_json::from_str::<PerformanceReport>(&report) {
+        Ok(report) => OptimisationResult { parameters, report: Some(report), error: None },
+        Err(e) => error(format!("Invalid report {}: {}", report_path.display(), e)),
+    }
+}
+
+fn write_results(file_path: &Path, results: &[OptimisationResult], metric: OptimisationMetric) -> Result<(), FundForgeError> {
+    let to_error = |e: csv::Error| FundForgeError::ClientSideErrorDebug(format!("Failed to write {}: {}", file_path.display(), e));
+    let mut writer = Writer::from_path(file_path).map_err(to_error)?;
+
+    let parameter_names: Vec<String> = results.first()
+        .map(|result| result.parameters.values.keys().cloned().collect())
+        .unwrap_or_default();
+    let mut header = vec!["rank".to_string()];
+    header.extend(parameter_names.iter().cloned());
+    header.extend([
+        format!("{:?}", metric), "net_profit".to_string(), "total_return_percent".to_string(), "profit_factor".to_string(), "max_drawdown".to_string(),
+        "max_drawdown_percent".to_string(), "sharpe_ratio".to_string(), "total_trades".to_string(), "win_rate_percent".to_string(), "error".to_string(),
+    ]);
+    writer.write_record(&header).map_err(to_error)?;
+
+    for (rank, result) in results.iter().enumerate() {
+        let mut record = vec![(rank + 1).to_string()];
+        record.extend(parameter_names.iter().map(|name| result.parameters.get(name).map(|value| value.to_string()).unwrap_or_default()));
+        match &result.report {
+            Some(report) => record.extend([
+                metric.value(report).to_string(), report.net_profit.to_string(), report.total_return_percent.to_string(), report.profit_factor.to_string(),
+                report.max_drawdown.to_string(), report.max_drawdown_percent.to_string(), report.sharpe_ratio.to_string(), report.total_trades.to_string(),
+                report.win_rate_percent.to_string(), String::new(),
+            ]),
+            None => {
+                record.extend(vec![String::new(); 9]);
+                record.push(result.error.clone().unwrap_or_default());
+            }
+        }
+        writer.write_record(&record).map_err(to_error)?;
+    }
+    writer.flush().map_err(|e| FundForgeError::ClientSideErrorDebug(format!("Failed to write {}: {}", file_path.display(), e)))
+}
+
+/// Writes the report to the file requested by the `Optimiser`, does nothing when the strategy was not launched by an optimiser.
+pub(crate) fn export_optimisation_report(report: &PerformanceReport) {
+    let path = match std::env::var(OPTIMISATION_REPORT_ENV) {
+        Ok(path) => path,
+        Err(_) => return,
+    };
+    match report.to_json() {
+        Ok(json) => {
+            if let Err(e) = std::fs::write(&path, json) {
+                eprintln!("Optimisation: Failed to write report to {}: {}", path, e);
+            }
+        }
+        Err(e) => eprintln!("Optimisation: Failed to serialize report: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grid_and_random_search() {
+        let space = ParameterSpace::new()
+            .with_range("renko_range", dec!(5), dec!(15), dec!(5))
+            .with_values("size", vec![dec!(1), dec!(2)]);
+        assert_eq!(space.combinations(), 6);
+
+        let grid = space.grid();
+        assert_eq!(grid.len(), 6);
+        assert_eq!(grid.iter().collect::<HashSet<_>>().len(), 6);
+        assert!(grid.iter().any(|set| set.get("renko_range") == Some(dec!(15)) && set.get("size") == Some(dec!(2))));
+
+        let random = space.random(4, 7);
+        assert_eq!(random.len(), 4);
+        assert_eq!(random, space.random(4, 7));
+        assert_eq!(random.iter().collect::<HashSet<_>>().len(), 4);
+        assert_eq!(space.random(100, 7).len(), 6);
+        assert_eq!(grid[0].get_or("missing", dec!(3)), dec!(3));
+    }
+}
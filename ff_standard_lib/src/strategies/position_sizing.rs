@@ -0,0 +1,195 @@
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use crate::helpers::decimal_calculators::calculate_theoretical_pnl;
+use crate::standardized_types::accounts::Currency;
+use crate::standardized_types::broker_enum::Brokerage;
+use crate::standardized_types::enums::PositionSide;
+use crate::standardized_types::new_types::{Price, Volume};
+use crate::standardized_types::symbol_info::SymbolInfo;
+use crate::strategies::indicators::indicator_values::IndicatorValues;
+use crate::strategies::statistics::PerformanceReport;
+
+/// Sizes positions from the account balance and the risk between the entry price and the stop.
+/// The risk of one unit is valued the same way the ledger books pnl, using `SymbolInfo.value_per_tick` and `tick_size`,
+/// converted to the account currency with `exchange_rate`, so the loss at the stop matches the loss the ledger will record.
+///
+/// Risk percentages are in percent, `1` is 1% of the balance. Quantities are rounded down to the `lot_step` of the brokerage,
+/// see `Brokerage::lot_step()`, and a size smaller than one step returns 0.
+///
+/// Use `FundForgeStrategy::position_sizer()` to build a sizer for a live account, or `PositionSizer::new()` with your own values.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PositionSizer {
+    pub brokerage: Brokerage,
+    pub symbol_info: SymbolInfo,
+    pub account_currency: Currency,
+    pub balance: Decimal,
+    /// The rate from the account currency to the pnl currency of the symbol, the same rate the ledger uses.
+    pub exchange_rate: Decimal,
+    pub lot_step: Volume,
+}
+
+impl PositionSizer {
+    pub fn new(brokerage: Brokerage, symbol_info: SymbolInfo, account_currency: Currency, balance: Decimal, exchange_rate: Decimal) -> Self {
+        let lot_step = brokerage.lot_step(&symbol_info);
+        Self {
+            brokerage,
+            symbol_info,
+            account_currency,
+            balance,
+            exchange_rate,
+            lot_step,
+        }
+    }
+
+    /// Overrides the lot step of the brokerage, for instruments with a different minimum increment.
+    pub fn with_lot_step(mut self, lot_step: Volume) -> Self {
+        self.lot_step = lot_step;
+        self
+    }
+
+    /// Rounds the quantity down to a multiple of the lot step.
+    pub fn round_quantity(&self, quantity: Volume) -> Volume {
+        if quantity <= dec!(0) {
+            return dec!(0);
+        }
+        if self.lot_step <= dec!(0) {
+            return quantity;
+        }
+        ((quantity / self.lot_step).floor() * self.lot_step).normalize()
+    }
+
+    /// The loss in the account currency of holding one unit from `entry_price` to a stop `stop_distance` away.
+    /// The stop distance is rounded up to whole ticks, the stop can not be placed between ticks.
+    pub fn risk_per_unit(&self, entry_price: Price, stop_distance: Price) -> Decimal {
+        let tick_size = self.symbol_info.tick_size;
+        let stop_distance = match tick_size > dec!(0) {
+            true => (stop_distance.abs() / tick_size).ceil() * tick_size,
+            false => stop_distance.abs(),
+        };
+        calculate_theoretical_pnl(
+            self.brokerage,
+            PositionSide::Long,
+            entry_price,
+            entry_price - stop_distance,
+            dec!(1),
+            &self.symbol_info,
+            self.exchange_rate,
+            self.account_currency,
+        ).abs()
+    }
+
+    /// Risks a fixed amount of the account currency between the entry and the stop.
+    pub fn fixed_dollar(&self, risk: Decimal, entry_price: Price, stop_distance: Price) -> Volume {
+        let risk_per_unit = self.risk_per_unit(entry_price, stop_distance);
+        if risk <= dec!(0) || risk_per_unit <= dec!(0) {
+            return dec!(0);
+        }
+        self.round_quantity(risk / risk_per_unit)
+    }
+
+    /// Risks `risk_percent` of the balance between the entry and the stop.
+    pub fn fixed_fractional(&self, risk_percent: Decimal, entry_price: Price, stop_distance: Price) -> Volume {
+        self.fixed_dollar(self.balance * risk_percent / dec!(100), entry_price, stop_distance)
+    }
+
+    /// Places the stop `atr_multiple` average true ranges from the entry and risks `risk_percent` of the balance,
+    /// so the position gets smaller as the instrument gets more volatile.
+    /// Use `atr_value()` to read the atr from the `AverageTrueRange` indicator.
+    pub fn volatility_target(&self, risk_percent: Decimal, entry_price: Price, atr: Price, atr_multiple: Decimal) -> Volume {
+        self.fixed_fractional(risk_percent, entry_price, atr * atr_multiple)
+    }
+
+    /// Risks a fraction of the kelly criterion, `kelly_multiplier` of 0.5 is half kelly.
+    /// The full kelly fraction is usually too aggressive for estimated win rates, a multiplier of 0.25 to 0.5 is common.
+    pub fn kelly(&self, win_rate: Decimal, win_loss_ratio: Decimal, kelly_multiplier: Decimal, entry_price: Price, stop_distance: Price) -> Volume {
+        let risk_percent = kelly_fraction(win_rate, win_loss_ratio) * kelly_multiplier * dec!(100);
+        self.fixed_fractional(risk_percent, entry_price, stop_distance)
+    }
+
+    /// Takes the win rate and win loss ratio for `kelly()` from the report of a previous backtest.
+    pub fn kelly_from_report(&self, report: &PerformanceReport, kelly_multiplier: Decimal, entry_price: Price, stop_distance: Price) -> Volume {
+        let (win_rate, win_loss_ratio) = kelly_inputs(report);
+        self.kelly(win_rate, win_loss_ratio, kelly_multiplier, entry_price, stop_distance)
+    }
+}
+
+/// The kelly criterion `w - (1 - w) / r`, the fraction of the balance to risk per trade, clamped to 0..=1.
+/// `win_rate` is a fraction, 0.55 is 55% winners, and `win_loss_ratio` is the average win divided by the average loss.
+pub fn kelly_fraction(win_rate: Decimal, win_loss_ratio: Decimal) -> Decimal {
+    if win_loss_ratio <= dec!(0) {
+        return dec!(0);
+    }
+    let fraction = win_rate - (dec!(1) - win_rate) / win_loss_ratio;
+    fraction.max(dec!(0)).min(dec!(1))
+}
+
+/// The win rate as a fraction and the win loss ratio of a report.
+pub fn kelly_inputs(report: &PerformanceReport) -> (Decimal, Decimal) {
+    let win_rate = report.win_rate_percent / dec!(100);
+    let win_loss_ratio = match report.average_loss != dec!(0) {
+        true => report.average_win / report.average_loss.abs(),
+        false => dec!(0),
+    };
+    (win_rate, win_loss_ratio)
+}
+
+/// Reads the `"atr"` plot of an `AverageTrueRange` indicator value.
+pub fn atr_value(values: &IndicatorValues) -> Option<Price> {
+    values.get_plot(&"atr".to_string()).map(|plot| plot.value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::standardized_types::broker_enum::Brokerage;
+
+    fn symbol_info(symbol_name: &str, base_currency: Option<Currency>, pnl_currency: Currency, value_per_tick: Price, tick_size: Price) -> SymbolInfo {
+        SymbolInfo::new(symbol_name.to_string(), base_currency, pnl_currency, value_per_tick, tick_size, 2)
+    }
+
+    #[test]
+    fn test_fixed_fractional_and_fixed_dollar() {
+        let es = symbol_info("ES", None, Currency::USD, dec!(12.5), dec!(0.25));
+        let sizer = PositionSizer::new(Brokerage::Test, es, Currency::USD, dec!(100000), dec!(1));
+        assert_eq!(sizer.lot_step, dec!(1));
+        // a 10 point stop risks 500 per contract
+        assert_eq!(sizer.risk_per_unit(dec!(5000), dec!(10)), dec!(500));
+        assert_eq!(sizer.fixed_dollar(dec!(1250), dec!(5000), dec!(10)), dec!(2));
+        assert_eq!(sizer.fixed_fractional(dec!(1), dec!(5000), dec!(10)), dec!(2));
+        // the stop is rounded up to whole ticks
+        assert_eq!(sizer.risk_per_unit(dec!(5000), dec!(9.9)), dec!(500));
+        // an atr of 4 with a multiple of 2.5 is the same 10 point stop
+        assert_eq!(sizer.volatility_target(dec!(1), dec!(5000), dec!(4), dec!(2.5)), dec!(2));
+        assert_eq!(sizer.fixed_dollar(dec!(400), dec!(5000), dec!(10)), dec!(0));
+        assert_eq!(sizer.fixed_dollar(dec!(400), dec!(5000), dec!(0)), dec!(0));
+    }
+
+    #[test]
+    fn test_exchange_rate_and_fractional_units() {
+        // a cfd quoted in AUD for a USD account, the ledger multiplies the pnl by the rate
+        let aus200 = symbol_info("AUS200-AUD", None, Currency::AUD, dec!(0.1), dec!(0.1));
+        let sizer = PositionSizer::new(Brokerage::Oanda, aus200, Currency::USD, dec!(10000), dec!(0.65));
+        assert_eq!(sizer.lot_step, dec!(0.1));
+        // 20 points risks 20 AUD or 13 USD per unit, 100 / 13 = 7.69 units
+        assert_eq!(sizer.fixed_dollar(dec!(100), dec!(8000), dec!(20)), dec!(7.6));
+
+        let bitget = PositionSizer::new(Brokerage::Bitget, symbol_info("BTCUSDT", None, Currency::USD, dec!(0.1), dec!(0.1)), Currency::USD, dec!(1000), dec!(1));
+        assert_eq!(bitget.fixed_fractional(dec!(1), dec!(60000), dec!(1500)), dec!(0.0066));
+        assert_eq!(bitget.clone().with_lot_step(dec!(0.001)).round_quantity(dec!(0.0066)), dec!(0.006));
+    }
+
+    #[test]
+    fn test_kelly_fraction() {
+        // 60% winners with wins the same size as losses risks 20%
+        assert_eq!(kelly_fraction(dec!(0.6), dec!(1)), dec!(0.2));
+        assert_eq!(kelly_fraction(dec!(0.5), dec!(2)), dec!(0.25));
+        // no edge, no bet
+        assert_eq!(kelly_fraction(dec!(0.4), dec!(1)), dec!(0));
+        assert_eq!(kelly_fraction(dec!(0.6), dec!(0)), dec!(0));
+
+        let es = symbol_info("ES", None, Currency::USD, dec!(12.5), dec!(0.25));
+        let sizer = PositionSizer::new(Brokerage::Test, es, Currency::USD, dec!(100000), dec!(1));
+        // quarter kelly of 20% risks 5% or 5000, 10 contracts at 500 each
+        assert_eq!(sizer.kelly(dec!(0.6), dec!(1), dec!(0.25), dec!(5000), dec!(10)), dec!(10));
+    }
+}
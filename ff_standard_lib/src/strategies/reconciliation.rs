@@ -0,0 +1,370 @@
+use std::fmt::{Display, Formatter};
+use chrono::Utc;
+use dashmap::DashMap;
+use uuid::Uuid;
+use crate::messages::data_server_messaging::{DataServerRequest, FundForgeError};
+use crate::standardized_types::accounts::{Account, AccountSnapshot, BrokerPosition};
+use crate::standardized_types::enums::{OrderSide, PositionSide};
+use crate::standardized_types::new_types::Volume;
+use crate::standardized_types::orders::{Order, OrderId, OrderRequest};
+use crate::standardized_types::position::Position;
+use crate::standardized_types::subscriptions::{SymbolCode, SymbolName};
+use crate::strategies::checkpoint::StrategyCheckpoint;
+use crate::strategies::client_features::connection_types::ConnectionType;
+use crate::strategies::client_features::request_handler::{DataServerSender, StrategyRequest};
+use crate::strategies::ledgers::ledger_service::LedgerService;
+
+/// What a live strategy does on startup when the positions and working orders at the brokerage do not match what it expects.
+/// The strategy expects the open positions and orders of the checkpoint it resumed from, or a flat account with no orders if it did not resume from a checkpoint.
+///
+/// Positions and orders the strategy expected which are no longer at the brokerage were closed or filled while the strategy was stopped,
+/// they are removed from the restored checkpoint with every policy except `Refuse`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReconciliationPolicy {
+    /// The brokerage is right, positions and orders the strategy did not expect are taken over by the strategy,
+    /// and positions with a different side or quantity are replaced by the brokerage position.
+    Adopt,
+    /// Orders the strategy did not expect are cancelled, positions it did not expect or which have a different side or quantity are flattened with market orders.
+    /// The strategy starts flat in those symbols.
+    CancelAndFlatten,
+    /// `FundForgeStrategy::initialize()` returns the report as an error instead of starting, the account has to be fixed by hand.
+    Refuse,
+}
+
+/// A difference between the brokerage and the strategy.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReconciliationMismatch {
+    /// The brokerage has a position the strategy did not expect.
+    UnexpectedPosition(BrokerPosition),
+    /// The strategy expected a position the brokerage does not have.
+    MissingPosition { symbol_name: SymbolName, symbol_code: SymbolCode, side: PositionSide, quantity: Volume },
+    /// Both have a position but the side or quantity is different.
+    PositionMismatch { expected_side: PositionSide, expected_quantity: Volume, position: BrokerPosition },
+    /// The brokerage has a working order the strategy did not place, or placed in a session it did not resume from.
+    UnexpectedOrder(Order),
+    /// The strategy expected a working order the brokerage does not have.
+    MissingOrder(Order),
+}
+
+impl ReconciliationMismatch {
+    pub fn symbol_code(&self) -> &SymbolCode {
+        match self {
+            ReconciliationMismatch::UnexpectedPosition(position) => &position.symbol_code,
+            ReconciliationMismatch::MissingPosition { symbol_code, .. } => symbol_code,
+            ReconciliationMismatch::PositionMismatch { position, .. } => &position.symbol_code,
+            ReconciliationMismatch::UnexpectedOrder(order) => &order.symbol_code,
+            ReconciliationMismatch::MissingOrder(order) => &order.symbol_code,
+        }
+    }
+}
+
+impl Display for ReconciliationMismatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReconciliationMismatch::UnexpectedPosition(position) => {
+                write!(f, "Unexpected position: {} {} {} @ {}", position.symbol_code, position.side, position.quantity, position.average_price)
+            }
+            ReconciliationMismatch::MissingPosition { symbol_code, side, quantity, .. } => {
+                write!(f, "Missing position: {} {} {}", symbol_code, side, quantity)
+            }
+            ReconciliationMismatch::PositionMismatch { expected_side, expected_quantity, position } => {
+                write!(f, "Position mismatch: {} expected {} {}, brokerage has {} {}", position.symbol_code, expected_side, expected_quantity, position.side, position.quantity)
+            }
+            ReconciliationMismatch::UnexpectedOrder(order) => {
+                write!(f, "Unexpected order: {} {} {:?} {:?} {}, tag: {}", order.id, order.symbol_code, order.order_type, order.side, order.quantity_open, order.tag)
+            }
+            ReconciliationMismatch::MissingOrder(order) => {
+                write!(f, "Missing order: {} {} {:?} {:?} {}, tag: {}", order.id, order.symbol_code, order.order_type, order.side, order.quantity_open, order.tag)
+            }
+        }
+    }
+}
+
+/// The differences found between the brokerage and the strategy for one account on live startup.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReconciliationReport {
+    pub account: Account,
+    pub mismatches: Vec<ReconciliationMismatch>,
+}
+
+impl ReconciliationReport {
+    pub fn is_reconciled(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+
+    /// Removes the positions and orders the brokerage does not have from the checkpoint, along with positions the brokerage has with a different side or quantity.
+    pub(crate) fn apply_to_checkpoint(&self, checkpoint: &mut StrategyCheckpoint) {
+        let mut symbol_codes = vec![];
+        let mut order_ids = vec![];
+        for mismatch in &self.mismatches {
+            match mismatch {
+                ReconciliationMismatch::MissingPosition { symbol_code, .. } => symbol_codes.push(symbol_code.clone()),
+                ReconciliationMismatch::PositionMismatch { position, .. } => symbol_codes.push(position.symbol_code.clone()),
+                ReconciliationMismatch::MissingOrder(order) => order_ids.push(order.id.clone()),
+                ReconciliationMismatch::UnexpectedPosition(_) | ReconciliationMismatch::UnexpectedOrder(_) => {}
+            }
+        }
+        for ledger in checkpoint.ledgers.iter_mut().filter(|ledger| ledger.account == self.account) {
+            ledger.positions.retain(|position| !symbol_codes.contains(&position.symbol_code));
+        }
+        checkpoint.open_orders.retain(|order| order.account != self.account || !order_ids.contains(&order.id));
+    }
+}
+
+impl Display for ReconciliationReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.is_reconciled() {
+            return write!(f, "Reconciliation: {} matches the brokerage", self.account);
+        }
+        write!(f, "Reconciliation: {} has {} mismatches with the brokerage", self.account, self.mismatches.len())?;
+        for mismatch in &self.mismatches {
+            write!(f, "\n  {}", mismatch)?;
+        }
+        Ok(())
+    }
+}
+
+/// The open positions and orders of the account in the checkpoint.
+pub fn expected_state(checkpoint: &StrategyCheckpoint, account: &Account) -> (Vec<Position>, Vec<Order>) {
+    let positions = checkpoint.ledgers.iter()
+        .filter(|ledger| &ledger.account == account)
+        .flat_map(|ledger| ledger.positions.iter())
+        .filter(|position| !position.is_closed && position.quantity_open > Volume::ZERO)
+        .cloned()
+        .collect();
+    let orders = checkpoint.open_orders.iter()
+        .filter(|order| &order.account == account)
+        .cloned()
+        .collect();
+    (positions, orders)
+}
+
+/// Compares the positions and working orders at the brokerage with the positions and orders the strategy expects.
+/// Positions are matched by symbol code and orders by order id.
+pub fn reconcile(snapshot: &AccountSnapshot, expected_positions: &[Position], expected_orders: &[Order]) -> ReconciliationReport {
+    let mut mismatches = vec![];
+    for position in &snapshot.positions {
+        match expected_positions.iter().find(|expected| expected.symbol_code == position.symbol_code) {
+            None => mismatches.push(ReconciliationMismatch::UnexpectedPosition(position.clone())),
+            Some(expected) if expected.side != position.side || expected.quantity_open != position.quantity => {
+                mismatches.push(ReconciliationMismatch::PositionMismatch {
+                    expected_side: expected.side,
+                    expected_quantity: expected.quantity_open,
+                    position: position.clone(),
+                })
+            }
+            Some(_) => {}
+        }
+    }
+    for expected in expected_positions {
+        if !snapshot.positions.iter().any(|position| position.symbol_code == expected.symbol_code) {
+            mismatches.push(ReconciliationMismatch::MissingPosition {
+                symbol_name: expected.symbol_name.clone(),
+                symbol_code: expected.symbol_code.clone(),
+                side: expected.side,
+                quantity: expected.quantity_open,
+            });
+        }
+    }
+    for order in &snapshot.orders {
+        if !expected_orders.iter().any(|expected| expected.id == order.id) {
+            mismatches.push(ReconciliationMismatch::UnexpectedOrder(order.clone()));
+        }
+    }
+    for expected in expected_orders {
+        if !snapshot.orders.iter().any(|order| order.id == expected.id) {
+            mismatches.push(ReconciliationMismatch::MissingOrder(expected.clone()));
+        }
+    }
+    ReconciliationReport {
+        account: snapshot.account.clone(),
+        mismatches,
+    }
+}
+
+/// Requests the account snapshot from the brokerage, reports the mismatches and applies the policy, called by `FundForgeStrategy::initialize()` before the checkpoint is restored.
+/// Nothing is changed with `ReconciliationPolicy::Refuse`, the caller refuses to start if the report has mismatches.
+pub(crate) async fn reconcile_account(
+    account: &Account,
+    policy: ReconciliationPolicy,
+    checkpoint: Option<&mut StrategyCheckpoint>,
+    ledger_service: &LedgerService,
+    open_order_cache: &DashMap<OrderId, Order>,
+    data_server_sender: &DataServerSender,
+) -> Result<ReconciliationReport, FundForgeError> {
+    let snapshot = account.brokerage.account_snapshot(account.account_id.clone(), data_server_sender).await?;
+    let (expected_positions, expected_orders) = match &checkpoint {
+        Some(checkpoint) => expected_state(checkpoint, account),
+        None => (vec![], vec![]),
+    };
+    let report = reconcile(&snapshot, &expected_positions, &expected_orders);
+    if policy == ReconciliationPolicy::Refuse {
+        return Ok(report);
+    }
+    if let Some(checkpoint) = checkpoint {
+        report.apply_to_checkpoint(checkpoint);
+    }
+
+    // the ledger opens every position we keep, a checkpoint restored afterwards replaces the matching positions with the saved ones
+    let time = Utc::now();
+    for position in snapshot.positions {
+        let is_mismatched = report.mismatches.iter().any(|mismatch| match mismatch {
+            ReconciliationMismatch::UnexpectedPosition(_) | ReconciliationMismatch::PositionMismatch { .. } => mismatch.symbol_code() == &position.symbol_code,
+            _ => false,
+        });
+        ledger_service.adopt_position(account, position.clone(), time).await;
+        if is_mismatched && policy == ReconciliationPolicy::CancelAndFlatten {
+            let side = match position.side {
+                PositionSide::Short => OrderSide::Buy,
+                _ => OrderSide::Sell,
+            };
+            let order = Order::market_order(position.symbol_name, Some(position.symbol_code), account, position.quantity, side, "Reconciliation Flatten".to_string(), Uuid::new_v4().to_string(), time, None);
+            open_order_cache.insert(order.id.clone(), order.clone());
+            let order_request = OrderRequest::Create { account: account.clone(), order_type: order.order_type.clone(), order };
+            send_order_request(order_request, data_server_sender).await;
+        }
+    }
+
+    for mismatch in &report.mismatches {
+        if let ReconciliationMismatch::UnexpectedOrder(order) = mismatch {
+            open_order_cache.insert(order.id.clone(), order.clone());
+            if policy == ReconciliationPolicy::CancelAndFlatten {
+                send_order_request(OrderRequest::Cancel { account: account.clone(), order_id: order.id.clone() }, data_server_sender).await;
+            }
+        }
+    }
+    Ok(report)
+}
+
+async fn send_order_request(order_request: OrderRequest, data_server_sender: &DataServerSender) {
+    let connection_type = ConnectionType::Broker(order_request.brokerage());
+    let request = StrategyRequest::OneWay(connection_type, DataServerRequest::OrderRequest { request: order_request });
+    data_server_sender.send(request).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+    use crate::standardized_types::broker_enum::Brokerage;
+    use crate::standardized_types::orders::TimeInForce;
+    use crate::standardized_types::position::PositionCalculationMode;
+    use crate::standardized_types::symbol_info::SymbolInfo;
+    use crate::standardized_types::accounts::Currency;
+    use crate::strategies::checkpoint::LedgerCheckpoint;
+
+    fn broker_position(symbol_code: &str, side: PositionSide, quantity: Volume) -> BrokerPosition {
+        BrokerPosition {
+            symbol_name: "MNQ".to_string(),
+            symbol_code: symbol_code.to_string(),
+            side,
+            quantity,
+            average_price: dec!(20000),
+        }
+    }
+
+    fn position(account: &Account, symbol_code: &str, side: PositionSide, quantity: Volume) -> Position {
+        let symbol_info = SymbolInfo::new("MNQ".to_string(), None, Currency::USD, dec!(0.5), dec!(0.25), 2);
+        Position::new(
+            "MNQ".to_string(), symbol_code.to_string(), "entry".to_string(), account.clone(), side, quantity, dec!(20000),
+            format!("{}-{}", side, symbol_code), symbol_info, dec!(1), "Enter".to_string(), Utc::now(), PositionCalculationMode::FIFO,
+        )
+    }
+
+    fn limit_order(account: &Account, order_id: &str) -> Order {
+        Order::limit_order("MNQ".to_string(), Some("MNQZ4".to_string()), account, dec!(1), OrderSide::Buy, "Entry".to_string(), order_id.to_string(), Utc::now(), dec!(19900), TimeInForce::Day, None)
+    }
+
+    #[test]
+    fn test_reconcile_reports_mismatches() {
+        let account = Account::new(Brokerage::Test, "Test_Account_1".to_string());
+        let snapshot = AccountSnapshot {
+            account: account.clone(),
+            positions: vec![
+                broker_position("MNQZ4", PositionSide::Long, dec!(2)),
+                broker_position("MESZ4", PositionSide::Short, dec!(1)),
+                broker_position("M6AZ4", PositionSide::Long, dec!(3)),
+            ],
+            orders: vec![limit_order(&account, "expected"), limit_order(&account, "orphan")],
+        };
+        let expected_positions = vec![
+            position(&account, "MNQZ4", PositionSide::Long, dec!(2)),
+            position(&account, "MESZ4", PositionSide::Long, dec!(1)),
+            position(&account, "MYMZ4", PositionSide::Short, dec!(1)),
+        ];
+        let expected_orders = vec![limit_order(&account, "expected"), limit_order(&account, "filled")];
+
+        let report = reconcile(&snapshot, &expected_positions, &expected_orders);
+        assert!(!report.is_reconciled());
+        assert_eq!(report.mismatches.len(), 5);
+        assert_eq!(report.mismatches[0], ReconciliationMismatch::PositionMismatch {
+            expected_side: PositionSide::Long,
+            expected_quantity: dec!(1),
+            position: broker_position("MESZ4", PositionSide::Short, dec!(1)),
+        });
+        assert_eq!(report.mismatches[1], ReconciliationMismatch::UnexpectedPosition(broker_position("M6AZ4", PositionSide::Long, dec!(3))));
+        assert!(matches!(&report.mismatches[2], ReconciliationMismatch::MissingPosition { symbol_code, .. } if symbol_code == "MYMZ4"));
+        assert!(matches!(&report.mismatches[3], ReconciliationMismatch::UnexpectedOrder(order) if order.id == "orphan"));
+        assert!(matches!(&report.mismatches[4], ReconciliationMismatch::MissingOrder(order) if order.id == "filled"));
+        assert_eq!(report.to_string().lines().count(), 6);
+
+        // a flat account with no orders and nothing expected is reconciled
+        let flat = AccountSnapshot { account: account.clone(), positions: vec![], orders: vec![] };
+        assert!(reconcile(&flat, &[], &[]).is_reconciled());
+    }
+
+    #[test]
+    fn test_apply_to_checkpoint() {
+        let account = Account::new(Brokerage::Test, "Test_Account_1".to_string());
+        let other = Account::new(Brokerage::Test, "Test_Account_2".to_string());
+        let ledger = |account: &Account, positions: Vec<Position>| LedgerCheckpoint {
+            account: account.clone(),
+            cash_value: dec!(100000),
+            cash_available: dec!(100000),
+            cash_used: dec!(0),
+            total_booked_pnl: dec!(0),
+            commissions_paid: dec!(0),
+            total_financing: dec!(0),
+            positions,
+            positions_closed: vec![],
+        };
+        let mut checkpoint = StrategyCheckpoint {
+            time: Utc::now().to_string(),
+            ledgers: vec![
+                ledger(&account, vec![
+                    position(&account, "MNQZ4", PositionSide::Long, dec!(2)),
+                    position(&account, "MESZ4", PositionSide::Long, dec!(1)),
+                    position(&account, "MYMZ4", PositionSide::Short, dec!(1)),
+                ]),
+                ledger(&other, vec![position(&other, "MYMZ4", PositionSide::Short, dec!(1))]),
+            ],
+            open_orders: vec![limit_order(&account, "expected"), limit_order(&account, "filled"), limit_order(&other, "filled")],
+            indicator_history: vec![],
+            timed_events: vec![],
+            drawing_tools: vec![],
+            user_state: vec![],
+        };
+        let snapshot = AccountSnapshot {
+            account: account.clone(),
+            positions: vec![broker_position("MNQZ4", PositionSide::Long, dec!(2)), broker_position("MESZ4", PositionSide::Long, dec!(4))],
+            orders: vec![limit_order(&account, "expected")],
+        };
+
+        let (expected_positions, expected_orders) = expected_state(&checkpoint, &account);
+        assert_eq!(expected_positions.len(), 3);
+        assert_eq!(expected_orders.len(), 2);
+        let report = reconcile(&snapshot, &expected_positions, &expected_orders);
+        report.apply_to_checkpoint(&mut checkpoint);
+
+        // the mismatched and missing positions are removed, the other account is untouched
+        let codes: Vec<&str> = checkpoint.ledgers[0].positions.iter().map(|position| position.symbol_code.as_str()).collect();
+        assert_eq!(codes, vec!["MNQZ4"]);
+        assert_eq!(checkpoint.ledgers[1].positions.len(), 1);
+        let orders: Vec<(&str, &str)> = checkpoint.open_orders.iter().map(|order| (order.account.account_id.as_str(), order.id.as_str())).collect();
+        assert_eq!(orders, vec![("Test_Account_1", "expected"), ("Test_Account_2", "filled")]);
+
+        let (expected_positions, expected_orders) = expected_state(&checkpoint, &account);
+        let snapshot = AccountSnapshot { positions: vec![broker_position("MNQZ4", PositionSide::Long, dec!(2))], ..snapshot };
+        assert!(reconcile(&snapshot, &expected_positions, &expected_orders).is_reconciled());
+    }
+}
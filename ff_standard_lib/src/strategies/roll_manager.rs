@@ -0,0 +1,359 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use dashmap::DashMap;
+use tokio::sync::mpsc::Sender;
+use uuid::Uuid;
+use crate::messages::data_server_messaging::DataServerRequest;
+use crate::product_maps::rithmic::rollover::get_contract_rollover;
+use crate::standardized_types::accounts::Account;
+use crate::standardized_types::enums::{OrderSide, PositionSide, StrategyMode};
+use crate::standardized_types::orders::{Order, OrderId, OrderRequest};
+use crate::standardized_types::position::{Position, PositionId};
+use crate::standardized_types::subscriptions::{SymbolCode, SymbolName};
+use crate::strategies::client_features::connection_types::ConnectionType;
+use crate::strategies::client_features::request_handler::{DataServerSender, StrategyRequest};
+use crate::strategies::handlers::market_handler::backtest_matching_engine::BackTestEngineMessage;
+use crate::strategies::historical_time::StrategyClock;
+use crate::strategies::ledgers::ledger_service::LedgerService;
+
+/// The prefix of the tag of both legs of a roll, the full tag is `"Roll: ESH24 -> ESM24"`.
+pub const ROLL_TAG: &str = "Roll: ";
+
+/// How often live strategies check their positions for rolls.
+const LIVE_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The tag of the orders which roll a position from `from_symbol_code` to `to_symbol_code`.
+pub fn roll_tag(from_symbol_code: &str, to_symbol_code: &str) -> String {
+    format!("{}{} -> {}", ROLL_TAG, from_symbol_code, to_symbol_code)
+}
+
+/// True if the order or position tag is a leg of a roll.
+pub fn is_roll_tag(tag: &str) -> bool {
+    tag.starts_with(ROLL_TAG)
+}
+
+/// Which futures positions the strategy rolls to the next contract before they expire, passed to `FundForgeStrategy::initialize()`.
+/// A position is rolled `days_before_rollover` days before the rollover day in `product_maps/rithmic/rollover.rs`,
+/// the position is closed and the same size is opened in the next front month with market orders tagged with `roll_tag()`.
+/// Working orders of the account in the expiring contract, such as stops and targets, are cancelled before the roll orders are sent.
+/// Only positions with a `SymbolCode` which is a contract of the `SymbolName` are rolled.
+#[derive(Clone, Debug, Default)]
+pub struct RollSettings {
+    days_before_rollover: Option<i64>,
+    symbol_names: Vec<SymbolName>,
+}
+
+impl RollSettings {
+    /// Positions are never rolled.
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// Rolls the positions of every symbol `days_before_rollover` days before the rollover day, 0 rolls at the start of the rollover day.
+    pub fn new(days_before_rollover: i64) -> Self {
+        Self {
+            days_before_rollover: Some(days_before_rollover),
+            symbol_names: vec![],
+        }
+    }
+
+    /// Only rolls the positions of these symbols.
+    pub fn with_symbols(mut self, symbol_names: Vec<SymbolName>) -> Self {
+        self.symbol_names = symbol_names;
+        self
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.days_before_rollover.is_some()
+    }
+
+    fn watches(&self, symbol_name: &SymbolName) -> bool {
+        self.symbol_names.is_empty() || self.symbol_names.contains(symbol_name)
+    }
+}
+
+/// A roll which is due for an open position.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct PendingRoll {
+    pub account: Account,
+    pub position_id: PositionId,
+    pub symbol_name: SymbolName,
+    pub from_symbol_code: SymbolCode,
+    pub to_symbol_code: SymbolCode,
+    pub side: PositionSide,
+    pub quantity: rust_decimal::Decimal,
+}
+
+impl PendingRoll {
+    /// The market orders which close the position and open the same size in the next contract.
+    pub(crate) fn orders(&self, time: DateTime<Utc>) -> (Order, Order) {
+        let tag = roll_tag(&self.from_symbol_code, &self.to_symbol_code);
+        let (exit_side, entry_side) = match self.side {
+            PositionSide::Short => (OrderSide::Buy, OrderSide::Sell),
+            PositionSide::Long | PositionSide::Flat => (OrderSide::Sell, OrderSide::Buy),
+        };
+        let exit = Order::market_order(self.symbol_name.clone(), Some(self.from_symbol_code.clone()), &self.account, self.quantity, exit_side, tag.clone(), Uuid::new_v4().to_string(), time, None);
+        let entry = Order::market_order(self.symbol_name.clone(), Some(self.to_symbol_code.clone()), &self.account, self.quantity, entry_side, tag, Uuid::new_v4().to_string(), time, None);
+        (exit, entry)
+    }
+}
+
+/// Watches the open futures positions of the strategy and rolls them before the contract expires, see `RollSettings`.
+/// Backtests are checked by the engine each time the strategy time moves, live modes are checked every minute.
+pub(crate) struct RollManager {
+    settings: RollSettings,
+    mode: StrategyMode,
+    ledger_service: Arc<LedgerService>,
+    open_order_cache: Arc<DashMap<OrderId, Order>>,
+    historical_message_sender: Option<Sender<BackTestEngineMessage>>,
+    clock: StrategyClock,
+    data_server_sender: DataServerSender,
+    /// The rollover time and next contract of each contract, `None` if the code is not a contract of the symbol.
+    rollovers: DashMap<SymbolCode, Option<(DateTime<Utc>, SymbolCode)>>,
+    /// The positions with roll orders sent, so a position is only rolled once while the orders fill.
+    rolling: DashMap<PositionId, SymbolCode>,
+}
+
+impl RollManager {
+    pub(crate) fn new(
+        settings: RollSettings,
+        mode: StrategyMode,
+        ledger_service: Arc<LedgerService>,
+        open_order_cache: Arc<DashMap<OrderId, Order>>,
+        historical_message_sender: Option<Sender<BackTestEngineMessage>>,
+        clock: StrategyClock,
+        data_server_sender: DataServerSender,
+    ) -> Self {
+        Self {
+            settings,
+            mode,
+            ledger_service,
+            open_order_cache,
+            historical_message_sender,
+            clock,
+            data_server_sender,
+            rollovers: DashMap::new(),
+            rolling: DashMap::new(),
+        }
+    }
+
+    /// Sends the roll orders for every position which is due to roll at `time`.
+    pub(crate) async fn update_time(&self, time: DateTime<Utc>) {
+        if !self.settings.is_enabled() {
+            return;
+        }
+        let mut positions: HashMap<PositionId, Position> = HashMap::new();
+        for ledger in self.ledger_service.ledgers.iter() {
+            for position in ledger.value().positions.iter() {
+                positions.insert(position.position_id.clone(), position.value().clone());
+            }
+        }
+        self.rolling.retain(|position_id, _| positions.contains_key(position_id));
+
+        for position in positions.values() {
+            if self.rolling.contains_key(&position.position_id) {
+                continue;
+            }
+            let roll = match self.due_roll(position, time) {
+                Some(roll) => roll,
+                None => continue,
+            };
+            let (exit, entry) = roll.orders(time);
+            self.rolling.insert(roll.position_id.clone(), roll.to_symbol_code.clone());
+            for order_id in self.orders_to_cancel(&roll) {
+                self.send_request(OrderRequest::Cancel { account: roll.account.clone(), order_id }).await;
+            }
+            self.send_order(exit).await;
+            self.send_order(entry).await;
+        }
+    }
+
+    /// The roll for the position if the contract is due to roll at `time`.
+    pub(crate) fn due_roll(&self, position: &Position, time: DateTime<Utc>) -> Option<PendingRoll> {
+        let days_before_rollover = self.settings.days_before_rollover?;
+        if position.is_closed || position.side == PositionSide::Flat || position.symbol_code == position.symbol_name || !self.settings.watches(&position.symbol_name) {
+            return None;
+        }
+        let rollover = self.rollovers.entry(position.symbol_code.clone())
+            .or_insert_with(|| get_contract_rollover(&position.symbol_name, &position.symbol_code, time).unwrap_or(None))
+            .clone();
+        let (rollover_time, to_symbol_code) = rollover?;
+        if time < rollover_time - ChronoDuration::days(days_before_rollover) {
+            return None;
+        }
+        Some(PendingRoll {
+            account: position.account.clone(),
+            position_id: position.position_id.clone(),
+            symbol_name: position.symbol_name.clone(),
+            from_symbol_code: position.symbol_code.clone(),
+            to_symbol_code,
+            side: position.side,
+            quantity: position.quantity_open,
+        })
+    }
+
+    /// The working orders of the account in the expiring contract, these would open a new position in the old contract once the position is rolled.
+    pub(crate) fn orders_to_cancel(&self, roll: &PendingRoll) -> Vec<OrderId> {
+        self.open_order_cache.iter()
+            .filter(|order| order.account == roll.account && order.symbol_code == roll.from_symbol_code && order.can_cancel())
+            .map(|order| order.id.clone())
+            .collect()
+    }
+
+    /// Sends the order to the broker in live mode, or to the backtest engine in backtests and live paper trading.
+    /// Roll orders keep the size of an existing position so they are not checked by the `RiskManager`.
+    async fn send_order(&self, order: Order) {
+        let order_request = OrderRequest::Create { account: order.account.clone(), order_type: order.order_type.clone(), order: order.clone() };
+        if self.mode == StrategyMode::Live {
+            self.open_order_cache.insert(order.id.clone(), order);
+        }
+        self.send_request(order_request).await;
+    }
+
+    async fn send_request(&self, order_request: OrderRequest) {
+        if self.mode == StrategyMode::Live {
+            let connection_type = ConnectionType::Broker(order_request.brokerage());
+            let request = StrategyRequest::OneWay(connection_type, DataServerRequest::OrderRequest { request: order_request });
+            self.data_server_sender.send(request).await;
+        } else if let Some(historical_message_sender) = &self.historical_message_sender {
+            if let Err(e) = historical_message_sender.send(BackTestEngineMessage::OrderRequest(order_request)).await {
+                eprintln!("Roll Manager: Failed to send order request: {}", e);
+            }
+        }
+    }
+
+    /// Checks the positions every minute in live modes, once the warm up is complete.
+    pub(crate) fn run(roll_manager: Arc<Self>) {
+        if !roll_manager.settings.is_enabled() || roll_manager.mode == StrategyMode::Backtest {
+            return;
+        }
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(LIVE_CHECK_INTERVAL);
+            loop {
+                interval.tick().await;
+                if !roll_manager.clock.is_warmup_complete() {
+                    continue;
+                }
+                roll_manager.update_time(Utc::now()).await;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use rust_decimal_macros::dec;
+    use super::*;
+    use crate::standardized_types::broker_enum::Brokerage;
+    use crate::standardized_types::orders::TimeInForce;
+    use crate::standardized_types::position::PositionCalculationMode;
+    use crate::standardized_types::symbol_info::SymbolInfo;
+    use crate::strategies::handlers::market_handler::price_service::MarketPriceService;
+    use crate::strategies::ledgers::commissions::CommissionSchedules;
+    use crate::strategies::ledgers::financing::FinancingSchedules;
+    use crate::strategies::ledgers::margin::MarginSchedules;
+
+    fn time(time: &str) -> DateTime<Utc> {
+        DateTime::from_str(time).unwrap()
+    }
+
+    fn roll_manager(settings: RollSettings) -> RollManager {
+        roll_manager_with_engine(settings, None)
+    }
+
+    fn roll_manager_with_engine(settings: RollSettings, historical_message_sender: Option<Sender<BackTestEngineMessage>>) -> RollManager {
+        let (sender, _receiver) = tokio::sync::mpsc::channel(10);
+        let (data_server_sender, _request_receiver) = DataServerSender::new();
+        let ledger_service = Arc::new(LedgerService::new(
+            sender,
+            Arc::new(MarketPriceService::new()),
+            Arc::new(CommissionSchedules::default()),
+            Arc::new(MarginSchedules::default()),
+            Arc::new(FinancingSchedules::default()),
+            data_server_sender.clone(),
+        ));
+        RollManager::new(settings, StrategyMode::Backtest, ledger_service, Arc::new(DashMap::new()), historical_message_sender, StrategyClock::new(time("2024-01-01T00:00:00Z")), data_server_sender)
+    }
+
+    fn position(symbol_name: &str, symbol_code: &str, side: PositionSide) -> Position {
+        let account = Account::new(Brokerage::Test, "Test".to_string());
+        let symbol_info = SymbolInfo::new(symbol_name.to_string(), None, crate::standardized_types::accounts::Currency::USD, dec!(12.5), dec!(0.25), 2);
+        Position::new(symbol_name.to_string(), symbol_code.to_string(), "1".to_string(), account, side, dec!(3), dec!(5000), "position".to_string(), symbol_info, dec!(1), "entry".to_string(), time("2024-02-01T15:00:00Z"), PositionCalculationMode::FIFO)
+    }
+
+    #[test]
+    fn test_roll_tag() {
+        assert_eq!(roll_tag("ESH24", "ESM24"), "Roll: ESH24 -> ESM24");
+        assert!(is_roll_tag(&roll_tag("ESH24", "ESM24")));
+        assert!(!is_roll_tag("Enter Long"));
+    }
+
+    #[test]
+    fn test_due_roll() {
+        let manager = roll_manager(RollSettings::new(5));
+        let long = position("ES", "ESH24", PositionSide::Long);
+        // the rollover is at 2024-03-09 06:00 utc, 5 days before is 2024-03-04 06:00
+        assert_eq!(manager.due_roll(&long, time("2024-03-04T05:59:00Z")), None);
+        let roll = manager.due_roll(&long, time("2024-03-04T06:00:00Z")).unwrap();
+        assert_eq!((roll.from_symbol_code.as_str(), roll.to_symbol_code.as_str(), roll.quantity), ("ESH24", "ESM24", dec!(3)));
+
+        let (exit, entry) = roll.orders(time("2024-03-04T06:00:00Z"));
+        assert_eq!((exit.side, exit.symbol_code.as_str(), exit.quantity_open), (OrderSide::Sell, "ESH24", dec!(3)));
+        assert_eq!((entry.side, entry.symbol_code.as_str(), entry.quantity_open), (OrderSide::Buy, "ESM24", dec!(3)));
+        assert_eq!(exit.tag, "Roll: ESH24 -> ESM24");
+        assert_eq!(entry.tag, exit.tag);
+
+        // rithmic codes keep the single digit year
+        let short = position("ES", "ESH4", PositionSide::Short);
+        let roll = manager.due_roll(&short, time("2024-03-10T00:00:00Z")).unwrap();
+        assert_eq!(roll.to_symbol_code, "ESM4");
+        assert_eq!(roll.orders(time("2024-03-10T00:00:00Z")).0.side, OrderSide::Buy);
+
+        // positions in the symbol name or an unknown symbol are never rolled
+        assert_eq!(manager.due_roll(&position("ES", "ES", PositionSide::Long), time("2024-03-10T00:00:00Z")), None);
+        assert_eq!(manager.due_roll(&position("EUR-USD", "EUR-USD-1", PositionSide::Long), time("2024-03-10T00:00:00Z")), None);
+
+        let filtered = roll_manager(RollSettings::new(5).with_symbols(vec!["NQ".to_string()]));
+        assert_eq!(filtered.due_roll(&long, time("2024-03-10T00:00:00Z")), None);
+        assert_eq!(roll_manager(RollSettings::none()).due_roll(&long, time("2024-03-10T00:00:00Z")), None);
+    }
+
+    #[tokio::test]
+    async fn test_roll_cancels_orders_in_expiring_contract() {
+        let (engine_sender, mut engine_receiver) = tokio::sync::mpsc::channel(10);
+        let manager = roll_manager_with_engine(RollSettings::new(5), Some(engine_sender));
+        let account = Account::new(Brokerage::Test, "Test".to_string());
+        manager.ledger_service.init_ledger(&account, StrategyMode::Backtest, false, dec!(100000), crate::standardized_types::accounts::Currency::USD).await;
+        manager.ledger_service.ledgers.get(&account).unwrap().positions.insert("ESH24".to_string(), position("ES", "ESH24", PositionSide::Long));
+
+        let created = time("2024-03-01T00:00:00Z");
+        let limit = |symbol_code: &str, account: &Account, order_id: &str| {
+            Order::limit_order("ES".to_string(), Some(symbol_code.to_string()), account, dec!(3), OrderSide::Sell, "target".to_string(), order_id.to_string(), created, dec!(5100), TimeInForce::GTC, None)
+        };
+        let other_account = Account::new(Brokerage::Test, "Other".to_string());
+        for order in [limit("ESH24", &account, "target"), limit("ESM24", &account, "next contract"), limit("ESH24", &other_account, "other account")] {
+            manager.open_order_cache.insert(order.id.clone(), order);
+        }
+
+        manager.update_time(time("2024-03-04T06:00:00Z")).await;
+        let mut requests = vec![];
+        while let Ok(BackTestEngineMessage::OrderRequest(request)) = engine_receiver.try_recv() {
+            requests.push(request);
+        }
+        assert_eq!(requests.len(), 3);
+        // the target is cancelled before the position is rolled, the orders of the next contract and other accounts are left working
+        match &requests[0] {
+            OrderRequest::Cancel { account: cancelled_account, order_id } => assert_eq!((cancelled_account, order_id.as_str()), (&account, "target")),
+            request => panic!("expected a cancel, got {:?}", request),
+        }
+        match (&requests[1], &requests[2]) {
+            (OrderRequest::Create { order: exit, .. }, OrderRequest::Create { order: entry, .. }) => {
+                assert_eq!((exit.symbol_code.as_str(), entry.symbol_code.as_str()), ("ESH24", "ESM24"));
+            }
+            requests => panic!("expected the roll orders, got {:?}", requests),
+        }
+    }
+}
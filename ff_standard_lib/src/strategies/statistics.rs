@@ -0,0 +1,595 @@
+use std::collections::{BTreeMap, HashMap};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+use rust_decimal_macros::dec;
+use serde_derive::{Deserialize, Serialize};
+use crate::standardized_types::accounts::{Account, Currency};
+use crate::standardized_types::position::Position;
+use crate::standardized_types::subscriptions::SymbolName;
+use crate::strategies::roll_manager::is_roll_tag;
+use crate::strategies::ledgers::ledger::Ledger;
+
+/// Returns are annualized using 252 trading days.
+const TRADING_DAYS_PER_YEAR: f64 = 252.0;
+
+/// The equity curve is sampled at most once per minute, drawdowns and exposure are tracked on every update.
+const EQUITY_CURVE_RESOLUTION_SECONDS: i64 = 60;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EquityPoint {
+    pub time: String,
+    /// Cash plus the open pnl of all positions in the account currency.
+    pub equity: Decimal,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DailyReturn {
+    pub date: String,
+    /// The closing equity of the day.
+    pub equity: Decimal,
+    /// The return since the previous close as a fraction, `0.01` is 1%.
+    pub return_fraction: Decimal,
+}
+
+/// Records the account equity each time slice, used to build the `PerformanceReport`.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct EquityRecorder {
+    points: Vec<(DateTime<Utc>, Decimal)>,
+    daily_closes: BTreeMap<NaiveDate, Decimal>,
+    starting_equity: Option<Decimal>,
+    first_time: Option<DateTime<Utc>>,
+    last_time: Option<DateTime<Utc>>,
+    had_positions: bool,
+    exposure: Duration,
+    peak: Decimal,
+    peak_time: Option<DateTime<Utc>>,
+    max_drawdown: Decimal,
+    max_drawdown_percent: Decimal,
+    max_drawdown_duration: Duration,
+}
+
+impl EquityRecorder {
+    pub(crate) fn record(&mut self, time: DateTime<Utc>, equity: Decimal, has_positions: bool) {
+        if let Some(last_time) = self.last_time {
+            if time < last_time {
+                return;
+            }
+            if self.had_positions {
+                self.exposure += time - last_time;
+            }
+        }
+        if self.starting_equity.is_none() {
+            self.starting_equity = Some(equity);
+            self.first_time = Some(time);
+            self.peak = equity;
+            self.peak_time = Some(time);
+        }
+        self.last_time = Some(time);
+        self.had_positions = has_positions;
+
+        if equity >= self.peak {
+            self.peak = equity;
+            self.peak_time = Some(time);
+        } else {
+            let drawdown = self.peak - equity;
+            if drawdown > self.max_drawdown {
+                self.max_drawdown = drawdown;
+            }
+            if self.peak > dec!(0) {
+                let drawdown_percent = drawdown / self.peak * dec!(100);
+                if drawdown_percent > self.max_drawdown_percent {
+                    self.max_drawdown_percent = drawdown_percent;
+                }
+            }
+        }
+        if let Some(peak_time) = self.peak_time {
+            let duration = time - peak_time;
+            if duration > self.max_drawdown_duration {
+                self.max_drawdown_duration = duration;
+            }
+        }
+
+        self.daily_closes.insert(time.date_naive(), equity);
+        match self.points.last_mut() {
+            Some((last_time, last_equity)) if time.timestamp() / EQUITY_CURVE_RESOLUTION_SECONDS == last_time.timestamp() / EQUITY_CURVE_RESOLUTION_SECONDS => {
+                *last_time = time;
+                *last_equity = equity;
+            }
+            _ => self.points.push((time, equity))
+        }
+    }
+
+    pub(crate) fn peak(&self) -> Option<Decimal> {
+        self.starting_equity.map(|_| self.peak)
+    }
+
+    /// The last equity recorded before the date, or the starting equity if there is none.
+    pub(crate) fn close_before(&self, date: NaiveDate) -> Option<Decimal> {
+        self.daily_closes.range(..date).next_back()
+            .map(|(_, equity)| *equity)
+            .or(self.starting_equity)
+    }
+}
+
+/// The performance of a ledger, built from the equity curve and the closed positions.
+/// Percentages are in percent, `12.5` is 12.5%, and the money fields are in the account currency.
+/// Closed positions are counted as trades, the same as the ledger statistics, a position rolled to the next contract and the position it rolled into are one trade.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PerformanceReport {
+    pub account: Account,
+    pub currency: Currency,
+    pub start_time: Option<String>,
+    pub end_time: Option<String>,
+    pub starting_equity: Decimal,
+    pub ending_equity: Decimal,
+    pub net_profit: Decimal,
+    pub total_return_percent: Decimal,
+    pub cagr_percent: Decimal,
+    pub sharpe_ratio: Decimal,
+    pub sortino_ratio: Decimal,
+    pub calmar_ratio: Decimal,
+    pub max_drawdown: Decimal,
+    pub max_drawdown_percent: Decimal,
+    /// The longest time the equity spent below a previous peak.
+    pub max_drawdown_duration_seconds: i64,
+    /// The percentage of the time the account held a position.
+    pub exposure_percent: Decimal,
+    pub total_trades: usize,
+    pub wins: usize,
+    pub losses: usize,
+    pub win_rate_percent: Decimal,
+    pub average_win: Decimal,
+    pub average_loss: Decimal,
+    /// The total pnl of the winning trades.
+    pub gross_profit: Decimal,
+    /// The total pnl of the losing trades, a negative number.
+    pub gross_loss: Decimal,
+    pub profit_factor: Decimal,
+    /// The average pnl per trade.
+    pub expectancy: Decimal,
+    /// The average maximum adverse excursion of a trade, the largest open loss while the position was open.
+    pub average_mae: Decimal,
+    /// The average maximum favourable excursion of a trade, the largest open profit while the position was open.
+    pub average_mfe: Decimal,
+    pub longest_win_streak: usize,
+    pub longest_loss_streak: usize,
+    pub commissions_paid: Decimal,
+    pub financing: Decimal,
+    pub equity_curve: Vec<EquityPoint>,
+    pub daily_returns: Vec<DailyReturn>,
+}
+
+impl PerformanceReport {
+    pub fn new(ledger: &Ledger) -> Self {
+        let mut positions: Vec<Position> = ledger.positions_closed.iter()
+            .flat_map(|positions| positions.value().clone())
+            .collect();
+        positions.sort_by(|a, b| a.close_time.cmp(&b.close_time));
+        let trades = TradeSummary::new(&join_rolled_positions(positions));
+        Self::from_parts(ledger.account.clone(), ledger.currency, &ledger.equity_recorder, ledger.cash_value, trades, ledger.commissions_paid, ledger.total_financing)
+    }
+
+    /// Joins the reports of consecutive backtests into one report, as if each backtest continued from the ending equity of the previous one.
+    /// The drawdowns and ratios are calculated from the stitched equity curve, the streaks are the longest within a single report.
+    /// Returns `None` if there are no reports.
+    pub fn stitch(reports: &[PerformanceReport]) -> Option<Self> {
+        let first = reports.first()?;
+        let mut recorder = EquityRecorder::default();
+        let mut equity = first.starting_equity;
+        let mut exposure_seconds = dec!(0);
+        for report in reports {
+            let offset = equity - report.starting_equity;
+            for point in &report.equity_curve {
+                if let Ok(time) = point.time.parse::<DateTime<Utc>>() {
+                    recorder.record(time, point.equity + offset, false);
+                }
+            }
+            equity = report.ending_equity + offset;
+            if let (Some(Ok(start)), Some(Ok(end))) = (report.start_time.as_ref().map(|time| time.parse::<DateTime<Utc>>()), report.end_time.as_ref().map(|time| time.parse::<DateTime<Utc>>())) {
+                exposure_seconds += Decimal::from((end - start).num_seconds()) * report.exposure_percent / dec!(100);
+            }
+        }
+        recorder.exposure = Duration::seconds(exposure_seconds.round().to_i64().unwrap_or(0));
+
+        let trades = TradeSummary::from_reports(reports);
+        let commissions_paid = reports.iter().map(|report| report.commissions_paid).sum();
+        let financing = reports.iter().map(|report| report.financing).sum();
+        Some(Self::from_parts(first.account.clone(), first.currency, &recorder, first.starting_equity, trades, commissions_paid, financing))
+    }
+
+    fn from_parts(account: Account, currency: Currency, recorder: &EquityRecorder, cash_value: Decimal, trades: TradeSummary, commissions_paid: Decimal, financing: Decimal) -> Self {
+        let starting_equity = recorder.starting_equity.unwrap_or(cash_value);
+        let ending_equity = recorder.points.last().map(|(_, equity)| *equity).unwrap_or(starting_equity);
+        let net_profit = ending_equity - starting_equity;
+        let total_return_percent = match starting_equity > dec!(0) {
+            true => net_profit / starting_equity * dec!(100),
+            false => dec!(0)
+        };
+
+        let daily_returns = daily_returns(starting_equity, &recorder.daily_closes);
+        let returns: Vec<f64> = daily_returns.iter().filter_map(|daily| daily.return_fraction.to_f64()).collect();
+        let (sharpe_ratio, sortino_ratio) = sharpe_and_sortino(&returns);
+
+        let elapsed = match (recorder.first_time, recorder.last_time) {
+            (Some(first), Some(last)) => last - first,
+            _ => Duration::zero()
+        };
+        let years = elapsed.num_seconds() as f64 / (365.25 * 86400.0);
+        let cagr_percent = match (starting_equity.to_f64(), ending_equity.to_f64()) {
+            (Some(start), Some(end)) if years > 0.0 && start > 0.0 && end > 0.0 => to_decimal(((end / start).powf(1.0 / years) - 1.0) * 100.0),
+            _ => dec!(0)
+        };
+        let calmar_ratio = match recorder.max_drawdown_percent > dec!(0) {
+            true => cagr_percent / recorder.max_drawdown_percent,
+            false => dec!(0)
+        };
+        let exposure_percent = match elapsed > Duration::zero() {
+            true => Decimal::from(recorder.exposure.num_seconds()) / Decimal::from(elapsed.num_seconds()) * dec!(100),
+            false => dec!(0)
+        };
+
+        PerformanceReport {
+            account,
+            currency,
+            start_time: recorder.first_time.map(|time| time.to_string()),
+            end_time: recorder.last_time.map(|time| time.to_string()),
+            starting_equity: starting_equity.round_dp(2),
+            ending_equity: ending_equity.round_dp(2),
+            net_profit: net_profit.round_dp(2),
+            total_return_percent: total_return_percent.round_dp(2),
+            cagr_percent: cagr_percent.round_dp(2),
+            sharpe_ratio: sharpe_ratio.round_dp(2),
+            sortino_ratio: sortino_ratio.round_dp(2),
+            calmar_ratio: calmar_ratio.round_dp(2),
+            max_drawdown: recorder.max_drawdown.round_dp(2),
+            max_drawdown_percent: recorder.max_drawdown_percent.round_dp(2),
+            max_drawdown_duration_seconds: recorder.max_drawdown_duration.num_seconds(),
+            exposure_percent: exposure_percent.round_dp(2),
+            total_trades: trades.total,
+            wins: trades.wins,
+            losses: trades.losses,
+            win_rate_percent: trades.win_rate_percent().round_dp(2),
+            average_win: trades.average_win().round_dp(2),
+            average_loss: trades.average_loss().round_dp(2),
+            gross_profit: trades.win_pnl.round_dp(2),
+            gross_loss: trades.loss_pnl.round_dp(2),
+            profit_factor: trades.profit_factor().round_dp(2),
+            expectancy: trades.expectancy().round_dp(2),
+            average_mae: trades.average(trades.total_mae).round_dp(2),
+            average_mfe: trades.average(trades.total_mfe).round_dp(2),
+            longest_win_streak: trades.longest_win_streak,
+            longest_loss_streak: trades.longest_loss_streak,
+            commissions_paid: commissions_paid.round_dp(2),
+            financing: financing.round_dp(2),
+            equity_curve: recorder.points.iter().map(|(time, equity)| EquityPoint { time: time.to_string(), equity: equity.round_dp(2) }).collect(),
+            daily_returns,
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+/// Joins each position closed by a roll with the position the roll opened, so a position held across rolls is counted as one trade.
+/// The positions should be sorted by close time, the pnl, financing and trades are added together and the excursions are the largest of the positions.
+/// The costs of the roll are deliberately kept in the trade: the slippage of both legs is in the booked pnl, and their fees stay in the trades and in `commissions_paid`,
+/// the same as the fees of any other order, because holding the position across contracts is what cost them.
+/// A rolled position whose next position is still open is returned on its own.
+pub(crate) fn join_rolled_positions(positions: Vec<Position>) -> Vec<Position> {
+    let mut rolled: HashMap<SymbolName, Position> = HashMap::new();
+    let mut joined = Vec::with_capacity(positions.len());
+    for mut position in positions {
+        if is_roll_tag(&position.tag) {
+            if let Some(previous) = rolled.remove(&position.symbol_name) {
+                position.open_time = previous.open_time;
+                position.tag = previous.tag;
+                position.booked_pnl += previous.booked_pnl;
+                position.financing += previous.financing;
+                let mut completed_trades = previous.completed_trades;
+                completed_trades.append(&mut position.completed_trades);
+                position.completed_trades = completed_trades;
+                let previous_excursion = previous.excursion;
+                if previous_excursion.mae_value > position.excursion.mae_value {
+                    position.excursion.mae_price = previous_excursion.mae_price;
+                    position.excursion.mae_ticks = previous_excursion.mae_ticks;
+                    position.excursion.mae_value = previous_excursion.mae_value;
+                    position.excursion.mae_time = previous_excursion.mae_time;
+                }
+                if previous_excursion.mfe_value > position.excursion.mfe_value {
+                    position.excursion.mfe_price = previous_excursion.mfe_price;
+                    position.excursion.mfe_ticks = previous_excursion.mfe_ticks;
+                    position.excursion.mfe_value = previous_excursion.mfe_value;
+                    position.excursion.mfe_time = previous_excursion.mfe_time;
+                }
+            }
+        }
+        match position.rolled {
+            true => {
+                rolled.insert(position.symbol_name.clone(), position);
+            }
+            false => joined.push(position)
+        }
+    }
+    joined.extend(rolled.into_values());
+    joined.sort_by(|a, b| a.close_time.cmp(&b.close_time));
+    joined
+}
+
+#[derive(Default)]
+struct TradeSummary {
+    total: usize,
+    wins: usize,
+    losses: usize,
+    win_pnl: Decimal,
+    loss_pnl: Decimal,
+    total_mae: Decimal,
+    total_mfe: Decimal,
+    longest_win_streak: usize,
+    longest_loss_streak: usize,
+}
+
+impl TradeSummary {
+    fn new(positions: &[Position]) -> Self {
+        let mut summary = TradeSummary::default();
+        let (mut win_streak, mut loss_streak) = (0, 0);
+        for position in positions {
+            summary.total += 1;
+            if position.booked_pnl > dec!(0) {
+                summary.wins += 1;
+                summary.win_pnl += position.booked_pnl;
+                win_streak += 1;
+                loss_streak = 0;
+            } else if position.booked_pnl < dec!(0) {
+                summary.losses += 1;
+                summary.loss_pnl += position.booked_pnl;
+                loss_streak += 1;
+                win_streak = 0;
+            } else {
+                win_streak = 0;
+                loss_streak = 0;
+            }
+            summary.longest_win_streak = summary.longest_win_streak.max(win_streak);
+            summary.longest_loss_streak = summary.longest_loss_streak.max(loss_streak);
+            summary.total_mae += position.excursion.mae_value;
+            summary.total_mfe += position.excursion.mfe_value;
+        }
+        summary
+    }
+
+    /// Combines the trades of several reports, the excursion totals are rebuilt from the averages.
+    fn from_reports(reports: &[PerformanceReport]) -> Self {
+        let mut summary = TradeSummary::default();
+        for report in reports {
+            let total = Decimal::from(report.total_trades);
+            summary.total += report.total_trades;
+            summary.wins += report.wins;
+            summary.losses += report.losses;
+            summary.win_pnl += report.gross_profit;
+            summary.loss_pnl += report.gross_loss;
+            summary.total_mae += report.average_mae * total;
+            summary.total_mfe += report.average_mfe * total;
+            summary.longest_win_streak = summary.longest_win_streak.max(report.longest_win_streak);
+            summary.longest_loss_streak = summary.longest_loss_streak.max(report.longest_loss_streak);
+        }
+        summary
+    }
+
+    fn average(&self, total: Decimal) -> Decimal {
+        match self.total {
+            0 => dec!(0),
+            count => total / Decimal::from(count)
+        }
+    }
+
+    fn win_rate_percent(&self) -> Decimal {
+        match self.total {
+            0 => dec!(0),
+            count => Decimal::from(self.wins) / Decimal::from(count) * dec!(100)
+        }
+    }
+
+    fn average_win(&self) -> Decimal {
+        match self.wins {
+            0 => dec!(0),
+            wins => self.win_pnl / Decimal::from(wins)
+        }
+    }
+
+    fn average_loss(&self) -> Decimal {
+        match self.losses {
+            0 => dec!(0),
+            losses => self.loss_pnl / Decimal::from(losses)
+        }
+    }
+
+    fn profit_factor(&self) -> Decimal {
+        if self.loss_pnl != dec!(0) {
+            self.win_pnl / -self.loss_pnl
+        } else if self.win_pnl > dec!(0) {
+            dec!(1000)
+        } else {
+            dec!(0)
+        }
+    }
+
+    fn expectancy(&self) -> Decimal {
+        self.average(self.win_pnl + self.loss_pnl)
+    }
+}
+
+fn daily_returns(starting_equity: Decimal, daily_closes: &BTreeMap<NaiveDate, Decimal>) -> Vec<DailyReturn> {
+    let mut previous = starting_equity;
+    let mut returns = Vec::with_capacity(daily_closes.len());
+    for (date, equity) in daily_closes {
+        let return_fraction = match previous != dec!(0) {
+            true => (*equity - previous) / previous,
+            false => dec!(0)
+        };
+        returns.push(DailyReturn {
+            date: date.to_string(),
+            equity: equity.round_dp(2),
+            return_fraction: return_fraction.round_dp(6),
+        });
+        previous = *equity;
+    }
+    returns
+}
+
+/// Returns the annualized Sharpe and Sortino ratios of the daily returns with a risk free rate of 0.
+fn sharpe_and_sortino(returns: &[f64]) -> (Decimal, Decimal) {
+    if returns.len() < 2 {
+        return (dec!(0), dec!(0));
+    }
+    let count = returns.len() as f64;
+    let mean = returns.iter().sum::<f64>() / count;
+    let variance = returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (count - 1.0);
+    let downside_variance = returns.iter().map(|r| r.min(0.0).powi(2)).sum::<f64>() / count;
+    let annualize = TRADING_DAYS_PER_YEAR.sqrt();
+    let ratio = |deviation: f64| match deviation > 0.0 {
+        true => to_decimal(mean / deviation * annualize),
+        false => dec!(0)
+    };
+    (ratio(variance.sqrt()), ratio(downside_variance.sqrt()))
+}
+
+fn to_decimal(value: f64) -> Decimal {
+    Decimal::from_f64(value).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use crate::standardized_types::broker_enum::Brokerage;
+    use crate::standardized_types::enums::PositionSide;
+    use crate::standardized_types::position::{Excursion, PositionCalculationMode, Trade, TradeResult};
+    use crate::standardized_types::symbol_info::SymbolInfo;
+    use crate::strategies::ledgers::commissions::CommissionBreakdown;
+    use crate::strategies::roll_manager::roll_tag;
+
+    #[test]
+    fn test_equity_recorder_drawdown_and_exposure() {
+        let mut recorder = EquityRecorder::default();
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        recorder.record(start, dec!(100000), false);
+        recorder.record(start + Duration::hours(1), dec!(110000), true);
+        recorder.record(start + Duration::hours(2), dec!(99000), true);
+        recorder.record(start + Duration::hours(4), dec!(104500), false);
+        recorder.record(start + Duration::hours(8), dec!(104500), false);
+
+        assert_eq!(recorder.max_drawdown, dec!(11000));
+        assert_eq!(recorder.max_drawdown_percent, dec!(10));
+        assert_eq!(recorder.max_drawdown_duration, Duration::hours(7));
+        assert_eq!(recorder.exposure, Duration::hours(3));
+        assert_eq!(recorder.points.len(), 5);
+        assert_eq!(recorder.daily_closes.len(), 1);
+
+        // updates in the same minute replace the last point
+        recorder.record(start + Duration::hours(8) + Duration::seconds(30), dec!(104000), false);
+        assert_eq!(recorder.points.len(), 5);
+    }
+
+    #[test]
+    fn test_sharpe_and_daily_returns() {
+        let mut closes = BTreeMap::new();
+        closes.insert(NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(), dec!(101));
+        closes.insert(NaiveDate::from_ymd_opt(2024, 1, 2).unwrap(), dec!(99.99));
+        let returns = daily_returns(dec!(100), &closes);
+        assert_eq!(returns[0].return_fraction, dec!(0.01));
+        assert_eq!(returns[1].return_fraction, dec!(-0.01));
+
+        let (sharpe, sortino) = sharpe_and_sortino(&[0.01, -0.01, 0.02]);
+        assert!(sharpe > dec!(0));
+        assert!(sortino > sharpe);
+        assert_eq!(sharpe_and_sortino(&[0.01]), (dec!(0), dec!(0)));
+    }
+
+    #[test]
+    fn test_stitch_reports() {
+        let account = Account::new(Brokerage::Test, "Test".to_string());
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let mut first = EquityRecorder::default();
+        first.record(start, dec!(100000), false);
+        first.record(start + Duration::hours(1), dec!(101000), false);
+        let first_trades = TradeSummary { total: 1, wins: 1, win_pnl: dec!(1000), longest_win_streak: 1, ..Default::default() };
+        let first = PerformanceReport::from_parts(account.clone(), Currency::USD, &first, dec!(100000), first_trades, dec!(10), dec!(0));
+
+        let mut second = EquityRecorder::default();
+        second.record(start + Duration::days(1), dec!(100000), false);
+        second.record(start + Duration::days(1) + Duration::hours(1), dec!(99500), true);
+        second.record(start + Duration::days(1) + Duration::hours(2), dec!(100500), false);
+        let second_trades = TradeSummary { total: 2, wins: 1, losses: 1, win_pnl: dec!(1000), loss_pnl: dec!(-500), longest_win_streak: 1, longest_loss_streak: 1, ..Default::default() };
+        let second = PerformanceReport::from_parts(account, Currency::USD, &second, dec!(100000), second_trades, dec!(20), dec!(0));
+
+        let stitched = PerformanceReport::stitch(&[first, second]).unwrap();
+        assert_eq!(stitched.starting_equity, dec!(100000));
+        assert_eq!(stitched.ending_equity, dec!(101500));
+        assert_eq!(stitched.net_profit, dec!(1500));
+        // the second report starts at the ending equity of the first
+        assert_eq!(stitched.max_drawdown, dec!(500));
+        assert_eq!(stitched.equity_curve.len(), 5);
+        assert_eq!(stitched.total_trades, 3);
+        assert_eq!(stitched.profit_factor, dec!(4));
+        assert_eq!(stitched.commissions_paid, dec!(30));
+        assert!(stitched.exposure_percent > dec!(0));
+        assert!(PerformanceReport::stitch(&[]).is_none());
+    }
+
+    fn closed_position(symbol_code: &str, tag: &str, booked_pnl: Decimal, close_hour: u32, rolled: bool) -> Position {
+        let account = Account::new(Brokerage::Test, "Test".to_string());
+        let symbol_info = SymbolInfo::new("ES".to_string(), None, Currency::USD, dec!(12.5), dec!(0.25), 2);
+        let open_time = Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+        let mut position = Position::new("ES".to_string(), symbol_code.to_string(), "1".to_string(), account, PositionSide::Long, dec!(1), dec!(5000), symbol_code.to_string(), symbol_info, dec!(1), tag.to_string(), open_time, PositionCalculationMode::FIFO);
+        position.booked_pnl = booked_pnl;
+        position.is_closed = true;
+        position.close_time = Some(Utc.with_ymd_and_hms(2024, 3, 10, close_hour, 0, 0).unwrap().to_string());
+        position.rolled = rolled;
+        position
+    }
+
+    #[test]
+    fn test_join_rolled_positions() {
+        let roll = roll_tag("ESH24", "ESM24");
+        let positions = vec![
+            closed_position("ESH24", "Enter Long", dec!(100), 1, true),
+            closed_position("ESM24", &roll, dec!(-30), 2, false),
+            closed_position("ESM24", "Enter Long", dec!(50), 3, false),
+        ];
+        let joined = join_rolled_positions(positions);
+        assert_eq!(joined.len(), 2);
+        assert_eq!(joined[0].booked_pnl, dec!(70));
+        assert_eq!(joined[0].tag, "Enter Long");
+        assert_eq!(joined[0].symbol_code, "ESM24");
+        assert_eq!(joined[1].booked_pnl, dec!(50));
+
+        // the position the roll opened is still open
+        let joined = join_rolled_positions(vec![closed_position("ESH24", "Enter Long", dec!(100), 1, true)]);
+        assert_eq!(joined.len(), 1);
+        assert_eq!(joined[0].booked_pnl, dec!(100));
+    }
+
+    #[test]
+    fn test_join_rolled_positions_keeps_roll_costs() {
+        let fees = |total: Decimal| CommissionBreakdown { commission: total, ..Default::default() };
+        // long ESH24 from 5000, rolled out at 5010 and into ESM24 at 5014 with the slippage in the fill prices, closed at 5020, each leg pays 2 in fees
+        let mut rolled = closed_position("ESH24", "Enter Long", dec!(500), 1, true);
+        rolled.completed_trades.push(Trade { entry_price: dec!(5000), entry_order_id: "1".to_string(), entry_quantity: dec!(1), exit_price: dec!(5010), exit_order_id: "2".to_string(), exit_quantity: dec!(1), entry_time: String::new(), exit_time: String::new(), profit: dec!(500), result: TradeResult::Win, commission: fees(dec!(4)), excursion: Excursion::default() });
+        let roll = roll_tag("ESH24", "ESM24");
+        let mut opened = closed_position("ESM24", &roll, dec!(300), 2, false);
+        opened.completed_trades.push(Trade { entry_price: dec!(5014), entry_order_id: "3".to_string(), entry_quantity: dec!(1), exit_price: dec!(5020), exit_order_id: "4".to_string(), exit_quantity: dec!(1), entry_time: String::new(), exit_time: String::new(), profit: dec!(300), result: TradeResult::Win, commission: fees(dec!(4)), excursion: Excursion::default() });
+
+        let joined = join_rolled_positions(vec![rolled, opened]);
+        assert_eq!(joined.len(), 1);
+        // the pnl is what the position made across both contracts, including the slippage of the roll legs
+        assert_eq!(joined[0].booked_pnl, dec!(800));
+        // the fees of the roll legs stay with the trade
+        let fees_paid: Decimal = joined[0].completed_trades.iter().map(|trade| trade.commission.total()).sum();
+        assert_eq!(fees_paid, dec!(8));
+
+        let trades = TradeSummary::new(&joined);
+        assert_eq!(trades.total, 1);
+        assert_eq!(trades.win_pnl, dec!(800));
+    }
+}
@@ -13,6 +13,10 @@ use rkyv::vec::ArchivedVec;
 use crate::strategies::indicators::indicator_events::IndicatorEvents;
 use crate::standardized_types::position::PositionUpdateEvent;
 use crate::standardized_types::orders::OrderUpdateEvent;
+use crate::standardized_types::accounts::Account;
+use crate::standardized_types::continuous_contracts::RollEvent;
+use crate::strategies::checkpoint::CheckpointEvent;
+use rust_decimal::Decimal;
 
 #[derive(Clone, Serialize_rkyv, Deserialize_rkyv, Archive, PartialEq, Debug, Copy, Ord, PartialOrd, Eq)]
 #[archive(compare(PartialEq), check_bytes)]
@@ -27,7 +31,10 @@ pub enum StrategyEventType {
     WarmUpComplete,
     IndicatorEvent,
     PositionEvents,
-    TimedEvents
+    TimedEvents,
+    MarginCall,
+    RollEvents,
+    CheckpointEvents
 }
 
 /// All strategies can be sent or received by the strategy or the UI.
@@ -93,7 +100,18 @@ pub enum StrategyEvent {
 
     PositionEvents(PositionUpdateEvent),
 
-    TimedEvent(String)
+    TimedEvent(String),
+
+    /// Sent by the backtest and paper ledgers when the account equity falls below the maintenance margin of the open positions.
+    /// The ledger flattens all positions for the account with the tag "Margin Call" after sending this event.
+    MarginCall{account: Account, equity: Decimal, maintenance_margin: Decimal, time: String},
+
+    /// Sent in backtests when a continuous futures series moves to the next contract, after the time slice with the last data of `from_symbol_code`.
+    /// Positions in `from_symbol_code` are not moved, the strategy can close them and open the same position in the new contract.
+    RollEvent(RollEvent),
+
+    /// Sent to live strategies when they resume from a checkpoint instead of warming up, or when a checkpoint can not be saved on the interval.
+    CheckpointEvent(CheckpointEvent)
 }
 
 impl StrategyEvent {
@@ -135,7 +153,10 @@ impl StrategyEvent {
             StrategyEvent::IndicatorEvent(_) => StrategyEventType::IndicatorEvent,
             StrategyEvent::PositionEvents(_) => StrategyEventType::PositionEvents,
             StrategyEvent::DataSubscriptionEvent(_) => StrategyEventType::DataSubscriptionEvents,
-            StrategyEvent::TimedEvent(_) => StrategyEventType::TimedEvents
+            StrategyEvent::TimedEvent(_) => StrategyEventType::TimedEvents,
+            StrategyEvent::MarginCall { .. } => StrategyEventType::MarginCall,
+            StrategyEvent::RollEvent(_) => StrategyEventType::RollEvents,
+            StrategyEvent::CheckpointEvent(_) => StrategyEventType::CheckpointEvents
         }
     }
 
@@ -0,0 +1,234 @@
+use std::fs::create_dir_all;
+use std::path::Path;
+use chrono::{Duration, NaiveDateTime, Utc};
+use csv::Writer;
+use rust_decimal::Decimal;
+use crate::messages::data_server_messaging::FundForgeError;
+use crate::strategies::optimisation::{BacktestWindow, OptimisationMetric, OptimisationResult, Optimiser, ParameterSet};
+use crate::strategies::statistics::PerformanceReport;
+
+/// One step of a walk forward analysis, the parameters are optimised in sample and then tested out of sample.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WalkForwardWindow {
+    pub index: usize,
+    pub in_sample: BacktestWindow,
+    pub out_of_sample: BacktestWindow,
+}
+
+/// The outcome of one window, `parameters` is `None` if every in sample backtest failed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WalkForwardWindowResult {
+    pub window: WalkForwardWindow,
+    pub parameters: Option<ParameterSet>,
+    /// The best in sample result, used to choose the parameters.
+    pub in_sample: Option<OptimisationResult>,
+    pub out_of_sample: Option<OptimisationResult>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct WalkForwardResult {
+    pub windows: Vec<WalkForwardWindowResult>,
+    /// The out of sample reports stitched into one equity curve, `None` if no out of sample backtest succeeded.
+    pub combined: Option<PerformanceReport>,
+}
+
+/// Splits `start..end` into in sample and out of sample windows, optimises the parameters on each in sample window with the `Optimiser`
+/// and backtests the best parameter set on the following out of sample window.
+/// The out of sample reports are stitched into one `PerformanceReport`, which is the performance we could have expected from re-optimising the strategy each period.
+///
+/// The windows roll forward by the out of sample duration, so the out of sample windows follow each other without gaps.
+/// Anchored windows keep the in sample start at `start` and grow with each step.
+/// The strategy reads its dates with `BacktestWindow::from_env()` and its parameters with `ParameterSet::from_env()`.
+#[derive(Clone, Debug)]
+pub struct WalkForward {
+    optimiser: Optimiser,
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+    in_sample: Duration,
+    out_of_sample: Duration,
+    anchored: bool,
+}
+
+impl WalkForward {
+    /// The results of each window are written to `window_{index}` in the output folder of the optimiser.
+    pub fn new(optimiser: Optimiser, start: NaiveDateTime, end: NaiveDateTime, in_sample: Duration, out_of_sample: Duration) -> Self {
+        Self {
+            optimiser,
+            start,
+            end,
+            in_sample,
+            out_of_sample,
+            anchored: false,
+        }
+    }
+
+    pub fn anchored(mut self) -> Self {
+        self.anchored = true;
+        self
+    }
+
+    /// Returns the windows which will be tested, the last out of sample window is cut short at `end`.
+    pub fn windows(&self) -> Vec<WalkForwardWindow> {
+        let mut windows = vec![];
+        if self.in_sample <= Duration::zero() || self.out_of_sample <= Duration::zero() {
+            return windows;
+        }
+        let mut in_sample_start = self.start;
+        let mut in_sample_end = self.start + self.in_sample;
+        while in_sample_end < self.end {
+            let out_of_sample_end = (in_sample_end + self.out_of_sample).min(self.end);
+            windows.push(WalkForwardWindow {
+                index: windows.len(),
+                in_sample: BacktestWindow::new(in_sample_start, in_sample_end),
+                out_of_sample: BacktestWindow::new(in_sample_end, out_of_sample_end),
+            });
+            if !self.anchored {
+                in_sample_start += self.out_of_sample;
+            }
+            in_sample_end += self.out_of_sample;
+        }
+        windows
+    }
+
+    /// Runs the windows in order and writes the window table and the combined report to the output folder of the optimiser.
+    pub async fn run(&self) -> Result<WalkForwardResult, FundForgeError> {
+        let windows = self.windows();
+        if windows.is_empty() {
+            return Err(FundForgeError::ClientSideErrorDebug(format!("Walk forward: No windows fit between {} and {}", self.start, self.end)));
+        }
+        let output_folder = self.optimiser.output_folder().to_path_buf();
+        create_dir_all(&output_folder)
+            .map_err(|e| FundForgeError::ClientSideErrorDebug(format!("Failed to create directory {}: {}", output_folder.display(), e)))?;
+        println!("Walk forward: Running {} windows", windows.len());
+
+        let mut results = Vec::with_capacity(windows.len());
+        for window in windows {
+            let window_folder = output_folder.join(format!("window_{}", window.index));
+            let in_sample = self.optimiser.clone()
+                .with_window(window.in_sample)
+                .with_output_folder(&window_folder.join("in_sample").to_string_lossy())
+                .run()
+                .await?
+                .into_iter()
+                .find(|result| result.report.is_some());
+
+            let parameters = in_sample.as_ref().map(|result| result.parameters.clone());
+            let out_of_sample = match &parameters {
+                Some(parameters) => self.optimiser.clone()
+                    .with_window(window.out_of_sample)
+                    .with_output_folder(&window_folder.join("out_of_sample").to_string_lossy())
+                    .run_parameter_sets(vec![parameters.clone()])
+                    .await?
+                    .pop(),
+                None => {
+                    eprintln!("Walk forward: Window {} has no in sample results, skipping the out of sample backtest", window.index);
+                    None
+                }
+            };
+            if let (Some(parameters), Some(value)) = (&parameters, out_of_sample.as_ref().and_then(|result| result.metric(self.optimiser.metric()))) {
+                println!("Walk forward: Window {} {:?} out of sample {:?}: {}", window.index, parameters.values, self.optimiser.metric(), value);
+            }
+            results.push(WalkForwardWindowResult { window, parameters, in_sample, out_of_sample });
+        }
+
+        let reports: Vec<PerformanceReport> = results.iter()
+            .filter_map(|result| result.out_of_sample.as_ref().and_then(|result| result.report.clone()))
+            .collect();
+        let combined = PerformanceReport::stitch(&reports);
+
+        let date = Utc::now().format("%Y%m%d_%H%M").to_string();
+        let file_path = output_folder.join(format!("WalkForward_{}.csv", date));
+        write_windows(&file_path, &results, self.optimiser.metric())?;
+        if let Some(combined) = &combined {
+            let report_path = output_folder.join(format!("WalkForward_{}.json", date));
+            let json = combined.to_json()
+                .map_err(|e| FundForgeError::ClientSideErrorDebug(format!("Failed to serialize the combined report: {}", e)))?;
+            std::fs::write(&report_path, json)
+                .map_err(|e| FundForgeError::ClientSideErrorDebug(format!("Failed to write {}: {}", report_path.display(), e)))?;
+        }
+        println!("Walk forward: Results written to {}", file_path.display());
+        Ok(WalkForwardResult { windows: results, combined })
+    }
+}
+
+fn write_windows(file_path: &Path, results: &[WalkForwardWindowResult], metric: OptimisationMetric) -> Result<(), FundForgeError> {
+    let to_error = |e: csv::Error| FundForgeError::ClientSideErrorDebug(format!("Failed to write {}: {}", file_path.display(), e));
+    let mut writer = Writer::from_path(file_path).map_err(to_error)?;
+
+    let parameter_names: Vec<String> = results.iter()
+        .find_map(|result| result.parameters.as_ref())
+        .map(|parameters| parameters.values.keys().cloned().collect())
+        .unwrap_or_default();
+    let mut header = vec![
+        "window".to_string(), "in_sample_start".to_string(), "in_sample_end".to_string(), "out_of_sample_start".to_string(), "out_of_sample_end".to_string(),
+    ];
+    header.extend(parameter_names.iter().cloned());
+    header.extend([
+        format!("in_sample_{:?}", metric), format!("out_of_sample_{:?}", metric), "out_of_sample_net_profit".to_string(),
+        "out_of_sample_trades".to_string(), "error".to_string(),
+    ]);
+    writer.write_record(&header).map_err(to_error)?;
+
+    let value = |result: &Option<OptimisationResult>, field: fn(&PerformanceReport) -> String| {
+        result.as_ref().and_then(|result| result.report.as_ref()).map(field).unwrap_or_default()
+    };
+    for result in results {
+        let window = &result.window;
+        let mut record = vec![
+            window.index.to_string(), window.in_sample.start.to_string(), window.in_sample.end.to_string(),
+            window.out_of_sample.start.to_string(), window.out_of_sample.end.to_string(),
+        ];
+        record.extend(parameter_names.iter().map(|name| {
+            result.parameters.as_ref().and_then(|parameters| parameters.get(name)).map(|value| value.to_string()).unwrap_or_default()
+        }));
+        let metric_value = |result: &Option<OptimisationResult>| {
+            result.as_ref().and_then(|result| result.metric(metric)).map(|value: Decimal| value.to_string()).unwrap_or_default()
+        };
+        record.extend([
+            metric_value(&result.in_sample),
+            metric_value(&result.out_of_sample),
+            value(&result.out_of_sample, |report| report.net_profit.to_string()),
+            value(&result.out_of_sample, |report| report.total_trades.to_string()),
+        ]);
+        let error = match (&result.in_sample, &result.out_of_sample) {
+            (None, _) => "No in sample results".to_string(),
+            (Some(_), Some(out_of_sample)) => out_of_sample.error.clone().unwrap_or_default(),
+            (Some(_), None) => String::new(),
+        };
+        record.push(error);
+        writer.write_record(&record).map_err(to_error)?;
+    }
+    writer.flush().map_err(|e| FundForgeError::ClientSideErrorDebug(format!("Failed to write {}: {}", file_path.display(), e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use std::path::PathBuf;
+    use crate::strategies::optimisation::{ParameterSpace, SearchMethod};
+
+    #[test]
+    fn test_rolling_and_anchored_windows() {
+        let date = |day: u32| NaiveDate::from_ymd_opt(2024, 1, day).unwrap().and_hms_opt(0, 0, 0).unwrap();
+        let optimiser = Optimiser::new(PathBuf::from("strategy"), ParameterSpace::new(), SearchMethod::Grid, OptimisationMetric::NetProfit);
+        let walk_forward = WalkForward::new(optimiser, date(1), date(20), Duration::days(7), Duration::days(5));
+
+        let windows = walk_forward.windows();
+        assert_eq!(windows.len(), 3);
+        assert_eq!(windows[0].in_sample, BacktestWindow::new(date(1), date(8)));
+        assert_eq!(windows[0].out_of_sample, BacktestWindow::new(date(8), date(13)));
+        assert_eq!(windows[1].in_sample, BacktestWindow::new(date(6), date(13)));
+        assert_eq!(windows[1].out_of_sample.start, windows[0].out_of_sample.end);
+        // the last out of sample window is cut short at the end date
+        assert_eq!(windows[2].out_of_sample, BacktestWindow::new(date(18), date(20)));
+
+        let anchored = walk_forward.clone().anchored().windows();
+        assert_eq!(anchored.len(), 3);
+        assert_eq!(anchored[2].in_sample, BacktestWindow::new(date(1), date(18)));
+        assert_eq!(anchored[2].out_of_sample, windows[2].out_of_sample);
+
+        let empty = WalkForward { out_of_sample: Duration::zero(), ..walk_forward };
+        assert!(empty.windows().is_empty());
+    }
+}
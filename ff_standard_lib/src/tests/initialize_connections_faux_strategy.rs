@@ -10,6 +10,11 @@ use crate::standardized_types::enums::{MarketType, StrategyMode, PrimarySubscrip
 use crate::standardized_types::resolution::Resolution;
 use crate::standardized_types::subscriptions::{CandleType, DataSubscription, SymbolName};
 use crate::strategies::fund_forge_strategy::FundForgeStrategy;
+use crate::strategies::handlers::market_handler::intrabar::IntrabarPath;
+use crate::strategies::handlers::market_handler::latency::LatencyModel;
+use crate::strategies::handlers::market_handler::risk_manager::RiskManager;
+use crate::strategies::checkpoint::CheckpointSettings;
+use crate::strategies::roll_manager::RollSettings;
 
 #[allow(dead_code)]
 pub(crate) fn initialize_tests() -> FundForgeStrategy {
@@ -40,7 +45,17 @@ pub(crate) fn initialize_tests() -> FundForgeStrategy {
                 Account::new(Brokerage::Test, "Test_Account_1".to_string()),
                 Account::new(Brokerage::Test, "Test_Account_2".to_string()),
             ],
+            None,
+            None,
+            IntrabarPath::Close,
+            LatencyModel::none(),
+            RiskManager::none(),
+            CheckpointSettings::none(),
+            None,
+            vec![],
+            RollSettings::none(),
         )
             .await
+            .unwrap()
     })
 }
\ No newline at end of file
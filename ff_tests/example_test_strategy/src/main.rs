@@ -10,6 +10,11 @@ use ff_standard_lib::standardized_types::enums::{MarketType, OrderSide, PrimaryS
 use ff_standard_lib::strategies::strategy_events::{StrategyControls, StrategyEvent};
 use ff_standard_lib::standardized_types::subscriptions::{CandleType, DataSubscription, SymbolName};
 use ff_standard_lib::strategies::fund_forge_strategy::FundForgeStrategy;
+use ff_standard_lib::strategies::handlers::market_handler::intrabar::IntrabarPath;
+use ff_standard_lib::strategies::handlers::market_handler::latency::LatencyModel;
+use ff_standard_lib::strategies::handlers::market_handler::risk_manager::RiskManager;
+use ff_standard_lib::strategies::checkpoint::CheckpointSettings;
+use ff_standard_lib::strategies::roll_manager::RollSettings;
 use rust_decimal_macros::dec;
 use tokio::sync::mpsc;
 use ff_standard_lib::standardized_types::broker_enum::Brokerage;
@@ -71,8 +76,26 @@ async fn main() {
         //tick over no data, strategy will run at buffer resolution speed to simulate weekends and holidays, if false we will just skip over them to the next data point.
         false,
         false,
-        vec![Account::new(Brokerage::Oanda, "Test_Account_1".to_string())]
-    ).await;
+        vec![Account::new(Brokerage::Oanda, "Test_Account_1".to_string())],
+        // fill model, None fills at the top of the book
+        None,
+        // commission model, None uses the commission schedules in resources/commissions.toml
+        None,
+        // intrabar path, Close only triggers orders at the bar close
+        IntrabarPath::Close,
+        // latency model, none processes order requests as soon as they are sent
+        LatencyModel::none(),
+        // risk manager, none sends every order
+        RiskManager::none(),
+        // checkpoints, none always starts from scratch
+        CheckpointSettings::none(),
+        // reconciliation with the brokerage on live startup, None skips it
+        None,
+        // continuous futures series for backtests, empty uses the data stored for each symbol name
+        vec![],
+        // roll expiring futures positions to the next contract, none leaves them to the strategy
+        RollSettings::none()
+    ).await.unwrap();
 
     // we can subscribe to indicators here or in our event loop at run time.
     let quotebar_5s_atr_5 = AverageTrueRange::new(
@@ -215,7 +238,7 @@ pub async fn on_data_received(
                                             && bars_since_entry == 3
                                             && current_heikin_3m_atr_5 >= last_heikin_3m_atr_5
                                         {
-                                            entry_order_id = Some(strategy.enter_long(&quotebar.symbol.name, None, &account, None, dec!(1000), String::from("Add Long")).await);
+                                            entry_order_id = Some(strategy.enter_long(&quotebar.symbol.name, None, &account, None, dec!(1000), String::from("Add Long"), None).await);
                                         }
                                     }
                                 }
@@ -233,6 +256,8 @@ pub async fn on_data_received(
                         }
                         BaseDataEnum::Candle(_candle) => {}
                         BaseDataEnum::Fundamental(_fundamental) => {}
+                        BaseDataEnum::OrderBook(_book) => {}
+                        BaseDataEnum::MarketByOrder(_mbo) => {}
                     }
                 }
             }
@@ -276,9 +301,12 @@ pub async fn on_data_received(
             }
 
             StrategyEvent::ShutdownEvent(event) => {
-                strategy.flatten_all_for(account).await;
+                strategy.flatten_all_for(account.clone()).await;
                 let msg = format!("{}",event);
                 println!("{}", msg.as_str().bright_magenta());
+                if let Err(e) = strategy.write_reports() {
+                    eprintln!("Failed to write reports: {}", e);
+                }
                 //we should handle shutdown gracefully by first ending the strategy loop.
                 break 'strategy_loop
             },
@@ -336,6 +364,15 @@ pub async fn on_data_received(
             StrategyEvent::TimedEvent(name) => {
                 println!("{} has triggered", name);
             }
+            StrategyEvent::MarginCall { account, equity, maintenance_margin, time } => {
+                println!("{} Margin Call: {}, Equity: {}, Maintenance Margin: {}", time, account, equity, maintenance_margin);
+            }
+            StrategyEvent::RollEvent(roll) => {
+                println!("{}", roll);
+            }
+            StrategyEvent::CheckpointEvent(event) => {
+                println!("{}", event);
+            }
         }
     }
     strategy.export_positions_to_csv(&String::from("./trades exports"));
@@ -9,6 +9,11 @@ use ff_standard_lib::standardized_types::enums::{MarketType, StrategyMode};
 use ff_standard_lib::strategies::strategy_events::{StrategyEvent};
 use ff_standard_lib::standardized_types::subscriptions::{DataSubscription, SymbolName};
 use ff_standard_lib::strategies::fund_forge_strategy::FundForgeStrategy;
+use ff_standard_lib::strategies::handlers::market_handler::intrabar::IntrabarPath;
+use ff_standard_lib::strategies::handlers::market_handler::latency::LatencyModel;
+use ff_standard_lib::strategies::handlers::market_handler::risk_manager::RiskManager;
+use ff_standard_lib::strategies::checkpoint::CheckpointSettings;
+use ff_standard_lib::strategies::roll_manager::RollSettings;
 use rust_decimal_macros::dec;
 use tokio::sync::mpsc;
 use ff_standard_lib::standardized_types::accounts::{Account, Currency};
@@ -62,8 +67,26 @@ async fn main() {
         //tick over no data, strategy will run at buffer resolution speed to simulate weekends and holidays, if false we will just skip over them to the next data point.
         false,
         false,
-        vec![Account::new(Brokerage::Oanda, "Test_Account_1".to_string()), Account::new(Brokerage::Oanda, "Test_Account_2".to_string())]
-    ).await;
+        vec![Account::new(Brokerage::Oanda, "Test_Account_1".to_string()), Account::new(Brokerage::Oanda, "Test_Account_2".to_string())],
+        // fill model, None fills at the top of the book
+        None,
+        // commission model, None uses the commission schedules in resources/commissions.toml
+        None,
+        // intrabar path, Close only triggers orders at the bar close
+        IntrabarPath::Close,
+        // latency model, none processes order requests as soon as they are sent
+        LatencyModel::none(),
+        // risk manager, none sends every order
+        RiskManager::none(),
+        // checkpoints, none always starts from scratch
+        CheckpointSettings::none(),
+        // reconciliation with the brokerage on live startup, None skips it
+        None,
+        // continuous futures series for backtests, empty uses the data stored for each symbol name
+        vec![],
+        // roll expiring futures positions to the next contract, none leaves them to the strategy
+        RollSettings::none()
+    ).await.unwrap();
 
     on_data_received(strategy, strategy_event_receiver).await;
 }
@@ -114,7 +137,7 @@ pub async fn on_data_received(
                                     if is_flat
                                         && qb.bid_close > qb.bid_open
                                     {
-                                        let _entry_order_id = strategy.enter_long(&qb.symbol.name, None, &account_1, None, dec!(1), String::from("Enter Long")).await;
+                                        let _entry_order_id = strategy.enter_long(&qb.symbol.name, None, &account_1, None, dec!(1), String::from("Enter Long"), None).await;
                                         println!("Strategy: Enter Long, Time {}", strategy.time_local());
                                         last_side = LastSide::Long;
                                     }
@@ -154,6 +177,9 @@ pub async fn on_data_received(
                 println!("{}", msg.as_str().bright_magenta());
                 strategy.export_trades_to_csv(&account_1, &String::from("./trades exports"));
                 strategy.print_ledgers();
+                if let Err(e) = strategy.write_reports() {
+                    eprintln!("Failed to write reports: {}", e);
+                }
                 //we should handle shutdown gracefully by first ending the strategy loop.
                 break 'strategy_loop
             },
@@ -8,6 +8,11 @@ use ff_standard_lib::standardized_types::enums::{Exchange, FuturesExchange, Mark
 use ff_standard_lib::strategies::strategy_events::{StrategyEvent};
 use ff_standard_lib::standardized_types::subscriptions::{DataSubscription, SymbolName};
 use ff_standard_lib::strategies::fund_forge_strategy::FundForgeStrategy;
+use ff_standard_lib::strategies::handlers::market_handler::intrabar::IntrabarPath;
+use ff_standard_lib::strategies::handlers::market_handler::latency::LatencyModel;
+use ff_standard_lib::strategies::handlers::market_handler::risk_manager::RiskManager;
+use ff_standard_lib::strategies::checkpoint::CheckpointSettings;
+use ff_standard_lib::strategies::roll_manager::RollSettings;
 use rust_decimal_macros::dec;
 use tokio::sync::mpsc;
 use tokio::task;
@@ -22,9 +27,51 @@ use ff_standard_lib::standardized_types::position::PositionUpdateEvent;
 use ff_standard_lib::standardized_types::resolution::Resolution;
 use ff_standard_lib::strategies::indicators::built_in::renko::Renko;
 use ff_standard_lib::strategies::indicators::indicator_events::IndicatorEvents;
+use ff_standard_lib::strategies::optimisation::{BacktestWindow, OptimisationMetric, Optimiser, ParameterSet, ParameterSpace, SearchMethod};
+use ff_standard_lib::strategies::walk_forward::WalkForward;
 
 #[tokio::main]
 async fn main() {
+    let start_date = NaiveDate::from_ymd_opt(2024, 11, 5).unwrap().and_hms_opt(0, 0, 0).unwrap();
+    let end_date = NaiveDate::from_ymd_opt(2024, 11, 26).unwrap().and_hms_opt(0, 0, 0).unwrap();
+
+    // `cargo run -- --optimise` backtests each parameter set in a separate process of this binary and ranks the results
+    // `cargo run -- --walk-forward` optimises on each 7 day window and tests the best parameters on the following 3 days
+    let optimise = std::env::args().any(|arg| arg == "--optimise");
+    let walk_forward = std::env::args().any(|arg| arg == "--walk-forward");
+    if ParameterSet::from_env().is_none() && (optimise || walk_forward) {
+        let space = ParameterSpace::new()
+            .with_range("renko_range", dec!(5), dec!(20), dec!(5))
+            .with_values("size", vec![dec!(1), dec!(5)])
+            .with_range("incremental_scalp_pnl", dec!(100), dec!(300), dec!(100));
+        let optimiser = Optimiser::new(std::env::current_exe().unwrap(), space, SearchMethod::Grid, OptimisationMetric::ProfitFactor)
+            .with_concurrency(4)
+            .with_output_folder("./optimisation");
+        if walk_forward {
+            match WalkForward::new(optimiser, start_date, end_date, Duration::days(7), Duration::days(3)).run().await {
+                Ok(result) => {
+                    if let Some(combined) = result.combined {
+                        println!("Walk forward out of sample: net profit {}, profit factor {}, max drawdown {}", combined.net_profit, combined.profit_factor, combined.max_drawdown);
+                    }
+                }
+                Err(e) => eprintln!("Walk forward failed: {}", e),
+            }
+            return;
+        }
+        match optimiser.run().await {
+            Ok(results) => {
+                if let Some(best) = results.first() {
+                    println!("Best parameters: {:?}", best.parameters.values);
+                }
+            }
+            Err(e) => eprintln!("Optimisation failed: {}", e),
+        }
+        return;
+    }
+
+    // the walk forward sets the dates of each backtest
+    let window = BacktestWindow::from_env().unwrap_or(BacktestWindow::new(start_date, end_date));
+
     let (strategy_event_sender, strategy_event_receiver) = mpsc::channel(100);
     let account = Account::new(Brokerage::Rithmic(RithmicSystem::Apex), "APEX-3396-169".to_string());
     let symbol_name = SymbolName::from("MNQ");
@@ -42,8 +89,8 @@ async fn main() {
         StrategyMode::Backtest,
         dec!(100000),
         Currency::USD,
-        NaiveDate::from_ymd_opt(2024, 11, 5).unwrap().and_hms_opt(0, 0, 0).unwrap(),
-        NaiveDate::from_ymd_opt(2024, 11, 26).unwrap().and_hms_opt(0, 0, 0).unwrap(),
+        window.start,
+        window.end,
         Australia::Sydney,
         Duration::hours(1),
         vec![
@@ -57,7 +104,25 @@ async fn main() {
         false,
         false,
         vec![account.clone()],
-    ).await;
+        // fill model, None fills at the top of the book
+        None,
+        // commission model, None uses the commission schedules in resources/commissions.toml
+        None,
+        // intrabar path, Close only triggers orders at the bar close
+        IntrabarPath::Close,
+        // latency model, none processes order requests as soon as they are sent
+        LatencyModel::none(),
+        // risk manager, none sends every order
+        RiskManager::none(),
+        // checkpoints, none always starts from scratch
+        CheckpointSettings::none(),
+        // reconciliation with the brokerage on live startup, None skips it
+        None,
+        // continuous futures series for backtests, empty uses the data stored for each symbol name
+        vec![],
+        // roll expiring futures positions to the next contract, none leaves them to the strategy
+        RollSettings::none()
+    ).await.unwrap();
 
     eprintln!("Strategy Initialized");
 
@@ -93,10 +158,16 @@ pub async fn on_data_received(
     account: Account,
     exchange: FuturesExchange
 ) {
-    println!("Starting Renko Pyramid Strategy with parameters: Renko Range: {}, Max Size: {}, Size: {}, Incremental Scalp PNL: {}, Limit Order Expire in Secs: {}, Trading Long: {}, Trading Short: {}", RENKO_RANGE, MAX_SIZE, SIZE, INCREMENTAL_SCALP_PNL, LIMIT_ORDER_EXPIRE_IN_SECS, TRADING_LONG, TRADING_SHORT);
+    // the parameters are set by the optimiser, the constants are used when the strategy is run on its own
+    let parameters = ParameterSet::from_env().unwrap_or_default();
+    let renko_range = parameters.get_or("renko_range", RENKO_RANGE);
+    let max_size = parameters.get_or("max_size", MAX_SIZE);
+    let size = parameters.get_or("size", SIZE);
+    let incremental_scalp_pnl = parameters.get_or("incremental_scalp_pnl", INCREMENTAL_SCALP_PNL);
+    println!("Starting Renko Pyramid Strategy with parameters: Renko Range: {}, Max Size: {}, Size: {}, Incremental Scalp PNL: {}, Limit Order Expire in Secs: {}, Trading Long: {}, Trading Short: {}", renko_range, max_size, size, incremental_scalp_pnl, LIMIT_ORDER_EXPIRE_IN_SECS, TRADING_LONG, TRADING_SHORT);
 
     let renko = "renko".to_string();
-    let renko_indicator = Renko::new(renko.clone(), subscription.clone(), RENKO_RANGE, Color::new(0, 128, 0), Color::new(128, 0, 0), 20).await;
+    let renko_indicator = Renko::new(renko.clone(), subscription.clone(), renko_range, Color::new(0, 128, 0), Color::new(128, 0, 0), 20).await;
     strategy.subscribe_indicator(renko_indicator, None).await;
     let open = "open".to_string();
     let close = "close".to_string();
@@ -175,12 +246,12 @@ pub async fn on_data_received(
                                         let pnl = strategy.pnl(&account, &symbol_code);
 
                                         // Buy on 2 bullish renko blocks
-                                        if two_blocks_ago_close < two_blocks_ago_open && block_close.value > block_open.value && last_close > last_open && entry_order_id == None && (!is_long || pnl > INCREMENTAL_SCALP_PNL) {
+                                        if two_blocks_ago_close < two_blocks_ago_open && block_close.value > block_open.value && last_close > last_open && entry_order_id == None && (!is_long || pnl > incremental_scalp_pnl) {
                                             let quantity = strategy.position_size(&account, &symbol_code);
-                                            if quantity < MAX_SIZE {
+                                            if quantity < max_size {
                                                 println!("Entering Long: Time: {}", strategy.time_local());
                                                 let tif = TimeInForce::Time((strategy.time_utc() + Duration::seconds(LIMIT_ORDER_EXPIRE_IN_SECS)).timestamp());
-                                                entry_order_id = Some(strategy.limit_order(&symbol_name, Some(symbol_code.clone()), &account, None, SIZE, OrderSide::Buy, block_close.value, tif, String::from("Enter Long")).await);
+                                                entry_order_id = Some(strategy.limit_order(&symbol_name, Some(symbol_code.clone()), &account, None, size, OrderSide::Buy, block_close.value, tif, String::from("Enter Long")).await);
                                             }
                                         }
                                         if is_long {
@@ -192,9 +263,9 @@ pub async fn on_data_received(
 
                                             let profit = strategy.pnl(&account, &symbol_code);
                                             let quantity = strategy.position_size(&account, &symbol_code);
-                                            if profit > INCREMENTAL_SCALP_PNL && quantity == MAX_SIZE && exit_order_id == None && tp_id == None {
+                                            if profit > incremental_scalp_pnl && quantity == max_size && exit_order_id == None && tp_id == None {
                                                 let tif = TimeInForce::Time((strategy.time_utc() + Duration::seconds(LIMIT_ORDER_EXPIRE_IN_SECS)).timestamp());
-                                                tp_id = Some(strategy.limit_order(&symbol_name, Some(symbol_code.clone()), &account, None, SIZE, OrderSide::Sell, last_close + RENKO_RANGE * dec!(4), tif, String::from("Partial TP Long")).await);
+                                                tp_id = Some(strategy.limit_order(&symbol_name, Some(symbol_code.clone()), &account, None, size, OrderSide::Sell, last_close + renko_range * dec!(4), tif, String::from("Partial TP Long")).await);
                                             }
                                         }
                                     }
@@ -204,12 +275,12 @@ pub async fn on_data_received(
                                         let pnl = strategy.pnl(&account, &symbol_code);
 
                                         // Buy on 2 bearish renko blocks
-                                        if two_blocks_ago_close > two_blocks_ago_open && block_close.value < block_open.value && last_close < last_open && entry_order_id == None && (!is_short || pnl > INCREMENTAL_SCALP_PNL) {
+                                        if two_blocks_ago_close > two_blocks_ago_open && block_close.value < block_open.value && last_close < last_open && entry_order_id == None && (!is_short || pnl > incremental_scalp_pnl) {
                                             let quantity = strategy.position_size(&account, &symbol_code);
-                                            if quantity < MAX_SIZE {
+                                            if quantity < max_size {
                                                 println!("Entering Short: Time: {}", strategy.time_local());
                                                 let tif = TimeInForce::Time((strategy.time_utc() + Duration::seconds(LIMIT_ORDER_EXPIRE_IN_SECS)).timestamp());
-                                                entry_order_id = Some(strategy.limit_order(&symbol_name, Some(symbol_code.clone()), &account, None, SIZE, OrderSide::Sell, block_close.value, tif, String::from("Enter Short")).await);
+                                                entry_order_id = Some(strategy.limit_order(&symbol_name, Some(symbol_code.clone()), &account, None, size, OrderSide::Sell, block_close.value, tif, String::from("Enter Short")).await);
                                             }
                                         }
                                         if is_short {
@@ -221,9 +292,9 @@ pub async fn on_data_received(
 
                                             let profit = strategy.pnl(&account, &symbol_code);
                                             let quantity = strategy.position_size(&account, &symbol_code);
-                                            if profit > INCREMENTAL_SCALP_PNL && quantity == MAX_SIZE && exit_order_id == None && tp_id == None {
+                                            if profit > incremental_scalp_pnl && quantity == max_size && exit_order_id == None && tp_id == None {
                                                 let tif = TimeInForce::Time((strategy.time_utc() + Duration::seconds(LIMIT_ORDER_EXPIRE_IN_SECS)).timestamp());
-                                                tp_id = Some(strategy.limit_order(&symbol_name, Some(symbol_code.clone()), &account, None, SIZE, OrderSide::Buy, last_close - RENKO_RANGE * dec!(4), tif, String::from("Partial TP Short")).await);
+                                                tp_id = Some(strategy.limit_order(&symbol_name, Some(symbol_code.clone()), &account, None, size, OrderSide::Buy, last_close - renko_range * dec!(4), tif, String::from("Partial TP Short")).await);
                                             }
                                         }
                                     }
@@ -250,11 +321,15 @@ pub async fn on_data_received(
                 }*/
             }
             StrategyEvent::ShutdownEvent(event) => {
-                strategy.flatten_all_for(account).await;
+                strategy.flatten_all_for(account.clone()).await;
                 let msg = format!("{}",event);
                 println!("{}", msg.as_str().bright_magenta());
                 strategy.export_positions_to_csv(&String::from("./trades exports"));
+                strategy.export_optimisation_report(&account);
                 strategy.print_ledgers();
+                if let Err(e) = strategy.write_reports() {
+                    eprintln!("Failed to write reports: {}", e);
+                }
                 //we should handle shutdown gracefully by first ending the strategy loop.
                 break 'strategy_loop
             },
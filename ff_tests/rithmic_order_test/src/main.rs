@@ -8,6 +8,11 @@ use ff_standard_lib::standardized_types::enums::{MarketType, PrimarySubscription
 use ff_standard_lib::strategies::strategy_events::{StrategyEvent};
 use ff_standard_lib::standardized_types::subscriptions::{DataSubscription, SymbolCode, SymbolName};
 use ff_standard_lib::strategies::fund_forge_strategy::FundForgeStrategy;
+use ff_standard_lib::strategies::handlers::market_handler::intrabar::IntrabarPath;
+use ff_standard_lib::strategies::handlers::market_handler::latency::LatencyModel;
+use ff_standard_lib::strategies::handlers::market_handler::risk_manager::RiskManager;
+use ff_standard_lib::strategies::checkpoint::CheckpointSettings;
+use ff_standard_lib::strategies::roll_manager::RollSettings;
 use rust_decimal_macros::dec;
 use tokio::sync::mpsc;
 use ff_standard_lib::apis::rithmic::rithmic_systems::RithmicSystem;
@@ -63,7 +68,25 @@ async fn main() {
         false,
         true,
         vec![account_1.clone()],
-    ).await;
+        // fill model, None fills at the top of the book
+        None,
+        // commission model, None uses the commission schedules in resources/commissions.toml
+        None,
+        // intrabar path, Close only triggers orders at the bar close
+        IntrabarPath::Close,
+        // latency model, none processes order requests as soon as they are sent
+        LatencyModel::none(),
+        // risk manager, none sends every order
+        RiskManager::none(),
+        // checkpoints, none always starts from scratch
+        CheckpointSettings::none(),
+        // reconciliation with the brokerage on live startup, None skips it
+        None,
+        // continuous futures series for backtests, empty uses the data stored for each symbol name
+        vec![],
+        // roll expiring futures positions to the next contract, none leaves them to the strategy
+        RollSettings::none()
+    ).await.unwrap();
 
     on_data_received(strategy, strategy_event_receiver, symbol_name, symbol_code, account_1).await;
 }
@@ -120,7 +143,7 @@ pub async fn on_data_received(
                                 {
                                     if count == 5 {
                                         println!("Rithmic Order Test: Enter Long, Time {}", strategy.time_local());
-                                        entry_order_id = strategy.enter_long(&candle.symbol.name, Some(symbol_code.clone()) ,&account_1, None, dec!(1), String::from("Enter Long")).await;
+                                        entry_order_id = strategy.enter_long(&candle.symbol.name, Some(symbol_code.clone()) ,&account_1, None, dec!(1), String::from("Enter Long"), None).await;
                                     }
 
                                     let open_pnl = strategy.pnl(&account_1, &symbol_code);
@@ -154,6 +177,9 @@ pub async fn on_data_received(
                 println!("{}", msg.as_str().bright_magenta());
                 strategy.export_positions_to_csv(&String::from("./trades exports"));
                 strategy.print_ledgers();
+                if let Err(e) = strategy.write_reports() {
+                    eprintln!("Failed to write reports: {}", e);
+                }
                 //we should handle shutdown gracefully by first ending the strategy loop.
                 break 'strategy_loop
             },